@@ -6,13 +6,17 @@ sed -i "s|project_path=\"\"|project_path=\"$directory\"|" $directory/bar-rs
 
 cp_cmd="cp $directory/bar-rs /usr/local/bin"
 chmod_cmd="chmod +x /usr/local/bin/bar-rs"
+# Linked, so that it's up to date after every build
+ln_cmd="ln -sf $directory/target/release/bar-rsctl /usr/local/bin/bar-rsctl"
 
 if [ "$UID" -ne 0 -a "$EUID" -ne 0 ]; then
     sudo $cp_cmd
     sudo $chmod_cmd
+    sudo $ln_cmd
 else
     $cp_cmd
     $chmod_cmd
+    $ln_cmd
 fi
 
 sed -i "s|project_path=\"$directory\"|project_path=\"\"|" $directory/bar-rs
@@ -0,0 +1,200 @@
+use std::time::{Duration, Instant};
+
+use iced::{
+    platform_specific::shell::commands::layer_surface::{set_exclusive_zone, set_margin},
+    window::Id,
+    Task,
+};
+
+use crate::{
+    config::{anchor::BarAnchor, Config},
+    Message,
+};
+
+/// The interval of animation frames
+const FRAME: Duration = Duration::from_millis(16);
+/// How often to check whether the bar is idle long enough to be hidden
+const IDLE_CHECK: Duration = Duration::from_millis(100);
+
+#[derive(Debug, Clone, Copy)]
+pub enum AutoHideEvent {
+    /// The cursor entered (`true`) or left the given window
+    Hovered(Id, bool),
+    /// Show the bar until it's idle again, e.g. requested via ipc
+    Reveal,
+    /// Advance the animation and hide the bar if it's idle
+    Frame,
+    /// Hide the bar while a window on its output is fullscreen (`true`), see
+    /// `hide_on_fullscreen` in `[general]`
+    Fullscreen(bool),
+}
+
+/// Slides the bar out of the screen while it isn't used, see `auto_hide` in `[general]`.
+/// One pixel of the bar stays on screen, so that touching the screen edge reveals it again.
+/// While a window is fullscreen the bar is hidden completely and nothing reveals it.
+#[derive(Debug)]
+pub struct AutoHide {
+    /// How much of the bar is shown, from 0 (hidden) to 1
+    shown: f32,
+    /// Whether the bar should be shown
+    reveal: bool,
+    hovered: bool,
+    fullscreen: bool,
+    /// The hide delay is counted from here
+    idle_since: Instant,
+    last_frame: Instant,
+}
+
+impl Default for AutoHide {
+    fn default() -> Self {
+        Self {
+            shown: 1.,
+            reveal: true,
+            hovered: false,
+            fullscreen: false,
+            idle_since: Instant::now(),
+            last_frame: Instant::now(),
+        }
+    }
+}
+
+impl AutoHide {
+    pub fn revealed(&self) -> bool {
+        self.reveal
+    }
+
+    /// Whether the cursor is over the bar
+    pub fn hovered(&self) -> bool {
+        self.hovered
+    }
+
+    /// Whether the bar is out of the screen, so that it doesn't take up space
+    pub fn hidden(&self) -> bool {
+        self.shown == 0.
+    }
+
+    fn target(&self) -> f32 {
+        match self.reveal {
+            true => 1.,
+            false => 0.,
+        }
+    }
+
+    /// How often the bar needs a [AutoHideEvent::Frame], `None` if nothing is about to change.
+    /// `keep_visible` keeps the bar from being hidden when it's idle.
+    pub fn frame_interval(&self, keep_visible: bool) -> Option<Duration> {
+        if self.shown != self.target() {
+            Some(FRAME)
+        } else if self.reveal && !self.hovered && !keep_visible {
+            Some(IDLE_CHECK)
+        } else {
+            None
+        }
+    }
+
+    pub fn update(
+        &mut self,
+        event: AutoHideEvent,
+        layer_id: Id,
+        keep_visible: bool,
+        config: &Config,
+    ) -> Task<Message> {
+        let was_animating = self.shown != self.target();
+        let was_hidden = self.shown == 0.;
+        match event {
+            AutoHideEvent::Hovered(id, hovered) if id == layer_id => {
+                self.hovered = hovered;
+                self.reveal |= hovered && !self.fullscreen;
+                self.idle_since = Instant::now();
+            }
+            AutoHideEvent::Hovered(..) => return Task::none(),
+            AutoHideEvent::Reveal => {
+                self.reveal |= !self.fullscreen;
+                self.idle_since = Instant::now();
+            }
+            AutoHideEvent::Frame => {}
+            AutoHideEvent::Fullscreen(fullscreen) if fullscreen == self.fullscreen => {
+                return Task::none()
+            }
+            AutoHideEvent::Fullscreen(fullscreen) => {
+                self.fullscreen = fullscreen;
+                self.reveal = !fullscreen;
+                self.idle_since = Instant::now();
+            }
+        }
+        if self.reveal
+            && !self.hovered
+            && !keep_visible
+            && self.idle_since.elapsed() >= config.hide_delay
+        {
+            self.reveal = false;
+        }
+
+        let now = Instant::now();
+        // The first frame of an animation doesn't move the bar yet
+        let step = match (was_animating, config.hide_duration.is_zero()) {
+            (_, true) => 1.,
+            (false, false) => 0.,
+            (true, false) => {
+                now.duration_since(self.last_frame).as_secs_f32()
+                    / config.hide_duration.as_secs_f32()
+            }
+        };
+        self.last_frame = now;
+        let shown = match self.reveal {
+            true => (self.shown + step).min(1.),
+            false => (self.shown - step).max(0.),
+        };
+        let moved = shown != self.shown;
+        self.shown = shown;
+
+        let mut tasks = vec![];
+        // Windows take up the space of the hidden bar, but make room as soon as it's revealed
+        if was_hidden && self.reveal {
+            tasks.push(set_exclusive_zone(layer_id, config.exclusive_zone()));
+        }
+        if moved {
+            tasks.push(self.margin(layer_id, config));
+        }
+        if !was_hidden && self.shown == 0. {
+            tasks.push(set_exclusive_zone(layer_id, 0));
+        }
+        Task::batch(tasks)
+    }
+
+    /// Moves the bar out of the screen by how far it's hidden
+    fn margin(&self, layer_id: Id, config: &Config) -> Task<Message> {
+        // Ease in and out
+        let shown = self.shown * self.shown * (3. - 2. * self.shown);
+        let distance = match self.fullscreen {
+            true => config.exclusive_zone(),
+            false => config.exclusive_zone() - 1,
+        };
+        let offset = ((1. - shown) * distance as f32).round() as i32;
+        let margin = config.module_config.global.margin;
+        let (mut top, mut right, mut bottom, mut left) =
+            (margin.top, margin.right, margin.bottom, margin.left);
+        match config.anchor {
+            BarAnchor::Top => top -= offset,
+            BarAnchor::Bottom => bottom -= offset,
+            BarAnchor::Left => left -= offset,
+            BarAnchor::Right => right -= offset,
+        }
+        set_margin(layer_id, top, right, bottom, left)
+    }
+
+    /// Shows the bar right away, e.g. after `auto_hide` was disabled
+    pub fn restore(&mut self, layer_id: Id, config: &Config) -> Task<Message> {
+        let was_hidden = self.shown == 0.;
+        let moved = self.shown != 1.;
+        *self = Self::default();
+        let mut tasks = vec![];
+        if was_hidden {
+            tasks.push(set_exclusive_zone(layer_id, config.exclusive_zone()));
+        }
+        if moved {
+            tasks.push(self.margin(layer_id, config));
+        }
+        Task::batch(tasks)
+    }
+}
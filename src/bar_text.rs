@@ -0,0 +1,280 @@
+use iced::core::{
+    layout, mouse, renderer, svg,
+    text::{LineHeight, Paragraph, Shaping, Wrapping},
+    widget::{tree, Tree},
+    Layout, Widget,
+};
+use iced::{
+    alignment::{Horizontal, Vertical},
+    font::{Family, Weight},
+    widget::{rich_text, span, text},
+    Color, Element, Font, Length, Pixels, Point, Radians, Rectangle, Size,
+};
+
+use crate::{config::anchor::BarAnchor, fill::FillExt};
+
+/// The text of a module. Vertical text (see `orientation`) is turned by 90° to fit into
+/// vertical bars, or stacked character by character if it's short. Otherwise it's a plain
+/// [text] filling the bar like [FillExt::fill].
+pub struct BarText {
+    content: String,
+    vertical: bool,
+    vertical_text: Option<VerticalText>,
+    size: Option<f32>,
+    color: Option<Color>,
+    font: Option<Font>,
+    strikethrough: bool,
+}
+
+/// How vertical text is drawn, see `ModuleConfigOverride::vertical_text`
+#[derive(Debug, Clone, Copy)]
+pub struct VerticalText {
+    pub rotation: Radians,
+    /// Text of at most this many characters is stacked instead
+    pub stack: usize,
+}
+
+pub fn bar_text(
+    content: impl Into<String>,
+    anchor: &BarAnchor,
+    vertical_text: Option<VerticalText>,
+) -> BarText {
+    BarText {
+        content: content.into(),
+        vertical: anchor.vertical(),
+        vertical_text,
+        size: None,
+        color: None,
+        font: None,
+        strikethrough: false,
+    }
+}
+
+impl BarText {
+    pub fn size(mut self, size: f32) -> Self {
+        self.size = Some(size);
+        self
+    }
+
+    pub fn color(mut self, color: Color) -> Self {
+        self.color = Some(color);
+        self
+    }
+
+    pub fn font(mut self, font: Font) -> Self {
+        self.font = Some(font);
+        self
+    }
+
+    pub fn strikethrough(mut self, strikethrough: bool) -> Self {
+        self.strikethrough = strikethrough;
+        self
+    }
+}
+
+impl<'a, Message: Clone + 'static> From<BarText> for Element<'a, Message> {
+    fn from(mut bar_text: BarText) -> Self {
+        let stacked = bar_text
+            .vertical_text
+            .is_some_and(|v| bar_text.content.chars().count() <= v.stack);
+        if stacked {
+            // One character per line, e.g. for the time
+            bar_text.content = bar_text
+                .content
+                .chars()
+                .map(String::from)
+                .collect::<Vec<_>>()
+                .join("\n");
+        }
+        match bar_text.vertical_text.filter(|_| !stacked) {
+            Some(VerticalText { rotation, .. }) => Element::new(Rotated {
+                content: bar_text.content,
+                vertical: bar_text.vertical,
+                rotation,
+                size: bar_text.size,
+                color: bar_text.color,
+                font: bar_text.font,
+                strikethrough: bar_text.strikethrough,
+            }),
+            // Only rich text can be struck through
+            None if bar_text.strikethrough => {
+                let mut content = span(bar_text.content).strikethrough(true);
+                if let Some(size) = bar_text.size {
+                    content = content.size(size);
+                }
+                if let Some(color) = bar_text.color {
+                    content = content.color(color);
+                }
+                if let Some(font) = bar_text.font {
+                    content = content.font(font);
+                }
+                rich_text([content])
+                    .line_height(line_height(stacked))
+                    .fillx(bar_text.vertical)
+                    .into()
+            }
+            None => {
+                let mut content = text(bar_text.content)
+                    .line_height(line_height(stacked))
+                    .fillx(bar_text.vertical);
+                if let Some(size) = bar_text.size {
+                    content = content.size(size);
+                }
+                if let Some(color) = bar_text.color {
+                    content = content.color(color);
+                }
+                if let Some(font) = bar_text.font {
+                    content = content.font(font);
+                }
+                content.into()
+            }
+        }
+    }
+}
+
+/// Stacked characters are set tightly, so that they still read as one word
+fn line_height(stacked: bool) -> LineHeight {
+    match stacked {
+        true => LineHeight::Relative(1.),
+        false => LineHeight::default(),
+    }
+}
+
+/// Text drawn as an svg, since that's what iced can rotate
+struct Rotated {
+    content: String,
+    vertical: bool,
+    rotation: Radians,
+    size: Option<f32>,
+    color: Option<Color>,
+    font: Option<Font>,
+    strikethrough: bool,
+}
+
+/// The size of the text before it's rotated
+#[derive(Debug, Default)]
+struct State(Size);
+
+impl Rotated {
+    fn svg(&self, size: Size, font_size: f32, font: Font) -> svg::Handle {
+        let family = match font.family {
+            Family::Name(name) => name,
+            Family::Serif => "serif",
+            Family::SansSerif => "sans-serif",
+            Family::Cursive => "cursive",
+            Family::Fantasy => "fantasy",
+            Family::Monospace => "monospace",
+        };
+        let weight = match font.weight {
+            Weight::Thin => 100,
+            Weight::ExtraLight => 200,
+            Weight::Light => 300,
+            Weight::Normal => 400,
+            Weight::Medium => 500,
+            Weight::Semibold => 600,
+            Weight::Bold => 700,
+            Weight::ExtraBold => 800,
+            Weight::Black => 900,
+        };
+        let content = self
+            .content
+            .replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;");
+        let decoration = match self.strikethrough {
+            true => "line-through",
+            false => "none",
+        };
+        // Black, since the color is applied when drawing
+        svg::Handle::from_memory(
+            format!(
+                r#"<svg xmlns="http://www.w3.org/2000/svg" width="{w}" height="{h}"><text x="{x}" y="{y}" font-family="{family}" font-size="{font_size}" font-weight="{weight}" text-decoration="{decoration}" text-anchor="middle" dominant-baseline="central" xml:space="preserve">{content}</text></svg>"#,
+                w = size.width,
+                h = size.height,
+                x = size.width / 2.,
+                y = size.height / 2.,
+            )
+            .into_bytes(),
+        )
+    }
+}
+
+impl<Message, Theme, Renderer> Widget<Message, Theme, Renderer> for Rotated
+where
+    Renderer: iced::core::text::Renderer<Font = Font> + svg::Renderer,
+{
+    fn tag(&self) -> tree::Tag {
+        tree::Tag::of::<State>()
+    }
+
+    fn state(&self) -> tree::State {
+        tree::State::new(State::default())
+    }
+
+    fn size(&self) -> Size<Length> {
+        match self.vertical {
+            true => Size::new(Length::Fill, Length::Shrink),
+            false => Size::new(Length::Shrink, Length::Fill),
+        }
+    }
+
+    fn layout(
+        &self,
+        tree: &mut Tree,
+        renderer: &Renderer,
+        limits: &layout::Limits,
+    ) -> layout::Node {
+        let size = Renderer::Paragraph::with_text(iced::core::Text {
+            content: self.content.as_str(),
+            bounds: Size::INFINITY,
+            size: self.size.map(Pixels).unwrap_or(renderer.default_size()),
+            line_height: LineHeight::default(),
+            font: self.font.unwrap_or(renderer.default_font()),
+            horizontal_alignment: Horizontal::Left,
+            vertical_alignment: Vertical::Top,
+            shaping: Shaping::Advanced,
+            wrapping: Wrapping::None,
+        })
+        .min_bounds();
+        tree.state.downcast_mut::<State>().0 = size;
+        let widget_size = <Self as Widget<Message, Theme, Renderer>>::size(self);
+        // Turned by 90°, the width of the text becomes its height
+        layout::Node::new(limits.resolve(
+            widget_size.width,
+            widget_size.height,
+            Size::new(size.height, size.width),
+        ))
+    }
+
+    fn draw(
+        &self,
+        tree: &Tree,
+        renderer: &mut Renderer,
+        _theme: &Theme,
+        style: &renderer::Style,
+        layout: Layout<'_>,
+        _cursor: mouse::Cursor,
+        _viewport: &Rectangle,
+    ) {
+        let size = tree.state.downcast_ref::<State>().0;
+        if size.width == 0. || size.height == 0. {
+            return;
+        }
+        let center = layout.bounds().center();
+        let handle = self.svg(
+            size,
+            self.size.unwrap_or(renderer.default_size().0),
+            self.font.unwrap_or(renderer.default_font()),
+        );
+        // The svg is rotated around the center of its bounds
+        renderer.draw_svg(
+            svg::Svg::new(handle)
+                .color(self.color.unwrap_or(style.text_color))
+                .rotation(self.rotation),
+            Rectangle::new(
+                Point::new(center.x - size.width / 2., center.y - size.height / 2.),
+                size,
+            ),
+        );
+    }
+}
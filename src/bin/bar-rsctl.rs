@@ -0,0 +1,59 @@
+use std::{
+    env,
+    io::{BufRead, BufReader, Write},
+    os::unix::net::UnixStream,
+    process::exit,
+};
+
+use bar_rs::socket_path;
+
+/// Sends a command to a running bar-rs instance, e.g. `bar-rsctl action volume left`
+fn main() {
+    let mut args = env::args().skip(1).collect::<Vec<String>>();
+    // The instance started with `--monitor`
+    let monitor = match args.first().map(|arg| arg.as_str()) {
+        Some("--monitor") if args.len() > 1 => args.drain(..2).nth(1),
+        _ => None,
+    };
+    let command = args.join(" ");
+    if command.is_empty() {
+        eprintln!(
+            "Usage: bar-rsctl [--monitor <output>] <command>\n\nCommands:\n  reload\n  refresh [module]\n  toggle <group|module>\n  reveal\n  profile <name>\n  action <module> [left|middle|right]\n  add-module <module> [--to <side> | --after <module> | --before <module>]\n  remove-module <module>\n  move-module <module> (--to <side> | --after <module> | --before <module>)\n  dump-config"
+        );
+        exit(1);
+    }
+    let path = socket_path(monitor.as_deref());
+    let mut stream = UnixStream::connect(&path).unwrap_or_else(|e| {
+        eprintln!(
+            "Failed to connect to bar-rs at {}: {e}",
+            path.to_string_lossy()
+        );
+        exit(1);
+    });
+    writeln!(stream, "{command}").unwrap_or_else(|e| {
+        eprintln!("Failed to send command: {e}");
+        exit(1);
+    });
+    // The output of the command, if it has one, followed by `ok` or `error: ...`
+    let mut output = false;
+    for line in BufReader::new(stream).lines() {
+        let line = line.unwrap_or_else(|e| {
+            eprintln!("Failed to read response: {e}");
+            exit(1);
+        });
+        if let Some(err) = line.strip_prefix("error: ") {
+            eprintln!("{err}");
+            exit(1);
+        }
+        if line == "ok" {
+            if !output {
+                println!("ok");
+            }
+            return;
+        }
+        println!("{line}");
+        output = true;
+    }
+    eprintln!("bar-rs closed the connection without a response");
+    exit(1);
+}
@@ -0,0 +1,61 @@
+use iced::{
+    alignment::{
+        Horizontal::{Left, Right},
+        Vertical::{Bottom, Top},
+    },
+    widget::{container, Space, Stack},
+    Color, Element,
+    Length::{Fill, Fixed},
+    Padding,
+};
+
+use crate::{config::border::BorderSides, Message};
+
+/// Draws the sides of the border which are set around `content`, since the border of iced is the
+/// same on all sides. Sides without a color use `color`.
+pub fn with_sides<'a>(
+    content: impl Into<Element<'a, Message>>,
+    sides: &BorderSides,
+    color: Color,
+) -> Element<'a, Message> {
+    if sides.is_empty() {
+        return content.into();
+    }
+    let edge = |width: f32, height: f32, side_color: Option<Color>| {
+        container(Space::new(
+            if width > 0. { Fixed(width) } else { Fill },
+            if height > 0. { Fixed(height) } else { Fill },
+        ))
+        .style(move |_| container::Style {
+            background: Some(side_color.unwrap_or(color).into()),
+            ..Default::default()
+        })
+    };
+    let edges = [
+        (sides.top.width > 0.)
+            .then(|| container(edge(0., sides.top.width, sides.top.color)).align_y(Top)),
+        (sides.bottom.width > 0.)
+            .then(|| container(edge(0., sides.bottom.width, sides.bottom.color)).align_y(Bottom)),
+        (sides.left.width > 0.)
+            .then(|| container(edge(sides.left.width, 0., sides.left.color)).align_x(Left)),
+        (sides.right.width > 0.)
+            .then(|| container(edge(sides.right.width, 0., sides.right.color)).align_x(Right)),
+    ];
+    // The first layer sets the size of the stack, the edges are drawn on top of its padding
+    Stack::with_children(
+        [Element::from(container(content).padding(Padding {
+            top: sides.top.width,
+            right: sides.right.width,
+            bottom: sides.bottom.width,
+            left: sides.left.width,
+        }))]
+        .into_iter()
+        .chain(
+            edges
+                .into_iter()
+                .flatten()
+                .map(|edge| Element::from(edge.width(Fill).height(Fill))),
+        ),
+    )
+    .into()
+}
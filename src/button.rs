@@ -1,6 +1,10 @@
 /// Literally 100% copypasta from https://github.com/iced-rs/iced/blob/master/widget/src/button.rs
+use std::cell::Cell;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
 use iced::core::widget::tree;
-use iced::core::{keyboard, overlay, renderer, touch};
+use iced::core::{keyboard, overlay, renderer, touch, window};
 use iced::{
     core::{
         event, layout, mouse,
@@ -9,10 +13,36 @@ use iced::{
     },
     id::Id,
     widget::button::{Catalog, Status, Style, StyleFn},
-    Element, Event, Length, Padding, Rectangle, Size,
+    Element, Event, Length, Padding, Point, Rectangle, Size,
 };
 use iced::{Background, Color, Vector};
 
+/// Fingers moving farther than this aren't tapping, e.g. because they scroll a popup
+const TOUCH_SLOP: f32 = 10.;
+
+/// How long a finger has to be held for a long press, in milliseconds
+static LONG_PRESS: AtomicU64 = AtomicU64::new(500);
+
+thread_local! {
+    /// The touch which the innermost button under the finger handles, so that the buttons
+    /// containing it don't handle it as well
+    static CLAIMED_TOUCH: Cell<Option<(touch::Finger, Point)>> = const { Cell::new(None) };
+}
+
+/// Sets how long a finger has to be held on a button to act like a right click
+pub fn set_long_press(duration: Duration) {
+    LONG_PRESS.store(duration.as_millis() as u64, Ordering::Relaxed);
+}
+
+fn long_press() -> Duration {
+    Duration::from_millis(LONG_PRESS.load(Ordering::Relaxed))
+}
+
+/// The mouse event a touch acts like, so that handlers only have to know about mouse buttons
+fn released(button: mouse::Button) -> Event {
+    Event::Mouse(mouse::Event::ButtonReleased(button))
+}
+
 type EventHandlerFn<'a, Message> = Box<
     dyn Fn(
             iced::Event,
@@ -61,6 +91,7 @@ where
 {
     content: Element<'a, Message, Theme, Renderer>,
     on_event: Option<ButtonEventHandler<'a, Message>>,
+    on_scroll: Option<Box<dyn Fn(mouse::ScrollDelta) -> Option<Message> + 'a>>,
     id: Id,
     width: Length,
     height: Length,
@@ -84,6 +115,7 @@ where
             content,
             id: Id::unique(),
             on_event: None,
+            on_scroll: None,
             width: size.width.fluid(),
             height: size.height.fluid(),
             padding: Padding::ZERO,
@@ -157,6 +189,15 @@ where
         self
     }
 
+    /// Determines the message produced by scrolling over the [`Button`], if any
+    pub fn on_scroll<F>(mut self, f: F) -> Self
+    where
+        F: Fn(mouse::ScrollDelta) -> Option<Message> + 'a,
+    {
+        self.on_scroll = Some(Box::new(f));
+        self
+    }
+
     /// Sets the width of the [`Button`].
     pub fn width(mut self, width: impl Into<Length>) -> Self {
         self.width = width.into();
@@ -199,11 +240,24 @@ where
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
 struct State {
     is_hovered: bool,
     is_pressed: bool,
     is_focused: bool,
+    touch: Option<Touch>,
+}
+
+/// A finger on the button, which acts like a left click when lifted or like a right click when
+/// held for the long press duration
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Touch {
+    finger: touch::Finger,
+    /// Where the finger touched the window
+    origin: Point,
+    since: Instant,
+    /// Whether the long press already fired
+    long_pressed: bool,
 }
 
 impl<'a, Message, Theme, Renderer> Widget<Message, Theme, Renderer>
@@ -287,14 +341,17 @@ where
             shell,
             viewport,
         ) {
+            // The content handled the touch, e.g. a button inside of this one
+            if let Event::Touch(_) = event {
+                tree.state.downcast_mut::<State>().touch = None;
+            }
             return event::Status::Captured;
         }
 
         match event {
             Event::Mouse(mouse::Event::ButtonPressed(mouse::Button::Left))
             | Event::Mouse(mouse::Event::ButtonPressed(mouse::Button::Middle))
-            | Event::Mouse(mouse::Event::ButtonPressed(mouse::Button::Right))
-            | Event::Touch(touch::Event::FingerPressed { .. }) => {
+            | Event::Mouse(mouse::Event::ButtonPressed(mouse::Button::Right)) => {
                 if self.on_event.is_some() {
                     let bounds = layout.bounds();
 
@@ -309,8 +366,7 @@ where
             }
             Event::Mouse(mouse::Event::ButtonReleased(mouse::Button::Left))
             | Event::Mouse(mouse::Event::ButtonReleased(mouse::Button::Middle))
-            | Event::Mouse(mouse::Event::ButtonReleased(mouse::Button::Right))
-            | Event::Touch(touch::Event::FingerLifted { .. }) => {
+            | Event::Mouse(mouse::Event::ButtonReleased(mouse::Button::Right)) => {
                 if let Some(on_press) = self.on_event.as_ref() {
                     let state = tree.state.downcast_mut::<State>();
 
@@ -346,11 +402,100 @@ where
                     }
                 }
             }
+            Event::Mouse(mouse::Event::WheelScrolled { delta }) => {
+                if let Some(msg) = self
+                    .on_scroll
+                    .as_ref()
+                    .filter(|_| cursor.is_over(layout.bounds()))
+                    .and_then(|on_scroll| on_scroll(delta))
+                {
+                    shell.publish(msg);
+                    return event::Status::Captured;
+                }
+            }
+            Event::Touch(touch::Event::FingerPressed { id, position }) => {
+                // Not captured, so that a scrollable around the button can still be dragged,
+                // which cancels the tap
+                if self.on_event.is_some()
+                    && cursor.is_over(layout.bounds())
+                    && CLAIMED_TOUCH.get() != Some((id, position))
+                {
+                    CLAIMED_TOUCH.set(Some((id, position)));
+                    let state = tree.state.downcast_mut::<State>();
+                    let since = Instant::now();
+                    state.is_pressed = true;
+                    state.touch = Some(Touch {
+                        finger: id,
+                        origin: position,
+                        since,
+                        long_pressed: false,
+                    });
+                    shell.request_redraw(window::RedrawRequest::At(since + long_press()));
+                }
+            }
+            Event::Touch(touch::Event::FingerMoved { id, position }) => {
+                let state = tree.state.downcast_mut::<State>();
+                if state
+                    .touch
+                    .is_some_and(|t| t.finger == id && t.origin.distance(position) > TOUCH_SLOP)
+                {
+                    state.touch = None;
+                    state.is_pressed = false;
+                }
+            }
+            Event::Touch(touch::Event::FingerLifted { id, .. }) => {
+                let state = tree.state.downcast_mut::<State>();
+                if let Some(touch) = state.touch.filter(|t| t.finger == id) {
+                    state.touch = None;
+                    state.is_pressed = false;
+                    // A long press already acted like a right click
+                    if touch.long_pressed {
+                        return event::Status::Captured;
+                    }
+                    if let Some(on_press) = self.on_event.as_ref() {
+                        if let Some(msg) = on_press.get(
+                            released(mouse::Button::Left),
+                            layout,
+                            cursor,
+                            clipboard,
+                            viewport,
+                        ) {
+                            shell.publish(msg);
+                        }
+                        return event::Status::Captured;
+                    }
+                }
+            }
+            Event::Window(window::Event::RedrawRequested(now)) => {
+                let state = tree.state.downcast_mut::<State>();
+                if let Some(touch) = state.touch.filter(|t| !t.long_pressed) {
+                    let due = touch.since + long_press();
+                    if now < due {
+                        shell.request_redraw(window::RedrawRequest::At(due));
+                    } else if let Some(on_press) = self.on_event.as_ref() {
+                        state.touch = Some(Touch {
+                            long_pressed: true,
+                            ..touch
+                        });
+                        state.is_pressed = false;
+                        if let Some(msg) = on_press.get(
+                            released(mouse::Button::Right),
+                            layout,
+                            cursor,
+                            clipboard,
+                            viewport,
+                        ) {
+                            shell.publish(msg);
+                        }
+                    }
+                }
+            }
             Event::Touch(touch::Event::FingerLost { .. })
             | Event::Mouse(mouse::Event::CursorLeft) => {
                 let state = tree.state.downcast_mut::<State>();
                 state.is_hovered = false;
                 state.is_pressed = false;
+                state.touch = None;
             }
             _ => {}
         }
@@ -424,18 +569,25 @@ where
 
     fn mouse_interaction(
         &self,
-        _tree: &Tree,
+        tree: &Tree,
         layout: Layout<'_>,
         cursor: mouse::Cursor,
-        _viewport: &Rectangle,
-        _renderer: &Renderer,
+        viewport: &Rectangle,
+        renderer: &Renderer,
     ) -> mouse::Interaction {
         let is_mouse_over = cursor.is_over(layout.bounds());
 
         if is_mouse_over && self.on_event.is_some() {
             mouse::Interaction::Pointer
         } else {
-            mouse::Interaction::default()
+            // Clickable content, like the button of a module opening a popup
+            self.content.as_widget().mouse_interaction(
+                &tree.children[0],
+                layout.children().next().unwrap(),
+                cursor,
+                viewport,
+                renderer,
+            )
         }
     }
 
@@ -0,0 +1,57 @@
+use crate::{
+    config::{get_config_dir, load_ini, read_config},
+    helpers::templates::Templates,
+    listeners::register_listeners,
+    modules::register_modules,
+    registry::Registry,
+    resolvers::register_resolvers,
+};
+
+/// `bar-rs check`: Reads the config, collects the data of every enabled module once and prints
+/// the text each module would show. Returns the exit code, which is 1 if any module failed or
+/// `strict_config` is set and the config has problems.
+pub fn run() -> i32 {
+    let mut registry = Registry::default();
+    register_modules(&mut registry);
+    register_listeners(&mut registry);
+    register_resolvers(&mut registry);
+
+    let mut templates = Templates::default();
+    let config_file = get_config_dir();
+    println!("Checking {}", config_file.to_string_lossy());
+    if let Err(e) = load_ini(&config_file) {
+        eprintln!("Failed to read config: {e}");
+        return 1;
+    }
+    // Problems in the config are printed while it's read
+    let config = read_config(&config_file, &mut registry, &mut templates, 1.);
+
+    let mut failed = config.strict_config && !config.problems.is_empty();
+    for name in config.enabled_modules.get_all() {
+        let Some((instance, module)) = registry
+            .resolve_instance(name, &config)
+            .and_then(|instance| registry.get_instance_mut(&instance).map(|m| (instance, m)))
+        else {
+            println!(
+                "{name}: error: no module named `{name}` is registered or it couldn't be resolved"
+            );
+            failed = true;
+            continue;
+        };
+        if let Err(e) = module.collect_data() {
+            println!("{name}: error: {e}");
+            failed = true;
+            continue;
+        }
+        match module.text_content(templates.get(&instance)) {
+            // Keep it to one line per module
+            Some(Ok(text)) => println!("{name}: {}", text.replace('\n', "\\n")),
+            Some(Err(e)) => {
+                println!("{name}: error: {e}");
+                failed = true;
+            }
+            None => println!("{name}: (no text)"),
+        }
+    }
+    failed as i32
+}
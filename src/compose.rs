@@ -0,0 +1,179 @@
+//! Composes the bar from the views of its modules, independent of the window it's shown in, so
+//! that `bar-rs render` draws exactly what the layer surface shows.
+use iced::{
+    theme::Palette,
+    widget::{container, stack},
+    Alignment, Border, Color, Element, Length, Theme,
+};
+
+use crate::{
+    config::{scale::Scale, Config, EnabledModules, Thrice},
+    fill::FillExt,
+    helpers::templates::Templates,
+    list::{list, DynamicAlign},
+    modules::{empty::EmptyModule, module_view, Module},
+    registry::Registry,
+    separator::with_separators,
+    Message,
+};
+
+/// The bar with the enabled modules. `dimmed` fades the modules into the background, see
+/// `dim_when_unfocused`.
+pub fn bar<'a>(
+    registry: &'a Registry,
+    config: &'a Config,
+    templates: &'a Templates,
+    dimmed: bool,
+) -> Element<'a, Message> {
+    let anchor = &config.anchor;
+    let make_list = |spacing: fn(&Thrice<f32>) -> f32,
+                     field: fn(&EnabledModules) -> &Vec<String>| {
+        let mut stretches = false;
+        let modules = registry
+            .get_modules(
+                field(&config.enabled_modules)
+                    .iter()
+                    .filter(|m| !registry.is_hidden(m, config)),
+                config,
+            )
+            .filter_map(|(name, m)| {
+                let spacer = m.spacer();
+                let module = module_view(
+                    &name,
+                    m,
+                    &config.module_config.local,
+                    &config.popup_config,
+                    anchor,
+                    templates.get(&name),
+                )?
+                .map(move |msg| Message::Instance {
+                    name: name.clone(),
+                    msg: Box::new(msg),
+                });
+                // Other modules take up as much space as their content, but stretching
+                // modules divide the free space of the section among each other
+                let module = match m.stretch() {
+                    Some(weight) => {
+                        stretches = true;
+                        let portion = Length::FillPortion(weight);
+                        match anchor.vertical() {
+                            true => container(module).width(Length::Fill).height(portion),
+                            false => container(module).width(portion).height(Length::Fill),
+                        }
+                        .into()
+                    }
+                    None => module,
+                };
+                Some((spacer, module))
+            })
+            .collect::<Vec<_>>();
+        let modules = with_separators(
+            modules,
+            &config.module_config.global,
+            &config.module_config.local,
+            anchor,
+        );
+        let content = if modules.is_empty() {
+            vec![registry.get_module::<EmptyModule>().wrapper(
+                &config.module_config.local,
+                "".into(),
+                anchor,
+            )]
+        } else {
+            modules
+        };
+        (
+            container(list(anchor, content).spacing(spacing(&config.module_config.global.spacing))),
+            stretches,
+        )
+    };
+    let (left, left_stretches) = make_list(|s| s.left, |m| &m.left);
+    let (center, _) = make_list(|s| s.center, |m| &m.center);
+    let (right, right_stretches) = make_list(|s| s.right, |m| &m.right);
+    // The left and right sections share the bar, a section with stretching modules takes up
+    // all the space the other one leaves
+    let (left, right) = match (left_stretches, right_stretches) {
+        (true, false) => (left.fillx(!anchor.vertical()), right),
+        (false, _) => (left, right.fillx(!anchor.vertical())),
+        (true, true) => (
+            left.fillx(!anchor.vertical()),
+            right.fillx(!anchor.vertical()),
+        ),
+    };
+    let global = &config.module_config.global;
+    // The background is drawn here instead of being the clear color of the window, so that
+    // it can have rounded corners and a border
+    let bar = container(stack!(
+        center
+            .fillx(!anchor.vertical())
+            .align(anchor, Alignment::Center),
+        list(
+            anchor,
+            [(left, Alignment::Start), (right, Alignment::End)]
+                .map(|(e, align)| e.align(anchor, align).into())
+        )
+    ))
+    .padding(global.padding)
+    .width(Length::Fill)
+    .height(Length::Fill)
+    .style(move |_| container::Style {
+        background: Some(global.background_color.into()),
+        border: global.border,
+        ..Default::default()
+    });
+    match dimmed {
+        // The modules fade into the color of the background
+        true => stack!(
+            bar,
+            container("")
+                .width(Length::Fill)
+                .height(Length::Fill)
+                .style(move |_| container::Style {
+                    background: Some(
+                        Color {
+                            a: 1. - global.dim_when_unfocused,
+                            ..global.background_color
+                        }
+                        .into()
+                    ),
+                    border: Border {
+                        radius: global.border.radius,
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                })
+        )
+        .into(),
+        false => bar.into(),
+    }
+}
+
+/// The theme of the bar window, which stays transparent since [bar] draws the background
+pub fn theme() -> Theme {
+    Theme::custom(
+        "Bar theme".to_string(),
+        Palette {
+            background: Color::TRANSPARENT,
+            text: Color::WHITE,
+            primary: Color::WHITE,
+            success: Color::WHITE,
+            danger: Color::WHITE,
+        },
+    )
+}
+
+/// The size of the bar on an output of this size: as long as the output unless configured
+/// otherwise
+pub fn size(config: &Config, (x, y): (u32, u32)) -> (u32, u32) {
+    let global = &config.module_config.global;
+    match config.anchor.vertical() {
+        true => (
+            global.width.unwrap_or(30_u32.scaled(config.scale_factor)),
+            global.height.unwrap_or(y),
+        ),
+        false => (
+            global.width.unwrap_or(x),
+            global.height.unwrap_or(30_u32.scaled(config.scale_factor)),
+        ),
+    }
+}
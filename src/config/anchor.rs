@@ -18,6 +18,26 @@ impl BarAnchor {
     }
 }
 
+/// The direction of the text of a module
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum Orientation {
+    /// Vertical on bars anchored `left` or `right`
+    #[default]
+    Auto,
+    Horizontal,
+    Vertical,
+}
+
+impl Orientation {
+    pub fn vertical(&self, anchor: &BarAnchor) -> bool {
+        match self {
+            Orientation::Auto => anchor.vertical(),
+            Orientation::Horizontal => false,
+            Orientation::Vertical => true,
+        }
+    }
+}
+
 impl From<BarAnchor> for String {
     fn from(anchor: BarAnchor) -> String {
         match anchor {
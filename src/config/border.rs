@@ -0,0 +1,134 @@
+use iced::{Border, Color, Radius};
+
+use super::{parse::IntoExt, scale::Scale};
+
+const SIDES: [&str; 4] = ["top", "right", "bottom", "left"];
+
+/// One side of [BorderSides]
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct BorderSide {
+    pub width: f32,
+    /// Falls back to the `border_color`
+    pub color: Option<Color>,
+}
+
+/// Borders on single sides of a module or popup (like an accent line on the left), set with
+/// `border_{{side}}_width` and `border_{{side}}_color`. The border of iced is the same on all
+/// sides, so these are drawn by [crate::border::with_sides].
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct BorderSides {
+    pub top: BorderSide,
+    pub right: BorderSide,
+    pub bottom: BorderSide,
+    pub left: BorderSide,
+}
+
+impl BorderSides {
+    /// Reads the sides from the values `get` returns for their keys, `None` if no side is set
+    pub fn read(get: impl Fn(&str) -> Option<String>) -> Option<Self> {
+        let mut set = false;
+        let [top, right, bottom, left] = SIDES.map(|side| {
+            let width_key = format!("border_{side}_width");
+            let color_key = format!("border_{side}_color");
+            let width = width(&width_key, get(&width_key).as_deref());
+            let color = color(&color_key, get(&color_key).as_deref());
+            set |= width.is_some() || color.is_some();
+            BorderSide {
+                width: width.unwrap_or_default(),
+                color,
+            }
+        });
+        set.then_some(Self {
+            top,
+            right,
+            bottom,
+            left,
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        [self.top, self.right, self.bottom, self.left]
+            .iter()
+            .all(|side| side.width == 0.)
+    }
+}
+
+impl Scale for BorderSides {
+    fn scaled(self, factor: f32) -> Self {
+        let side = |side: BorderSide| BorderSide {
+            width: side.width.scaled(factor),
+            ..side
+        };
+        Self {
+            top: side(self.top),
+            right: side(self.right),
+            bottom: side(self.bottom),
+            left: side(self.left),
+        }
+    }
+}
+
+/// Reads `border_color`, `border_width` and `border_radius`, `None` if none of them is set
+pub fn read_border(get: impl Fn(&str) -> Option<String>) -> Option<Border> {
+    let color = color("border_color", get("border_color").as_deref());
+    let width = width("border_width", get("border_width").as_deref());
+    let radius = radius("border_radius", get("border_radius").as_deref());
+    (color.is_some() || width.is_some() || radius.is_some()).then(|| Border {
+        color: color.unwrap_or_default(),
+        width: width.unwrap_or_default(),
+        radius: radius.unwrap_or_default(),
+    })
+}
+
+/// A width which isn't negative, invalid values are reported with their key
+pub fn width(key: &str, value: Option<&str>) -> Option<f32> {
+    let value = value?;
+    match value.trim().parse::<f32>() {
+        Ok(width) if width >= 0. => Some(width),
+        _ => {
+            eprintln!("Invalid value for {key}: `{value}` is not a positive number");
+            None
+        }
+    }
+}
+
+/// A color, invalid values are reported with their key
+pub fn color(key: &str, value: Option<&str>) -> Option<Color> {
+    let value = value?;
+    csscolorparser::parse(value)
+        .map(|color| color.into_ext())
+        .map_err(|e| eprintln!("Invalid value for {key}: `{value}` is not a color: {e}"))
+        .ok()
+}
+
+/// The radius of the corners, like in CSS: one value for all corners, two for top left and
+/// bottom right / top right and bottom left, or four values starting at the top left corner and
+/// going clockwise. Invalid values are reported with their key.
+pub fn radius(key: &str, value: Option<&str>) -> Option<Radius> {
+    let value = value?;
+    let radii = value
+        .split_whitespace()
+        .map(|radius| radius.parse::<f32>().ok().filter(|radius| *radius >= 0.))
+        .collect::<Option<Vec<f32>>>();
+    match radii.as_deref() {
+        Some(&[all]) => Some(all.into()),
+        Some(&[a, b]) => Some(Radius {
+            top_left: a,
+            top_right: b,
+            bottom_right: a,
+            bottom_left: b,
+        }),
+        Some(&[top_left, top_right, bottom_right, bottom_left]) => Some(Radius {
+            top_left,
+            top_right,
+            bottom_right,
+            bottom_left,
+        }),
+        _ => {
+            eprintln!(
+                "Invalid value for {key}: `{value}`, expected 1, 2 or 4 radii which aren't negative"
+            );
+            None
+        }
+    }
+}
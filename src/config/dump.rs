@@ -0,0 +1,106 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    path::PathBuf,
+};
+
+use crate::registry::Registry;
+
+use super::{
+    load_ini, output, profile,
+    schema::{general_keys, ConfigKey, MODULE_STYLE, POPUP_STYLE, STYLE},
+    Config,
+};
+
+/// The sections which are written first, in this order
+const GLOBAL: &[&str] = &["general", "style", "module_style", "popup_style"];
+
+/// The effective config, as it could be written into the config file: the config file with the
+/// active profile applied, the modules as they are enabled right now (including the edits made
+/// with `bar-rsctl`) and the defaults of every option which isn't set. Module sections get the
+/// defaults of their module's own options, the style options are inherited from
+/// `[module_style]` and `[popup_style]` as usual. Used by `bar-rsctl dump-config`.
+pub fn dump(path: &PathBuf, registry: &Registry, config: &Config) -> Result<String, String> {
+    let mut ini = load_ini(path)?;
+    // The profile is applied, so its sections would only apply it twice
+    profile::apply(&mut ini);
+    ini.remove_key("general", "default_profile");
+    let sections = ini.get_map_ref();
+
+    let general = general_keys(registry);
+    let mut keys: HashMap<String, Vec<ConfigKey>> = HashMap::from([
+        ("general".to_string(), general),
+        ("style".to_string(), STYLE.to_vec()),
+        ("module_style".to_string(), MODULE_STYLE.to_vec()),
+        ("popup_style".to_string(), POPUP_STYLE.to_vec()),
+    ]);
+    // Keyed by the instance, like `disk_usage.home`, whose section overrides the module's one
+    for (instance, module) in registry.get_modules(config.enabled_modules.get_all(), config) {
+        keys.insert(format!("module:{instance}"), module.known_keys());
+        let popup_keys = module.known_popup_keys();
+        if module.popup_cfg_override().is_some() || !popup_keys.is_empty() {
+            keys.insert(format!("module_popup:{instance}"), popup_keys);
+        }
+    }
+
+    // The modules of the bar's output may be set in its own section
+    let modules_section = output::output_name(config.monitor.as_ref())
+        .map(|output| format!("modules:{output}"))
+        .filter(|section| sections.contains_key(section))
+        .unwrap_or("modules".to_string());
+    let modules = &config.enabled_modules;
+    let enabled = write_section(
+        &modules_section,
+        [
+            ("left", &modules.left),
+            ("center", &modules.center),
+            ("right", &modules.right),
+        ]
+        .map(|(side, list)| (side.to_string(), Some(list.join(", "))))
+        .into(),
+    );
+
+    let mut out = String::new();
+    let rest = sections
+        .keys()
+        .chain(keys.keys())
+        .filter(|section| {
+            !GLOBAL.contains(&section.as_str())
+                && **section != modules_section
+                && !section.starts_with("profile:")
+                // Options before the first section header
+                && *section != "default"
+        })
+        .collect::<BTreeSet<_>>();
+    for section in GLOBAL
+        .iter()
+        .map(|s| s.to_string())
+        .chain(rest.into_iter().cloned())
+    {
+        let mut options: BTreeMap<String, Option<String>> = keys
+            .get(&section)
+            .into_iter()
+            .flatten()
+            // Options like `icon.*` stand for many, they have no default
+            .filter(|key| !key.name.contains('*'))
+            .filter_map(|key| Some((key.name.to_string(), Some(key.default?.to_string()))))
+            .collect();
+        options.extend(sections.get(&section).cloned().unwrap_or_default());
+        out.push_str(&write_section(&section, options));
+        if section == "popup_style" {
+            out.push_str(&enabled);
+        }
+    }
+    Ok(out)
+}
+
+fn write_section(name: &str, options: BTreeMap<String, Option<String>>) -> String {
+    let mut section = format!("[{name}]\n");
+    for (key, value) in options {
+        match value {
+            Some(value) => section.push_str(&format!("{key} = {value}\n")),
+            None => section.push_str(&format!("{key}\n")),
+        }
+    }
+    section.push('\n');
+    section
+}
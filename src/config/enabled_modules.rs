@@ -1,6 +1,6 @@
 use configparser::ini::Ini;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct EnabledModules {
     pub left: Vec<String>,
     pub center: Vec<String>,
@@ -19,25 +19,55 @@ impl Default for EnabledModules {
     }
 }
 
-impl From<&Ini> for EnabledModules {
-    fn from(ini: &Ini) -> Self {
+/// Reads the `[modules]` section, which can be overridden for a single output with a
+/// `[modules:{output}]` section. Modules whose `outputs` option doesn't contain the output of the
+/// bar are left out.
+impl From<(&Ini, Option<&str>)> for EnabledModules {
+    fn from((ini, output): (&Ini, Option<&str>)) -> Self {
+        let output_section = output.map(|output| format!("modules:{output}"));
         let get = |field: &str| {
-            ini.get("modules", field).map(|value| {
-                value
-                    .split(',')
-                    .filter(|v| !v.is_empty())
-                    .map(|v| v.trim().to_string())
-                    .collect()
-            })
+            output_section
+                .as_ref()
+                .and_then(|section| ini.get(section, field))
+                .or_else(|| ini.get("modules", field))
+                .map(|value| {
+                    value
+                        .split(',')
+                        .filter(|v| !v.is_empty())
+                        .map(|v| v.trim().to_string())
+                        .collect()
+                })
+        };
+        let on_output = |modules: Vec<String>| {
+            modules
+                .into_iter()
+                .filter(|module| shown_on(ini, module, output))
+                .collect()
         };
 
         let default = Self::default();
 
         Self {
-            left: get("left").unwrap_or(default.left),
-            center: get("center").unwrap_or(default.center),
-            right: get("right").unwrap_or(default.right),
+            left: on_output(get("left").unwrap_or(default.left)),
+            center: on_output(get("center").unwrap_or(default.center)),
+            right: on_output(get("right").unwrap_or(default.right)),
+        }
+    }
+}
+
+/// Whether the module is shown on the given output according to its `outputs` option, which
+/// instances like `disk_usage.home` inherit from their module
+fn shown_on(ini: &Ini, module: &str, output: Option<&str>) -> bool {
+    let outputs = ini.get(&format!("module:{module}"), "outputs").or_else(|| {
+        module
+            .rsplit_once('.')
+            .and_then(|(base, _)| ini.get(&format!("module:{base}"), "outputs"))
+    });
+    match outputs {
+        Some(outputs) => {
+            output.is_some_and(|output| outputs.split(',').any(|o| o.trim() == output))
         }
+        None => true,
     }
 }
 
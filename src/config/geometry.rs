@@ -0,0 +1,46 @@
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use super::{anchor::BarAnchor, module_config::GlobalModuleConfig, scale::Scale};
+
+/// Bit pattern of the inner thickness of the bar, which sizes given in percent refer to
+static THICKNESS: AtomicU32 = AtomicU32::new(0);
+
+/// The size of the bar, so that modules can adapt their content to it
+#[derive(Debug, Default, Clone, Copy)]
+pub struct BarGeometry {
+    pub anchor: BarAnchor,
+    /// The size of the bar across its length, i.e. the height of horizontal bars
+    pub thickness: f32,
+    /// The thickness without the `padding` of `[style]`, the space modules have
+    pub inner_thickness: f32,
+}
+
+impl BarGeometry {
+    pub fn new(anchor: BarAnchor, global: &GlobalModuleConfig, scale: f32) -> Self {
+        let (size, padding) = match anchor.vertical() {
+            true => (global.width, global.padding.left + global.padding.right),
+            false => (global.height, global.padding.top + global.padding.bottom),
+        };
+        let thickness = size.unwrap_or(30_u32.scaled(scale)) as f32;
+        Self {
+            anchor,
+            thickness,
+            inner_thickness: (thickness - padding).max(0.),
+        }
+    }
+
+    /// How many lines of text of `font_size` fit across the bar, at least one
+    pub fn lines(&self, font_size: f32) -> usize {
+        // iced lays out text with a line height of 1.3 times its size by default
+        (self.inner_thickness / (font_size * 1.3)).floor().max(1.) as usize
+    }
+}
+
+/// The inner thickness of the bar of the config which is being read
+pub fn thickness() -> f32 {
+    f32::from_bits(THICKNESS.load(Ordering::Relaxed))
+}
+
+pub fn set_thickness(thickness: f32) {
+    THICKNESS.store(thickness.to_bits(), Ordering::Relaxed);
+}
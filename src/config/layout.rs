@@ -0,0 +1,209 @@
+use std::{str::FromStr, sync::Mutex};
+
+use super::EnabledModules;
+
+/// The edits of the enabled modules made at runtime, which last until the config is reloaded
+static EDITS: Mutex<Vec<LayoutEdit>> = Mutex::new(vec![]);
+
+/// A side of the bar, as in the `[modules]` section
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Side {
+    Left,
+    Center,
+    Right,
+}
+
+impl FromStr for Side {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "left" => Ok(Side::Left),
+            "center" => Ok(Side::Center),
+            "right" => Ok(Side::Right),
+            _ => Err(format!(
+                "unknown side `{s}`, expected left, center or right"
+            )),
+        }
+    }
+}
+
+/// Where a module is put by a [LayoutEdit]
+#[derive(Debug, Clone, PartialEq)]
+pub enum Placement {
+    /// At the end of a side
+    End(Side),
+    /// Right after another enabled module
+    After(String),
+    /// Right before another enabled module
+    Before(String),
+}
+
+/// A change of the enabled modules, e.g. via `bar-rsctl add-module`
+#[derive(Debug, Clone, PartialEq)]
+pub enum LayoutEdit {
+    Add {
+        module: String,
+        placement: Placement,
+    },
+    Remove(String),
+    Move {
+        module: String,
+        placement: Placement,
+    },
+}
+
+impl LayoutEdit {
+    pub fn apply(&self, modules: &mut EnabledModules) -> Result<(), String> {
+        match self {
+            LayoutEdit::Add { module, placement } => {
+                if modules.contains(module) {
+                    return Err(format!(
+                        "`{module}` is enabled already, use move-module to move it"
+                    ));
+                }
+                place(modules, module, placement)
+            }
+            LayoutEdit::Remove(module) => {
+                if !modules.contains(module) {
+                    return Err(format!("no enabled module named `{module}`"));
+                }
+                remove(modules, module);
+                Ok(())
+            }
+            LayoutEdit::Move { module, placement } => {
+                if !modules.contains(module) {
+                    return Err(format!("no enabled module named `{module}`"));
+                }
+                if let Placement::After(other) | Placement::Before(other) = placement {
+                    if other == module {
+                        return Err(format!("can't move `{module}` next to itself"));
+                    }
+                }
+                let mut moved = modules.clone();
+                remove(&mut moved, module);
+                place(&mut moved, module, placement)?;
+                *modules = moved;
+                Ok(())
+            }
+        }
+    }
+}
+
+fn side_mut(modules: &mut EnabledModules, side: Side) -> &mut Vec<String> {
+    match side {
+        Side::Left => &mut modules.left,
+        Side::Center => &mut modules.center,
+        Side::Right => &mut modules.right,
+    }
+}
+
+fn remove(modules: &mut EnabledModules, module: &String) {
+    for side in [Side::Left, Side::Center, Side::Right] {
+        side_mut(modules, side).retain(|m| m != module);
+    }
+}
+
+fn place(modules: &mut EnabledModules, module: &str, placement: &Placement) -> Result<(), String> {
+    let (other, offset) = match placement {
+        Placement::End(side) => {
+            side_mut(modules, *side).push(module.to_string());
+            return Ok(());
+        }
+        Placement::After(other) => (other, 1),
+        Placement::Before(other) => (other, 0),
+    };
+    for side in [Side::Left, Side::Center, Side::Right] {
+        let list = side_mut(modules, side);
+        if let Some(i) = list.iter().position(|m| m == other) {
+            list.insert(i + offset, module.to_string());
+            return Ok(());
+        }
+    }
+    Err(format!("no enabled module named `{other}`"))
+}
+
+pub fn push(edit: LayoutEdit) {
+    EDITS.lock().unwrap().push(edit);
+}
+
+/// Drops the edits, so that the modules are the ones of the config again
+pub fn clear() {
+    EDITS.lock().unwrap().clear();
+}
+
+/// Applies the edits made since the config was last reloaded
+pub fn apply(modules: &mut EnabledModules) {
+    for edit in EDITS.lock().unwrap().iter() {
+        if let Err(e) = edit.apply(modules) {
+            eprintln!("Failed to apply {edit:?}: {e}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn modules() -> EnabledModules {
+        let vec = |list: &[&str]| list.iter().map(|m| m.to_string()).collect();
+        EnabledModules {
+            left: vec(&["workspaces"]),
+            center: vec(&["time"]),
+            right: vec(&["volume", "memory"]),
+        }
+    }
+
+    #[test]
+    fn adds_moves_and_removes_modules() {
+        let mut modules = modules();
+        LayoutEdit::Add {
+            module: "cpu".to_string(),
+            placement: Placement::After("volume".to_string()),
+        }
+        .apply(&mut modules)
+        .unwrap();
+        assert_eq!(modules.right, ["volume", "cpu", "memory"]);
+        LayoutEdit::Move {
+            module: "time".to_string(),
+            placement: Placement::Before("workspaces".to_string()),
+        }
+        .apply(&mut modules)
+        .unwrap();
+        assert_eq!(modules.left, ["time", "workspaces"]);
+        assert!(modules.center.is_empty());
+        LayoutEdit::Move {
+            module: "cpu".to_string(),
+            placement: Placement::End(Side::Center),
+        }
+        .apply(&mut modules)
+        .unwrap();
+        assert_eq!(modules.center, ["cpu"]);
+        LayoutEdit::Remove("volume".to_string())
+            .apply(&mut modules)
+            .unwrap();
+        assert_eq!(modules.right, ["memory"]);
+    }
+
+    #[test]
+    fn rejects_edits_which_dont_fit_the_modules() {
+        let mut modules = modules();
+        let add = |module: &str, after: &str| LayoutEdit::Add {
+            module: module.to_string(),
+            placement: Placement::After(after.to_string()),
+        };
+        assert!(add("time", "volume").apply(&mut modules).is_err());
+        assert!(add("cpu", "battery").apply(&mut modules).is_err());
+        assert!(LayoutEdit::Remove("cpu".to_string())
+            .apply(&mut modules)
+            .is_err());
+        // A failed move leaves the module where it was
+        assert!(LayoutEdit::Move {
+            module: "time".to_string(),
+            placement: Placement::After("battery".to_string()),
+        }
+        .apply(&mut modules)
+        .is_err());
+        assert_eq!(modules.center, ["time"]);
+    }
+}
@@ -5,42 +5,91 @@ use std::{
     io::Write,
     path::PathBuf,
     sync::Arc,
+    time::Duration,
 };
 
 use anchor::BarAnchor;
 use configparser::ini::{Ini, IniDefault};
 use directories::ProjectDirs;
+pub use dump::dump;
 pub use enabled_modules::EnabledModules;
-use handlebars::Handlebars;
 use iced::{
     futures::{channel::mpsc::Sender, SinkExt},
     platform_specific::shell::commands::layer_surface::KeyboardInteractivity,
 };
 use module_config::ModuleConfig;
+use parse::StringExt;
 use popup_config::PopupConfig;
+use scale::{Scale, ScaleFactor};
 use tokio::sync::mpsc;
 
-use crate::{registry::Registry, Message};
+use crate::{button, helpers::templates::Templates, registry::Registry, Message};
 pub use thrice::Thrice;
 
 pub mod anchor;
+pub mod border;
+mod dump;
 mod enabled_modules;
+pub mod geometry;
 mod insets;
+pub mod layout;
 pub mod module_config;
+pub mod output;
 pub mod parse;
 pub mod popup_config;
+pub mod profile;
+pub mod scale;
+pub mod schedule;
+pub mod schema;
+pub mod text_icons;
+pub mod thresholds;
 mod thrice;
+mod toml_config;
+mod validate;
 
 #[derive(Debug)]
 pub struct Config {
     pub hard_reload: bool,
+    /// Whether the runtime state of modules is saved on exit and restored at startup
+    pub persist_state: bool,
+    /// Whether the bar slides out of the screen while it isn't used
+    pub auto_hide: bool,
+    /// Whether the bar slides out of the screen while a window on its output is fullscreen
+    pub hide_on_fullscreen: bool,
+    /// How long the bar stays visible after the cursor left it
+    pub hide_delay: Duration,
+    /// The length of the slide animation
+    pub hide_duration: Duration,
+    /// Updates of modules within this long are applied and drawn together, `0` draws every
+    /// update right away
+    pub redraw_interval: Duration,
+    /// Holding a finger on a module this long acts like a right click
+    pub long_press: Duration,
     pub enabled_modules: EnabledModules,
+    /// The listeners enabled by their own config option, the ones required by modules are
+    /// started by [Registry::get_listeners] as long as a module needs them
     pub enabled_listeners: HashSet<TypeId>,
     pub module_config: ModuleConfig,
     pub popup_config: PopupConfig,
     pub anchor: BarAnchor,
     pub monitor: Option<String>,
     pub kb_focus: KeyboardInteractivity,
+    /// The namespace of the layer surface, which compositors match their layer rules against
+    pub namespace: String,
+    pub scale: ScaleFactor,
+    /// `scale` resolved for the output of the bar, all sizes are multiplied with it while the
+    /// config is read
+    pub scale_factor: f32,
+    /// Named sets of modules which can be hidden together, see `ToggleGroupAction`
+    pub groups: HashMap<String, HashSet<String>>,
+    /// The names of all profiles, see `ProfileAction`
+    pub profiles: Vec<String>,
+    /// The active profile
+    pub profile: Option<String>,
+    /// Whether problems in the config keep the bar from starting
+    pub strict_config: bool,
+    /// Unknown sections and options and invalid values found in the config
+    pub problems: Vec<String>,
 }
 
 impl Config {
@@ -48,16 +97,17 @@ impl Config {
         let enabled_modules = EnabledModules::default();
         Self {
             hard_reload: false,
+            persist_state: false,
+            auto_hide: false,
+            hide_on_fullscreen: false,
+            hide_delay: Duration::from_secs(1),
+            hide_duration: Duration::from_millis(200),
+            redraw_interval: Duration::from_millis(16),
+            long_press: Duration::from_millis(500),
             enabled_listeners: registry
-                .enabled_listeners(&enabled_modules, &None)
-                .chain(
-                    registry
-                        .all_listeners()
-                        .flat_map(|(l_id, l)| {
-                            l.config().into_iter().map(move |option| (l_id, option))
-                        })
-                        .filter_map(|(l_id, option)| option.default.then_some(*l_id)),
-                )
+                .all_listeners()
+                .flat_map(|(l_id, l)| l.config().into_iter().map(move |option| (l_id, option)))
+                .filter_map(|(l_id, option)| option.default.then_some(*l_id))
                 .collect(),
             enabled_modules,
             module_config: ModuleConfig::default(),
@@ -65,13 +115,29 @@ impl Config {
             anchor: BarAnchor::default(),
             monitor: None,
             kb_focus: KeyboardInteractivity::None,
+            namespace: "bar-rs".to_string(),
+            scale: ScaleFactor::default(),
+            scale_factor: 1.,
+            groups: HashMap::new(),
+            profiles: vec![],
+            profile: None,
+            strict_config: false,
+            problems: vec![],
         }
     }
 
     pub fn exclusive_zone(&self) -> i32 {
         (match self.anchor {
-            BarAnchor::Left | BarAnchor::Right => self.module_config.global.width.unwrap_or(30),
-            BarAnchor::Top | BarAnchor::Bottom => self.module_config.global.height.unwrap_or(30),
+            BarAnchor::Left | BarAnchor::Right => self
+                .module_config
+                .global
+                .width
+                .unwrap_or(30_u32.scaled(self.scale_factor)),
+            BarAnchor::Top | BarAnchor::Bottom => self
+                .module_config
+                .global
+                .height
+                .unwrap_or(30_u32.scaled(self.scale_factor)),
         }) as i32
     }
 }
@@ -84,6 +150,10 @@ pub fn get_config_dir() -> PathBuf {
             PathBuf::from("")
         });
     let _ = create_dir_all(&config_dir);
+    let toml_file = config_dir.join("bar-rs.toml");
+    if toml_file.exists() {
+        return toml_file;
+    }
     let config_file = config_dir.join("bar-rs.ini");
 
     if let Ok(mut file) = File::create_new(&config_file) {
@@ -99,32 +169,115 @@ pub fn get_config_dir() -> PathBuf {
     config_file
 }
 
-pub fn read_config(path: &PathBuf, registry: &mut Registry, templates: &mut Handlebars) -> Config {
+/// The directory to keep state in across restarts, `$XDG_STATE_HOME/bar-rs`
+pub fn get_state_dir() -> Option<PathBuf> {
+    let state_dir = ProjectDirs::from("fun.killarchive", "faervan", "bar-rs")
+        .and_then(|dirs| dirs.state_dir().map(|dir| dir.to_path_buf()))
+        .or_else(|| {
+            eprintln!("Failed to get state directory");
+            None
+        })?;
+    create_dir_all(&state_dir)
+        .map_err(|e| {
+            eprintln!(
+                "Failed to create state directory {}: {e}",
+                state_dir.to_string_lossy()
+            )
+        })
+        .ok()?;
+    Some(state_dir)
+}
+
+/// The directory to keep data in which can be regenerated, `$XDG_CACHE_HOME/bar-rs`
+pub fn get_cache_dir() -> Option<PathBuf> {
+    let cache_dir = ProjectDirs::from("fun.killarchive", "faervan", "bar-rs")
+        .map(|dirs| dirs.cache_dir().to_path_buf())
+        .or_else(|| {
+            eprintln!("Failed to get cache directory");
+            None
+        })?;
+    create_dir_all(&cache_dir)
+        .map_err(|e| {
+            eprintln!(
+                "Failed to create cache directory {}: {e}",
+                cache_dir.to_string_lossy()
+            )
+        })
+        .ok()?;
+    Some(cache_dir)
+}
+
+pub fn load_ini(path: &PathBuf) -> Result<Ini, String> {
     let mut ini = Ini::new();
     let mut defaults = IniDefault::default();
     defaults.delimiters = vec!['='];
     ini.load_defaults(defaults);
-    let Ok(_) = ini.load(path) else {
+    match path.extension().is_some_and(|ext| ext == "toml") {
+        true => toml_config::load(path, &mut ini).map(|_| ini),
+        false => ini.load(path).map(|_| ini),
+    }
+}
+
+/// Reads the config for a bar on an output of `output_scale` (as reported by wl_output), which
+/// `scale = auto` resolves to
+pub fn read_config(
+    path: &PathBuf,
+    registry: &mut Registry,
+    templates: &mut Templates,
+    output_scale: f32,
+) -> Config {
+    let Ok(mut ini) = load_ini(path) else {
         eprintln!("Failed to read config from {}", path.to_string_lossy());
-        return Config::default(registry);
+        let config = Config::default(registry);
+        button::set_long_press(config.long_press);
+        return config;
+    };
+    // All sizes are stored scaled, so the factor has to be known before parsing anything else
+    let scale_factor = ini
+        .get("general", "scale")
+        .into_scale()
+        .unwrap_or_default()
+        .resolve(output_scale);
+    // Before the profile is applied, so problems are reported in the section they're in
+    let problems = validate::validate(&ini, registry);
+    let profile = profile::apply(&mut ini);
+    let mut config = Config {
+        profile,
+        problems,
+        ..(&ini, &*registry, scale_factor).into()
+    };
+    layout::apply(&mut config.enabled_modules);
+    validate::report(&config.problems, config.strict_config);
+    button::set_long_press(config.long_press);
+    registry.sync_instances(config.enabled_modules.get_all());
+    let sections = ini.get_map_ref();
+    // Instances like `disk_usage.home` inherit the options of their module (`disk_usage`)
+    let section = |kind: &str, module: &str, instance: &str| {
+        let mut map = sections
+            .get(&format!("{kind}:{module}"))
+            .cloned()
+            .unwrap_or_default();
+        if instance != module {
+            map.extend(
+                sections
+                    .get(&format!("{kind}:{instance}"))
+                    .cloned()
+                    .unwrap_or_default(),
+            );
+        }
+        map
     };
-    let config: Config = (&ini, &*registry).into();
-    let empty_config = HashMap::new();
     registry
         .get_modules_mut(config.enabled_modules.get_all(), &config)
-        .map(|m| {
-            let name = m.name();
-            let cfg_map = ini
-                .get_map_ref()
-                .get(&format!("module:{}", name))
-                .unwrap_or(&empty_config);
-            let popup_cfg_map = ini
-                .get_map_ref()
-                .get(&format!("module_popup:{}", name))
-                .unwrap_or(&empty_config);
-            (m, cfg_map, popup_cfg_map)
-        })
-        .for_each(|(m, cfg_map, popup_cfg_map)| m.read_config(cfg_map, popup_cfg_map, templates));
+        .for_each(|(instance, m)| {
+            let module = m.name();
+            m.read_config(
+                &section("module", &module, instance),
+                &section("module_popup", &module, instance),
+                templates.get_mut(instance),
+                config.scale_factor,
+            )
+        });
     config
 }
 
@@ -140,16 +293,16 @@ pub async fn get_config(sender: &mut Sender<Message>) -> (Arc<PathBuf>, Arc<Conf
 }
 
 pub struct ConfigEntry {
-    pub section: String,
-    pub name: String,
+    pub section: &'static str,
+    pub name: &'static str,
     pub default: bool,
 }
 
 impl ConfigEntry {
-    pub fn new<S: ToString>(section: S, name: S, default: bool) -> Self {
+    pub fn new(section: &'static str, name: &'static str, default: bool) -> Self {
         Self {
-            section: section.to_string(),
-            name: name.to_string(),
+            section,
+            name,
             default,
         }
     }
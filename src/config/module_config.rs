@@ -1,14 +1,30 @@
 use std::collections::HashMap;
 
 use configparser::ini::Ini;
+use std::{f32::consts::FRAC_PI_2, time::Duration};
+
 use iced::{
-    runtime::platform_specific::wayland::layer_surface::IcedMargin, Background, Border, Color,
-    Padding,
+    runtime::platform_specific::wayland::layer_surface::IcedMargin, Alignment, Background, Border,
+    Color, Font, Padding, Radians,
 };
 
-use crate::modules::OnClickAction;
+use crate::{
+    bar_text::VerticalText,
+    helpers::{status::OnError, supervisor::ActionMode},
+    modules::OnClickAction,
+    NERD_FONT,
+};
 
-use super::{parse::StringExt, Thrice};
+use super::{
+    anchor::{BarAnchor, Orientation},
+    border::{self, read_border, BorderSides},
+    geometry::{self, BarGeometry},
+    parse::StringExt,
+    scale::Scale,
+    schedule::Schedule,
+    text_icons::text_icon,
+    Thrice,
+};
 
 #[derive(Debug, Default)]
 pub struct ModuleConfig {
@@ -24,6 +40,15 @@ pub struct GlobalModuleConfig {
     pub margin: IcedMargin,
     pub padding: Padding,
     pub background_color: Color,
+    /// Drawn around the whole bar, its radius rounds the corners of the background
+    pub border: Border,
+    /// The opacity of the modules while the cursor isn't over the bar and no popup is open
+    pub dim_when_unfocused: f32,
+    /// Drawn between adjacent modules of a section, except next to spacers
+    pub separator: Option<String>,
+    pub separator_color: Color,
+    /// The size of the separator, the `font_size` of `[module_style]` if it isn't set
+    pub separator_size: Option<f32>,
 }
 
 impl Default for GlobalModuleConfig {
@@ -35,41 +60,100 @@ impl Default for GlobalModuleConfig {
             margin: IcedMargin::default(),
             padding: Padding::default(),
             background_color: Color::from_rgba(0., 0., 0., 0.5),
+            border: Border {
+                width: 1.,
+                ..Default::default()
+            },
+            dim_when_unfocused: 1.,
+            separator: None,
+            separator_color: Color::from_rgba(1., 1., 1., 0.5),
+            separator_size: None,
         }
     }
 }
 
 #[derive(Debug)]
 pub struct LocalModuleConfig {
+    /// The size and anchor of the bar
+    pub bar: BarGeometry,
     pub text_color: Color,
     pub icon_color: Color,
+    /// The size of the text, set with `text_size` or `font_size`
     pub font_size: f32,
     pub icon_size: f32,
+    /// The font of the text, the default font unless `text_font` is set
+    pub text_font: Font,
+    /// Selected by `icon_font` in `[general]`
+    pub icon_font: Font,
+    /// Whether icons should be replaced by text (or emoji) for systems without a Nerd Font
+    pub text_icons: bool,
+    /// Whether text is vertical, see [VerticalText]
+    pub orientation: Orientation,
+    /// Vertical text of at most this many characters is stacked instead of turned
+    pub stack_text: usize,
+    /// The length a module takes up along the bar at least, so that its neighbors don't move
+    /// whenever its text changes
+    pub min_width: f32,
+    /// How the content of a module is aligned within `min_width`
+    pub content_align: Alignment,
     pub text_margin: Padding,
     pub icon_margin: Padding,
     pub spacing: f32,
     pub margin: Padding,
     pub padding: Padding,
     pub background: Option<Background>,
+    /// The background while the cursor is over a module which has an action
+    pub hover_background: Option<Background>,
+    /// The background while such a module is pressed, falls back to `hover_background`
+    pub pressed_background: Option<Background>,
     pub border: Border,
+    /// Borders on single sides, drawn in addition to `border`
+    pub border_sides: BorderSides,
     pub action: OnClickAction,
+    /// Clicks on a module within this long after the previous one are ignored
+    pub debounce: Duration,
+    /// How commands of actions are run while they're still running from a previous click
+    pub action_mode: ActionMode,
+    /// Commands of actions are killed after this long
+    pub action_timeout: Option<Duration>,
+    /// What modules show while their backend is unavailable
+    pub on_error: OnError,
+    pub error_icon: String,
+    pub error_color: Color,
 }
 
 impl Default for LocalModuleConfig {
     fn default() -> Self {
         Self {
+            bar: BarGeometry::default(),
             text_color: Color::WHITE,
             icon_color: Color::WHITE,
             font_size: 16.,
             icon_size: 20.,
+            text_font: Font::DEFAULT,
+            icon_font: NERD_FONT,
+            text_icons: false,
+            orientation: Orientation::Auto,
+            stack_text: 5,
+            min_width: 0.,
+            content_align: Alignment::Center,
             text_margin: Padding::default(),
             icon_margin: Padding::default(),
             spacing: 10.,
             margin: Padding::default(),
             padding: Padding::default(),
             background: None,
+            hover_background: None,
+            pressed_background: None,
             border: Border::default(),
+            border_sides: BorderSides::default(),
             action: OnClickAction::default(),
+            debounce: Duration::from_millis(300),
+            action_mode: ActionMode::default(),
+            action_timeout: None,
+            on_error: OnError::default(),
+            error_icon: "󰀦".to_string(),
+            error_color: Color::from_rgb(0.9, 0.3, 0.3),
         }
     }
 }
@@ -80,107 +164,228 @@ pub struct ModuleConfigOverride {
     pub icon_color: Option<Color>,
     pub font_size: Option<f32>,
     pub icon_size: Option<f32>,
+    pub text_font: Option<Font>,
+    pub orientation: Option<Orientation>,
+    pub stack_text: Option<usize>,
+    pub min_width: Option<f32>,
+    pub content_align: Option<Alignment>,
     pub text_margin: Option<Padding>,
     pub icon_margin: Option<Padding>,
     pub spacing: Option<f32>,
     pub margin: Option<Padding>,
     pub padding: Option<Padding>,
     pub background: Option<Option<Background>>,
+    pub hover_background: Option<Option<Background>>,
+    pub pressed_background: Option<Option<Background>>,
     pub border: Option<Border>,
+    pub border_sides: Option<BorderSides>,
     pub action: Option<OnClickAction>,
+    pub debounce: Option<Duration>,
+    pub action_mode: Option<ActionMode>,
+    pub action_timeout: Option<Duration>,
+    pub on_error: Option<OnError>,
+    pub error_icon: Option<String>,
+    pub error_color: Option<Color>,
+    /// Whether the bar stays visible with `auto_hide` while this module is active
+    pub keep_visible: bool,
+    /// The times of day the module is shown at, it's hidden at other times
+    pub visible_between: Option<Schedule>,
+    /// Text fallbacks by icon, set with `text_icon.{{icon}} = {{text}}`
+    pub text_icons: HashMap<String, String>,
 }
 
-impl From<&HashMap<String, Option<String>>> for ModuleConfigOverride {
-    fn from(map: &HashMap<String, Option<String>>) -> Self {
+impl ModuleConfigOverride {
+    /// The icon to show, replaced by its text fallback if `text_icons` is enabled
+    pub fn icon<'a>(&'a self, config: &LocalModuleConfig, icon: &'a str) -> &'a str {
+        match config.text_icons {
+            true => self
+                .text_icons
+                .get(icon.trim())
+                .map(|text| text.as_str())
+                .or_else(|| text_icon(icon))
+                .unwrap_or(icon),
+            false => icon,
+        }
+    }
+
+    /// How the text of this module is drawn if it's vertical, `None` if it's horizontal
+    pub fn vertical_text(
+        &self,
+        config: &LocalModuleConfig,
+        anchor: &BarAnchor,
+    ) -> Option<VerticalText> {
+        let vertical = self
+            .orientation
+            .unwrap_or(config.orientation)
+            .vertical(anchor);
+        vertical.then_some(VerticalText {
+            // Read from bottom to top on the left edge and from top to bottom on the right one
+            rotation: match anchor {
+                BarAnchor::Right => Radians(FRAC_PI_2),
+                _ => Radians(-FRAC_PI_2),
+            },
+            stack: self.stack_text.unwrap_or(config.stack_text),
+        })
+    }
+}
+
+impl From<(&HashMap<String, Option<String>>, f32)> for ModuleConfigOverride {
+    fn from((map, scale): (&HashMap<String, Option<String>>, f32)) -> Self {
         Self {
             text_color: map.get("text_color").and_then(|s| s.into_color()),
             icon_color: map.get("icon_color").and_then(|s| s.into_color()),
-            font_size: map.get("font_size").and_then(|s| s.into_float()),
-            icon_size: map.get("icon_size").and_then(|s| s.into_float()),
+            font_size: map
+                .get("text_size")
+                .or_else(|| map.get("font_size"))
+                .and_then(|s| s.into_size(scale)),
+            icon_size: map.get("icon_size").and_then(|s| s.into_size(scale)),
+            text_font: map.get("text_font").and_then(|s| s.into_font()),
+            orientation: map
+                .get("orientation")
+                .and_then(|s| s.into_orientation())
+                .or_else(|| map.get("rotate_text").and_then(rotate_text)),
+            stack_text: map
+                .get("stack_text")
+                .and_then(|s| s.as_ref().and_then(|s| s.parse().ok())),
+            min_width: map
+                .get("min_width")
+                .and_then(|s| s.into_float())
+                .scaled(scale),
+            content_align: map.get("content_align").and_then(|s| s.into_alignment()),
             text_margin: map
                 .get("text_margin")
-                .and_then(|s| s.into_insets().map(|i| i.into())),
+                .and_then(|s| s.into_insets().map(Padding::from))
+                .scaled(scale),
             icon_margin: map
                 .get("icon_margin")
-                .and_then(|s| s.into_insets().map(|i| i.into())),
-            spacing: map.get("spacing").and_then(|s| s.into_float()),
+                .and_then(|s| s.into_insets().map(Padding::from))
+                .scaled(scale),
+            spacing: map
+                .get("spacing")
+                .and_then(|s| s.into_float())
+                .scaled(scale),
             margin: map
                 .get("margin")
-                .and_then(|s| s.into_insets().map(|i| i.into())),
+                .and_then(|s| s.into_insets().map(Padding::from))
+                .scaled(scale),
             padding: map
                 .get("padding")
-                .and_then(|s| s.into_insets().map(|i| i.into())),
+                .and_then(|s| s.into_insets().map(Padding::from))
+                .scaled(scale),
             background: map.get("background").map(|s| s.into_background()),
-            border: {
-                let color = map.get("border_color").and_then(|s| s.into_color());
-                let width = map.get("border_width").and_then(|s| s.into_float());
-                let radius = map
-                    .get("border_radius")
-                    .and_then(|s| s.into_insets().map(|i| i.into()));
-                if color.is_some() || width.is_some() || radius.is_some() {
-                    Some(Border {
-                        color: color.unwrap_or_default(),
-                        width: width.unwrap_or_default(),
-                        radius: radius.unwrap_or_default(),
-                    })
-                } else {
-                    None
-                }
-            },
-            action: {
-                let left = map
-                    .get("on_click")
-                    .and_then(|s| s.as_ref().map(|s| s.into()));
-                let center = map
-                    .get("on_middle_click")
-                    .and_then(|s| s.as_ref().map(|s| s.into()));
-                let right = map
-                    .get("on_right_click")
-                    .and_then(|s| s.as_ref().map(|s| s.into()));
-                if left.is_some() || center.is_some() || right.is_some() {
-                    Some(OnClickAction {
-                        left,
-                        center,
-                        right,
-                    })
-                } else {
-                    None
-                }
-            },
+            hover_background: map.get("hover_background").map(|s| s.into_background()),
+            pressed_background: map.get("pressed_background").map(|s| s.into_background()),
+            border: read_border(|key| map.get(key).cloned().flatten())
+                .map(|border| border.scaled(scale)),
+            border_sides: BorderSides::read(|key| map.get(key).cloned().flatten())
+                .map(|border| border.scaled(scale)),
+            action: OnClickAction::read(|key| map.get(key).cloned().flatten()),
+            debounce: map.get("debounce").and_then(|s| s.into_duration()),
+            action_mode: map.get("action_mode").and_then(|s| s.into_action_mode()),
+            action_timeout: map.get("action_timeout").and_then(|s| s.into_duration()),
+            on_error: map.get("on_error").and_then(|s| s.into_on_error()),
+            keep_visible: map
+                .get("keep_visible")
+                .and_then(|s| s.into_bool())
+                .unwrap_or_default(),
+            visible_between: map.get("visible_between").and_then(|s| s.into_schedule()),
+            error_icon: map.get("error_icon").and_then(|s| s.clone()),
+            error_color: map.get("error_color").and_then(|s| s.into_color()),
+            text_icons: map
+                .iter()
+                .filter_map(|(key, text)| {
+                    key.strip_prefix("text_icon.")
+                        .zip(text.clone())
+                        .map(|(icon, text)| (icon.trim().to_string(), text))
+                })
+                .collect(),
         }
     }
 }
 
-impl From<&Ini> for ModuleConfig {
-    fn from(ini: &Ini) -> Self {
+impl From<(&Ini, f32)> for ModuleConfig {
+    fn from((ini, scale): (&Ini, f32)) -> Self {
         let global = Self::default().global;
         let local = Self::default().local;
         let section = "style";
         let module_section = "module_style";
-        ModuleConfig {
-            global: GlobalModuleConfig {
-                background_color: ini
-                    .get(section, "background")
-                    .into_color()
-                    .unwrap_or(global.background_color),
-                spacing: ini
-                    .get(section, "spacing")
-                    .into_thrice_float()
-                    .unwrap_or(global.spacing),
-                height: ini.get(section, "height").and_then(|v| v.parse().ok()),
-                width: ini.get(section, "width").and_then(|v| v.parse().ok()),
-                margin: ini
-                    .get(section, "margin")
-                    .into_insets()
-                    .map(|i| i.into())
-                    .unwrap_or(global.margin),
-                padding: ini
-                    .get(section, "padding")
-                    .into_insets()
-                    .map(|i| i.into())
-                    .unwrap_or(global.padding),
+        let global = GlobalModuleConfig {
+            background_color: ini
+                .get(section, "background")
+                .into_color()
+                .unwrap_or(global.background_color),
+            border: {
+                let get = |key| ini.get(section, key);
+                let color = border::color("border_color", get("border_color").as_deref())
+                    .unwrap_or(global.border.color);
+                let width = border::width("border_width", get("border_width").as_deref())
+                    .unwrap_or(global.border.width);
+                let radius = border::radius("corner_radius", get("corner_radius").as_deref())
+                    .unwrap_or(global.border.radius);
+                Border {
+                    color,
+                    width,
+                    radius,
+                }
+                .scaled(scale)
             },
+            dim_when_unfocused: ini
+                .get(section, "dim_when_unfocused")
+                .into_float()
+                .map(|opacity| opacity.clamp(0., 1.))
+                .unwrap_or(global.dim_when_unfocused),
+            spacing: ini
+                .get(section, "spacing")
+                .into_thrice_float()
+                .unwrap_or(global.spacing)
+                .scaled(scale),
+            height: ini
+                .get(section, "height")
+                .and_then(|v| v.parse::<u32>().ok())
+                .scaled(scale),
+            width: ini
+                .get(section, "width")
+                .and_then(|v| v.parse::<u32>().ok())
+                .scaled(scale),
+            margin: ini
+                .get(section, "margin")
+                .into_insets()
+                .map(|i| i.into())
+                .unwrap_or(global.margin)
+                .scaled(scale),
+            padding: ini
+                .get(section, "padding")
+                .into_insets()
+                .map(|i| i.into())
+                .unwrap_or(global.padding)
+                .scaled(scale),
+            separator: ini
+                .get(section, "separator")
+                .filter(|s| !s.is_empty())
+                .or(global.separator),
+            separator_color: ini
+                .get(section, "separator_color")
+                .into_color()
+                .unwrap_or(global.separator_color),
+            separator_size: ini
+                .get(section, "separator_size")
+                .into_float()
+                .or(global.separator_size)
+                .scaled(scale),
+        };
+        let bar = BarGeometry::new(
+            ini.get("general", "anchor")
+                .into_anchor()
+                .unwrap_or_default(),
+            &global,
+            scale,
+        );
+        // Sizes in percent refer to the bar, also the ones of modules read after this
+        geometry::set_thickness(bar.inner_thickness);
+        ModuleConfig {
+            global,
             local: LocalModuleConfig {
+                bar,
                 text_color: ini
                     .get(module_section, "text_color")
                     .into_color()
@@ -190,73 +395,138 @@ impl From<&Ini> for ModuleConfig {
                     .into_color()
                     .unwrap_or(local.icon_color),
                 font_size: ini
-                    .get(module_section, "font_size")
-                    .into_float()
-                    .unwrap_or(local.font_size),
+                    .get(module_section, "text_size")
+                    .or_else(|| ini.get(module_section, "font_size"))
+                    .into_size(scale)
+                    .unwrap_or(local.font_size.scaled(scale)),
                 icon_size: ini
                     .get(module_section, "icon_size")
+                    .into_size(scale)
+                    .unwrap_or(local.icon_size.scaled(scale)),
+                text_font: ini
+                    .get(module_section, "text_font")
+                    .into_font()
+                    .unwrap_or(local.text_font),
+                text_icons: ini
+                    .get("general", "text_icons")
+                    .into_bool()
+                    .unwrap_or(local.text_icons),
+                // Text icons are shown in the default font
+                icon_font: match ini.get("general", "text_icons").into_bool() {
+                    Some(true) => Font::DEFAULT,
+                    _ => ini
+                        .get("general", "icon_font")
+                        .into_font()
+                        .unwrap_or(local.icon_font),
+                },
+                orientation: ini
+                    .get(module_section, "orientation")
+                    .into_orientation()
+                    .or_else(|| rotate_text(&ini.get(module_section, "rotate_text")))
+                    .unwrap_or(local.orientation),
+                stack_text: ini
+                    .get(module_section, "stack_text")
+                    .and_then(|s| s.parse().ok())
+                    .unwrap_or(local.stack_text),
+                min_width: ini
+                    .get(module_section, "min_width")
                     .into_float()
-                    .unwrap_or(local.icon_size),
+                    .unwrap_or(local.min_width)
+                    .scaled(scale),
+                content_align: ini
+                    .get(module_section, "content_align")
+                    .into_alignment()
+                    .unwrap_or(local.content_align),
                 text_margin: ini
                     .get(module_section, "text_margin")
                     .into_insets()
                     .map(|i| i.into())
-                    .unwrap_or(local.text_margin),
+                    .unwrap_or(local.text_margin)
+                    .scaled(scale),
                 icon_margin: ini
                     .get(module_section, "icon_margin")
                     .into_insets()
                     .map(|i| i.into())
-                    .unwrap_or(local.icon_margin),
+                    .unwrap_or(local.icon_margin)
+                    .scaled(scale),
                 spacing: ini
                     .get(module_section, "spacing")
                     .into_float()
-                    .unwrap_or(local.spacing),
+                    .unwrap_or(local.spacing)
+                    .scaled(scale),
                 margin: ini
                     .get(module_section, "margin")
                     .into_insets()
                     .map(|i| i.into())
-                    .unwrap_or(local.margin),
+                    .unwrap_or(local.margin)
+                    .scaled(scale),
                 padding: ini
                     .get(module_section, "padding")
                     .into_insets()
                     .map(|i| i.into())
-                    .unwrap_or(local.padding),
+                    .unwrap_or(local.padding)
+                    .scaled(scale),
                 background: ini.get(module_section, "background").into_background(),
+                hover_background: ini
+                    .get(module_section, "hover_background")
+                    .into_background(),
+                pressed_background: ini
+                    .get(module_section, "pressed_background")
+                    .into_background(),
                 border: {
-                    let color = ini
-                        .get(module_section, "border_color")
-                        .into_color()
+                    let get = |key| ini.get(module_section, key);
+                    let color = border::color("border_color", get("border_color").as_deref())
                         .unwrap_or(local.border.color);
-                    let width = ini
-                        .get(module_section, "border_width")
-                        .into_float()
+                    let width = border::width("border_width", get("border_width").as_deref())
                         .unwrap_or(local.border.width);
-                    let radius = ini
-                        .get(module_section, "border_radius")
-                        .into_insets()
-                        .map(|i| i.into())
+                    let radius = border::radius("border_radius", get("border_radius").as_deref())
                         .unwrap_or(local.border.radius);
                     Border {
                         color,
                         width,
                         radius,
                     }
+                    .scaled(scale)
                 },
-                action: {
-                    let left = ini.get(module_section, "on_click").map(|s| (&s).into());
-                    let center = ini
-                        .get(module_section, "on_middle_click")
-                        .map(|s| (&s).into());
-                    let right = ini
-                        .get(module_section, "on_right_click")
-                        .map(|s| (&s).into());
-                    OnClickAction {
-                        left,
-                        center,
-                        right,
-                    }
-                },
+                border_sides: BorderSides::read(|key| ini.get(module_section, key))
+                    .unwrap_or(local.border_sides)
+                    .scaled(scale),
+                action: OnClickAction::read(|key| ini.get(module_section, key)).unwrap_or_default(),
+                debounce: ini
+                    .get(module_section, "debounce")
+                    .into_duration()
+                    .unwrap_or(local.debounce),
+                action_mode: ini
+                    .get(module_section, "action_mode")
+                    .into_action_mode()
+                    .unwrap_or(local.action_mode),
+                action_timeout: ini
+                    .get(module_section, "action_timeout")
+                    .into_duration()
+                    .or(local.action_timeout),
+                on_error: ini
+                    .get(module_section, "on_error")
+                    .into_on_error()
+                    .unwrap_or(local.on_error),
+                error_icon: ini
+                    .get(module_section, "error_icon")
+                    .unwrap_or(local.error_icon),
+                error_color: ini
+                    .get(module_section, "error_color")
+                    .into_color()
+                    .unwrap_or(local.error_color),
             },
         }
     }
 }
+
+/// `rotate_text`, which `orientation` replaced: a bool, or `auto` to leave it to the anchor
+fn rotate_text(value: &Option<String>) -> Option<Orientation> {
+    match value.as_deref() {
+        Some("auto") => Some(Orientation::Auto),
+        _ => value.into_bool().map(|rotate| match rotate {
+            true => Orientation::Vertical,
+            false => Orientation::Horizontal,
+        }),
+    }
+}
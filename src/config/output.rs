@@ -0,0 +1,30 @@
+use std::sync::{Mutex, OnceLock};
+
+/// The output passed with `--monitor`, which overrides `monitor` in `[general]`
+static MONITOR_ARG: OnceLock<String> = OnceLock::new();
+/// The name of the output the bar is opened on, once it's known
+static OUTPUT_NAME: Mutex<Option<String>> = Mutex::new(None);
+
+pub fn monitor_arg() -> Option<String> {
+    MONITOR_ARG.get().cloned()
+}
+
+pub fn set_monitor_arg(monitor: String) {
+    let _ = MONITOR_ARG.set(monitor);
+}
+
+/// Returns whether the name changed
+pub fn set_output_name(name: Option<String>) -> bool {
+    let mut output = OUTPUT_NAME.lock().unwrap();
+    let changed = *output != name;
+    *output = name;
+    changed
+}
+
+/// The output per-output sections refer to: the configured monitor, or the output the bar was
+/// opened on if none is configured
+pub fn output_name(monitor: Option<&String>) -> Option<String> {
+    monitor
+        .cloned()
+        .or_else(|| OUTPUT_NAME.lock().unwrap().clone())
+}
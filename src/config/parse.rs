@@ -1,27 +1,81 @@
 use configparser::ini::Ini;
+use std::{
+    collections::HashMap,
+    process::Command,
+    sync::{LazyLock, Mutex},
+    time::Duration,
+};
+
 use iced::{
-    platform_specific::shell::commands::layer_surface::KeyboardInteractivity, Background, Color,
+    platform_specific::shell::commands::layer_surface::KeyboardInteractivity, Alignment,
+    Background, Color, Font,
 };
 
-use crate::{registry::Registry, OptionExt};
+use crate::{
+    helpers::{status::OnError, supervisor::ActionMode},
+    registry::Registry,
+    OptionExt,
+};
 
-use super::{anchor::BarAnchor, insets::Insets, Config, Thrice};
+use super::{
+    anchor::{BarAnchor, Orientation},
+    geometry,
+    insets::Insets,
+    module_config::ModuleConfig,
+    output,
+    popup_config::PopupConfig,
+    profile,
+    scale::{Scale, ScaleFactor},
+    schedule::Schedule,
+    Config, Thrice,
+};
 
-impl From<(&Ini, &Registry)> for Config {
-    fn from((ini, registry): (&Ini, &Registry)) -> Self {
-        let enabled_modules = ini.into();
+impl From<(&Ini, &Registry, f32)> for Config {
+    fn from((ini, registry, scale_factor): (&Ini, &Registry, f32)) -> Self {
+        let monitor = output::monitor_arg().or_else(|| ini.get("general", "monitor"));
+        let enabled_modules = (ini, output::output_name(monitor.as_ref()).as_deref()).into();
+        registry.check_modules(&enabled_modules);
+        let module_config: ModuleConfig = (ini, scale_factor).into();
         let default = Self::default(registry);
         Self {
             hard_reload: ini
                 .get("general", "hard_reloading")
                 .into_bool()
                 .unwrap_or(default.hard_reload),
+            persist_state: ini
+                .get("general", "persist_state")
+                .into_bool()
+                .unwrap_or(default.persist_state),
+            auto_hide: ini
+                .get("general", "auto_hide")
+                .into_bool()
+                .unwrap_or(default.auto_hide),
+            hide_on_fullscreen: ini
+                .get("general", "hide_on_fullscreen")
+                .into_bool()
+                .unwrap_or(default.hide_on_fullscreen),
+            hide_delay: ini
+                .get("general", "hide_delay")
+                .into_duration()
+                .unwrap_or(default.hide_delay),
+            hide_duration: ini
+                .get("general", "hide_duration")
+                .into_duration()
+                .unwrap_or(default.hide_duration),
+            redraw_interval: ini
+                .get("general", "redraw_interval")
+                .into_duration()
+                .unwrap_or(default.redraw_interval),
+            long_press: ini
+                .get("general", "long_press")
+                .into_duration()
+                .unwrap_or(default.long_press),
             enabled_listeners: registry
                 .all_listeners()
                 .fold(vec![], |mut acc, (id, l)| {
                     l.config().into_iter().for_each(|option| {
                         if ini
-                            .get(&option.section, &option.name)
+                            .get(option.section, option.name)
                             .into_bool()
                             .unwrap_or(option.default)
                         {
@@ -31,20 +85,59 @@ impl From<(&Ini, &Registry)> for Config {
                     acc
                 })
                 .into_iter()
-                .chain(registry.enabled_listeners(&enabled_modules, &None))
                 .collect(),
             enabled_modules,
-            module_config: ini.into(),
-            popup_config: ini.into(),
+            popup_config: PopupConfig {
+                icon_font: module_config.local.icon_font,
+                text_icons: module_config.local.text_icons,
+                ..(ini, scale_factor).into()
+            },
+            module_config,
             anchor: ini
                 .get("general", "anchor")
                 .into_anchor()
                 .unwrap_or(default.anchor),
-            monitor: ini.get("general", "monitor"),
+            monitor,
             kb_focus: ini
                 .get("general", "kb_focus")
                 .into_kb_focus()
                 .unwrap_or(default.kb_focus),
+            namespace: ini
+                .get("general", "namespace")
+                .filter(|n| !n.is_empty())
+                .unwrap_or(default.namespace),
+            scale: ini
+                .get("general", "scale")
+                .into_scale()
+                .unwrap_or(default.scale),
+            scale_factor,
+            groups: ini
+                .get_map_ref()
+                .get("groups")
+                .map(|groups| {
+                    groups
+                        .iter()
+                        .map(|(name, modules)| {
+                            (
+                                name.clone(),
+                                modules
+                                    .iter()
+                                    .flat_map(|m| m.split(','))
+                                    .map(|m| m.trim().to_string())
+                                    .filter(|m| !m.is_empty())
+                                    .collect(),
+                            )
+                        })
+                        .collect()
+                })
+                .unwrap_or(default.groups),
+            profiles: profile::names(ini),
+            profile: None,
+            strict_config: ini
+                .get("general", "strict_config")
+                .into_bool()
+                .unwrap_or(default.strict_config),
+            problems: vec![],
         }
     }
 }
@@ -53,11 +146,20 @@ pub trait StringExt {
     fn into_bool(self) -> Option<bool>;
     fn into_color(self) -> Option<Color>;
     fn into_float(self) -> Option<f32>;
+    fn into_size(self, scale: f32) -> Option<f32>;
     fn into_thrice_float(self) -> Option<Thrice<f32>>;
     fn into_anchor(self) -> Option<BarAnchor>;
     fn into_insets(self) -> Option<Insets>;
     fn into_background(self) -> Option<Background>;
     fn into_kb_focus(self) -> Option<KeyboardInteractivity>;
+    fn into_scale(self) -> Option<ScaleFactor>;
+    fn into_font(self) -> Option<Font>;
+    fn into_on_error(self) -> Option<OnError>;
+    fn into_duration(self) -> Option<Duration>;
+    fn into_action_mode(self) -> Option<ActionMode>;
+    fn into_orientation(self) -> Option<Orientation>;
+    fn into_alignment(self) -> Option<Alignment>;
+    fn into_schedule(self) -> Option<Schedule>;
 }
 
 impl StringExt for &Option<String> {
@@ -69,16 +171,24 @@ impl StringExt for &Option<String> {
         })
     }
     fn into_color(self) -> Option<Color> {
-        self.as_ref().and_then(|color| {
-            csscolorparser::parse(color)
-                .map(|v| v.into_ext())
-                .ok()
-                .map_none(|| println!("Failed to parse color!"))
-        })
+        self.as_ref()
+            .and_then(|color| csscolorparser::parse(color).map(|v| v.into_ext()).ok())
     }
     fn into_float(self) -> Option<f32> {
         self.as_ref().and_then(|v| v.parse().ok())
     }
+    /// A size in pixels, which is scaled, or in percent of the thickness of the bar like `60%`
+    fn into_size(self, scale: f32) -> Option<f32> {
+        self.as_ref()
+            .and_then(|v| match v.trim().strip_suffix('%') {
+                Some(percent) => percent
+                    .trim()
+                    .parse::<f32>()
+                    .ok()
+                    .map(|percent| geometry::thickness() * percent / 100.),
+                None => v.trim().parse::<f32>().ok().map(|size| size.scaled(scale)),
+            })
+    }
     fn into_thrice_float(self) -> Option<Thrice<f32>> {
         self.as_ref().and_then(|value| {
             if let [left, center, right] = value.split_whitespace().collect::<Vec<&str>>()[..] {
@@ -132,6 +242,122 @@ impl StringExt for &Option<String> {
             _ => None,
         })
     }
+    fn into_scale(self) -> Option<ScaleFactor> {
+        self.as_ref().and_then(|v| match v.as_str() {
+            "auto" => Some(ScaleFactor::Auto),
+            v => v
+                .parse()
+                .ok()
+                .filter(|f: &f32| f.is_normal() && *f > 0.)
+                .map(ScaleFactor::Fixed)
+                .map_none(|| eprintln!("Failed to parse scale, expected `auto` or a float")),
+        })
+    }
+    fn into_font(self) -> Option<Font> {
+        self.as_ref().and_then(|family| {
+            // `fc-list` takes a while and the config is read before the bar opens, so every
+            // family is only looked up once
+            *FONTS
+                .lock()
+                .unwrap()
+                .entry(family.clone())
+                .or_insert_with(|| {
+                    font_installed(family)
+                        // iced refers to fonts by a static family name, so this leaks the
+                        // (short) name once per family
+                        .then(|| Font::with_name(Box::leak(family.clone().into_boxed_str())))
+                        .map_none(|| {
+                            eprintln!("Font `{family}` isn't installed, using the bundled font")
+                        })
+                })
+        })
+    }
+    fn into_on_error(self) -> Option<OnError> {
+        self.as_ref().and_then(|v| match v.as_str() {
+            "hide" => Some(OnError::Hide),
+            "show" => Some(OnError::Show),
+            "stale" => Some(OnError::Stale),
+            _ => {
+                eprintln!("Failed to parse on_error, expected `hide`, `show` or `stale`");
+                None
+            }
+        })
+    }
+    /// Time ranges like `09:00-17:00, 22:00-02:00`
+    fn into_schedule(self) -> Option<Schedule> {
+        self.as_ref().and_then(|v| {
+            v.parse()
+                .map_err(|e| eprintln!("Failed to parse schedule `{v}`: {e}"))
+                .ok()
+        })
+    }
+    /// Milliseconds, like `200` or `200ms`, or seconds like `1.5s`
+    fn into_duration(self) -> Option<Duration> {
+        self.as_ref().and_then(|v| {
+            let v = v.trim();
+            let duration = match v.strip_suffix("ms") {
+                Some(ms) => ms.trim().parse().ok().map(Duration::from_millis),
+                None => match v.strip_suffix('s') {
+                    Some(s) => s
+                        .trim()
+                        .parse()
+                        .ok()
+                        .and_then(|s| Duration::try_from_secs_f32(s).ok()),
+                    None => v.parse().ok().map(Duration::from_millis),
+                },
+            };
+            duration.map_none(|| eprintln!("Failed to parse duration `{v}`"))
+        })
+    }
+    fn into_action_mode(self) -> Option<ActionMode> {
+        self.as_ref().and_then(|v| match v.as_str() {
+            "spawn" => Some(ActionMode::Spawn),
+            "single" => Some(ActionMode::Single),
+            "queue" => Some(ActionMode::Queue),
+            _ => {
+                eprintln!("Failed to parse action_mode, expected `spawn`, `single` or `queue`");
+                None
+            }
+        })
+    }
+    fn into_orientation(self) -> Option<Orientation> {
+        self.as_ref().and_then(|v| match v.as_str() {
+            "auto" => Some(Orientation::Auto),
+            "horizontal" => Some(Orientation::Horizontal),
+            "vertical" => Some(Orientation::Vertical),
+            _ => {
+                eprintln!(
+                    "Failed to parse orientation, expected `auto`, `horizontal` or `vertical`"
+                );
+                None
+            }
+        })
+    }
+    fn into_alignment(self) -> Option<Alignment> {
+        self.as_ref().and_then(|v| match v.as_str() {
+            "start" | "left" | "top" => Some(Alignment::Start),
+            "center" => Some(Alignment::Center),
+            "end" | "right" | "bottom" => Some(Alignment::End),
+            _ => {
+                eprintln!("Failed to parse alignment, expected `start`, `center` or `end`");
+                None
+            }
+        })
+    }
+}
+
+/// The fonts which were looked up, `None` if they aren't installed
+static FONTS: LazyLock<Mutex<HashMap<String, Option<Font>>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// Whether fontconfig knows a font of this family. Assumes it's installed if `fc-list` is
+/// unavailable.
+fn font_installed(family: &str) -> bool {
+    Command::new("fc-list")
+        .arg(format!(":family={family}"))
+        .output()
+        .map(|output| !output.stdout.is_empty())
+        .unwrap_or(true)
 }
 
 pub trait IntoExt<T> {
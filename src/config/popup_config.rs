@@ -1,9 +1,16 @@
 use std::collections::HashMap;
 
 use configparser::ini::Ini;
-use iced::{Background, Border, Color, Padding};
+use iced::{Background, Border, Color, Font, Padding};
 
-use super::parse::StringExt;
+use crate::NERD_FONT;
+
+use super::{
+    border::{self, read_border, BorderSides},
+    parse::StringExt,
+    scale::Scale,
+    text_icons::text_icon,
+};
 
 #[derive(Debug)]
 pub struct PopupConfig {
@@ -16,11 +23,19 @@ pub struct PopupConfig {
     pub icon_color: Color,
     pub font_size: f32,
     pub icon_size: f32,
+    /// The same font as [LocalModuleConfig::icon_font](super::module_config::LocalModuleConfig)
+    pub icon_font: Font,
+    /// The same as [LocalModuleConfig::text_icons](super::module_config::LocalModuleConfig)
+    pub text_icons: bool,
     pub text_margin: Padding,
     pub icon_margin: Padding,
     pub spacing: f32,
     pub background: Background,
     pub border: Border,
+    /// Borders on single sides, drawn in addition to `border`
+    pub border_sides: BorderSides,
+    /// How many popups can be pinned at once, `0` disables pinning
+    pub max_pinned: usize,
 }
 
 impl Default for PopupConfig {
@@ -34,6 +49,8 @@ impl Default for PopupConfig {
             icon_color: Color::WHITE,
             font_size: 14.,
             icon_size: 24.,
+            icon_font: NERD_FONT,
+            text_icons: false,
             text_margin: Padding::default(),
             icon_margin: Padding::default(),
             spacing: 0.,
@@ -44,6 +61,18 @@ impl Default for PopupConfig {
                 a: 0.8,
             }),
             border: Border::default().rounded(8),
+            border_sides: BorderSides::default(),
+            max_pinned: 3,
+        }
+    }
+}
+
+impl PopupConfig {
+    /// The icon to show, replaced by its text fallback if `text_icons` is enabled
+    pub fn icon<'a>(&self, icon: &'a str) -> &'a str {
+        match self.text_icons {
+            true => text_icon(icon).unwrap_or(icon),
+            false => icon,
         }
     }
 }
@@ -63,21 +92,24 @@ pub struct PopupConfigOverride {
     pub spacing: Option<f32>,
     pub background: Option<Background>,
     pub border: Option<Border>,
+    pub border_sides: Option<BorderSides>,
 }
 
-impl From<&Ini> for PopupConfig {
-    fn from(ini: &Ini) -> Self {
+impl From<(&Ini, f32)> for PopupConfig {
+    fn from((ini, scale): (&Ini, f32)) -> Self {
         let default = Self::default();
         let section = "popup_style";
         Self {
             width: ini
                 .get(section, "width")
                 .and_then(|s| s.parse().ok())
-                .unwrap_or(default.width),
+                .unwrap_or(default.width)
+                .scaled(scale),
             height: ini
                 .get(section, "height")
                 .and_then(|s| s.parse().ok())
-                .unwrap_or(default.height),
+                .unwrap_or(default.height)
+                .scaled(scale),
             fill_content_to_size: ini
                 .get(section, "fill_content_to_size")
                 .into_bool()
@@ -86,7 +118,8 @@ impl From<&Ini> for PopupConfig {
                 .get(section, "padding")
                 .into_insets()
                 .map(|i| i.into())
-                .unwrap_or(default.padding),
+                .unwrap_or(default.padding)
+                .scaled(scale),
             text_color: ini
                 .get(section, "text_color")
                 .into_color()
@@ -98,100 +131,109 @@ impl From<&Ini> for PopupConfig {
             font_size: ini
                 .get(section, "font_size")
                 .into_float()
-                .unwrap_or(default.font_size),
+                .unwrap_or(default.font_size)
+                .scaled(scale),
             icon_size: ini
                 .get(section, "icon_size")
                 .into_float()
-                .unwrap_or(default.icon_size),
+                .unwrap_or(default.icon_size)
+                .scaled(scale),
+            icon_font: default.icon_font,
+            text_icons: default.text_icons,
             text_margin: ini
                 .get(section, "text_margin")
                 .into_insets()
                 .map(|i| i.into())
-                .unwrap_or(default.text_margin),
+                .unwrap_or(default.text_margin)
+                .scaled(scale),
             icon_margin: ini
                 .get(section, "icon_margin")
                 .into_insets()
                 .map(|i| i.into())
-                .unwrap_or(default.icon_margin),
+                .unwrap_or(default.icon_margin)
+                .scaled(scale),
             spacing: ini
                 .get(section, "spacing")
                 .into_float()
-                .unwrap_or(default.spacing),
+                .unwrap_or(default.spacing)
+                .scaled(scale),
             background: ini
                 .get(section, "background")
                 .into_background()
                 .unwrap_or(default.background),
             border: {
-                let color = ini
-                    .get(section, "border_color")
-                    .into_color()
+                let get = |key| ini.get(section, key);
+                let color = border::color("border_color", get("border_color").as_deref())
                     .unwrap_or(default.border.color);
-                let width = ini
-                    .get(section, "border_width")
-                    .into_float()
+                let width = border::width("border_width", get("border_width").as_deref())
                     .unwrap_or(default.border.width);
-                let radius = ini
-                    .get(section, "border_radius")
-                    .into_insets()
-                    .map(|i| i.into())
+                let radius = border::radius("border_radius", get("border_radius").as_deref())
                     .unwrap_or(default.border.radius);
                 Border {
                     color,
                     width,
                     radius,
                 }
+                .scaled(scale)
             },
+            border_sides: BorderSides::read(|key| ini.get(section, key))
+                .unwrap_or(default.border_sides)
+                .scaled(scale),
+            max_pinned: ini
+                .get(section, "max_pinned")
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(default.max_pinned),
         }
     }
 }
 
 impl PopupConfigOverride {
-    pub fn update(&mut self, config: &HashMap<String, Option<String>>) {
+    pub fn update(&mut self, config: &HashMap<String, Option<String>>, scale: f32) {
         if let Some(width) = config
             .get("width")
-            .and_then(|s| s.as_ref().and_then(|v| v.parse().ok()))
+            .and_then(|s| s.as_ref().and_then(|v| v.parse::<i32>().ok()))
         {
-            self.width = Some(width);
+            self.width = Some(width.scaled(scale));
         }
         if let Some(height) = config
             .get("height")
-            .and_then(|s| s.as_ref().and_then(|v| v.parse().ok()))
+            .and_then(|s| s.as_ref().and_then(|v| v.parse::<i32>().ok()))
         {
-            self.height = Some(height);
+            self.height = Some(height.scaled(scale));
         }
         self.fill_content_to_size = config
             .get("fill_content_to_size")
             .and_then(|s| s.into_bool());
         self.padding = config
             .get("padding")
-            .and_then(|s| s.into_insets().map(|i| i.into()));
+            .and_then(|s| s.into_insets().map(Padding::from))
+            .scaled(scale);
         self.text_color = config.get("text_color").and_then(|s| s.into_color());
         self.icon_color = config.get("icon_color").and_then(|s| s.into_color());
-        self.font_size = config.get("font_size").and_then(|s| s.into_float());
-        self.icon_size = config.get("icon_size").and_then(|s| s.into_float());
+        self.font_size = config
+            .get("font_size")
+            .and_then(|s| s.into_float())
+            .scaled(scale);
+        self.icon_size = config
+            .get("icon_size")
+            .and_then(|s| s.into_float())
+            .scaled(scale);
         self.text_margin = config
             .get("text_margin")
-            .and_then(|s| s.into_insets().map(|i| i.into()));
+            .and_then(|s| s.into_insets().map(Padding::from))
+            .scaled(scale);
         self.icon_margin = config
             .get("icon_margin")
-            .and_then(|s| s.into_insets().map(|i| i.into()));
-        self.spacing = config.get("spacing").and_then(|s| s.into_float());
+            .and_then(|s| s.into_insets().map(Padding::from))
+            .scaled(scale);
+        self.spacing = config
+            .get("spacing")
+            .and_then(|s| s.into_float())
+            .scaled(scale);
         self.background = config.get("background").and_then(|s| s.into_background());
-        self.border = {
-            let color = config.get("border_color").and_then(|s| s.into_color());
-            let width = config.get("border_width").and_then(|s| s.into_float());
-            let radius = config
-                .get("border_radius")
-                .and_then(|s| s.into_insets().map(|i| i.into()));
-            if color.is_some() || width.is_some() || radius.is_some() {
-                Some(Border {
-                    color: color.unwrap_or_default(),
-                    width: width.unwrap_or_default(),
-                    radius: radius.unwrap_or_default(),
-                })
-            } else {
-                None
-            }
-        };
+        self.border = read_border(|key| config.get(key).cloned().flatten())
+            .map(|border| border.scaled(scale));
+        self.border_sides = BorderSides::read(|key| config.get(key).cloned().flatten())
+            .map(|border| border.scaled(scale));
     }
 }
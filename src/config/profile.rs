@@ -0,0 +1,49 @@
+use std::sync::Mutex;
+
+use configparser::ini::Ini;
+
+use super::schema::STYLE;
+
+/// The profile switched to at runtime, which takes precedence over `default_profile`
+static ACTIVE: Mutex<Option<String>> = Mutex::new(None);
+
+pub fn set_active(name: &str) {
+    *ACTIVE.lock().unwrap() = Some(name.to_lowercase());
+}
+
+/// The names of the `[profile:{name}]` sections
+pub fn names(ini: &Ini) -> Vec<String> {
+    let mut names = ini
+        .sections()
+        .iter()
+        .filter_map(|section| section.strip_prefix("profile:"))
+        .map(|name| name.to_string())
+        .collect::<Vec<_>>();
+    names.sort();
+    names
+}
+
+/// Copies the options of the active profile into the sections they override: `left`, `center`
+/// and `right` into `[modules]`, the options of `[style]` into `[style]` and everything else
+/// into `[general]`. Returns the name of the profile, `None` if no profile is active.
+pub fn apply(ini: &mut Ini) -> Option<String> {
+    let name = ACTIVE
+        .lock()
+        .unwrap()
+        .clone()
+        .or_else(|| ini.get("general", "default_profile"))?
+        .to_lowercase();
+    let Some(options) = ini.get_map_ref().get(&format!("profile:{name}")).cloned() else {
+        eprintln!("No profile named `{name}` exists, add a [profile:{name}] section");
+        return None;
+    };
+    for (key, value) in options {
+        let section = match key.as_str() {
+            "left" | "center" | "right" => "modules",
+            key if STYLE.iter().any(|option| option.matches(key)) => "style",
+            _ => "general",
+        };
+        ini.set(section, &key, value);
+    }
+    Some(name)
+}
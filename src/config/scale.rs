@@ -0,0 +1,110 @@
+use iced::{
+    runtime::platform_specific::wayland::layer_surface::IcedMargin, Border, Padding, Radius,
+};
+
+use super::Thrice;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ScaleFactor {
+    Fixed(f32),
+    /// Follow the scale of the output
+    Auto,
+}
+
+impl Default for ScaleFactor {
+    fn default() -> Self {
+        Self::Fixed(1.)
+    }
+}
+
+impl ScaleFactor {
+    /// The factor for an output of the given scale, as reported by wl_output
+    pub fn resolve(&self, output_scale: f32) -> f32 {
+        match self {
+            ScaleFactor::Fixed(factor) => *factor,
+            ScaleFactor::Auto => output_scale,
+        }
+    }
+}
+
+/// Multiply a size by the resolved scale factor, see [ScaleFactor::resolve].
+pub trait Scale {
+    fn scaled(self, factor: f32) -> Self;
+}
+
+impl Scale for f32 {
+    fn scaled(self, factor: f32) -> Self {
+        self * factor
+    }
+}
+
+impl Scale for u32 {
+    fn scaled(self, factor: f32) -> Self {
+        (self as f32 * factor).round() as u32
+    }
+}
+
+impl Scale for i32 {
+    fn scaled(self, factor: f32) -> Self {
+        (self as f32 * factor).round() as i32
+    }
+}
+
+impl Scale for Padding {
+    fn scaled(self, factor: f32) -> Self {
+        Padding {
+            top: self.top.scaled(factor),
+            right: self.right.scaled(factor),
+            bottom: self.bottom.scaled(factor),
+            left: self.left.scaled(factor),
+        }
+    }
+}
+
+impl Scale for IcedMargin {
+    fn scaled(self, factor: f32) -> Self {
+        IcedMargin {
+            top: self.top.scaled(factor),
+            right: self.right.scaled(factor),
+            bottom: self.bottom.scaled(factor),
+            left: self.left.scaled(factor),
+        }
+    }
+}
+
+impl Scale for Radius {
+    fn scaled(self, factor: f32) -> Self {
+        Radius {
+            top_left: self.top_left.scaled(factor),
+            top_right: self.top_right.scaled(factor),
+            bottom_right: self.bottom_right.scaled(factor),
+            bottom_left: self.bottom_left.scaled(factor),
+        }
+    }
+}
+
+impl Scale for Border {
+    fn scaled(self, factor: f32) -> Self {
+        Border {
+            width: self.width.scaled(factor),
+            radius: self.radius.scaled(factor),
+            ..self
+        }
+    }
+}
+
+impl Scale for Thrice<f32> {
+    fn scaled(self, factor: f32) -> Self {
+        Thrice {
+            left: self.left.scaled(factor),
+            center: self.center.scaled(factor),
+            right: self.right.scaled(factor),
+        }
+    }
+}
+
+impl<T: Scale> Scale for Option<T> {
+    fn scaled(self, factor: f32) -> Self {
+        self.map(|value| value.scaled(factor))
+    }
+}
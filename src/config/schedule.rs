@@ -0,0 +1,109 @@
+use std::{cmp::Ordering, str::FromStr, time::Duration};
+
+use chrono::{NaiveTime, Timelike};
+use iced::{futures::SinkExt, stream, Subscription};
+use tokio::time::sleep;
+
+use crate::{helpers::clock, Message};
+
+/// The times of day a module is shown at, set with `visible_between`, like
+/// `09:00-17:00, 22:00-02:00`. A range may wrap around midnight, a range which ends where it
+/// starts lasts the whole day.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Schedule(Vec<(NaiveTime, NaiveTime)>);
+
+impl Schedule {
+    /// Whether `time` is within one of the ranges, which include their start but not their end
+    pub fn contains(&self, time: NaiveTime) -> bool {
+        self.0.iter().any(|(start, end)| match start.cmp(end) {
+            Ordering::Less => (*start..*end).contains(&time),
+            Ordering::Greater => time >= *start || time < *end,
+            Ordering::Equal => true,
+        })
+    }
+
+    /// Whether the current time is within the schedule, see [clock::now]
+    pub fn now(&self) -> bool {
+        self.contains(clock::now().time())
+    }
+}
+
+/// A time like `9`, `9:30` or `09:30`
+fn parse_time(time: &str) -> Result<NaiveTime, String> {
+    let time = time.trim();
+    let (hour, minute) = time.split_once(':').unwrap_or((time, "0"));
+    hour.trim()
+        .parse()
+        .ok()
+        .zip(minute.trim().parse().ok())
+        .and_then(|(hour, minute)| NaiveTime::from_hms_opt(hour, minute, 0))
+        .ok_or_else(|| format!("`{time}` is no time of day like `09:30`"))
+}
+
+impl FromStr for Schedule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.split(',')
+            .map(|range| {
+                let (start, end) = range
+                    .split_once('-')
+                    .ok_or_else(|| format!("`{}` is no range like `09:00-17:00`", range.trim()))?;
+                Ok((parse_time(start)?, parse_time(end)?))
+            })
+            .collect::<Result<Vec<_>, String>>()
+            .map(Schedule)
+    }
+}
+
+/// Redraws the bar at the start of every minute, so that modules with `visible_between` appear
+/// and disappear on time
+pub fn subscription() -> Subscription<Message> {
+    Subscription::run_with_id(
+        "schedule",
+        stream::channel(1, |mut sender| async move {
+            loop {
+                let now = chrono::Local::now();
+                let millis = now.timestamp_subsec_millis().min(999) as u64;
+                let wait = (60 - now.second().min(59) as u64) * 1000 - millis;
+                sleep(Duration::from_millis(wait)).await;
+                if sender.send(Message::update(|_| {})).await.is_err() {
+                    return;
+                }
+            }
+        }),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn time(hour: u32, minute: u32) -> NaiveTime {
+        NaiveTime::from_hms_opt(hour, minute, 0).unwrap()
+    }
+
+    #[test]
+    fn parses_ranges_and_wraps_around_midnight() {
+        let schedule: Schedule = "9-17:30, 22:00 - 02:00".parse().unwrap();
+        assert!(schedule.contains(time(9, 0)));
+        assert!(schedule.contains(time(17, 29)));
+        assert!(!schedule.contains(time(17, 30)));
+        assert!(!schedule.contains(time(8, 59)));
+        assert!(schedule.contains(time(23, 0)));
+        assert!(schedule.contains(time(1, 59)));
+        assert!(!schedule.contains(time(2, 0)));
+        assert!("0:00-0:00"
+            .parse::<Schedule>()
+            .unwrap()
+            .contains(time(12, 0)));
+    }
+
+    #[test]
+    fn rejects_invalid_ranges() {
+        assert!("9".parse::<Schedule>().is_err());
+        assert!("9-25".parse::<Schedule>().is_err());
+        assert!("9:60-17".parse::<Schedule>().is_err());
+        assert!("9-17,".parse::<Schedule>().is_err());
+    }
+}
@@ -0,0 +1,396 @@
+use std::{collections::BTreeMap, fmt::Display};
+
+use serde::Serialize;
+
+use crate::registry::Registry;
+
+use super::{parse::StringExt, schedule::Schedule};
+
+/// What an option accepts, the values in the config are checked against it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(tag = "type", content = "choices", rename_all = "snake_case")]
+pub enum Kind {
+    Bool,
+    /// A whole number
+    Integer,
+    Float,
+    /// A float in pixels, or a percentage of the thickness of the bar like `60%`
+    Size,
+    Color,
+    /// Like `500ms`, `1.5s` or `500` (milliseconds)
+    Duration,
+    /// One, two or four floats
+    Insets,
+    /// One float, or three for the left, center and right
+    Thrice,
+    /// Time ranges like `09:00-17:00, 22:00-02:00`
+    Schedule,
+    /// One of these words
+    Choice(&'static [&'static str]),
+    /// Anything, like a format, an icon or a command
+    Text,
+}
+
+impl Kind {
+    /// Whether `value` is valid for an option of this kind
+    pub fn accepts(&self, value: &str) -> bool {
+        let value = value.trim();
+        let floats = |counts: &[usize]| {
+            let values = value.split_whitespace().collect::<Vec<_>>();
+            counts.contains(&values.len()) && values.iter().all(|v| v.parse::<f32>().is_ok())
+        };
+        match self {
+            Kind::Bool => Some(value.to_string()).into_bool().is_some(),
+            Kind::Integer => value.parse::<i64>().is_ok(),
+            Kind::Float => value.parse::<f32>().is_ok(),
+            Kind::Size => value
+                .strip_suffix('%')
+                .unwrap_or(value)
+                .trim()
+                .parse::<f32>()
+                .is_ok(),
+            Kind::Color => csscolorparser::parse(value).is_ok(),
+            Kind::Duration => match value.strip_suffix("ms") {
+                Some(ms) => ms.trim().parse::<u64>().is_ok(),
+                None => match value.strip_suffix('s') {
+                    Some(s) => s.trim().parse::<f32>().is_ok_and(|s| s >= 0.),
+                    None => value.parse::<u64>().is_ok(),
+                },
+            },
+            Kind::Insets => floats(&[1, 2, 4]),
+            Kind::Thrice => floats(&[1, 3]),
+            Kind::Schedule => value.parse::<Schedule>().is_ok(),
+            Kind::Choice(choices) => choices.contains(&value),
+            Kind::Text => true,
+        }
+    }
+}
+
+impl Display for Kind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Kind::Bool => write!(f, "`true` or `false`"),
+            Kind::Integer => write!(f, "a whole number"),
+            Kind::Float => write!(f, "a number"),
+            Kind::Size => write!(f, "a number or a percentage"),
+            Kind::Color => write!(f, "a color"),
+            Kind::Duration => write!(f, "a duration like `500ms` or `2s`"),
+            Kind::Insets => write!(f, "1, 2 or 4 numbers"),
+            Kind::Thrice => write!(f, "1 or 3 numbers"),
+            Kind::Schedule => write!(f, "time ranges like `09:00-17:00`"),
+            Kind::Choice(choices) => write!(
+                f,
+                "one of {}",
+                choices
+                    .iter()
+                    .map(|choice| format!("`{choice}`"))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            Kind::Text => write!(f, "any text"),
+        }
+    }
+}
+
+/// An option of a config section
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct ConfigKey {
+    /// A `*` in the name stands for any text, like `icon.*` for `icon.muted`
+    pub name: &'static str,
+    #[serde(flatten)]
+    pub kind: Kind,
+    /// The default as it would be written in the config, `None` if the option isn't set by
+    /// default
+    pub default: Option<&'static str>,
+}
+
+impl ConfigKey {
+    pub const fn new(name: &'static str, kind: Kind, default: Option<&'static str>) -> Self {
+        Self {
+            name,
+            kind,
+            default,
+        }
+    }
+
+    pub fn matches(&self, key: &str) -> bool {
+        glob(self.name, key)
+    }
+}
+
+/// Whether `text` matches `pattern`, in which a `*` stands for any (possibly empty) text
+fn glob(pattern: &str, text: &str) -> bool {
+    match pattern.split_once('*') {
+        None => pattern == text,
+        Some((prefix, rest)) => text.strip_prefix(prefix).is_some_and(|text| {
+            text.char_indices()
+                .map(|(i, _)| i)
+                .chain([text.len()])
+                .any(|i| glob(rest, &text[i..]))
+        }),
+    }
+}
+
+const ORIENTATIONS: &[&str] = &["auto", "horizontal", "vertical"];
+const ALIGNMENTS: &[&str] = &["start", "center", "end", "left", "right", "top", "bottom"];
+const ACTION_MODES: &[&str] = &["spawn", "single", "queue"];
+const ON_ERROR: &[&str] = &["hide", "show", "stale"];
+
+/// The options of the `[general]` section, besides the ones of the listeners
+pub const GENERAL: &[ConfigKey] = &[
+    ConfigKey::new("monitor", Kind::Text, None),
+    ConfigKey::new("hard_reloading", Kind::Bool, Some("false")),
+    ConfigKey::new("persist_state", Kind::Bool, Some("false")),
+    ConfigKey::new("default_profile", Kind::Text, None),
+    ConfigKey::new("auto_hide", Kind::Bool, Some("false")),
+    ConfigKey::new("hide_delay", Kind::Duration, Some("1s")),
+    ConfigKey::new("hide_duration", Kind::Duration, Some("200ms")),
+    ConfigKey::new("redraw_interval", Kind::Duration, Some("16ms")),
+    ConfigKey::new("long_press", Kind::Duration, Some("500ms")),
+    ConfigKey::new(
+        "anchor",
+        Kind::Choice(&["top", "bottom", "left", "right"]),
+        Some("top"),
+    ),
+    ConfigKey::new(
+        "kb_focus",
+        Kind::Choice(&["none", "on_demand", "exclusive"]),
+        Some("none"),
+    ),
+    ConfigKey::new("namespace", Kind::Text, Some("bar-rs")),
+    // A float or `auto`
+    ConfigKey::new("scale", Kind::Text, Some("1")),
+    ConfigKey::new("icon_font", Kind::Text, Some("3270 Nerd Font")),
+    ConfigKey::new("text_icons", Kind::Bool, Some("false")),
+    ConfigKey::new("strict_config", Kind::Bool, Some("false")),
+];
+
+/// The options of the `[style]` section
+pub const STYLE: &[ConfigKey] = &[
+    ConfigKey::new("background", Kind::Color, Some("rgba(0, 0, 0, 0.5)")),
+    ConfigKey::new("corner_radius", Kind::Insets, Some("0")),
+    ConfigKey::new("border_color", Kind::Color, None),
+    ConfigKey::new("border_width", Kind::Float, Some("1")),
+    ConfigKey::new("dim_when_unfocused", Kind::Float, Some("1")),
+    ConfigKey::new("spacing", Kind::Thrice, Some("20 10 15")),
+    ConfigKey::new("height", Kind::Integer, None),
+    ConfigKey::new("width", Kind::Integer, None),
+    ConfigKey::new("margin", Kind::Insets, Some("0")),
+    ConfigKey::new("padding", Kind::Insets, Some("0")),
+    ConfigKey::new("separator", Kind::Text, None),
+    ConfigKey::new(
+        "separator_color",
+        Kind::Color,
+        Some("rgba(255, 255, 255, 0.5)"),
+    ),
+    ConfigKey::new("separator_size", Kind::Float, None),
+];
+
+/// The options of the `[module_style]` section, which every module section can override
+pub const MODULE_STYLE: &[ConfigKey] = &[
+    ConfigKey::new("text_color", Kind::Color, Some("white")),
+    ConfigKey::new("icon_color", Kind::Color, Some("white")),
+    ConfigKey::new("text_size", Kind::Size, Some("16")),
+    ConfigKey::new("font_size", Kind::Size, None),
+    ConfigKey::new("icon_size", Kind::Size, Some("20")),
+    ConfigKey::new("text_font", Kind::Text, None),
+    ConfigKey::new("orientation", Kind::Choice(ORIENTATIONS), Some("auto")),
+    ConfigKey::new("stack_text", Kind::Integer, Some("5")),
+    ConfigKey::new("rotate_text", Kind::Bool, None),
+    ConfigKey::new("min_width", Kind::Float, Some("0")),
+    ConfigKey::new("content_align", Kind::Choice(ALIGNMENTS), Some("center")),
+    ConfigKey::new("text_margin", Kind::Insets, Some("0")),
+    ConfigKey::new("icon_margin", Kind::Insets, Some("0")),
+    ConfigKey::new("spacing", Kind::Float, Some("10")),
+    ConfigKey::new("margin", Kind::Insets, Some("0")),
+    ConfigKey::new("padding", Kind::Insets, Some("0")),
+    ConfigKey::new("background", Kind::Color, None),
+    ConfigKey::new("hover_background", Kind::Color, None),
+    ConfigKey::new("pressed_background", Kind::Color, None),
+    ConfigKey::new("border_color", Kind::Color, None),
+    ConfigKey::new("border_width", Kind::Float, Some("1")),
+    ConfigKey::new("border_radius", Kind::Insets, Some("0")),
+    ConfigKey::new("border_top_width", Kind::Float, Some("0")),
+    ConfigKey::new("border_right_width", Kind::Float, Some("0")),
+    ConfigKey::new("border_bottom_width", Kind::Float, Some("0")),
+    ConfigKey::new("border_left_width", Kind::Float, Some("0")),
+    ConfigKey::new("border_top_color", Kind::Color, None),
+    ConfigKey::new("border_right_color", Kind::Color, None),
+    ConfigKey::new("border_bottom_color", Kind::Color, None),
+    ConfigKey::new("border_left_color", Kind::Color, None),
+    ConfigKey::new("on_click", Kind::Text, None),
+    ConfigKey::new("on_middle_click", Kind::Text, None),
+    ConfigKey::new("on_right_click", Kind::Text, None),
+    ConfigKey::new("on_click_left", Kind::Text, None),
+    ConfigKey::new("on_click_middle", Kind::Text, None),
+    ConfigKey::new("on_click_right", Kind::Text, None),
+    ConfigKey::new("on_scroll_up", Kind::Text, None),
+    ConfigKey::new("on_scroll_down", Kind::Text, None),
+    ConfigKey::new("debounce", Kind::Duration, Some("300ms")),
+    ConfigKey::new("action_mode", Kind::Choice(ACTION_MODES), Some("spawn")),
+    ConfigKey::new("action_timeout", Kind::Duration, None),
+    ConfigKey::new("on_error", Kind::Choice(ON_ERROR), Some("hide")),
+    ConfigKey::new("error_icon", Kind::Text, Some("󰀦")),
+    ConfigKey::new("error_color", Kind::Color, Some("rgb(230, 76, 76)")),
+];
+
+/// The options every `[module:{{name}}]` section accepts besides the [MODULE_STYLE] and the
+/// options of the module
+pub const MODULE: &[ConfigKey] = &[
+    ConfigKey::new("outputs", Kind::Text, None),
+    ConfigKey::new("keep_visible", Kind::Bool, Some("false")),
+    ConfigKey::new("visible_between", Kind::Schedule, None),
+    ConfigKey::new("text_icon.*", Kind::Text, None),
+];
+
+/// The options of the `[popup_style]` section, which every popup section can override
+pub const POPUP_STYLE: &[ConfigKey] = &[
+    ConfigKey::new("width", Kind::Integer, Some("300")),
+    ConfigKey::new("height", Kind::Integer, Some("300")),
+    ConfigKey::new("fill_content_to_size", Kind::Bool, Some("false")),
+    ConfigKey::new("padding", Kind::Insets, Some("10 20")),
+    ConfigKey::new("text_color", Kind::Color, Some("white")),
+    ConfigKey::new("icon_color", Kind::Color, Some("white")),
+    ConfigKey::new("font_size", Kind::Float, Some("14")),
+    ConfigKey::new("icon_size", Kind::Float, Some("24")),
+    ConfigKey::new("text_margin", Kind::Insets, Some("0")),
+    ConfigKey::new("icon_margin", Kind::Insets, Some("0")),
+    ConfigKey::new("spacing", Kind::Float, Some("0")),
+    ConfigKey::new("background", Kind::Color, Some("rgba(0, 0, 0, 0.8)")),
+    ConfigKey::new("border_color", Kind::Color, None),
+    ConfigKey::new("border_width", Kind::Float, Some("0")),
+    ConfigKey::new("border_radius", Kind::Insets, Some("8")),
+    ConfigKey::new("border_top_width", Kind::Float, Some("0")),
+    ConfigKey::new("border_right_width", Kind::Float, Some("0")),
+    ConfigKey::new("border_bottom_width", Kind::Float, Some("0")),
+    ConfigKey::new("border_left_width", Kind::Float, Some("0")),
+    ConfigKey::new("border_top_color", Kind::Color, None),
+    ConfigKey::new("border_right_color", Kind::Color, None),
+    ConfigKey::new("border_bottom_color", Kind::Color, None),
+    ConfigKey::new("border_left_color", Kind::Color, None),
+    ConfigKey::new("max_pinned", Kind::Integer, Some("3")),
+];
+
+/// The options of the `[modules]` and `[modules:{{output}}]` sections
+pub const MODULES: &[ConfigKey] = &[
+    ConfigKey::new("left", Kind::Text, None),
+    ConfigKey::new("center", Kind::Text, None),
+    ConfigKey::new("right", Kind::Text, None),
+];
+
+/// The options of the `[general]` section, including the ones enabling listeners
+pub fn general_keys(registry: &Registry) -> Vec<ConfigKey> {
+    GENERAL
+        .iter()
+        .copied()
+        .chain(
+            registry
+                .all_listeners()
+                .flat_map(|(_, l)| l.config())
+                .filter(|option| option.section == "general")
+                .map(|option| {
+                    let default = match option.default {
+                        true => "true",
+                        false => "false",
+                    };
+                    ConfigKey::new(option.name, Kind::Bool, Some(default))
+                }),
+        )
+        .collect()
+}
+
+/// Everything `bar-rs --dump-schema` prints
+#[derive(Debug, Serialize)]
+pub struct Schema {
+    /// The options of the sections which aren't tied to a module. The ones of `module` are
+    /// accepted by every `[module:{{name}}]` section.
+    sections: BTreeMap<&'static str, Vec<ConfigKey>>,
+    modules: BTreeMap<String, ModuleSchema>,
+}
+
+/// The options of the `[module:{{name}}]` and `[module_popup:{{name}}]` sections of a module
+#[derive(Debug, Serialize)]
+struct ModuleSchema {
+    keys: Vec<ConfigKey>,
+    popup_keys: Vec<ConfigKey>,
+}
+
+impl Schema {
+    /// The options of every section and registered module
+    pub fn new(registry: &Registry) -> Self {
+        let sections = BTreeMap::from([
+            ("general", general_keys(registry)),
+            ("style", STYLE.to_vec()),
+            ("module_style", MODULE_STYLE.to_vec()),
+            ("module", MODULE.to_vec()),
+            ("popup_style", POPUP_STYLE.to_vec()),
+            ("modules", MODULES.to_vec()),
+        ]);
+        let modules = registry
+            .registered_modules()
+            .map(|(name, module)| {
+                (
+                    name.clone(),
+                    ModuleSchema {
+                        keys: module.known_keys(),
+                        popup_keys: module.known_popup_keys(),
+                    },
+                )
+            })
+            .collect();
+        Self { sections, modules }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wildcards_match_any_text() {
+        let key = |name| ConfigKey::new(name, Kind::Text, None);
+        assert!(key("icon.*").matches("icon.muted"));
+        assert!(!key("icon.*").matches("icon_color"));
+        assert!(key("*:*").matches("dp-1:3"));
+        assert!(!key("*:*").matches("format"));
+        assert!(key("(*)").matches("(0, 1)"));
+        assert!(key("format").matches("format"));
+        assert!(!key("format").matches("format_alt"));
+    }
+
+    #[test]
+    fn checks_values_by_kind() {
+        assert!(Kind::Bool.accepts("yes"));
+        assert!(!Kind::Bool.accepts("maybe"));
+        assert!(Kind::Size.accepts("60%"));
+        assert!(!Kind::Size.accepts("20px"));
+        assert!(Kind::Color.accepts("rgba(0, 0, 0, 0.5)"));
+        assert!(!Kind::Color.accepts("blurple"));
+        assert!(Kind::Duration.accepts("1.5s"));
+        assert!(Kind::Duration.accepts("200"));
+        assert!(!Kind::Duration.accepts("1.5ms"));
+        assert!(Kind::Insets.accepts("0 10"));
+        assert!(!Kind::Insets.accepts("0 10 5"));
+        assert!(Kind::Choice(&["top", "bottom"]).accepts("bottom"));
+        assert!(!Kind::Choice(&["top", "bottom"]).accepts("middle"));
+    }
+
+    #[test]
+    fn serializes_keys_with_their_kind() {
+        let key = ConfigKey::new("anchor", Kind::Choice(&["top", "bottom"]), Some("top"));
+        assert_eq!(
+            serde_json::to_value(key).unwrap(),
+            serde_json::json!({
+                "name": "anchor",
+                "type": "choice",
+                "choices": ["top", "bottom"],
+                "default": "top"
+            })
+        );
+        let key = ConfigKey::new("height", Kind::Integer, None);
+        assert_eq!(
+            serde_json::to_value(key).unwrap(),
+            serde_json::json!({ "name": "height", "type": "integer", "default": null })
+        );
+    }
+}
@@ -0,0 +1,73 @@
+/// Text fallbacks for the Nerd Font glyphs bar-rs uses by default, shown if `text_icons` is
+/// enabled in `[general]`.
+pub fn text_icon(glyph: &str) -> Option<&'static str> {
+    Some(match glyph.trim() {
+        // battery
+        "󱊣" | "󱊢" | "󱊡" => "🔋",
+        "󰂎" => "🪫",
+        "󱊦" | "󱊥" | "󱊤" | "󰢟" => "⚡",
+        // bluetooth devices
+        "󰓃" => "🔊",
+        "󰋋" => "🎧",
+        "󰂀" => "🔋",
+        "󰻛" => "📷",
+        "󰌌" => "⌨",
+        "󰍽" => "🖱",
+        "󰊴" => "🎮",
+        "󰏲" => "📱",
+        // caffeine
+        "󰅶" => "☕",
+        "󰛊" => "💤",
+        // cpu, memory and disk usage
+        "󰻠" => "CPU",
+        "󰍛" => "RAM",
+        "󰦚" => "💾",
+        // cpu frequency
+        "󰾅" => "⏱",
+        // date
+        "\u{f073}" => "📅",
+        "\u{f053}" => "‹",
+        "\u{f054}" => "›",
+        // do not disturb
+        "󰂛" => "🔕",
+        "󰂚" => "🔔",
+        // docker
+        "󰡨" => "🐳",
+        // errors
+        "󰀦" => "⚠",
+        // focus
+        "󰈈" => "👁",
+        "󰈉" => "🙈",
+        // lock keys
+        "󰘲" => "CAPS",
+        "󰎠" => "NUM",
+        // mail
+        "󰇮" => "📬",
+        "󰇰" => "📭",
+        // media
+        "" => "🎵",
+        "󰒮" => "⏮",
+        "" => "▶",
+        "" => "⏸",
+        "󰒭" => "⏭",
+        // niri workspaces
+        "" => "○",
+        "" => "●",
+        // power profiles
+        "󰌪" => "🍃",
+        "󰗑" => "⚖",
+        "󰓅" => "🚀",
+        // recording
+        "󰑊" => "⏺",
+        // systemd
+        "󰒓" => "⚙",
+        // uptime
+        "󰔟" => "⏳",
+        // volume
+        "󰖁" => "🔇",
+        "󰕾" => "🔊",
+        "󰖀" => "🔉",
+        "󰕿" => "🔈",
+        _ => return None,
+    })
+}
@@ -0,0 +1,77 @@
+use iced::Color;
+
+use super::parse::IntoExt;
+
+/// Colors for ranges of a percentage, like `thresholds = 0 #a6e3a1; 60 #f9e2af; 85 #f38ba8`.
+/// Each color is used from its value up to the next one, values below the first keep the color
+/// of the module.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Thresholds(Vec<(f32, Color)>);
+
+impl Thresholds {
+    /// Reads the `value color` pairs separated by `;`, invalid pairs are reported and skipped
+    pub fn read(value: Option<&str>) -> Self {
+        let mut thresholds = value
+            .into_iter()
+            .flat_map(|value| value.split(';'))
+            .map(str::trim)
+            .filter(|pair| !pair.is_empty())
+            .filter_map(|pair| {
+                let parsed = pair
+                    .split_once(char::is_whitespace)
+                    .and_then(|(value, color)| {
+                        Some((
+                            value.trim_end_matches('%').parse::<f32>().ok()?,
+                            csscolorparser::parse(color.trim()).ok()?.into_ext(),
+                        ))
+                    });
+                if parsed.is_none() {
+                    eprintln!("Failed to parse threshold `{pair}`, expected a value and a color");
+                }
+                parsed
+            })
+            .collect::<Vec<_>>();
+        thresholds.sort_by(|(a, _), (b, _)| a.total_cmp(b));
+        Self(thresholds)
+    }
+
+    /// The color of the highest threshold `percent` reached, `None` if it's below all of them
+    pub fn color(&self, percent: f32) -> Option<Color> {
+        self.0
+            .iter()
+            .rev()
+            .find(|(value, _)| percent >= *value)
+            .map(|(_, color)| *color)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GREEN: Color = Color::from_rgb(0., 1., 0.);
+    const RED: Color = Color::from_rgb(1., 0., 0.);
+
+    #[test]
+    fn picks_the_highest_reached_threshold() {
+        let thresholds = Thresholds::read(Some("80 #ff0000; 0 #00ff00; 50 rgb(0, 0, 255)"));
+        assert_eq!(thresholds.color(0.), Some(GREEN));
+        assert_eq!(thresholds.color(49.9), Some(GREEN));
+        assert_eq!(thresholds.color(50.), Some(Color::from_rgb(0., 0., 1.)));
+        assert_eq!(thresholds.color(100.), Some(RED));
+    }
+
+    #[test]
+    fn below_all_thresholds_keeps_the_module_color() {
+        let thresholds = Thresholds::read(Some("20% #ff0000"));
+        assert_eq!(thresholds.color(10.), None);
+        assert_eq!(thresholds.color(20.), Some(RED));
+        assert_eq!(Thresholds::read(None).color(50.), None);
+    }
+
+    #[test]
+    fn invalid_pairs_are_skipped() {
+        let thresholds = Thresholds::read(Some("abc #ff0000; 10; 30 nocolor; 60 #00ff00;"));
+        assert_eq!(thresholds, Thresholds(vec![(60., GREEN)]));
+    }
+}
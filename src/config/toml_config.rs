@@ -0,0 +1,155 @@
+use std::{fs, path::Path};
+
+use configparser::ini::Ini;
+use toml::{Table, Value};
+
+/// Tables holding one section per name, like `[module.volume]` for `[module:volume]`
+const NAMESPACES: &[&str] = &["module", "module_popup", "modules", "profile"];
+
+/// Reads a TOML config into the same sections and options as the ini format, so modules don't
+/// notice the difference
+pub fn load(path: &Path, ini: &mut Ini) -> Result<(), String> {
+    let text = fs::read_to_string(path).map_err(|e| e.to_string())?;
+    read(&text, ini)
+}
+
+/// Tables within the [NAMESPACES] become sections like `module:volume`, other nested tables
+/// become dotted options like `icon.muted`. Options outside of any table end up in the
+/// `default` section, like they do in the ini format.
+fn read(text: &str, ini: &mut Ini) -> Result<(), String> {
+    let config = text.parse::<Table>().map_err(|e| e.to_string())?;
+    for (name, value) in config {
+        let Value::Table(options) = value else {
+            set_option(ini, "default", &name, value)?;
+            continue;
+        };
+        for (key, value) in options {
+            match value {
+                Value::Table(section) if NAMESPACES.contains(&name.as_str()) => {
+                    let section_name = format!("{name}:{key}");
+                    for (key, value) in section {
+                        set_option(ini, &section_name, &key, value)?;
+                    }
+                }
+                value => set_option(ini, &name, &key, value)?,
+            }
+        }
+    }
+    Ok(())
+}
+
+fn set_option(ini: &mut Ini, section: &str, key: &str, value: Value) -> Result<(), String> {
+    match value {
+        Value::Table(table) => {
+            for (name, value) in table {
+                set_option(ini, section, &format!("{key}.{name}"), value)?;
+            }
+        }
+        value => {
+            let value = to_string(value)
+                .ok_or_else(|| format!("Unsupported value for `{key}` in [{section}]"))?;
+            ini.set(section, key, Some(value));
+        }
+    }
+    Ok(())
+}
+
+/// The value as it would be written in the ini format. Arrays of numbers, like insets, are
+/// separated by spaces and other arrays, like lists of modules, by commas.
+fn to_string(value: Value) -> Option<String> {
+    match value {
+        Value::String(value) => Some(value),
+        Value::Integer(value) => Some(value.to_string()),
+        Value::Float(value) => Some(value.to_string()),
+        Value::Boolean(value) => Some(value.to_string()),
+        Value::Datetime(value) => Some(value.to_string()),
+        Value::Array(values) => {
+            let separator = match values.iter().all(|v| v.is_integer() || v.is_float()) {
+                true => " ",
+                false => ", ",
+            };
+            values
+                .into_iter()
+                .map(to_string)
+                .collect::<Option<Vec<_>>>()
+                .map(|values| values.join(separator))
+        }
+        Value::Table(_) => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(text: &str) -> Ini {
+        let mut ini = Ini::new();
+        read(text, &mut ini).unwrap();
+        ini
+    }
+
+    #[test]
+    fn tables_become_sections() {
+        let ini = parse(
+            r#"
+            [general]
+            hot_reloading = false
+            hide_delay = "800ms"
+
+            [style]
+            padding = [0, 10.5]
+
+            [modules]
+            left = ["hyprland.workspaces", "hyprland.window"]
+
+            [modules.DP-1]
+            right = ["date", "time"]
+            "#,
+        );
+        assert_eq!(
+            ini.get("general", "hot_reloading").as_deref(),
+            Some("false")
+        );
+        assert_eq!(ini.get("general", "hide_delay").as_deref(), Some("800ms"));
+        assert_eq!(ini.get("style", "padding").as_deref(), Some("0 10.5"));
+        assert_eq!(
+            ini.get("modules", "left").as_deref(),
+            Some("hyprland.workspaces, hyprland.window")
+        );
+        assert_eq!(
+            ini.get("modules:dp-1", "right").as_deref(),
+            Some("date, time")
+        );
+    }
+
+    #[test]
+    fn nested_tables_become_dotted_options() {
+        let ini = parse(
+            r#"
+            [module."disk_usage.home"]
+            path = "/home"
+
+            [module.volume]
+            icon = { muted = "M", high = "H" }
+            scroll_step = 2
+            "#,
+        );
+        assert_eq!(
+            ini.get("module:disk_usage.home", "path").as_deref(),
+            Some("/home")
+        );
+        assert_eq!(ini.get("module:volume", "icon.muted").as_deref(), Some("M"));
+        assert_eq!(ini.get("module:volume", "icon.high").as_deref(), Some("H"));
+        assert_eq!(
+            ini.get("module:volume", "scroll_step").as_deref(),
+            Some("2")
+        );
+    }
+
+    #[test]
+    fn rejects_unsupported_values() {
+        let mut ini = Ini::new();
+        assert!(read("[style]\nmargin = [{ top = 1 }]", &mut ini).is_err());
+        assert!(read("[style\n", &mut ini).is_err());
+    }
+}
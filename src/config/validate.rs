@@ -0,0 +1,163 @@
+use std::sync::Mutex;
+
+use configparser::ini::Ini;
+
+use crate::registry::Registry;
+
+use super::schema::{general_keys, ConfigKey, MODULE, MODULES, MODULE_STYLE, POPUP_STYLE, STYLE};
+
+/// The problems which were printed last, the config is read more than once at startup
+static REPORTED: Mutex<Vec<String>> = Mutex::new(vec![]);
+
+/// Checks every section of the config against the options bar-rs knows. Returns a message for
+/// every unknown section, unknown option and value which can't be parsed.
+pub fn validate(ini: &Ini, registry: &Registry) -> Vec<String> {
+    let general = general_keys(registry);
+
+    let mut problems = vec![];
+    let mut sections = ini.get_map_ref().iter().collect::<Vec<_>>();
+    sections.sort_by_key(|(section, _)| *section);
+    for (section, options) in sections {
+        let known = match section.as_str() {
+            "general" => general.clone(),
+            "style" => STYLE.to_vec(),
+            "module_style" => MODULE_STYLE.to_vec(),
+            "popup_style" => POPUP_STYLE.to_vec(),
+            "modules" => MODULES.to_vec(),
+            s if s.starts_with("modules:") => MODULES.to_vec(),
+            s if s.starts_with("profile:") => [STYLE, &general[..], MODULES].concat(),
+            // Options before the first section header
+            "default" => vec![],
+            "groups" => continue,
+            _ => match module_keys(section, registry) {
+                Ok(known) => known,
+                Err(problem) => {
+                    problems.push(problem);
+                    continue;
+                }
+            },
+        };
+        let mut options = options.iter().collect::<Vec<_>>();
+        options.sort_by_key(|(key, _)| *key);
+        for (key, value) in options {
+            match known.iter().find(|option| option.matches(key)) {
+                Some(option) => {
+                    if let Some(value) = value.as_ref().filter(|v| !option.kind.accepts(v)) {
+                        problems.push(format!(
+                            "invalid value `{value}` for `{key}` in [{section}], expected {}",
+                            option.kind
+                        ));
+                    }
+                }
+                None => problems.push(
+                    match suggestion(key, known.iter().map(|option| option.name)) {
+                        Some(name) => {
+                            format!("unknown key `{key}` in [{section}], did you mean `{name}`?")
+                        }
+                        None => format!("unknown key `{key}` in [{section}]"),
+                    },
+                ),
+            }
+        }
+    }
+    problems
+}
+
+/// The options of a `[module:{{name}}]` or `[module_popup:{{name}}]` section, the ones of the
+/// module first so they take precedence over the style options
+fn module_keys(section: &str, registry: &Registry) -> Result<Vec<ConfigKey>, String> {
+    let (popup, name) = match section.split_once(':') {
+        Some(("module", name)) => (false, name),
+        Some(("module_popup", name)) => (true, name),
+        _ => return Err(format!("unknown section [{section}]")),
+    };
+    // Instances like `disk_usage.home` read the same options as their module
+    let module = registry
+        .type_of(name)
+        .and_then(|id| registry.module_name(id))
+        .and_then(|name| registry.get_instance(name))
+        .ok_or_else(|| format!("[{section}]: no module named `{name}` is registered"))?;
+    Ok(match popup {
+        true => [&module.known_popup_keys()[..], POPUP_STYLE].concat(),
+        false => [&module.known_keys()[..], MODULE_STYLE, MODULE].concat(),
+    })
+}
+
+/// Prints the problems, as errors if `strict_config` is set. Nothing is printed if they are the
+/// same as last time.
+pub fn report(problems: &[String], strict: bool) {
+    let mut reported = REPORTED.lock().unwrap();
+    if *reported == problems {
+        return;
+    }
+    let level = match strict {
+        true => "error",
+        false => "warning",
+    };
+    for problem in problems {
+        eprintln!("{level}: {problem}");
+    }
+    *reported = problems.to_vec();
+}
+
+/// The known option closest to `key`, if it's close enough to be a typo
+fn suggestion<'a>(key: &str, known: impl Iterator<Item = &'a str>) -> Option<&'a str> {
+    let max_distance = (key.chars().count() / 3).max(1);
+    known
+        .filter(|name| !name.contains('*'))
+        .map(|name| (levenshtein(key, name), name))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, name)| name)
+}
+
+/// The number of characters which have to be inserted, deleted or replaced to turn `a` into `b`
+fn levenshtein(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    // The distances of the part of `a` handled so far to every prefix of `b`
+    let mut row = (0..=b.len()).collect::<Vec<_>>();
+    for (i, a) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, b) in b.iter().enumerate() {
+            let replace = diagonal + (a != *b) as usize;
+            diagonal = row[j + 1];
+            row[j + 1] = replace.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+    row[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn levenshtein_distance() {
+        assert_eq!(levenshtein("icon_colour", "icon_color"), 1);
+        assert_eq!(levenshtein("kitten", "sitting"), 3);
+        assert_eq!(levenshtein("", "abc"), 3);
+        assert_eq!(levenshtein("same", "same"), 0);
+        assert_eq!(levenshtein("äb", "ab"), 1);
+    }
+
+    #[test]
+    fn suggests_the_closest_key() {
+        let known = ["icon_color", "icon_size", "text_color", "icon.*"];
+        assert_eq!(
+            suggestion("icon_colour", known.into_iter()),
+            Some("icon_color")
+        );
+        assert_eq!(
+            suggestion("icon_sise", known.into_iter()),
+            Some("icon_size")
+        );
+    }
+
+    #[test]
+    fn no_suggestion_for_unrelated_keys() {
+        let known = ["icon_color", "text_color"];
+        assert_eq!(suggestion("on_scroll_up", known.into_iter()), None);
+        assert_eq!(suggestion("x", known.into_iter()), None);
+    }
+}
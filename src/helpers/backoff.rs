@@ -0,0 +1,31 @@
+use std::time::Duration;
+
+use tokio::time::sleep;
+
+const MIN_DELAY: Duration = Duration::from_secs(1);
+const MAX_DELAY: Duration = Duration::from_secs(60);
+
+/// Exponential backoff for subscriptions which retry to connect to their backend
+#[derive(Debug)]
+pub struct Backoff {
+    delay: Duration,
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Self { delay: MIN_DELAY }
+    }
+}
+
+impl Backoff {
+    /// Waits before the next attempt, twice as long as before (up to a minute)
+    pub async fn wait(&mut self) {
+        sleep(self.delay).await;
+        self.delay = (self.delay * 2).min(MAX_DELAY);
+    }
+
+    /// Call this once connected, so that the next failure is retried quickly again
+    pub fn reset(&mut self) {
+        self.delay = MIN_DELAY;
+    }
+}
@@ -0,0 +1,17 @@
+use std::sync::OnceLock;
+
+use chrono::{DateTime, Local};
+
+/// The time `bar-rs render --time` shows instead of the current one
+static FROZEN: OnceLock<DateTime<Local>> = OnceLock::new();
+
+/// Stops the clock of the time and date modules at `time`, so that rendered images don't depend
+/// on when they were rendered
+pub fn freeze(time: DateTime<Local>) {
+    let _ = FROZEN.set(time);
+}
+
+/// The time the time and date modules show
+pub fn now() -> DateTime<Local> {
+    FROZEN.get().copied().unwrap_or_else(Local::now)
+}
@@ -0,0 +1,79 @@
+use std::{
+    any::TypeId,
+    hash::Hash,
+    sync::{LazyLock, Mutex, MutexGuard},
+    time::Duration,
+};
+
+use iced::{
+    advanced::subscription::{from_recipe, into_recipes, EventStream, Hasher, Recipe},
+    futures::{future, stream::BoxStream, SinkExt, StreamExt},
+    stream, Subscription,
+};
+use tokio::{sync::Notify, time::sleep};
+
+use crate::Message;
+
+/// The messages of module subscriptions which are delivered with the next frame
+static PENDING: LazyLock<Mutex<Vec<Message>>> = LazyLock::new(Default::default);
+/// Wakes [subscription] once a message is pending
+static WAKE: LazyLock<Notify> = LazyLock::new(Notify::new);
+
+fn pending() -> MutexGuard<'static, Vec<Message>> {
+    PENDING.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Runs a subscription, but instead of sending its messages right away, they are collected until
+/// the next frame, so that the updates of all modules within a frame are applied together and the
+/// bar is only laid out and drawn once for them
+struct Deferred(Box<dyn Recipe<Output = Message>>);
+
+impl Recipe for Deferred {
+    type Output = Message;
+
+    fn hash(&self, state: &mut Hasher) {
+        TypeId::of::<Self>().hash(state);
+        self.0.hash(state);
+    }
+
+    fn stream(self: Box<Self>, input: EventStream) -> BoxStream<'static, Message> {
+        self.0
+            .stream(input)
+            .filter_map(|msg| {
+                pending().push(msg);
+                WAKE.notify_one();
+                future::ready(None)
+            })
+            .boxed()
+    }
+}
+
+/// Defers the messages of `subscription` to the next frame, see [subscription]
+pub fn deferred(subscription: Subscription<Message>) -> Subscription<Message> {
+    Subscription::batch(
+        into_recipes(subscription)
+            .into_iter()
+            .map(|recipe| from_recipe(Deferred(recipe))),
+    )
+}
+
+/// Sends the deferred messages as one [Message::Coalesced], `frame` after the first of them
+/// arrived
+pub fn subscription(frame: Duration) -> Subscription<Message> {
+    Subscription::run_with_id(
+        ("coalesce", frame),
+        stream::channel(1, move |mut sender| async move {
+            loop {
+                WAKE.notified().await;
+                sleep(frame).await;
+                let messages = std::mem::take(&mut *pending());
+                if messages.is_empty() {
+                    continue;
+                }
+                if sender.send(Message::Coalesced(messages)).await.is_err() {
+                    return;
+                }
+            }
+        }),
+    )
+}
@@ -0,0 +1,153 @@
+use std::{
+    any::Any,
+    collections::{HashMap, HashSet},
+    hash::Hash,
+    panic::{catch_unwind, AssertUnwindSafe},
+    sync::{LazyLock, Mutex, MutexGuard},
+    time::{Duration, Instant},
+};
+
+use iced::{
+    advanced::subscription::{from_recipe, into_recipes, EventStream, Hasher, Recipe},
+    futures::{
+        stream::{self, BoxStream},
+        StreamExt,
+    },
+    Subscription,
+};
+use tokio::time::sleep;
+
+use crate::Message;
+
+/// A module instance failing this often within [FAILURE_WINDOW] is disabled for the session
+const MAX_FAILURES: usize = 5;
+const FAILURE_WINDOW: Duration = Duration::from_secs(60);
+
+static HEALTH: LazyLock<Mutex<Health>> = LazyLock::new(Default::default);
+
+/// Panics of module instances, in their views and subscriptions
+#[derive(Debug, Default)]
+struct Health {
+    failures: HashMap<String, Vec<Instant>>,
+    /// Why a module instance failed, until its subscription works again
+    reasons: HashMap<String, String>,
+    /// How often the subscription of a module instance was restarted, which is part of its id
+    restarts: HashMap<String, usize>,
+    disabled: HashSet<String>,
+}
+
+fn health() -> MutexGuard<'static, Health> {
+    HEALTH.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Records a failure of the module instance, returns how often it failed within the last minute
+fn report(module: &str, reason: String) -> usize {
+    eprintln!("[{module}] {reason}");
+    let mut health = health();
+    let now = Instant::now();
+    let failures = health.failures.entry(module.to_string()).or_default();
+    failures.retain(|time| now.duration_since(*time) < FAILURE_WINDOW);
+    failures.push(now);
+    let count = failures.len();
+    if count >= MAX_FAILURES && health.disabled.insert(module.to_string()) {
+        eprintln!("[{module}] Failed {count} times within a minute, disabling it for this session");
+    }
+    health.reasons.insert(module.to_string(), reason);
+    count
+}
+
+/// Whether the module instance failed too often and isn't shown anymore
+pub fn is_disabled(module: &str) -> bool {
+    health().disabled.contains(module)
+}
+
+/// Why the subscription of the module instance failed, while it's restarted
+pub fn failure(module: &str) -> Option<String> {
+    health().reasons.get(module).cloned()
+}
+
+/// Restarts the subscription of the module instance with the next update of the bar, so that it
+/// reads its data again, e.g. for `bar-rsctl refresh`
+pub fn restart(module: &str) {
+    *health().restarts.entry(module.to_string()).or_default() += 1;
+}
+
+/// Runs `f` (e.g. building the view of a module), a panic is reported as a failure of the
+/// module instance and returned as the error
+pub fn catch<T>(module: &str, f: impl FnOnce() -> T) -> Result<T, String> {
+    catch_unwind(AssertUnwindSafe(f)).map_err(|payload| {
+        let reason = format!("panicked: {}", panic_message(payload.as_ref()));
+        report(module, reason.clone());
+        reason
+    })
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    payload
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| payload.downcast_ref::<String>().map(|s| s.as_str()))
+        .unwrap_or("unknown reason")
+}
+
+/// The subscription of a module instance, which is restarted with a backoff once it panics.
+/// Subscriptions may end on purpose, e.g. if their backend is missing, so that isn't a failure.
+pub fn guard(module: &str, subscription: Subscription<Message>) -> Subscription<Message> {
+    let restarts = health().restarts.get(module).copied().unwrap_or_default();
+    Subscription::batch(into_recipes(subscription).into_iter().map(|recipe| {
+        from_recipe(Guarded {
+            module: module.to_string(),
+            restarts,
+            recipe,
+        })
+    }))
+}
+
+struct Guarded {
+    module: String,
+    restarts: usize,
+    recipe: Box<dyn Recipe<Output = Message>>,
+}
+
+impl Recipe for Guarded {
+    type Output = Message;
+
+    fn hash(&self, state: &mut Hasher) {
+        self.module.hash(state);
+        self.restarts.hash(state);
+        self.recipe.hash(state);
+    }
+
+    fn stream(self: Box<Self>, input: EventStream) -> BoxStream<'static, Message> {
+        let Guarded {
+            module,
+            restarts,
+            recipe,
+        } = *self;
+        let messages = AssertUnwindSafe(recipe.stream(input)).catch_unwind();
+        stream::unfold(Some(messages), move |messages| {
+            let module = module.clone();
+            async move {
+                let mut messages = messages?;
+                let reason = match messages.next().await? {
+                    Ok(msg) => {
+                        if restarts > 0 {
+                            health().reasons.remove(&module);
+                        }
+                        return Some((msg, Some(messages)));
+                    }
+                    Err(payload) => format!("panicked: {}", panic_message(payload.as_ref())),
+                };
+                let count = report(&module, reason);
+                if !is_disabled(&module) {
+                    sleep(Duration::from_secs(1 << (count - 1))).await;
+                    *health().restarts.entry(module).or_default() += 1;
+                }
+                // Updating the bar restarts the subscription with its new id, or hides the
+                // disabled module
+                Some((Message::update(|_| {}), None))
+            }
+        })
+        .boxed()
+    }
+}
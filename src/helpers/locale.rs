@@ -0,0 +1,131 @@
+use std::{env, fmt::Write};
+
+use chrono::{DateTime, Locale, NaiveDate, TimeZone, Weekday};
+
+/// Territories whose weeks start on Sunday, as listed by the CLDR
+const SUNDAY_START: &[&str] = &[
+    "AG", "AS", "BD", "BR", "BS", "BT", "BW", "BZ", "CA", "CN", "CO", "DM", "DO", "ET", "GT", "GU",
+    "HK", "HN", "ID", "IL", "IN", "JM", "JP", "KE", "KH", "KR", "LA", "MH", "MM", "MO", "MT", "MX",
+    "MZ", "NI", "NP", "PA", "PE", "PH", "PK", "PR", "PT", "PY", "SA", "SG", "SV", "TH", "TT", "TW",
+    "UM", "US", "VE", "VI", "WS", "YE", "ZA", "ZW",
+];
+/// Territories whose weeks start on Saturday, as listed by the CLDR
+const SATURDAY_START: &[&str] = &[
+    "AE", "AF", "BH", "DJ", "DZ", "EG", "IQ", "IR", "JO", "KW", "LY", "OM", "QA", "SD", "SY",
+];
+
+/// The locale named by the `locale` option: `auto` takes it from `LC_ALL`, `LC_TIME` or `LANG`,
+/// and no value or an invalid one falls back to `POSIX` (English names)
+pub fn read_locale(value: Option<&str>) -> Locale {
+    let name = match value.map(|v| v.trim()) {
+        None | Some("") => return Locale::POSIX,
+        Some("auto") => ["LC_ALL", "LC_TIME", "LANG"]
+            .iter()
+            .find_map(|var| env::var(var).ok().filter(|v| !v.is_empty()))
+            .unwrap_or_default(),
+        Some(name) => name.to_string(),
+    };
+    // Without the encoding and modifier, like `de_DE` for `de_DE.UTF-8@euro`
+    let name = name.split(['.', '@']).next().unwrap_or_default();
+    if name.is_empty() || name == "C" {
+        return Locale::POSIX;
+    }
+    Locale::try_from(name).unwrap_or_else(|_| {
+        eprintln!("Unknown locale `{name}`, falling back to POSIX");
+        Locale::POSIX
+    })
+}
+
+/// The first day of the week in the territory of the locale, Monday if it has none
+pub fn week_start(locale: Locale) -> Weekday {
+    let name = format!("{locale:?}");
+    let territory = name.split('_').nth(1).unwrap_or_default();
+    match territory {
+        t if SUNDAY_START.contains(&t) => Weekday::Sun,
+        t if SATURDAY_START.contains(&t) => Weekday::Sat,
+        _ => Weekday::Mon,
+    }
+}
+
+/// Whether the locale shows times with a 12-hour clock
+pub fn twelve_hour(locale: Locale) -> bool {
+    // `%X` is the time as the locale shows it, which has no `13` with a 12-hour clock
+    NaiveDate::from_ymd_opt(2000, 1, 1)
+        .and_then(|date| date.and_hms_opt(13, 0, 0))
+        .is_some_and(|time| !format(&time.and_utc(), "%X", locale).contains("13"))
+}
+
+/// The time formatted with weekday and month names of the locale, an invalid format is logged
+/// and shown as nothing
+pub fn format<Tz>(time: &DateTime<Tz>, fmt: &str, locale: Locale) -> String
+where
+    Tz: TimeZone,
+    Tz::Offset: std::fmt::Display,
+{
+    let mut text = String::new();
+    write!(text, "{}", time.format_localized(fmt, locale))
+        .map(|_| text)
+        .unwrap_or_else(|_| {
+            eprintln!("Invalid date format `{fmt}`");
+            String::new()
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+
+    use super::*;
+
+    fn date(year: i32, month: u32, day: u32) -> DateTime<Utc> {
+        NaiveDate::from_ymd_opt(year, month, day)
+            .and_then(|date| date.and_hms_opt(15, 30, 0))
+            .unwrap()
+            .and_utc()
+    }
+
+    #[test]
+    fn locales_are_read_without_encoding() {
+        assert_eq!(read_locale(Some("de_DE")), Locale::de_DE);
+        assert_eq!(read_locale(Some("de_DE.UTF-8")), Locale::de_DE);
+        assert_eq!(read_locale(Some("fr_FR.UTF-8@euro")), Locale::fr_FR);
+        assert_eq!(read_locale(None), Locale::POSIX);
+        assert_eq!(read_locale(Some("C")), Locale::POSIX);
+    }
+
+    #[test]
+    fn invalid_locales_fall_back_to_posix() {
+        assert_eq!(read_locale(Some("xx_YY")), Locale::POSIX);
+        assert_eq!(
+            format(&date(2025, 3, 3), "%A %B", read_locale(Some("nonsense"))),
+            "Monday March"
+        );
+    }
+
+    #[test]
+    fn names_are_localized() {
+        let monday = date(2025, 3, 3);
+        assert_eq!(
+            format(&monday, "%A, %d. %B", Locale::de_DE),
+            "Montag, 03. März"
+        );
+        assert_eq!(format(&monday, "%A %B", Locale::fr_FR), "lundi mars");
+        assert_eq!(format(&monday, "%A %B", Locale::POSIX), "Monday March");
+    }
+
+    #[test]
+    fn clock_conventions() {
+        assert!(twelve_hour(Locale::en_US));
+        assert!(!twelve_hour(Locale::de_DE));
+        assert!(!twelve_hour(Locale::POSIX));
+    }
+
+    #[test]
+    fn weeks_start_by_territory() {
+        assert_eq!(week_start(Locale::de_DE), Weekday::Mon);
+        assert_eq!(week_start(Locale::en_GB), Weekday::Mon);
+        assert_eq!(week_start(Locale::en_US), Weekday::Sun);
+        assert_eq!(week_start(Locale::ar_EG), Weekday::Sat);
+        assert_eq!(week_start(Locale::POSIX), Weekday::Mon);
+    }
+}
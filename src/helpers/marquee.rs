@@ -0,0 +1,171 @@
+use std::{collections::HashMap, time::Duration};
+
+use crate::config::{
+    parse::StringExt,
+    schema::{ConfigKey, Kind},
+};
+
+/// Scrolls a text which is longer than `width` characters by one character per tick, stopping
+/// for `pause` at its start and end
+#[derive(Debug)]
+pub struct Marquee {
+    pub enabled: bool,
+    /// The number of characters shown
+    width: usize,
+    /// Characters per second
+    speed: f32,
+    /// How long the text stops at its start and end, in seconds
+    pause: f32,
+    /// The text which is scrolling, it starts over once the text changes
+    text: String,
+    step: usize,
+}
+
+impl Default for Marquee {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            width: 28,
+            speed: 4.,
+            pause: 2.,
+            text: String::new(),
+            step: 0,
+        }
+    }
+}
+
+impl Marquee {
+    pub const KEYS: &'static [ConfigKey] = &[
+        ConfigKey::new("marquee", Kind::Bool, Some("false")),
+        ConfigKey::new("marquee_width", Kind::Integer, Some("28")),
+        ConfigKey::new("marquee_speed", Kind::Float, Some("4")),
+        ConfigKey::new("marquee_pause", Kind::Float, Some("2")),
+    ];
+
+    pub fn read_config(&mut self, config: &HashMap<String, Option<String>>) {
+        let default = Self::default();
+        self.enabled = config
+            .get("marquee")
+            .and_then(|v| v.into_bool())
+            .unwrap_or(default.enabled);
+        self.width = config
+            .get("marquee_width")
+            .and_then(|v| v.as_ref().and_then(|v| v.parse().ok()))
+            .filter(|width| *width > 0)
+            .unwrap_or(default.width);
+        self.speed = config
+            .get("marquee_speed")
+            .and_then(|v| v.as_ref().and_then(|v| v.parse().ok()))
+            .filter(|speed| *speed > 0.)
+            .unwrap_or(default.speed);
+        self.pause = config
+            .get("marquee_pause")
+            .and_then(|v| v.as_ref().and_then(|v| v.parse().ok()))
+            .filter(|pause| *pause >= 0.)
+            .unwrap_or(default.pause);
+    }
+
+    /// Whether `text` doesn't fit and has to scroll
+    pub fn scrolls(&self, text: &str) -> bool {
+        self.enabled && text.chars().count() > self.width
+    }
+
+    /// How often [Marquee::tick] has to be called
+    pub fn interval(&self) -> Duration {
+        Duration::from_secs_f32(1. / self.speed)
+    }
+
+    /// Moves `text` by one character, or starts over if it isn't the text which was scrolling
+    pub fn tick(&mut self, text: String) {
+        match text == self.text {
+            true => self.step = self.step.wrapping_add(1),
+            false => {
+                self.text = text;
+                self.step = 0;
+            }
+        }
+    }
+
+    /// The part of `text` which is visible, all of it if it fits or the marquee is disabled
+    pub fn visible(&self, text: &str) -> String {
+        if !self.scrolls(text) {
+            return text.to_string();
+        }
+        let overflow = text.chars().count() - self.width;
+        let offset = match text == self.text {
+            true => {
+                let pause = (self.pause * self.speed).round() as usize;
+                let position = self.step % (2 * pause + overflow + 1);
+                position.saturating_sub(pause).min(overflow)
+            }
+            false => 0,
+        };
+        text.chars().skip(offset).take(self.width).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn marquee(width: usize, speed: f32, pause: f32) -> Marquee {
+        Marquee {
+            enabled: true,
+            width,
+            speed,
+            pause,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn short_texts_dont_scroll() {
+        let mut marquee = marquee(5, 1., 0.);
+        assert!(!marquee.scrolls("abcde"));
+        marquee.tick("abcde".to_string());
+        marquee.tick("abcde".to_string());
+        assert_eq!(marquee.visible("abcde"), "abcde");
+    }
+
+    #[test]
+    fn scrolls_with_pauses_at_the_ends() {
+        let text = "abcdefg";
+        let mut marquee = marquee(5, 1., 1.);
+        let mut shown = vec![];
+        for _ in 0..6 {
+            marquee.tick(text.to_string());
+            shown.push(marquee.visible(text));
+        }
+        assert_eq!(
+            shown,
+            ["abcde", "abcde", "bcdef", "cdefg", "cdefg", "abcde"]
+        );
+    }
+
+    #[test]
+    fn new_texts_start_over() {
+        let mut marquee = marquee(3, 1., 0.);
+        marquee.tick("abcdef".to_string());
+        marquee.tick("abcdef".to_string());
+        assert_eq!(marquee.visible("abcdef"), "bcd");
+        assert_eq!(marquee.visible("uvwxyz"), "uvw");
+        marquee.tick("uvwxyz".to_string());
+        assert_eq!(marquee.visible("uvwxyz"), "uvw");
+    }
+
+    #[test]
+    fn counts_characters_instead_of_bytes() {
+        let mut marquee = marquee(2, 1., 0.);
+        marquee.tick("äöü".to_string());
+        marquee.tick("äöü".to_string());
+        assert_eq!(marquee.visible("äöü"), "öü");
+    }
+
+    #[test]
+    fn disabled_shows_everything() {
+        let marquee = Marquee::default();
+        let text = "a".repeat(100);
+        assert!(!marquee.scrolls(&text));
+        assert_eq!(marquee.visible(&text), text);
+    }
+}
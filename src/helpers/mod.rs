@@ -1,3 +1,21 @@
+pub mod backoff;
+pub mod clock;
+pub mod coalesce;
+pub mod health;
+pub mod locale;
+pub mod marquee;
+pub mod notify_daemon;
+pub mod process;
+pub mod scroll;
+pub mod source;
+pub mod state;
+pub mod status;
+pub mod supervisor;
+pub mod templates;
+pub mod timings;
+pub mod window;
+pub mod workspaces;
+
 pub trait UnEscapeString {
     /// Unescape special characters like '\n' and '\t'
     fn unescape(self) -> Option<String>;
@@ -0,0 +1,350 @@
+use std::{collections::HashMap, time::Duration};
+
+use iced::futures::{
+    future,
+    stream::{self, BoxStream},
+    StreamExt,
+};
+use tokio::{process::Command, time::sleep};
+use zbus::{dbus_proxy, zvariant::OwnedValue, Connection};
+
+/// The mode mako uses for do-not-disturb by convention, see `mako(5)`
+const MAKO_DND_MODE: &str = "do-not-disturb";
+/// How often the state of daemons which don't signal changes is read
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+#[dbus_proxy(
+    interface = "org.freedesktop.Notifications",
+    default_service = "org.freedesktop.Notifications",
+    default_path = "/org/freedesktop/Notifications"
+)]
+trait Notifications {
+    /// Returns the name, vendor, version and spec version of the daemon
+    fn get_server_information(&self) -> zbus::Result<(String, String, String, String)>;
+}
+
+#[dbus_proxy(
+    interface = "org.erikreider.swaync.cc",
+    default_service = "org.erikreider.swaync.cc",
+    default_path = "/org/erikreider/swaync/cc"
+)]
+trait SwayncControlCenter {
+    fn get_dnd(&self) -> zbus::Result<bool>;
+    fn set_dnd(&self, state: bool) -> zbus::Result<()>;
+    fn notification_count(&self) -> zbus::Result<u32>;
+    fn close_all_notifications(&self) -> zbus::Result<()>;
+    /// Emitted whenever the notifications or the state of the control center change
+    #[dbus_proxy(signal)]
+    fn subscribe(&self, count: u32, dnd: bool, cc_open: bool) -> zbus::Result<()>;
+}
+
+#[dbus_proxy(
+    interface = "org.dunstproject.cmd0",
+    default_service = "org.freedesktop.Notifications",
+    default_path = "/org/freedesktop/Notifications"
+)]
+trait Dunst {
+    #[dbus_proxy(property)]
+    fn paused(&self) -> zbus::Result<bool>;
+    #[dbus_proxy(property)]
+    fn set_paused(&self, paused: bool) -> zbus::Result<()>;
+    /// The notifications which were shown and closed, newest first
+    fn notification_list_history(&self) -> zbus::Result<Vec<HashMap<String, OwnedValue>>>;
+    fn notification_remove_from_history(&self, id: u32) -> zbus::Result<()>;
+    fn notification_clear_history(&self) -> zbus::Result<()>;
+}
+
+#[dbus_proxy(
+    interface = "fr.emersion.Mako",
+    default_service = "org.freedesktop.Notifications",
+    default_path = "/fr/emersion/Mako"
+)]
+trait Mako {
+    /// The notifications which are shown
+    fn list_notifications(&self) -> zbus::Result<Vec<HashMap<String, OwnedValue>>>;
+}
+
+/// A notification, as far as the bar shows it
+#[derive(Debug, Clone, PartialEq)]
+pub struct Notification {
+    pub id: u32,
+    pub app_name: String,
+    pub summary: String,
+    pub body: String,
+}
+
+impl Notification {
+    /// Reads a notification as dunst (`appname`) and mako (`app-name`) list them
+    fn from_dict(dict: &HashMap<String, OwnedValue>) -> Option<Self> {
+        let string = |key: &str| {
+            dict.get(key)
+                .and_then(|value| String::try_from(value.clone()).ok())
+                .unwrap_or_default()
+        };
+        let id = dict.get("id").and_then(|id| {
+            u32::try_from(id.clone())
+                .ok()
+                .or_else(|| i32::try_from(id.clone()).ok().map(|id| id as u32))
+        })?;
+        Some(Notification {
+            id,
+            app_name: match dict.contains_key("appname") {
+                true => string("appname"),
+                false => string("app-name"),
+            },
+            summary: string("summary"),
+            body: string("body"),
+        })
+    }
+}
+
+/// The notifications of the daemon
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Notifications {
+    pub count: usize,
+    /// `None` if the daemon only reports how many there are (swaync)
+    pub list: Option<Vec<Notification>>,
+}
+
+/// The notification daemon which is currently running. Used by modules to work with any of the
+/// supported daemons.
+#[derive(Debug, Clone)]
+pub enum NotifyDaemon {
+    Swaync(SwayncControlCenterProxy<'static>),
+    Dunst(DunstProxy<'static>),
+    /// mako has no D-Bus API for its modes and dismissing notifications, so `makoctl` is used
+    /// for them instead
+    Mako(MakoProxy<'static>),
+}
+
+impl NotifyDaemon {
+    /// Finds out which daemon owns `org.freedesktop.Notifications`
+    pub async fn connect() -> Result<Self, String> {
+        let connection = Connection::session()
+            .await
+            .map_err(|e| format!("Failed to connect to the session bus: {e}"))?;
+        let (name, ..) = NotificationsProxy::new(&connection)
+            .await
+            .map_err(|e| e.to_string())?
+            .get_server_information()
+            .await
+            .map_err(|e| format!("No notification daemon is running: {e}"))?;
+        match name.as_str() {
+            "SwayNotificationCenter" => SwayncControlCenterProxy::new(&connection)
+                .await
+                .map(NotifyDaemon::Swaync)
+                .map_err(|e| e.to_string()),
+            "dunst" => DunstProxy::new(&connection)
+                .await
+                .map(NotifyDaemon::Dunst)
+                .map_err(|e| e.to_string()),
+            "mako" => MakoProxy::new(&connection)
+                .await
+                .map(NotifyDaemon::Mako)
+                .map_err(|e| e.to_string()),
+            _ => Err(format!("The notification daemon {name} is not supported")),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            NotifyDaemon::Swaync(_) => "swaync",
+            NotifyDaemon::Dunst(_) => "dunst",
+            NotifyDaemon::Mako(_) => "mako",
+        }
+    }
+
+    /// Whether do-not-disturb is enabled (called paused by dunst)
+    pub async fn dnd(&self) -> Result<bool, String> {
+        match self {
+            NotifyDaemon::Swaync(proxy) => proxy.get_dnd().await.map_err(|e| e.to_string()),
+            NotifyDaemon::Dunst(proxy) => proxy.paused().await.map_err(|e| e.to_string()),
+            NotifyDaemon::Mako(_) => makoctl(&["mode"])
+                .await
+                .map(|modes| modes.lines().any(|mode| mode.trim() == MAKO_DND_MODE)),
+        }
+    }
+
+    pub async fn set_dnd(&self, dnd: bool) -> Result<(), String> {
+        match self {
+            NotifyDaemon::Swaync(proxy) => proxy.set_dnd(dnd).await.map_err(|e| e.to_string()),
+            NotifyDaemon::Dunst(proxy) => proxy.set_paused(dnd).await.map_err(|e| e.to_string()),
+            NotifyDaemon::Mako(_) => makoctl(&[
+                "mode",
+                match dnd {
+                    true => "-a",
+                    false => "-r",
+                },
+                MAKO_DND_MODE,
+            ])
+            .await
+            .map(|_| ()),
+        }
+    }
+
+    /// The do-not-disturb state whenever it changes. mako doesn't signal changes, so it is polled
+    /// every two seconds.
+    pub async fn dnd_changes(&self) -> Result<BoxStream<'static, bool>, String> {
+        match self {
+            NotifyDaemon::Swaync(proxy) => Ok(proxy
+                .receive_subscribe()
+                .await
+                .map_err(|e| e.to_string())?
+                .filter_map(|signal| future::ready(signal.args().ok().map(|args| *args.dnd())))
+                .boxed()),
+            NotifyDaemon::Dunst(proxy) => Ok(proxy
+                .receive_paused_changed()
+                .await
+                .filter_map(|change| async move { change.get().await.ok() })
+                .boxed()),
+            NotifyDaemon::Mako(_) => {
+                let daemon = self.clone();
+                Ok(stream::unfold(daemon, |daemon| async move {
+                    sleep(POLL_INTERVAL).await;
+                    let dnd = daemon.dnd().await;
+                    Some((dnd, daemon))
+                })
+                .filter_map(|dnd| future::ready(dnd.ok()))
+                .boxed())
+            }
+        }
+    }
+
+    /// The notifications which are shown by mako, the notification history of dunst and the
+    /// number of notifications in the control center of swaync
+    pub async fn notifications(&self) -> Result<Notifications, String> {
+        let list = match self {
+            NotifyDaemon::Swaync(proxy) => {
+                return proxy
+                    .notification_count()
+                    .await
+                    .map(|count| Notifications {
+                        count: count as usize,
+                        list: None,
+                    })
+                    .map_err(|e| e.to_string())
+            }
+            NotifyDaemon::Dunst(proxy) => proxy.notification_list_history().await,
+            NotifyDaemon::Mako(proxy) => proxy.list_notifications().await,
+        }
+        .map_err(|e| e.to_string())?;
+        let list = list
+            .iter()
+            .filter_map(Notification::from_dict)
+            .collect::<Vec<_>>();
+        Ok(Notifications {
+            count: list.len(),
+            list: Some(list),
+        })
+    }
+
+    pub async fn dismiss(&self, id: u32) -> Result<(), String> {
+        match self {
+            NotifyDaemon::Swaync(_) => {
+                Err("swaync doesn't list its notifications, so they can't be dismissed".to_string())
+            }
+            NotifyDaemon::Dunst(proxy) => proxy
+                .notification_remove_from_history(id)
+                .await
+                .map_err(|e| e.to_string()),
+            NotifyDaemon::Mako(_) => makoctl(&["dismiss", "-n", &id.to_string()])
+                .await
+                .map(|_| ()),
+        }
+    }
+
+    pub async fn dismiss_all(&self) -> Result<(), String> {
+        match self {
+            NotifyDaemon::Swaync(proxy) => proxy
+                .close_all_notifications()
+                .await
+                .map_err(|e| e.to_string()),
+            NotifyDaemon::Dunst(proxy) => proxy
+                .notification_clear_history()
+                .await
+                .map_err(|e| e.to_string()),
+            NotifyDaemon::Mako(_) => makoctl(&["dismiss", "-a"]).await.map(|_| ()),
+        }
+    }
+
+    /// Yields whenever the notifications might have changed. Only swaync signals changes, dunst
+    /// and mako are polled every two seconds.
+    pub async fn notification_changes(&self) -> Result<BoxStream<'static, ()>, String> {
+        match self {
+            NotifyDaemon::Swaync(proxy) => Ok(proxy
+                .receive_subscribe()
+                .await
+                .map_err(|e| e.to_string())?
+                .map(|_| ())
+                .boxed()),
+            NotifyDaemon::Dunst(_) | NotifyDaemon::Mako(_) => {
+                Ok(stream::repeat(()).then(|()| sleep(POLL_INTERVAL)).boxed())
+            }
+        }
+    }
+}
+
+async fn makoctl(args: &[&str]) -> Result<String, String> {
+    let output = Command::new("makoctl")
+        .args(args)
+        .output()
+        .await
+        .map_err(|e| format!("Failed to run makoctl: {e}"))?;
+    match output.status.success() {
+        true => Ok(String::from_utf8_lossy(&output.stdout).to_string()),
+        false => Err(format!(
+            "makoctl {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use zbus::zvariant::Value;
+
+    use super::*;
+
+    fn dict(entries: &[(&str, Value)]) -> HashMap<String, OwnedValue> {
+        entries
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.clone().into()))
+            .collect()
+    }
+
+    #[test]
+    fn reads_dunst_and_mako_notifications() {
+        let dunst = dict(&[
+            ("id", Value::from(7_i32)),
+            ("appname", Value::from("Firefox")),
+            ("summary", Value::from("Download finished")),
+            ("body", Value::from("bar-rs.tar.gz")),
+            ("urgency", Value::from("NORMAL")),
+        ]);
+        let mako = dict(&[
+            ("id", Value::from(3_u32)),
+            ("app-name", Value::from("Signal")),
+            ("summary", Value::from("Alice")),
+        ]);
+        assert_eq!(
+            Notification::from_dict(&dunst),
+            Some(Notification {
+                id: 7,
+                app_name: "Firefox".to_string(),
+                summary: "Download finished".to_string(),
+                body: "bar-rs.tar.gz".to_string(),
+            })
+        );
+        assert_eq!(
+            Notification::from_dict(&mako),
+            Some(Notification {
+                id: 3,
+                app_name: "Signal".to_string(),
+                summary: "Alice".to_string(),
+                body: String::new(),
+            })
+        );
+        assert_eq!(Notification::from_dict(&dict(&[])), None);
+    }
+}
@@ -0,0 +1,66 @@
+use std::{io, os::unix::process::CommandExt, sync::Mutex};
+
+use libc::pid_t;
+use tokio::process::{Child, Command};
+
+/// The process groups of long running children, killed when bar-rs exits
+static GROUPS: Mutex<Vec<pid_t>> = Mutex::new(Vec::new());
+
+/// A child process started in its own process group, so that it can be killed together with
+/// the processes it started itself (`sh -c` usually does). The group is killed once this is
+/// dropped, or by [kill_all] when bar-rs exits.
+#[derive(Debug)]
+pub struct ChildGroup<C = Child> {
+    pub child: C,
+    pgid: pid_t,
+}
+
+impl<C> ChildGroup<C> {
+    fn track(child: C, id: Option<u32>) -> io::Result<Self> {
+        let pgid = id.ok_or_else(|| io::Error::other("the process exited right away"))? as pid_t;
+        GROUPS.lock().unwrap().push(pgid);
+        Ok(Self { child, pgid })
+    }
+}
+
+impl ChildGroup {
+    pub fn spawn(command: &mut Command) -> io::Result<Self> {
+        let child = command.process_group(0).kill_on_drop(true).spawn()?;
+        let id = child.id();
+        Self::track(child, id)
+    }
+}
+
+impl ChildGroup<std::process::Child> {
+    /// Like [ChildGroup::spawn], for callers outside of an async context
+    pub fn spawn_std(command: &mut std::process::Command) -> io::Result<Self> {
+        let child = command.process_group(0).spawn()?;
+        let id = child.id();
+        Self::track(child, Some(id))
+    }
+
+    /// Kills the group and waits for the child, so that it doesn't linger as a zombie
+    pub fn kill(mut self) {
+        kill_group(self.pgid);
+        if let Err(e) = self.child.wait() {
+            eprintln!("Failed to wait for process {}: {e}", self.child.id());
+        }
+    }
+}
+
+impl<C> Drop for ChildGroup<C> {
+    fn drop(&mut self) {
+        GROUPS.lock().unwrap().retain(|pgid| *pgid != self.pgid);
+        kill_group(self.pgid);
+    }
+}
+
+fn kill_group(pgid: pid_t) {
+    // Fails if the group is gone already, which is fine
+    unsafe { libc::kill(-pgid, libc::SIGTERM) };
+}
+
+/// Kills the children which are still running, call this before exiting
+pub fn kill_all() {
+    GROUPS.lock().unwrap().drain(..).for_each(kill_group);
+}
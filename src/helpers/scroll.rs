@@ -0,0 +1,103 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use iced::mouse::ScrollDelta;
+
+use crate::config::{
+    parse::StringExt,
+    schema::{ConfigKey, Kind},
+};
+
+/// Touchpads emit lots of small scroll events, so switch at most this often
+const MIN_INTERVAL: Duration = Duration::from_millis(100);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ScrollDirection {
+    Up,
+    Down,
+}
+
+impl ScrollDirection {
+    pub fn from_delta(delta: ScrollDelta) -> Option<Self> {
+        let y = match delta {
+            ScrollDelta::Lines { y, .. } | ScrollDelta::Pixels { y, .. } => y,
+        };
+        match y {
+            y if y > 0. => Some(ScrollDirection::Up),
+            y if y < 0. => Some(ScrollDirection::Down),
+            _ => None,
+        }
+    }
+}
+
+/// Switching workspaces by scrolling over a workspace module
+#[derive(Debug)]
+pub struct WorkspaceScroll {
+    /// Whether scrolling past the last workspace goes to the first one and vice versa
+    wrap: bool,
+    /// Scrolling up goes to the next workspace instead of the previous one
+    invert: bool,
+    last_switch: Option<Instant>,
+}
+
+impl Default for WorkspaceScroll {
+    fn default() -> Self {
+        Self {
+            wrap: true,
+            invert: false,
+            last_switch: None,
+        }
+    }
+}
+
+impl WorkspaceScroll {
+    pub const KEYS: &'static [ConfigKey] = &[
+        ConfigKey::new("scroll_wrap", Kind::Bool, Some("true")),
+        ConfigKey::new("invert_scroll", Kind::Bool, Some("false")),
+    ];
+
+    pub fn read_config(&mut self, config: &HashMap<String, Option<String>>) {
+        let default = Self::default();
+        self.wrap = config
+            .get("scroll_wrap")
+            .and_then(|v| v.into_bool())
+            .unwrap_or(default.wrap);
+        self.invert = config
+            .get("invert_scroll")
+            .and_then(|v| v.into_bool())
+            .unwrap_or(default.invert);
+    }
+
+    /// The index of the workspace to switch to, given the index of the active one and the
+    /// number of workspaces. `None` if there is nothing to switch to or the last switch was
+    /// too recent.
+    pub fn target(
+        &mut self,
+        direction: ScrollDirection,
+        active: usize,
+        count: usize,
+    ) -> Option<usize> {
+        if count == 0
+            || self
+                .last_switch
+                .is_some_and(|last| last.elapsed() < MIN_INTERVAL)
+        {
+            return None;
+        }
+        // Wheel up goes to the previous workspace
+        let next = (direction == ScrollDirection::Down) != self.invert;
+        let target = match (next, self.wrap) {
+            (true, true) => (active + 1) % count,
+            (true, false) => (active + 1).min(count - 1),
+            (false, true) => (active + count - 1) % count,
+            (false, false) => active.saturating_sub(1),
+        };
+        if target == active {
+            return None;
+        }
+        self.last_switch = Some(Instant::now());
+        Some(target)
+    }
+}
@@ -0,0 +1,102 @@
+#[cfg(test)]
+use std::collections::HashMap;
+use std::{fs, io, path::PathBuf, process::Command};
+
+/// Where modules read the data of the system from. The bar uses [Host], tests feed modules
+/// canned files and command outputs with [Fixtures], so that they don't depend on the system
+/// they run on.
+pub trait Source {
+    /// The content of a file, like `/proc/stat`
+    fn read(&self, path: &str) -> io::Result<String>;
+    /// The standard output of a command, like `free -b`
+    fn output(&self, program: &str, args: &[&str]) -> io::Result<String>;
+}
+
+/// The files and commands of the system bar-rs runs on
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Host;
+
+impl Source for Host {
+    fn read(&self, path: &str) -> io::Result<String> {
+        fs::read_to_string(path)
+    }
+
+    fn output(&self, program: &str, args: &[&str]) -> io::Result<String> {
+        let output = Command::new(program).args(args).output()?;
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+}
+
+impl<S: Source + ?Sized> Source for &S {
+    fn read(&self, path: &str) -> io::Result<String> {
+        (**self).read(path)
+    }
+
+    fn output(&self, program: &str, args: &[&str]) -> io::Result<String> {
+        (**self).output(program, args)
+    }
+}
+
+/// The files and command outputs of a system, saved in a directory for `bar-rs render`:
+/// `/proc/stat` is read from `<root>/proc/stat`, the output of `free -b` from
+/// `<root>/commands/free -b`
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    pub root: PathBuf,
+}
+
+impl Source for Snapshot {
+    fn read(&self, path: &str) -> io::Result<String> {
+        fs::read_to_string(self.root.join(path.trim_start_matches('/')))
+    }
+
+    fn output(&self, program: &str, args: &[&str]) -> io::Result<String> {
+        let command = std::iter::once(program)
+            .chain(args.iter().copied())
+            .collect::<Vec<_>>()
+            .join(" ");
+        fs::read_to_string(self.root.join("commands").join(command))
+    }
+}
+
+/// Canned files and command outputs, everything else doesn't exist
+#[cfg(test)]
+#[derive(Debug, Default)]
+pub struct Fixtures {
+    files: HashMap<String, String>,
+    /// By the command line, like `free -b`
+    outputs: HashMap<String, String>,
+}
+
+#[cfg(test)]
+impl Fixtures {
+    pub fn file(mut self, path: &str, content: &str) -> Self {
+        self.files.insert(path.to_string(), content.to_string());
+        self
+    }
+
+    pub fn output(mut self, command: &str, output: &str) -> Self {
+        self.outputs.insert(command.to_string(), output.to_string());
+        self
+    }
+}
+
+#[cfg(test)]
+impl Source for Fixtures {
+    fn read(&self, path: &str) -> io::Result<String> {
+        self.files
+            .get(path)
+            .cloned()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("no fixture {path}")))
+    }
+
+    fn output(&self, program: &str, args: &[&str]) -> io::Result<String> {
+        let command = std::iter::once(program)
+            .chain(args.iter().copied())
+            .collect::<Vec<_>>()
+            .join(" ");
+        self.outputs.get(&command).cloned().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::NotFound, format!("no fixture `{command}`"))
+        })
+    }
+}
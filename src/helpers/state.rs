@@ -0,0 +1,53 @@
+use std::{fs, io::ErrorKind, path::PathBuf};
+
+use serde_json::{Map, Value};
+
+use crate::config::get_state_dir;
+
+/// The runtime state of every module instance which has some, by instance name
+pub type ModuleState = Map<String, Value>;
+
+fn state_file() -> Option<PathBuf> {
+    get_state_dir().map(|dir| dir.join("modules.json"))
+}
+
+/// Read the module state saved on the last exit
+pub fn load() -> ModuleState {
+    let Some(path) = state_file() else {
+        return ModuleState::new();
+    };
+    let content = match fs::read_to_string(&path) {
+        Ok(content) => content,
+        // Nothing was saved yet
+        Err(e) if e.kind() == ErrorKind::NotFound => return ModuleState::new(),
+        Err(e) => {
+            eprintln!(
+                "Failed to read the module state from {}: {e}",
+                path.to_string_lossy()
+            );
+            return ModuleState::new();
+        }
+    };
+    serde_json::from_str(&content).unwrap_or_else(|e| {
+        eprintln!(
+            "Failed to parse the module state from {}: {e}",
+            path.to_string_lossy()
+        );
+        ModuleState::new()
+    })
+}
+
+pub fn save(state: &ModuleState) {
+    let Some(path) = state_file() else {
+        return;
+    };
+    if let Err(e) = serde_json::to_string(state)
+        .map_err(|e| e.to_string())
+        .and_then(|json| fs::write(&path, json).map_err(|e| e.to_string()))
+    {
+        eprintln!(
+            "Failed to save the module state to {}: {e}",
+            path.to_string_lossy()
+        );
+    }
+}
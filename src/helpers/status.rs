@@ -0,0 +1,81 @@
+use std::{
+    fmt::Display,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use iced::futures::{channel::mpsc::Sender, SinkExt};
+
+use crate::{modules::Module, Message};
+
+/// Whether bar-rs was started with `--verbose`
+static VERBOSE: AtomicBool = AtomicBool::new(false);
+
+pub fn verbose() -> bool {
+    VERBOSE.load(Ordering::Relaxed)
+}
+
+pub fn set_verbose(verbose: bool) {
+    VERBOSE.store(verbose, Ordering::Relaxed);
+}
+
+/// The health of the backend a module gets its data from
+#[derive(Debug, Default, Clone, PartialEq)]
+pub enum ModuleStatus {
+    #[default]
+    Ok,
+    /// The backend isn't there (yet), e.g. no bluetooth adapter is plugged in
+    Unavailable(String),
+    /// The backend is there, but talking to it failed
+    Error(String),
+}
+
+impl ModuleStatus {
+    /// Why the module doesn't work, `None` if it does
+    pub fn reason(&self) -> Option<&str> {
+        match self {
+            ModuleStatus::Ok => None,
+            ModuleStatus::Unavailable(reason) | ModuleStatus::Error(reason) => Some(reason),
+        }
+    }
+
+    /// Replaces the status, transitions are logged with `--verbose`
+    pub fn update(&mut self, module: &str, status: ModuleStatus) {
+        if *self != status && verbose() {
+            println!("{module}: {self} -> {status}");
+        }
+        *self = status;
+    }
+}
+
+/// Sets the status of the module `M` from its subscription, e.g. `Unavailable` when connecting
+/// to its backend failed. Returns `false` if the bar is closing.
+pub async fn send_status<M: Module>(sender: &mut Sender<Message>, status: ModuleStatus) -> bool {
+    sender
+        .send(Message::update(move |reg| {
+            reg.get_module_mut::<M>().set_status(status)
+        }))
+        .await
+        .is_ok()
+}
+
+impl Display for ModuleStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ModuleStatus::Ok => write!(f, "ok"),
+            ModuleStatus::Unavailable(reason) => write!(f, "unavailable ({reason})"),
+            ModuleStatus::Error(reason) => write!(f, "error ({reason})"),
+        }
+    }
+}
+
+/// What a module shows while its status isn't [ModuleStatus::Ok]
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum OnError {
+    /// Hide the module
+    #[default]
+    Hide,
+    /// Show the error icon, with the reason as a tooltip
+    Show,
+    /// Keep showing the last known data, marked by the error icon
+    Stale,
+}
@@ -0,0 +1,123 @@
+use std::{
+    collections::HashMap,
+    process::{Command, Stdio},
+    sync::Arc,
+    time::Duration,
+};
+
+use iced::Task;
+use tokio::{io::AsyncReadExt, select, sync::Mutex, time::timeout};
+
+use crate::Message;
+
+/// How the command of an action is run while it's still running from a previous click
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum ActionMode {
+    /// Run it again
+    #[default]
+    Spawn,
+    /// Ignore the click
+    Single,
+    /// Run it once the previous one finished
+    Queue,
+}
+
+/// Runs the commands of actions and reports failed ones, see `action_mode` and `action_timeout`
+#[derive(Debug, Default)]
+pub struct Supervisor {
+    /// Held while a command runs, by module instance and command
+    running: HashMap<String, Arc<Mutex<()>>>,
+}
+
+impl Supervisor {
+    /// Runs the command for the given module instance, `None` if it wasn't started by a module
+    pub fn spawn(
+        &mut self,
+        command: Command,
+        module: Option<String>,
+        mode: ActionMode,
+        kill_after: Option<Duration>,
+    ) -> Task<Message> {
+        let module = module.unwrap_or_else(|| "bar-rs".to_string());
+        // Forget the commands which aren't running anymore
+        self.running.retain(|_, lock| Arc::strong_count(lock) > 1);
+        let lock = self
+            .running
+            .entry(format!("{module} {command:?}"))
+            .or_default()
+            .clone();
+        match mode {
+            ActionMode::Spawn => Task::future(run(command, module, kill_after)).discard(),
+            ActionMode::Single => match lock.try_lock_owned() {
+                Ok(guard) => Task::future(async move {
+                    run(command, module, kill_after).await;
+                    drop(guard);
+                })
+                .discard(),
+                Err(_) => {
+                    eprintln!("[{module}] Ignoring a click, {command:?} is still running");
+                    Task::none()
+                }
+            },
+            ActionMode::Queue => Task::future(async move {
+                let _guard = lock.lock_owned().await;
+                run(command, module, kill_after).await;
+            })
+            .discard(),
+        }
+    }
+}
+
+/// Runs the command until it exits or `kill_after` passed, and logs why if it failed
+async fn run(command: Command, module: String, kill_after: Option<Duration>) {
+    let mut command = tokio::process::Command::from(command);
+    let program = format!("{:?}", command.as_std());
+    let mut child = match command.stderr(Stdio::piped()).spawn() {
+        Ok(child) => child,
+        Err(e) => {
+            eprintln!("[{module}] Failed to spawn {program}: {e}");
+            return;
+        }
+    };
+    let mut stderr = child
+        .stderr
+        .take()
+        .expect("child did not have a handle to stderr");
+    let mut output = vec![];
+    let finished = async {
+        // Keep reading, so that the command doesn't block on a full pipe
+        let mut open = true;
+        loop {
+            select! {
+                status = child.wait() => break status,
+                read = stderr.read_buf(&mut output), if open => open = matches!(read, Ok(1..)),
+            }
+        }
+    };
+    let status = match kill_after {
+        Some(kill_after) => match timeout(kill_after, finished).await {
+            Ok(status) => status,
+            Err(_) => {
+                eprintln!("[{module}] {program} is still running after {kill_after:?}, killing it");
+                if let Err(e) = child.kill().await {
+                    eprintln!("[{module}] Failed to kill {program}: {e}");
+                }
+                return;
+            }
+        },
+        None => finished.await,
+    };
+    match status {
+        Ok(status) if status.success() => {}
+        Ok(status) => {
+            // Whatever is left, processes started in the background may keep stderr open
+            let _ = timeout(Duration::from_millis(50), stderr.read_to_end(&mut output)).await;
+            let stderr = String::from_utf8_lossy(&output);
+            match stderr.trim() {
+                "" => eprintln!("[{module}] {program} exited with {status}"),
+                stderr => eprintln!("[{module}] {program} exited with {status}:\n{stderr}"),
+            }
+        }
+        Err(e) => eprintln!("[{module}] Failed to wait for {program}: {e}"),
+    }
+}
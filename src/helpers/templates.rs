@@ -0,0 +1,298 @@
+use std::collections::HashMap;
+
+use handlebars::{
+    handlebars_helper, Context, Handlebars, Helper, HelperResult, Output, RenderContext,
+};
+use serde_json::Value;
+
+handlebars_helper!(plural: |count: f64, word: str, {many: str = ""}| {
+    let word = match count == 1. {
+        true => word.to_string(),
+        false if many.is_empty() => format!("{word}s"),
+        false => many.to_string(),
+    };
+    format!("{count} {word}")
+});
+
+/// A number, also if it's given as a string (like the padded minutes of some modules)
+fn number(value: &Value) -> Option<f64> {
+    match value {
+        Value::Number(number) => number.as_f64(),
+        Value::String(string) => string.trim().parse().ok(),
+        _ => None,
+    }
+}
+
+/// The number passed as parameter `idx`, `None` if it's missing or not a number
+fn param(h: &Helper, idx: usize) -> Option<f64> {
+    h.param(idx).and_then(|param| number(param.value()))
+}
+
+/// `bytes` scaled to the largest unit it's at least one of, `si` uses powers of 1000 instead of
+/// 1024
+fn format_bytes(bytes: f64, precision: usize, si: bool) -> String {
+    let (base, units) = match si {
+        true => (1000., ["B", "kB", "MB", "GB", "TB", "PB"]),
+        false => (1024., ["B", "KiB", "MiB", "GiB", "TiB", "PiB"]),
+    };
+    let mut value = bytes.abs();
+    let mut unit = 0;
+    while value >= base && unit < units.len() - 1 {
+        value /= base;
+        unit += 1;
+    }
+    let sign = if bytes < 0. { "-" } else { "" };
+    match unit {
+        // There are no fractions of a byte
+        0 => format!("{sign}{value} {}", units[unit]),
+        _ => format!("{sign}{value:.precision$} {}", units[unit]),
+    }
+}
+
+/// `seconds` like `1h 23m` (the two largest units) or like a clock with the `clock` style
+fn format_duration(seconds: f64, style: &str) -> String {
+    let sign = if seconds < 0. { "-" } else { "" };
+    let total = seconds.abs().floor() as u64;
+    let (days, hours, minutes, seconds) = (
+        total / 86400,
+        total / 3600 % 24,
+        total / 60 % 60,
+        total % 60,
+    );
+    if style == "clock" {
+        let hours = total / 3600;
+        return match hours {
+            0 => format!("{sign}{minutes:02}:{seconds:02}"),
+            _ => format!("{sign}{hours:02}:{minutes:02}:{seconds:02}"),
+        };
+    }
+    let parts = [(days, "d"), (hours, "h"), (minutes, "m"), (seconds, "s")];
+    let largest = parts
+        .iter()
+        .position(|(value, _)| *value > 0)
+        .unwrap_or(parts.len() - 1);
+    let shown = parts[largest..]
+        .iter()
+        .take(2)
+        .filter(|(value, _)| *value > 0)
+        .map(|(value, unit)| format!("{value}{unit}"))
+        .collect::<Vec<_>>();
+    match shown.is_empty() {
+        true => "0s".to_string(),
+        false => format!("{sign}{}", shown.join(" ")),
+    }
+}
+
+/// {{bytes value [precision] [si=true]}}, `precision` defaults to 1
+fn bytes(
+    h: &Helper,
+    _: &Handlebars,
+    _: &Context,
+    _: &mut RenderContext,
+    out: &mut dyn Output,
+) -> HelperResult {
+    let Some(value) = param(h, 0) else {
+        return Ok(());
+    };
+    let precision = param(h, 1).map(|p| p.max(0.) as usize).unwrap_or(1);
+    let si = h
+        .hash_get("si")
+        .and_then(|si| si.value().as_bool())
+        .unwrap_or(false);
+    out.write(&format_bytes(value, precision, si))?;
+    Ok(())
+}
+
+/// {{duration seconds [style]}}, the style is `short` (default) or `clock`
+fn duration(
+    h: &Helper,
+    _: &Handlebars,
+    _: &Context,
+    _: &mut RenderContext,
+    out: &mut dyn Output,
+) -> HelperResult {
+    let Some(seconds) = param(h, 0) else {
+        return Ok(());
+    };
+    let style = h
+        .param(1)
+        .and_then(|style| style.value().as_str())
+        .unwrap_or("short");
+    out.write(&format_duration(seconds, style))?;
+    Ok(())
+}
+
+/// {{pad value [width]}}, pads the rounded value with zeros to `width` (default 2) digits
+fn pad(
+    h: &Helper,
+    _: &Handlebars,
+    _: &Context,
+    _: &mut RenderContext,
+    out: &mut dyn Output,
+) -> HelperResult {
+    let Some(value) = param(h, 0) else {
+        return Ok(());
+    };
+    let width = param(h, 1).map(|w| w.max(0.) as usize).unwrap_or(2);
+    let value = value.round() as i64;
+    let sign = if value < 0 { "-" } else { "" };
+    out.write(&format!("{sign}{:0width$}", value.unsigned_abs()))?;
+    Ok(())
+}
+
+/// {{round value [precision]}}, `precision` defaults to 0
+fn round(
+    h: &Helper,
+    _: &Handlebars,
+    _: &Context,
+    _: &mut RenderContext,
+    out: &mut dyn Output,
+) -> HelperResult {
+    let Some(value) = param(h, 0) else {
+        return Ok(());
+    };
+    let precision = param(h, 1).map(|p| p.max(0.) as usize).unwrap_or(0);
+    out.write(&format!("{value:.precision$}"))?;
+    Ok(())
+}
+
+/// Registers the helpers which can be used in the templates of every module. Missing values and
+/// values which aren't numbers render as nothing instead of failing the whole template.
+pub fn register_helpers(templates: &mut Handlebars) {
+    // {{plural days "day"}} renders `1 day` or `2 days`, irregular plurals can be given with
+    // {{plural count "child" many="children"}}
+    templates.register_helper("plural", Box::new(plural));
+    // {{bytes used}} renders `1.5 GiB`, {{bytes used 2 si=true}} renders `1.61 GB`
+    templates.register_helper("bytes", Box::new(bytes));
+    // {{duration seconds}} renders `1h 23m`, {{duration seconds "clock"}} renders `01:23:45`
+    templates.register_helper("duration", Box::new(duration));
+    // {{pad minutes}} renders `05`, {{pad n 3}} renders `005`
+    templates.register_helper("pad", Box::new(pad));
+    // {{round load 2}} renders `0.42`
+    templates.register_helper("round", Box::new(round));
+}
+
+/// The templates of the module instances. Every instance registers its templates (like
+/// `disk_usage`) in a registry of its own, so that instances of the same module, like
+/// `disk_usage.root` and `disk_usage.home`, can have different formats.
+#[derive(Debug)]
+pub struct Templates {
+    instances: HashMap<String, Handlebars<'static>>,
+    /// Used by instances which didn't register any templates
+    shared: Handlebars<'static>,
+}
+
+impl Default for Templates {
+    fn default() -> Self {
+        Self {
+            instances: HashMap::new(),
+            shared: with_helpers(),
+        }
+    }
+}
+
+impl Templates {
+    /// The templates of the module instance named `instance`
+    pub fn get(&self, instance: &str) -> &Handlebars<'static> {
+        self.instances.get(instance).unwrap_or(&self.shared)
+    }
+
+    /// The templates of the module instance named `instance`, to register them while reading
+    /// its config
+    pub fn get_mut(&mut self, instance: &str) -> &mut Handlebars<'static> {
+        self.instances
+            .entry(instance.to_string())
+            .or_insert_with(with_helpers)
+    }
+}
+
+fn with_helpers() -> Handlebars<'static> {
+    let mut templates = Handlebars::new();
+    register_helpers(&mut templates);
+    templates
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn render(template: &str, ctx: Value) -> String {
+        with_helpers().render_template(template, &ctx).unwrap()
+    }
+
+    #[test]
+    fn bytes_scale_at_the_unit_boundary() {
+        assert_eq!(format_bytes(0., 1, false), "0 B");
+        assert_eq!(format_bytes(1023., 1, false), "1023 B");
+        assert_eq!(format_bytes(1024., 1, false), "1.0 KiB");
+        assert_eq!(format_bytes(1024. * 1024. - 1., 1, false), "1024.0 KiB");
+        assert_eq!(format_bytes(1024. * 1024., 2, false), "1.00 MiB");
+        assert_eq!(format_bytes(999., 1, true), "999 B");
+        assert_eq!(format_bytes(1000., 1, true), "1.0 kB");
+        assert_eq!(format_bytes(1.5e9, 1, true), "1.5 GB");
+    }
+
+    #[test]
+    fn bytes_keep_the_sign() {
+        assert_eq!(format_bytes(-1024., 1, false), "-1.0 KiB");
+        assert_eq!(format_bytes(-1., 1, false), "-1 B");
+    }
+
+    #[test]
+    fn durations() {
+        assert_eq!(format_duration(0., "short"), "0s");
+        assert_eq!(format_duration(59., "short"), "59s");
+        assert_eq!(format_duration(60., "short"), "1m");
+        assert_eq!(format_duration(3600. + 23. * 60. + 5., "short"), "1h 23m");
+        assert_eq!(format_duration(86400. + 3600., "short"), "1d 1h");
+        assert_eq!(format_duration(86400. + 59., "short"), "1d");
+        assert_eq!(format_duration(-90., "short"), "-1m 30s");
+        assert_eq!(format_duration(0., "clock"), "00:00");
+        assert_eq!(format_duration(225.7, "clock"), "03:45");
+        assert_eq!(format_duration(5025., "clock"), "01:23:45");
+        assert_eq!(format_duration(-65., "clock"), "-01:05");
+    }
+
+    #[test]
+    fn helpers_in_templates() {
+        let ctx = json!({
+            "size": 1536,
+            "secs": 5025,
+            "minutes": "5",
+            "delta": -3,
+            "load": 0.4251,
+        });
+        assert_eq!(render("{{bytes size}}", ctx.clone()), "1.5 KiB");
+        assert_eq!(render("{{bytes size 0 si=true}}", ctx.clone()), "2 kB");
+        assert_eq!(render("{{duration secs}}", ctx.clone()), "1h 23m");
+        assert_eq!(
+            render("{{duration secs \"clock\"}}", ctx.clone()),
+            "01:23:45"
+        );
+        assert_eq!(render("{{pad minutes}}", ctx.clone()), "05");
+        assert_eq!(render("{{pad delta 3}}", ctx.clone()), "-003");
+        assert_eq!(render("{{round load 2}}", ctx.clone()), "0.43");
+        assert_eq!(render("{{round load}}", ctx), "0");
+    }
+
+    #[test]
+    fn missing_and_invalid_values_render_nothing() {
+        let ctx = json!({ "text": "abc", "list": [1, 2] });
+        for helper in ["bytes", "duration", "pad", "round"] {
+            assert_eq!(
+                render(&format!("[{{{{{helper} missing}}}}]"), ctx.clone()),
+                "[]"
+            );
+            assert_eq!(
+                render(&format!("[{{{{{helper} text}}}}]"), ctx.clone()),
+                "[]"
+            );
+            assert_eq!(
+                render(&format!("[{{{{{helper} list}}}}]"), ctx.clone()),
+                "[]"
+            );
+        }
+    }
+}
@@ -0,0 +1,36 @@
+use std::{
+    collections::HashSet,
+    sync::{LazyLock, Mutex, OnceLock},
+    time::Instant,
+};
+
+/// When bar-rs was started, only set with `--timings`
+static START: OnceLock<Instant> = OnceLock::new();
+/// The events which were already logged
+static LOGGED: LazyLock<Mutex<HashSet<String>>> = LazyLock::new(|| Mutex::new(HashSet::new()));
+
+/// Starts the clock the timings are measured with, called once at startup with `--timings`
+pub fn enable() {
+    START.get_or_init(Instant::now);
+}
+
+/// Logs the time since startup the first time `event` happens, e.g. `first frame`. Does nothing
+/// without `--timings`.
+pub fn once(event: &str) {
+    let Some(start) = START.get() else {
+        return;
+    };
+    if LOGGED.lock().unwrap().insert(event.to_string()) {
+        println!(
+            "[timings] {event} after {:.1}ms",
+            start.elapsed().as_secs_f64() * 1000.
+        );
+    }
+}
+
+/// Logs when the module instance `name` got its first data from its subscription
+pub fn first_data(name: &str) {
+    if START.get().is_some() {
+        once(&format!("{name}: first data"));
+    }
+}
@@ -0,0 +1,238 @@
+use std::{
+    collections::HashMap,
+    env, fs,
+    path::{Path, PathBuf},
+    sync::{LazyLock, Mutex},
+    thread,
+};
+
+use iced::{
+    futures::SinkExt,
+    stream,
+    widget::{image, svg},
+    Element, Subscription,
+};
+use regex::{Regex, RegexBuilder};
+use tokio::sync::Notify;
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::{
+    config::{
+        parse::StringExt,
+        schema::{ConfigKey, Kind},
+    },
+    Message,
+};
+
+/// Icon paths by app id and size. Looking them up requires scanning all desktop entries, so it's
+/// done in the background instead of while drawing the bar.
+static ICONS: LazyLock<Mutex<HashMap<(String, u16), IconLookup>>> = LazyLock::new(Default::default);
+/// Wakes [icon_subscription] once an icon was looked up
+static LOOKED_UP: LazyLock<Notify> = LazyLock::new(Notify::new);
+
+#[derive(Debug)]
+enum IconLookup {
+    Pending,
+    Done(Option<PathBuf>),
+}
+
+/// Title rewriting, truncation and app icons shared by the window modules.
+#[derive(Debug)]
+pub struct WindowTitle {
+    show_icon: bool,
+    max_length: usize,
+    /// Fixed titles by (lowercase) app id / class
+    apps: HashMap<String, String>,
+    patterns: Vec<(Regex, String)>,
+}
+
+impl Default for WindowTitle {
+    fn default() -> Self {
+        Self {
+            show_icon: false,
+            max_length: 25,
+            apps: HashMap::new(),
+            patterns: vec![],
+        }
+    }
+}
+
+impl WindowTitle {
+    pub const KEYS: &'static [ConfigKey] = &[
+        ConfigKey::new("show_icon", Kind::Bool, Some("false")),
+        ConfigKey::new("max_length", Kind::Integer, Some("25")),
+        ConfigKey::new("rewrite.*", Kind::Text, None),
+    ];
+
+    pub fn read_config(&mut self, config: &HashMap<String, Option<String>>) {
+        let default = Self::default();
+        self.show_icon = config
+            .get("show_icon")
+            .and_then(|v| v.into_bool())
+            .unwrap_or(default.show_icon);
+        self.max_length = config
+            .get("max_length")
+            .and_then(|v| v.as_ref().and_then(|v| v.parse().ok()))
+            .unwrap_or(default.max_length);
+        self.apps.clear();
+        self.patterns.clear();
+        let mut rewrites = config
+            .iter()
+            .filter_map(|(key, value)| {
+                key.strip_prefix("rewrite.")
+                    .map(|target| (target, value.clone().unwrap_or_default()))
+            })
+            .collect::<Vec<_>>();
+        // The config doesn't keep the order of the options, so at least make it deterministic
+        rewrites.sort();
+        for (target, replacement) in rewrites {
+            match target.strip_prefix('"').and_then(|t| t.strip_suffix('"')) {
+                // Option names are lowercased by the config parser
+                Some(pattern) => match RegexBuilder::new(pattern).case_insensitive(true).build() {
+                    Ok(regex) => self.patterns.push((regex, replacement)),
+                    Err(e) => eprintln!("Failed to parse title rewrite `{pattern}`: {e}"),
+                },
+                None => {
+                    self.apps.insert(target.to_lowercase(), replacement);
+                }
+            }
+        }
+    }
+
+    /// The title with the rewrite rules applied, but not yet truncated
+    pub fn full(&self, app_id: Option<&str>, title: &str) -> String {
+        if let Some(title) = app_id.and_then(|app| self.apps.get(&app.to_lowercase())) {
+            return title.clone();
+        }
+        self.patterns
+            .iter()
+            .find(|(regex, _)| regex.is_match(title))
+            .map(|(regex, replacement)| regex.replace(title, replacement).into_owned())
+            .unwrap_or_else(|| title.to_string())
+    }
+
+    /// The rewritten title, truncated to `max_length`. Lengths are counted in graphemes, so
+    /// that accents and emoji sequences aren't cut apart.
+    pub fn trimmed(&self, app_id: Option<&str>, title: &str) -> String {
+        let title = self.full(app_id, title);
+        match title.graphemes(true).count() > self.max_length {
+            true => format!(
+                "{}...",
+                title
+                    .graphemes(true)
+                    .take(self.max_length.saturating_sub(3))
+                    .collect::<String>()
+            ),
+            false => title,
+        }
+    }
+
+    /// Whether the rewritten title is too long to be shown completely
+    pub fn is_truncated(&self, app_id: Option<&str>, title: &str) -> bool {
+        self.full(app_id, title).graphemes(true).count() > self.max_length
+    }
+
+    /// The icon of the given app, if `show_icon` is enabled and an icon could be found
+    pub fn icon<'a>(&self, app_id: Option<&str>, size: f32) -> Option<Element<'a, Message>> {
+        if !self.show_icon {
+            return None;
+        }
+        let path = cached_icon(app_id?, size as u16)?;
+        Some(match path.extension().is_some_and(|ext| ext == "svg") {
+            true => svg(svg::Handle::from_path(path))
+                .width(size)
+                .height(size)
+                .into(),
+            false => image(image::Handle::from_path(path))
+                .width(size)
+                .height(size)
+                .into(),
+        })
+    }
+}
+
+/// The icon of the app if it was looked up already, otherwise it's looked up in the background
+/// and the bar is redrawn by [icon_subscription] once it's known
+fn cached_icon(app_id: &str, size: u16) -> Option<PathBuf> {
+    let key = (app_id.to_string(), size);
+    let mut icons = ICONS.lock().ok()?;
+    match icons.get(&key) {
+        Some(IconLookup::Done(path)) => path.clone(),
+        Some(IconLookup::Pending) => None,
+        None => {
+            icons.insert(key.clone(), IconLookup::Pending);
+            thread::spawn(move || {
+                let path = find_icon(&key.0, size);
+                if let Ok(mut icons) = ICONS.lock() {
+                    icons.insert(key, IconLookup::Done(path));
+                }
+                LOOKED_UP.notify_one();
+            });
+            None
+        }
+    }
+}
+
+/// Redraws the bar whenever icons were looked up, so that they're shown
+pub fn icon_subscription() -> Subscription<Message> {
+    Subscription::run_with_id(
+        "window_icons",
+        stream::channel(1, |mut sender| async move {
+            loop {
+                LOOKED_UP.notified().await;
+                if sender.send(Message::update(|_| {})).await.is_err() {
+                    return;
+                }
+            }
+        }),
+    )
+}
+
+fn find_icon(app_id: &str, size: u16) -> Option<PathBuf> {
+    let icon = desktop_entry_icon(app_id).unwrap_or_else(|| app_id.to_lowercase());
+    if Path::new(&icon).is_absolute() {
+        return Some(PathBuf::from(icon));
+    }
+    freedesktop_icons::lookup(&icon)
+        .with_size(size)
+        .with_cache()
+        .find()
+}
+
+/// Find the `Icon` of the desktop entry belonging to the given app id or window class
+fn desktop_entry_icon(app_id: &str) -> Option<String> {
+    let app_id = app_id.to_lowercase();
+    let data_home = env::var("XDG_DATA_HOME").ok().or_else(|| {
+        env::var("HOME")
+            .ok()
+            .map(|home| format!("{home}/.local/share"))
+    });
+    let data_dirs =
+        env::var("XDG_DATA_DIRS").unwrap_or_else(|_| "/usr/local/share:/usr/share".to_string());
+    data_home
+        .into_iter()
+        .chain(data_dirs.split(':').map(|dir| dir.to_string()))
+        .filter_map(|dir| fs::read_dir(Path::new(&dir).join("applications")).ok())
+        .flat_map(|entries| entries.filter_map(|e| e.ok().map(|e| e.path())))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "desktop"))
+        .find_map(|path| {
+            let content = fs::read_to_string(&path).ok()?;
+            // e.g. `org.mozilla.firefox.desktop` for the app id `firefox`
+            let name_matches = path.file_stem().is_some_and(|stem| {
+                let stem = stem.to_string_lossy().to_lowercase();
+                stem == app_id || stem.rsplit('.').next() == Some(app_id.as_str())
+            });
+            let class_matches = content.lines().any(|line| {
+                line.strip_prefix("StartupWMClass=")
+                    .is_some_and(|class| class.trim().to_lowercase() == app_id)
+            });
+            (name_matches || class_matches)
+                .then(|| {
+                    content
+                        .lines()
+                        .find_map(|line| line.strip_prefix("Icon="))
+                        .map(|icon| icon.trim().to_string())
+                })
+                .flatten()
+        })
+}
@@ -0,0 +1,314 @@
+use std::collections::HashMap;
+
+use iced::{
+    widget::{button::Style, container, row, scrollable, text, Column},
+    Alignment, Background, Color, Element,
+    Length::Fill,
+};
+
+use crate::{
+    button::button,
+    config::{
+        parse::StringExt,
+        popup_config::{PopupConfig, PopupConfigOverride},
+        scale::Scale,
+        schema::{ConfigKey, Kind},
+    },
+    helpers::window::WindowTitle,
+    Message,
+};
+
+/// The state of a workspace as reported by the compositor
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WorkspaceState {
+    Empty,
+    Occupied,
+    /// A window on the workspace demands attention
+    Urgent,
+    /// Shown on an output which isn't focused
+    Visible,
+    Focused,
+}
+
+impl WorkspaceState {
+    pub fn new(focused: bool, urgent: bool, occupied: bool) -> Self {
+        match (focused, urgent, occupied) {
+            (true, _, _) => WorkspaceState::Focused,
+            (_, true, _) => WorkspaceState::Urgent,
+            (_, _, true) => WorkspaceState::Occupied,
+            _ => WorkspaceState::Empty,
+        }
+    }
+
+    /// For compositors with a workspace per output: marks a workspace which is shown on an
+    /// output that isn't focused, unless it's focused or urgent
+    pub fn visible(self, visible: bool) -> Self {
+        match (self, visible) {
+            (WorkspaceState::Empty | WorkspaceState::Occupied, true) => WorkspaceState::Visible,
+            _ => self,
+        }
+    }
+
+    /// The name of the state, as used in config keys and templates
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            WorkspaceState::Empty => "empty",
+            WorkspaceState::Occupied => "occupied",
+            WorkspaceState::Urgent => "urgent",
+            WorkspaceState::Visible => "visible",
+            WorkspaceState::Focused => "focused",
+        }
+    }
+}
+
+/// How a workspace in a certain state is drawn, `None` keeps the style of the icons
+#[derive(Debug, Default, Clone, Copy)]
+pub struct StateStyle {
+    pub color: Option<Color>,
+    pub background: Option<Background>,
+}
+
+/// The styles of the workspaces which aren't focused, the focused one is styled by the `active_*`
+/// options of the workspace modules
+#[derive(Debug)]
+pub struct WorkspaceStyles {
+    empty: StateStyle,
+    occupied: StateStyle,
+    urgent: StateStyle,
+    visible: StateStyle,
+}
+
+impl Default for WorkspaceStyles {
+    fn default() -> Self {
+        Self {
+            empty: StateStyle::default(),
+            occupied: StateStyle::default(),
+            urgent: StateStyle {
+                color: Some(Color::from_rgb8(255, 85, 85)),
+                background: None,
+            },
+            visible: StateStyle {
+                color: None,
+                background: Some(Background::Color(Color::from_rgba(1., 1., 1., 0.2))),
+            },
+        }
+    }
+}
+
+/// The options of the workspace icons of `hyprland.workspaces` and `niri.workspaces`
+pub const ICON_KEYS: &[ConfigKey] = &[
+    ConfigKey::new("icon_padding", Kind::Insets, Some("0")),
+    ConfigKey::new("icon_background", Kind::Color, None),
+    ConfigKey::new("icon_border_color", Kind::Color, None),
+    ConfigKey::new("icon_border_width", Kind::Float, None),
+    ConfigKey::new("icon_border_radius", Kind::Insets, None),
+    ConfigKey::new("active_padding", Kind::Insets, None),
+    ConfigKey::new("active_size", Kind::Float, Some("20")),
+    ConfigKey::new("active_color", Kind::Color, Some("white")),
+    ConfigKey::new("active_background", Kind::Color, None),
+    ConfigKey::new("active_border_color", Kind::Color, None),
+    ConfigKey::new("active_border_width", Kind::Float, None),
+    ConfigKey::new("active_border_radius", Kind::Insets, None),
+];
+
+impl WorkspaceStyles {
+    pub const KEYS: &'static [ConfigKey] = &[
+        ConfigKey::new("empty_color", Kind::Color, None),
+        ConfigKey::new("empty_background", Kind::Color, None),
+        ConfigKey::new("occupied_color", Kind::Color, None),
+        ConfigKey::new("occupied_background", Kind::Color, None),
+        ConfigKey::new("urgent_color", Kind::Color, Some("rgb(255, 85, 85)")),
+        ConfigKey::new("urgent_background", Kind::Color, None),
+    ];
+
+    /// The options of the `visible` state, for the modules which report it
+    pub const VISIBLE_KEYS: &'static [ConfigKey] = &[
+        ConfigKey::new("visible_color", Kind::Color, None),
+        ConfigKey::new(
+            "visible_background",
+            Kind::Color,
+            Some("rgba(255, 255, 255, 0.2)"),
+        ),
+    ];
+
+    pub fn read_config(&mut self, config: &HashMap<String, Option<String>>) {
+        let default = Self::default();
+        let read = |state: WorkspaceState, default: StateStyle| StateStyle {
+            color: config
+                .get(&format!("{}_color", state.as_str()))
+                .and_then(|v| v.into_color())
+                .or(default.color),
+            background: config
+                .get(&format!("{}_background", state.as_str()))
+                .map(|v| v.into_background())
+                .unwrap_or(default.background),
+        };
+        self.empty = read(WorkspaceState::Empty, default.empty);
+        self.occupied = read(WorkspaceState::Occupied, default.occupied);
+        self.urgent = read(WorkspaceState::Urgent, default.urgent);
+        self.visible = read(WorkspaceState::Visible, default.visible);
+    }
+
+    pub fn get(&self, state: WorkspaceState) -> StateStyle {
+        match state {
+            WorkspaceState::Empty => self.empty,
+            WorkspaceState::Occupied => self.occupied,
+            WorkspaceState::Urgent => self.urgent,
+            WorkspaceState::Visible => self.visible,
+            WorkspaceState::Focused => StateStyle::default(),
+        }
+    }
+}
+
+/// A window listed in the [WorkspaceOverview]
+pub struct OverviewWindow<'a> {
+    pub app_id: Option<&'a str>,
+    pub title: &'a str,
+    /// Focuses the window
+    pub on_press: Message,
+}
+
+/// A workspace listed in the [WorkspaceOverview]
+pub struct OverviewWorkspace<'a> {
+    pub label: String,
+    pub state: WorkspaceState,
+    pub windows: Vec<OverviewWindow<'a>>,
+}
+
+/// The popup of the workspace modules, which lists every workspace with its windows
+#[derive(Debug)]
+pub struct WorkspaceOverview {
+    pub popup_cfg_override: PopupConfigOverride,
+    titles: WindowTitle,
+    /// The popup grows with its content up to this height, `None` for the popup `height`
+    max_height: Option<i32>,
+    focused_color: Option<Color>,
+    focused_background: Option<Background>,
+}
+
+impl Default for WorkspaceOverview {
+    fn default() -> Self {
+        Self {
+            popup_cfg_override: PopupConfigOverride::default(),
+            titles: WindowTitle::default(),
+            max_height: None,
+            focused_color: None,
+            focused_background: Some(Background::Color(Color::from_rgba(1., 1., 1., 0.2))),
+        }
+    }
+}
+
+impl WorkspaceOverview {
+    /// The options of the popup besides the ones of [WindowTitle]
+    pub const KEYS: &'static [ConfigKey] = &[
+        ConfigKey::new("max_height", Kind::Integer, None),
+        ConfigKey::new("active_color", Kind::Color, None),
+        ConfigKey::new(
+            "active_background",
+            Kind::Color,
+            Some("rgba(255, 255, 255, 0.2)"),
+        ),
+    ];
+
+    pub fn read_config(&mut self, popup_config: &HashMap<String, Option<String>>, scale: f32) {
+        let default = Self::default();
+        self.popup_cfg_override.update(popup_config, scale);
+        self.titles.read_config(popup_config);
+        self.max_height = popup_config
+            .get("max_height")
+            .and_then(|v| v.as_ref().and_then(|v| v.parse::<i32>().ok()))
+            .scaled(scale);
+        self.focused_color = popup_config
+            .get("active_color")
+            .and_then(|v| v.into_color())
+            .or(default.focused_color);
+        self.focused_background = popup_config
+            .get("active_background")
+            .map(|v| v.into_background())
+            .unwrap_or(default.focused_background);
+    }
+
+    pub fn width(&self, config: &PopupConfig) -> i32 {
+        self.popup_cfg_override.width.unwrap_or(config.width)
+    }
+
+    /// The height the popup needs to show `rows` workspaces and windows, up to
+    /// `max_height`
+    pub fn height(&self, config: &PopupConfig, rows: usize) -> i32 {
+        let cfg = &self.popup_cfg_override;
+        let max = self
+            .max_height
+            .unwrap_or(cfg.height.unwrap_or(config.height));
+        let padding = cfg.padding.unwrap_or(config.padding);
+        let margin = cfg.text_margin.unwrap_or(config.text_margin);
+        let line = cfg.font_size.unwrap_or(config.font_size) * 1.3 + margin.top + margin.bottom;
+        let spacing = cfg.spacing.unwrap_or(config.spacing);
+        let border = cfg.border.unwrap_or(config.border).width;
+        let content = padding.top
+            + padding.bottom
+            + 2. * border
+            + rows as f32 * line
+            + rows.saturating_sub(1) as f32 * spacing;
+        (content.ceil() as i32).min(max)
+    }
+
+    pub fn view<'a>(
+        &'a self,
+        config: &'a PopupConfig,
+        workspaces: Vec<OverviewWorkspace<'a>>,
+    ) -> Element<'a, Message> {
+        let cfg = &self.popup_cfg_override;
+        let text_color = cfg.text_color.unwrap_or(config.text_color);
+        let font_size = cfg.font_size.unwrap_or(config.font_size);
+        let text_margin = cfg.text_margin.unwrap_or(config.text_margin);
+        let label = |content: String, color: Color| {
+            container(text(content).size(font_size).color(color)).padding(text_margin)
+        };
+        let rows = workspaces.into_iter().flat_map(|ws| {
+            let focused = ws.state == WorkspaceState::Focused;
+            let header: Element<'a, Message> = container(label(
+                ws.label,
+                match focused {
+                    true => self.focused_color.unwrap_or(text_color),
+                    false => text_color,
+                },
+            ))
+            .width(Fill)
+            .style(move |_| container::Style {
+                background: focused.then_some(self.focused_background).flatten(),
+                ..Default::default()
+            })
+            .into();
+            let windows = ws
+                .windows
+                .into_iter()
+                .map(|window| -> Element<'a, Message> {
+                    let app: Element<'a, Message> = self
+                        .titles
+                        .icon(window.app_id, font_size)
+                        .unwrap_or_else(|| {
+                            label(window.app_id.unwrap_or_default().to_string(), text_color).into()
+                        });
+                    button(
+                        row![
+                            app,
+                            label(self.titles.trimmed(window.app_id, window.title), text_color)
+                                .width(Fill),
+                        ]
+                        .spacing(cfg.spacing.unwrap_or(config.spacing))
+                        .align_y(Alignment::Center),
+                    )
+                    .padding([0, 16])
+                    .on_event(window.on_press)
+                    .style(|_, _| Style::default())
+                    .width(Fill)
+                    .into()
+                });
+            [header].into_iter().chain(windows).collect::<Vec<_>>()
+        });
+        container(scrollable(
+            Column::with_children(rows).spacing(cfg.spacing.unwrap_or(config.spacing)),
+        ))
+        .into()
+    }
+}
@@ -0,0 +1,15 @@
+//! What the bar shares with `bar-rsctl`
+
+use std::{env, path::PathBuf};
+
+/// The socket bar-rs listens on for ipc commands, `$XDG_RUNTIME_DIR/bar-rs.sock`, or
+/// `bar-rs-{monitor}.sock` for the instance started with `--monitor`
+pub fn socket_path(monitor: Option<&str>) -> PathBuf {
+    env::var("XDG_RUNTIME_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| env::temp_dir())
+        .join(match monitor {
+            Some(monitor) => format!("bar-rs-{monitor}.sock"),
+            None => "bar-rs.sock".to_string(),
+        })
+}
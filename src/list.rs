@@ -1,5 +1,5 @@
 use iced::{
-    widget::{column, row, Column, Container, Row},
+    widget::{column, row, Column, Container, Row, Space},
     Alignment, Element, Padding, Pixels,
 };
 
@@ -90,6 +90,25 @@ where
     }
 }
 
+/// Makes `content` at least `min` long along the bar, aligned by `align` within that length
+pub fn min_length<'a, Message: 'a>(
+    anchor: &BarAnchor,
+    content: impl Into<Element<'a, Message>>,
+    min: f32,
+    align: Alignment,
+) -> Element<'a, Message> {
+    if min <= 0. {
+        return content.into();
+    }
+    // A column is as wide as its widest child, a row as high as its highest one
+    match anchor.vertical() {
+        true => row![Space::with_height(min), content].align_y(align).into(),
+        false => column![Space::with_width(min), content]
+            .align_x(align)
+            .into(),
+    }
+}
+
 macro_rules! list {
     ($anchor:expr) => (
         $crate::list::List::new($anchor)
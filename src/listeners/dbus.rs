@@ -0,0 +1,281 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{LazyLock, Mutex, MutexGuard},
+};
+
+use bar_rs_derive::Builder;
+use iced::{
+    futures::{channel::mpsc::Sender, SinkExt},
+    stream, Subscription,
+};
+use serde_json::Value;
+use tokio::sync::mpsc::{self, UnboundedSender};
+use zbus::{
+    dbus_interface, fdo,
+    zvariant::{self, OwnedValue},
+    Connection,
+};
+
+use crate::{
+    config::{output::monitor_arg, Config, ConfigEntry},
+    helpers::templates::Templates,
+    registry::Registry,
+    Message,
+};
+
+use super::Listener;
+
+const PATH: &str = "/org/bar_rs/Bar1";
+
+static EXPORTER: LazyLock<Mutex<Exporter>> = LazyLock::new(Default::default);
+
+/// What was exported last, so that only changes are published
+#[derive(Debug, Default)]
+struct Exporter {
+    /// Set while the service runs
+    sender: Option<UnboundedSender<Export>>,
+    modules: BTreeMap<String, ModuleExport>,
+}
+
+fn exporter() -> MutexGuard<'static, Exporter> {
+    EXPORTER.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// The data of a module instance which is exported
+#[derive(Debug, Clone, PartialEq)]
+struct ModuleExport {
+    text: String,
+    variables: BTreeMap<String, Value>,
+}
+
+/// The changes since the last export
+#[derive(Debug)]
+struct Export {
+    /// All enabled module instances
+    modules: Vec<String>,
+    changed: Vec<(String, ModuleExport)>,
+    removed: Vec<String>,
+}
+
+/// Publishes the modules which changed since the last call over D-Bus, called after every update
+/// of the registry
+pub fn publish(registry: &Registry, config: &Config, templates: &Templates) {
+    let mut exporter = exporter();
+    let Some(sender) = exporter.sender.clone() else {
+        return;
+    };
+    let modules = registry
+        .get_modules(config.enabled_modules.get_all(), config)
+        .map(|(name, module)| {
+            let export = ModuleExport {
+                text: module
+                    .text_content(templates.get(&name))
+                    .and_then(|text| text.ok())
+                    .unwrap_or_default(),
+                variables: module.variables(),
+            };
+            (name, export)
+        })
+        .collect::<BTreeMap<_, _>>();
+    let changed = modules
+        .iter()
+        .filter(|(name, export)| exporter.modules.get(*name) != Some(*export))
+        .map(|(name, export)| (name.clone(), export.clone()))
+        .collect::<Vec<_>>();
+    let removed = exporter
+        .modules
+        .keys()
+        .filter(|name| !modules.contains_key(*name))
+        .cloned()
+        .collect::<Vec<_>>();
+    if changed.is_empty() && removed.is_empty() {
+        return;
+    }
+    let export = Export {
+        modules: modules.keys().cloned().collect(),
+        changed,
+        removed,
+    };
+    exporter.modules = modules;
+    if sender.send(export).is_err() {
+        // The service stopped, it logged why
+        exporter.sender = None;
+    }
+}
+
+/// Serves `org.bar_rs.Bar1` on the session bus, with the data of every enabled module
+#[derive(Debug, Builder)]
+pub struct DbusListener;
+
+impl Listener for DbusListener {
+    fn config(&self) -> Vec<ConfigEntry> {
+        vec![ConfigEntry::new("general", "dbus", true)]
+    }
+
+    fn subscription(&self) -> Subscription<Message> {
+        Subscription::run(|| {
+            stream::channel(1, |sender| async move {
+                if let Err(e) = serve(sender).await {
+                    eprintln!("Failed to export the modules over D-Bus: {e}");
+                }
+            })
+        })
+    }
+}
+
+/// `org.bar_rs.Bar1`, or `org.bar_rs.Bar1.{monitor}` if bar-rs was started with `--monitor`
+fn bus_name() -> String {
+    match monitor_arg() {
+        Some(monitor) => format!(
+            "org.bar_rs.Bar1.{}",
+            monitor.replace(|c: char| !c.is_ascii_alphanumeric(), "_")
+        ),
+        None => "org.bar_rs.Bar1".to_string(),
+    }
+}
+
+/// The object path of a module instance, characters which aren't allowed are escaped like `_2e`
+fn object_path(module: &str) -> String {
+    let escaped = module
+        .chars()
+        .map(|c| match c.is_ascii_alphanumeric() {
+            true => c.to_string(),
+            false => format!("_{:02x}", c as u32),
+        })
+        .collect::<String>();
+    format!("{PATH}/modules/{escaped}")
+}
+
+struct Bar {
+    modules: Vec<String>,
+    sender: Sender<Message>,
+}
+
+#[dbus_interface(name = "org.bar_rs.Bar1")]
+impl Bar {
+    /// The enabled module instances
+    fn list_modules(&self) -> Vec<String> {
+        self.modules.clone()
+    }
+
+    /// Opens the popup of a module, centered along the bar
+    async fn open_popup(&mut self, module: String) -> fdo::Result<()> {
+        if !self.modules.contains(&module) {
+            return Err(fdo::Error::InvalidArgs(format!(
+                "no enabled module named `{module}`"
+            )));
+        }
+        self.sender
+            .send(Message::OpenPopup(module))
+            .await
+            .map_err(|e| fdo::Error::Failed(e.to_string()))
+    }
+
+    /// Reloads the config file
+    async fn reload(&mut self) -> fdo::Result<()> {
+        self.sender
+            .send(Message::ReloadConfig)
+            .await
+            .map_err(|e| fdo::Error::Failed(e.to_string()))
+    }
+}
+
+struct Module {
+    name: String,
+    export: ModuleExport,
+}
+
+#[dbus_interface(name = "org.bar_rs.Module1")]
+impl Module {
+    #[dbus_interface(property)]
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    /// The text the module shows
+    #[dbus_interface(property)]
+    fn text(&self) -> String {
+        self.export.text.clone()
+    }
+
+    /// The variables of the `format` of the module, numbers are doubles and anything else a
+    /// string
+    #[dbus_interface(property)]
+    fn variables(&self) -> HashMap<String, OwnedValue> {
+        self.export
+            .variables
+            .iter()
+            .map(|(name, value)| {
+                let value = match value {
+                    Value::Number(number) => {
+                        zvariant::Value::from(number.as_f64().unwrap_or_default())
+                    }
+                    Value::String(string) => zvariant::Value::from(string.clone()),
+                    value => zvariant::Value::from(value.to_string()),
+                };
+                (name.clone(), value.into())
+            })
+            .collect()
+    }
+}
+
+async fn serve(mut sender: Sender<Message>) -> zbus::Result<()> {
+    let connection = Connection::session().await?;
+    connection
+        .object_server()
+        .at(
+            PATH,
+            Bar {
+                modules: vec![],
+                sender: sender.clone(),
+            },
+        )
+        .await?;
+    connection.request_name(bus_name()).await?;
+    let bar = connection.object_server().interface::<_, Bar>(PATH).await?;
+
+    let (export_sx, mut exports) = mpsc::unbounded_channel();
+    {
+        let mut exporter = exporter();
+        exporter.sender = Some(export_sx);
+        // Export every module again
+        exporter.modules.clear();
+    }
+    // Publish the modules right away instead of with the next update of any module
+    let _ = sender.send(Message::update(|_| {})).await;
+
+    while let Some(export) = exports.recv().await {
+        for name in export.removed {
+            connection
+                .object_server()
+                .remove::<Module, _>(object_path(&name))
+                .await?;
+        }
+        for (name, export) in export.changed {
+            let path = object_path(&name);
+            let Ok(module) = connection
+                .object_server()
+                .interface::<_, Module>(path.as_str())
+                .await
+            else {
+                connection
+                    .object_server()
+                    .at(path.as_str(), Module { name, export })
+                    .await?;
+                continue;
+            };
+            let mut module_ref = module.get_mut().await;
+            let ctxt = module.signal_context();
+            if module_ref.export.text != export.text {
+                module_ref.export.text = export.text;
+                module_ref.text_changed(ctxt).await?;
+            }
+            if module_ref.export.variables != export.variables {
+                module_ref.export.variables = export.variables;
+                module_ref.variables_changed(ctxt).await?;
+            }
+        }
+        bar.get_mut().await.modules = export.modules;
+    }
+    Ok(())
+}
@@ -1,12 +1,22 @@
 use bar_rs_derive::Builder;
-use hyprland::{data::Client, event_listener::AsyncEventListener, shared::HyprDataActiveOptional};
-use iced::{futures::SinkExt, stream, Subscription};
+use hyprland::{
+    data::{Monitors, Workspaces},
+    event_listener::AsyncEventListener,
+    shared::{HyprData, HyprDataVec},
+};
+use iced::{
+    futures::{channel::mpsc::Sender, SinkExt},
+    stream, Subscription,
+};
 
 use crate::{
-    config::ConfigEntry,
+    config::{output, ConfigEntry},
+    helpers::backoff::Backoff,
     modules::hyprland::{
+        scratchpad::update_scratchpads,
+        submap::HyprSubmapMod,
         window::update_window,
-        workspaces::{get_workspaces, HyprWorkspaceMod},
+        workspaces::{set_urgent, update_workspaces},
     },
     Message,
 };
@@ -17,60 +27,171 @@ use super::Listener;
 pub struct HyprListener;
 
 impl Listener for HyprListener {
+    /// Modules start the listener when they need it, `hide_on_fullscreen` needs it on its own
     fn config(&self) -> Vec<ConfigEntry> {
-        vec![]
+        vec![ConfigEntry::new("general", "hide_on_fullscreen", false)]
     }
     fn subscription(&self) -> Subscription<Message> {
         Subscription::run(|| {
             stream::channel(1, |mut sender| async move {
-                let workspaces = get_workspaces(None).await;
-                sender
-                    .send(Message::update(move |reg| {
-                        let ws = reg.get_module_mut::<HyprWorkspaceMod>();
-                        ws.active = workspaces.0;
-                        ws.open = workspaces.1;
-                    }))
-                    .await
-                    .unwrap_or_else(|err| {
-                        eprintln!("Trying to send workspaces failed with err: {err}");
-                    });
-                if let Ok(window) = Client::get_active_async().await {
-                    update_window(&mut sender, window.map(|w| w.title)).await;
+                update_workspaces(&mut sender, None).await;
+                update_window(&mut sender).await;
+                update_scratchpads(&mut sender).await;
+                update_fullscreen(&mut sender).await;
+
+                // The socket is missing if bar-rs was started before hyprland or outside of it
+                let mut backoff = Backoff::default();
+                loop {
+                    if let Err(e) = event_listener(&sender).start_listener_async().await {
+                        eprintln!("Failed to listen for hyprland events: {e}");
+                    }
+                    backoff.wait().await;
                 }
+            })
+        })
+    }
+}
 
-                let mut listener = AsyncEventListener::new();
+/// Builds a listener which forwards hyprland events to the modules
+fn event_listener(sender: &Sender<Message>) -> AsyncEventListener {
+    let mut listener = AsyncEventListener::new();
 
-                let senderx = sender.clone();
-                listener.add_active_window_changed_handler(move |data| {
-                    let mut sender = senderx.clone();
-                    Box::pin(async move {
-                        update_window(&mut sender, data.map(|window| window.title)).await;
-                    })
-                });
+    // The active window module also shows whether the window is fullscreen or floating, how
+    // many windows its workspace has and its tab in a group, so it's refreshed on all of these
+    // events. Switching tabs changes the active window.
+    macro_rules! refresh_window_on {
+        ($($handler:ident),*) => {$(
+            let senderx = sender.clone();
+            listener.$handler(move |_| {
+                let mut sender = senderx.clone();
+                Box::pin(async move { update_window(&mut sender).await })
+            });
+        )*};
+    }
+    refresh_window_on!(
+        add_active_window_changed_handler,
+        add_fullscreen_state_changed_handler,
+        add_float_state_changed_handler,
+        add_window_opened_handler,
+        add_window_closed_handler,
+        add_window_moved_handler,
+        add_group_toggled_handler,
+        add_window_moved_into_group_handler,
+        add_window_moved_out_of_group_handler
+    );
 
-                let senderx = sender.clone();
-                listener.add_workspace_changed_handler(move |data| {
-                    let mut sender = senderx.clone();
-                    Box::pin(async move {
-                        let workspaces = get_workspaces(Some(data.id)).await;
-                        sender
-                            .send(Message::update(move |reg| {
-                                let ws = reg.get_module_mut::<HyprWorkspaceMod>();
-                                ws.active = workspaces.0;
-                                ws.open = workspaces.1;
-                            }))
-                            .await
-                            .unwrap_or_else(|err| {
-                                eprintln!("Trying to send workspaces failed with err: {err}");
-                            });
-                    })
-                });
+    // Special workspaces are shown while they have windows, `activespecial` reports when one is
+    // shown or hidden
+    macro_rules! refresh_scratchpads_on {
+        ($($handler:ident),*) => {$(
+            let senderx = sender.clone();
+            listener.$handler(move |_| {
+                let mut sender = senderx.clone();
+                Box::pin(async move { update_scratchpads(&mut sender).await })
+            });
+        )*};
+    }
+    refresh_scratchpads_on!(
+        add_window_opened_handler,
+        add_window_closed_handler,
+        add_window_moved_handler,
+        add_changed_special_handler
+    );
 
-                listener
-                    .start_listener_async()
-                    .await
-                    .expect("Failed to listen for hyprland events");
-            })
-        })
+    // The workspaces show whether they have windows and whether a special workspace is shown,
+    // and their overview lists the windows
+    macro_rules! refresh_workspaces_on {
+        ($($handler:ident),*) => {$(
+            let senderx = sender.clone();
+            listener.$handler(move |_| {
+                let mut sender = senderx.clone();
+                Box::pin(async move { update_workspaces(&mut sender, None).await })
+            });
+        )*};
+    }
+    refresh_workspaces_on!(
+        add_window_opened_handler,
+        add_window_closed_handler,
+        add_window_moved_handler,
+        add_window_title_changed_handler,
+        add_changed_special_handler
+    );
+
+    // Whether the bar is hidden for a fullscreen window, see `hide_on_fullscreen`
+    macro_rules! refresh_fullscreen_on {
+        ($($handler:ident),*) => {$(
+            let senderx = sender.clone();
+            listener.$handler(move |_| {
+                let mut sender = senderx.clone();
+                Box::pin(async move { update_fullscreen(&mut sender).await })
+            });
+        )*};
     }
+    refresh_fullscreen_on!(
+        add_fullscreen_state_changed_handler,
+        add_workspace_changed_handler,
+        add_active_monitor_changed_handler,
+        add_window_closed_handler,
+        add_window_moved_handler,
+        add_changed_special_handler
+    );
+
+    let senderx = sender.clone();
+    listener.add_workspace_changed_handler(move |data| {
+        let mut sender = senderx.clone();
+        Box::pin(async move { update_workspaces(&mut sender, Some(data.id)).await })
+    });
+
+    let senderx = sender.clone();
+    listener.add_urgent_state_changed_handler(move |address| {
+        let mut sender = senderx.clone();
+        Box::pin(async move { set_urgent(&mut sender, address).await })
+    });
+
+    let senderx = sender.clone();
+    listener.add_sub_map_changed_handler(move |submap| {
+        let mut sender = senderx.clone();
+        Box::pin(async move {
+            // Leaving a submap is reported as an empty name
+            let submap = (!submap.is_empty()).then_some(submap);
+            sender
+                .send(Message::update(move |reg| {
+                    reg.get_module_mut::<HyprSubmapMod>().submap = submap
+                }))
+                .await
+                .unwrap_or_else(|err| {
+                    eprintln!("Trying to send the submap failed with err: {err}");
+                });
+        })
+    });
+    listener
+}
+
+/// Sends whether a window is fullscreen on the output of the bar, or on the focused monitor as
+/// long as the output isn't known
+async fn update_fullscreen(sender: &mut Sender<Message>) {
+    let (Ok(monitors), Ok(workspaces)) =
+        (Monitors::get_async().await, Workspaces::get_async().await)
+    else {
+        eprintln!("[hyprland] Failed to get the monitors and workspaces");
+        return;
+    };
+    let output = output::output_name(None);
+    let Some(monitor) = monitors.to_vec().into_iter().find(|monitor| match &output {
+        Some(output) => monitor.name == *output,
+        None => monitor.focused,
+    }) else {
+        return;
+    };
+    // A special workspace shown on the monitor covers its regular workspace
+    let fullscreen = workspaces.to_vec().into_iter().any(|ws| {
+        ws.fullscreen
+            && (ws.id == monitor.active_workspace.id || ws.id == monitor.special_workspace.id)
+    });
+    sender
+        .send(Message::Fullscreen(fullscreen))
+        .await
+        .unwrap_or_else(|err| {
+            eprintln!("Trying to send the fullscreen state failed with err: {err}");
+        });
 }
@@ -0,0 +1,239 @@
+use std::str::FromStr;
+
+use bar_rs::socket_path;
+use bar_rs_derive::Builder;
+use iced::{
+    futures::{channel::mpsc::Sender, SinkExt},
+    mouse, stream, Event, Subscription,
+};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    net::{UnixListener, UnixStream},
+    sync::mpsc,
+};
+
+use crate::{
+    auto_hide::AutoHideEvent,
+    config::{
+        get_config,
+        layout::{LayoutEdit, Placement, Side},
+        output::monitor_arg,
+        ConfigEntry,
+    },
+    modules::{Action, ProfileAction, ToggleGroupAction},
+    Message,
+};
+
+use super::Listener;
+
+#[derive(Debug, PartialEq)]
+enum IpcCommand {
+    /// Reload the config file
+    Reload,
+    /// Read the data of a module, or of all modules, again
+    Refresh(Option<String>),
+    /// Hide or show a group or a single module
+    Toggle(String),
+    /// Show the bar until it's idle again, if `auto_hide` is enabled
+    Reveal,
+    /// Switch to another profile
+    Profile(String),
+    /// Trigger the click action of a module
+    Action {
+        module: String,
+        button: mouse::Button,
+    },
+    /// Add, remove or move a module until the config is reloaded
+    Layout(LayoutEdit),
+    /// Print the effective config
+    DumpConfig,
+}
+
+/// Where `add-module` and `move-module` put the module: `--to <side>`, `--after <module>` or
+/// `--before <module>`
+fn placement(args: &[&str]) -> Result<Option<Placement>, String> {
+    match args {
+        [] => Ok(None),
+        ["--to", side] => Ok(Some(Placement::End(side.parse::<Side>()?))),
+        ["--after", module] => Ok(Some(Placement::After(module.to_string()))),
+        ["--before", module] => Ok(Some(Placement::Before(module.to_string()))),
+        _ => Err(format!(
+            "invalid placement `{}`, expected --to <side>, --after <module> or --before <module>",
+            args.join(" ")
+        )),
+    }
+}
+
+impl FromStr for IpcCommand {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let args: Vec<&str> = s.split_whitespace().collect();
+        match args[..] {
+            ["reload"] => Ok(IpcCommand::Reload),
+            ["refresh"] => Ok(IpcCommand::Refresh(None)),
+            ["refresh", module] => Ok(IpcCommand::Refresh(Some(module.to_string()))),
+            ["toggle", name] => Ok(IpcCommand::Toggle(name.to_string())),
+            ["reveal"] => Ok(IpcCommand::Reveal),
+            ["profile", name] => Ok(IpcCommand::Profile(name.to_string())),
+            ["action", module] | ["action", module, "left"] => Ok(IpcCommand::Action {
+                module: module.to_string(),
+                button: mouse::Button::Left,
+            }),
+            ["action", module, "middle"] => Ok(IpcCommand::Action {
+                module: module.to_string(),
+                button: mouse::Button::Middle,
+            }),
+            ["action", module, "right"] => Ok(IpcCommand::Action {
+                module: module.to_string(),
+                button: mouse::Button::Right,
+            }),
+            ["action", _, button] => Err(format!(
+                "unknown button `{button}`, expected left, middle or right"
+            )),
+            // Added to the end of the right side by default
+            ["add-module", module, ref rest @ ..] => Ok(IpcCommand::Layout(LayoutEdit::Add {
+                module: module.to_string(),
+                placement: placement(rest)?.unwrap_or(Placement::End(Side::Right)),
+            })),
+            ["remove-module", module] => {
+                Ok(IpcCommand::Layout(LayoutEdit::Remove(module.to_string())))
+            }
+            ["move-module", module, ref rest @ ..] => Ok(IpcCommand::Layout(LayoutEdit::Move {
+                module: module.to_string(),
+                placement: placement(rest)?.ok_or(
+                    "move-module needs --to <side>, --after <module> or --before <module>",
+                )?,
+            })),
+            ["dump-config"] => Ok(IpcCommand::DumpConfig),
+            [] => Err("empty command".to_string()),
+            [cmd, ..] => Err(format!(
+                "invalid command `{cmd}`, expected reload, refresh [module], toggle <group|module>, reveal, profile <name>, action <module> [button], add-module <module> [placement], remove-module <module>, move-module <module> <placement> or dump-config"
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Builder)]
+pub struct IpcListener;
+
+impl Listener for IpcListener {
+    fn config(&self) -> Vec<ConfigEntry> {
+        vec![ConfigEntry::new("general", "ipc", true)]
+    }
+
+    fn subscription(&self) -> Subscription<Message> {
+        Subscription::run(|| {
+            stream::channel(1, |sender| async move {
+                let path = socket_path(monitor_arg().as_deref());
+                // Remove the socket of a previous instance
+                let _ = std::fs::remove_file(&path);
+                let listener = match UnixListener::bind(&path) {
+                    Ok(listener) => listener,
+                    Err(e) => {
+                        eprintln!("Failed to bind ipc socket {}: {e}", path.to_string_lossy());
+                        return;
+                    }
+                };
+                loop {
+                    match listener.accept().await {
+                        Ok((stream, _)) => {
+                            tokio::spawn(handle_client(stream, sender.clone()));
+                        }
+                        Err(e) => eprintln!("Failed to accept ipc connection: {e}"),
+                    }
+                }
+            })
+        })
+    }
+}
+
+async fn handle_client(stream: UnixStream, mut sender: Sender<Message>) {
+    let (reader, mut writer) = stream.into_split();
+    let mut lines = BufReader::new(reader).lines();
+    while let Ok(Some(line)) = lines.next_line().await {
+        // Commands with an output answer with its lines before the `ok`
+        let response = match line.parse::<IpcCommand>() {
+            Ok(cmd) => run_command(cmd, &mut sender)
+                .await
+                .map(|output| format!("{output}ok"))
+                .unwrap_or_else(|e| format!("error: {e}")),
+            Err(e) => format!("error: {e}"),
+        };
+        if writer
+            .write_all(format!("{response}\n").as_bytes())
+            .await
+            .is_err()
+        {
+            return;
+        }
+    }
+}
+
+/// Runs the command, returns its output
+async fn run_command(cmd: IpcCommand, sender: &mut Sender<Message>) -> Result<String, String> {
+    let config = get_config(sender).await.1;
+    let msg = match cmd {
+        IpcCommand::Reload => Message::ReloadConfig,
+        IpcCommand::Refresh(module) => {
+            // The bar looks up the module instance, since it knows the registered modules
+            let (sx, mut rx) = mpsc::channel(1);
+            sender
+                .send(Message::Refresh(module, sx))
+                .await
+                .map_err(|e| e.to_string())?;
+            rx.recv().await.ok_or("the bar is closing")??;
+            return Ok(String::new());
+        }
+        IpcCommand::Toggle(name) => {
+            if !config.groups.contains_key(&name) && !config.enabled_modules.contains(&name) {
+                return Err(format!("no group or enabled module named `{name}`"));
+            }
+            ToggleGroupAction(name).as_message()
+        }
+        IpcCommand::Reveal => {
+            if !config.auto_hide {
+                return Err("auto_hide is disabled".to_string());
+            }
+            Message::AutoHide(AutoHideEvent::Reveal)
+        }
+        IpcCommand::Profile(name) => {
+            if !config.profiles.contains(&name.to_lowercase()) {
+                return Err(format!("no profile named `{name}`"));
+            }
+            ProfileAction(name).as_message()
+        }
+        IpcCommand::Action { module, button } => {
+            if !config.enabled_modules.contains(&module) {
+                return Err(format!("no enabled module named `{module}`"));
+            }
+            Message::ModuleAction {
+                module,
+                event: Event::Mouse(mouse::Event::ButtonReleased(button)),
+            }
+        }
+        IpcCommand::Layout(edit) => {
+            // The bar checks the edit, since it knows the registered modules
+            let (sx, mut rx) = mpsc::channel(1);
+            sender
+                .send(Message::EditLayout(edit, sx))
+                .await
+                .map_err(|e| e.to_string())?;
+            rx.recv().await.ok_or("the bar is closing")??;
+            return Ok(String::new());
+        }
+        IpcCommand::DumpConfig => {
+            let (sx, mut rx) = mpsc::channel(1);
+            sender
+                .send(Message::DumpConfig(sx))
+                .await
+                .map_err(|e| e.to_string())?;
+            return rx.recv().await.ok_or("the bar is closing")?;
+        }
+    };
+    sender
+        .send(msg)
+        .await
+        .map(|_| String::new())
+        .map_err(|e| e.to_string())
+}
@@ -3,13 +3,17 @@ use std::{any::Any, fmt::Debug};
 use downcast_rs::{impl_downcast, Downcast};
 use hyprland::HyprListener;
 use iced::Subscription;
+use ipc::IpcListener;
 use niri::NiriListener;
 use reload::ReloadListener;
 use wayfire::WayfireListener;
 
 use crate::{config::ConfigEntry, registry::Registry, Message};
 
+#[cfg(feature = "dbus")]
+pub mod dbus;
 pub mod hyprland;
+pub mod ipc;
 pub mod niri;
 mod reload;
 pub mod wayfire;
@@ -27,4 +31,7 @@ pub fn register_listeners(registry: &mut Registry) {
     registry.register_listener::<WayfireListener>();
     registry.register_listener::<NiriListener>();
     registry.register_listener::<ReloadListener>();
+    registry.register_listener::<IpcListener>();
+    #[cfg(feature = "dbus")]
+    registry.register_listener::<dbus::DbusListener>();
 }
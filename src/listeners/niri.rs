@@ -84,12 +84,33 @@ impl Listener for NiriListener {
                                         ws_mod.focused = active_ws.unwrap();
                                         ws_mod.workspaces = workspaces
                                     })),
-                                    Event::WorkspaceActivated { id, focused } => match focused {
-                                        true => Some(Box::new(move |reg| {
-                                            reg.get_module_mut::<NiriWorkspaceMod>().focused = id
-                                        })),
-                                        false => None,
-                                    },
+                                    Event::WorkspaceActivated { id, focused } => Some(Box::new(move |reg| {
+                                        let ws_mod = reg.get_module_mut::<NiriWorkspaceMod>();
+                                        if focused {
+                                            ws_mod.focused = id;
+                                        }
+                                        // The workspace replaces the active one of its output
+                                        if let Some(workspaces) = ws_mod
+                                            .workspaces
+                                            .values_mut()
+                                            .find(|workspaces| workspaces.iter().any(|ws| ws.id == id))
+                                        {
+                                            for ws in workspaces {
+                                                ws.is_active = ws.id == id;
+                                            }
+                                        }
+                                    })),
+                                    Event::WorkspaceActiveWindowChanged { workspace_id, active_window_id } => Some(Box::new(move |reg| {
+                                        if let Some(ws) = reg
+                                            .get_module_mut::<NiriWorkspaceMod>()
+                                            .workspaces
+                                            .values_mut()
+                                            .flatten()
+                                            .find(|ws| ws.id == workspace_id)
+                                        {
+                                            ws.active_window_id = active_window_id;
+                                        }
+                                    })),
                                     Event::WindowsChanged { windows } => Some(Box::new(move |reg| {
                                         let window_mod = reg.get_module_mut::<NiriWindowMod>();
                                         window_mod.focused =
@@ -97,7 +118,8 @@ impl Listener for NiriListener {
                                         window_mod.windows = windows
                                             .into_iter()
                                             .map(|w| (w.id, w))
-                                            .collect()
+                                            .collect();
+                                        sync_windows(reg);
                                     })),
                                     Event::WindowFocusChanged { id } => Some(Box::new(move |reg| {
                                         reg.get_module_mut::<NiriWindowMod>().focused = id
@@ -110,9 +132,11 @@ impl Listener for NiriListener {
                                         window_mod
                                         .windows
                                         .insert(window.id, window);
+                                        sync_windows(reg);
                                     })),
                                     Event::WindowClosed { id } => Some(Box::new(move |reg| {
                                         reg.get_module_mut::<NiriWindowMod>().windows.remove(&id);
+                                        sync_windows(reg);
                                     })),
                                     _ => None,
                                 },
@@ -130,9 +154,14 @@ impl Listener for NiriListener {
                                 buf.clear();
                             }
                         Ok(action) = receiver.recv() => {
-                            if let Some(id) = action.downcast_ref::<u64>() {
+                            // Workspace ids are sent to switch to them, anything else as an action
+                            let action = match action.downcast_ref::<u64>() {
+                                Some(id) => Some(niri_ipc::Action::FocusWorkspace { reference: niri_ipc::WorkspaceReferenceArg::Id(*id) }),
+                                None => action.downcast_ref::<niri_ipc::Action>().cloned(),
+                            };
+                            if let Some(action) = action {
                                 let mut socket = UnixStream::connect(&socket_path).await.unwrap();
-                                let buf = serde_json::to_string(&Request::Action(niri_ipc::Action::FocusWorkspace { reference: niri_ipc::WorkspaceReferenceArg::Id(*id) })).unwrap();
+                                let buf = serde_json::to_string(&Request::Action(action)).unwrap();
                                 socket.write_all(buf.as_bytes()).await.unwrap();
                                 socket.shutdown().await.unwrap();
                             }
@@ -143,3 +172,9 @@ impl Listener for NiriListener {
         })
     }
 }
+
+/// The overview of the workspace module lists the windows the window module keeps track of
+fn sync_windows(reg: &mut Registry) {
+    let windows = reg.get_module::<NiriWindowMod>().windows.clone();
+    reg.get_module_mut::<NiriWorkspaceMod>().windows = windows;
+}
@@ -5,9 +5,12 @@ use iced::{
     futures::{channel::mpsc::Sender, SinkExt},
     stream, Subscription,
 };
-use serde_json::Value;
-use tokio::time::sleep;
-use wayfire_rs::ipc::WayfireSocket;
+use serde_json::{json, Value};
+use tokio::{
+    sync::mpsc::{self, UnboundedReceiver},
+    time::sleep,
+};
+use wayfire_rs::{ipc::WayfireSocket, models::MsgTemplate};
 
 use crate::{
     modules::wayfire::{WayfireWindowMod, WayfireWorkspaceMod},
@@ -19,16 +22,49 @@ use super::Listener;
 #[derive(Debug, Builder)]
 pub struct WayfireListener;
 
+/// Switches to the workspaces the workspace module asks for, using a separate connection
+async fn switch_workspaces(mut requests: UnboundedReceiver<(i64, i64)>) {
+    let Ok(mut socket) = WayfireSocket::connect().await else {
+        eprintln!("Failed to connect to wayfire socket");
+        return;
+    };
+    while let Some((x, y)) = requests.recv().await {
+        let result = match socket.get_focused_output().await {
+            Ok(output) => {
+                socket
+                    .send_json(&MsgTemplate {
+                        method: "vswitch/set-workspace".to_string(),
+                        data: Some(json!({ "x": x, "y": y, "output-id": output.id })),
+                    })
+                    .await
+            }
+            Err(e) => Err(e),
+        };
+        if let Err(e) = result {
+            eprintln!("Failed to switch to workspace {x}/{y}: {e}");
+        }
+    }
+}
+
 async fn send_first_values(
     socket: &mut WayfireSocket,
     sender: &mut Sender<Message>,
 ) -> Result<(), Box<dyn Error>> {
-    let title = socket.get_focused_view().await.ok().map(|v| v.title);
+    let (title, app_id) = socket
+        .get_focused_view()
+        .await
+        .ok()
+        .map(|v| (v.title, v.app_id))
+        .unzip();
     let workspace = socket.get_focused_output().await?.workspace;
     sender
         .send(Message::update(move |reg| {
-            reg.get_module_mut::<WayfireWindowMod>().title = title;
-            reg.get_module_mut::<WayfireWorkspaceMod>().active = (workspace.x, workspace.y);
+            let window = reg.get_module_mut::<WayfireWindowMod>();
+            window.title = title;
+            window.app_id = app_id;
+            let ws = reg.get_module_mut::<WayfireWorkspaceMod>();
+            ws.active = (workspace.x, workspace.y);
+            ws.grid = (workspace.grid_width, workspace.grid_height);
         }))
         .await?;
     Ok(())
@@ -49,6 +85,17 @@ impl Listener for WayfireListener {
                         eprintln!("Failed to send initial wayfire module data: {e}")
                     });
 
+                let (switch_sx, switch_rx) = mpsc::unbounded_channel();
+                tokio::spawn(switch_workspaces(switch_rx));
+                sender
+                    .send(Message::update(move |reg| {
+                        reg.get_module_mut::<WayfireWorkspaceMod>().switch = Some(switch_sx)
+                    }))
+                    .await
+                    .unwrap_or_else(|e| {
+                        eprintln!("Failed to send the workspace switcher to the module: {e}")
+                    });
+
                 socket
                     .watch(Some(vec![
                         "wset-workspace-changed".to_string(),
@@ -77,12 +124,19 @@ impl Listener for WayfireListener {
                                 // With this wayfire will send an additional msg, see the None
                                 // match arm... No idea why tho
                                 sleep(Duration::from_millis(150)).await;
-                                let title = socket.get_focused_view().await.ok().map(|v| v.title);
+                                let (title, app_id) = socket
+                                    .get_focused_view()
+                                    .await
+                                    .ok()
+                                    .map(|v| (v.title, v.app_id))
+                                    .unzip();
                                 active_window = title.clone();
                                 sender
                                     .send(Message::update(move |reg| {
                                         reg.get_module_mut::<WayfireWorkspaceMod>().active = (x, y);
-                                        reg.get_module_mut::<WayfireWindowMod>().title = title
+                                        let window = reg.get_module_mut::<WayfireWindowMod>();
+                                        window.title = title;
+                                        window.app_id = app_id;
                                     }))
                                     .await
                                     .unwrap();
@@ -92,20 +146,25 @@ impl Listener for WayfireListener {
                         Some(Value::String(val))
                             if val == "view-focused" || val == "view-title-changed" =>
                         {
-                            let Some(Value::String(title)) = msg
-                                .get("view")
-                                .and_then(|v| v.as_object())
-                                .and_then(|o| o.get("title").map(|t| t.to_owned()))
+                            let view = msg.get("view").and_then(|v| v.as_object());
+                            let Some(Value::String(title)) =
+                                view.and_then(|o| o.get("title").map(|t| t.to_owned()))
                             else {
                                 continue;
                             };
+                            let app_id = view
+                                .and_then(|o| o.get("app-id"))
+                                .and_then(|a| a.as_str())
+                                .map(|a| a.to_string());
                             match Some(&title) == active_window.as_ref() {
                                 true => continue,
                                 false => active_window = Some(title.clone()),
                             }
                             sender
                                 .send(Message::update(move |reg| {
-                                    reg.get_module_mut::<WayfireWindowMod>().title = Some(title)
+                                    let window = reg.get_module_mut::<WayfireWindowMod>();
+                                    window.title = Some(title);
+                                    window.app_id = app_id;
                                 }))
                                 .await
                                 .unwrap();
@@ -117,24 +176,25 @@ impl Listener for WayfireListener {
 
                         None => {
                             if let Some("ok") = msg.get("result").and_then(|r| r.as_str()) {
-                                let Some(title) = msg.get("info").map(|info| {
-                                    if info.is_null() {
-                                        return None;
-                                    }
+                                let Some(info) = msg.get("info") else {
+                                    continue;
+                                };
+                                let get = |key: &str| {
                                     info.as_object()
-                                        .and_then(|obj| obj.get("title"))
+                                        .and_then(|obj| obj.get(key))
                                         .and_then(|t| t.as_str())
                                         .map(|s| s.to_string())
-                                }) else {
-                                    continue;
                                 };
+                                let (title, app_id) = (get("title"), get("app-id"));
                                 match title == active_window {
                                     true => continue,
                                     false => active_window = title.clone(),
                                 }
                                 sender
                                     .send(Message::update(move |reg| {
-                                        reg.get_module_mut::<WayfireWindowMod>().title = title
+                                        let window = reg.get_module_mut::<WayfireWindowMod>();
+                                        window.title = title;
+                                        window.app_id = app_id;
                                     }))
                                     .await
                                     .unwrap();
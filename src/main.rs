@@ -1,19 +1,38 @@
 use std::{
     any::{Any, TypeId},
+    collections::HashMap,
     fmt::Debug,
     path::PathBuf,
     process::{exit, Command},
-    sync::Arc,
-    time::Duration,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
 };
 
-use config::{anchor::BarAnchor, get_config_dir, read_config, Config, EnabledModules, Thrice};
-use fill::FillExt;
-use handlebars::Handlebars;
+use auto_hide::{AutoHide, AutoHideEvent};
+use config::{
+    anchor::BarAnchor,
+    dump, get_config_dir,
+    layout::{self, LayoutEdit},
+    output, profile, read_config,
+    scale::ScaleFactor,
+    schedule,
+    schema::Schema,
+    Config,
+};
+use helpers::{
+    coalesce, health, process,
+    state::{self, ModuleState},
+    status,
+    supervisor::Supervisor,
+    templates::Templates,
+    timings,
+};
 use iced::{
     daemon,
     platform_specific::shell::commands::{
-        layer_surface::{destroy_layer_surface, get_layer_surface, Layer},
+        layer_surface::{
+            destroy_layer_surface, get_layer_surface, set_exclusive_zone, set_size, Layer,
+        },
         output::{get_output, get_output_info, OutputInfo},
         popup::{destroy_popup, get_popup},
     },
@@ -22,16 +41,17 @@ use iced::{
         popup::{SctkPopupSettings, SctkPositioner},
     },
     stream,
-    theme::Palette,
-    widget::{container, stack},
+    widget::text,
     window::Id,
-    Alignment, Color, Element, Font, Rectangle, Subscription, Task, Theme,
+    Element, Font, Rectangle, Subscription, Task, Theme,
 };
-use list::{list, DynamicAlign};
 use listeners::register_listeners;
-use modules::{empty::EmptyModule, register_modules, Module};
+use modules::{register_modules, Module};
+use osd::{Osd, OsdContent, OsdPosition};
+use pin::Pinned;
 use registry::Registry;
 use resolvers::register_resolvers;
+use toast::Toast;
 use tokio::{
     sync::{broadcast, mpsc},
     time::sleep,
@@ -40,19 +60,56 @@ use tokio::{
 mod config;
 #[macro_use]
 mod list;
+mod auto_hide;
+mod bar_text;
+mod border;
 mod button;
+mod check;
+mod compose;
 mod event_action;
 mod fill;
 mod helpers;
 mod listeners;
 mod modules;
+mod osd;
+mod pin;
+mod popup;
 mod registry;
+mod render;
 mod resolvers;
+mod separator;
+mod toast;
 mod tooltip;
 
 const NERD_FONT: Font = Font::with_name("3270 Nerd Font");
 
 fn main() -> iced::Result {
+    status::set_verbose(std::env::args().any(|arg| arg == "--verbose"));
+    if std::env::args().any(|arg| arg == "--timings") {
+        timings::enable();
+    }
+    if let Some(monitor) = std::env::args().skip_while(|arg| arg != "--monitor").nth(1) {
+        output::set_monitor_arg(monitor);
+    }
+    if std::env::args().nth(1).as_deref() == Some("check") {
+        exit(check::run());
+    }
+    if std::env::args().nth(1).as_deref() == Some("render") {
+        exit(render::run());
+    }
+    if std::env::args().any(|arg| arg == "--dump-schema") {
+        let mut registry = Registry::default();
+        register_modules(&mut registry);
+        register_listeners(&mut registry);
+        match serde_json::to_string_pretty(&Schema::new(&registry)) {
+            Ok(schema) => println!("{schema}"),
+            Err(e) => {
+                eprintln!("Failed to serialize the config schema: {e}");
+                exit(1);
+            }
+        }
+        exit(0);
+    }
     daemon("Bar", Bar::update, Bar::view)
         .theme(Bar::theme)
         .font(include_bytes!("../assets/3270/3270NerdFont-Regular.ttf"))
@@ -62,14 +119,50 @@ fn main() -> iced::Result {
                     state
                         .registry
                         .get_modules(state.config.enabled_modules.get_all(), &state.config)
-                        .filter(|m| state.config.enabled_modules.contains(&m.name()))
-                        .filter_map(|m| m.subscription())
+                        .filter(|(name, _)| {
+                            state.config.enabled_modules.contains(name)
+                                && !health::is_disabled(name)
+                        })
+                        .filter_map(|(name, m)| {
+                            // Every instance gets its own subscription, which updates only it
+                            let subscription =
+                                health::catch(&name, || m.subscription()).ok().flatten()?;
+                            let subscription =
+                                health::guard(&name, subscription)
+                                    .with(name)
+                                    .map(|(name, msg)| Message::Instance {
+                                        name,
+                                        msg: Box::new(msg),
+                                    });
+                            Some(
+                                match m.urgent_updates() || state.config.redraw_interval.is_zero() {
+                                    true => subscription,
+                                    false => coalesce::deferred(subscription),
+                                },
+                            )
+                        })
+                        .chain(
+                            (!state.config.redraw_interval.is_zero())
+                                .then(|| coalesce::subscription(state.config.redraw_interval)),
+                        )
                         .chain(
                             state
                                 .registry
-                                .get_listeners(&state.config.enabled_listeners)
+                                .get_listeners(&state.config)
                                 .map(|l| l.subscription()),
                         )
+                        .chain(
+                            (state.config.auto_hide
+                                || state.config.hide_on_fullscreen
+                                || state.config.module_config.global.dim_when_unfocused < 1.)
+                                .then(|| state.auto_hide_subscription()),
+                        )
+                        .chain(
+                            (state.popup.is_some() || !state.pinned.is_empty())
+                                .then(popup::dismiss_subscription),
+                        )
+                        .chain(state.has_schedules().then(schedule::subscription))
+                        .chain([helpers::window::icon_subscription()])
                 })
             } else {
                 Subscription::none()
@@ -101,7 +194,10 @@ impl Debug for ActionFn {
 pub enum Message {
     Popup {
         type_id: TypeId,
-        dimension: Rectangle<i32>,
+        /// The size of the popup
+        size: (u32, u32),
+        /// The popup is centered on this rectangle, which lies next to the clicked module
+        anchor_rect: Rectangle<i32>,
     },
     Update(Arc<UpdateFn>),
     Action(Arc<ActionFn>),
@@ -112,9 +208,70 @@ pub enum Message {
     ),
     Spawn(Arc<Command>),
     ReloadConfig,
+    /// Change the enabled modules until the config is reloaded, e.g. via ipc. The result is sent
+    /// back before the bar is updated.
+    EditLayout(LayoutEdit, mpsc::Sender<Result<(), String>>),
+    /// Read the data of the module instance enabled with this name, or of all enabled modules,
+    /// again by restarting their subscriptions. The result is sent back before the bar is updated.
+    Refresh(Option<String>, mpsc::Sender<Result<(), String>>),
+    /// Request the effective config, see [config::dump]
+    DumpConfig(mpsc::Sender<Result<String, String>>),
+    /// Switch to the profile with this name and reload the config
+    SwitchProfile(String),
     LoadRegistry,
     GotOutput(Option<IcedOutput>),
     GotOutputInfo(Option<OutputInfo>),
+    /// Trigger the click action of the module with the given name, e.g. via ipc
+    ModuleAction {
+        module: String,
+        event: iced::Event,
+    },
+    /// A message concerning a specific module instance, e.g. `disk_usage.home`.
+    /// `Registry::get_module` refers to this instance while handling `msg`.
+    Instance {
+        name: String,
+        msg: Box<Message>,
+    },
+    /// The action of a click on a module, which is dropped if the module was clicked less than
+    /// its `debounce` ago
+    Click(Box<Message>),
+    /// Handle multiple messages at once
+    Batch(Vec<Message>),
+    /// The messages of module subscriptions within one frame, which are handled together so
+    /// that the work following an update is only done once for all of them
+    Coalesced(Vec<Message>),
+    /// Close the open popup, if any
+    ClosePopup,
+    /// Pin the open popup of the targeted module instance, or close it if it's pinned already
+    TogglePin,
+    /// Escape was pressed while the window with this id had the keyboard focus
+    EscapePressed(Id),
+    /// A mouse button was pressed in the window with this id, but no widget handled it
+    ClickedOutside(Id),
+    /// The compositor closed the popup with this id, e.g. since something else was clicked
+    PopupDone(Id),
+    /// Open the popup of the module instance with this name, centered along the bar, e.g. via
+    /// D-Bus
+    #[cfg(feature = "dbus")]
+    OpenPopup(String),
+    AutoHide(AutoHideEvent),
+    /// Whether a window on the output of the bar is fullscreen
+    Fullscreen(bool),
+    /// Show a text next to the bar for a while, see [Message::toast]
+    Toast {
+        text: String,
+        duration: Duration,
+    },
+    /// Close the toast with this serial, if it's still shown
+    DismissToast(u64),
+    /// Show a level on the screen for a while, see [Message::osd]
+    Osd {
+        content: OsdContent,
+        timeout: Duration,
+        position: OsdPosition,
+    },
+    /// Fade out the OSD with this serial, if it's still shown
+    FadeOsd(u64),
 }
 
 impl Message {
@@ -124,6 +281,7 @@ impl Message {
     {
         Message::Update(Arc::new(UpdateFn(Box::new(f))))
     }
+    #[allow(dead_code)]
     fn action<F>(f: F) -> Self
     where
         F: FnOnce(&Registry) + Send + Sync + 'static,
@@ -140,6 +298,23 @@ impl Message {
         cmd.args(args);
         Message::Spawn(Arc::new(cmd))
     }
+    /// A toast, which modules can raise to alert beyond changing their looks, e.g. from their
+    /// subscription when a value crosses a threshold
+    fn toast(text: impl Into<String>, duration: Duration) -> Self {
+        Message::Toast {
+            text: text.into(),
+            duration,
+        }
+    }
+    /// An OSD, which modules can raise from their subscription to show a change of their level
+    /// where the user looks, independent of the bar
+    fn osd(content: OsdContent, timeout: Duration, position: OsdPosition) -> Self {
+        Message::Osd {
+            content,
+            timeout,
+            position,
+        }
+    }
     fn command_sh<S>(arg: S) -> Self
     where
         S: AsRef<std::ffi::OsStr>,
@@ -170,58 +345,104 @@ impl Message {
               _: iced::mouse::Cursor,
               _: &mut dyn iced::core::Clipboard,
               _: &Rectangle| {
+            // The on-screen bounds of the clicked module
             let bounds = layout.bounds();
-            let position = layout.position();
-            let x = match anchor {
-                BarAnchor::Left => bounds.width as i32,
-                BarAnchor::Right => -width,
-                _ => position.x as i32,
-            };
-            let y = match anchor {
-                BarAnchor::Top => bounds.height as i32,
-                BarAnchor::Bottom => -height,
-                _ => position.y as i32,
-            };
-            Message::Popup {
-                type_id: TypeId::of::<T>(),
-                dimension: Rectangle {
+            let (x, y) = (bounds.x as i32, bounds.y as i32);
+            let (module_width, module_height) = (bounds.width as i32, bounds.height as i32);
+            // Place the popup right next to the module, centered along the bar
+            let anchor_rect = match anchor {
+                BarAnchor::Top => Rectangle {
                     x,
+                    y: y + module_height,
+                    width: module_width,
+                    height,
+                },
+                BarAnchor::Bottom => Rectangle {
+                    x,
+                    y: y - height,
+                    width: module_width,
+                    height,
+                },
+                BarAnchor::Left => Rectangle {
+                    x: x + module_width,
                     y,
                     width,
-                    height,
+                    height: module_height,
                 },
+                BarAnchor::Right => Rectangle {
+                    x: x - width,
+                    y,
+                    width,
+                    height: module_height,
+                },
+            };
+            Message::Popup {
+                type_id: TypeId::of::<T>(),
+                size: (width as u32, height as u32),
+                anchor_rect,
             }
         }
     }
 }
 
 #[derive(Debug)]
-struct Bar<'a> {
+struct Bar {
     config_file: Arc<PathBuf>,
     config: Arc<Config>,
     registry: Registry,
     logical_size: Option<(u32, u32)>,
+    /// The scale of the output the bar is opened on, as reported by wl_output
+    output_scale: f32,
     output: IcedOutput,
     layer_id: Id,
     open: bool,
-    popup: Option<(TypeId, Id)>,
-    templates: Handlebars<'a>,
+    /// The module instance, id and bounds (relative to the bar) of the open popup
+    popup: Option<(String, Id, Rectangle<i32>)>,
+    pinned: Pinned,
+    templates: Templates,
+    /// The module state to save on exit, `None` if `persist_state` is disabled
+    state: Arc<Mutex<Option<ModuleState>>>,
+    auto_hide: AutoHide,
+    /// Whether a window on the output of the bar is fullscreen, see `hide_on_fullscreen`
+    fullscreen: bool,
+    toast: Toast,
+    osd: Osd,
+    /// When each module instance was last clicked, to debounce clicks
+    last_clicks: HashMap<String, Instant>,
+    supervisor: Supervisor,
+    /// Set while handling [Message::Coalesced], so that updates only mark the bar as `dirty`
+    deferred: bool,
+    /// Whether a module was updated while `deferred` was set
+    dirty: bool,
 }
 
-impl Bar<'_> {
+impl Bar {
     fn new() -> (Self, Task<Message>) {
         let mut registry = Registry::default();
         register_modules(&mut registry);
         register_listeners(&mut registry);
         register_resolvers(&mut registry);
 
-        let mut templates = Handlebars::new();
+        let mut templates = Templates::default();
 
         let config_file = get_config_dir();
-        let config = read_config(&config_file, &mut registry, &mut templates);
+        let config = read_config(&config_file, &mut registry, &mut templates, 1.);
+        if config.strict_config && !config.problems.is_empty() {
+            eprintln!("Not starting because of the problems above, `strict_config` is set");
+            exit(1);
+        }
+        if config.persist_state {
+            registry.load_state(&state::load());
+        }
 
-        ctrlc::set_handler(|| {
+        let saved_state = Arc::new(Mutex::new(None));
+        let state = saved_state.clone();
+        ctrlc::set_handler(move || {
             println!("Received exit signal...Exiting");
+            if let Some(state) = &*state.lock().unwrap() {
+                state::save(state);
+            }
+            process::kill_all();
             exit(0);
         })
         .unwrap();
@@ -231,15 +452,28 @@ impl Bar<'_> {
             config: config.into(),
             registry,
             logical_size: None,
+            output_scale: 1.,
             output: IcedOutput::Active,
             layer_id: Id::unique(),
             open: true,
             popup: None,
+            pinned: Pinned::default(),
             templates,
+            state: saved_state,
+            auto_hide: AutoHide::default(),
+            fullscreen: false,
+            toast: Toast::default(),
+            osd: Osd::default(),
+            last_clicks: HashMap::new(),
+            supervisor: Supervisor::default(),
+            deferred: false,
+            dirty: false,
         };
+        bar.snapshot_state();
         let task = match &bar.config.monitor {
             Some(_) => bar.try_get_output(),
-            None => bar.open(),
+            // We need to know the output scale and name before opening the bar
+            None => bar.try_get_output_info(),
         };
 
         (bar, task)
@@ -247,36 +481,46 @@ impl Bar<'_> {
 
     fn update(&mut self, msg: Message) -> Task<Message> {
         match msg {
-            Message::Popup { type_id, dimension } => {
+            Message::Popup {
+                type_id,
+                size,
+                anchor_rect,
+            } => {
                 let settings = |id| SctkPopupSettings {
                     parent: self.layer_id,
                     id,
                     positioner: SctkPositioner {
-                        size: Some((dimension.width as u32, dimension.height as u32)),
-                        anchor_rect: Rectangle {
-                            x: dimension.x,
-                            y: dimension.y,
-                            width: dimension.width,
-                            height: dimension.height,
-                        },
+                        size: Some(size),
+                        anchor_rect,
                         ..Default::default()
                     },
                     parent_size: None,
                     grab: true,
                 };
-                return match self.popup {
+                let Some(instance) = self.registry.current_instance(type_id).cloned() else {
+                    return Task::none();
+                };
+                // Clicking the module of a pinned popup closes it
+                if self.pinned.contains(&instance) {
+                    return self.pinned.unpin(&instance);
+                }
+                let (width, height) = (size.0 as i32, size.1 as i32);
+                let bounds = Rectangle {
+                    x: anchor_rect.x + (anchor_rect.width - width) / 2,
+                    y: anchor_rect.y + (anchor_rect.height - height) / 2,
+                    width,
+                    height,
+                };
+                return match self.popup.take() {
                     None => {
                         let id = Id::unique();
-                        self.popup = Some((type_id, id));
+                        self.popup = Some((instance, id, bounds));
                         get_popup(settings(id))
                     }
-                    Some((old_ty_id, id)) => match old_ty_id == type_id {
-                        true => {
-                            self.popup = None;
-                            destroy_popup(id)
-                        }
+                    Some((old_instance, id, _)) => match old_instance == instance {
+                        true => destroy_popup(id),
                         false => {
-                            self.popup = Some((type_id, id));
+                            self.popup = Some((instance, id, bounds));
                             destroy_popup(id).chain(get_popup(settings(id)))
                         }
                     },
@@ -284,6 +528,10 @@ impl Bar<'_> {
             }
             Message::Update(task) => {
                 Arc::into_inner(task).unwrap().0(&mut self.registry);
+                match self.deferred {
+                    true => self.dirty = true,
+                    false => return self.updated(),
+                }
             }
             Message::Action(task) => {
                 Arc::into_inner(task).unwrap().0(&self.registry);
@@ -293,34 +541,302 @@ impl Bar<'_> {
                 .unwrap(),
             Message::GetReceiver(sx, f) => sx.try_send(f(&self.registry)).unwrap(),
             Message::Spawn(cmd) => {
-                Arc::into_inner(cmd)
-                    .unwrap()
-                    .spawn()
-                    .inspect_err(|e| eprintln!("Failed to spawn command: {e}"))
-                    .ok();
+                let module = self.registry.target().cloned();
+                let cfg = module
+                    .as_ref()
+                    .and_then(|name| self.registry.get_instance(name))
+                    .and_then(|m| m.cfg_override());
+                let local = &self.config.module_config.local;
+                let mode = cfg
+                    .and_then(|cfg| cfg.action_mode)
+                    .unwrap_or(local.action_mode);
+                // `0` disables the timeout, e.g. for a single module
+                let kill_after = cfg
+                    .and_then(|cfg| cfg.action_timeout)
+                    .or(local.action_timeout)
+                    .filter(|timeout| !timeout.is_zero());
+                return self.supervisor.spawn(
+                    Arc::into_inner(cmd).unwrap(),
+                    module,
+                    mode,
+                    kill_after,
+                );
             }
             Message::ReloadConfig => {
-                println!(
-                    "Reloading config from {}",
-                    self.config_file.to_string_lossy()
+                // Edits of the layout only last until the config is reloaded
+                layout::clear();
+                return self.reload();
+            }
+            Message::EditLayout(edit, reply) => {
+                let checked = self.check_layout_edit(&edit);
+                let valid = checked.is_ok();
+                let _ = reply.try_send(checked);
+                if valid {
+                    layout::push(edit);
+                    return self.reload();
+                }
+            }
+            Message::Refresh(module, reply) => {
+                let instances = match module {
+                    Some(module) => {
+                        let instance = self
+                            .config
+                            .enabled_modules
+                            .contains(&module)
+                            .then(|| self.registry.resolve_instance(&module, &self.config))
+                            .flatten()
+                            .filter(|name| self.registry.get_instance(name).is_some());
+                        match instance {
+                            Some(name) => vec![name],
+                            None => {
+                                let _ = reply
+                                    .try_send(Err(format!("no enabled module named `{module}`")));
+                                return Task::none();
+                            }
+                        }
+                    }
+                    None => self
+                        .registry
+                        .get_modules(self.config.enabled_modules.get_all(), &self.config)
+                        .map(|(name, _)| name)
+                        .collect(),
+                };
+                instances.iter().for_each(|name| health::restart(name));
+                let _ = reply.try_send(Ok(()));
+            }
+            Message::DumpConfig(reply) => {
+                let _ = reply.try_send(dump(&self.config_file, &self.registry, &self.config));
+            }
+            Message::SwitchProfile(name) => {
+                if !self.config.profiles.contains(&name.to_lowercase()) {
+                    eprintln!("No profile named `{name}` exists");
+                    return Task::none();
+                }
+                profile::set_active(&name);
+                return self.update(Message::ReloadConfig);
+            }
+            Message::Instance { name, msg } => {
+                if matches!(*msg, Message::Update(_)) {
+                    timings::first_data(&name);
+                }
+                let previous = self.registry.set_target(Some(name));
+                let task = self.update(*msg);
+                self.registry.set_target(previous);
+                return task;
+            }
+            Message::Click(msg) => {
+                if let Some(name) = self.registry.target().cloned() {
+                    let debounce = self
+                        .registry
+                        .get_instance(&name)
+                        .and_then(|m| m.cfg_override())
+                        .and_then(|cfg| cfg.debounce)
+                        .unwrap_or(self.config.module_config.local.debounce);
+                    let now = Instant::now();
+                    if self
+                        .last_clicks
+                        .get(&name)
+                        .is_some_and(|last| now.duration_since(*last) < debounce)
+                    {
+                        return Task::none();
+                    }
+                    self.last_clicks.insert(name, now);
+                }
+                return self.update(*msg);
+            }
+            Message::Coalesced(msgs) => {
+                self.deferred = true;
+                let mut tasks = msgs
+                    .into_iter()
+                    .map(|msg| self.update(msg))
+                    .collect::<Vec<_>>();
+                self.deferred = false;
+                if std::mem::take(&mut self.dirty) {
+                    tasks.push(self.updated());
+                }
+                return Task::batch(tasks);
+            }
+            Message::Batch(msgs) => {
+                // Handled right away, so that all messages refer to the same module instance
+                return Task::batch(
+                    msgs.into_iter()
+                        .map(|msg| self.update(msg))
+                        .collect::<Vec<_>>(),
                 );
-                self.config =
-                    read_config(&self.config_file, &mut self.registry, &mut self.templates).into();
-                if self.config.hard_reload {
-                    self.open = false;
-                    return destroy_layer_surface(self.layer_id)
-                        .chain(self.open())
-                        .chain(Task::done(Message::LoadRegistry));
+            }
+            Message::AutoHide(event) => {
+                let keep_visible = !self.config.auto_hide || self.keeps_visible();
+                return self
+                    .auto_hide
+                    .update(event, self.layer_id, keep_visible, &self.config);
+            }
+            Message::Fullscreen(fullscreen) => {
+                self.fullscreen = fullscreen;
+                return self.sync_fullscreen();
+            }
+            Message::Toast { text, duration } => {
+                return self
+                    .toast
+                    .show(text, duration, self.output.clone(), &self.config);
+            }
+            Message::DismissToast(serial) => return self.toast.dismiss(serial),
+            Message::Osd {
+                content,
+                timeout,
+                position,
+            } => {
+                return self.osd.show(
+                    content,
+                    timeout,
+                    position,
+                    self.output.clone(),
+                    &self.config,
+                );
+            }
+            Message::FadeOsd(serial) => return self.osd.fade(serial),
+            Message::ClosePopup => {
+                if let Some((_, id, _)) = self.popup.take() {
+                    return destroy_popup(id);
+                }
+            }
+            Message::TogglePin => {
+                let Some(instance) = self.registry.target().cloned() else {
+                    return Task::none();
+                };
+                if self.pinned.contains(&instance) {
+                    return self.pinned.unpin(&instance);
+                }
+                if self.config.popup_config.max_pinned == 0 {
+                    return Task::none();
+                }
+                let Some((_, id, bounds)) = self.popup.take_if(|(name, ..)| *name == instance)
+                else {
+                    return Task::none();
+                };
+                return destroy_popup(id).chain(self.pinned.pin(
+                    instance,
+                    bounds,
+                    self.size(),
+                    self.logical_size.unwrap_or((1920, 1080)),
+                    self.output.clone(),
+                    &self.config,
+                ));
+            }
+            Message::EscapePressed(id) => {
+                if let Some((_, id, _)) = self.popup.take_if(|(_, popup, _)| *popup == id) {
+                    return destroy_popup(id);
                 }
+                if let Some(instance) = self.pinned.instance(id).cloned() {
+                    return self.pinned.unpin(&instance);
+                }
+            }
+            Message::ClickedOutside(id) => {
+                if let Some((_, id, _)) = self.popup.take_if(|(_, popup, _)| *popup != id) {
+                    return destroy_popup(id);
+                }
+            }
+            Message::PopupDone(id) => {
+                if let Some((_, id, _)) = self.popup.take_if(|(_, popup, _)| *popup == id) {
+                    return destroy_popup(id);
+                }
+            }
+            #[cfg(feature = "dbus")]
+            Message::OpenPopup(name) => {
+                let Some((name, module)) = self
+                    .registry
+                    .get_modules(std::iter::once(&name), &self.config)
+                    .next()
+                else {
+                    eprintln!("No enabled module named {name}");
+                    return Task::none();
+                };
+                let Some(type_id) = self.registry.type_of(&name) else {
+                    return Task::none();
+                };
+                let popup_cfg = module.popup_cfg_override();
+                let width = popup_cfg
+                    .and_then(|cfg| cfg.width)
+                    .unwrap_or(self.config.popup_config.width);
+                let height = popup_cfg
+                    .and_then(|cfg| cfg.height)
+                    .unwrap_or(self.config.popup_config.height);
+                let (bar_width, bar_height) = self.size();
+                let (bar_width, bar_height) = (bar_width as i32, bar_height as i32);
+                // There is no clicked module, so the popup is centered along the whole bar
+                let anchor_rect = match self.config.anchor {
+                    BarAnchor::Top => Rectangle {
+                        x: 0,
+                        y: bar_height,
+                        width: bar_width,
+                        height,
+                    },
+                    BarAnchor::Bottom => Rectangle {
+                        x: 0,
+                        y: -height,
+                        width: bar_width,
+                        height,
+                    },
+                    BarAnchor::Left => Rectangle {
+                        x: bar_width,
+                        y: 0,
+                        width,
+                        height: bar_height,
+                    },
+                    BarAnchor::Right => Rectangle {
+                        x: -width,
+                        y: 0,
+                        width,
+                        height: bar_height,
+                    },
+                };
+                return self.update(Message::Instance {
+                    name,
+                    msg: Box::new(Message::Popup {
+                        type_id,
+                        size: (width as u32, height as u32),
+                        anchor_rect,
+                    }),
+                });
+            }
+            Message::ModuleAction { module, event } => {
+                return self
+                    .registry
+                    .get_modules(std::iter::once(&module), &self.config)
+                    .next()
+                    .and_then(|(name, m)| {
+                        m.on_click(event, &self.config.module_config.local)
+                            .map(|action| (name, action))
+                    })
+                    .map(|(name, action)| {
+                        Task::done(Message::Instance {
+                            name,
+                            msg: Box::new(action.as_message()),
+                        })
+                    })
+                    .unwrap_or_else(|| {
+                        eprintln!("Module {module} has no action for this event");
+                        Task::none()
+                    });
             }
             Message::LoadRegistry => {
+                // Keep the runtime state of the modules across the reload
+                let state = self.registry.save_state();
                 self.registry = Registry::default();
                 register_modules(&mut self.registry);
                 register_listeners(&mut self.registry);
                 register_resolvers(&mut self.registry);
-                self.config =
-                    read_config(&self.config_file, &mut self.registry, &mut self.templates).into();
+                self.config = read_config(
+                    &self.config_file,
+                    &mut self.registry,
+                    &mut self.templates,
+                    self.output_scale,
+                )
+                .into();
+                self.registry.load_state(&state);
                 self.open = true;
+                #[cfg(feature = "dbus")]
+                listeners::dbus::publish(&self.registry, &self.config, &self.templates);
+                return self.sync_pinned();
             }
             Message::GotOutput(optn) => {
                 return match optn {
@@ -338,13 +854,27 @@ impl Bar<'_> {
                 return match optn {
                     Some(info) => {
                         self.logical_size = info.logical_size.map(|(x, y)| (x as u32, y as u32));
+                        self.output_scale = info.scale_factor as f32;
+                        // Per-output sections refer to this output if no monitor is configured
+                        let renamed = output::set_output_name(info.name.clone())
+                            && self.config.monitor.is_none();
+                        if self.config.scale == ScaleFactor::Auto || renamed {
+                            self.config = read_config(
+                                &self.config_file,
+                                &mut self.registry,
+                                &mut self.templates,
+                                self.output_scale,
+                            )
+                            .into();
+                        }
+                        timings::once("output found, opening the bar");
                         self.open()
                     }
                     None => Task::stream(stream::channel(1, |_| async {
                         sleep(Duration::from_millis(500)).await;
                     }))
                     .chain(self.try_get_output_info()),
-                }
+                };
             }
         }
         Task::none()
@@ -352,90 +882,228 @@ impl Bar<'_> {
 
     fn view(&self, window_id: Id) -> Element<Message> {
         if window_id == self.layer_id {
+            timings::once("first frame");
             self.bar_view()
-        } else if let Some(mod_id) = self
-            .popup
-            .and_then(|(m_id, p_id)| (p_id == window_id).then_some(m_id))
+        } else if self.toast.id() == Some(window_id) {
+            self.toast.view(&self.config.popup_config)
+        } else if self.osd.id() == Some(window_id) {
+            self.osd.view(&self.config.popup_config)
+        } else if let Some((name, module)) = self
+            .popup_instance(window_id)
+            .and_then(|name| Some((name, self.registry.get_instance(name)?)))
         {
-            self.registry.get_module_by_id(mod_id).popup_wrapper(
-                &self.config.popup_config,
-                &self.config.anchor,
-                &self.templates,
-            )
+            let pinned =
+                (self.config.popup_config.max_pinned > 0).then(|| self.pinned.contains(name));
+            let popup = health::catch(name, || {
+                module.popup_wrapper(
+                    &self.config.popup_config,
+                    &self.config.anchor,
+                    self.templates.get(name),
+                    pinned,
+                )
+            });
+            match popup {
+                Ok(popup) => popup.map(|msg| Message::Instance {
+                    name: name.clone(),
+                    msg: Box::new(msg),
+                }),
+                Err(crash) => text(format!("{name}: {crash}")).into(),
+            }
         } else {
             "Internal error".into()
         }
     }
 
     fn bar_view(&self) -> Element<Message> {
-        let anchor = &self.config.anchor;
-        let make_list = |spacing: fn(&Thrice<f32>) -> f32,
-                         field: fn(&EnabledModules) -> &Vec<String>| {
-            let modules = self
+        compose::bar(&self.registry, &self.config, &self.templates, self.dimmed())
+    }
+
+    /// Whether the modules are dimmed by `dim_when_unfocused`, since the cursor isn't over the
+    /// bar and no popup is open
+    fn dimmed(&self) -> bool {
+        self.config.module_config.global.dim_when_unfocused < 1.
+            && self.popup.is_none()
+            && !self.auto_hide.hovered()
+    }
+
+    /// The module instance of the open or pinned popup with this id
+    fn popup_instance(&self, window_id: Id) -> Option<&String> {
+        self.popup
+            .as_ref()
+            .filter(|(_, p_id, _)| *p_id == window_id)
+            .map(|(name, ..)| name)
+            .or_else(|| self.pinned.instance(window_id))
+    }
+
+    /// Whether any enabled module is only shown at some times of day, which needs the bar to be
+    /// redrawn every minute
+    fn has_schedules(&self) -> bool {
+        self.registry
+            .get_modules(self.config.enabled_modules.get_all(), &self.config)
+            .any(|(_, m)| {
+                m.cfg_override()
+                    .is_some_and(|c| c.visible_between.is_some())
+            })
+    }
+
+    /// Whether the bar has to stay visible with `auto_hide`: while a popup is open or a module
+    /// with `keep_visible` is active. Pinned popups don't count, they stay without the bar.
+    fn keeps_visible(&self) -> bool {
+        self.popup.is_some()
+            || self
                 .registry
-                .get_modules(field(&self.config.enabled_modules).iter(), &self.config)
-                .filter(|&m| m.active())
-                .map(|m| {
-                    m.wrapper(
-                        &self.config.module_config.local,
-                        m.view(
-                            &self.config.module_config.local,
-                            &self.config.popup_config,
-                            anchor,
-                            &self.templates,
-                        ),
-                        anchor,
-                    )
+                .get_modules(self.config.enabled_modules.get_all(), &self.config)
+                .any(|(_, m)| {
+                    m.active() && m.scheduled() && m.cfg_override().is_some_and(|c| c.keep_visible)
                 })
-                .collect::<Vec<_>>();
-            let content = if modules.is_empty() {
-                vec![self.registry.get_module::<EmptyModule>().wrapper(
-                    &self.config.module_config.local,
-                    "".into(),
-                    anchor,
-                )]
-            } else {
-                modules
-            };
-            container(
-                list(anchor, content).spacing(spacing(&self.config.module_config.global.spacing)),
-            )
-            .fillx(!anchor.vertical())
-        };
-        let left = make_list(|s| s.left, |m| &m.left);
-        let center = make_list(|s| s.center, |m| &m.center);
-        let right = make_list(|s| s.right, |m| &m.right);
-        container(stack!(
-            center.align(anchor, Alignment::Center),
-            list(
-                anchor,
-                [(left, Alignment::Start), (right, Alignment::End)]
-                    .map(|(e, align)| e.align(anchor, align).into())
-            )
+    }
+
+    /// Hides the bar while a window is fullscreen if `hide_on_fullscreen` is enabled, and shows
+    /// it again otherwise. Open popups are closed when the bar is hidden.
+    fn sync_fullscreen(&mut self) -> Task<Message> {
+        let hide = self.fullscreen && self.config.hide_on_fullscreen;
+        let popup = self
+            .popup
+            .take_if(|_| hide)
+            .map(|(_, id, _)| destroy_popup(id))
+            .unwrap_or_else(Task::none);
+        let keep_visible = !self.config.auto_hide || self.keeps_visible();
+        popup.chain(self.auto_hide.update(
+            AutoHideEvent::Fullscreen(hide),
+            self.layer_id,
+            keep_visible,
+            &self.config,
         ))
-        .padding(self.config.module_config.global.padding)
-        .into()
+    }
+
+    /// Reads the config file again and applies it to the bar
+    fn reload(&mut self) -> Task<Message> {
+        println!(
+            "Reloading config from {}",
+            self.config_file.to_string_lossy()
+        );
+        let previous_size = self.size();
+        self.config = read_config(
+            &self.config_file,
+            &mut self.registry,
+            &mut self.templates,
+            self.output_scale,
+        )
+        .into();
+        self.snapshot_state();
+        #[cfg(feature = "dbus")]
+        listeners::dbus::publish(&self.registry, &self.config, &self.templates);
+        if self.config.hard_reload {
+            self.open = false;
+            self.auto_hide = AutoHide::default();
+            // The new bar is hidden again if a window is still fullscreen
+            let fullscreen = self.sync_fullscreen();
+            return destroy_layer_surface(self.layer_id)
+                .chain(self.open())
+                .chain(Task::done(Message::LoadRegistry))
+                .chain(fullscreen);
+        }
+        let mut tasks = vec![];
+        let (width, height) = self.size();
+        if (width, height) != previous_size {
+            tasks.push(set_size(self.layer_id, Some(width), Some(height)));
+            if !self.auto_hide.hidden() {
+                tasks.push(set_exclusive_zone(
+                    self.layer_id,
+                    self.config.exclusive_zone(),
+                ));
+            }
+        }
+        let hidden_for_fullscreen = self.fullscreen && self.config.hide_on_fullscreen;
+        if !self.config.auto_hide && !hidden_for_fullscreen {
+            tasks.push(self.auto_hide.restore(self.layer_id, &self.config));
+        }
+        tasks.push(self.sync_fullscreen());
+        tasks.push(self.sync_pinned());
+        Task::batch(tasks)
+    }
+
+    /// Whether the layout edit can be applied to the enabled modules
+    fn check_layout_edit(&self, edit: &LayoutEdit) -> Result<(), String> {
+        if let LayoutEdit::Add { module, .. } = edit {
+            if !self.registry.is_known(module) {
+                let names = self.registry.known_names();
+                return Err(format!(
+                    "no module named `{module}`, valid modules are: {}",
+                    names
+                        .iter()
+                        .map(|name| name.as_str())
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ));
+            }
+        }
+        edit.apply(&mut self.config.enabled_modules.clone())
+    }
+
+    /// Closes the pinned popups of modules which were removed from the config, and the oldest
+    /// ones if `max_pinned` was lowered
+    fn sync_pinned(&mut self) -> Task<Message> {
+        let registry = &self.registry;
+        let enabled = &self.config.enabled_modules;
+        self.pinned.sync(
+            |name| enabled.contains(&name.to_string()) && registry.get_instance(name).is_some(),
+            self.config.popup_config.max_pinned,
+        )
+    }
+
+    fn auto_hide_subscription(&self) -> Subscription<Message> {
+        let hover = iced::event::listen_with(|event, _, id| match event {
+            iced::Event::Mouse(iced::mouse::Event::CursorEntered) => {
+                Some(Message::AutoHide(AutoHideEvent::Hovered(id, true)))
+            }
+            iced::Event::Mouse(iced::mouse::Event::CursorLeft) => {
+                Some(Message::AutoHide(AutoHideEvent::Hovered(id, false)))
+            }
+            _ => None,
+        });
+        let frames = self
+            .auto_hide
+            .frame_interval(!self.config.auto_hide || self.keeps_visible())
+            .map(|interval| {
+                iced::time::every(interval).map(|_| Message::AutoHide(AutoHideEvent::Frame))
+            });
+        Subscription::batch([hover].into_iter().chain(frames))
+    }
+
+    /// The work following an update of the modules
+    fn updated(&mut self) -> Task<Message> {
+        self.snapshot_state();
+        #[cfg(feature = "dbus")]
+        listeners::dbus::publish(&self.registry, &self.config, &self.templates);
+        if self.config.auto_hide && !self.auto_hide.revealed() && self.keeps_visible() {
+            return self.update(Message::AutoHide(AutoHideEvent::Reveal));
+        }
+        Task::none()
+    }
+
+    /// Remember the current module state, so that it can be saved on exit
+    fn snapshot_state(&self) {
+        *self.state.lock().unwrap() = self
+            .config
+            .persist_state
+            .then(|| self.registry.save_state());
+    }
+
+    /// The size of the bar, which is as long as the output unless configured otherwise
+    fn size(&self) -> (u32, u32) {
+        compose::size(&self.config, self.logical_size.unwrap_or((1920, 1080)))
     }
 
     fn open(&self) -> Task<Message> {
-        let (x, y) = self.logical_size.unwrap_or((1920, 1080));
-        let (width, height) = match self.config.anchor.vertical() {
-            true => (
-                self.config.module_config.global.width.unwrap_or(30),
-                self.config.module_config.global.height.unwrap_or(y),
-            ),
-            false => (
-                self.config.module_config.global.width.unwrap_or(x),
-                self.config.module_config.global.height.unwrap_or(30),
-            ),
-        };
+        let (width, height) = self.size();
         get_layer_surface(SctkLayerSurfaceSettings {
             layer: Layer::Top,
             keyboard_interactivity: self.config.kb_focus,
             anchor: (&self.config.anchor).into(),
             exclusive_zone: self.config.exclusive_zone(),
             size: Some((Some(width), Some(height))),
-            namespace: "bar-rs".to_string(),
+            namespace: self.config.namespace.clone(),
             output: self.output.clone(),
             margin: self.config.module_config.global.margin,
             id: self.layer_id,
@@ -465,10 +1133,12 @@ impl Bar<'_> {
             output_state
                 .outputs()
                 .find(|o| {
-                    output_state
-                        .info(o)
-                        .map(|info| info.name == monitor)
-                        .unwrap_or(false)
+                    // Without a configured monitor, take the first output
+                    monitor.is_none()
+                        || output_state
+                            .info(o)
+                            .map(|info| info.name == monitor)
+                            .unwrap_or(false)
                 })
                 .and_then(|o| output_state.info(&o))
                 .clone()
@@ -477,22 +1147,17 @@ impl Bar<'_> {
     }
 
     fn theme(&self, window_id: Id) -> Theme {
-        if let Some(mod_id) = self
-            .popup
-            .and_then(|(m_id, p_id)| (p_id == window_id).then_some(m_id))
+        if let Some(module) = self
+            .popup_instance(window_id)
+            .and_then(|name| self.registry.get_instance(name))
         {
-            self.registry.get_module_by_id(mod_id).popup_theme()
+            module.popup_theme(&self.config.popup_config)
+        } else if self.toast.id() == Some(window_id) {
+            Toast::theme(&self.config.popup_config)
+        } else if self.osd.id() == Some(window_id) {
+            Osd::theme(&self.config.popup_config)
         } else {
-            Theme::custom(
-                "Bar theme".to_string(),
-                Palette {
-                    background: self.config.module_config.global.background_color,
-                    text: Color::WHITE,
-                    primary: Color::WHITE,
-                    success: Color::WHITE,
-                    danger: Color::WHITE,
-                },
-            )
+            compose::theme()
         }
     }
 }
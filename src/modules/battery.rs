@@ -1,25 +1,33 @@
 use std::collections::BTreeMap;
 use std::fmt::Display;
-use std::{collections::HashMap, time::Duration};
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
 
 use bar_rs_derive::Builder;
-use handlebars::Handlebars;
+use handlebars::{Handlebars, RenderError};
 use iced::widget::button::Style;
 use iced::widget::{column, container, scrollable};
-use iced::{futures::SinkExt, stream, widget::text, Element, Subscription};
+use iced::{futures::SinkExt, stream, widget::text, Background, Color, Element, Subscription};
+use serde_json::Value;
 use tokio::{fs, io, runtime, select, sync::mpsc, task, time::sleep};
 use udev::Device;
 
+use crate::bar_text::bar_text;
 use crate::button::button;
+use crate::config::parse::StringExt;
 use crate::config::popup_config::{PopupConfig, PopupConfigOverride};
 use crate::helpers::UnEscapeString;
 use crate::{
     config::{
         anchor::BarAnchor,
         module_config::{LocalModuleConfig, ModuleConfigOverride},
+        schema::{ConfigKey, Kind},
+        thresholds::Thresholds,
     },
     fill::FillExt,
-    Message, NERD_FONT,
+    Message,
 };
 use crate::{impl_on_click, impl_wrapper};
 
@@ -33,6 +41,27 @@ pub struct BatteryMod {
     popup_cfg_override: PopupConfigOverride,
     icons: BTreeMap<u8, String>,
     icons_charging: BTreeMap<u8, String>,
+    /// Shown instead of the remaining time when it can't be estimated
+    time_unavailable: String,
+    /// Only show the remaining time while discharging
+    time_discharging_only: bool,
+    /// Cycle through `charging_icons` while charging
+    animate: bool,
+    /// The icons shown one after another while charging, from empty to full
+    charging_icons: Vec<String>,
+    frame_interval: Duration,
+    /// Advanced by the animation timer
+    frame: usize,
+    /// The background shown briefly when charging starts or stops, `None` to disable this
+    flash_color: Option<Color>,
+    flash_duration: Duration,
+    /// The end of the current flash
+    flash_until: Option<Instant>,
+    /// Colors for the capacity, e.g. red below 15%
+    thresholds: Thresholds,
+    /// A toast is shown when the capacity drops to one of these while discharging
+    warn_levels: Vec<u8>,
+    warn_duration: Duration,
 }
 
 impl Default for BatteryMod {
@@ -58,6 +87,23 @@ impl Default for BatteryMod {
                 (25, "󱊤 ".to_string()),
                 (0, "󰢟 ".to_string()),
             ]),
+            time_unavailable: "--".to_string(),
+            time_discharging_only: false,
+            animate: true,
+            charging_icons: vec![
+                "󰢟 ".to_string(),
+                "󱊤 ".to_string(),
+                "󱊥 ".to_string(),
+                "󱊦 ".to_string(),
+            ],
+            frame_interval: Duration::from_millis(500),
+            frame: 0,
+            flash_color: Some(Color::from_rgba(1., 1., 1., 0.3)),
+            flash_duration: Duration::from_millis(600),
+            flash_until: None,
+            thresholds: Thresholds::default(),
+            warn_levels: vec![10],
+            warn_duration: Duration::from_secs(5),
         }
     }
 }
@@ -77,6 +123,63 @@ impl BatteryMod {
             .unwrap()
             .1
     }
+
+    fn animating(&self) -> bool {
+        self.avg.charging && self.animate && self.charging_icons.len() > 1
+    }
+
+    /// The icon shown in the bar: while charging, the icons from the current capacity up to
+    /// the full one are shown one after another
+    fn bar_icon(&self) -> &String {
+        if !self.animating() {
+            return self.icon(None, None);
+        }
+        let count = self.charging_icons.len();
+        let start = (self.avg.capacity as usize * count / 101).min(count - 1);
+        &self.charging_icons[start + self.frame % (count - start)]
+    }
+
+    fn set_stats(&mut self, avg: AverageStats, batteries: Vec<Battery>) {
+        // Don't flash when the first stats arrive
+        if !self.batteries.is_empty()
+            && avg.charging != self.avg.charging
+            && self.flash_color.is_some()
+            && !self.flash_duration.is_zero()
+        {
+            self.flash_until = Some(Instant::now() + self.flash_duration);
+        }
+        if avg.charging != self.avg.charging {
+            self.frame = 0;
+        }
+        self.avg = avg;
+        self.batteries = batteries;
+    }
+
+    fn text(&self, handlebars: &Handlebars) -> Result<String, RenderError> {
+        let time_remaining = match (self.avg.valid, self.avg.charging) {
+            (_, true) if self.time_discharging_only => String::new(),
+            (true, _) => {
+                let time_ctx = BTreeMap::from([
+                    ("hours", self.avg.hours.to_string()),
+                    ("minutes", format!("{:02}", self.avg.minutes)),
+                    ("seconds", self.avg.seconds().to_string()),
+                ]);
+                handlebars.render("battery_time_remaining", &time_ctx)?
+            }
+            // E.g. right after plugging in, when the batteries don't report a power draw yet
+            (false, _) if self.time_discharging_only => String::new(),
+            (false, _) => self.time_unavailable.clone(),
+        };
+
+        let ctx = BTreeMap::from([
+            ("capacity", self.avg.capacity.to_string()),
+            ("hours", self.avg.hours.to_string()),
+            ("minutes", self.avg.minutes.to_string()),
+            ("seconds", self.avg.seconds().to_string()),
+            ("time_remaining", time_remaining),
+        ]);
+        handlebars.render("battery", &ctx)
+    }
 }
 
 #[derive(Debug, Default)]
@@ -89,6 +192,13 @@ struct AverageStats {
     valid: bool,
 }
 
+impl AverageStats {
+    /// The remaining time in seconds, for the `duration` helper
+    fn seconds(&self) -> u32 {
+        u32::from(self.hours) * 3600 + u32::from(self.minutes) * 60
+    }
+}
+
 #[derive(Debug, Default)]
 enum BatteryState {
     Charging,
@@ -161,45 +271,35 @@ impl Module for BatteryMod {
         anchor: &BarAnchor,
         handlebars: &Handlebars,
     ) -> Element<Message> {
-        let time_remaining = if self.avg.valid {
-            let time_ctx =
-                BTreeMap::from([("hours", self.avg.hours), ("minutes", self.avg.minutes)]);
-            handlebars
-                .render("battery_time_remaining", &time_ctx)
-                .inspect_err(|e| eprintln!("Failed to render remaining battery time: {e}"))
-                .unwrap_or_default()
-        } else {
-            String::new()
-        };
-
-        let ctx = BTreeMap::from([
-            ("capacity", self.avg.capacity.to_string()),
-            ("hours", self.avg.hours.to_string()),
-            ("minutes", self.avg.minutes.to_string()),
-            ("time_remaining", time_remaining),
-        ]);
-
+        let level = self.thresholds.color(self.avg.capacity as f32);
         button(
             list![
                 anchor,
                 container(
-                    text(self.icon(None, None))
+                    text(self.cfg_override.icon(config, self.bar_icon()))
                         .fill(anchor)
-                        .color(self.cfg_override.icon_color.unwrap_or(config.icon_color))
+                        .color(
+                            level.unwrap_or(
+                                self.cfg_override.icon_color.unwrap_or(config.icon_color)
+                            )
+                        )
                         .size(self.cfg_override.icon_size.unwrap_or(config.icon_size))
-                        .font(NERD_FONT)
+                        .font(config.icon_font)
                 )
                 .padding(self.cfg_override.icon_margin.unwrap_or(config.icon_margin)),
                 container(
-                    text(
-                        handlebars
-                            .render("battery", &ctx)
+                    bar_text(
+                        self.text(handlebars)
                             .inspect_err(|e| eprintln!("Failed to render battery: {e}"))
-                            .unwrap_or_default()
+                            .unwrap_or_default(),
+                        anchor,
+                        self.cfg_override.vertical_text(config, anchor),
+                    )
+                    .color(
+                        level.unwrap_or(self.cfg_override.text_color.unwrap_or(config.text_color))
                     )
-                    .fill(anchor)
-                    .color(self.cfg_override.text_color.unwrap_or(config.text_color))
                     .size(self.cfg_override.font_size.unwrap_or(config.font_size))
+                    .font(self.cfg_override.text_font.unwrap_or(config.text_font))
                 )
                 .padding(self.cfg_override.text_margin.unwrap_or(config.text_margin)),
             ]
@@ -216,6 +316,10 @@ impl Module for BatteryMod {
         .into()
     }
 
+    fn popup_cfg_override(&self) -> Option<&PopupConfigOverride> {
+        Some(&self.popup_cfg_override)
+    }
+
     fn popup_view<'a>(
         &'a self,
         config: &'a PopupConfig,
@@ -231,7 +335,11 @@ impl Module for BatteryMod {
                 let remaining = bat
                     .remaining
                     .and_then(|(hours, minutes)| {
-                        let time_ctx = BTreeMap::from([("hours", hours), ("minutes", minutes)]);
+                        let time_ctx = BTreeMap::from([
+                            ("hours", u32::from(hours)),
+                            ("minutes", u32::from(minutes)),
+                            ("seconds", u32::from(hours) * 3600 + u32::from(minutes) * 60),
+                        ]);
                         template
                             .render("battery_popup_time_remaining", &time_ctx)
                             .inspect_err(|e| {
@@ -244,7 +352,8 @@ impl Module for BatteryMod {
                 let mut ctx = BTreeMap::new();
                 ctx.insert("name", &bat.name);
                 ctx.insert("state", &state);
-                ctx.insert("icon", bat.icon(self));
+                let icon = config.icon(bat.icon(self)).to_string();
+                ctx.insert("icon", &icon);
                 ctx.insert("capacity", &capacity);
                 ctx.insert("energy", &energy);
                 ctx.insert("health", &health);
@@ -278,41 +387,83 @@ impl Module for BatteryMod {
             }))
             .spacing(self.popup_cfg_override.spacing.unwrap_or(config.spacing)),
         ))
-        .padding(self.popup_cfg_override.padding.unwrap_or(config.padding))
-        .style(|_| container::Style {
-            background: Some(
-                self.popup_cfg_override
-                    .background
-                    .unwrap_or(config.background),
-            ),
-            border: self.popup_cfg_override.border.unwrap_or(config.border),
-            ..Default::default()
-        })
-        .fill_maybe(
-            self.popup_cfg_override
-                .fill_content_to_size
-                .unwrap_or(config.fill_content_to_size),
-        )
         .into()
     }
 
     impl_wrapper!();
 
+    fn background_override(&self) -> Option<Background> {
+        self.flash_until
+            .and(self.flash_color)
+            .map(Background::Color)
+    }
+
     fn read_config(
         &mut self,
         config: &HashMap<String, Option<String>>,
         popup_config: &HashMap<String, Option<String>>,
         templates: &mut Handlebars,
+        scale: f32,
     ) {
-        self.cfg_override = config.into();
-        self.popup_cfg_override.update(popup_config);
+        let default = Self::default();
+        self.cfg_override = (config, scale).into();
+        self.popup_cfg_override.update(popup_config, scale);
+        self.time_unavailable = config
+            .get("time_unavailable")
+            .unescape()
+            .unwrap_or(default.time_unavailable);
+        self.time_discharging_only = config
+            .get("time_discharging_only")
+            .and_then(|v| v.into_bool())
+            .unwrap_or(default.time_discharging_only);
+        self.animate = config
+            .get("animate")
+            .and_then(|v| v.into_bool())
+            .unwrap_or(default.animate);
+        self.charging_icons = config
+            .get("charging_icons")
+            .and_then(|v| v.as_ref())
+            .map(|icons| icons.split_whitespace().map(|i| i.to_string()).collect())
+            .unwrap_or(default.charging_icons);
+        self.frame_interval = config
+            .get("frame_interval")
+            .and_then(|v| v.into_duration())
+            .filter(|interval| !interval.is_zero())
+            .unwrap_or(default.frame_interval);
+        self.flash_color = match config.get("flash_color").and_then(|v| v.as_deref()) {
+            Some("none") => None,
+            Some(_) => config
+                .get("flash_color")
+                .and_then(|v| v.into_color())
+                .or(default.flash_color),
+            None => default.flash_color,
+        };
+        self.flash_duration = config
+            .get("flash_duration")
+            .and_then(|v| v.into_duration())
+            .unwrap_or(default.flash_duration);
+        self.thresholds = Thresholds::read(config.get("thresholds").and_then(|v| v.as_deref()));
+        self.warn_levels = config
+            .get("warn_levels")
+            .and_then(|v| v.as_ref())
+            .map(|levels| {
+                levels
+                    .split_whitespace()
+                    .filter_map(|level| level.trim_end_matches('%').parse().ok())
+                    .collect()
+            })
+            .unwrap_or(default.warn_levels);
+        self.warn_duration = config
+            .get("warn_duration")
+            .and_then(|v| v.into_duration())
+            .unwrap_or(default.warn_duration);
         templates
             .register_template_string(
                 "battery",
                 config
                     .get("format")
                     .unescape()
-                    .unwrap_or("{{capacity}}%{{time_remaining}}".to_string()),
+                    .unwrap_or("{{capacity}}%".to_string()),
             )
             .unwrap_or_else(|e| eprintln!("Failed to parse battery format: {e}"));
         templates
@@ -321,7 +472,7 @@ impl Module for BatteryMod {
                 config
                     .get("format_time")
                     .unescape()
-                    .unwrap_or(" ({{hours}}h {{minutes}}min left)".to_string()),
+                    .unwrap_or("{{hours}}h{{pad minutes}}m".to_string()),
             )
             .unwrap_or_else(|e| eprintln!("Failed to parse battery time format: {e}"));
         templates
@@ -338,63 +489,164 @@ impl Module for BatteryMod {
                 popup_config
                     .get("format_time")
                     .unescape()
-                    .unwrap_or("\n\t{{hours}}h {{minutes}}min remaining".to_string()),
+                    .unwrap_or("\n\t{{duration seconds}} remaining".to_string()),
             )
             .unwrap_or_else(|e| eprintln!("Failed to parse battery popup time format: {e}"));
     }
 
+    fn known_keys(&self) -> Vec<ConfigKey> {
+        vec![
+            ConfigKey::new("time_unavailable", Kind::Text, Some("--")),
+            ConfigKey::new("time_discharging_only", Kind::Bool, Some("false")),
+            ConfigKey::new("animate", Kind::Bool, Some("true")),
+            // Icons separated by spaces
+            ConfigKey::new("charging_icons", Kind::Text, None),
+            ConfigKey::new("frame_interval", Kind::Duration, Some("500ms")),
+            // A color or `none`
+            ConfigKey::new("flash_color", Kind::Text, Some("rgba(255, 255, 255, 0.3)")),
+            ConfigKey::new("flash_duration", Kind::Duration, Some("600ms")),
+            ConfigKey::new("thresholds", Kind::Text, None),
+            // Percentages separated by spaces
+            ConfigKey::new("warn_levels", Kind::Text, Some("10")),
+            ConfigKey::new("warn_duration", Kind::Duration, Some("5s")),
+            ConfigKey::new("format", Kind::Text, Some("{{capacity}}%")),
+            ConfigKey::new(
+                "format_time",
+                Kind::Text,
+                Some("{{hours}}h{{pad minutes}}m"),
+            ),
+        ]
+    }
+
+    fn known_popup_keys(&self) -> Vec<ConfigKey> {
+        vec![
+            ConfigKey::new(
+                "format",
+                Kind::Text,
+                Some(
+                    r#"{{name}}: {{state}}\n\t{{icon}} {{capacity}}% ({{energy}} Wh)\n\thealth: {{health}}%{{time_remaining}}\n\tmodel: {{model}}"#,
+                ),
+            ),
+            ConfigKey::new(
+                "format_time",
+                Kind::Text,
+                Some(r#"\n\t{{duration seconds}} remaining"#),
+            ),
+        ]
+    }
+
     impl_on_click!();
 
+    fn has_popup(&self) -> bool {
+        true
+    }
+
+    fn collect_data(&mut self) -> Result<(), String> {
+        let runtime = runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .map_err(|e| e.to_string())?;
+        (self.avg, self.batteries) = runtime
+            .block_on(get_stats(None, false))
+            .map_err(|e| format!("Failed to read battery stats: {e}"))?;
+        Ok(())
+    }
+
+    fn text_content(&self, template: &Handlebars) -> Option<Result<String, String>> {
+        Some(self.text(template).map_err(|e| e.to_string()))
+    }
+
+    fn variables(&self) -> BTreeMap<String, Value> {
+        BTreeMap::from([
+            ("capacity".to_string(), self.avg.capacity.into()),
+            ("charging".to_string(), self.avg.charging.into()),
+            ("hours".to_string(), self.avg.hours.into()),
+            ("minutes".to_string(), self.avg.minutes.into()),
+            ("seconds".to_string(), self.avg.seconds().into()),
+        ])
+    }
+
     fn subscription(&self) -> Option<iced::Subscription<Message>> {
-        Some(Subscription::run(|| {
-            let (sx, mut rx) = mpsc::channel(10);
-            std::thread::spawn(move || {
-                let local = task::LocalSet::new();
-                let runtime = runtime::Builder::new_current_thread()
-                    .enable_all()
-                    .build()
-                    .unwrap();
-
-                runtime.block_on(local.run_until(async move {
-                    task::spawn_local(async move {
-                        let socket = udev::MonitorBuilder::new()
-                            .and_then(|b| b.match_subsystem_devtype("power_supply", "power_supply"))
-                            .and_then(|b| b.listen())
-                            .expect("Failed to build udev MonitorBuilder");
-
-                        loop {
-                            let Some(event) = socket.iter().next() else {
-                                sleep(Duration::from_millis(10)).await;
-                                continue;
-                            };
-
-                            if event.sysname() != "AC" {
-                                continue;
-                            }
-                            sleep(Duration::from_secs(1)).await;
-                            if sx.send(()).await.is_err() {
-                                return;
+        // The timers only exist while needed, so that there are no wakeups on battery
+        let animation = self.animating().then(|| {
+            iced::time::every(self.frame_interval)
+                .map(|_| Message::update(|reg| reg.get_module_mut::<BatteryMod>().frame += 1))
+        });
+        let flash = self.flash_until.map(|_| {
+            iced::time::every(self.flash_duration).map(|_| {
+                Message::update(|reg| {
+                    let m = reg.get_module_mut::<BatteryMod>();
+                    m.flash_until = m.flash_until.filter(|until| *until > Instant::now());
+                })
+            })
+        });
+        let warn_levels = self.warn_levels.clone();
+        let warn_duration = self.warn_duration;
+        let stats = Subscription::run_with_id(
+            ("battery", warn_levels.clone(), warn_duration),
+            stream::channel(1, move |mut sender| async move {
+                let (sx, mut rx) = mpsc::channel(10);
+                std::thread::spawn(move || {
+                    let local = task::LocalSet::new();
+                    let runtime = runtime::Builder::new_current_thread()
+                        .enable_all()
+                        .build()
+                        .unwrap();
+
+                    runtime.block_on(local.run_until(async move {
+                        task::spawn_local(async move {
+                            let socket = udev::MonitorBuilder::new()
+                                .and_then(|b| {
+                                    b.match_subsystem_devtype("power_supply", "power_supply")
+                                })
+                                .and_then(|b| b.listen())
+                                .expect("Failed to build udev MonitorBuilder");
+
+                            loop {
+                                let Some(event) = socket.iter().next() else {
+                                    sleep(Duration::from_millis(10)).await;
+                                    continue;
+                                };
+
+                                if event.sysname() != "AC" {
+                                    continue;
+                                }
+                                sleep(Duration::from_secs(1)).await;
+                                if sx.send(()).await.is_err() {
+                                    return;
+                                }
                             }
-                        }
-                    })
-                    .await
-                    .unwrap();
-                }));
-            });
+                        })
+                        .await
+                        .unwrap();
+                    }));
+                });
 
-            stream::channel(1, |mut sender| async move {
                 tokio::spawn(async move {
+                    // The capacity while discharging, `None` while charging
+                    let mut discharging = None;
                     loop {
                         let (avg, batteries) = get_stats(None, false).await.unwrap();
-                        if sender
-                            .send(Message::update(move |reg| {
-                                let m = reg.get_module_mut::<BatteryMod>();
-                                m.avg = avg;
-                                m.batteries = batteries
-                            }))
-                            .await
-                            .is_err()
-                        {
+                        let warning = match avg.charging {
+                            true => None,
+                            false => crossed_level(&warn_levels, discharging, avg.capacity),
+                        };
+                        discharging = (!avg.charging).then_some(avg.capacity);
+                        let capacity = avg.capacity;
+                        let update = Message::update(move |reg| {
+                            reg.get_module_mut::<BatteryMod>().set_stats(avg, batteries)
+                        });
+                        let msg = match warning {
+                            Some(_) => Message::Batch(vec![
+                                update,
+                                Message::toast(
+                                    format!("Battery low: {capacity}% remaining"),
+                                    warn_duration,
+                                ),
+                            ]),
+                            None => update,
+                        };
+                        if sender.send(msg).await.is_err() {
                             return;
                         }
                         select! {
@@ -403,8 +655,11 @@ impl Module for BatteryMod {
                         }
                     }
                 });
-            })
-        }))
+            }),
+        );
+        Some(Subscription::batch(
+            [Some(stats), animation, flash].into_iter().flatten(),
+        ))
     }
 }
 
@@ -513,6 +768,16 @@ fn get_property<'a>(device: &'a Device, property: &'static str) -> &'a str {
         .unwrap_or("")
 }
 
+/// The lowest warn level the capacity dropped to since the `previous` stats, `None` if there are
+/// no previous stats while discharging
+fn crossed_level(levels: &[u8], previous: Option<u8>, capacity: u8) -> Option<u8> {
+    levels
+        .iter()
+        .copied()
+        .filter(|level| capacity <= *level && previous.map_or(true, |previous| previous > *level))
+        .min()
+}
+
 async fn get_stats(
     selection: Option<&Vec<String>>,
     is_blacklist: bool,
@@ -567,3 +832,74 @@ async fn get_stats(
             time_to_full_total = SECONDS_PER_HOUR * ((energy_full_total - energy_total) / energy_rate_total);
     }
 */
+
+#[cfg(test)]
+mod tests {
+    use crate::modules::testing::Harness;
+
+    use super::*;
+
+    const FORMAT: (&str, &str) = (
+        "format",
+        "{{capacity}}%{{#if time_remaining}} {{time_remaining}}{{/if}}",
+    );
+
+    fn battery(energy_now: f32, power_now: f32, charging: bool) -> BatteryStats {
+        BatteryStats {
+            energy_now,
+            energy_full: 50_000_000.,
+            power_now,
+            voltage_now: 12_000_000.,
+            charging,
+            ..Default::default()
+        }
+    }
+
+    fn harness(options: &[(&str, &str)], stats: BatteryStats) -> Harness {
+        let avg = AverageStats::from(&vec![stats]);
+        let mut harness = Harness::new::<BatteryMod>(options);
+        harness.update(|m: &mut BatteryMod| m.set_stats(avg, vec![]));
+        harness
+    }
+
+    #[test]
+    fn empty_battery() {
+        let harness = harness(&[FORMAT], battery(0., 10_000_000., false));
+        assert_eq!(harness.text::<BatteryMod>(), "0% 0h00m");
+        let m = harness.module::<BatteryMod>();
+        assert_eq!(m.icon(None, None), &m.icons[&0]);
+    }
+
+    #[test]
+    fn discharging_battery() {
+        let harness = harness(&[FORMAT], battery(40_000_000., 16_000_000., false));
+        assert_eq!(harness.text::<BatteryMod>(), "80% 2h30m");
+        let m = harness.module::<BatteryMod>();
+        assert_eq!(m.icon(None, None), &m.icons[&80]);
+    }
+
+    #[test]
+    fn remaining_time_without_power_draw() {
+        let harness = harness(&[FORMAT], battery(25_000_000., 0., false));
+        assert_eq!(harness.text::<BatteryMod>(), "50% --");
+        let options = [FORMAT, ("time_discharging_only", "true")];
+        let harness = self::harness(&options, battery(25_000_000., 10_000_000., true));
+        assert_eq!(harness.text::<BatteryMod>(), "50%");
+    }
+
+    #[test]
+    fn warns_once_per_level() {
+        let levels = [20, 10, 5];
+        assert_eq!(crossed_level(&levels, Some(21), 20), Some(20));
+        assert_eq!(crossed_level(&levels, Some(20), 19), None);
+        assert_eq!(crossed_level(&levels, Some(12), 4), Some(5));
+        assert_eq!(crossed_level(&levels, Some(50), 40), None);
+    }
+
+    #[test]
+    fn warns_when_discharging_starts_below_a_level() {
+        assert_eq!(crossed_level(&[10], None, 8), Some(10));
+        assert_eq!(crossed_level(&[10], None, 80), None);
+        assert_eq!(crossed_level(&[], None, 1), None);
+    }
+}
@@ -1,91 +1,211 @@
 use std::{
     collections::{HashMap, HashSet},
+    sync::LazyLock,
     time::Duration,
 };
 
 use bar_rs_derive::Builder;
-use bluer::Adapter;
+use bluer::{Adapter, Address, Session};
 use handlebars::Handlebars;
 use iced::widget::button::Style;
-use iced::widget::container;
-use iced::{futures::SinkExt, stream, widget::text, Element, Subscription};
-use tokio::{io, time::sleep};
+use iced::widget::{column, container, row, scrollable, text, Column, Text};
+use iced::{
+    futures::{
+        channel::mpsc::Sender,
+        stream::{BoxStream, SelectAll},
+        SinkExt, StreamExt,
+    },
+    mouse, stream,
+    widget::Container,
+    Alignment, Element, Event,
+    Length::Fill,
+    Subscription,
+};
+use tokio::{io, select, sync::mpsc, time::sleep};
 
+use crate::bar_text::bar_text;
 use crate::button::button;
-use crate::config::popup_config::PopupConfig;
+use crate::config::popup_config::{PopupConfig, PopupConfigOverride};
 use crate::{
     config::{
         anchor::BarAnchor,
         module_config::{LocalModuleConfig, ModuleConfigOverride},
     },
     fill::FillExt,
-    Message, NERD_FONT,
+    helpers::{
+        backoff::Backoff,
+        status::{send_status, ModuleStatus},
+    },
+    Message,
 };
-use crate::{impl_on_click, impl_wrapper};
+use crate::{impl_on_click, impl_status, impl_wrapper};
+
+use super::{click_message, Action, CommandAction, Module, OnClickAction};
 
-use super::Module;
+/// How often the adapters are read even if no change was reported
+const FALLBACK_POLL: Duration = Duration::from_secs(30);
+
+static DEFAULT_ACTION: LazyLock<OnClickAction> = LazyLock::new(|| OnClickAction {
+    right: Some(Box::new(CommandAction("blueman-manager".to_string()))),
+    ..Default::default()
+});
 
 #[derive(Clone, Debug, Eq, PartialEq, Hash)]
 struct Device {
+    address: Address,
     icon: &'static str,
     name: String,
+    connected: bool,
+    battery: Option<u8>,
+}
+
+impl Device {
+    /// The icon for the type of the device, as BlueZ names it after the freedesktop icon names
+    fn icon(kind: Option<&str>) -> &'static str {
+        match kind {
+            Some("audio-card") => "󰓃",
+            Some("audio-input-microphone") => "\u{f130}",
+            Some("audio-headphones" | "audio-headset") => "󰋋",
+            Some("battery") => "󰂀",
+            Some("camera-photo") => "󰻛",
+            Some("computer") => "\u{f109}",
+            Some("input-keyboard") => "󰌌",
+            Some("input-mouse") => "󰍽",
+            Some("input-gaming") => "󰊴",
+            Some("phone") => "󰏲",
+            None => "\u{f294}",
+            Some(_) => "\u{f293}",
+        }
+    }
+
+    /// The alias of the device, or its address if the alias is empty
+    fn label(&self) -> String {
+        match self.name.trim().is_empty() {
+            true => self.address.to_string(),
+            false => self.name.clone(),
+        }
+    }
 }
 
 #[derive(Clone, Debug)]
 struct Controller {
     is_powered: bool,
-    connected_devices: HashSet<Device>,
+    /// Paired or connected devices
+    devices: Vec<Device>,
 }
 
 impl Controller {
-    async fn get_all_devices(adapter: &Adapter) -> Result<HashSet<Device>, io::Error> {
-        let mut connected_devices = HashSet::new();
+    async fn get_all_devices(adapter: &Adapter) -> Result<Vec<Device>, io::Error> {
+        let mut devices = Vec::new();
 
-        let connected_devices_addresses = adapter.device_addresses().await?;
-        for addr in connected_devices_addresses {
+        let device_addresses = adapter.device_addresses().await?;
+        for addr in device_addresses {
             let device = adapter.device(addr)?;
 
-            let icon = match device.icon().await?.unwrap_or("None".to_string()).as_ref() {
-                "audio-card" => "󰓃",
-                "audio-input-microphone" => "",
-                "audio-headphones" | "audio-headset" => "󰋋",
-                "battery" => "󰂀",
-                "camera-photo" => "󰻛",
-                "computer" => "",
-                "input-keyboard" => "󰌌",
-                "input-mouse" => "󰍽",
-                "input-gaming" => "󰊴",
-                "phone" => "󰏲",
-                "None" => "",
-                _ => "",
-            };
-            if device.is_connected().await? {
-                connected_devices.insert(Device {
-                    icon,
-                    name: device.alias().await?,
-                });
+            let connected = device.is_connected().await?;
+            if !connected && !device.is_paired().await? {
+                continue;
             }
+
+            let icon = Device::icon(device.icon().await?.as_deref());
+            devices.push(Device {
+                address: addr,
+                icon,
+                name: device.alias().await?,
+                connected,
+                // Only available if the device implements the battery interface
+                battery: device.battery_percentage().await.ok().flatten(),
+            });
         }
-        Ok(connected_devices)
+        devices.sort_by(|a, b| {
+            b.connected
+                .cmp(&a.connected)
+                .then(a.label().cmp(&b.label()))
+        });
+        Ok(devices)
     }
 
     async fn from_adaper(adapter: Adapter) -> Result<Controller, io::Error> {
         let is_powered = adapter.is_powered().await?;
         Ok(Controller {
             is_powered,
-            connected_devices: if is_powered {
+            devices: if is_powered {
                 Controller::get_all_devices(&adapter).await?
             } else {
-                HashSet::default()
+                Vec::new()
             },
         })
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BluetoothAction {
+    Connect(Address),
+    Disconnect(Address),
+    TogglePower,
+}
+
+impl BluetoothAction {
+    /// What the user is told when the action failed, before the error
+    fn failure(&self) -> &'static str {
+        match self {
+            BluetoothAction::Connect(_) => "Connection failed",
+            BluetoothAction::Disconnect(_) => "Disconnect failed",
+            BluetoothAction::TogglePower => "Power toggle failed",
+        }
+    }
+}
+
+impl Action for BluetoothAction {
+    fn as_message(&self) -> Message {
+        let action = *self;
+        Message::update(move |reg| reg.get_module_mut::<BluetoothMod>().handle_action(&action))
+    }
+}
+
+impl BluetoothAction {
+    async fn run(self, session: &Session) -> Result<(), bluer::Error> {
+        let adapters = session.adapter_names().await?;
+        match self {
+            BluetoothAction::TogglePower => {
+                let mut powered = false;
+                for name in &adapters {
+                    powered |= session.adapter(name)?.is_powered().await?;
+                }
+                for name in &adapters {
+                    session.adapter(name)?.set_powered(!powered).await?;
+                }
+            }
+            BluetoothAction::Connect(addr) | BluetoothAction::Disconnect(addr) => {
+                for name in &adapters {
+                    let adapter = session.adapter(name)?;
+                    if !adapter.device_addresses().await?.contains(&addr) {
+                        continue;
+                    }
+                    let device = adapter.device(addr)?;
+                    return match self {
+                        BluetoothAction::Connect(_) => device.connect().await,
+                        _ => device.disconnect().await,
+                    };
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
 #[derive(Debug, Builder)]
 pub struct BluetoothMod {
     controllers: Vec<Controller>,
     cfg_override: ModuleConfigOverride,
+    popup_cfg_override: PopupConfigOverride,
+    /// Passes actions to the subscription, which owns the bluetooth session
+    actions: Option<mpsc::UnboundedSender<BluetoothAction>>,
+    /// Actions which haven't finished yet
+    pending: HashSet<BluetoothAction>,
+    /// The error of the last failed action, by action
+    failed: HashMap<BluetoothAction, String>,
+    status: ModuleStatus,
 }
 
 impl Default for BluetoothMod {
@@ -93,24 +213,35 @@ impl Default for BluetoothMod {
         Self {
             controllers: Vec::new(),
             cfg_override: Default::default(),
+            popup_cfg_override: PopupConfigOverride {
+                width: Some(300),
+                height: Some(300),
+                ..Default::default()
+            },
+            actions: None,
+            pending: HashSet::new(),
+            failed: HashMap::new(),
+            status: ModuleStatus::Ok,
         }
     }
 }
 
 impl BluetoothMod {
     fn status_icon(&self) -> &'static str {
-        if self.controllers.iter().any(|c| c.is_powered) {
-            ""
+        if self.is_powered() {
+            "\u{f293}"
         } else {
-            ""
+            "\u{f294}"
         }
     }
+    fn is_powered(&self) -> bool {
+        self.controllers.iter().any(|c| c.is_powered)
+    }
+    fn devices(&self) -> impl Iterator<Item = &Device> {
+        self.controllers.iter().flat_map(|c| c.devices.iter())
+    }
     fn connected_devices(&self) -> HashSet<&Device> {
-        let mut devices = HashSet::new();
-        for c in self.controllers.iter() {
-            devices.extend(&c.connected_devices);
-        }
-        devices
+        self.devices().filter(|d| d.connected).collect()
     }
 }
 
@@ -119,27 +250,47 @@ impl Module for BluetoothMod {
         "bluetooth".to_string()
     }
 
+    impl_status!();
+
     fn view(
         &self,
         config: &LocalModuleConfig,
-        _popup_config: &PopupConfig,
+        popup_config: &PopupConfig,
         anchor: &BarAnchor,
         _handlebars: &Handlebars,
     ) -> Element<Message> {
+        let popup = Message::popup::<Self>(
+            self.popup_cfg_override.width.unwrap_or(popup_config.width),
+            self.popup_cfg_override
+                .height
+                .unwrap_or(popup_config.height),
+            anchor,
+        );
+        // Left clicks open the popup, the other buttons keep their on_click actions
+        let middle = click_message(self, mouse::Button::Middle, config);
+        let right = click_message(self, mouse::Button::Right, config);
         let connected_devices = self.connected_devices();
         let (bt_icons, bt_text) = match connected_devices.len() {
-            0 => (self.status_icon().to_string(), None),
+            0 => (
+                self.cfg_override
+                    .icon(config, self.status_icon())
+                    .to_string(),
+                None,
+            ),
             // show name if only one connected device
             1 => {
                 let device = connected_devices.iter().next().unwrap();
-                (device.icon.to_string(), Some(&device.name))
+                (
+                    self.cfg_override.icon(config, device.icon).to_string(),
+                    Some(device.label()),
+                )
             }
             // show icons for connected bluetooth devices
             _ => (
                 connected_devices
                     .iter()
                     .fold(String::new(), |mut acc, elem| {
-                        acc.push_str(elem.icon);
+                        acc.push_str(self.cfg_override.icon(config, elem.icon));
                         acc
                     }),
                 None,
@@ -153,14 +304,18 @@ impl Module for BluetoothMod {
                         .fill(anchor)
                         .color(self.cfg_override.icon_color.unwrap_or(config.icon_color))
                         .size(self.cfg_override.icon_size.unwrap_or(config.icon_size))
-                        .font(NERD_FONT)
+                        .font(config.icon_font)
                 )
                 .padding(self.cfg_override.icon_margin.unwrap_or(config.icon_margin)),
                 container(
-                    text(bt_text)
-                        .fill(anchor)
-                        .color(self.cfg_override.text_color.unwrap_or(config.text_color))
-                        .size(self.cfg_override.font_size.unwrap_or(config.font_size))
+                    bar_text(
+                        bt_text,
+                        anchor,
+                        self.cfg_override.vertical_text(config, anchor)
+                    )
+                    .color(self.cfg_override.text_color.unwrap_or(config.text_color))
+                    .size(self.cfg_override.font_size.unwrap_or(config.font_size))
+                    .font(self.cfg_override.text_font.unwrap_or(config.text_font))
                 )
                 .padding(self.cfg_override.text_margin.unwrap_or(config.text_margin))
             ]
@@ -172,62 +327,382 @@ impl Module for BluetoothMod {
                         .fill(anchor)
                         .color(self.cfg_override.icon_color.unwrap_or(config.icon_color))
                         .size(self.cfg_override.icon_size.unwrap_or(config.icon_size))
-                        .font(NERD_FONT)
+                        .font(config.icon_font)
                 )
                 .padding(self.cfg_override.icon_margin.unwrap_or(config.icon_margin))
             ]
         };
 
         button(list.spacing(self.cfg_override.spacing.unwrap_or(config.spacing)))
+            .on_event_try(
+                move |event, layout, cursor, clipboard, viewport| match event {
+                    Event::Mouse(mouse::Event::ButtonReleased(mouse::Button::Middle)) => {
+                        middle.clone()
+                    }
+                    Event::Mouse(mouse::Event::ButtonReleased(mouse::Button::Right)) => {
+                        right.clone()
+                    }
+                    _ => Some(popup(event, layout, cursor, clipboard, viewport)),
+                },
+            )
             .style(|_, _| Style::default())
             .into()
     }
 
+    fn popup_cfg_override(&self) -> Option<&PopupConfigOverride> {
+        Some(&self.popup_cfg_override)
+    }
+
+    fn popup_view<'a>(
+        &'a self,
+        config: &'a PopupConfig,
+        _template: &Handlebars,
+    ) -> Element<'a, Message> {
+        let fmt_text = |text: Text<'a>| -> Container<'a, Message> {
+            container(
+                text.size(
+                    self.popup_cfg_override
+                        .font_size
+                        .unwrap_or(config.font_size),
+                )
+                .color(
+                    self.popup_cfg_override
+                        .text_color
+                        .unwrap_or(config.text_color),
+                ),
+            )
+            .padding(
+                self.popup_cfg_override
+                    .text_margin
+                    .unwrap_or(config.text_margin),
+            )
+        };
+        let icon = |icon: &'a str| -> Container<'a, Message> {
+            container(
+                text(config.icon(icon))
+                    .font(config.icon_font)
+                    .size(
+                        self.popup_cfg_override
+                            .icon_size
+                            .unwrap_or(config.icon_size),
+                    )
+                    .color(
+                        self.popup_cfg_override
+                            .icon_color
+                            .unwrap_or(config.icon_color),
+                    ),
+            )
+            .padding(
+                self.popup_cfg_override
+                    .icon_margin
+                    .unwrap_or(config.icon_margin),
+            )
+        };
+        // The text of an action button, or its error if it failed
+        let status = |action: BluetoothAction, label: &'a str| -> (String, Option<String>) {
+            match self.pending.contains(&action) {
+                true => ("...".to_string(), None),
+                false => (
+                    label.to_string(),
+                    self.failed
+                        .get(&action)
+                        .map(|e| format!("{}: {e}", action.failure())),
+                ),
+            }
+        };
+
+        let power = BluetoothAction::TogglePower;
+        let (power_label, power_error) = status(
+            power,
+            match self.is_powered() {
+                true => "Turn off",
+                false => "Turn on",
+            },
+        );
+        let header = column![row![
+            icon(self.status_icon()),
+            fmt_text(text("Bluetooth")).width(Fill),
+            button(fmt_text(text(power_label)))
+                .on_event(power.as_message())
+                .style(|_, _| Style::default()),
+        ]
+        .align_y(Alignment::Center)]
+        .push_maybe(power_error.map(|e| fmt_text(text(e))));
+
+        let devices = self.devices().map(|device| -> Element<'a, Message> {
+            let action = match device.connected {
+                true => BluetoothAction::Disconnect(device.address),
+                false => BluetoothAction::Connect(device.address),
+            };
+            let (label, error) = status(
+                action,
+                match device.connected {
+                    true => "Disconnect",
+                    false => "Connect",
+                },
+            );
+            column![row![
+                icon(device.icon),
+                fmt_text(text(device.label())).width(Fill),
+            ]
+            .push_maybe(device.battery.map(|battery| fmt_text(text!("{battery}%"))))
+            .push(
+                button(fmt_text(text(label)))
+                    .on_event(action.as_message())
+                    .style(|_, _| Style::default())
+            )
+            .align_y(Alignment::Center)]
+            .push_maybe(error.map(|e| fmt_text(text(e))))
+            .into()
+        });
+
+        container(scrollable(
+            Column::with_children([header.into()].into_iter().chain(devices))
+                .spacing(self.popup_cfg_override.spacing.unwrap_or(config.spacing)),
+        ))
+        .into()
+    }
+
     impl_wrapper!();
 
     fn read_config(
         &mut self,
         config: &HashMap<String, Option<String>>,
-        _popup_config: &HashMap<String, Option<String>>,
+        popup_config: &HashMap<String, Option<String>>,
         _templates: &mut Handlebars,
+        scale: f32,
     ) {
-        self.cfg_override = config.into();
+        self.cfg_override = (config, scale).into();
+        self.popup_cfg_override.update(popup_config, scale);
     }
 
     impl_on_click!();
 
+    fn has_popup(&self) -> bool {
+        true
+    }
+
+    fn default_action(&self) -> Option<&OnClickAction> {
+        Some(&DEFAULT_ACTION)
+    }
+
+    fn handle_action(&mut self, action: &dyn Action) {
+        let Some(action) = action.downcast_ref::<BluetoothAction>() else {
+            return;
+        };
+        self.failed.remove(action);
+        match self.actions.as_ref().map(|sender| sender.send(*action)) {
+            Some(Ok(())) => {
+                self.pending.insert(*action);
+            }
+            _ => {
+                self.failed
+                    .insert(*action, "the bluetooth service is unavailable".to_string());
+            }
+        }
+    }
+
+    /// The name of the connected device if there is only one, like in the bar
+    fn text_content(&self, _template: &Handlebars) -> Option<Result<String, String>> {
+        let connected = self.connected_devices();
+        Some(Ok(match connected.len() {
+            1 => connected.iter().next().unwrap().label(),
+            _ => String::new(),
+        }))
+    }
+
     fn subscription(&self) -> Option<iced::Subscription<Message>> {
         Some(Subscription::run(|| {
             stream::channel(1, |mut sender| async move {
-                if let Ok(session) = bluer::Session::new().await {
-                    loop {
-                        let mut controllers: Vec<Controller> = Vec::new();
-                        let Ok(adapter_names) = session.adapter_names().await else {
-                            return;
-                        };
-                        for adapter_name in adapter_names {
-                            // swallow any io errors for fetch adaper informations,
-                            // because it will be retred and frequently fetch in a loop
-                            if let Ok(adapter) = session.adapter(&adapter_name) {
-                                if let Ok(controller) = Controller::from_adaper(adapter).await {
-                                    controllers.push(controller);
-                                }
-                            }
-                        }
-                        if sender
-                            .send(Message::update(move |reg| {
-                                let m = reg.get_module_mut::<BluetoothMod>();
-                                m.controllers = controllers
-                            }))
-                            .await
-                            .is_err()
-                        {
-                            return;
-                        }
-                        sleep(Duration::from_secs(1)).await;
+                let (action_sx, mut action_rx) = mpsc::unbounded_channel();
+                if sender
+                    .send(Message::update(move |reg| {
+                        reg.get_module_mut::<BluetoothMod>().actions = Some(action_sx)
+                    }))
+                    .await
+                    .is_err()
+                {
+                    return;
+                }
+                // bluetoothd may be started after the bar, or restarted
+                let mut backoff = Backoff::default();
+                while let Err(e) = watch_bluetooth(&mut sender, &mut action_rx, &mut backoff).await
+                {
+                    if !send_status::<BluetoothMod>(&mut sender, ModuleStatus::Unavailable(e)).await
+                    {
+                        return;
                     }
+                    backoff.wait().await;
                 }
             })
         }))
     }
 }
+
+/// Reads the bluetooth adapters whenever they or their devices change, and runs the actions of the
+/// popup. Returns if the bar is closing.
+async fn watch_bluetooth(
+    sender: &mut Sender<Message>,
+    actions: &mut mpsc::UnboundedReceiver<BluetoothAction>,
+    backoff: &mut Backoff,
+) -> Result<(), String> {
+    let session = Session::new()
+        .await
+        .map_err(|e| format!("Failed to connect to the bluetooth service: {e}"))?;
+    // Adapters being plugged in or removed
+    let mut session_events = session
+        .events()
+        .await
+        .map_err(|e| format!("Failed to watch the bluetooth adapters: {e}"))?
+        .boxed();
+    loop {
+        let mut controllers: Vec<Controller> = Vec::new();
+        let adapter_names = session
+            .adapter_names()
+            .await
+            .map_err(|e| format!("Failed to list the bluetooth adapters: {e}"))?;
+        backoff.reset();
+        let status = match adapter_names.is_empty() {
+            true => ModuleStatus::Unavailable("No bluetooth adapter found".to_string()),
+            false => ModuleStatus::Ok,
+        };
+        let mut changes = SelectAll::new();
+        for adapter_name in adapter_names {
+            // swallow any io errors for fetch adaper informations,
+            // because they are read again on the next change or poll
+            if let Ok(adapter) = session.adapter(&adapter_name) {
+                watch_adapter(&adapter, &mut changes).await;
+                if let Ok(controller) = Controller::from_adaper(adapter).await {
+                    controllers.push(controller);
+                }
+            }
+        }
+        if sender
+            .send(Message::update(move |reg| {
+                let m = reg.get_module_mut::<BluetoothMod>();
+                m.controllers = controllers;
+                m.set_status(status);
+            }))
+            .await
+            .is_err()
+        {
+            return Ok(());
+        }
+        select! {
+            // In case a change was missed
+            _ = sleep(FALLBACK_POLL) => {}
+            Some(_) = session_events.next() => {}
+            Some(_) = changes.next() => {
+                // Connecting a device changes several properties at once
+                sleep(Duration::from_millis(100)).await;
+            }
+            Some(action) = actions.recv() => {
+                let result = action.run(&session).await;
+                if sender
+                    .send(Message::update(move |reg| {
+                        let m = reg.get_module_mut::<BluetoothMod>();
+                        m.pending.remove(&action);
+                        if let Err(e) = result {
+                            m.failed.insert(action, e.to_string());
+                        }
+                    }))
+                    .await
+                    .is_err()
+                {
+                    return Ok(());
+                }
+            }
+        }
+    }
+}
+
+/// Adds the property changes of the adapter and its devices (e.g. being powered or connected),
+/// and devices being paired or removed, to `changes`
+async fn watch_adapter(adapter: &Adapter, changes: &mut SelectAll<BoxStream<'static, ()>>) {
+    if let Ok(events) = adapter.events().await {
+        changes.push(events.map(|_| ()).boxed());
+    }
+    for addr in adapter.device_addresses().await.unwrap_or_default() {
+        if let Ok(device) = adapter.device(addr) {
+            if let Ok(events) = device.events().await {
+                changes.push(events.map(|_| ()).boxed());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::modules::testing::Harness;
+
+    use super::*;
+
+    fn device(last: u8, name: &str, connected: bool) -> Device {
+        Device {
+            address: Address::new([0x00, 0x1a, 0x7d, 0xda, 0x71, last]),
+            icon: Device::icon(Some("audio-headphones")),
+            name: name.to_string(),
+            connected,
+            battery: None,
+        }
+    }
+
+    fn text(devices: Vec<Device>) -> String {
+        let mut harness = Harness::new::<BluetoothMod>(&[]);
+        harness.update(|m: &mut BluetoothMod| {
+            m.controllers = vec![Controller {
+                is_powered: true,
+                devices,
+            }]
+        });
+        harness.text::<BluetoothMod>()
+    }
+
+    #[test]
+    fn shows_the_only_connected_device() {
+        assert_eq!(
+            text(vec![
+                device(1, "Headphones", true),
+                device(2, "Mouse", false)
+            ]),
+            "Headphones"
+        );
+        assert_eq!(text(vec![device(1, "Headphones", false)]), "");
+        assert_eq!(
+            text(vec![
+                device(1, "Headphones", true),
+                device(2, "Mouse", true)
+            ]),
+            ""
+        );
+    }
+
+    #[test]
+    fn picks_icons_by_device_type() {
+        assert_eq!(Device::icon(Some("audio-headset")), "\u{f02cb}");
+        assert_eq!(Device::icon(Some("computer")), "\u{f109}");
+        assert_eq!(Device::icon(Some("audio-input-microphone")), "\u{f130}");
+        assert_eq!(Device::icon(None), "\u{f294}");
+        assert_eq!(Device::icon(Some("printer")), "\u{f293}");
+    }
+
+    #[test]
+    fn shows_the_power_state() {
+        let mut harness = Harness::new::<BluetoothMod>(&[]);
+        harness.update(|m: &mut BluetoothMod| {
+            m.controllers = vec![Controller {
+                is_powered: true,
+                devices: vec![],
+            }]
+        });
+        assert_eq!(harness.module::<BluetoothMod>().status_icon(), "\u{f293}");
+        harness.update(|m: &mut BluetoothMod| m.controllers[0].is_powered = false);
+        assert_eq!(harness.module::<BluetoothMod>().status_icon(), "\u{f294}");
+    }
+
+    #[test]
+    fn devices_without_alias_show_their_address() {
+        assert_eq!(text(vec![device(0x13, "", true)]), "00:1A:7D:DA:71:13");
+        assert_eq!(text(vec![device(0x13, "  ", true)]), "00:1A:7D:DA:71:13");
+    }
+}
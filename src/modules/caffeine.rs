@@ -0,0 +1,213 @@
+use std::{collections::HashMap, process::Command, time::Duration};
+
+use bar_rs_derive::Builder;
+use handlebars::Handlebars;
+use iced::widget::{container, text};
+use iced::{Color, Element, Subscription};
+
+use crate::config::parse::StringExt;
+use crate::config::popup_config::PopupConfig;
+use crate::helpers::process::ChildGroup;
+use crate::{
+    config::{
+        anchor::BarAnchor,
+        module_config::{LocalModuleConfig, ModuleConfigOverride},
+        schema::{ConfigKey, Kind},
+    },
+    fill::FillExt,
+    impl_on_click, impl_wrapper, Message,
+};
+
+use super::{Action, Module, OnClickAction};
+
+#[derive(Debug, Builder)]
+pub struct CaffeineMod {
+    cfg_override: ModuleConfigOverride,
+    icon_on: String,
+    icon_off: String,
+    /// The icon color while idling is inhibited
+    color_on: Option<Color>,
+    /// The command holding the inhibitor for as long as it runs
+    command: String,
+    /// Toggles the inhibitor on left click
+    toggle: OnClickAction,
+    /// The running inhibitor, `None` while idling is allowed
+    inhibitor: Option<ChildGroup<std::process::Child>>,
+}
+
+impl Default for CaffeineMod {
+    fn default() -> Self {
+        Self {
+            cfg_override: Default::default(),
+            icon_on: "󰅶".to_string(),
+            icon_off: "󰛊".to_string(),
+            color_on: None,
+            command:
+                "systemd-inhibit --what=idle --who=bar-rs --why='Caffeine mode' sleep infinity"
+                    .to_string(),
+            toggle: OnClickAction {
+                left: Some(Box::new(CaffeineAction::Toggle)),
+                ..Default::default()
+            },
+            inhibitor: None,
+        }
+    }
+}
+
+impl CaffeineMod {
+    fn icon(&self) -> &str {
+        match self.inhibitor.is_some() {
+            true => &self.icon_on,
+            false => &self.icon_off,
+        }
+    }
+
+    fn inhibit(&mut self) {
+        match ChildGroup::spawn_std(Command::new("sh").arg("-c").arg(&self.command)) {
+            Ok(inhibitor) => self.inhibitor = Some(inhibitor),
+            Err(e) => eprintln!("Failed to run the idle inhibitor `{}`: {e}", self.command),
+        }
+    }
+
+    fn release(&mut self) {
+        if let Some(inhibitor) = self.inhibitor.take() {
+            inhibitor.kill();
+        }
+    }
+
+    /// Notices if the inhibitor exited by itself, e.g. because `systemd-inhibit` isn't installed
+    fn check(&mut self) {
+        let Some(inhibitor) = self.inhibitor.as_mut() else {
+            return;
+        };
+        match inhibitor.child.try_wait() {
+            Ok(None) => {}
+            Ok(Some(status)) => {
+                eprintln!("The idle inhibitor `{}` exited with {status}", self.command);
+                self.inhibitor = None;
+            }
+            Err(e) => eprintln!("Failed to check on the idle inhibitor: {e}"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum CaffeineAction {
+    Toggle,
+}
+
+impl Action for CaffeineAction {
+    fn as_message(&self) -> Message {
+        let action = *self;
+        Message::update(move |reg| reg.get_module_mut::<CaffeineMod>().handle_action(&action))
+    }
+}
+
+impl Module for CaffeineMod {
+    fn name(&self) -> String {
+        "caffeine".to_string()
+    }
+
+    fn view(
+        &self,
+        config: &LocalModuleConfig,
+        _popup_config: &PopupConfig,
+        anchor: &BarAnchor,
+        _handlebars: &Handlebars,
+    ) -> Element<Message> {
+        let color = match self.inhibitor {
+            Some(_) => self.color_on,
+            None => None,
+        };
+        container(
+            text(self.cfg_override.icon(config, self.icon()))
+                .fill(anchor)
+                .size(self.cfg_override.icon_size.unwrap_or(config.icon_size))
+                .color(
+                    color
+                        .or(self.cfg_override.icon_color)
+                        .unwrap_or(config.icon_color),
+                )
+                .font(config.icon_font),
+        )
+        .padding(self.cfg_override.icon_margin.unwrap_or(config.icon_margin))
+        .into()
+    }
+
+    impl_wrapper!();
+
+    fn read_config(
+        &mut self,
+        config: &HashMap<String, Option<String>>,
+        _popup_config: &HashMap<String, Option<String>>,
+        _templates: &mut Handlebars,
+        scale: f32,
+    ) {
+        let default = Self::default();
+        self.cfg_override = (config, scale).into();
+        self.icon_on = config
+            .get("icon_on")
+            .and_then(|v| v.clone())
+            .unwrap_or(default.icon_on);
+        self.icon_off = config
+            .get("icon_off")
+            .and_then(|v| v.clone())
+            .unwrap_or(default.icon_off);
+        self.color_on = config
+            .get("color_on")
+            .and_then(|v| v.into_color())
+            .or(default.color_on);
+        // A running inhibitor is kept, even if the command changed
+        self.command = config
+            .get("command")
+            .and_then(|v| v.clone())
+            .unwrap_or(default.command);
+    }
+
+    fn known_keys(&self) -> Vec<ConfigKey> {
+        vec![
+            ConfigKey::new("icon_on", Kind::Text, Some("󰅶")),
+            ConfigKey::new("icon_off", Kind::Text, Some("󰛊")),
+            ConfigKey::new("color_on", Kind::Color, None),
+            ConfigKey::new(
+                "command",
+                Kind::Text,
+                Some(
+                    "systemd-inhibit --what=idle --who=bar-rs --why='Caffeine mode' sleep infinity",
+                ),
+            ),
+        ]
+    }
+
+    impl_on_click!();
+
+    fn default_action(&self) -> Option<&OnClickAction> {
+        Some(&self.toggle)
+    }
+
+    fn handle_action(&mut self, action: &dyn Action) {
+        let Some(CaffeineAction::Toggle) = action.downcast_ref::<CaffeineAction>() else {
+            return;
+        };
+        match self.inhibitor.is_some() {
+            true => self.release(),
+            false => self.inhibit(),
+        }
+    }
+
+    fn text_content(&self, _template: &Handlebars) -> Option<Result<String, String>> {
+        Some(Ok(match self.inhibitor.is_some() {
+            true => "caffeine on".to_string(),
+            false => "caffeine off".to_string(),
+        }))
+    }
+
+    fn subscription(&self) -> Option<Subscription<Message>> {
+        // Only needed while the inhibitor runs
+        self.inhibitor.as_ref()?;
+        Some(
+            iced::time::every(Duration::from_secs(5))
+                .map(|_| Message::update(|reg| reg.get_module_mut::<CaffeineMod>().check())),
+        )
+    }
+}
@@ -0,0 +1,579 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    fs,
+    path::PathBuf,
+    process::Stdio,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use bar_rs_derive::Builder;
+use handlebars::Handlebars;
+use iced::widget::{button::Style, container, row, scrollable, text, Column, Container};
+use iced::{
+    futures::{channel::mpsc::Sender, SinkExt},
+    stream, Alignment, Element,
+    Length::Fill,
+    Subscription,
+};
+use serde::{Deserialize, Serialize};
+use tokio::{
+    io::{AsyncBufReadExt, BufReader},
+    process::Command,
+};
+
+use crate::bar_text::bar_text;
+use crate::button::button;
+use crate::config::get_cache_dir;
+use crate::config::parse::StringExt;
+use crate::config::popup_config::{PopupConfig, PopupConfigOverride};
+use crate::helpers::{backoff::Backoff, status::ModuleStatus};
+use crate::list::list;
+use crate::{
+    config::{
+        anchor::BarAnchor,
+        module_config::{LocalModuleConfig, ModuleConfigOverride},
+        schema::{ConfigKey, Kind},
+    },
+    fill::FillExt,
+    Message,
+};
+use crate::{impl_on_click, impl_status, impl_wrapper};
+
+use super::{Action, Module};
+
+/// Mime types `wl-paste` offers for text, the first one offered is stored
+const TEXT_TYPES: &[&str] = &[
+    "text/plain;charset=utf-8",
+    "text/plain",
+    "UTF8_STRING",
+    "STRING",
+    "TEXT",
+];
+/// Set by password managers like KeePassXC for copied passwords
+const PASSWORD_HINT: &str = "x-kde-passwordManagerHint";
+
+/// An entry of the clipboard history, its content is stored in a file of the same id
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClipEntry {
+    id: u128,
+    mime: String,
+    /// The first line of text entries
+    preview: String,
+    size: usize,
+}
+
+impl ClipEntry {
+    fn path(&self) -> Option<PathBuf> {
+        history_dir().map(|dir| dir.join(self.id.to_string()))
+    }
+
+    fn is_image(&self) -> bool {
+        self.mime.starts_with("image/")
+    }
+
+    /// Puts the entry back on the clipboard
+    fn copy_command(&self) -> Option<String> {
+        self.path().map(|path| {
+            format!(
+                "wl-copy --type '{}' < '{}'",
+                self.mime,
+                path.to_string_lossy()
+            )
+        })
+    }
+}
+
+#[derive(Debug, Builder)]
+pub struct ClipboardMod {
+    cfg_override: ModuleConfigOverride,
+    popup_cfg_override: PopupConfigOverride,
+    /// The newest entry first
+    history: VecDeque<ClipEntry>,
+    icon: String,
+    image_icon: String,
+    max_entries: usize,
+    /// The number of characters shown of text entries
+    preview_len: usize,
+    /// Skip entries marked as passwords by password managers
+    ignore_passwords: bool,
+    status: ModuleStatus,
+}
+
+impl Default for ClipboardMod {
+    fn default() -> Self {
+        Self {
+            cfg_override: Default::default(),
+            popup_cfg_override: PopupConfigOverride {
+                width: Some(400),
+                height: Some(300),
+                ..Default::default()
+            },
+            history: VecDeque::new(),
+            icon: "".to_string(),
+            image_icon: "".to_string(),
+            max_entries: 50,
+            preview_len: 50,
+            ignore_passwords: true,
+            status: ModuleStatus::Ok,
+        }
+    }
+}
+
+impl ClipboardMod {
+    /// The text of an entry in the popup, shortened to `preview_len` characters
+    fn preview(&self, entry: &ClipEntry) -> String {
+        if entry.is_image() {
+            return format!("{} ({})", entry.mime, human_size(entry.size));
+        }
+        match entry.preview.chars().count() > self.preview_len {
+            true => format!(
+                "{}…",
+                entry
+                    .preview
+                    .chars()
+                    .take(self.preview_len)
+                    .collect::<String>()
+            ),
+            false => entry.preview.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum ClipboardAction {
+    /// Copy the entry back to the clipboard
+    Copy(ClipEntry),
+}
+
+impl Action for ClipboardAction {
+    fn as_message(&self) -> Message {
+        let action = self.clone();
+        let ClipboardAction::Copy(entry) = self;
+        let copy = entry.copy_command().map(Message::command_sh);
+        Message::Batch(
+            [
+                Message::update(move |reg| {
+                    reg.get_module_mut::<ClipboardMod>().handle_action(&action)
+                }),
+                Message::ClosePopup,
+            ]
+            .into_iter()
+            .chain(copy)
+            .collect(),
+        )
+    }
+}
+
+impl Module for ClipboardMod {
+    fn name(&self) -> String {
+        "clipboard".to_string()
+    }
+
+    impl_status!();
+
+    fn view(
+        &self,
+        config: &LocalModuleConfig,
+        popup_config: &PopupConfig,
+        anchor: &BarAnchor,
+        _handlebars: &Handlebars,
+    ) -> Element<Message> {
+        button(
+            list![
+                anchor,
+                container(
+                    text(self.cfg_override.icon(config, &self.icon))
+                        .fill(anchor)
+                        .size(self.cfg_override.icon_size.unwrap_or(config.icon_size))
+                        .color(self.cfg_override.icon_color.unwrap_or(config.icon_color))
+                        .font(config.icon_font)
+                )
+                .padding(self.cfg_override.icon_margin.unwrap_or(config.icon_margin)),
+                container(
+                    bar_text(
+                        self.history.len().to_string(),
+                        anchor,
+                        self.cfg_override.vertical_text(config, anchor)
+                    )
+                    .size(self.cfg_override.font_size.unwrap_or(config.font_size))
+                    .font(self.cfg_override.text_font.unwrap_or(config.text_font))
+                    .color(self.cfg_override.text_color.unwrap_or(config.text_color))
+                )
+                .padding(self.cfg_override.text_margin.unwrap_or(config.text_margin)),
+            ]
+            .spacing(self.cfg_override.spacing.unwrap_or(config.spacing)),
+        )
+        .on_event_with(Message::popup::<Self>(
+            self.popup_cfg_override.width.unwrap_or(popup_config.width),
+            self.popup_cfg_override
+                .height
+                .unwrap_or(popup_config.height),
+            anchor,
+        ))
+        .style(|_, _| Style::default())
+        .into()
+    }
+
+    fn popup_cfg_override(&self) -> Option<&PopupConfigOverride> {
+        Some(&self.popup_cfg_override)
+    }
+
+    fn popup_view<'a>(
+        &'a self,
+        config: &'a PopupConfig,
+        _template: &Handlebars,
+    ) -> Element<'a, Message> {
+        let fmt_text = |content: String| -> Container<'a, Message> {
+            container(
+                text(content)
+                    .size(
+                        self.popup_cfg_override
+                            .font_size
+                            .unwrap_or(config.font_size),
+                    )
+                    .color(
+                        self.popup_cfg_override
+                            .text_color
+                            .unwrap_or(config.text_color),
+                    ),
+            )
+            .padding(
+                self.popup_cfg_override
+                    .text_margin
+                    .unwrap_or(config.text_margin),
+            )
+        };
+        let entries = self.history.iter().map(|entry| -> Element<'a, Message> {
+            // Images are only named, with an icon in place of a thumbnail
+            let icon = entry.is_image().then(|| {
+                container(
+                    text(config.icon(&self.image_icon))
+                        .font(config.icon_font)
+                        .size(
+                            self.popup_cfg_override
+                                .icon_size
+                                .unwrap_or(config.icon_size),
+                        )
+                        .color(
+                            self.popup_cfg_override
+                                .icon_color
+                                .unwrap_or(config.icon_color),
+                        ),
+                )
+                .padding(
+                    self.popup_cfg_override
+                        .icon_margin
+                        .unwrap_or(config.icon_margin),
+                )
+            });
+            button(
+                row![]
+                    .push_maybe(icon)
+                    .push(fmt_text(self.preview(entry)).width(Fill))
+                    .align_y(Alignment::Center),
+            )
+            .on_event(ClipboardAction::Copy(entry.clone()).as_message())
+            .style(|_, _| Style::default())
+            .width(Fill)
+            .into()
+        });
+        container(scrollable(
+            Column::with_children(entries)
+                .push_maybe(
+                    self.history
+                        .is_empty()
+                        .then(|| fmt_text("The clipboard history is empty".to_string())),
+                )
+                .spacing(self.popup_cfg_override.spacing.unwrap_or(config.spacing)),
+        ))
+        .into()
+    }
+
+    impl_wrapper!();
+
+    fn read_config(
+        &mut self,
+        config: &HashMap<String, Option<String>>,
+        popup_config: &HashMap<String, Option<String>>,
+        _templates: &mut Handlebars,
+        scale: f32,
+    ) {
+        let default = Self::default();
+        self.cfg_override = (config, scale).into();
+        self.popup_cfg_override.update(popup_config, scale);
+        self.icon = config
+            .get("icon")
+            .and_then(|v| v.clone())
+            .unwrap_or(default.icon);
+        self.image_icon = popup_config
+            .get("image_icon")
+            .and_then(|v| v.clone())
+            .unwrap_or(default.image_icon);
+        self.max_entries = config
+            .get("max_entries")
+            .and_then(|v| v.as_ref().and_then(|v| v.parse().ok()))
+            .unwrap_or(default.max_entries);
+        self.preview_len = config
+            .get("preview_len")
+            .and_then(|v| v.as_ref().and_then(|v| v.parse().ok()))
+            .unwrap_or(default.preview_len);
+        self.ignore_passwords = config
+            .get("ignore_passwords")
+            .and_then(|v| v.into_bool())
+            .unwrap_or(default.ignore_passwords);
+    }
+
+    fn known_keys(&self) -> Vec<ConfigKey> {
+        vec![
+            ConfigKey::new("icon", Kind::Text, Some("")),
+            ConfigKey::new("max_entries", Kind::Integer, Some("50")),
+            ConfigKey::new("preview_len", Kind::Integer, Some("50")),
+            ConfigKey::new("ignore_passwords", Kind::Bool, Some("true")),
+        ]
+    }
+
+    fn known_popup_keys(&self) -> Vec<ConfigKey> {
+        vec![ConfigKey::new("image_icon", Kind::Text, Some(""))]
+    }
+
+    impl_on_click!();
+
+    fn has_popup(&self) -> bool {
+        true
+    }
+
+    fn handle_action(&mut self, action: &dyn Action) {
+        let Some(ClipboardAction::Copy(entry)) = action.downcast_ref::<ClipboardAction>() else {
+            return;
+        };
+        // wl-paste reports the copied entry again, which moves it to the top anyway
+        if let Some(index) = self.history.iter().position(|e| e.id == entry.id) {
+            if let Some(entry) = self.history.remove(index) {
+                self.history.push_front(entry);
+            }
+        }
+    }
+
+    fn collect_data(&mut self) -> Result<(), String> {
+        self.history = load_history();
+        Ok(())
+    }
+
+    fn text_content(&self, _template: &Handlebars) -> Option<Result<String, String>> {
+        Some(Ok(format!("{} {}", self.icon, self.history.len())))
+    }
+
+    fn subscription(&self) -> Option<Subscription<Message>> {
+        let (max_entries, ignore_passwords) = (self.max_entries, self.ignore_passwords);
+        Some(Subscription::run_with_id(
+            ("clipboard", max_entries, ignore_passwords),
+            stream::channel(1, move |mut sender| async move {
+                let mut history = load_history();
+                history.truncate(max_entries);
+                let mut backoff = Backoff::default();
+                loop {
+                    let Err(e) = watch_clipboard(
+                        &mut sender,
+                        &mut history,
+                        &mut backoff,
+                        max_entries,
+                        ignore_passwords,
+                    )
+                    .await
+                    else {
+                        return;
+                    };
+                    if !send_history(&mut sender, &history, ModuleStatus::Unavailable(e)).await {
+                        return;
+                    }
+                    backoff.wait().await;
+                }
+            }),
+        ))
+    }
+}
+
+async fn send_history(
+    sender: &mut Sender<Message>,
+    history: &VecDeque<ClipEntry>,
+    status: ModuleStatus,
+) -> bool {
+    let history = history.clone();
+    sender
+        .send(Message::update(move |reg| {
+            let clipboard = reg.get_module_mut::<ClipboardMod>();
+            clipboard.history = history;
+            clipboard.set_status(status);
+        }))
+        .await
+        .is_ok()
+}
+
+/// Adds every new clipboard content to the history. Returns if the bar is closing.
+async fn watch_clipboard(
+    sender: &mut Sender<Message>,
+    history: &mut VecDeque<ClipEntry>,
+    backoff: &mut Backoff,
+    max_entries: usize,
+    ignore_passwords: bool,
+) -> Result<(), String> {
+    // `wl-paste --watch` runs the command whenever the clipboard changes
+    let mut child = Command::new("wl-paste")
+        .args(["--watch", "echo"])
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|e| format!("Failed to run wl-paste: {e}"))?;
+    let stdout = child
+        .stdout
+        .take()
+        .ok_or("wl-paste did not have a handle to stdout")?;
+    backoff.reset();
+    if !send_history(sender, history, ModuleStatus::Ok).await {
+        return Ok(());
+    }
+    let mut changes = BufReader::new(stdout).lines();
+    while changes
+        .next_line()
+        .await
+        .map_err(|e| format!("Failed to read from wl-paste: {e}"))?
+        .is_some()
+    {
+        let Some((mime, content)) = read_clipboard(ignore_passwords).await else {
+            continue;
+        };
+        add_entry(history, mime, content, max_entries);
+        if !send_history(sender, history, ModuleStatus::Ok).await {
+            return Ok(());
+        }
+    }
+    Err("wl-paste stopped".to_string())
+}
+
+/// The mime type and content of the clipboard, `None` if it's empty, neither text nor an image,
+/// or a password which should be ignored
+async fn read_clipboard(ignore_passwords: bool) -> Option<(String, Vec<u8>)> {
+    let types = Command::new("wl-paste")
+        .arg("--list-types")
+        .output()
+        .await
+        .ok()?
+        .stdout;
+    let types = String::from_utf8_lossy(&types);
+    let types: Vec<&str> = types.lines().map(|t| t.trim()).collect();
+    if ignore_passwords && types.contains(&PASSWORD_HINT) {
+        return None;
+    }
+    let mime = TEXT_TYPES
+        .iter()
+        .find(|t| types.contains(t))
+        .or_else(|| types.iter().find(|t| t.starts_with("image/")))?
+        .to_string();
+    let content = Command::new("wl-paste")
+        .args(["--no-newline", "--type", &mime])
+        .output()
+        .await
+        .ok()?
+        .stdout;
+    let mime = match TEXT_TYPES.contains(&mime.as_str()) {
+        true => "text/plain;charset=utf-8".to_string(),
+        false => mime,
+    };
+    (!content.is_empty()).then_some((mime, content))
+}
+
+/// Stores the content as the newest entry, an equal older entry is replaced
+fn add_entry(history: &mut VecDeque<ClipEntry>, mime: String, content: Vec<u8>, max: usize) {
+    let Some(dir) = history_dir() else {
+        return;
+    };
+    let id = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|time| time.as_nanos())
+        .unwrap_or_default();
+    let entry = ClipEntry {
+        id,
+        preview: match mime.starts_with("image/") {
+            true => String::new(),
+            false => String::from_utf8_lossy(&content)
+                .lines()
+                .map(|line| line.trim())
+                .find(|line| !line.is_empty())
+                .unwrap_or_default()
+                .to_string(),
+        },
+        mime,
+        size: content.len(),
+    };
+    if let Some(index) = history.iter().position(|e| {
+        e.mime == entry.mime
+            && e.size == entry.size
+            && e.path().and_then(|path| fs::read(path).ok()).as_ref() == Some(&content)
+    }) {
+        remove_entry(history, index);
+    }
+    if let Err(e) = fs::write(dir.join(id.to_string()), &content) {
+        eprintln!("[clipboard] Failed to store a clipboard entry: {e}");
+        return;
+    }
+    history.push_front(entry);
+    while history.len() > max {
+        remove_entry(history, history.len() - 1);
+    }
+    save_history(history);
+}
+
+fn remove_entry(history: &mut VecDeque<ClipEntry>, index: usize) {
+    if let Some(path) = history.remove(index).and_then(|entry| entry.path()) {
+        let _ = fs::remove_file(path);
+    }
+}
+
+/// `$XDG_CACHE_HOME/bar-rs/clipboard`, which holds the content of every entry and `history.json`
+fn history_dir() -> Option<PathBuf> {
+    let dir = get_cache_dir()?.join("clipboard");
+    fs::create_dir_all(&dir)
+        .map_err(|e| {
+            eprintln!(
+                "[clipboard] Failed to create {}: {e}",
+                dir.to_string_lossy()
+            )
+        })
+        .ok()?;
+    Some(dir)
+}
+
+fn load_history() -> VecDeque<ClipEntry> {
+    history_dir()
+        .and_then(|dir| fs::read_to_string(dir.join("history.json")).ok())
+        .and_then(|json| {
+            serde_json::from_str(&json)
+                .map_err(|e| eprintln!("[clipboard] Failed to parse the history: {e}"))
+                .ok()
+        })
+        .unwrap_or_default()
+}
+
+fn save_history(history: &VecDeque<ClipEntry>) {
+    let Some(path) = history_dir().map(|dir| dir.join("history.json")) else {
+        return;
+    };
+    if let Err(e) = serde_json::to_string(history)
+        .map_err(|e| e.to_string())
+        .and_then(|json| fs::write(&path, json).map_err(|e| e.to_string()))
+    {
+        eprintln!(
+            "[clipboard] Failed to save the history to {}: {e}",
+            path.to_string_lossy()
+        );
+    }
+}
+
+fn human_size(bytes: usize) -> String {
+    match bytes {
+        b if b >= 1 << 20 => format!("{:.1} MiB", b as f64 / (1 << 20) as f64),
+        b if b >= 1 << 10 => format!("{:.1} KiB", b as f64 / (1 << 10) as f64),
+        b => format!("{b} B"),
+    }
+}
@@ -1,9 +1,7 @@
 use std::{
     collections::{BTreeMap, HashMap},
-    fs::File,
     hash::Hash,
-    io::{self, BufRead, BufReader},
-    num,
+    io, num,
     time::Duration,
 };
 
@@ -11,18 +9,25 @@ use bar_rs_derive::Builder;
 use handlebars::Handlebars;
 use iced::widget::{button::Style, container, scrollable, Container, Text};
 use iced::{futures::SinkExt, stream, widget::text, Element, Subscription};
+use serde_json::Value;
 use tokio::time::sleep;
 
+use crate::bar_text::bar_text;
 use crate::{
     button::button,
     config::{
         anchor::BarAnchor,
         module_config::{LocalModuleConfig, ModuleConfigOverride},
         popup_config::{PopupConfig, PopupConfigOverride},
+        schema::{ConfigKey, Kind},
+        thresholds::Thresholds,
     },
     fill::FillExt,
-    helpers::UnEscapeString,
-    impl_on_click, impl_wrapper, Message, NERD_FONT,
+    helpers::{
+        source::{Host, Source},
+        UnEscapeString,
+    },
+    impl_on_click, impl_wrapper, Message,
 };
 
 use super::Module;
@@ -34,6 +39,7 @@ pub struct CpuMod {
     cfg_override: ModuleConfigOverride,
     popup_cfg_override: PopupConfigOverride,
     icon: Option<String>,
+    thresholds: Thresholds,
 }
 
 impl Default for CpuMod {
@@ -48,6 +54,7 @@ impl Default for CpuMod {
                 ..Default::default()
             },
             icon: None,
+            thresholds: Thresholds::default(),
         }
     }
 }
@@ -64,22 +71,34 @@ impl Module for CpuMod {
         anchor: &BarAnchor,
         _handlebars: &Handlebars,
     ) -> Element<Message> {
+        let level = self.thresholds.color(self.avg_usage.all as f32);
         button(
             list![
                 anchor,
                 container(
-                    text!("{}", self.icon.as_ref().unwrap_or(&"󰻠".to_string()))
-                        .fill(anchor)
-                        .size(self.cfg_override.icon_size.unwrap_or(config.icon_size))
-                        .color(self.cfg_override.icon_color.unwrap_or(config.icon_color))
-                        .font(NERD_FONT)
+                    text(
+                        self.cfg_override
+                            .icon(config, self.icon.as_deref().unwrap_or("󰻠"))
+                    )
+                    .fill(anchor)
+                    .size(self.cfg_override.icon_size.unwrap_or(config.icon_size))
+                    .color(
+                        level.unwrap_or(self.cfg_override.icon_color.unwrap_or(config.icon_color))
+                    )
+                    .font(config.icon_font)
                 )
                 .padding(self.cfg_override.icon_margin.unwrap_or(config.icon_margin)),
                 container(
-                    text!["{}%", self.avg_usage.all]
-                        .fill(anchor)
-                        .size(self.cfg_override.font_size.unwrap_or(config.font_size))
-                        .color(self.cfg_override.text_color.unwrap_or(config.text_color))
+                    bar_text(
+                        format!("{}%", self.avg_usage.all),
+                        anchor,
+                        self.cfg_override.vertical_text(config, anchor)
+                    )
+                    .size(self.cfg_override.font_size.unwrap_or(config.font_size))
+                    .font(self.cfg_override.text_font.unwrap_or(config.text_font))
+                    .color(
+                        level.unwrap_or(self.cfg_override.text_color.unwrap_or(config.text_color))
+                    )
                 )
                 .padding(self.cfg_override.text_margin.unwrap_or(config.text_margin)),
             ]
@@ -96,6 +115,10 @@ impl Module for CpuMod {
         .into()
     }
 
+    fn popup_cfg_override(&self) -> Option<&PopupConfigOverride> {
+        Some(&self.popup_cfg_override)
+    }
+
     fn popup_view<'a>(
         &'a self,
         config: &'a PopupConfig,
@@ -150,23 +173,7 @@ impl Module for CpuMod {
             .render("cpu", &ctx)
             .map_err(|e| eprintln!("Failed to render cpu stats: {e}"))
             .unwrap_or_default();
-        container(scrollable(fmt_text(text(format))))
-            .padding(self.popup_cfg_override.padding.unwrap_or(config.padding))
-            .style(|_| container::Style {
-                background: Some(
-                    self.popup_cfg_override
-                        .background
-                        .unwrap_or(config.background),
-                ),
-                border: self.popup_cfg_override.border.unwrap_or(config.border),
-                ..Default::default()
-            })
-            .fill_maybe(
-                self.popup_cfg_override
-                    .fill_content_to_size
-                    .unwrap_or(config.fill_content_to_size),
-            )
-            .into()
+        container(scrollable(fmt_text(text(format)))).into()
     }
 
     impl_wrapper!();
@@ -176,10 +183,12 @@ impl Module for CpuMod {
         config: &HashMap<String, Option<String>>,
         popup_config: &HashMap<String, Option<String>>,
         templates: &mut Handlebars,
+        scale: f32,
     ) {
-        self.cfg_override = config.into();
-        self.popup_cfg_override.update(popup_config);
+        self.cfg_override = (config, scale).into();
+        self.popup_cfg_override.update(popup_config, scale);
         self.icon = config.get("icon").and_then(|v| v.clone());
+        self.thresholds = Thresholds::read(config.get("thresholds").and_then(|v| v.as_deref()));
         templates
             .register_template_string(
                 "cpu",
@@ -200,39 +209,86 @@ impl Module for CpuMod {
             .unwrap_or_else(|e| eprintln!("Failed to parse battery popup format: {e}"));
     }
 
+    fn known_keys(&self) -> Vec<ConfigKey> {
+        vec![
+            ConfigKey::new("icon", Kind::Text, None),
+            ConfigKey::new("thresholds", Kind::Text, None),
+        ]
+    }
+
+    fn known_popup_keys(&self) -> Vec<ConfigKey> {
+        vec![
+            ConfigKey::new(
+                "format",
+                Kind::Text,
+                Some(
+                    r#"Total: {{total}}%\nUser: {{user}}%\nSystem: {{system}}%\nGuest: {{guest}}%\n{{cores}}"#,
+                ),
+            ),
+            ConfigKey::new(
+                "format_core",
+                Kind::Text,
+                Some("Core {{index}}: {{total}}%"),
+            ),
+        ]
+    }
+
     impl_on_click!();
 
+    fn has_popup(&self) -> bool {
+        true
+    }
+
+    fn collect_data(&mut self) -> Result<(), String> {
+        let raw_stats1 = read_raw_stats(&Host).map_err(|e| format!("{e:?}"))?;
+        std::thread::sleep(Duration::from_millis(500));
+        let raw_stats2 = read_raw_stats(&Host).map_err(|e| format!("{e:?}"))?;
+        (self.avg_usage, self.cores) = usage(raw_stats1, raw_stats2);
+        Ok(())
+    }
+
+    fn collect_data_from(&mut self, source: &dyn Source) -> Result<(), String> {
+        // A snapshot has a single reading, so this is the usage since boot
+        let read = || read_raw_stats(&source).map_err(|e| format!("{e:?}"));
+        let boot = read()?
+            .into_iter()
+            .map(|(ty, _)| (ty, CpuStats::default()))
+            .collect();
+        (self.avg_usage, self.cores) = usage(boot, read()?);
+        Ok(())
+    }
+
+    fn text_content(&self, _template: &Handlebars) -> Option<Result<String, String>> {
+        Some(Ok(format!("{}%", self.avg_usage.all)))
+    }
+
+    fn variables(&self) -> BTreeMap<String, Value> {
+        BTreeMap::from([
+            ("usage".to_string(), self.avg_usage.all.into()),
+            ("user".to_string(), self.avg_usage.user.into()),
+            ("system".to_string(), self.avg_usage.system.into()),
+            ("guest".to_string(), self.avg_usage.guest.into()),
+        ])
+    }
+
     fn subscription(&self) -> Option<iced::Subscription<Message>> {
         Some(Subscription::run(|| {
             stream::channel(1, |mut sender| async move {
                 let interval: u64 = 500;
                 let gap: u64 = 2000;
                 loop {
-                    let Ok(mut raw_stats1) = read_raw_stats()
+                    let Ok(raw_stats1) = read_raw_stats(&Host)
                         .map_err(|e| eprintln!("Failed to read cpu stats from /proc/stat: {e:?}"))
                     else {
                         return;
                     };
                     sleep(Duration::from_millis(interval)).await;
-                    let Ok(mut raw_stats2) = read_raw_stats() else {
+                    let Ok(raw_stats2) = read_raw_stats(&Host) else {
                         eprintln!("Failed to read cpu stats from /proc/stat");
                         return;
                     };
 
-                    let avg = (
-                        &raw_stats1.remove(&CpuType::All).unwrap(),
-                        &raw_stats2.remove(&CpuType::All).unwrap(),
-                    )
-                        .into();
-
-                    let cores = raw_stats1
-                        .into_iter()
-                        .filter_map(|(ty, stats1)| {
-                            raw_stats2
-                                .get(&ty)
-                                .map(|stats2| (ty, (&stats1, stats2).into()))
-                        })
-                        .collect();
+                    let (avg, cores) = usage(raw_stats1, raw_stats2);
 
                     sender
                         .send(Message::update(move |reg| {
@@ -329,14 +385,33 @@ impl From<(&CpuStats<usize>, &CpuStats<usize>)> for CpuStats<u8> {
     }
 }
 
-fn read_raw_stats() -> Result<HashMap<CpuType, CpuStats<usize>>, ReadError> {
-    let file = File::open("/proc/stat")?;
-    let reader = BufReader::new(file);
-    let lines = reader.lines().filter_map(|l| {
-        l.ok().and_then(|line| {
-            let (cpu, data) = line.split_once(' ')?;
-            Some((cpu.into(), data.try_into().ok()?))
+/// The average and per core usage between two readings of /proc/stat
+fn usage(
+    mut raw_stats1: HashMap<CpuType, CpuStats<usize>>,
+    mut raw_stats2: HashMap<CpuType, CpuStats<usize>>,
+) -> (CpuStats<u8>, BTreeMap<CpuType, CpuStats<u8>>) {
+    let avg = (
+        &raw_stats1.remove(&CpuType::All).unwrap_or_default(),
+        &raw_stats2.remove(&CpuType::All).unwrap_or_default(),
+    )
+        .into();
+
+    let cores = raw_stats1
+        .into_iter()
+        .filter_map(|(ty, stats1)| {
+            raw_stats2
+                .get(&ty)
+                .map(|stats2| (ty, (&stats1, stats2).into()))
         })
+        .collect();
+    (avg, cores)
+}
+
+fn read_raw_stats(source: &impl Source) -> Result<HashMap<CpuType, CpuStats<usize>>, ReadError> {
+    let stat = source.read("/proc/stat")?;
+    let lines = stat.lines().filter_map(|line| {
+        let (cpu, data) = line.split_once(' ')?;
+        Some((cpu.into(), data.try_into().ok()?))
     });
     Ok(lines.collect())
 }
@@ -360,3 +435,63 @@ impl From<num::ParseIntError> for ReadError {
         Self::ParseError(value)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::{helpers::source::Fixtures, modules::testing::Harness};
+
+    use super::*;
+
+    const STAT1: &str = "cpu  100 0 100 800 0 0 0 0 0 0
+cpu0 50 0 50 400 0 0 0 0 0 0
+cpu1 50 0 50 400 0 0 0 0 0 0
+intr 1 2 3
+btime 1700000000
+";
+    const STAT2: &str = "cpu  200 0 150 850 0 0 0 0 0 0
+cpu0 120 0 80 400 0 0 0 0 0 0
+cpu1 80 0 70 450 0 0 0 0 0 0
+intr 4 5 6
+btime 1700000000
+";
+
+    fn stats(stat: &str) -> HashMap<CpuType, CpuStats<usize>> {
+        read_raw_stats(&Fixtures::default().file("/proc/stat", stat)).unwrap()
+    }
+
+    #[test]
+    fn usage_between_two_readings() {
+        let (avg, cores) = usage(stats(STAT1), stats(STAT2));
+        let mut harness = Harness::new::<CpuMod>(&[]);
+        harness.update(move |m: &mut CpuMod| {
+            m.avg_usage = avg;
+            m.cores = cores;
+        });
+        assert_eq!(harness.text::<CpuMod>(), "75%");
+        let m = harness.module::<CpuMod>();
+        assert_eq!((m.avg_usage.user, m.avg_usage.system), (50, 25));
+        assert_eq!(m.cores[&CpuType::Core(0)].all, 100);
+        assert_eq!(m.cores[&CpuType::Core(1)].all, 50);
+    }
+
+    #[test]
+    fn unchanged_counters_are_no_usage() {
+        let (avg, cores) = usage(stats(STAT1), stats(STAT1));
+        assert_eq!(avg.all, 0);
+        assert!(cores.values().all(|core| core.all == 0));
+    }
+
+    #[test]
+    fn snapshot_shows_the_usage_since_boot() {
+        let mut cpu = CpuMod::default();
+        cpu.collect_data_from(&Fixtures::default().file("/proc/stat", STAT1))
+            .unwrap();
+        assert_eq!(cpu.avg_usage.all, 20);
+        assert_eq!(cpu.cores[&CpuType::Core(1)].all, 20);
+    }
+
+    #[test]
+    fn missing_proc_stat_is_an_error() {
+        assert!(read_raw_stats(&Fixtures::default()).is_err());
+    }
+}
@@ -0,0 +1,433 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs, io,
+    path::Path,
+    time::Duration,
+};
+
+use bar_rs_derive::Builder;
+use handlebars::Handlebars;
+use iced::widget::{button::Style, container, scrollable, text, Column, Container};
+use iced::{futures::SinkExt, stream, Background, Color, Element, Length::Fill, Subscription};
+use serde_json::Value;
+use tokio::time::sleep;
+
+use crate::bar_text::bar_text;
+use crate::button::button;
+use crate::config::parse::StringExt;
+use crate::config::popup_config::{PopupConfig, PopupConfigOverride};
+use crate::{
+    config::{
+        anchor::BarAnchor,
+        module_config::{LocalModuleConfig, ModuleConfigOverride},
+        schema::{ConfigKey, Kind},
+    },
+    fill::FillExt,
+    helpers::UnEscapeString,
+    impl_on_click, impl_wrapper, Message,
+};
+
+use super::{Action, CommandAction, Module};
+
+const CPUS: &str = "/sys/devices/system/cpu";
+const CPUFREQ: &str = "/sys/devices/system/cpu/cpu0/cpufreq";
+
+#[derive(Debug, Default, Clone)]
+struct FreqState {
+    governor: String,
+    governors: Vec<String>,
+    /// The energy performance preference, only supported by some drivers (e.g. intel_pstate and
+    /// amd-pstate)
+    preference: Option<String>,
+    preferences: Vec<String>,
+    /// The current frequency averaged across all cores in kHz
+    frequency: u64,
+}
+
+impl FreqState {
+    fn read() -> io::Result<Self> {
+        let cpufreq = Path::new(CPUFREQ);
+        let read = |file: &str| -> io::Result<String> {
+            fs::read_to_string(cpufreq.join(file)).map(|v| v.trim().to_string())
+        };
+        let list = |file: &str| -> Vec<String> {
+            read(file)
+                .map(|v| v.split_whitespace().map(|v| v.to_string()).collect())
+                .unwrap_or_default()
+        };
+        Ok(FreqState {
+            governor: read("scaling_governor")?,
+            governors: list("scaling_available_governors"),
+            preference: read("energy_performance_preference").ok(),
+            preferences: list("energy_performance_available_preferences"),
+            frequency: average_frequency()?,
+        })
+    }
+
+    fn context(&self) -> BTreeMap<&'static str, String> {
+        BTreeMap::from([
+            ("governor", self.governor.clone()),
+            ("preference", self.preference.clone().unwrap_or_default()),
+            ("freq", format!("{:.1}", self.frequency as f32 / 1_000_000.)),
+            ("freq_mhz", (self.frequency / 1000).to_string()),
+        ])
+    }
+}
+
+/// Averages `scaling_cur_freq` of all cores
+fn average_frequency() -> io::Result<u64> {
+    let frequencies = fs::read_dir(CPUS)?
+        .flatten()
+        .filter(|cpu| {
+            cpu.file_name()
+                .to_string_lossy()
+                .strip_prefix("cpu")
+                .is_some_and(|index| index.parse::<u32>().is_ok())
+        })
+        .filter_map(|cpu| {
+            fs::read_to_string(cpu.path().join("cpufreq/scaling_cur_freq"))
+                .ok()
+                .and_then(|freq| freq.trim().parse::<u64>().ok())
+        })
+        .collect::<Vec<_>>();
+    match frequencies.is_empty() {
+        true => Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            "no core reports its frequency",
+        )),
+        false => Ok(frequencies.iter().sum::<u64>() / frequencies.len() as u64),
+    }
+}
+
+/// Fills a value into a command template like `cpupower frequency-set -g {governor}`
+fn command(template: &str, key: &str, value: &str) -> CommandAction {
+    CommandAction(template.replace(&format!("{{{key}}}"), value))
+}
+
+#[derive(Debug, Builder)]
+pub struct CpuFreqMod {
+    cfg_override: ModuleConfigOverride,
+    popup_cfg_override: PopupConfigOverride,
+    /// `None` if the system doesn't support cpufreq, e.g. in a VM
+    state: Option<FreqState>,
+    icon: String,
+    /// Icons for specific governors
+    icons: HashMap<String, String>,
+    /// Icon colors for specific governors
+    colors: HashMap<String, Color>,
+    set_governor_cmd: String,
+    set_preference_cmd: String,
+    active_color: Option<Color>,
+    active_background: Option<Background>,
+}
+
+impl Default for CpuFreqMod {
+    fn default() -> Self {
+        Self {
+            cfg_override: Default::default(),
+            popup_cfg_override: PopupConfigOverride {
+                width: Some(250),
+                height: Some(300),
+                ..Default::default()
+            },
+            state: None,
+            icon: "󰾅".to_string(),
+            icons: HashMap::from([
+                ("performance".to_string(), "󰓅".to_string()),
+                ("powersave".to_string(), "󰌪".to_string()),
+            ]),
+            colors: HashMap::new(),
+            set_governor_cmd: "pkexec cpupower frequency-set -g {governor}".to_string(),
+            set_preference_cmd: format!(
+                "echo {{preference}} | pkexec tee {CPUS}/cpu*/cpufreq/energy_performance_preference"
+            ),
+            active_color: None,
+            active_background: Some(Background::Color(Color::from_rgba(1., 1., 1., 0.2))),
+        }
+    }
+}
+
+impl CpuFreqMod {
+    fn icon(&self, governor: &str) -> &str {
+        self.icons.get(governor).unwrap_or(&self.icon)
+    }
+}
+
+impl Module for CpuFreqMod {
+    fn name(&self) -> String {
+        "cpu_freq".to_string()
+    }
+
+    fn active(&self) -> bool {
+        self.state.is_some()
+    }
+
+    fn view(
+        &self,
+        config: &LocalModuleConfig,
+        popup_config: &PopupConfig,
+        anchor: &BarAnchor,
+        handlebars: &Handlebars,
+    ) -> Element<Message> {
+        let Some(state) = &self.state else {
+            return "".into();
+        };
+        let format = handlebars
+            .render("cpu_freq", &state.context())
+            .map_err(|e| eprintln!("Failed to render cpu_freq stats: {e}"))
+            .unwrap_or_default();
+        button(
+            list![
+                anchor,
+                container(
+                    text(self.cfg_override.icon(config, self.icon(&state.governor)))
+                        .fill(anchor)
+                        .size(self.cfg_override.icon_size.unwrap_or(config.icon_size))
+                        .color(
+                            self.colors
+                                .get(&state.governor)
+                                .copied()
+                                .or(self.cfg_override.icon_color)
+                                .unwrap_or(config.icon_color)
+                        )
+                        .font(config.icon_font)
+                )
+                .padding(self.cfg_override.icon_margin.unwrap_or(config.icon_margin)),
+                container(
+                    bar_text(
+                        format,
+                        anchor,
+                        self.cfg_override.vertical_text(config, anchor)
+                    )
+                    .size(self.cfg_override.font_size.unwrap_or(config.font_size))
+                    .font(self.cfg_override.text_font.unwrap_or(config.text_font))
+                    .color(self.cfg_override.text_color.unwrap_or(config.text_color))
+                )
+                .padding(self.cfg_override.text_margin.unwrap_or(config.text_margin)),
+            ]
+            .spacing(self.cfg_override.spacing.unwrap_or(config.spacing)),
+        )
+        .on_event_with(Message::popup::<Self>(
+            self.popup_cfg_override.width.unwrap_or(popup_config.width),
+            self.popup_cfg_override
+                .height
+                .unwrap_or(popup_config.height),
+            anchor,
+        ))
+        .style(|_, _| Style::default())
+        .into()
+    }
+
+    fn popup_cfg_override(&self) -> Option<&PopupConfigOverride> {
+        Some(&self.popup_cfg_override)
+    }
+
+    fn popup_view<'a>(
+        &'a self,
+        config: &'a PopupConfig,
+        _template: &Handlebars,
+    ) -> Element<'a, Message> {
+        let Some(state) = &self.state else {
+            return "cpufreq is not available".into();
+        };
+        let text_color = self
+            .popup_cfg_override
+            .text_color
+            .unwrap_or(config.text_color);
+        let fmt_text = |content: &'a str, color: Color| -> Container<'a, Message> {
+            container(
+                text(content)
+                    .size(
+                        self.popup_cfg_override
+                            .font_size
+                            .unwrap_or(config.font_size),
+                    )
+                    .color(color),
+            )
+            .padding(
+                self.popup_cfg_override
+                    .text_margin
+                    .unwrap_or(config.text_margin),
+            )
+        };
+        // A list of options, clicking one runs the matching command
+        let options = |options: &'a [String],
+                       active: Option<&'a str>,
+                       action: &dyn Fn(&str) -> CommandAction|
+         -> Vec<Element<'a, Message>> {
+            options
+                .iter()
+                .map(|option| {
+                    let is_active = Some(option.as_str()) == active;
+                    let color = match is_active {
+                        true => self.active_color.unwrap_or(text_color),
+                        false => text_color,
+                    };
+                    let background = is_active.then_some(self.active_background).flatten();
+                    button(fmt_text(option, color).width(Fill))
+                        .on_event(action(option).as_message())
+                        .style(move |_, _| Style {
+                            background,
+                            ..Default::default()
+                        })
+                        .width(Fill)
+                        .into()
+                })
+                .collect()
+        };
+
+        let governors = options(
+            &state.governors,
+            Some(state.governor.as_str()),
+            &|governor| command(&self.set_governor_cmd, "governor", governor),
+        );
+        let preferences = options(
+            &state.preferences,
+            state.preference.as_deref(),
+            &|preference| command(&self.set_preference_cmd, "preference", preference),
+        );
+
+        container(scrollable(
+            Column::new()
+                .push(fmt_text("Governor", text_color))
+                .extend(governors)
+                .push_maybe(
+                    (!preferences.is_empty()).then(|| fmt_text("Energy preference", text_color)),
+                )
+                .extend(preferences)
+                .spacing(self.popup_cfg_override.spacing.unwrap_or(config.spacing)),
+        ))
+        .into()
+    }
+
+    impl_wrapper!();
+
+    fn read_config(
+        &mut self,
+        config: &HashMap<String, Option<String>>,
+        popup_config: &HashMap<String, Option<String>>,
+        templates: &mut Handlebars,
+        scale: f32,
+    ) {
+        let default = Self::default();
+        self.cfg_override = (config, scale).into();
+        self.popup_cfg_override.update(popup_config, scale);
+        self.icon = config
+            .get("icon")
+            .and_then(|v| v.clone())
+            .unwrap_or(default.icon);
+        self.icons = default.icons;
+        self.colors = HashMap::new();
+        for (key, value) in config {
+            if let Some(governor) = key.strip_prefix("icon.") {
+                if let Some(icon) = value {
+                    self.icons.insert(governor.to_string(), icon.clone());
+                }
+            } else if let Some(governor) = key.strip_prefix("icon_color.") {
+                if let Some(color) = value.into_color() {
+                    self.colors.insert(governor.to_string(), color);
+                }
+            }
+        }
+        self.set_governor_cmd = config
+            .get("set_governor_cmd")
+            .and_then(|v| v.clone())
+            .unwrap_or(default.set_governor_cmd);
+        self.set_preference_cmd = config
+            .get("set_preference_cmd")
+            .and_then(|v| v.clone())
+            .unwrap_or(default.set_preference_cmd);
+        self.active_color = popup_config
+            .get("active_color")
+            .and_then(|v| v.into_color())
+            .or(default.active_color);
+        self.active_background = popup_config
+            .get("active_background")
+            .map(|v| v.into_background())
+            .unwrap_or(default.active_background);
+        templates
+            .register_template_string(
+                "cpu_freq",
+                config
+                    .get("format")
+                    .unescape()
+                    .unwrap_or("{{freq}} GHz".to_string()),
+            )
+            .unwrap_or_else(|e| eprintln!("Failed to parse cpu_freq format: {e}"));
+    }
+
+    fn known_keys(&self) -> Vec<ConfigKey> {
+        vec![
+            ConfigKey::new("icon", Kind::Text, Some("󰾅")),
+            ConfigKey::new("icon.*", Kind::Text, None),
+            ConfigKey::new("icon_color.*", Kind::Color, None),
+            ConfigKey::new("set_governor_cmd", Kind::Text, Some("pkexec cpupower frequency-set -g {governor}")),
+            ConfigKey::new("set_preference_cmd", Kind::Text, Some("echo {preference} | pkexec tee /sys/devices/system/cpu/cpu*/cpufreq/energy_performance_preference")),
+            ConfigKey::new("format", Kind::Text, Some("{{freq}} GHz")),
+        ]
+    }
+
+    fn known_popup_keys(&self) -> Vec<ConfigKey> {
+        vec![
+            ConfigKey::new("active_color", Kind::Color, None),
+            ConfigKey::new(
+                "active_background",
+                Kind::Color,
+                Some("rgba(255, 255, 255, 0.2)"),
+            ),
+        ]
+    }
+
+    impl_on_click!();
+
+    fn has_popup(&self) -> bool {
+        true
+    }
+
+    fn collect_data(&mut self) -> Result<(), String> {
+        self.state = Some(
+            FreqState::read().map_err(|e| format!("Failed to read cpufreq from {CPUFREQ}: {e}"))?,
+        );
+        Ok(())
+    }
+
+    fn text_content(&self, template: &Handlebars) -> Option<Result<String, String>> {
+        let state = self.state.as_ref()?;
+        Some(
+            template
+                .render("cpu_freq", &state.context())
+                .map_err(|e| e.to_string()),
+        )
+    }
+
+    fn variables(&self) -> BTreeMap<String, Value> {
+        self.state
+            .iter()
+            .flat_map(|state| state.context())
+            .map(|(name, value)| (name.to_string(), value.into()))
+            .collect()
+    }
+
+    fn subscription(&self) -> Option<Subscription<Message>> {
+        Some(Subscription::run(|| {
+            stream::channel(1, |mut sender| async move {
+                loop {
+                    // Systems without cpufreq leave the module inactive
+                    let state = FreqState::read().ok();
+                    let available = state.is_some();
+                    if sender
+                        .send(Message::update(move |reg| {
+                            reg.get_module_mut::<CpuFreqMod>().state = state
+                        }))
+                        .await
+                        .is_err()
+                        || !available
+                    {
+                        return;
+                    }
+                    sleep(Duration::from_secs(2)).await;
+                }
+            })
+        }))
+    }
+}
@@ -1,41 +1,142 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::sync::LazyLock;
+use std::time::Duration;
 
 use bar_rs_derive::Builder;
-use chrono::Local;
+use chrono::{Datelike, Locale, Months, NaiveDate, Weekday};
 use handlebars::Handlebars;
-use iced::widget::{container, text};
-use iced::Element;
+use iced::widget::{button::Style, container, row, text, Column, Container};
+use iced::{
+    futures::SinkExt, mouse, stream, Background, Color, Element, Event, Length::Fill, Subscription,
+};
+use serde_json::{json, Value};
+use tokio::{process::Command, time::sleep};
 
-use crate::config::popup_config::PopupConfig;
+use crate::bar_text::bar_text;
+use crate::button::button;
+use crate::config::parse::StringExt;
+use crate::config::popup_config::{PopupConfig, PopupConfigOverride};
+use crate::helpers::{clock, locale};
 use crate::{
     config::{
         anchor::BarAnchor,
         module_config::{LocalModuleConfig, ModuleConfigOverride},
+        schema::{ConfigKey, Kind},
     },
     fill::FillExt,
-    Message, NERD_FONT,
+    Message,
 };
 use crate::{impl_on_click, impl_wrapper};
 
-use super::Module;
+use super::{click_message, Action, CommandAction, Module, OnClickAction};
+
+static DEFAULT_ACTION: LazyLock<OnClickAction> = LazyLock::new(|| OnClickAction {
+    right: Some(Box::new(CommandAction("gnome-calendar".to_string()))),
+    ..Default::default()
+});
+/// With a `format_alt`, middle clicks toggle the format
+static DEFAULT_ACTION_ALT: LazyLock<OnClickAction> = LazyLock::new(|| OnClickAction {
+    center: Some(Box::new(DateAction::ToggleFormat)),
+    right: Some(Box::new(CommandAction("gnome-calendar".to_string()))),
+    ..Default::default()
+});
 
 #[derive(Debug, Builder)]
 pub struct DateMod {
     cfg_override: ModuleConfigOverride,
+    popup_cfg_override: PopupConfigOverride,
     icon: String,
     fmt: String,
+    fmt_alt: Option<String>,
+    /// Whether the user toggled the format at runtime, kept across config reloads
+    toggled: bool,
+    locale: Locale,
+    week_start: Weekday,
+    today_color: Option<Color>,
+    today_background: Option<Background>,
+    events_command: Option<String>,
+    events_date_format: String,
+    /// Days on which the events command reported an event
+    events: HashSet<NaiveDate>,
+    /// How many months the calendar is moved away from the current one
+    month_offset: i32,
 }
 
 impl Default for DateMod {
     fn default() -> Self {
         Self {
             cfg_override: Default::default(),
-            icon: "".to_string(),
+            popup_cfg_override: PopupConfigOverride {
+                width: Some(260),
+                height: Some(250),
+                ..Default::default()
+            },
+            icon: "\u{f073}".to_string(),
             fmt: "%a, %d. %b".to_string(),
+            fmt_alt: None,
+            toggled: false,
+            locale: Locale::POSIX,
+            week_start: Weekday::Mon,
+            today_color: None,
+            today_background: Some(Background::Color(Color::from_rgba(1., 1., 1., 0.2))),
+            events_command: None,
+            events_date_format: "%Y-%m-%d".to_string(),
+            events: HashSet::new(),
+            month_offset: 0,
         }
     }
 }
 
+impl DateMod {
+    /// The first day of the month the calendar currently shows
+    fn shown_month(&self) -> NaiveDate {
+        let today = clock::now().date_naive();
+        let first = today.with_day(1).unwrap_or(today);
+        let months = Months::new(self.month_offset.unsigned_abs());
+        match self.month_offset >= 0 {
+            true => first.checked_add_months(months),
+            false => first.checked_sub_months(months),
+        }
+        .unwrap_or(first)
+    }
+
+    fn date(&self) -> String {
+        let fmt = match (&self.fmt_alt, self.toggled) {
+            (Some(fmt_alt), true) => fmt_alt,
+            _ => &self.fmt,
+        };
+        locale::format(&clock::now(), fmt, self.locale)
+    }
+
+    /// The name of the weekday as the locale abbreviates it
+    fn weekday_name(&self, weekday: Weekday) -> String {
+        NaiveDate::from_isoywd_opt(2024, 1, weekday)
+            .and_then(|date| date.and_hms_opt(0, 0, 0))
+            .map(|date| locale::format(&date.and_utc(), "%a", self.locale))
+            .unwrap_or_else(|| weekday.to_string())
+    }
+}
+
+/// How many cells of the first week of the month belong to the previous month
+fn leading_days(first: NaiveDate, week_start: Weekday) -> u32 {
+    (first.weekday().num_days_from_monday() + 7 - week_start.num_days_from_monday()) % 7
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum DateAction {
+    PrevMonth,
+    NextMonth,
+    CurrentMonth,
+    ToggleFormat,
+}
+
+impl Action for DateAction {
+    fn as_message(&self) -> Message {
+        let action = *self;
+        Message::update(move |reg| reg.get_module_mut::<DateMod>().handle_action(&action))
+    }
+}
+
 impl Module for DateMod {
     fn name(&self) -> String {
         "date".to_string()
@@ -44,32 +145,167 @@ impl Module for DateMod {
     fn view(
         &self,
         config: &LocalModuleConfig,
-        _popup_config: &PopupConfig,
+        popup_config: &PopupConfig,
         anchor: &BarAnchor,
         _handlebars: &Handlebars,
     ) -> Element<Message> {
-        let time = Local::now();
-        list![
+        let popup = Message::popup::<Self>(
+            self.popup_cfg_override.width.unwrap_or(popup_config.width),
+            self.popup_cfg_override
+                .height
+                .unwrap_or(popup_config.height),
             anchor,
-            container(
-                text!("{}", self.icon)
-                    .size(self.cfg_override.icon_size.unwrap_or(config.icon_size))
-                    .color(self.cfg_override.icon_color.unwrap_or(config.icon_color))
-                    .font(NERD_FONT)
-                    .fill(anchor)
-            )
-            .fill(anchor)
-            .padding(self.cfg_override.icon_margin.unwrap_or(config.icon_margin)),
-            container(
-                text!("{}", time.format(&self.fmt))
+        );
+        // Left clicks open the popup, the other buttons keep their on_click actions
+        let middle = click_message(self, mouse::Button::Middle, config);
+        let right = click_message(self, mouse::Button::Right, config);
+        button(
+            list![
+                anchor,
+                container(
+                    text(self.cfg_override.icon(config, &self.icon))
+                        .size(self.cfg_override.icon_size.unwrap_or(config.icon_size))
+                        .color(self.cfg_override.icon_color.unwrap_or(config.icon_color))
+                        .font(config.icon_font)
+                        .fill(anchor)
+                )
+                .fill(anchor)
+                .padding(self.cfg_override.icon_margin.unwrap_or(config.icon_margin)),
+                container(
+                    bar_text(
+                        self.date(),
+                        anchor,
+                        self.cfg_override.vertical_text(config, anchor)
+                    )
                     .size(self.cfg_override.font_size.unwrap_or(config.font_size))
+                    .font(self.cfg_override.text_font.unwrap_or(config.text_font))
                     .color(self.cfg_override.text_color.unwrap_or(config.text_color))
-                    .fill(anchor)
+                )
+                .fill(anchor)
+                .padding(self.cfg_override.text_margin.unwrap_or(config.text_margin)),
+            ]
+            .spacing(self.cfg_override.spacing.unwrap_or(config.spacing)),
+        )
+        .on_event_try(
+            move |event, layout, cursor, clipboard, viewport| match event {
+                Event::Mouse(mouse::Event::ButtonReleased(mouse::Button::Middle)) => middle.clone(),
+                Event::Mouse(mouse::Event::ButtonReleased(mouse::Button::Right)) => right.clone(),
+                _ => Some(popup(event, layout, cursor, clipboard, viewport)),
+            },
+        )
+        .style(|_, _| Style::default())
+        .into()
+    }
+
+    fn popup_cfg_override(&self) -> Option<&PopupConfigOverride> {
+        Some(&self.popup_cfg_override)
+    }
+
+    fn popup_view<'a>(
+        &'a self,
+        config: &'a PopupConfig,
+        _template: &Handlebars,
+    ) -> Element<'a, Message> {
+        let font_size = self
+            .popup_cfg_override
+            .font_size
+            .unwrap_or(config.font_size);
+        let text_color = self
+            .popup_cfg_override
+            .text_color
+            .unwrap_or(config.text_color);
+        let cell = |content: String, color: Color| -> Container<'a, Message> {
+            container(text(content).size(font_size).color(color))
+                .center_x(Fill)
+                .padding(
+                    self.popup_cfg_override
+                        .text_margin
+                        .unwrap_or(config.text_margin),
+                )
+        };
+        let nav = |icon: &'a str, action: DateAction| {
+            button(
+                text(config.icon(icon))
+                    .font(config.icon_font)
+                    .size(
+                        self.popup_cfg_override
+                            .icon_size
+                            .unwrap_or(config.icon_size),
+                    )
+                    .color(
+                        self.popup_cfg_override
+                            .icon_color
+                            .unwrap_or(config.icon_color),
+                    ),
             )
-            .fill(anchor)
-            .padding(self.cfg_override.text_margin.unwrap_or(config.text_margin)),
-        ]
-        .spacing(self.cfg_override.spacing.unwrap_or(config.spacing))
+            .on_event(action.as_message())
+            .style(|_, _| Style::default())
+        };
+
+        let today = clock::now().date_naive();
+        let first = self.shown_month();
+        let days_in_month = first
+            .checked_add_months(Months::new(1))
+            .and_then(|next| next.pred_opt())
+            .map(|last| last.day())
+            .unwrap_or(28);
+        let lead = leading_days(first, self.week_start);
+
+        let header = row![
+            nav("\u{f053}", DateAction::PrevMonth),
+            button(cell(
+                first
+                    .and_hms_opt(0, 0, 0)
+                    .map(|first| locale::format(&first.and_utc(), "%B %Y", self.locale))
+                    .unwrap_or_default(),
+                text_color
+            ))
+            .on_event(DateAction::CurrentMonth.as_message())
+            .style(|_, _| Style::default())
+            .width(Fill),
+            nav("\u{f054}", DateAction::NextMonth),
+        ];
+        let weekdays = row((0..7).map(|i| {
+            let mut day = self.week_start;
+            (0..i).for_each(|_| day = day.succ());
+            cell(self.weekday_name(day).chars().take(2).collect(), text_color).into()
+        }));
+        let day_cell = |index: u32| -> Element<'a, Message> {
+            let Some(date) = index
+                .checked_sub(lead)
+                .filter(|day| *day < days_in_month)
+                .and_then(|day| first.with_day(day + 1))
+            else {
+                return cell(String::new(), text_color).into();
+            };
+            let is_today = date == today;
+            let label = match self.events.contains(&date) {
+                true => format!("{}•", date.day()),
+                false => date.day().to_string(),
+            };
+            let today_background = self.today_background;
+            cell(
+                label,
+                match is_today {
+                    true => self.today_color.unwrap_or(text_color),
+                    false => text_color,
+                },
+            )
+            .style(move |_| container::Style {
+                background: is_today.then_some(today_background).flatten(),
+                ..Default::default()
+            })
+            .into()
+        };
+        let week_count = (lead + days_in_month).div_ceil(7);
+        let weeks = (0..week_count).map(|week| -> Element<'a, Message> {
+            row((0..7).map(|weekday| day_cell(week * 7 + weekday))).into()
+        });
+
+        container(
+            Column::with_children([header.into(), weekdays.into()].into_iter().chain(weeks))
+                .spacing(self.popup_cfg_override.spacing.unwrap_or(config.spacing)),
+        )
         .into()
     }
 
@@ -78,11 +314,13 @@ impl Module for DateMod {
     fn read_config(
         &mut self,
         config: &HashMap<String, Option<String>>,
-        _popup_config: &HashMap<String, Option<String>>,
+        popup_config: &HashMap<String, Option<String>>,
         _templates: &mut Handlebars,
+        scale: f32,
     ) {
         let default = Self::default();
-        self.cfg_override = config.into();
+        self.cfg_override = (config, scale).into();
+        self.popup_cfg_override.update(popup_config, scale);
         self.icon = config
             .get("icon")
             .and_then(|v| v.clone())
@@ -91,7 +329,161 @@ impl Module for DateMod {
             .get("format")
             .and_then(|v| v.clone())
             .unwrap_or(default.fmt);
+        self.fmt_alt = config.get("format_alt").and_then(|v| v.clone());
+        self.locale = locale::read_locale(config.get("locale").and_then(|v| v.as_deref()));
+        // The first day of the week of the locale, unless it's set explicitly
+        self.week_start = popup_config
+            .get("week_start")
+            .and_then(|v| {
+                v.as_ref().and_then(|v| {
+                    v.parse()
+                        .map_err(|_| eprintln!("Failed to parse week_start: {v}"))
+                        .ok()
+                })
+            })
+            .unwrap_or_else(|| locale::week_start(self.locale));
+        self.today_color = popup_config
+            .get("today_color")
+            .and_then(|v| v.into_color())
+            .or(default.today_color);
+        self.today_background = popup_config
+            .get("today_background")
+            .map(|v| v.into_background())
+            .unwrap_or(default.today_background);
+        self.events_command = popup_config.get("events_command").and_then(|v| v.clone());
+        self.events_date_format = popup_config
+            .get("events_date_format")
+            .and_then(|v| v.clone())
+            .unwrap_or(default.events_date_format);
+    }
+
+    fn known_keys(&self) -> Vec<ConfigKey> {
+        vec![
+            ConfigKey::new("icon", Kind::Text, Some("\u{f073}")),
+            ConfigKey::new("format", Kind::Text, Some("%a, %d. %b")),
+            ConfigKey::new("format_alt", Kind::Text, None),
+            ConfigKey::new("locale", Kind::Text, None),
+        ]
+    }
+
+    fn known_popup_keys(&self) -> Vec<ConfigKey> {
+        vec![
+            // Defaults to the first day of the week of the locale
+            ConfigKey::new("week_start", Kind::Text, None),
+            ConfigKey::new("today_color", Kind::Color, None),
+            ConfigKey::new(
+                "today_background",
+                Kind::Color,
+                Some("rgba(255, 255, 255, 0.2)"),
+            ),
+            ConfigKey::new("events_command", Kind::Text, None),
+            ConfigKey::new("events_date_format", Kind::Text, Some("%Y-%m-%d")),
+        ]
     }
 
     impl_on_click!();
+
+    fn has_popup(&self) -> bool {
+        true
+    }
+
+    fn default_action(&self) -> Option<&OnClickAction> {
+        match self.fmt_alt {
+            Some(_) => Some(&DEFAULT_ACTION_ALT),
+            None => Some(&DEFAULT_ACTION),
+        }
+    }
+
+    fn handle_action(&mut self, action: &dyn Action) {
+        if let Some(action) = action.downcast_ref::<DateAction>() {
+            match action {
+                DateAction::PrevMonth => self.month_offset -= 1,
+                DateAction::NextMonth => self.month_offset += 1,
+                DateAction::CurrentMonth => self.month_offset = 0,
+                DateAction::ToggleFormat => self.toggled = !self.toggled,
+            }
+        }
+    }
+
+    fn save_state(&self) -> Value {
+        json!({ "toggled": self.toggled })
+    }
+
+    fn load_state(&mut self, state: Value) {
+        if let Some(toggled) = state["toggled"].as_bool() {
+            self.toggled = toggled;
+        }
+    }
+
+    fn text_content(&self, _template: &Handlebars) -> Option<Result<String, String>> {
+        Some(Ok(self.date()))
+    }
+
+    fn subscription(&self) -> Option<Subscription<Message>> {
+        let cmd = self.events_command.clone()?;
+        let date_fmt = self.events_date_format.clone();
+        Some(Subscription::run_with_id(
+            ("date_events", cmd.clone(), date_fmt.clone()),
+            stream::channel(1, move |mut sender| async move {
+                loop {
+                    let events = match Command::new("sh").arg("-c").arg(&cmd).output().await {
+                        Ok(output) => String::from_utf8_lossy(&output.stdout)
+                            .lines()
+                            .filter_map(|line| {
+                                NaiveDate::parse_from_str(line.trim(), &date_fmt).ok()
+                            })
+                            .collect::<HashSet<NaiveDate>>(),
+                        Err(e) => {
+                            eprintln!("Failed to run calendar events command: {e}");
+                            return;
+                        }
+                    };
+                    if sender
+                        .send(Message::update(move |reg| {
+                            reg.get_module_mut::<DateMod>().events = events
+                        }))
+                        .await
+                        .is_err()
+                    {
+                        return;
+                    }
+                    sleep(Duration::from_secs(300)).await;
+                }
+            }),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn calendar_starts_the_week_on_the_locale_week_start() {
+        // A Sunday
+        let september = NaiveDate::from_ymd_opt(2024, 9, 1).unwrap();
+        assert_eq!(leading_days(september, Weekday::Mon), 6);
+        assert_eq!(leading_days(september, Weekday::Sun), 0);
+        assert_eq!(leading_days(september, Weekday::Sat), 1);
+        assert_eq!(
+            leading_days(september, locale::week_start(Locale::de_DE)),
+            6
+        );
+        assert_eq!(
+            leading_days(september, locale::week_start(Locale::en_US)),
+            0
+        );
+    }
+
+    #[test]
+    fn calendar_weekdays_are_localized() {
+        let date = DateMod {
+            locale: Locale::de_DE,
+            ..Default::default()
+        };
+        assert_eq!(date.weekday_name(Weekday::Mon), "Mo");
+        assert_eq!(date.weekday_name(Weekday::Sun), "So");
+        let date = DateMod::default();
+        assert_eq!(date.weekday_name(Weekday::Mon), "Mon");
+    }
 }
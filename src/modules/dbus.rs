@@ -0,0 +1,536 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::Display,
+    future::pending,
+    time::Duration,
+};
+
+use bar_rs_derive::Builder;
+use handlebars::Handlebars;
+use iced::widget::{container, text};
+use iced::{
+    futures::{channel::mpsc::Sender, SinkExt, StreamExt},
+    stream, Element, Subscription,
+};
+use serde_json::{json, Value};
+use tokio::{select, time::sleep};
+use zbus::{
+    fdo::{DBusProxy, PropertiesProxy},
+    names::{BusName, InterfaceName},
+    zvariant, Connection,
+};
+
+use crate::bar_text::bar_text;
+use crate::{
+    config::{
+        anchor::BarAnchor,
+        module_config::{LocalModuleConfig, ModuleConfigOverride},
+        parse::StringExt,
+        popup_config::PopupConfig,
+        schema::{ConfigKey, Kind},
+    },
+    fill::FillExt,
+    helpers::{
+        backoff::Backoff,
+        status::{send_status, ModuleStatus},
+        UnEscapeString,
+    },
+    impl_on_click, impl_status, impl_wrapper,
+    list::list,
+    Message,
+};
+
+use super::Module;
+
+/// How often a method is called in `method` mode, unless `interval` is set
+const METHOD_INTERVAL: Duration = Duration::from_secs(5);
+
+/// The D-Bus property a [DbusMod] shows
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct Property {
+    /// Whether the service is on the system bus instead of the session bus
+    system_bus: bool,
+    service: String,
+    path: String,
+    interface: String,
+    member: Member,
+}
+
+/// What the value is read from
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Member {
+    Property(String),
+    /// A method without arguments which returns the value
+    Method(String),
+}
+
+impl Display for Property {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match &self.member {
+            Member::Property(name) => name.clone(),
+            Member::Method(name) => format!("{name}()"),
+        };
+        write!(
+            f,
+            "{}.{name} of {} at {}",
+            self.interface, self.service, self.path
+        )
+    }
+}
+
+/// Shows a property of any D-Bus service, updated whenever the service reports a change
+#[derive(Debug, Default, Builder)]
+pub struct DbusMod {
+    cfg_override: ModuleConfigOverride,
+    icon: Option<String>,
+    property: Option<Property>,
+    /// Read the property this often, for services which don't emit `PropertiesChanged` for it
+    interval: Option<Duration>,
+    format: String,
+    /// Joins the items of arrays of numbers, strings and booleans
+    separator: String,
+    /// Shown instead of the format while the service isn't running
+    placeholder: Option<String>,
+    /// The last value of the property, `None` until it was read
+    value: Option<Value>,
+    status: ModuleStatus,
+}
+
+impl DbusMod {
+    fn text(&self, handlebars: &Handlebars) -> Result<String, String> {
+        match (&self.value, &self.placeholder) {
+            (None, Some(placeholder)) => Ok(placeholder.clone()),
+            _ => handlebars
+                .render_template(&self.format, &json!({ "value": self.value }))
+                .map_err(|e| e.to_string()),
+        }
+    }
+
+    /// Sets the value, arrays of simple values are joined by the `separator`
+    fn set_value(&mut self, value: Value) {
+        let simple = |item: &Value| !item.is_array() && !item.is_object() && !item.is_null();
+        self.value = Some(match value {
+            Value::Array(items) if items.iter().all(simple) => Value::String(
+                items
+                    .iter()
+                    .map(|item| match item {
+                        Value::String(s) => s.clone(),
+                        item => item.to_string(),
+                    })
+                    .collect::<Vec<_>>()
+                    .join(&self.separator),
+            ),
+            value => value,
+        });
+    }
+}
+
+impl Module for DbusMod {
+    fn name(&self) -> String {
+        "dbus".to_string()
+    }
+
+    fn active(&self) -> bool {
+        self.value.is_some() || self.placeholder.is_some()
+    }
+
+    impl_status!();
+
+    fn view(
+        &self,
+        config: &LocalModuleConfig,
+        _popup_config: &PopupConfig,
+        anchor: &BarAnchor,
+        handlebars: &Handlebars,
+    ) -> Element<Message> {
+        let icon = self.icon.as_ref().map(|icon| -> Element<Message> {
+            container(
+                text(self.cfg_override.icon(config, icon))
+                    .fill(anchor)
+                    .size(self.cfg_override.icon_size.unwrap_or(config.icon_size))
+                    .color(self.cfg_override.icon_color.unwrap_or(config.icon_color))
+                    .font(config.icon_font),
+            )
+            .padding(self.cfg_override.icon_margin.unwrap_or(config.icon_margin))
+            .into()
+        });
+        let content: Element<Message> = container(
+            bar_text(
+                self.text(handlebars)
+                    .inspect_err(|e| eprintln!("Failed to render dbus module: {e}"))
+                    .unwrap_or_default(),
+                anchor,
+                self.cfg_override.vertical_text(config, anchor),
+            )
+            .size(self.cfg_override.font_size.unwrap_or(config.font_size))
+            .font(self.cfg_override.text_font.unwrap_or(config.text_font))
+            .color(self.cfg_override.text_color.unwrap_or(config.text_color)),
+        )
+        .padding(self.cfg_override.text_margin.unwrap_or(config.text_margin))
+        .into();
+        list(anchor, icon.into_iter().chain([content]))
+            .spacing(self.cfg_override.spacing.unwrap_or(config.spacing))
+            .into()
+    }
+
+    impl_wrapper!();
+
+    fn read_config(
+        &mut self,
+        config: &HashMap<String, Option<String>>,
+        _popup_config: &HashMap<String, Option<String>>,
+        _templates: &mut Handlebars,
+        scale: f32,
+    ) {
+        self.cfg_override = (config, scale).into();
+        self.icon = config.get("icon").and_then(|v| v.clone());
+        let get = |key: &str| config.get(key).and_then(|v| v.clone());
+        let member = get("property")
+            .map(Member::Property)
+            .or_else(|| get("method").map(Member::Method));
+        self.property = match (get("service"), get("path"), get("interface"), member) {
+            (Some(service), Some(path), Some(interface), Some(member)) => Some(Property {
+                system_bus: get("bus").is_some_and(|bus| bus.trim() == "system"),
+                service,
+                path,
+                interface,
+                member,
+            }),
+            _ => {
+                eprintln!(
+                    "The dbus module needs `service`, `path`, `interface` and `property` or `method` in its section"
+                );
+                None
+            }
+        };
+        let method = matches!(
+            self.property,
+            Some(Property {
+                member: Member::Method(_),
+                ..
+            })
+        );
+        self.interval = match config.get("interval").and_then(|v| v.into_duration()) {
+            Some(interval) => Some(interval).filter(|interval| !interval.is_zero()),
+            // Methods don't report changes
+            None => method.then_some(METHOD_INTERVAL),
+        };
+        self.format = config
+            .get("format")
+            .unescape()
+            .unwrap_or("{{value}}".to_string());
+        self.separator = config
+            .get("separator")
+            .unescape()
+            .unwrap_or(", ".to_string());
+        self.placeholder = config.get("placeholder").unescape();
+    }
+
+    fn known_keys(&self) -> Vec<ConfigKey> {
+        vec![
+            ConfigKey::new("icon", Kind::Text, None),
+            ConfigKey::new("service", Kind::Text, None),
+            ConfigKey::new("path", Kind::Text, None),
+            ConfigKey::new("interface", Kind::Text, None),
+            ConfigKey::new("property", Kind::Text, None),
+            ConfigKey::new("method", Kind::Text, None),
+            ConfigKey::new("bus", Kind::Choice(&["session", "system"]), Some("session")),
+            ConfigKey::new("interval", Kind::Duration, None),
+            ConfigKey::new("format", Kind::Text, Some("{{value}}")),
+            ConfigKey::new("separator", Kind::Text, Some(", ")),
+            ConfigKey::new("placeholder", Kind::Text, None),
+        ]
+    }
+
+    impl_on_click!();
+
+    fn text_content(&self, template: &Handlebars) -> Option<Result<String, String>> {
+        self.active().then(|| self.text(template))
+    }
+
+    fn variables(&self) -> BTreeMap<String, Value> {
+        self.value
+            .iter()
+            .map(|value| ("value".to_string(), value.clone()))
+            .collect()
+    }
+
+    fn subscription(&self) -> Option<Subscription<Message>> {
+        let property = self.property.clone()?;
+        let interval = self.interval;
+        Some(Subscription::run_with_id(
+            ("dbus", property.clone(), interval),
+            stream::channel(1, move |mut sender| async move {
+                let mut backoff = Backoff::default();
+                while let Err(e) = watch(&property, interval, &mut sender, &mut backoff).await {
+                    let reason = format!("Failed to read {property}: {e}");
+                    // Services which aren't running are waited for, other failures are retried
+                    if let Ok(false) = has_owner(&property).await {
+                        if !send_absent(&mut sender, reason).await {
+                            return;
+                        }
+                        if let Err(e) = wait_for_owner(&property).await {
+                            eprintln!("Failed to wait for {}: {e}", property.service);
+                            backoff.wait().await;
+                        }
+                        continue;
+                    }
+                    if !send_status::<DbusMod>(&mut sender, ModuleStatus::Unavailable(reason)).await
+                    {
+                        return;
+                    }
+                    backoff.wait().await;
+                }
+            }),
+        ))
+    }
+}
+
+/// Sends the value of the property whenever it changes. Returns `Ok` once the bar is closing.
+async fn watch(
+    property: &Property,
+    interval: Option<Duration>,
+    sender: &mut Sender<Message>,
+    backoff: &mut Backoff,
+) -> Result<(), String> {
+    let connection = connect(property).await?;
+    let mut owner_changes = DBusProxy::new(&connection)
+        .await
+        .map_err(|e| e.to_string())?
+        .receive_name_owner_changed()
+        .await
+        .map_err(|e| e.to_string())?;
+    let proxy = PropertiesProxy::builder(&connection)
+        .destination(property.service.as_str())
+        .and_then(|builder| builder.path(property.path.as_str()))
+        .map_err(|e| e.to_string())?
+        .build()
+        .await
+        .map_err(|e| e.to_string())?;
+    let interface =
+        InterfaceName::try_from(property.interface.as_str()).map_err(|e| e.to_string())?;
+    let mut changes = proxy
+        .receive_properties_changed()
+        .await
+        .map_err(|e| e.to_string())?;
+    let read = || async {
+        match &property.member {
+            Member::Property(name) => proxy
+                .get(interface.clone(), name)
+                .await
+                .map(|value| to_json(&value))
+                .map_err(|e| e.to_string()),
+            Member::Method(name) => call(&connection, property, name).await,
+        }
+    };
+    let mut value = read().await?;
+    loop {
+        backoff.reset();
+        if sender
+            .send(Message::update(move |reg| {
+                let m = reg.get_module_mut::<DbusMod>();
+                m.set_status(ModuleStatus::Ok);
+                m.set_value(value);
+            }))
+            .await
+            .is_err()
+        {
+            return Ok(());
+        }
+        value = loop {
+            let poll = async {
+                match interval {
+                    Some(interval) => sleep(interval).await,
+                    None => pending().await,
+                }
+            };
+            let signal = select! {
+                signal = changes.next() => signal.ok_or("the connection was closed")?,
+                owner = owner_changes.next() => {
+                    let owner = owner.ok_or("the connection was closed")?;
+                    if owner.args().is_ok_and(|args| {
+                        args.name().as_str() == property.service && args.new_owner().is_none()
+                    }) {
+                        return Err("the service stopped".to_string());
+                    }
+                    continue;
+                }
+                _ = poll => break read().await?,
+            };
+            let Member::Property(name) = &property.member else {
+                continue;
+            };
+            let Ok(args) = signal.args() else {
+                continue;
+            };
+            if args.interface_name().as_str() != property.interface {
+                continue;
+            }
+            if let Some(changed) = args.changed_properties().get(name.as_str()) {
+                break to_json(changed);
+            }
+            if args.invalidated_properties().contains(&name.as_str()) {
+                break read().await?;
+            }
+        };
+    }
+}
+
+async fn connect(property: &Property) -> Result<Connection, String> {
+    match property.system_bus {
+        true => Connection::system().await,
+        false => Connection::session().await,
+    }
+    .map_err(|e| e.to_string())
+}
+
+/// Calls the method without arguments, its first return value is the value
+async fn call(connection: &Connection, property: &Property, method: &str) -> Result<Value, String> {
+    let reply = connection
+        .call_method(
+            Some(property.service.as_str()),
+            property.path.as_str(),
+            Some(property.interface.as_str()),
+            method,
+            &(),
+        )
+        .await
+        .map_err(|e| e.to_string())?;
+    let body: zvariant::Structure = reply.body().map_err(|e| e.to_string())?;
+    Ok(body.fields().first().map(to_json).unwrap_or_default())
+}
+
+/// Whether the service is running
+async fn has_owner(property: &Property) -> Result<bool, String> {
+    let connection = connect(property).await?;
+    let name = BusName::try_from(property.service.as_str()).map_err(|e| e.to_string())?;
+    DBusProxy::new(&connection)
+        .await
+        .map_err(|e| e.to_string())?
+        .name_has_owner(name)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Waits until the service is started
+async fn wait_for_owner(property: &Property) -> Result<(), String> {
+    let connection = connect(property).await?;
+    let dbus = DBusProxy::new(&connection)
+        .await
+        .map_err(|e| e.to_string())?;
+    let mut owner_changes = dbus
+        .receive_name_owner_changed()
+        .await
+        .map_err(|e| e.to_string())?;
+    let name = BusName::try_from(property.service.as_str()).map_err(|e| e.to_string())?;
+    // It may have been started since it was checked
+    if dbus.name_has_owner(name).await.map_err(|e| e.to_string())? {
+        return Ok(());
+    }
+    while let Some(signal) = owner_changes.next().await {
+        if signal.args().is_ok_and(|args| {
+            args.name().as_str() == property.service && args.new_owner().is_some()
+        }) {
+            return Ok(());
+        }
+    }
+    Err("the connection was closed".to_string())
+}
+
+/// Drops the value since the service isn't running, the module shows the `placeholder` if it
+/// has one and is unavailable otherwise. Returns `false` if the bar is closing.
+async fn send_absent(sender: &mut Sender<Message>, reason: String) -> bool {
+    sender
+        .send(Message::update(move |reg| {
+            let m = reg.get_module_mut::<DbusMod>();
+            m.value = None;
+            let status = match m.placeholder {
+                Some(_) => ModuleStatus::Ok,
+                None => ModuleStatus::Unavailable(reason),
+            };
+            m.set_status(status);
+        }))
+        .await
+        .is_ok()
+}
+
+/// The value as JSON for the format: numbers, booleans and strings as they are, arrays and
+/// structures as arrays, and dictionaries with string keys as objects
+fn to_json(value: &zvariant::Value) -> Value {
+    use zvariant::Value as V;
+    match value {
+        V::U8(n) => json!(n),
+        V::Bool(b) => json!(b),
+        V::I16(n) => json!(n),
+        V::U16(n) => json!(n),
+        V::I32(n) => json!(n),
+        V::U32(n) => json!(n),
+        V::I64(n) => json!(n),
+        V::U64(n) => json!(n),
+        V::F64(n) => json!(n),
+        V::Str(s) => json!(s.as_str()),
+        V::Signature(s) => json!(s.as_str()),
+        V::ObjectPath(p) => json!(p.as_str()),
+        V::Value(v) => to_json(v),
+        V::Array(array) => Value::Array(array.get().iter().map(to_json).collect()),
+        V::Structure(structure) => Value::Array(structure.fields().iter().map(to_json).collect()),
+        V::Dict(dict) => HashMap::<String, zvariant::Value>::try_from(dict.clone())
+            .map(|dict| {
+                Value::Object(
+                    dict.iter()
+                        .map(|(key, value)| (key.clone(), to_json(value)))
+                        .collect(),
+                )
+            })
+            .unwrap_or_default(),
+        _ => Value::Null,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::modules::testing::Harness;
+
+    use super::*;
+
+    const SECTION: [(&str, &str); 4] = [
+        ("service", "org.example"),
+        ("path", "/org/example"),
+        ("interface", "org.example"),
+        ("property", "Devices"),
+    ];
+
+    #[test]
+    fn joins_simple_arrays() {
+        let mut options = SECTION.to_vec();
+        options.push(("separator", " | "));
+        let mut harness = Harness::new::<DbusMod>(&options);
+        harness.update(|m: &mut DbusMod| m.set_value(json!(["phone", 42, true])));
+        assert_eq!(harness.text::<DbusMod>(), "phone | 42 | true");
+        harness.update(|m: &mut DbusMod| m.set_value(json!({ "Name": "phone" })));
+        assert_eq!(harness.text::<DbusMod>(), "[object]");
+    }
+
+    #[test]
+    fn shows_the_placeholder_without_a_value() {
+        let mut options = SECTION.to_vec();
+        options.push(("placeholder", "offline"));
+        let mut harness = Harness::new::<DbusMod>(&options);
+        assert_eq!(harness.text::<DbusMod>(), "offline");
+        harness.update(|m: &mut DbusMod| m.set_value(json!(87)));
+        assert_eq!(harness.text::<DbusMod>(), "87");
+    }
+
+    #[test]
+    fn methods_are_polled() {
+        let mut options = SECTION[..3].to_vec();
+        options.push(("method", "GetStatus"));
+        let harness = Harness::new::<DbusMod>(&options);
+        let m = harness.module::<DbusMod>();
+        assert_eq!(m.interval, Some(METHOD_INTERVAL));
+        assert!(m
+            .property
+            .as_ref()
+            .is_some_and(|p| p.member == Member::Method("GetStatus".to_string())));
+    }
+}
@@ -2,40 +2,140 @@ use std::{
     collections::{BTreeMap, HashMap},
     ffi::CString,
     mem,
+    time::{Duration, Instant},
 };
 
 use bar_rs_derive::Builder;
 use handlebars::Handlebars;
 use iced::{
-    widget::{button::Style, container, scrollable, text, Container, Text},
-    Element,
+    futures::SinkExt,
+    stream,
+    widget::{button::Style, column, container, scrollable, text, Container, Text},
+    Background, Color, Element, Subscription,
 };
 use libc::{__errno_location, statvfs};
+use serde_json::{json, Value};
+use tokio::{
+    process::Command,
+    select,
+    sync::mpsc::{self, UnboundedSender},
+    time::sleep,
+};
 
+use crate::bar_text::bar_text;
 use crate::{
     button::button,
     config::{
         anchor::BarAnchor,
         module_config::{LocalModuleConfig, ModuleConfigOverride},
+        parse::StringExt,
         popup_config::{PopupConfig, PopupConfigOverride},
+        schema::{ConfigKey, Kind},
+        thresholds::Thresholds,
     },
     fill::FillExt,
     helpers::UnEscapeString,
-    impl_on_click, impl_wrapper, Message, NERD_FONT,
+    impl_on_click, impl_wrapper, Message,
 };
 
-use super::Module;
+use super::{Action, Module};
+
+/// How often the usage is checked against `warn_percent`
+const WARN_POLL: Duration = Duration::from_secs(30);
+/// Opening the popup doesn't run smartctl again within this long after the last run
+const SMART_MIN_AGE: Duration = Duration::from_secs(60);
 
-#[derive(Debug, Builder, Default)]
+#[derive(Debug, Builder)]
 pub struct DiskUsageMod {
     icon: Option<String>,
     cfg_override: ModuleConfigOverride,
     popup_cfg_override: PopupConfigOverride,
     path: CString,
+    /// Warn once this much of the filesystem is used, in percent
+    warn_percent: Option<u8>,
+    /// The warning ends once the usage dropped this many percentage points below `warn_percent`
+    warn_hysteresis: u8,
+    warn_color: Color,
+    warn_background: Option<Background>,
+    /// A command run whenever the usage crosses `warn_percent`
+    on_warn: Option<String>,
+    /// Whether the usage is above `warn_percent`
+    warning: bool,
+    /// Colors for the usage, which take precedence over the `warn_color`
+    thresholds: Thresholds,
+    /// Whether the SMART health of the disks is shown in the popup, using `smartctl`
+    smart: bool,
+    smart_interval: Duration,
+    /// The SMART health by device, as of the last run of smartctl
+    smart_devices: Vec<SmartDevice>,
+    /// Runs smartctl again, e.g. when the popup is opened
+    smart_refresh: Option<UnboundedSender<()>>,
+}
+
+impl Default for DiskUsageMod {
+    fn default() -> Self {
+        Self {
+            icon: None,
+            cfg_override: Default::default(),
+            popup_cfg_override: Default::default(),
+            path: CString::new("/").unwrap(),
+            warn_percent: Some(90),
+            warn_hysteresis: 2,
+            warn_color: Color::from_rgb8(243, 139, 168),
+            warn_background: None,
+            on_warn: None,
+            warning: false,
+            thresholds: Thresholds::default(),
+            smart: false,
+            smart_interval: Duration::from_secs(60 * 60),
+            smart_devices: vec![],
+            smart_refresh: None,
+        }
+    }
+}
+
+/// The SMART health of a physical device, as reported by `smartctl -j`
+#[derive(Debug, Clone)]
+struct SmartDevice {
+    /// Like `/dev/sda`
+    name: String,
+    model: Option<String>,
+    passed: bool,
+    /// In °C
+    temperature: Option<i64>,
+}
+
+impl SmartDevice {
+    /// `None` for devices without SMART support
+    fn parse(name: String, info: &Value) -> Option<Self> {
+        Some(Self {
+            passed: info["smart_status"]["passed"].as_bool()?,
+            model: info["model_name"].as_str().map(|m| m.to_string()),
+            temperature: info["temperature"]["current"].as_i64(),
+            name,
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct RefreshSmartAction;
+
+impl Action for RefreshSmartAction {
+    fn as_message(&self) -> Message {
+        Message::update(|reg| {
+            if let Some(refresh) = reg
+                .instances_mut::<DiskUsageMod>()
+                .find_map(|m| m.smart_refresh.as_ref())
+            {
+                // The subscription is gone if this fails, it reports why
+                let _ = refresh.send(());
+            }
+        })
+    }
 }
 
 #[derive(Debug, Default)]
-/// All values are represented in megabytes, except the `_perc` fields
+/// All values are represented in bytes, except the `_perc` fields
 struct FileSystemStats {
     total: u64,
     free: u64,
@@ -49,12 +149,15 @@ struct FileSystemStats {
 impl From<FileSystemStats> for BTreeMap<&'static str, u64> {
     fn from(value: FileSystemStats) -> Self {
         BTreeMap::from([
-            ("total", value.total),
-            ("total_gb", value.total / 1000),
-            ("used", value.used),
-            ("used_gb", value.used / 1000),
-            ("free", value.free),
-            ("free_gb", value.free / 1000),
+            ("total", value.total / 1_000_000),
+            ("total_gb", value.total / 1_000_000_000),
+            ("total_bytes", value.total),
+            ("used", value.used / 1_000_000),
+            ("used_gb", value.used / 1_000_000_000),
+            ("used_bytes", value.used),
+            ("free", value.free / 1_000_000),
+            ("free_gb", value.free / 1_000_000_000),
+            ("free_bytes", value.free),
             ("used_perc", value.used_perc.into()),
             ("free_perc", value.free_perc.into()),
         ])
@@ -65,9 +168,9 @@ impl From<statvfs> for FileSystemStats {
     fn from(value: statvfs) -> Self {
         let free_perc = (value.f_bavail as f32 / value.f_blocks as f32 * 100.) as u8;
         Self {
-            total: value.f_blocks * value.f_frsize / 1_000_000,
-            free: value.f_bavail * value.f_frsize / 1_000_000,
-            used: (value.f_blocks - value.f_bavail) * value.f_frsize / 1_000_000,
+            total: value.f_blocks * value.f_frsize,
+            free: value.f_bavail * value.f_frsize,
+            used: (value.f_blocks - value.f_bavail) * value.f_frsize,
             free_perc,
             used_perc: 100 - free_perc,
         }
@@ -89,43 +192,68 @@ impl Module for DiskUsageMod {
         let Ok(stats) = get_stats(&self.path) else {
             return "Error".into();
         };
+        let level = self.thresholds.color(stats.used_perc as f32);
         let ctx: BTreeMap<&'static str, u64> = stats.into();
         let format = handlebars
             .render("disk_usage", &ctx)
             .map_err(|e| eprintln!("Failed to render disk_usage stats: {e}"))
             .unwrap_or_default();
+        let text_color = match self.warning {
+            true => level.unwrap_or(self.warn_color),
+            false => level.unwrap_or(self.cfg_override.text_color.unwrap_or(config.text_color)),
+        };
+        let open_popup = Message::popup::<Self>(
+            self.popup_cfg_override.width.unwrap_or(popup_config.width),
+            self.popup_cfg_override
+                .height
+                .unwrap_or(popup_config.height),
+            anchor,
+        );
         button(
             list![
                 anchor,
                 container(
-                    text!("{}", self.icon.as_ref().unwrap_or(&"󰦚".to_string()))
-                        .fill(anchor)
-                        .size(self.cfg_override.icon_size.unwrap_or(config.icon_size))
-                        .color(self.cfg_override.icon_color.unwrap_or(config.icon_color))
-                        .font(NERD_FONT)
+                    text(
+                        self.cfg_override
+                            .icon(config, self.icon.as_deref().unwrap_or("󰦚"))
+                    )
+                    .fill(anchor)
+                    .size(self.cfg_override.icon_size.unwrap_or(config.icon_size))
+                    .color(
+                        level.unwrap_or(self.cfg_override.icon_color.unwrap_or(config.icon_color))
+                    )
+                    .font(config.icon_font)
                 )
                 .padding(self.cfg_override.icon_margin.unwrap_or(config.icon_margin)),
                 container(
-                    text(format)
-                        .fill(anchor)
-                        .size(self.cfg_override.font_size.unwrap_or(config.font_size))
-                        .color(self.cfg_override.text_color.unwrap_or(config.text_color))
+                    bar_text(
+                        format,
+                        anchor,
+                        self.cfg_override.vertical_text(config, anchor)
+                    )
+                    .size(self.cfg_override.font_size.unwrap_or(config.font_size))
+                    .font(self.cfg_override.text_font.unwrap_or(config.text_font))
+                    .color(text_color)
                 )
                 .padding(self.cfg_override.text_margin.unwrap_or(config.text_margin)),
             ]
             .spacing(self.cfg_override.spacing.unwrap_or(config.spacing)),
         )
-        .on_event_with(Message::popup::<Self>(
-            self.popup_cfg_override.width.unwrap_or(popup_config.width),
-            self.popup_cfg_override
-                .height
-                .unwrap_or(popup_config.height),
-            anchor,
-        ))
+        .on_event_with(move |event, layout, cursor, clipboard, viewport| {
+            let popup = open_popup(event, layout, cursor, clipboard, viewport);
+            match self.smart {
+                true => Message::Batch(vec![popup, RefreshSmartAction.as_message()]),
+                false => popup,
+            }
+        })
         .style(|_, _| Style::default())
         .into()
     }
 
+    fn popup_cfg_override(&self) -> Option<&PopupConfigOverride> {
+        Some(&self.popup_cfg_override)
+    }
+
     fn popup_view<'a>(
         &'a self,
         config: &'a PopupConfig,
@@ -158,36 +286,174 @@ impl Module for DiskUsageMod {
             .render("disk_usage_popup", &ctx)
             .map_err(|e| eprintln!("Failed to render disk_usage stats: {e}"))
             .unwrap_or_default();
-        container(scrollable(fmt_text(text(format))))
-            .padding(self.popup_cfg_override.padding.unwrap_or(config.padding))
-            .style(|_| container::Style {
-                background: Some(
-                    self.popup_cfg_override
-                        .background
-                        .unwrap_or(config.background),
-                ),
-                border: self.popup_cfg_override.border.unwrap_or(config.border),
-                ..Default::default()
-            })
-            .fill_maybe(
-                self.popup_cfg_override
-                    .fill_content_to_size
-                    .unwrap_or(config.fill_content_to_size),
-            )
+        let devices = self.smart_devices.iter().map(|device| {
+            let ctx = json!({
+                "device": device.name,
+                "model": device.model.as_deref().unwrap_or(&device.name),
+                "health": match device.passed {
+                    true => "PASSED",
+                    false => "FAILED",
+                },
+                "passed": device.passed,
+                "temperature": device.temperature,
+            });
+            let line = template
+                .render("disk_usage_smart", &ctx)
+                .map_err(|e| eprintln!("Failed to render the SMART health of a disk: {e}"))
+                .unwrap_or_default();
+            let line = text(line);
+            match device.passed {
+                true => fmt_text(line),
+                false => fmt_text(line.color(self.warn_color)),
+            }
             .into()
+        });
+        container(scrollable(
+            column![fmt_text(text(format))]
+                .extend(devices)
+                .spacing(self.popup_cfg_override.spacing.unwrap_or(config.spacing)),
+        ))
+        .into()
     }
 
     impl_wrapper!();
 
+    fn background_override(&self) -> Option<Background> {
+        self.warning.then_some(self.warn_background).flatten()
+    }
+
+    fn subscription(&self) -> Option<Subscription<Message>> {
+        let warn = self.warn_percent.map(|warn_percent| {
+            let path = self.path.clone();
+            let hysteresis = self.warn_hysteresis;
+            let on_warn = self.on_warn.clone();
+            Subscription::run_with_id(
+                (
+                    "disk_usage",
+                    path.clone(),
+                    warn_percent,
+                    hysteresis,
+                    on_warn.clone(),
+                ),
+                stream::channel(1, move |mut sender| async move {
+                    let mut warning = false;
+                    loop {
+                        if let Ok(stats) = get_stats(&path) {
+                            let was_warning = warning;
+                            warning = match warning {
+                                true => stats.used_perc.saturating_add(hysteresis) >= warn_percent,
+                                false => stats.used_perc >= warn_percent,
+                            };
+                            if warning != was_warning {
+                                let path = path.clone();
+                                let update = Message::update(move |reg| {
+                                    reg.instances_mut::<DiskUsageMod>()
+                                        .filter(|m| m.path == path)
+                                        .for_each(|m| m.warning = warning);
+                                });
+                                let msg = match (warning, &on_warn) {
+                                    (true, Some(cmd)) => {
+                                        Message::Batch(vec![update, Message::command_sh(cmd)])
+                                    }
+                                    _ => update,
+                                };
+                                if sender.send(msg).await.is_err() {
+                                    return;
+                                }
+                            }
+                        }
+                        sleep(WARN_POLL).await;
+                    }
+                }),
+            )
+        });
+        let smart = self.smart.then(|| {
+            let interval = self.smart_interval;
+            Subscription::run_with_id(
+                ("disk_usage.smart", interval),
+                stream::channel(1, move |mut sender| async move {
+                    let (refresh_sx, mut refresh_rx) = mpsc::unbounded_channel();
+                    if sender
+                        .send(Message::update(move |reg| {
+                            reg.instances_mut::<DiskUsageMod>()
+                                .for_each(|m| m.smart_refresh = Some(refresh_sx.clone()));
+                        }))
+                        .await
+                        .is_err()
+                    {
+                        return;
+                    }
+                    loop {
+                        let last_run = Instant::now();
+                        let devices = read_smart().await;
+                        if sender
+                            .send(Message::update(move |reg| {
+                                reg.instances_mut::<DiskUsageMod>()
+                                    .for_each(|m| m.smart_devices = devices.clone());
+                            }))
+                            .await
+                            .is_err()
+                        {
+                            return;
+                        }
+                        let next_run = sleep(interval);
+                        tokio::pin!(next_run);
+                        loop {
+                            select! {
+                                _ = &mut next_run => break,
+                                Some(()) = refresh_rx.recv() => {
+                                    if last_run.elapsed() >= SMART_MIN_AGE {
+                                        break;
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }),
+            )
+        });
+        Some(Subscription::batch(warn.into_iter().chain(smart)))
+    }
+
     fn read_config(
         &mut self,
         config: &HashMap<String, Option<String>>,
         popup_config: &HashMap<String, Option<String>>,
         templates: &mut Handlebars,
+        scale: f32,
     ) {
-        self.cfg_override = config.into();
-        self.popup_cfg_override.update(popup_config);
+        let default = Self::default();
+        self.cfg_override = (config, scale).into();
+        self.popup_cfg_override.update(popup_config, scale);
         self.icon = config.get("icon").and_then(|v| v.clone());
+        // `0` disables the warning
+        self.warn_percent = config
+            .get("warn_percent")
+            .and_then(|v| v.as_ref().and_then(|v| v.parse().ok()))
+            .or(default.warn_percent)
+            .filter(|percent| *percent > 0);
+        self.warn_hysteresis = config
+            .get("warn_hysteresis")
+            .and_then(|v| v.as_ref().and_then(|v| v.parse().ok()))
+            .unwrap_or(default.warn_hysteresis);
+        self.warn_color = config
+            .get("warn_color")
+            .and_then(|v| v.into_color())
+            .unwrap_or(default.warn_color);
+        self.warn_background = config
+            .get("warn_background")
+            .and_then(|v| v.into_background());
+        self.on_warn = config.get("on_warn").and_then(|v| v.clone());
+        self.thresholds = Thresholds::read(config.get("thresholds").and_then(|v| v.as_deref()));
+        self.smart = config
+            .get("smart")
+            .and_then(|v| v.into_bool())
+            .unwrap_or(default.smart);
+        self.smart_interval = config
+            .get("smart_interval")
+            .and_then(|v| v.into_duration())
+            .filter(|interval| !interval.is_zero())
+            .unwrap_or(default.smart_interval);
         self.path = config
             .get("path")
             .and_then(|v| v.clone().and_then(|v| CString::new(v).ok()))
@@ -207,12 +473,83 @@ impl Module for DiskUsageMod {
                 popup_config
                     .get("format")
                     .unescape()
-                    .unwrap_or("Total: {{total_gb}} GB\nUsed: {{used_gb}} GB ({{used_perc}}%)\nFree: {{free_gb}} GB ({{free_perc}}%)".to_string()),
+                    .unwrap_or("Total: {{bytes total_bytes si=true}}\nUsed: {{bytes used_bytes si=true}} ({{used_perc}}%)\nFree: {{bytes free_bytes si=true}} ({{free_perc}}%)".to_string()),
             )
             .unwrap_or_else(|e| eprintln!("Failed to parse battery popup format: {e}"));
+        templates
+            .register_template_string(
+                "disk_usage_smart",
+                popup_config.get("smart_format").unescape().unwrap_or(
+                    "{{model}}: {{health}}{{#if temperature}}, {{temperature}}°C{{/if}}"
+                        .to_string(),
+                ),
+            )
+            .unwrap_or_else(|e| eprintln!("Failed to parse disk_usage smart_format: {e}"));
+    }
+
+    fn known_keys(&self) -> Vec<ConfigKey> {
+        vec![
+            ConfigKey::new("icon", Kind::Text, None),
+            ConfigKey::new("warn_percent", Kind::Integer, Some("90")),
+            ConfigKey::new("warn_hysteresis", Kind::Integer, Some("2")),
+            ConfigKey::new("warn_color", Kind::Color, Some("rgb(243, 139, 168)")),
+            ConfigKey::new("warn_background", Kind::Color, None),
+            ConfigKey::new("on_warn", Kind::Text, None),
+            ConfigKey::new("thresholds", Kind::Text, None),
+            ConfigKey::new("smart", Kind::Bool, Some("false")),
+            ConfigKey::new("smart_interval", Kind::Duration, Some("3600s")),
+            ConfigKey::new("path", Kind::Text, Some("/")),
+            ConfigKey::new("format", Kind::Text, Some("{{used_perc}}%")),
+        ]
+    }
+
+    fn known_popup_keys(&self) -> Vec<ConfigKey> {
+        vec![
+            ConfigKey::new(
+                "format",
+                Kind::Text,
+                Some(
+                    r#"Total: {{bytes total_bytes si=true}}\nUsed: {{bytes used_bytes si=true}} ({{used_perc}}%)\nFree: {{bytes free_bytes si=true}} ({{free_perc}}%)"#,
+                ),
+            ),
+            ConfigKey::new(
+                "smart_format",
+                Kind::Text,
+                Some("{{model}}: {{health}}{{#if temperature}}, {{temperature}}°C{{/if}}"),
+            ),
+        ]
     }
 
     impl_on_click!();
+
+    fn has_popup(&self) -> bool {
+        true
+    }
+
+    fn text_content(&self, template: &Handlebars) -> Option<Result<String, String>> {
+        let Ok(stats) = get_stats(&self.path) else {
+            return Some(Err(format!(
+                "Failed to get filesystem stats of {}",
+                self.path.to_string_lossy()
+            )));
+        };
+        let ctx: BTreeMap<&'static str, u64> = stats.into();
+        Some(
+            template
+                .render("disk_usage", &ctx)
+                .map_err(|e| e.to_string()),
+        )
+    }
+
+    fn variables(&self) -> BTreeMap<String, Value> {
+        let Ok(stats) = get_stats(&self.path) else {
+            return BTreeMap::new();
+        };
+        BTreeMap::<&'static str, u64>::from(stats)
+            .into_iter()
+            .map(|(name, value)| (name.to_string(), value.into()))
+            .collect()
+    }
 }
 
 /// Get file system statistics using the statvfs system call, see
@@ -228,3 +565,43 @@ fn get_stats(path: &CString) -> Result<FileSystemStats, ()> {
     }
     Ok(raw_stats.into())
 }
+
+/// The SMART health of all physical devices which support it, using `smartctl -j`
+async fn read_smart() -> Vec<SmartDevice> {
+    let Some(scan) = smartctl(&["--scan"]).await else {
+        return vec![];
+    };
+    let mut devices = vec![];
+    for device in scan["devices"].as_array().into_iter().flatten() {
+        let Some(name) = device["name"].as_str() else {
+            continue;
+        };
+        let mut args = vec!["-H", "-A"];
+        if let Some(kind) = device["type"].as_str() {
+            args.extend(["-d", kind]);
+        }
+        args.push(name);
+        if let Some(device) = smartctl(&args)
+            .await
+            .and_then(|info| SmartDevice::parse(name.to_string(), &info))
+        {
+            devices.push(device);
+        }
+    }
+    devices
+}
+
+/// Runs smartctl with JSON output, its exit code is a bit mask which is also set for warnings, so
+/// only the output matters
+async fn smartctl(args: &[&str]) -> Option<Value> {
+    let output = Command::new("smartctl")
+        .arg("-j")
+        .args(args)
+        .output()
+        .await
+        .map_err(|e| eprintln!("Failed to run smartctl: {e}"))
+        .ok()?;
+    serde_json::from_slice(&output.stdout)
+        .map_err(|e| eprintln!("Failed to parse the output of smartctl {args:?}: {e}"))
+        .ok()
+}
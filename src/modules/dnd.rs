@@ -0,0 +1,304 @@
+use std::{collections::HashMap, fs, path::PathBuf};
+
+use bar_rs_derive::Builder;
+use handlebars::Handlebars;
+use iced::widget::{container, text};
+use iced::{
+    futures::{channel::mpsc::Sender, SinkExt, StreamExt},
+    stream, Color, Element, Subscription,
+};
+use tokio::{
+    select,
+    sync::mpsc::{self, UnboundedReceiver, UnboundedSender},
+};
+
+use crate::config::get_state_dir;
+use crate::config::parse::StringExt;
+use crate::config::popup_config::PopupConfig;
+use crate::helpers::{
+    backoff::Backoff,
+    notify_daemon::NotifyDaemon,
+    status::{send_status, ModuleStatus},
+};
+use crate::{
+    config::{
+        anchor::BarAnchor,
+        module_config::{LocalModuleConfig, ModuleConfigOverride},
+        schema::{ConfigKey, Kind},
+    },
+    fill::FillExt,
+    impl_on_click, impl_status, impl_wrapper, Message,
+};
+
+use super::{Action, Module, OnClickAction};
+
+#[derive(Debug, Builder)]
+pub struct DndMod {
+    cfg_override: ModuleConfigOverride,
+    /// `None` until the state was read from the notification daemon
+    dnd: Option<bool>,
+    icon_on: String,
+    icon_off: String,
+    /// The icon color while do-not-disturb is enabled
+    color_on: Option<Color>,
+    /// Whether the state is saved and restored when the bar is started
+    persist: bool,
+    /// Toggles do-not-disturb on left click
+    toggle: OnClickAction,
+    /// Passes state changes to the subscription
+    actions: Option<UnboundedSender<bool>>,
+    status: ModuleStatus,
+}
+
+impl Default for DndMod {
+    fn default() -> Self {
+        Self {
+            cfg_override: Default::default(),
+            dnd: None,
+            icon_on: "󰂛".to_string(),
+            icon_off: "󰂚".to_string(),
+            color_on: None,
+            persist: false,
+            toggle: OnClickAction {
+                left: Some(Box::new(DndAction::Toggle)),
+                ..Default::default()
+            },
+            actions: None,
+            status: ModuleStatus::Ok,
+        }
+    }
+}
+
+impl DndMod {
+    fn icon(&self) -> &str {
+        match self.dnd {
+            Some(true) => &self.icon_on,
+            _ => &self.icon_off,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum DndAction {
+    Toggle,
+}
+
+impl Action for DndAction {
+    fn as_message(&self) -> Message {
+        let action = *self;
+        Message::update(move |reg| reg.get_module_mut::<DndMod>().handle_action(&action))
+    }
+}
+
+impl Module for DndMod {
+    fn name(&self) -> String {
+        "dnd".to_string()
+    }
+
+    fn active(&self) -> bool {
+        self.dnd.is_some()
+    }
+
+    impl_status!();
+
+    fn view(
+        &self,
+        config: &LocalModuleConfig,
+        _popup_config: &PopupConfig,
+        anchor: &BarAnchor,
+        _handlebars: &Handlebars,
+    ) -> Element<Message> {
+        let color = match self.dnd {
+            Some(true) => self.color_on,
+            _ => None,
+        };
+        container(
+            text(self.cfg_override.icon(config, self.icon()))
+                .fill(anchor)
+                .size(self.cfg_override.icon_size.unwrap_or(config.icon_size))
+                .color(
+                    color
+                        .or(self.cfg_override.icon_color)
+                        .unwrap_or(config.icon_color),
+                )
+                .font(config.icon_font),
+        )
+        .padding(self.cfg_override.icon_margin.unwrap_or(config.icon_margin))
+        .into()
+    }
+
+    impl_wrapper!();
+
+    fn read_config(
+        &mut self,
+        config: &HashMap<String, Option<String>>,
+        _popup_config: &HashMap<String, Option<String>>,
+        _templates: &mut Handlebars,
+        scale: f32,
+    ) {
+        let default = Self::default();
+        self.cfg_override = (config, scale).into();
+        self.icon_on = config
+            .get("icon_on")
+            .and_then(|v| v.clone())
+            .unwrap_or(default.icon_on);
+        self.icon_off = config
+            .get("icon_off")
+            .and_then(|v| v.clone())
+            .unwrap_or(default.icon_off);
+        self.color_on = config
+            .get("color_on")
+            .and_then(|v| v.into_color())
+            .or(default.color_on);
+        self.persist = config
+            .get("persist")
+            .and_then(|v| v.into_bool())
+            .unwrap_or(default.persist);
+    }
+
+    fn known_keys(&self) -> Vec<ConfigKey> {
+        vec![
+            ConfigKey::new("icon_on", Kind::Text, Some("󰂛")),
+            ConfigKey::new("icon_off", Kind::Text, Some("󰂚")),
+            ConfigKey::new("color_on", Kind::Color, None),
+            ConfigKey::new("persist", Kind::Bool, Some("false")),
+        ]
+    }
+
+    impl_on_click!();
+
+    fn default_action(&self) -> Option<&OnClickAction> {
+        Some(&self.toggle)
+    }
+
+    fn handle_action(&mut self, action: &dyn Action) {
+        let (Some(DndAction::Toggle), Some(dnd)) = (action.downcast_ref::<DndAction>(), self.dnd)
+        else {
+            return;
+        };
+        if let Some(Err(_)) = self.actions.as_ref().map(|sender| sender.send(!dnd)) {
+            eprintln!("Failed to toggle do-not-disturb: the subscription is gone");
+        }
+    }
+
+    fn collect_data(&mut self) -> Result<(), String> {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .map_err(|e| e.to_string())?;
+        self.dnd = Some(runtime.block_on(async { NotifyDaemon::connect().await?.dnd().await })?);
+        Ok(())
+    }
+
+    fn text_content(&self, _template: &Handlebars) -> Option<Result<String, String>> {
+        self.dnd.map(|dnd| {
+            Ok(match dnd {
+                true => "DND on".to_string(),
+                false => "DND off".to_string(),
+            })
+        })
+    }
+
+    fn subscription(&self) -> Option<Subscription<Message>> {
+        let persist = self.persist;
+        Some(Subscription::run_with_id(
+            ("dnd", persist),
+            stream::channel(1, move |mut sender| async move {
+                let (action_sx, mut action_rx) = mpsc::unbounded_channel();
+                if sender
+                    .send(Message::update(move |reg| {
+                        reg.get_module_mut::<DndMod>().actions = Some(action_sx)
+                    }))
+                    .await
+                    .is_err()
+                {
+                    return;
+                }
+                // The daemon may be started after the bar, or restarted
+                let mut backoff = Backoff::default();
+                while let Err(e) =
+                    watch_dnd(&mut sender, &mut action_rx, persist, &mut backoff).await
+                {
+                    if !send_status::<DndMod>(&mut sender, ModuleStatus::Unavailable(e)).await {
+                        return;
+                    }
+                    backoff.wait().await;
+                }
+            }),
+        ))
+    }
+}
+
+fn state_file() -> Option<PathBuf> {
+    get_state_dir().map(|dir| dir.join("dnd"))
+}
+
+fn load_dnd() -> Option<bool> {
+    let state = fs::read_to_string(state_file()?).ok()?;
+    (&Some(state.trim().to_string())).into_bool()
+}
+
+fn save_dnd(dnd: bool) {
+    let Some(path) = state_file() else {
+        return;
+    };
+    if let Err(e) = fs::write(&path, if dnd { "on" } else { "off" }) {
+        eprintln!(
+            "Failed to save the do-not-disturb state to {}: {e}",
+            path.to_string_lossy()
+        );
+    }
+}
+
+/// Follows the do-not-disturb state of the notification daemon and switches it. Returns if the
+/// bar is closing.
+async fn watch_dnd(
+    sender: &mut Sender<Message>,
+    actions: &mut UnboundedReceiver<bool>,
+    persist: bool,
+    backoff: &mut Backoff,
+) -> Result<(), String> {
+    let daemon = NotifyDaemon::connect().await?;
+    let mut changes = daemon.dnd_changes().await?;
+    backoff.reset();
+    let mut dnd = daemon.dnd().await?;
+    if let Some(saved) = persist.then(load_dnd).flatten() {
+        if saved != dnd {
+            daemon.set_dnd(saved).await?;
+            dnd = saved;
+        }
+    }
+    loop {
+        if persist {
+            save_dnd(dnd);
+        }
+        if sender
+            .send(Message::update(move |reg| {
+                let m = reg.get_module_mut::<DndMod>();
+                m.dnd = Some(dnd);
+                m.set_status(ModuleStatus::Ok);
+            }))
+            .await
+            .is_err()
+        {
+            return Ok(());
+        }
+        // Wait for the state to actually change
+        loop {
+            let new = select! {
+                Some(new) = changes.next() => new,
+                Some(set) = actions.recv() => {
+                    if let Err(e) = daemon.set_dnd(set).await {
+                        eprintln!("Failed to toggle do-not-disturb of {}: {e}", daemon.name());
+                    }
+                    daemon.dnd().await?
+                }
+                else => return Err(format!("{} stopped", daemon.name())),
+            };
+            if new != dnd {
+                dnd = new;
+                break;
+            }
+        }
+    }
+}
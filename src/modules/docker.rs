@@ -0,0 +1,444 @@
+use std::{collections::HashMap, env, time::Duration};
+
+use bar_rs_derive::Builder;
+use handlebars::Handlebars;
+use iced::widget::{button::Style, container, row, scrollable, text, Column, Container};
+use iced::{
+    futures::{channel::mpsc::Sender, SinkExt},
+    stream, Alignment, Color, Element,
+    Length::Fill,
+    Subscription,
+};
+use serde_json::Value;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::UnixStream,
+    select,
+    sync::mpsc::{self, UnboundedReceiver, UnboundedSender},
+    time::sleep,
+};
+
+use crate::bar_text::bar_text;
+use crate::button::button;
+use crate::config::parse::StringExt;
+use crate::config::popup_config::{PopupConfig, PopupConfigOverride};
+use crate::{
+    config::{
+        anchor::BarAnchor,
+        module_config::{LocalModuleConfig, ModuleConfigOverride},
+        schema::{ConfigKey, Kind},
+    },
+    fill::FillExt,
+    impl_on_click, impl_wrapper, Message,
+};
+
+use super::{Action, Module};
+
+const DEFAULT_SOCKET: &str = "/var/run/docker.sock";
+
+#[derive(Debug, Clone, PartialEq)]
+struct ContainerState {
+    id: String,
+    name: String,
+    /// `running`, `exited`, `paused`, `restarting`, ...
+    state: String,
+    /// A human readable status, e.g. `Up 2 hours`
+    status: String,
+}
+
+impl ContainerState {
+    fn from_json(container: &Value) -> Option<Self> {
+        Some(Self {
+            id: container["Id"].as_str()?.to_string(),
+            name: container["Names"][0]
+                .as_str()
+                .map(|name| name.trim_start_matches('/').to_string())
+                .unwrap_or_default(),
+            state: container["State"].as_str()?.to_string(),
+            status: container["Status"].as_str().unwrap_or_default().to_string(),
+        })
+    }
+
+    fn running(&self) -> bool {
+        self.state == "running"
+    }
+}
+
+/// Starts or stops a container, passed to the subscription
+#[derive(Debug)]
+struct SetContainer {
+    id: String,
+    start: bool,
+}
+
+#[derive(Debug, Builder)]
+pub struct DockerMod {
+    cfg_override: ModuleConfigOverride,
+    popup_cfg_override: PopupConfigOverride,
+    icon: String,
+    /// The path of the Docker (or Podman) API socket
+    socket: String,
+    /// `None` if the daemon isn't reachable
+    containers: Option<Vec<ContainerState>>,
+    running_color: Color,
+    stopped_color: Color,
+    /// Passes container changes to the subscription
+    actions: Option<UnboundedSender<SetContainer>>,
+}
+
+impl Default for DockerMod {
+    fn default() -> Self {
+        Self {
+            cfg_override: Default::default(),
+            popup_cfg_override: PopupConfigOverride {
+                width: Some(350),
+                height: Some(250),
+                ..Default::default()
+            },
+            icon: "󰡨".to_string(),
+            socket: env::var("DOCKER_HOST")
+                .ok()
+                .and_then(|host| host.strip_prefix("unix://").map(|path| path.to_string()))
+                .unwrap_or(DEFAULT_SOCKET.to_string()),
+            containers: None,
+            running_color: Color::from_rgb(0.3, 0.8, 0.3),
+            stopped_color: Color::from_rgb(0.5, 0.5, 0.5),
+            actions: None,
+        }
+    }
+}
+
+impl DockerMod {
+    fn running(&self) -> usize {
+        self.containers
+            .iter()
+            .flatten()
+            .filter(|container| container.running())
+            .count()
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum DockerAction {
+    /// Stops the container at this index if it is running, and starts it otherwise
+    Toggle(usize),
+}
+
+impl Action for DockerAction {
+    fn as_message(&self) -> Message {
+        let action = *self;
+        Message::update(move |reg| reg.get_module_mut::<DockerMod>().handle_action(&action))
+    }
+}
+
+impl Module for DockerMod {
+    fn name(&self) -> String {
+        "docker".to_string()
+    }
+
+    fn active(&self) -> bool {
+        self.containers.is_some()
+    }
+
+    fn view(
+        &self,
+        config: &LocalModuleConfig,
+        popup_config: &PopupConfig,
+        anchor: &BarAnchor,
+        _handlebars: &Handlebars,
+    ) -> Element<Message> {
+        button(
+            list![
+                anchor,
+                container(
+                    text(self.cfg_override.icon(config, &self.icon))
+                        .fill(anchor)
+                        .size(self.cfg_override.icon_size.unwrap_or(config.icon_size))
+                        .color(self.cfg_override.icon_color.unwrap_or(config.icon_color))
+                        .font(config.icon_font)
+                )
+                .padding(self.cfg_override.icon_margin.unwrap_or(config.icon_margin)),
+                container(
+                    bar_text(
+                        self.running().to_string(),
+                        anchor,
+                        self.cfg_override.vertical_text(config, anchor)
+                    )
+                    .size(self.cfg_override.font_size.unwrap_or(config.font_size))
+                    .font(self.cfg_override.text_font.unwrap_or(config.text_font))
+                    .color(self.cfg_override.text_color.unwrap_or(config.text_color))
+                )
+                .padding(self.cfg_override.text_margin.unwrap_or(config.text_margin)),
+            ]
+            .spacing(self.cfg_override.spacing.unwrap_or(config.spacing)),
+        )
+        .on_event_with(Message::popup::<Self>(
+            self.popup_cfg_override.width.unwrap_or(popup_config.width),
+            self.popup_cfg_override
+                .height
+                .unwrap_or(popup_config.height),
+            anchor,
+        ))
+        .style(|_, _| Style::default())
+        .into()
+    }
+
+    fn popup_cfg_override(&self) -> Option<&PopupConfigOverride> {
+        Some(&self.popup_cfg_override)
+    }
+
+    fn popup_view<'a>(
+        &'a self,
+        config: &'a PopupConfig,
+        _template: &Handlebars,
+    ) -> Element<'a, Message> {
+        let text_color = self
+            .popup_cfg_override
+            .text_color
+            .unwrap_or(config.text_color);
+        let fmt_text = |content: String, color: Color| -> Container<'a, Message> {
+            container(
+                text(content)
+                    .size(
+                        self.popup_cfg_override
+                            .font_size
+                            .unwrap_or(config.font_size),
+                    )
+                    .color(color),
+            )
+            .padding(
+                self.popup_cfg_override
+                    .text_margin
+                    .unwrap_or(config.text_margin),
+            )
+        };
+
+        let containers = self.containers.iter().flatten().enumerate().map(
+            |(index, container)| -> Element<'a, Message> {
+                let color = match container.running() {
+                    true => self.running_color,
+                    false => self.stopped_color,
+                };
+                button(
+                    row![
+                        fmt_text("●".to_string(), color),
+                        fmt_text(container.name.clone(), text_color).width(Fill),
+                        fmt_text(container.status.clone(), text_color),
+                    ]
+                    .align_y(Alignment::Center),
+                )
+                .on_event(DockerAction::Toggle(index).as_message())
+                .style(|_, _| Style::default())
+                .width(Fill)
+                .into()
+            },
+        );
+
+        container(scrollable(Column::with_children(containers).spacing(
+            self.popup_cfg_override.spacing.unwrap_or(config.spacing),
+        )))
+        .into()
+    }
+
+    impl_wrapper!();
+
+    fn read_config(
+        &mut self,
+        config: &HashMap<String, Option<String>>,
+        popup_config: &HashMap<String, Option<String>>,
+        _templates: &mut Handlebars,
+        scale: f32,
+    ) {
+        let default = Self::default();
+        self.cfg_override = (config, scale).into();
+        self.popup_cfg_override.update(popup_config, scale);
+        self.icon = config
+            .get("icon")
+            .and_then(|v| v.clone())
+            .unwrap_or(default.icon);
+        self.socket = config
+            .get("socket")
+            .and_then(|v| v.clone())
+            .unwrap_or(default.socket);
+        self.running_color = config
+            .get("running_color")
+            .and_then(|v| v.into_color())
+            .unwrap_or(default.running_color);
+        self.stopped_color = config
+            .get("stopped_color")
+            .and_then(|v| v.into_color())
+            .unwrap_or(default.stopped_color);
+    }
+
+    fn known_keys(&self) -> Vec<ConfigKey> {
+        vec![
+            ConfigKey::new("icon", Kind::Text, Some("󰡨")),
+            // Defaults to `$DOCKER_HOST` or /var/run/docker.sock
+            ConfigKey::new("socket", Kind::Text, None),
+            ConfigKey::new("running_color", Kind::Color, Some("rgb(30%, 80%, 30%)")),
+            ConfigKey::new("stopped_color", Kind::Color, Some("rgb(50%, 50%, 50%)")),
+        ]
+    }
+
+    impl_on_click!();
+
+    fn has_popup(&self) -> bool {
+        true
+    }
+
+    fn handle_action(&mut self, action: &dyn Action) {
+        let Some(DockerAction::Toggle(index)) = action.downcast_ref::<DockerAction>() else {
+            return;
+        };
+        let Some(container) = self.containers.as_ref().and_then(|c| c.get(*index)) else {
+            return;
+        };
+        let set = SetContainer {
+            id: container.id.clone(),
+            start: !container.running(),
+        };
+        if let Some(Err(_)) = self.actions.as_ref().map(|sender| sender.send(set)) {
+            eprintln!(
+                "Failed to change container `{}`: the subscription is gone",
+                container.name
+            );
+        }
+    }
+
+    fn collect_data(&mut self) -> Result<(), String> {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .map_err(|e| e.to_string())?;
+        self.containers = Some(runtime.block_on(list_containers(&self.socket))?);
+        Ok(())
+    }
+
+    fn text_content(&self, _template: &Handlebars) -> Option<Result<String, String>> {
+        self.containers
+            .as_ref()
+            .map(|_| Ok(self.running().to_string()))
+    }
+
+    fn subscription(&self) -> Option<Subscription<Message>> {
+        let socket = self.socket.clone();
+        Some(Subscription::run_with_id(
+            ("docker", socket.clone()),
+            stream::channel(1, move |mut sender| async move {
+                let (action_sx, mut action_rx) = mpsc::unbounded_channel();
+                if sender
+                    .send(Message::update(move |reg| {
+                        reg.get_module_mut::<DockerMod>().actions = Some(action_sx)
+                    }))
+                    .await
+                    .is_err()
+                {
+                    return;
+                }
+                watch_containers(&mut sender, &mut action_rx, &socket).await;
+            }),
+        ))
+    }
+}
+
+/// Polls the containers and starts or stops them. Returns if the bar is closing.
+async fn watch_containers(
+    sender: &mut Sender<Message>,
+    actions: &mut UnboundedReceiver<SetContainer>,
+    socket: &str,
+) {
+    let mut previous = None;
+    let mut reported = false;
+    loop {
+        let containers = match list_containers(socket).await {
+            Ok(containers) => {
+                reported = false;
+                Some(containers)
+            }
+            Err(e) => {
+                // The daemon not running is common, so this is only printed once
+                if !reported {
+                    eprintln!("{e}, hiding the docker module");
+                    reported = true;
+                }
+                None
+            }
+        };
+        if previous != Some(containers.clone()) {
+            previous = Some(containers.clone());
+            if sender
+                .send(Message::update(move |reg| {
+                    reg.get_module_mut::<DockerMod>().containers = containers
+                }))
+                .await
+                .is_err()
+            {
+                return;
+            }
+        }
+        select! {
+            _ = sleep(Duration::from_secs(5)) => {}
+            Some(set) = actions.recv() => {
+                let action = match set.start {
+                    true => "start",
+                    false => "stop",
+                };
+                if let Err(e) =
+                    request(socket, "POST", &format!("/containers/{}/{action}", set.id)).await
+                {
+                    eprintln!("Failed to {action} container {}: {e}", set.id);
+                }
+            }
+        }
+    }
+}
+
+async fn list_containers(socket: &str) -> Result<Vec<ContainerState>, String> {
+    let body = request(socket, "GET", "/containers/json?all=true").await?;
+    let Ok(Value::Array(containers)) = serde_json::from_str(&body) else {
+        return Err("Failed to parse the container list of the Docker API".to_string());
+    };
+    Ok(containers
+        .iter()
+        .filter_map(ContainerState::from_json)
+        .collect())
+}
+
+/// Sends a request to the Docker API and returns the response body. HTTP/1.0 is used so that
+/// the response is neither chunked nor kept alive.
+async fn request(socket: &str, method: &str, path: &str) -> Result<String, String> {
+    let mut stream = UnixStream::connect(socket)
+        .await
+        .map_err(|e| format!("Failed to connect to {socket}: {e}"))?;
+    stream
+        .write_all(
+            format!("{method} {path} HTTP/1.0\r\nHost: localhost\r\nContent-Length: 0\r\n\r\n")
+                .as_bytes(),
+        )
+        .await
+        .map_err(|e| format!("Failed to send a request to {socket}: {e}"))?;
+    let mut response = String::new();
+    stream
+        .read_to_string(&mut response)
+        .await
+        .map_err(|e| format!("Failed to read the response from {socket}: {e}"))?;
+    let (head, body) = response
+        .split_once("\r\n\r\n")
+        .ok_or_else(|| format!("Got an invalid response from {socket}"))?;
+    let status = head
+        .split_whitespace()
+        .nth(1)
+        .and_then(|status| status.parse::<u16>().ok())
+        .ok_or_else(|| format!("Got an invalid response from {socket}"))?;
+    match status {
+        // 304: the container already was started or stopped
+        200..=299 | 304 => Ok(body.to_string()),
+        _ => Err(format!(
+            "{method} {path} failed with status {status}: {}",
+            serde_json::from_str::<Value>(body)
+                .ok()
+                .and_then(|v| v["message"].as_str().map(|m| m.to_string()))
+                .unwrap_or_else(|| body.trim().to_string())
+        )),
+    }
+}
@@ -1,34 +1,94 @@
+use std::collections::HashMap;
+
 use bar_rs_derive::Builder;
 
-use iced::Element;
+use handlebars::Handlebars;
+use iced::{
+    widget::Space,
+    Element,
+    Length::{self, Fill},
+};
 
 use crate::{
     config::{
         anchor::BarAnchor,
         module_config::{LocalModuleConfig, ModuleConfigOverride},
+        parse::StringExt,
+        popup_config::PopupConfig,
+        scale::Scale,
+        schema::{ConfigKey, Kind},
     },
     impl_wrapper, FillExt, Message,
 };
 
 use super::Module;
 
+/// A spacer of a fixed `width`, or one that takes all available space if `flex` is set
 #[derive(Debug, Default, Builder)]
 pub struct EmptyModule {
     cfg_override: ModuleConfigOverride,
+    width: f32,
+    flex: bool,
 }
 
 impl Module for EmptyModule {
     fn name(&self) -> String {
         String::from("empty")
     }
+
     fn view(
         &self,
         _config: &LocalModuleConfig,
-        _popup_config: &crate::config::popup_config::PopupConfig,
-        _anchor: &BarAnchor,
-        _template: &handlebars::Handlebars,
-    ) -> iced::Element<crate::Message> {
-        "".into()
+        _popup_config: &PopupConfig,
+        anchor: &BarAnchor,
+        _template: &Handlebars,
+    ) -> Element<Message> {
+        // The width is along the bar, so it becomes the height of vertical bars
+        let length = match self.flex {
+            true => Fill,
+            false => Length::Fixed(self.width),
+        };
+        match anchor.vertical() {
+            true => Space::new(Fill, length),
+            false => Space::new(length, Fill),
+        }
+        .into()
+    }
+
+    fn stretch(&self) -> Option<u16> {
+        self.flex.then_some(1)
+    }
+
+    fn spacer(&self) -> bool {
+        true
     }
+
     impl_wrapper!();
+
+    fn read_config(
+        &mut self,
+        config: &HashMap<String, Option<String>>,
+        _popup_config: &HashMap<String, Option<String>>,
+        _templates: &mut Handlebars,
+        scale: f32,
+    ) {
+        let default = Self::default();
+        self.cfg_override = (config, scale).into();
+        self.width = config
+            .get("width")
+            .and_then(|v| v.into_float())
+            .map(|w| w.scaled(scale))
+            .unwrap_or(default.width);
+        self.flex = config
+            .get("flex")
+            .and_then(|v| v.into_bool())
+            .unwrap_or(default.flex);
+    }
+
+    fn known_keys(&self) -> Vec<ConfigKey> {
+        vec![
+            ConfigKey::new("width", Kind::Float, Some("0")),
+            ConfigKey::new("flex", Kind::Bool, Some("false")),
+        ]
+    }
 }
@@ -0,0 +1,132 @@
+use std::collections::HashMap;
+
+use bar_rs_derive::Builder;
+use handlebars::Handlebars;
+use iced::widget::{container, text};
+use iced::{Element, Event};
+
+use crate::config::popup_config::PopupConfig;
+use crate::{
+    config::{
+        anchor::BarAnchor,
+        module_config::{LocalModuleConfig, ModuleConfigOverride},
+        schema::{ConfigKey, Kind},
+    },
+    fill::FillExt,
+    impl_wrapper, Message,
+};
+
+use super::{Action, Module, ToggleGroupAction};
+
+/// Toggles the visibility of a group of modules when clicked
+#[derive(Debug, Builder)]
+pub struct FocusMod {
+    cfg_override: ModuleConfigOverride,
+    toggle: ToggleGroupAction,
+    icon: String,
+    icon_hidden: String,
+    hidden: bool,
+}
+
+impl Default for FocusMod {
+    fn default() -> Self {
+        Self {
+            cfg_override: Default::default(),
+            toggle: ToggleGroupAction("focus".to_string()),
+            icon: "󰈈".to_string(),
+            icon_hidden: "󰈉".to_string(),
+            hidden: false,
+        }
+    }
+}
+
+impl FocusMod {
+    pub fn group_toggled(&mut self, group: &str, hidden: bool) {
+        if self.toggle.0 == group {
+            self.hidden = hidden;
+        }
+    }
+}
+
+impl Module for FocusMod {
+    fn name(&self) -> String {
+        "focus".to_string()
+    }
+
+    fn view(
+        &self,
+        config: &LocalModuleConfig,
+        _popup_config: &PopupConfig,
+        anchor: &BarAnchor,
+        _handlebars: &Handlebars,
+    ) -> Element<Message> {
+        container(
+            text(self.cfg_override.icon(
+                config,
+                match self.hidden {
+                    true => &self.icon_hidden,
+                    false => &self.icon,
+                },
+            ))
+            .size(self.cfg_override.icon_size.unwrap_or(config.icon_size))
+            .color(self.cfg_override.icon_color.unwrap_or(config.icon_color))
+            .font(config.icon_font)
+            .fill(anchor),
+        )
+        .fill(anchor)
+        .padding(self.cfg_override.icon_margin.unwrap_or(config.icon_margin))
+        .into()
+    }
+
+    impl_wrapper!();
+
+    fn read_config(
+        &mut self,
+        config: &HashMap<String, Option<String>>,
+        _popup_config: &HashMap<String, Option<String>>,
+        _templates: &mut Handlebars,
+        scale: f32,
+    ) {
+        let default = Self::default();
+        self.cfg_override = (config, scale).into();
+        self.toggle = config
+            .get("group")
+            .and_then(|v| v.clone())
+            .map(ToggleGroupAction)
+            .unwrap_or(default.toggle);
+        self.icon = config
+            .get("icon")
+            .and_then(|v| v.clone())
+            .unwrap_or(default.icon);
+        self.icon_hidden = config
+            .get("icon_hidden")
+            .and_then(|v| v.clone())
+            .unwrap_or(default.icon_hidden);
+    }
+
+    fn known_keys(&self) -> Vec<ConfigKey> {
+        vec![
+            ConfigKey::new("group", Kind::Text, Some("focus")),
+            ConfigKey::new("icon", Kind::Text, Some("󰈈")),
+            ConfigKey::new("icon_hidden", Kind::Text, Some("󰈉")),
+        ]
+    }
+
+    fn on_click<'a>(
+        &'a self,
+        event: iced::Event,
+        config: &'a LocalModuleConfig,
+    ) -> Option<&'a dyn Action> {
+        self.cfg_override
+            .action
+            .as_ref()
+            .unwrap_or(&config.action)
+            .event(event.clone())
+            .or(match event {
+                Event::Mouse(iced::mouse::Event::ButtonReleased(iced::mouse::Button::Left)) => {
+                    Some(&self.toggle as &dyn Action)
+                }
+                _ => None,
+            })
+    }
+}
@@ -0,0 +1,440 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    time::Duration,
+};
+
+use bar_rs_derive::Builder;
+use handlebars::Handlebars;
+use iced::widget::container;
+use iced::{futures::SinkExt, stream, widget::text, Element, Subscription};
+use serde_json::Value;
+use tokio::{task, time::sleep};
+
+use crate::bar_text::bar_text;
+use crate::config::{parse::StringExt, popup_config::PopupConfig};
+use crate::helpers::{
+    source::{Host, Source},
+    status::{send_status, ModuleStatus},
+    UnEscapeString,
+};
+use crate::{
+    config::{
+        anchor::BarAnchor,
+        module_config::{LocalModuleConfig, ModuleConfigOverride},
+        schema::{ConfigKey, Kind},
+        thresholds::Thresholds,
+    },
+    fill::FillExt,
+    Message,
+};
+use crate::{impl_on_click, impl_status, impl_wrapper};
+
+use super::Module;
+
+/// The DRM cards which are searched for one reporting its VRAM, `card0` to `card7`
+const MAX_CARDS: u32 = 8;
+const MIB: u64 = 1024 * 1024;
+
+/// Where the VRAM usage is read from
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+enum Backend {
+    /// AMD if a card reports its VRAM, NVIDIA otherwise
+    #[default]
+    Auto,
+    /// `mem_info_vram_used` and `mem_info_vram_total` of the amdgpu driver in `/sys/class/drm`
+    Amd,
+    /// `nvidia-smi`
+    Nvidia,
+}
+
+impl Backend {
+    fn named(backend: &str) -> Option<Self> {
+        match backend.trim() {
+            "auto" => Some(Self::Auto),
+            "amd" => Some(Self::Amd),
+            "nvidia" => Some(Self::Nvidia),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Builder)]
+pub struct GpuMemoryMod {
+    cfg_override: ModuleConfigOverride,
+    icon: Option<String>,
+    backend: Backend,
+    /// The number of the DRM card for AMD or the index of the GPU for NVIDIA, the first GPU
+    /// which reports its VRAM if it's `None`
+    device: Option<u32>,
+    interval: Duration,
+    /// `None` until the subscription read them for the first time
+    stats: Option<VramStats>,
+    thresholds: Thresholds,
+    status: ModuleStatus,
+}
+
+impl Default for GpuMemoryMod {
+    fn default() -> Self {
+        Self {
+            cfg_override: Default::default(),
+            icon: None,
+            backend: Backend::default(),
+            device: None,
+            interval: Duration::from_secs(2),
+            stats: None,
+            thresholds: Thresholds::default(),
+            status: ModuleStatus::Ok,
+        }
+    }
+}
+
+/// The memory of a GPU, in bytes
+#[derive(Debug, Default, Clone, PartialEq)]
+struct VramStats {
+    total: u64,
+    used: u64,
+}
+
+impl VramStats {
+    /// Used VRAM in percentage points
+    fn usage(&self) -> u64 {
+        match self.total {
+            0 => 0,
+            total => (self.used as f64 / total as f64 * 100.).round() as u64,
+        }
+    }
+
+    fn context(&self) -> BTreeMap<String, Value> {
+        BTreeMap::from([
+            ("usage".to_string(), self.usage().into()),
+            ("total".to_string(), self.total.into()),
+            ("used".to_string(), self.used.into()),
+            (
+                "free".to_string(),
+                self.total.saturating_sub(self.used).into(),
+            ),
+            ("total_mb".to_string(), (self.total / MIB).into()),
+            ("used_mb".to_string(), (self.used / MIB).into()),
+        ])
+    }
+}
+
+impl GpuMemoryMod {
+    fn text(&self, handlebars: &Handlebars) -> Result<String, String> {
+        let stats = self
+            .stats
+            .as_ref()
+            .ok_or("The VRAM usage wasn't read yet")?;
+        handlebars
+            .render("gpu_memory", &stats.context())
+            .map_err(|e| e.to_string())
+    }
+}
+
+impl Module for GpuMemoryMod {
+    fn name(&self) -> String {
+        "gpu_memory".to_string()
+    }
+
+    fn active(&self) -> bool {
+        self.stats.is_some()
+    }
+
+    impl_status!();
+
+    fn view(
+        &self,
+        config: &LocalModuleConfig,
+        _popup_config: &PopupConfig,
+        anchor: &BarAnchor,
+        handlebars: &Handlebars,
+    ) -> Element<Message> {
+        let usage = self.text(handlebars).unwrap_or_else(|e| {
+            eprintln!("{e}");
+            "999%".to_string()
+        });
+        let level = self
+            .stats
+            .as_ref()
+            .and_then(|stats| self.thresholds.color(stats.usage() as f32));
+
+        list![
+            anchor,
+            container(
+                text(
+                    self.cfg_override
+                        .icon(config, self.icon.as_deref().unwrap_or("󰢮"))
+                )
+                .fill(anchor)
+                .size(self.cfg_override.icon_size.unwrap_or(config.icon_size))
+                .color(level.unwrap_or(self.cfg_override.icon_color.unwrap_or(config.icon_color)))
+                .font(config.icon_font)
+            )
+            .padding(self.cfg_override.icon_margin.unwrap_or(config.icon_margin)),
+            container(
+                bar_text(
+                    usage,
+                    anchor,
+                    self.cfg_override.vertical_text(config, anchor)
+                )
+                .size(self.cfg_override.font_size.unwrap_or(config.font_size))
+                .font(self.cfg_override.text_font.unwrap_or(config.text_font))
+                .color(level.unwrap_or(self.cfg_override.text_color.unwrap_or(config.text_color)))
+            )
+            .padding(self.cfg_override.text_margin.unwrap_or(config.text_margin)),
+        ]
+        .spacing(self.cfg_override.spacing.unwrap_or(config.spacing))
+        .into()
+    }
+
+    impl_wrapper!();
+
+    fn read_config(
+        &mut self,
+        config: &HashMap<String, Option<String>>,
+        _popup_config: &HashMap<String, Option<String>>,
+        templates: &mut Handlebars,
+        scale: f32,
+    ) {
+        let default = Self::default();
+        self.cfg_override = (config, scale).into();
+        self.icon = config.get("icon").and_then(|v| v.clone());
+        self.backend = config
+            .get("backend")
+            .and_then(|v| v.as_deref())
+            .and_then(|backend| {
+                Backend::named(backend).or_else(|| {
+                    eprintln!("Unknown backend `{backend}` of the gpu_memory module, using `auto`");
+                    None
+                })
+            })
+            .unwrap_or(default.backend);
+        self.device = config
+            .get("device")
+            .and_then(|v| v.as_ref().and_then(|v| v.trim().parse().ok()));
+        self.interval = config
+            .get("interval")
+            .and_then(|v| v.into_duration())
+            .unwrap_or(default.interval);
+        self.thresholds = Thresholds::read(config.get("thresholds").and_then(|v| v.as_deref()));
+        templates
+            .register_template_string(
+                "gpu_memory",
+                config
+                    .get("format")
+                    .unescape()
+                    .unwrap_or("{{used_mb}}/{{total_mb}} MB ({{usage}}%)".to_string()),
+            )
+            .unwrap_or_else(|e| eprintln!("Failed to parse gpu_memory format: {e}"));
+    }
+
+    fn known_keys(&self) -> Vec<ConfigKey> {
+        vec![
+            ConfigKey::new("icon", Kind::Text, None),
+            ConfigKey::new(
+                "backend",
+                Kind::Choice(&["auto", "amd", "nvidia"]),
+                Some("auto"),
+            ),
+            ConfigKey::new("device", Kind::Integer, None),
+            ConfigKey::new("interval", Kind::Duration, Some("2s")),
+            ConfigKey::new("thresholds", Kind::Text, None),
+            ConfigKey::new(
+                "format",
+                Kind::Text,
+                Some("{{used_mb}}/{{total_mb}} MB ({{usage}}%)"),
+            ),
+        ]
+    }
+
+    impl_on_click!();
+
+    fn collect_data(&mut self) -> Result<(), String> {
+        self.stats = Some(get_stats(&Host, self.backend, self.device)?);
+        Ok(())
+    }
+
+    fn collect_data_from(&mut self, source: &dyn Source) -> Result<(), String> {
+        self.stats = Some(get_stats(&source, self.backend, self.device)?);
+        Ok(())
+    }
+
+    fn text_content(&self, template: &Handlebars) -> Option<Result<String, String>> {
+        Some(self.text(template))
+    }
+
+    fn variables(&self) -> BTreeMap<String, Value> {
+        self.stats
+            .as_ref()
+            .map(|stats| stats.context())
+            .unwrap_or_default()
+    }
+
+    fn subscription(&self) -> Option<Subscription<Message>> {
+        let (backend, device, interval) = (self.backend, self.device, self.interval);
+        Some(Subscription::run_with_id(
+            ("gpu_memory", backend, device, interval),
+            stream::channel(1, move |mut sender| async move {
+                loop {
+                    let stats = task::spawn_blocking(move || get_stats(&Host, backend, device))
+                        .await
+                        .unwrap_or_else(|e| Err(e.to_string()));
+                    let sent = match stats {
+                        Ok(stats) => sender
+                            .send(Message::update(move |reg| {
+                                let m = reg.get_module_mut::<GpuMemoryMod>();
+                                m.stats = Some(stats);
+                                m.set_status(ModuleStatus::Ok);
+                            }))
+                            .await
+                            .is_ok(),
+                        // There may be no GPU or driver, so this is retried at the same pace
+                        Err(e) => {
+                            send_status::<GpuMemoryMod>(&mut sender, ModuleStatus::Unavailable(e))
+                                .await
+                        }
+                    };
+                    if !sent {
+                        return;
+                    }
+                    sleep(interval).await;
+                }
+            }),
+        ))
+    }
+}
+
+fn get_stats(
+    source: &impl Source,
+    backend: Backend,
+    device: Option<u32>,
+) -> Result<VramStats, String> {
+    match backend {
+        Backend::Auto => amd_stats(source, device).or_else(|amd| {
+            nvidia_stats(source, device).map_err(|nvidia| format!("{amd}, and {nvidia}"))
+        }),
+        Backend::Amd => amd_stats(source, device),
+        Backend::Nvidia => nvidia_stats(source, device),
+    }
+}
+
+/// Reads the VRAM of the amdgpu driver, in bytes, from the first card reporting it
+fn amd_stats(source: &impl Source, device: Option<u32>) -> Result<VramStats, String> {
+    let read = |card: u32, file: &str| -> Option<u64> {
+        source
+            .read(&format!("/sys/class/drm/card{card}/device/{file}"))
+            .ok()?
+            .trim()
+            .parse()
+            .ok()
+    };
+    let cards = match device {
+        Some(card) => card..card + 1,
+        None => 0..MAX_CARDS,
+    };
+    cards
+        .find_map(|card| {
+            Some(VramStats {
+                total: read(card, "mem_info_vram_total")?,
+                used: read(card, "mem_info_vram_used")?,
+            })
+        })
+        .ok_or_else(|| match device {
+            Some(card) => format!("card{card} doesn't report its VRAM in /sys/class/drm"),
+            None => "No AMD GPU reports its VRAM in /sys/class/drm".to_string(),
+        })
+}
+
+/// Reads the used and total memory of a GPU from `nvidia-smi`, which reports them in MiB, one
+/// line per GPU
+fn nvidia_stats(source: &impl Source, device: Option<u32>) -> Result<VramStats, String> {
+    let stdout = source
+        .output(
+            "nvidia-smi",
+            &[
+                "--query-gpu=memory.used,memory.total",
+                "--format=csv,noheader,nounits",
+            ],
+        )
+        .map_err(|e| format!("Failed to run nvidia-smi: {e}"))?;
+    let line = stdout
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .nth(device.unwrap_or(0) as usize)
+        .ok_or("nvidia-smi didn't report the GPU")?;
+    let values = line
+        .split(',')
+        .map(|value| value.trim().parse::<u64>())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Failed to parse the VRAM usage (output from nvidia-smi): {e}"))?;
+    match values[..] {
+        [used, total] => Ok(VramStats {
+            total: total * MIB,
+            used: used * MIB,
+        }),
+        _ => Err(format!(
+            "Failed to parse the VRAM usage (output from nvidia-smi): `{line}`"
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{helpers::source::Fixtures, modules::testing::Harness};
+
+    use super::*;
+
+    const NVIDIA_SMI: &str =
+        "nvidia-smi --query-gpu=memory.used,memory.total --format=csv,noheader,nounits";
+
+    fn amd(card: u32, used: u64, total: u64) -> Fixtures {
+        Fixtures::default()
+            .file(
+                &format!("/sys/class/drm/card{card}/device/mem_info_vram_used"),
+                &format!("{used}\n"),
+            )
+            .file(
+                &format!("/sys/class/drm/card{card}/device/mem_info_vram_total"),
+                &format!("{total}\n"),
+            )
+    }
+
+    #[test]
+    fn reads_amd_and_nvidia_vram() {
+        let stats = VramStats {
+            total: 8192 * MIB,
+            used: 2048 * MIB,
+        };
+        // The first card may be an integrated GPU without VRAM info
+        let source = amd(1, 2048 * MIB, 8192 * MIB);
+        assert_eq!(get_stats(&source, Backend::Auto, None), Ok(stats.clone()));
+        assert!(get_stats(&source, Backend::Amd, Some(0)).is_err());
+        assert!(get_stats(&source, Backend::Nvidia, None).is_err());
+
+        let source = Fixtures::default().output(NVIDIA_SMI, "512, 4096\n2048, 8192\n");
+        assert_eq!(get_stats(&source, Backend::Auto, Some(1)), Ok(stats));
+        assert_eq!(
+            get_stats(&source, Backend::Nvidia, None).map(|stats| stats.usage()),
+            Ok(13)
+        );
+        assert!(get_stats(&source, Backend::Nvidia, Some(2)).is_err());
+        assert!(get_stats(&source, Backend::Amd, None).is_err());
+    }
+
+    #[test]
+    fn rejects_unexpected_nvidia_smi_output() {
+        let smi = |output: &str| Fixtures::default().output(NVIDIA_SMI, output);
+        assert!(nvidia_stats(&smi(""), None).is_err());
+        assert!(nvidia_stats(&smi("[N/A], 4096\n"), None).is_err());
+        assert!(nvidia_stats(&smi("512\n"), None).is_err());
+        assert_eq!(VramStats::default().usage(), 0);
+    }
+
+    #[test]
+    fn renders_the_format() {
+        let stats = get_stats(&amd(0, 3072 * MIB, 12288 * MIB), Backend::Amd, None).unwrap();
+        let mut harness = Harness::new::<GpuMemoryMod>(&[]);
+        harness.update(|m: &mut GpuMemoryMod| m.stats = Some(stats));
+        assert_eq!(harness.text::<GpuMemoryMod>(), "3072/12288 MB (25%)");
+    }
+}
@@ -1,2 +1,4 @@
+pub mod scratchpad;
+pub mod submap;
 pub mod window;
 pub mod workspaces;
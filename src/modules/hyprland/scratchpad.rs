@@ -0,0 +1,218 @@
+use std::{any::TypeId, collections::HashMap};
+
+use bar_rs_derive::Builder;
+use handlebars::Handlebars;
+use hyprland::{
+    data::{Monitors, Workspaces},
+    shared::{HyprData, HyprDataVec},
+};
+use iced::{
+    futures::{channel::mpsc::Sender, SinkExt},
+    widget::{button, container, rich_text, span},
+    Background, Color, Element,
+};
+
+use crate::{
+    config::{
+        anchor::BarAnchor,
+        module_config::{LocalModuleConfig, ModuleConfigOverride},
+        parse::StringExt,
+        popup_config::PopupConfig,
+        schema::{ConfigKey, Kind},
+    },
+    fill::FillExt,
+    impl_on_click, impl_wrapper,
+    list::list,
+    listeners::hyprland::HyprListener,
+    modules::{require_listener, Module},
+    Message,
+};
+
+/// A special workspace (scratchpad) which has windows
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Scratchpad {
+    /// The name without the `special:` prefix
+    pub name: String,
+    pub windows: u16,
+    /// Whether it's shown on any monitor
+    pub visible: bool,
+}
+
+#[derive(Debug, Builder)]
+pub struct HyprScratchpadMod {
+    pub scratchpads: Vec<Scratchpad>,
+    cfg_override: ModuleConfigOverride,
+    /// Icons for specific special workspaces
+    icons: HashMap<String, String>,
+    default_icon: String,
+    visible_color: Color,
+    visible_background: Option<Background>,
+}
+
+impl Default for HyprScratchpadMod {
+    fn default() -> Self {
+        Self {
+            scratchpads: vec![],
+            cfg_override: ModuleConfigOverride::default(),
+            icons: HashMap::new(),
+            default_icon: "S".to_string(),
+            visible_color: Color::WHITE,
+            visible_background: None,
+        }
+    }
+}
+
+impl HyprScratchpadMod {
+    fn icon(&self, name: &str) -> &str {
+        self.icons
+            .get(&name.to_lowercase())
+            .unwrap_or(&self.default_icon)
+    }
+}
+
+impl Module for HyprScratchpadMod {
+    fn name(&self) -> String {
+        "hyprland.scratchpad".to_string()
+    }
+
+    fn active(&self) -> bool {
+        !self.scratchpads.is_empty()
+    }
+
+    fn view(
+        &self,
+        config: &LocalModuleConfig,
+        _popup_config: &PopupConfig,
+        anchor: &BarAnchor,
+        _handlebars: &Handlebars,
+    ) -> Element<Message> {
+        list(
+            anchor,
+            self.scratchpads.iter().map(|scratchpad| {
+                let mut span = span(self.icon(&scratchpad.name))
+                    .size(self.cfg_override.icon_size.unwrap_or(config.icon_size))
+                    .color(self.cfg_override.icon_color.unwrap_or(config.icon_color))
+                    .font(config.icon_font);
+                if scratchpad.visible {
+                    span = span
+                        .color(self.visible_color)
+                        .background_maybe(self.visible_background);
+                }
+                button(
+                    container(rich_text![span].fill(anchor))
+                        .padding(self.cfg_override.icon_margin.unwrap_or(config.icon_margin)),
+                )
+                .padding(0)
+                .style(|_, _| button::Style::default())
+                .on_press(Message::command_sh(format!(
+                    "hyprctl dispatch togglespecialworkspace {}",
+                    scratchpad.name
+                )))
+                .into()
+            }),
+        )
+        .padding(self.cfg_override.padding.unwrap_or(config.padding))
+        .spacing(self.cfg_override.spacing.unwrap_or(config.spacing))
+        .into()
+    }
+
+    impl_wrapper!();
+
+    fn requires(&self) -> Vec<TypeId> {
+        vec![require_listener::<HyprListener>()]
+    }
+
+    fn read_config(
+        &mut self,
+        config: &HashMap<String, Option<String>>,
+        _popup_config: &HashMap<String, Option<String>>,
+        _templates: &mut Handlebars,
+        scale: f32,
+    ) {
+        let default = Self::default();
+        self.cfg_override = (config, scale).into();
+        self.icons = config
+            .iter()
+            .filter_map(|(key, value)| {
+                let name = key.strip_prefix("icon.")?;
+                let name = name.strip_prefix("special:").unwrap_or(name);
+                Some((name.to_string(), value.clone()?))
+            })
+            .collect();
+        self.default_icon = config
+            .get("icon")
+            .and_then(|v| v.clone())
+            .unwrap_or(default.default_icon);
+        self.visible_color = config
+            .get("visible_color")
+            .and_then(|v| v.into_color())
+            .unwrap_or(default.visible_color);
+        self.visible_background = config
+            .get("visible_background")
+            .map(|v| v.into_background())
+            .unwrap_or(default.visible_background);
+    }
+
+    fn known_keys(&self) -> Vec<ConfigKey> {
+        vec![
+            ConfigKey::new("icon", Kind::Text, Some("S")),
+            ConfigKey::new("icon.*", Kind::Text, None),
+            ConfigKey::new("visible_color", Kind::Color, Some("white")),
+            ConfigKey::new("visible_background", Kind::Color, None),
+        ]
+    }
+
+    impl_on_click!();
+
+    fn text_content(&self, _template: &Handlebars) -> Option<Result<String, String>> {
+        Some(Ok(self
+            .scratchpads
+            .iter()
+            .map(|scratchpad| self.icon(&scratchpad.name))
+            .collect::<Vec<_>>()
+            .join(" ")))
+    }
+}
+
+/// Sends the special workspaces which have windows, sorted by name
+pub async fn update_scratchpads(sender: &mut Sender<Message>) {
+    let Ok(workspaces) = Workspaces::get_async().await else {
+        eprintln!("[hyprland.scratchpad] Failed to get Workspaces!");
+        return;
+    };
+    // The special workspace shown on a monitor, the name is empty if there is none
+    let visible = Monitors::get_async()
+        .await
+        .map(|monitors| {
+            monitors
+                .to_vec()
+                .into_iter()
+                .map(|monitor| monitor.special_workspace.name)
+                .filter(|name| !name.is_empty())
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+    let mut scratchpads = workspaces
+        .to_vec()
+        .into_iter()
+        .filter(|ws| ws.windows > 0 && (ws.id < 0 || ws.name.starts_with("special:")))
+        .map(|ws| Scratchpad {
+            visible: visible.contains(&ws.name),
+            name: ws
+                .name
+                .strip_prefix("special:")
+                .unwrap_or(&ws.name)
+                .to_string(),
+            windows: ws.windows,
+        })
+        .collect::<Vec<_>>();
+    scratchpads.sort_by(|a, b| a.name.cmp(&b.name));
+    sender
+        .send(Message::update(move |reg| {
+            reg.get_module_mut::<HyprScratchpadMod>().scratchpads = scratchpads
+        }))
+        .await
+        .unwrap_or_else(|err| {
+            eprintln!("Trying to send the scratchpads failed with err: {err}");
+        });
+}
@@ -0,0 +1,120 @@
+use std::{any::TypeId, collections::HashMap};
+
+use bar_rs_derive::Builder;
+use handlebars::Handlebars;
+use iced::widget::{container, text};
+use iced::{Color, Element};
+
+use crate::config::parse::StringExt;
+use crate::config::popup_config::PopupConfig;
+use crate::{
+    config::{
+        anchor::BarAnchor,
+        module_config::{LocalModuleConfig, ModuleConfigOverride},
+        schema::{ConfigKey, Kind},
+    },
+    fill::FillExt,
+    listeners::hyprland::HyprListener,
+    modules::{require_listener, Message, Module},
+};
+use crate::{impl_on_click, impl_wrapper};
+
+#[derive(Debug, Default, Builder)]
+pub struct HyprSubmapMod {
+    /// The active submap, `None` in the default state
+    pub submap: Option<String>,
+    /// Labels shown instead of the submap names
+    labels: HashMap<String, String>,
+    /// Text colors for specific submaps
+    colors: HashMap<String, Color>,
+    cfg_override: ModuleConfigOverride,
+}
+
+impl Module for HyprSubmapMod {
+    fn name(&self) -> String {
+        "hyprland.submap".to_string()
+    }
+
+    fn active(&self) -> bool {
+        self.submap.is_some()
+    }
+
+    fn view(
+        &self,
+        config: &LocalModuleConfig,
+        _popup_config: &PopupConfig,
+        anchor: &BarAnchor,
+        _handlebars: &Handlebars,
+    ) -> Element<Message> {
+        let submap = self.submap.as_deref().unwrap_or_default();
+        // Config keys are lowercase
+        let key = submap.to_lowercase();
+        container(
+            text(self.labels.get(&key).map(|l| l.as_str()).unwrap_or(submap))
+                .fill(anchor)
+                .size(self.cfg_override.font_size.unwrap_or(config.font_size))
+                .font(self.cfg_override.text_font.unwrap_or(config.text_font))
+                .color(
+                    self.colors
+                        .get(&key)
+                        .copied()
+                        .or(self.cfg_override.text_color)
+                        .unwrap_or(config.text_color),
+                ),
+        )
+        .padding(self.cfg_override.text_margin.unwrap_or(config.text_margin))
+        .into()
+    }
+
+    impl_wrapper!();
+
+    fn requires(&self) -> Vec<TypeId> {
+        vec![require_listener::<HyprListener>()]
+    }
+
+    fn read_config(
+        &mut self,
+        config: &HashMap<String, Option<String>>,
+        _popup_config: &HashMap<String, Option<String>>,
+        _templates: &mut Handlebars,
+        scale: f32,
+    ) {
+        self.cfg_override = (config, scale).into();
+        self.labels = HashMap::new();
+        self.colors = HashMap::new();
+        for (key, value) in config {
+            if let Some(submap) = key.strip_prefix("label.") {
+                if let Some(label) = value {
+                    self.labels.insert(submap.to_string(), label.clone());
+                }
+            } else if let Some(submap) = key.strip_prefix("color.") {
+                if let Some(color) = value.into_color() {
+                    self.colors.insert(submap.to_string(), color);
+                }
+            }
+        }
+    }
+
+    fn known_keys(&self) -> Vec<ConfigKey> {
+        vec![
+            ConfigKey::new("label.*", Kind::Text, None),
+            ConfigKey::new("color.*", Kind::Color, None),
+        ]
+    }
+
+    impl_on_click!();
+
+    fn collect_data(&mut self) -> Result<(), String> {
+        // Hyprland only reports submap changes, so the bar always starts in the default state
+        Ok(())
+    }
+
+    fn text_content(&self, _template: &Handlebars) -> Option<Result<String, String>> {
+        let submap = self.submap.as_ref()?;
+        Some(Ok(self
+            .labels
+            .get(&submap.to_lowercase())
+            .unwrap_or(submap)
+            .clone()))
+    }
+}
@@ -2,53 +2,195 @@ use std::{any::TypeId, collections::HashMap};
 
 use bar_rs_derive::Builder;
 use handlebars::Handlebars;
-use iced::widget::{container, rich_text, span, text};
+use hyprland::{
+    data::{Client, Workspace},
+    dispatch::{Dispatch, DispatchType, WindowSwitchDirection},
+    shared::{HyprDataActive, HyprDataActiveOptional},
+};
+use iced::widget::{button, container, rich_text, span, text};
 use iced::{
     futures::{channel::mpsc::Sender, SinkExt},
-    Element,
+    Color, Element,
 };
+use serde_json::json;
 
-use crate::config::popup_config::PopupConfig;
+use crate::config::{parse::StringExt, popup_config::PopupConfig};
+use crate::helpers::{window::WindowTitle, UnEscapeString};
+use crate::list::list;
 use crate::tooltip::ElementExt;
 use crate::{
     config::{
         anchor::BarAnchor,
         module_config::{LocalModuleConfig, ModuleConfigOverride},
+        schema::{ConfigKey, Kind},
     },
     fill::FillExt,
     listeners::hyprland::HyprListener,
-    modules::{require_listener, Message, Module},
+    modules::{require_listener, Action, Message, Module},
 };
 use crate::{impl_on_click, impl_wrapper};
 
+/// The focused window, as far as this module cares about it
+#[derive(Debug, Default, Clone)]
+struct ActiveWindow {
+    title: String,
+    class: String,
+    xwayland: bool,
+    floating: bool,
+    /// Whether the workspace of the window has a fullscreen window, which is usually this one
+    fullscreen: bool,
+    /// The number of windows on the workspace of the window
+    count: u16,
+    /// The (1-based) position of the window in its group (tabs) and the size of the group
+    group: Option<(usize, usize)>,
+}
+
+impl ActiveWindow {
+    /// Gets the focused window from hyprland, `None` if no window is focused
+    async fn get() -> Result<Option<Self>, String> {
+        let client = Client::get_active_async()
+            .await
+            .map_err(|e| format!("Failed to get the active window from hyprland: {e}"))?;
+        let Some(client) = client else {
+            return Ok(None);
+        };
+        let workspace = Workspace::get_active_async()
+            .await
+            .map_err(|e| format!("Failed to get the active workspace from hyprland: {e}"))?;
+        Ok(Some(Self::new(client, workspace)))
+    }
+
+    fn new(client: Client, workspace: Workspace) -> Self {
+        // `grouped` lists the windows of the group in the order of their tabs
+        let group = client
+            .grouped
+            .iter()
+            .position(|address| **address == client.address)
+            .map(|index| (index + 1, client.grouped.len()));
+        Self {
+            title: client.title,
+            class: client.class,
+            xwayland: client.xwayland,
+            floating: client.floating,
+            fullscreen: workspace.fullscreen,
+            count: workspace.windows,
+            group,
+        }
+    }
+}
+
 #[derive(Debug, Builder)]
 pub struct HyprWindowMod {
-    title: Option<String>,
-    max_length: usize,
+    window: Option<ActiveWindow>,
+    window_title: WindowTitle,
+    format: String,
+    /// Shown while no window is focused, the module is hidden if this is empty
+    empty_text: String,
+    /// Whether a dot for each tab is shown next to the title of grouped windows
+    group_dots: bool,
+    group_dot: String,
+    group_dot_color: Color,
+    group_active_color: Color,
     cfg_override: ModuleConfigOverride,
 }
 
 impl Default for HyprWindowMod {
     fn default() -> Self {
         Self {
-            title: None,
-            max_length: 25,
+            window: None,
+            window_title: WindowTitle::default(),
+            format: "{{title}}{{#if group_total}} [{{group_index}}/{{group_total}}]{{/if}}"
+                .to_string(),
+            empty_text: String::new(),
+            group_dots: false,
+            group_dot: "●".to_string(),
+            group_dot_color: Color::from_rgba(1., 1., 1., 0.4),
+            group_active_color: Color::WHITE,
             cfg_override: Default::default(),
         }
     }
 }
 
 impl HyprWindowMod {
-    pub fn get_title(&self) -> Option<String> {
-        self.title
-            .as_ref()
-            .map(|title| match title.len() > self.max_length {
-                true => format!(
-                    "{}...",
-                    title.chars().take(self.max_length - 3).collect::<String>()
-                ),
-                false => title.to_string(),
+    fn text(&self, handlebars: &Handlebars) -> Result<String, String> {
+        let Some(window) = self.window.as_ref() else {
+            return Ok(self.empty_text.clone());
+        };
+        let ctx = json!({
+            "title": self.window_title.trimmed(Some(&window.class), &window.title),
+            "class": window.class,
+            "xwayland": window.xwayland,
+            "floating": window.floating,
+            "fullscreen": window.fullscreen,
+            "count": window.count,
+            // Empty if the window isn't grouped
+            "group_index": window.group.map(|(index, _)| json!(index)).unwrap_or(json!("")),
+            "group_total": window.group.map(|(_, total)| json!(total)).unwrap_or(json!("")),
+        });
+        handlebars
+            .render_template(&self.format, &ctx)
+            .map_err(|e| e.to_string())
+    }
+
+    /// A dot for each tab of the group of the focused window, clicking one focuses its tab
+    fn group_dots<'a>(&'a self, config: &LocalModuleConfig) -> Vec<Element<'a, Message>> {
+        let Some((active, total)) = self.window.as_ref().and_then(|w| w.group) else {
+            return vec![];
+        };
+        if !self.group_dots {
+            return vec![];
+        }
+        (1..=total)
+            .map(|index| {
+                button(rich_text![span(&self.group_dot)
+                    .size(self.cfg_override.icon_size.unwrap_or(config.icon_size))
+                    .color(match index == active {
+                        true => self.group_active_color,
+                        false => self.group_dot_color,
+                    })])
+                .padding(0)
+                .style(|_, _| button::Style::default())
+                .on_press(HyprWindowAction::FocusTab(index).as_message())
+                .into()
             })
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum HyprWindowAction {
+    /// Focus the tab at this (1-based) position in the group of the focused window
+    FocusTab(usize),
+}
+
+impl Action for HyprWindowAction {
+    fn as_message(&self) -> Message {
+        let action = *self;
+        Message::update(move |reg| reg.get_module_mut::<HyprWindowMod>().handle_action(&action))
+    }
+}
+
+/// Switches from the tab at `current` to the one at `target`
+fn focus_tab(current: usize, target: usize) {
+    // Hyprland 0.35 and later take the position of the tab, older versions only go forward (f)
+    // or back (b) by one tab
+    if Dispatch::call(DispatchType::Custom(
+        "changegroupactive",
+        &target.to_string(),
+    ))
+    .is_ok()
+    {
+        return;
+    }
+    for _ in 0..target.abs_diff(current) {
+        let direction = match target > current {
+            true => WindowSwitchDirection::Forward,
+            false => WindowSwitchDirection::Back,
+        };
+        if let Err(e) = Dispatch::call(DispatchType::ChangeGroupActive(direction)) {
+            eprintln!("Failed to switch to tab {target} of the group: {e}");
+            return;
+        }
     }
 }
 
@@ -58,7 +200,7 @@ impl Module for HyprWindowMod {
     }
 
     fn active(&self) -> bool {
-        self.title.is_some()
+        self.window.is_some() || !self.empty_text.is_empty()
     }
 
     fn view(
@@ -66,18 +208,49 @@ impl Module for HyprWindowMod {
         config: &LocalModuleConfig,
         _popup_config: &PopupConfig,
         anchor: &BarAnchor,
-        _handlebars: &Handlebars,
+        handlebars: &Handlebars,
     ) -> Element<Message> {
-        container(
-            rich_text([span(self.get_title().unwrap_or_default())
-                .size(self.cfg_override.font_size.unwrap_or(config.font_size))
-                .color(self.cfg_override.text_color.unwrap_or(config.text_color))])
+        let title = self
+            .window
+            .as_ref()
+            .map(|w| w.title.as_str())
+            .unwrap_or_default();
+        let class = self.window.as_ref().map(|w| w.class.as_str());
+        let icon: Option<Element<Message>> = self
+            .window_title
+            .icon(
+                class,
+                self.cfg_override.icon_size.unwrap_or(config.icon_size),
+            )
+            .map(|icon| {
+                container(icon)
+                    .padding(self.cfg_override.icon_margin.unwrap_or(config.icon_margin))
+                    .into()
+            });
+        let title_text: Element<Message> = container(
+            rich_text([span(
+                self.text(handlebars)
+                    .inspect_err(|e| eprintln!("Failed to render hyprland.window: {e}"))
+                    .unwrap_or_default(),
+            )
+            .size(self.cfg_override.font_size.unwrap_or(config.font_size))
+            .font(self.cfg_override.text_font.unwrap_or(config.text_font))
+            .color(self.cfg_override.text_color.unwrap_or(config.text_color))])
             .fill(anchor),
         )
         .padding(self.cfg_override.text_margin.unwrap_or(config.text_margin))
+        .into();
+        list(
+            anchor,
+            icon.into_iter()
+                .chain([title_text])
+                .chain(self.group_dots(config)),
+        )
+        .spacing(self.cfg_override.spacing.unwrap_or(config.spacing))
         .tooltip_maybe(
-            self.get_title()
-                .and_then(|t| (t.len() > self.max_length).then_some(text(t).size(12))),
+            self.window_title
+                .is_truncated(class, title)
+                .then(|| text(self.window_title.full(class, title)).size(12)),
         )
     }
 
@@ -92,24 +265,150 @@ impl Module for HyprWindowMod {
         config: &HashMap<String, Option<String>>,
         _popup_config: &HashMap<String, Option<String>>,
         _templates: &mut Handlebars,
+        scale: f32,
     ) {
-        self.cfg_override = config.into();
-        self.max_length = config
-            .get("max_length")
-            .and_then(|v| v.as_ref().and_then(|v| v.parse().ok()))
-            .unwrap_or(Self::default().max_length);
+        let default = Self::default();
+        self.cfg_override = (config, scale).into();
+        self.window_title.read_config(config);
+        self.format = config.get("format").unescape().unwrap_or(default.format);
+        self.empty_text = config
+            .get("empty_text")
+            .unescape()
+            .unwrap_or(default.empty_text);
+        self.group_dots = config
+            .get("group_dots")
+            .and_then(|v| v.into_bool())
+            .unwrap_or(default.group_dots);
+        self.group_dot = config
+            .get("group_dot")
+            .and_then(|v| v.clone())
+            .unwrap_or(default.group_dot);
+        self.group_dot_color = config
+            .get("group_dot_color")
+            .and_then(|v| v.into_color())
+            .unwrap_or(default.group_dot_color);
+        self.group_active_color = config
+            .get("group_active_color")
+            .and_then(|v| v.into_color())
+            .unwrap_or(default.group_active_color);
+    }
+
+    fn known_keys(&self) -> Vec<ConfigKey> {
+        let mut keys = vec![
+            ConfigKey::new(
+                "format",
+                Kind::Text,
+                Some("{{title}}{{#if group_total}} [{{group_index}}/{{group_total}}]{{/if}}"),
+            ),
+            ConfigKey::new("empty_text", Kind::Text, None),
+            ConfigKey::new("group_dots", Kind::Bool, Some("false")),
+            ConfigKey::new("group_dot", Kind::Text, Some("●")),
+            ConfigKey::new(
+                "group_dot_color",
+                Kind::Color,
+                Some("rgba(255, 255, 255, 0.4)"),
+            ),
+            ConfigKey::new("group_active_color", Kind::Color, Some("white")),
+        ];
+        keys.extend_from_slice(WindowTitle::KEYS);
+        keys
     }
 
     impl_on_click!();
+
+    fn handle_action(&mut self, action: &dyn Action) {
+        let Some(HyprWindowAction::FocusTab(target)) = action.downcast_ref() else {
+            return;
+        };
+        if let Some((current, _)) = self.window.as_ref().and_then(|w| w.group) {
+            focus_tab(current, *target);
+        }
+    }
+
+    fn collect_data(&mut self) -> Result<(), String> {
+        let client = Client::get_active()
+            .map_err(|e| format!("Failed to get the active window from hyprland: {e}"))?;
+        self.window = match client {
+            Some(client) => {
+                let workspace = Workspace::get_active().map_err(|e| {
+                    format!("Failed to get the active workspace from hyprland: {e}")
+                })?;
+                Some(ActiveWindow::new(client, workspace))
+            }
+            None => None,
+        };
+        Ok(())
+    }
+
+    fn text_content(&self, template: &Handlebars) -> Option<Result<String, String>> {
+        Some(self.text(template))
+    }
 }
 
-pub async fn update_window(sender: &mut Sender<Message>, title: Option<String>) {
+/// Fetch the active window and send it to the module
+pub async fn update_window(sender: &mut Sender<Message>) {
+    let window = match ActiveWindow::get().await {
+        Ok(window) => window,
+        Err(e) => {
+            eprintln!("[hyprland.window] {e}");
+            return;
+        }
+    };
     sender
         .send(Message::update(move |reg| {
-            reg.get_module_mut::<HyprWindowMod>().title = title
+            reg.get_module_mut::<HyprWindowMod>().window = window
         }))
         .await
         .unwrap_or_else(|err| {
-            eprintln!("Trying to send workspaces failed with err: {err}");
+            eprintln!("Trying to send the active window failed with err: {err}");
         });
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::modules::testing::Harness;
+
+    use super::*;
+
+    fn text(options: &[(&str, &str)], window: Option<ActiveWindow>) -> String {
+        let mut harness = Harness::new::<HyprWindowMod>(options);
+        harness.update(|m: &mut HyprWindowMod| m.window = window);
+        harness.text::<HyprWindowMod>()
+    }
+
+    fn window(title: &str) -> Option<ActiveWindow> {
+        Some(ActiveWindow {
+            title: title.to_string(),
+            class: "firefox".to_string(),
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn truncates_titles_between_graphemes() {
+        let options = [("max_length", "7")];
+        // `é` is an `e` followed by a combining acute accent
+        assert_eq!(
+            text(&options, window("Cafe\u{301} au lait")),
+            "Cafe\u{301}..."
+        );
+        assert_eq!(text(&options, window("Cafe\u{301}s")), "Cafe\u{301}s");
+        let options = [("max_length", "4")];
+        assert_eq!(text(&options, window("👨‍👩‍👧 family photos")), "👨‍👩‍👧...");
+    }
+
+    #[test]
+    fn shows_the_position_in_the_group() {
+        let window = window("Inbox").map(|window| ActiveWindow {
+            group: Some((2, 3)),
+            ..window
+        });
+        assert_eq!(text(&[], window), "Inbox [2/3]");
+    }
+
+    #[test]
+    fn shows_the_empty_text_without_a_window() {
+        assert_eq!(text(&[("empty_text", "Desktop")], None), "Desktop");
+        assert_eq!(text(&[], None), "");
+    }
+}
@@ -1,15 +1,23 @@
-use std::{any::TypeId, collections::HashMap, time::Duration};
+use std::{
+    any::TypeId,
+    collections::{HashMap, HashSet},
+    time::Duration,
+};
 
 use bar_rs_derive::Builder;
 use handlebars::Handlebars;
 use hyprland::{
-    data::{Workspace, Workspaces},
-    shared::{HyprData, HyprDataActive, HyprDataVec},
+    data::{Clients, Monitors, Workspace, Workspaces},
+    dispatch::{Dispatch, DispatchType, WindowIdentifier, WorkspaceIdentifierWithSpecial},
+    shared::{Address, HyprData, HyprDataActive, HyprDataVec},
 };
 use iced::{
-    widget::{container, rich_text, span},
-    Background, Border, Color, Element, Padding,
+    futures::{channel::mpsc::Sender, SinkExt},
+    mouse::{self, ScrollDelta},
+    widget::{button, container, rich_text, span},
+    Background, Border, Color, Element, Event, Padding,
 };
+use serde_json::json;
 use tokio::time::sleep;
 
 use crate::{
@@ -17,21 +25,52 @@ use crate::{
         anchor::BarAnchor,
         module_config::{LocalModuleConfig, ModuleConfigOverride},
         parse::StringExt,
-        popup_config::PopupConfig,
+        popup_config::{PopupConfig, PopupConfigOverride},
+        scale::Scale,
+        schema::{ConfigKey, Kind},
     },
     fill::FillExt,
+    helpers::{
+        scroll::{ScrollDirection, WorkspaceScroll},
+        window::WindowTitle,
+        workspaces::{
+            OverviewWindow, OverviewWorkspace, WorkspaceOverview, WorkspaceState, WorkspaceStyles,
+            ICON_KEYS,
+        },
+        UnEscapeString,
+    },
     impl_on_click, impl_wrapper,
     list::list,
     listeners::hyprland::HyprListener,
-    modules::{require_listener, Module},
-    Message, NERD_FONT,
+    modules::{click_message, require_listener, Action, Module},
+    Message,
 };
 
+/// A window as listed in the workspace overview
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WorkspaceWindow {
+    pub address: Address,
+    pub class: String,
+    pub title: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OpenWorkspace {
+    pub id: i32,
+    pub name: String,
+    pub fullscreen: bool,
+    pub windows: u16,
+    pub clients: Vec<WorkspaceWindow>,
+}
+
 #[derive(Debug, Builder)]
 pub struct HyprWorkspaceMod {
     pub active: usize,
-    // (Name, Fullscreen state)
-    pub open: Vec<(String, bool)>,
+    pub open: Vec<OpenWorkspace>,
+    /// The ids of the workspaces with a window which demands attention, until they are focused
+    pub urgent: HashSet<i32>,
+    /// The special workspace shown on the focused monitor, without the `special:` prefix
+    pub special: Option<String>,
     cfg_override: ModuleConfigOverride,
     icon_padding: Padding,
     icon_background: Option<Background>,
@@ -41,6 +80,15 @@ pub struct HyprWorkspaceMod {
     active_color: Color,
     active_background: Option<Background>,
     active_icon_border: Border,
+    styles: WorkspaceStyles,
+    format: String,
+    /// Whether to show `special_icon` while a special workspace is shown
+    show_special: bool,
+    special_icon: String,
+    special_color: Option<Color>,
+    special_background: Option<Background>,
+    scroll: WorkspaceScroll,
+    overview: WorkspaceOverview,
 }
 
 impl Default for HyprWorkspaceMod {
@@ -48,6 +96,8 @@ impl Default for HyprWorkspaceMod {
         Self {
             active: 0,
             open: vec![],
+            urgent: HashSet::new(),
+            special: None,
             cfg_override: ModuleConfigOverride::default(),
             icon_padding: Padding::default(),
             icon_background: None,
@@ -57,10 +107,129 @@ impl Default for HyprWorkspaceMod {
             active_color: Color::WHITE,
             active_background: None,
             active_icon_border: Border::default().rounded(8),
+            styles: WorkspaceStyles::default(),
+            format: "{{name}}".to_string(),
+            show_special: false,
+            special_icon: "S".to_string(),
+            special_color: None,
+            special_background: None,
+            scroll: WorkspaceScroll::default(),
+            overview: WorkspaceOverview::default(),
+        }
+    }
+}
+
+impl HyprWorkspaceMod {
+    fn state(&self, idx: usize, ws: &OpenWorkspace) -> WorkspaceState {
+        WorkspaceState::new(
+            idx == self.active,
+            self.urgent.contains(&ws.id),
+            ws.windows > 0,
+        )
+    }
+
+    fn label(&self, idx: usize, ws: &OpenWorkspace, handlebars: &Handlebars) -> String {
+        handlebars
+            .render_template(
+                &self.format,
+                &json!({
+                    "name": ws.name,
+                    "id": ws.id,
+                    "windows": ws.windows,
+                    "fullscreen": ws.fullscreen,
+                    "state": self.state(idx, ws).as_str(),
+                }),
+            )
+            .unwrap_or_else(|e| {
+                eprintln!("Failed to render hyprland.workspaces: {e}");
+                ws.name.clone()
+            })
+    }
+
+    /// Sets the workspaces, the focused one isn't urgent anymore
+    pub fn set_workspaces(
+        &mut self,
+        (active, open): (usize, Vec<OpenWorkspace>),
+        special: Option<String>,
+    ) {
+        if let Some(ws) = open.get(active) {
+            self.urgent.remove(&ws.id);
+        }
+        self.urgent.retain(|id| open.iter().any(|ws| ws.id == *id));
+        self.active = active;
+        self.open = open;
+        self.special = special;
+    }
+
+    /// The indicator of the shown special workspace, clicking it hides the workspace
+    fn special_indicator<'a>(
+        &'a self,
+        config: &LocalModuleConfig,
+        anchor: &BarAnchor,
+    ) -> Option<Element<'a, Message>> {
+        let special = self.special.as_ref().filter(|_| self.show_special)?;
+        let span = span(self.cfg_override.icon(config, &self.special_icon))
+            .padding(self.active_padding.unwrap_or(self.icon_padding))
+            .size(self.cfg_override.icon_size.unwrap_or(config.icon_size))
+            .color(self.special_color.unwrap_or(self.active_color))
+            .background_maybe(self.special_background.or(self.active_background))
+            .border(self.active_icon_border)
+            .font(config.icon_font);
+        Some(
+            container(
+                button(rich_text![span].fill(anchor))
+                    .padding(0)
+                    .style(|_, _| button::Style::default())
+                    .on_press(HyprWorkspaceAction::ToggleSpecial(special.clone()).as_message()),
+            )
+            .padding(self.cfg_override.icon_margin.unwrap_or(config.icon_margin))
+            .into(),
+        )
+    }
+
+    fn switch_by_scroll(&mut self, direction: ScrollDirection) {
+        if let Some(ws) = self
+            .scroll
+            .target(direction, self.active, self.open.len())
+            .and_then(|target| self.open.get(target))
+        {
+            switch_to(ws);
         }
     }
 }
 
+/// Switches to the workspace, special workspaces are toggled instead
+fn switch_to(ws: &OpenWorkspace) {
+    match ws.name.strip_prefix("special:") {
+        Some(special) => Dispatch::call(DispatchType::ToggleSpecialWorkspace(Some(
+            special.to_string(),
+        ))),
+        None => Dispatch::call(DispatchType::Workspace(WorkspaceIdentifierWithSpecial::Id(
+            ws.id,
+        ))),
+    }
+    .unwrap_or_else(|e| eprintln!("Failed to switch to workspace {}: {e}", ws.name));
+}
+
+#[derive(Debug, Clone)]
+pub enum HyprWorkspaceAction {
+    /// Switch to the workspace with this id
+    Switch(i32),
+    /// Show or hide the special workspace with this name
+    ToggleSpecial(String),
+    FocusWindow(Address),
+}
+
+impl Action for HyprWorkspaceAction {
+    fn as_message(&self) -> Message {
+        let action = self.clone();
+        Message::update(move |reg| {
+            reg.get_module_mut::<HyprWorkspaceMod>()
+                .handle_action(&action)
+        })
+    }
+}
+
 impl Module for HyprWorkspaceMod {
     fn name(&self) -> String {
         "hyprland.workspaces".to_string()
@@ -69,40 +238,140 @@ impl Module for HyprWorkspaceMod {
     fn view(
         &self,
         config: &LocalModuleConfig,
-        _popup_config: &PopupConfig,
+        popup_config: &PopupConfig,
         anchor: &BarAnchor,
-        _handlebars: &Handlebars,
+        handlebars: &Handlebars,
     ) -> Element<Message> {
-        list(
+        let rows = self.open.len() + self.open.iter().map(|ws| ws.clients.len()).sum::<usize>();
+        let popup = Message::popup::<Self>(
+            self.overview.width(popup_config),
+            self.overview.height(popup_config, rows),
             anchor,
-            self.open.iter().enumerate().map(|(id, (ws, _))| {
-                let mut span = span(ws)
-                    .padding(self.icon_padding)
-                    .size(self.cfg_override.icon_size.unwrap_or(config.icon_size))
-                    .color(self.cfg_override.icon_color.unwrap_or(config.icon_color))
-                    .background_maybe(self.icon_background)
-                    .border(self.icon_border)
-                    .font(NERD_FONT);
-                if id == self.active {
-                    span = span
-                        .padding(self.active_padding.unwrap_or(self.icon_padding))
-                        .size(self.active_size)
-                        .color(self.active_color)
-                        .background_maybe(self.active_background)
-                        .border(self.active_icon_border);
-                }
-                container(rich_text![span].fill(anchor))
+        );
+        // Right clicks open the overview, unless `on_click_right` is set
+        let left = click_message(self, mouse::Button::Left, config);
+        let middle = click_message(self, mouse::Button::Middle, config);
+        let right = click_message(self, mouse::Button::Right, config);
+        let workspaces = list(
+            anchor,
+            self.open
+                .iter()
+                .enumerate()
+                .map(|(id, ws)| -> Element<Message> {
+                    let style = self.styles.get(self.state(id, ws));
+                    let mut span =
+                        span(self.label(id, ws, handlebars))
+                            .padding(self.icon_padding)
+                            .size(self.cfg_override.icon_size.unwrap_or(config.icon_size))
+                            .color(style.color.unwrap_or(
+                                self.cfg_override.icon_color.unwrap_or(config.icon_color),
+                            ))
+                            .background_maybe(style.background.or(self.icon_background))
+                            .border(self.icon_border)
+                            .font(config.icon_font);
+                    if id == self.active {
+                        span = span
+                            .padding(self.active_padding.unwrap_or(self.icon_padding))
+                            .size(self.active_size)
+                            .color(self.active_color)
+                            .background_maybe(self.active_background)
+                            .border(self.active_icon_border);
+                    }
+                    container(
+                        button(rich_text![span].fill(anchor))
+                            .padding(0)
+                            .style(|_, _| button::Style::default())
+                            .on_press(HyprWorkspaceAction::Switch(ws.id).as_message()),
+                    )
                     .padding(self.cfg_override.icon_margin.unwrap_or(config.icon_margin))
                     .into()
-            }),
+                })
+                .chain(self.special_indicator(config, anchor)),
         )
         .padding(self.cfg_override.padding.unwrap_or(config.padding))
-        .spacing(self.cfg_override.spacing.unwrap_or(config.spacing))
-        .into()
+        .spacing(self.cfg_override.spacing.unwrap_or(config.spacing));
+        crate::button::button(workspaces)
+            .on_event_try(
+                move |event, layout, cursor, clipboard, viewport| match event {
+                    Event::Mouse(mouse::Event::ButtonReleased(mouse::Button::Right)) => right
+                        .clone()
+                        .or_else(|| Some(popup(event, layout, cursor, clipboard, viewport))),
+                    Event::Mouse(mouse::Event::ButtonReleased(mouse::Button::Middle)) => {
+                        middle.clone()
+                    }
+                    _ => left.clone(),
+                },
+            )
+            .style(|_, _| button::Style::default())
+            .into()
     }
 
     impl_wrapper!();
 
+    fn popup_cfg_override(&self) -> Option<&PopupConfigOverride> {
+        Some(&self.overview.popup_cfg_override)
+    }
+
+    fn popup_view<'a>(
+        &'a self,
+        config: &'a PopupConfig,
+        template: &Handlebars,
+    ) -> Element<'a, Message> {
+        self.overview.view(
+            config,
+            self.open
+                .iter()
+                .enumerate()
+                .map(|(idx, ws)| OverviewWorkspace {
+                    label: self.label(idx, ws, template),
+                    state: self.state(idx, ws),
+                    windows: ws
+                        .clients
+                        .iter()
+                        .map(|client| OverviewWindow {
+                            app_id: Some(&client.class),
+                            title: &client.title,
+                            on_press: HyprWorkspaceAction::FocusWindow(client.address.clone())
+                                .as_message(),
+                        })
+                        .collect(),
+                })
+                .collect(),
+        )
+    }
+
+    fn handle_action(&mut self, action: &dyn Action) {
+        let Some(action) = action.downcast_ref::<HyprWorkspaceAction>() else {
+            return;
+        };
+        match action {
+            HyprWorkspaceAction::Switch(id) => {
+                // The workspace might be gone already
+                if let Some(ws) = self.open.iter().find(|ws| ws.id == *id) {
+                    switch_to(ws);
+                }
+            }
+            HyprWorkspaceAction::ToggleSpecial(name) => {
+                Dispatch::call(DispatchType::ToggleSpecialWorkspace(Some(name.clone())))
+                    .unwrap_or_else(|e| eprintln!("Failed to toggle special workspace {name}: {e}"))
+            }
+            HyprWorkspaceAction::FocusWindow(address) => {
+                // The window might have been closed while the overview was open
+                if !self
+                    .open
+                    .iter()
+                    .any(|ws| ws.clients.iter().any(|client| client.address == *address))
+                {
+                    return;
+                }
+                Dispatch::call(DispatchType::FocusWindow(WindowIdentifier::Address(
+                    address.clone(),
+                )))
+                .unwrap_or_else(|e| eprintln!("Failed to focus window {address}: {e}"));
+            }
+        }
+    }
+
     fn requires(&self) -> Vec<TypeId> {
         vec![require_listener::<HyprListener>()]
     }
@@ -110,15 +379,18 @@ impl Module for HyprWorkspaceMod {
     fn read_config(
         &mut self,
         config: &HashMap<String, Option<String>>,
-        _popup_config: &HashMap<String, Option<String>>,
+        popup_config: &HashMap<String, Option<String>>,
         _templates: &mut Handlebars,
+        scale: f32,
     ) {
         let default = Self::default();
-        self.cfg_override = config.into();
+        self.cfg_override = (config, scale).into();
+        self.overview.read_config(popup_config, scale);
         self.icon_padding = config
             .get("icon_padding")
             .and_then(|v| v.into_insets().map(|i| i.into()))
-            .unwrap_or(default.icon_padding);
+            .unwrap_or(default.icon_padding)
+            .scaled(scale);
         self.icon_background = config
             .get("icon_background")
             .map(|v| v.into_background())
@@ -138,15 +410,18 @@ impl Module for HyprWorkspaceMod {
             } else {
                 default.active_icon_border
             }
-        };
+        }
+        .scaled(scale);
         self.active_padding = config
             .get("active_padding")
             .map(|v| v.into_insets().map(|i| i.into()))
-            .unwrap_or(default.active_padding);
+            .unwrap_or(default.active_padding)
+            .scaled(scale);
         self.active_size = config
             .get("active_size")
             .and_then(|v| v.into_float())
-            .unwrap_or(default.active_size);
+            .unwrap_or(default.active_size)
+            .scaled(scale);
         self.active_color = config
             .get("active_color")
             .and_then(|v| v.into_color())
@@ -174,13 +449,69 @@ impl Module for HyprWorkspaceMod {
             } else {
                 default.active_icon_border
             }
-        };
+        }
+        .scaled(scale);
+        self.styles.read_config(config);
+        self.show_special = config
+            .get("show_special")
+            .and_then(|v| v.into_bool())
+            .unwrap_or(default.show_special);
+        self.special_icon = config
+            .get("special_icon")
+            .and_then(|v| v.clone())
+            .unwrap_or(default.special_icon);
+        self.special_color = config
+            .get("special_color")
+            .and_then(|v| v.into_color())
+            .or(default.special_color);
+        self.special_background = config
+            .get("special_background")
+            .map(|v| v.into_background())
+            .unwrap_or(default.special_background);
+        self.format = config.get("format").unescape().unwrap_or(default.format);
+        self.scroll.read_config(config);
+    }
+
+    fn known_keys(&self) -> Vec<ConfigKey> {
+        let mut keys = vec![
+            ConfigKey::new("show_special", Kind::Bool, Some("false")),
+            ConfigKey::new("special_icon", Kind::Text, Some("S")),
+            ConfigKey::new("special_color", Kind::Color, None),
+            ConfigKey::new("special_background", Kind::Color, None),
+            ConfigKey::new("format", Kind::Text, Some("{{name}}")),
+        ];
+        keys.extend_from_slice(ICON_KEYS);
+        keys.extend_from_slice(WorkspaceStyles::KEYS);
+        keys.extend_from_slice(WorkspaceScroll::KEYS);
+        keys
+    }
+
+    fn known_popup_keys(&self) -> Vec<ConfigKey> {
+        [WorkspaceOverview::KEYS, WindowTitle::KEYS].concat()
     }
 
     impl_on_click!();
+
+    fn on_scroll(&self, delta: ScrollDelta) -> Option<Message> {
+        let direction = ScrollDirection::from_delta(delta)?;
+        Some(Message::update(move |reg| {
+            reg.get_module_mut::<HyprWorkspaceMod>()
+                .switch_by_scroll(direction)
+        }))
+    }
+
+    fn text_content(&self, template: &Handlebars) -> Option<Result<String, String>> {
+        Some(Ok(self
+            .open
+            .iter()
+            .enumerate()
+            .map(|(idx, ws)| self.label(idx, ws, template))
+            .collect::<Vec<_>>()
+            .join(" ")))
+    }
 }
 
-pub async fn get_workspaces(active: Option<i32>) -> (usize, Vec<(String, bool)>) {
+pub async fn get_workspaces(active: Option<i32>) -> (usize, Vec<OpenWorkspace>) {
     // Sleep a bit, to reduce the probability that a nonexisting ws is still reported active
     sleep(Duration::from_millis(10)).await;
     let Ok(workspaces) = Workspaces::get_async().await else {
@@ -189,6 +520,13 @@ pub async fn get_workspaces(active: Option<i32>) -> (usize, Vec<(String, bool)>)
     };
     let mut open = workspaces.to_vec();
     open.sort_by(|a, b| a.id.cmp(&b.id));
+    let clients = match Clients::get_async().await {
+        Ok(clients) => clients.to_vec(),
+        Err(e) => {
+            eprintln!("[hyprland.workspaces] Failed to get Clients: {e}");
+            vec![]
+        }
+    };
     (
         open.iter()
             .position(|ws| {
@@ -198,7 +536,127 @@ pub async fn get_workspaces(active: Option<i32>) -> (usize, Vec<(String, bool)>)
             })
             .unwrap_or(0),
         open.into_iter()
-            .map(|ws| (ws.name, ws.fullscreen))
+            .map(|ws| OpenWorkspace {
+                id: ws.id,
+                name: ws.name,
+                fullscreen: ws.fullscreen,
+                windows: ws.windows,
+                clients: clients
+                    .iter()
+                    .filter(|client| client.workspace.id == ws.id)
+                    .map(|client| WorkspaceWindow {
+                        address: client.address.clone(),
+                        class: client.class.clone(),
+                        title: client.title.clone(),
+                    })
+                    .collect(),
+            })
             .collect(),
     )
 }
+
+/// Sends the workspaces, `active` is the id of the focused workspace if it's known already
+pub async fn update_workspaces(sender: &mut Sender<Message>, active: Option<i32>) {
+    let workspaces = get_workspaces(active).await;
+    // The name is empty if no special workspace is shown
+    let special = Monitors::get_async()
+        .await
+        .ok()
+        .and_then(|monitors| monitors.to_vec().into_iter().find(|m| m.focused))
+        .map(|monitor| monitor.special_workspace.name)
+        .filter(|name| !name.is_empty())
+        .map(|name| name.strip_prefix("special:").unwrap_or(&name).to_string());
+    sender
+        .send(Message::update(move |reg| {
+            reg.get_module_mut::<HyprWorkspaceMod>()
+                .set_workspaces(workspaces, special)
+        }))
+        .await
+        .unwrap_or_else(|err| {
+            eprintln!("Trying to send workspaces failed with err: {err}");
+        });
+}
+
+/// Marks the workspace of the window as urgent, unless it's focused
+pub async fn set_urgent(sender: &mut Sender<Message>, address: Address) {
+    let Ok(clients) = Clients::get_async().await else {
+        eprintln!("[hyprland.workspaces] Failed to get Clients!");
+        return;
+    };
+    let Some(id) = clients
+        .to_vec()
+        .into_iter()
+        .find(|client| client.address == address)
+        .map(|client| client.workspace.id)
+    else {
+        return;
+    };
+    sender
+        .send(Message::update(move |reg| {
+            let ws = reg.get_module_mut::<HyprWorkspaceMod>();
+            if ws
+                .open
+                .get(ws.active)
+                .map_or(true, |active| active.id != id)
+            {
+                ws.urgent.insert(id);
+            }
+        }))
+        .await
+        .unwrap_or_else(|err| {
+            eprintln!("Trying to send an urgent workspace failed with err: {err}");
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::modules::testing::Harness;
+
+    use super::*;
+
+    fn workspaces(count: i32) -> Vec<OpenWorkspace> {
+        (1..=count)
+            .map(|id| OpenWorkspace {
+                id,
+                name: id.to_string(),
+                fullscreen: false,
+                windows: (id % 2) as u16,
+                clients: vec![],
+            })
+            .collect()
+    }
+
+    #[test]
+    fn renders_many_workspaces() {
+        let mut harness = Harness::new::<HyprWorkspaceMod>(&[("format", "{{id}}:{{state}}")]);
+        harness.update(|m: &mut HyprWorkspaceMod| m.set_workspaces((119, workspaces(150)), None));
+        let text = harness.text::<HyprWorkspaceMod>();
+        let labels = text.split(' ').collect::<Vec<_>>();
+        assert_eq!(labels.len(), 150);
+        assert_eq!(labels[0], "1:occupied");
+        assert_eq!(labels[1], "2:empty");
+        assert_eq!(labels[119], "120:focused");
+        assert_eq!(labels[149], "150:empty");
+    }
+
+    #[test]
+    fn focusing_a_workspace_clears_its_urgency() {
+        let mut harness = Harness::new::<HyprWorkspaceMod>(&[("format", "{{state}}")]);
+        harness.update(|m: &mut HyprWorkspaceMod| {
+            m.urgent = HashSet::from([5, 121, 200]);
+            m.set_workspaces((0, workspaces(150)), None);
+        });
+        // Workspace 200 doesn't exist anymore
+        assert_eq!(
+            harness.module::<HyprWorkspaceMod>().urgent,
+            HashSet::from([5, 121])
+        );
+        harness.update(|m: &mut HyprWorkspaceMod| m.set_workspaces((120, workspaces(150)), None));
+        let m = harness.module::<HyprWorkspaceMod>();
+        assert_eq!(m.urgent, HashSet::from([5]));
+        assert_eq!(
+            harness.text::<HyprWorkspaceMod>().split(' ').nth(4),
+            Some("urgent")
+        );
+    }
+}
@@ -0,0 +1,587 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    time::Duration,
+};
+
+use bar_rs_derive::Builder;
+use handlebars::Handlebars;
+use iced::widget::{container, text};
+use iced::{
+    futures::{channel::mpsc::Sender, stream::BoxStream, SinkExt, StreamExt},
+    stream, Element, Subscription,
+};
+use serde_json::Value;
+use tokio::{
+    process::Command,
+    select,
+    sync::mpsc::{self, UnboundedReceiver, UnboundedSender},
+    time::sleep,
+};
+use zbus::{dbus_proxy, Connection, ConnectionBuilder};
+
+use crate::bar_text::bar_text;
+use crate::config::popup_config::PopupConfig;
+use crate::helpers::{
+    backoff::Backoff,
+    status::{send_status, ModuleStatus},
+};
+use crate::{
+    config::{
+        anchor::BarAnchor,
+        module_config::{LocalModuleConfig, ModuleConfigOverride},
+        schema::{ConfigKey, Kind},
+    },
+    fill::FillExt,
+    impl_on_click, impl_status, impl_wrapper, Message,
+};
+
+use super::{Action, Module, OnClickAction};
+
+/// fcitx5 doesn't signal when the input method changes, so it's read this often
+const FCITX5_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+#[dbus_proxy(
+    interface = "org.fcitx.Fcitx.Controller1",
+    default_service = "org.fcitx.Fcitx5",
+    default_path = "/controller"
+)]
+trait Fcitx5Controller {
+    /// 0 without a focused input context, 1 while the keyboard layout is used and 2 while an
+    /// input method is active
+    fn state(&self) -> zbus::Result<i32>;
+    fn current_input_method(&self) -> zbus::Result<String>;
+    fn current_input_method_group(&self) -> zbus::Result<String>;
+    /// The default layout and the input methods (with their layouts) of the group
+    fn input_method_group_info(&self, name: &str) -> zbus::Result<(String, Vec<(String, String)>)>;
+    /// Switches between the keyboard layout and the last active input method
+    fn toggle(&self) -> zbus::Result<()>;
+    #[dbus_proxy(name = "SetCurrentIM")]
+    fn set_current_im(&self, name: &str) -> zbus::Result<()>;
+}
+
+#[dbus_proxy(
+    interface = "org.freedesktop.IBus",
+    default_service = "org.freedesktop.IBus",
+    default_path = "/org/freedesktop/IBus"
+)]
+trait IBus {
+    fn set_global_engine(&self, engine_name: &str) -> zbus::Result<()>;
+    #[dbus_proxy(signal)]
+    fn global_engine_changed(&self, engine_name: String) -> zbus::Result<()>;
+}
+
+/// The input method framework which is running
+#[derive(Debug, Clone)]
+enum Ime {
+    Fcitx5(Fcitx5ControllerProxy<'static>),
+    /// IBus runs its own bus, whose address `ibus address` prints
+    IBus(IBusProxy<'static>),
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+struct ImeState {
+    /// `fcitx5` or `ibus`
+    framework: &'static str,
+    /// Whether an input method is active, instead of a plain keyboard layout
+    enabled: bool,
+    /// The name of the active engine, like `mozc` or `keyboard-us`
+    engine: String,
+    /// The engines the user switches between, in their configured order
+    engines: Vec<String>,
+}
+
+impl Ime {
+    /// Connects to fcitx5, or to IBus if fcitx5 isn't running
+    async fn connect() -> Result<Self, String> {
+        let fcitx5 = async {
+            let connection = Connection::session().await?;
+            let proxy = Fcitx5ControllerProxy::new(&connection).await?;
+            proxy.state().await?;
+            zbus::Result::Ok(proxy)
+        };
+        let fcitx5_error = match fcitx5.await {
+            Ok(proxy) => return Ok(Ime::Fcitx5(proxy)),
+            Err(e) => e,
+        };
+        let ibus = async {
+            let address = run("ibus", &["address"]).await?;
+            let connection = ConnectionBuilder::address(address.trim())
+                .map_err(|e| e.to_string())?
+                .build()
+                .await
+                .map_err(|e| e.to_string())?;
+            IBusProxy::new(&connection).await.map_err(|e| e.to_string())
+        };
+        match ibus.await {
+            Ok(proxy) => Ok(Ime::IBus(proxy)),
+            Err(ibus_error) => Err(format!(
+                "Neither fcitx5 ({fcitx5_error}) nor IBus ({ibus_error}) is running"
+            )),
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Ime::Fcitx5(_) => "fcitx5",
+            Ime::IBus(_) => "ibus",
+        }
+    }
+
+    async fn state(&self) -> Result<ImeState, String> {
+        match self {
+            Ime::Fcitx5(proxy) => {
+                let read = async {
+                    let group = proxy.current_input_method_group().await?;
+                    zbus::Result::Ok(ImeState {
+                        framework: self.name(),
+                        enabled: proxy.state().await? == 2,
+                        engine: proxy.current_input_method().await?,
+                        engines: proxy
+                            .input_method_group_info(&group)
+                            .await?
+                            .1
+                            .into_iter()
+                            .map(|(name, _layout)| name)
+                            .collect(),
+                    })
+                };
+                read.await.map_err(|e| e.to_string())
+            }
+            Ime::IBus(_) => {
+                let engine = run("ibus", &["engine"]).await?.trim().to_string();
+                let engines = run(
+                    "gsettings",
+                    &["get", "org.freedesktop.ibus.general", "preload-engines"],
+                )
+                .await
+                .map(|list| parse_gvariant_list(&list))
+                .unwrap_or_default();
+                Ok(ImeState {
+                    framework: self.name(),
+                    enabled: !engine.starts_with("xkb:"),
+                    engine,
+                    engines,
+                })
+            }
+        }
+    }
+
+    async fn set_engine(&self, engine: &str) -> Result<(), String> {
+        match self {
+            Ime::Fcitx5(proxy) => proxy.set_current_im(engine).await,
+            Ime::IBus(proxy) => proxy.set_global_engine(engine).await,
+        }
+        .map_err(|e| e.to_string())
+    }
+
+    async fn handle(
+        &self,
+        action: ImeAction,
+        state: &ImeState,
+        previous: Option<&str>,
+    ) -> Result<(), String> {
+        match (action, self) {
+            (ImeAction::Toggle, Ime::Fcitx5(proxy)) => {
+                proxy.toggle().await.map_err(|e| e.to_string())
+            }
+            // IBus has no inactive state, so toggling switches back to the previous engine
+            (ImeAction::Toggle, Ime::IBus(_)) => match previous {
+                Some(engine) => self.set_engine(engine).await,
+                None => self.set_engine_after(state).await,
+            },
+            (ImeAction::Next, _) => self.set_engine_after(state).await,
+        }
+    }
+
+    async fn set_engine_after(&self, state: &ImeState) -> Result<(), String> {
+        match next_engine(&state.engines, &state.engine) {
+            Some(engine) => self.set_engine(engine).await,
+            None => Err("there is no other engine to switch to".to_string()),
+        }
+    }
+
+    /// Yields whenever the engine might have changed
+    async fn changes(&self) -> Result<BoxStream<'static, ()>, String> {
+        match self {
+            Ime::Fcitx5(_) => Ok(iced::futures::stream::repeat(())
+                .then(|()| sleep(FCITX5_POLL_INTERVAL))
+                .boxed()),
+            Ime::IBus(proxy) => Ok(proxy
+                .receive_global_engine_changed()
+                .await
+                .map_err(|e| e.to_string())?
+                .map(|_| ())
+                .boxed()),
+        }
+    }
+}
+
+async fn run(program: &str, args: &[&str]) -> Result<String, String> {
+    let output = Command::new(program)
+        .args(args)
+        .output()
+        .await
+        .map_err(|e| format!("Failed to run {program}: {e}"))?;
+    match output.status.success() {
+        true => Ok(String::from_utf8_lossy(&output.stdout).to_string()),
+        false => Err(format!(
+            "{program} {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        )),
+    }
+}
+
+/// Reads a list of strings as `gsettings` prints it, like `['xkb:us::eng', 'mozc-jp']`
+fn parse_gvariant_list(list: &str) -> Vec<String> {
+    list.trim()
+        .trim_start_matches("@as")
+        .trim()
+        .trim_start_matches('[')
+        .trim_end_matches(']')
+        .split(',')
+        .map(|item| item.trim().trim_matches('\'').to_string())
+        .filter(|item| !item.is_empty())
+        .collect()
+}
+
+/// The engine after `current`, wrapping around
+fn next_engine<'a>(engines: &'a [String], current: &str) -> Option<&'a String> {
+    let next = engines
+        .iter()
+        .position(|engine| engine == current)
+        .map_or(0, |index| index + 1);
+    engines
+        .get(next % engines.len().max(1))
+        .filter(|engine| *engine != current)
+}
+
+/// A short label for engines without a configured one: the layout of keyboard layouts
+/// (`keyboard-us` of fcitx5 and `xkb:us::eng` of IBus are both `us`), the name otherwise
+fn default_label(engine: &str) -> &str {
+    if let Some(layout) = engine.strip_prefix("keyboard-") {
+        return layout;
+    }
+    match engine.strip_prefix("xkb:") {
+        Some(xkb) => xkb.split(':').next().unwrap_or(xkb),
+        None => engine,
+    }
+}
+
+#[derive(Debug, Builder)]
+pub struct ImeMod {
+    cfg_override: ModuleConfigOverride,
+    /// `None` until the state was read from the input method framework
+    state: Option<ImeState>,
+    icon_enabled: String,
+    icon_disabled: String,
+    /// Labels by engine, set by `label_{engine}`
+    labels: HashMap<String, String>,
+    /// Toggles the input method on left click and switches to the next engine on right click
+    default_actions: OnClickAction,
+    /// Passes actions to the subscription
+    actions: Option<UnboundedSender<ImeAction>>,
+    status: ModuleStatus,
+}
+
+impl Default for ImeMod {
+    fn default() -> Self {
+        Self {
+            cfg_override: Default::default(),
+            state: None,
+            icon_enabled: "󰗊".to_string(),
+            icon_disabled: "󰌌".to_string(),
+            labels: HashMap::new(),
+            default_actions: OnClickAction {
+                left: Some(Box::new(ImeAction::Toggle)),
+                right: Some(Box::new(ImeAction::Next)),
+                ..Default::default()
+            },
+            actions: None,
+            status: ModuleStatus::Ok,
+        }
+    }
+}
+
+impl ImeMod {
+    fn icon(&self) -> &str {
+        match self.state.as_ref().is_some_and(|state| state.enabled) {
+            true => &self.icon_enabled,
+            false => &self.icon_disabled,
+        }
+    }
+
+    fn label(&self) -> &str {
+        let Some(state) = &self.state else {
+            return "";
+        };
+        self.labels
+            .get(&state.engine)
+            .map(|label| label.as_str())
+            .unwrap_or(default_label(&state.engine))
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum ImeAction {
+    /// Switches between the keyboard layout and the input method
+    Toggle,
+    /// Switches to the next engine
+    Next,
+}
+
+impl Action for ImeAction {
+    fn as_message(&self) -> Message {
+        let action = *self;
+        Message::update(move |reg| reg.get_module_mut::<ImeMod>().handle_action(&action))
+    }
+}
+
+impl Module for ImeMod {
+    fn name(&self) -> String {
+        "ime".to_string()
+    }
+
+    fn active(&self) -> bool {
+        self.state.is_some()
+    }
+
+    impl_status!();
+
+    fn view(
+        &self,
+        config: &LocalModuleConfig,
+        _popup_config: &PopupConfig,
+        anchor: &BarAnchor,
+        _handlebars: &Handlebars,
+    ) -> Element<Message> {
+        list![
+            anchor,
+            container(
+                text(self.cfg_override.icon(config, self.icon()))
+                    .fill(anchor)
+                    .size(self.cfg_override.icon_size.unwrap_or(config.icon_size))
+                    .color(self.cfg_override.icon_color.unwrap_or(config.icon_color))
+                    .font(config.icon_font)
+            )
+            .padding(self.cfg_override.icon_margin.unwrap_or(config.icon_margin)),
+            container(
+                bar_text(
+                    self.label(),
+                    anchor,
+                    self.cfg_override.vertical_text(config, anchor)
+                )
+                .size(self.cfg_override.font_size.unwrap_or(config.font_size))
+                .font(self.cfg_override.text_font.unwrap_or(config.text_font))
+                .color(self.cfg_override.text_color.unwrap_or(config.text_color))
+            )
+            .padding(self.cfg_override.text_margin.unwrap_or(config.text_margin)),
+        ]
+        .spacing(self.cfg_override.spacing.unwrap_or(config.spacing))
+        .into()
+    }
+
+    impl_wrapper!();
+
+    fn read_config(
+        &mut self,
+        config: &HashMap<String, Option<String>>,
+        _popup_config: &HashMap<String, Option<String>>,
+        _templates: &mut Handlebars,
+        scale: f32,
+    ) {
+        let default = Self::default();
+        self.cfg_override = (config, scale).into();
+        self.icon_enabled = config
+            .get("icon_enabled")
+            .and_then(|v| v.clone())
+            .unwrap_or(default.icon_enabled);
+        self.icon_disabled = config
+            .get("icon_disabled")
+            .and_then(|v| v.clone())
+            .unwrap_or(default.icon_disabled);
+        self.labels = config
+            .iter()
+            .filter_map(|(key, value)| {
+                let engine = key.strip_prefix("label_")?;
+                Some((engine.to_string(), value.clone()?))
+            })
+            .collect();
+    }
+
+    fn known_keys(&self) -> Vec<ConfigKey> {
+        vec![
+            ConfigKey::new("icon_enabled", Kind::Text, Some("󰗊")),
+            ConfigKey::new("icon_disabled", Kind::Text, Some("󰌌")),
+            ConfigKey::new("label_*", Kind::Text, None),
+        ]
+    }
+
+    impl_on_click!();
+
+    fn default_action(&self) -> Option<&OnClickAction> {
+        Some(&self.default_actions)
+    }
+
+    fn handle_action(&mut self, action: &dyn Action) {
+        let Some(action) = action.downcast_ref::<ImeAction>() else {
+            return;
+        };
+        if let Some(Err(_)) = self.actions.as_ref().map(|sender| sender.send(*action)) {
+            eprintln!("Failed to switch the input method: the subscription is gone");
+        }
+    }
+
+    fn collect_data(&mut self) -> Result<(), String> {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .map_err(|e| e.to_string())?;
+        self.state = Some(runtime.block_on(async { Ime::connect().await?.state().await })?);
+        Ok(())
+    }
+
+    fn text_content(&self, _template: &Handlebars) -> Option<Result<String, String>> {
+        self.state
+            .as_ref()
+            .map(|_| Ok(format!("{} {}", self.icon(), self.label())))
+    }
+
+    fn variables(&self) -> BTreeMap<String, Value> {
+        let Some(state) = &self.state else {
+            return BTreeMap::new();
+        };
+        BTreeMap::from([
+            ("framework".to_string(), state.framework.into()),
+            ("engine".to_string(), state.engine.clone().into()),
+            ("label".to_string(), self.label().into()),
+            ("enabled".to_string(), state.enabled.into()),
+        ])
+    }
+
+    fn subscription(&self) -> Option<Subscription<Message>> {
+        Some(Subscription::run(|| {
+            stream::channel(1, |mut sender| async move {
+                let (action_sx, mut action_rx) = mpsc::unbounded_channel();
+                if sender
+                    .send(Message::update(move |reg| {
+                        reg.get_module_mut::<ImeMod>().actions = Some(action_sx)
+                    }))
+                    .await
+                    .is_err()
+                {
+                    return;
+                }
+                // The input method framework may be started after the bar, or restarted
+                let mut backoff = Backoff::default();
+                while let Err(e) = watch_ime(&mut sender, &mut action_rx, &mut backoff).await {
+                    if !send_status::<ImeMod>(&mut sender, ModuleStatus::Unavailable(e)).await {
+                        return;
+                    }
+                    backoff.wait().await;
+                }
+            })
+        }))
+    }
+}
+
+/// Follows the engine of the input method framework and switches it. Returns if the bar is
+/// closing.
+async fn watch_ime(
+    sender: &mut Sender<Message>,
+    actions: &mut UnboundedReceiver<ImeAction>,
+    backoff: &mut Backoff,
+) -> Result<(), String> {
+    let ime = Ime::connect().await?;
+    let mut changes = ime.changes().await?;
+    backoff.reset();
+    let mut state = ime.state().await?;
+    // The engine before the current one, which toggling switches back to with IBus
+    let mut previous: Option<String> = None;
+    loop {
+        let update = state.clone();
+        if sender
+            .send(Message::update(move |reg| {
+                let m = reg.get_module_mut::<ImeMod>();
+                m.state = Some(update);
+                m.set_status(ModuleStatus::Ok);
+            }))
+            .await
+            .is_err()
+        {
+            return Ok(());
+        }
+        // Wait for the state to actually change
+        loop {
+            select! {
+                Some(()) = changes.next() => {}
+                Some(action) = actions.recv() => {
+                    if let Err(e) = ime.handle(action, &state, previous.as_deref()).await {
+                        eprintln!("Failed to switch the input method of {}: {e}", ime.name());
+                    }
+                }
+                else => return Err(format!("{} stopped", ime.name())),
+            }
+            let new = ime.state().await?;
+            if new != state {
+                if new.engine != state.engine {
+                    previous = Some(state.engine.clone());
+                }
+                state = new;
+                break;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::modules::testing::Harness;
+
+    use super::*;
+
+    fn engines(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn parses_gsettings_lists() {
+        assert_eq!(
+            parse_gvariant_list("['xkb:us::eng', 'mozc-jp', 'hangul']\n"),
+            engines(&["xkb:us::eng", "mozc-jp", "hangul"])
+        );
+        assert!(parse_gvariant_list("@as []\n").is_empty());
+    }
+
+    #[test]
+    fn cycles_through_the_engines() {
+        let list = engines(&["keyboard-us", "mozc", "pinyin"]);
+        assert_eq!(next_engine(&list, "mozc").unwrap(), "pinyin");
+        assert_eq!(next_engine(&list, "pinyin").unwrap(), "keyboard-us");
+        // An engine outside of the group switches to the first one
+        assert_eq!(next_engine(&list, "anthy").unwrap(), "keyboard-us");
+        assert_eq!(next_engine(&engines(&["mozc"]), "mozc"), None);
+        assert_eq!(next_engine(&[], "mozc"), None);
+    }
+
+    #[test]
+    fn labels_engines() {
+        let mut harness = Harness::new::<ImeMod>(&[("label_mozc", "あ")]);
+        let show = |harness: &mut Harness, engine: &str, enabled: bool| {
+            let engine = engine.to_string();
+            harness.update(move |m: &mut ImeMod| {
+                m.state = Some(ImeState {
+                    engine,
+                    enabled,
+                    ..Default::default()
+                })
+            });
+        };
+        show(&mut harness, "mozc", true);
+        assert_eq!(harness.text::<ImeMod>(), "󰗊 あ");
+        show(&mut harness, "keyboard-us", false);
+        assert_eq!(harness.text::<ImeMod>(), "󰌌 us");
+        show(&mut harness, "xkb:de:nodeadkeys:ger", false);
+        assert_eq!(harness.text::<ImeMod>(), "󰌌 de");
+    }
+}
@@ -0,0 +1,377 @@
+use std::{
+    collections::HashMap,
+    fs::{self, File},
+    io::{self, Read},
+    mem::size_of,
+    thread,
+    time::Duration,
+};
+
+use bar_rs_derive::Builder;
+use handlebars::Handlebars;
+use iced::widget::{container, text};
+use iced::{futures::SinkExt, stream, Color, Element, Subscription};
+use tokio::{
+    sync::mpsc::{self, UnboundedSender},
+    time::sleep,
+};
+
+use crate::config::parse::StringExt;
+use crate::config::popup_config::PopupConfig;
+use crate::list::list;
+use crate::{
+    config::{
+        anchor::BarAnchor,
+        module_config::{LocalModuleConfig, ModuleConfigOverride},
+        schema::{ConfigKey, Kind},
+    },
+    fill::FillExt,
+    impl_on_click, impl_wrapper, Message,
+};
+
+use super::Module;
+
+const LEDS: &str = "/sys/class/leds";
+const INPUT: &str = "/sys/class/input";
+const EV_LED: u16 = 0x11;
+const LED_NUML: u16 = 0x00;
+const LED_CAPSL: u16 = 0x01;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LockKey {
+    Caps,
+    Num,
+}
+
+impl LockKey {
+    /// The suffix of the LED names in `/sys/class/leds`, e.g. `input3::capslock`
+    fn led_name(&self) -> &'static str {
+        match self {
+            LockKey::Caps => "::capslock",
+            LockKey::Num => "::numlock",
+        }
+    }
+}
+
+/// When to show the icon of a lock key
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+enum Visibility {
+    #[default]
+    Always,
+    Active,
+    Never,
+}
+
+const VISIBILITIES: &[&str] = &["always", "active", "never"];
+
+impl Visibility {
+    fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "always" => Some(Visibility::Always),
+            "active" => Some(Visibility::Active),
+            "never" => Some(Visibility::Never),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Lock {
+    /// `None` if no keyboard has an LED for this key
+    active: Option<bool>,
+    icon: String,
+    visibility: Visibility,
+}
+
+impl Lock {
+    fn new(icon: &str) -> Self {
+        Self {
+            active: None,
+            icon: icon.to_string(),
+            visibility: Visibility::default(),
+        }
+    }
+
+    fn visible(&self) -> bool {
+        match self.visibility {
+            Visibility::Always => self.active.is_some(),
+            Visibility::Active => self.active == Some(true),
+            Visibility::Never => false,
+        }
+    }
+
+    fn read_config(&mut self, config: &HashMap<String, Option<String>>, key: &str, default: Lock) {
+        self.icon = config
+            .get(&format!("icon_{key}"))
+            .and_then(|v| v.clone())
+            .unwrap_or(default.icon);
+        self.visibility = config
+            .get(&format!("show_{key}"))
+            .and_then(|v| v.as_deref())
+            .and_then(|v| {
+                Visibility::parse(v).or_else(|| {
+                    eprintln!(
+                        "Invalid value for show_{key}: {v}, expected always, active or never"
+                    );
+                    None
+                })
+            })
+            .unwrap_or(default.visibility);
+    }
+}
+
+#[derive(Debug, Builder)]
+pub struct LockKeysMod {
+    cfg_override: ModuleConfigOverride,
+    caps_lock: Lock,
+    num_lock: Lock,
+    inactive_color: Color,
+}
+
+impl Default for LockKeysMod {
+    fn default() -> Self {
+        Self {
+            cfg_override: Default::default(),
+            caps_lock: Lock::new("󰘲"),
+            num_lock: Lock::new("󰎠"),
+            inactive_color: Color::from_rgba(1., 1., 1., 0.3),
+        }
+    }
+}
+
+impl LockKeysMod {
+    fn lock_mut(&mut self, key: LockKey) -> &mut Lock {
+        match key {
+            LockKey::Caps => &mut self.caps_lock,
+            LockKey::Num => &mut self.num_lock,
+        }
+    }
+
+    fn locks(&self) -> impl Iterator<Item = &Lock> {
+        [&self.caps_lock, &self.num_lock].into_iter()
+    }
+}
+
+impl Module for LockKeysMod {
+    fn name(&self) -> String {
+        "lock_keys".to_string()
+    }
+
+    fn active(&self) -> bool {
+        self.locks().any(|lock| lock.visible())
+    }
+
+    fn view(
+        &self,
+        config: &LocalModuleConfig,
+        _popup_config: &PopupConfig,
+        anchor: &BarAnchor,
+        _handlebars: &Handlebars,
+    ) -> Element<Message> {
+        list(
+            anchor,
+            self.locks().filter(|lock| lock.visible()).map(|lock| {
+                container(
+                    text(self.cfg_override.icon(config, &lock.icon))
+                        .fill(anchor)
+                        .size(self.cfg_override.icon_size.unwrap_or(config.icon_size))
+                        .color(match lock.active {
+                            Some(true) => self.cfg_override.icon_color.unwrap_or(config.icon_color),
+                            _ => self.inactive_color,
+                        })
+                        .font(config.icon_font),
+                )
+                .padding(self.cfg_override.icon_margin.unwrap_or(config.icon_margin))
+                .into()
+            }),
+        )
+        .spacing(self.cfg_override.spacing.unwrap_or(config.spacing))
+        .into()
+    }
+
+    impl_wrapper!();
+
+    fn read_config(
+        &mut self,
+        config: &HashMap<String, Option<String>>,
+        _popup_config: &HashMap<String, Option<String>>,
+        _templates: &mut Handlebars,
+        scale: f32,
+    ) {
+        let default = Self::default();
+        self.cfg_override = (config, scale).into();
+        self.caps_lock
+            .read_config(config, "caps_lock", default.caps_lock);
+        self.num_lock
+            .read_config(config, "num_lock", default.num_lock);
+        self.inactive_color = config
+            .get("inactive_color")
+            .and_then(|v| v.into_color())
+            .unwrap_or(default.inactive_color);
+    }
+
+    fn known_keys(&self) -> Vec<ConfigKey> {
+        vec![
+            ConfigKey::new("icon_caps_lock", Kind::Text, Some("󰘲")),
+            ConfigKey::new("show_caps_lock", Kind::Choice(VISIBILITIES), Some("always")),
+            ConfigKey::new("icon_num_lock", Kind::Text, Some("󰎠")),
+            ConfigKey::new("show_num_lock", Kind::Choice(VISIBILITIES), Some("always")),
+            ConfigKey::new(
+                "inactive_color",
+                Kind::Color,
+                Some("rgba(255, 255, 255, 0.3)"),
+            ),
+        ]
+    }
+
+    impl_on_click!();
+
+    fn collect_data(&mut self) -> Result<(), String> {
+        self.caps_lock.active = read_led(LockKey::Caps)
+            .map_err(|e| format!("Failed to read the keyboard LEDs in {LEDS}: {e}"))?;
+        self.num_lock.active = read_led(LockKey::Num)
+            .map_err(|e| format!("Failed to read the keyboard LEDs in {LEDS}: {e}"))?;
+        Ok(())
+    }
+
+    fn text_content(&self, _template: &Handlebars) -> Option<Result<String, String>> {
+        Some(Ok(self
+            .locks()
+            .filter(|lock| lock.visible() && lock.active == Some(true))
+            .map(|lock| lock.icon.as_str())
+            .collect::<Vec<_>>()
+            .join(" ")))
+    }
+
+    fn urgent_updates(&self) -> bool {
+        true
+    }
+
+    fn subscription(&self) -> Option<Subscription<Message>> {
+        Some(Subscription::run(|| {
+            stream::channel(1, |mut sender| async move {
+                let mut state = [LockKey::Caps, LockKey::Num].map(|key| {
+                    (
+                        key,
+                        read_led(key).unwrap_or_else(|e| {
+                            eprintln!("Failed to read the keyboard LEDs in {LEDS}: {e}");
+                            None
+                        }),
+                    )
+                });
+                for (key, active) in state {
+                    if sender
+                        .send(Message::update(move |reg| {
+                            reg.get_module_mut::<LockKeysMod>().lock_mut(key).active = active
+                        }))
+                        .await
+                        .is_err()
+                    {
+                        return;
+                    }
+                }
+
+                let (sx, mut rx) = mpsc::unbounded_channel();
+                if watch_keyboards(sx) == 0 {
+                    eprintln!("Failed to open any keyboard in /dev/input (is your user in the `input` group?), polling {LEDS} instead");
+                    loop {
+                        sleep(Duration::from_secs(1)).await;
+                        for (key, active) in state.iter_mut() {
+                            let new = read_led(*key).ok().flatten();
+                            if new == *active {
+                                continue;
+                            }
+                            *active = new;
+                            let (key, active) = (*key, *active);
+                            if sender
+                                .send(Message::update(move |reg| {
+                                    reg.get_module_mut::<LockKeysMod>().lock_mut(key).active =
+                                        active
+                                }))
+                                .await
+                                .is_err()
+                            {
+                                return;
+                            }
+                        }
+                    }
+                }
+                while let Some((key, active)) = rx.recv().await {
+                    if sender
+                        .send(Message::update(move |reg| {
+                            reg.get_module_mut::<LockKeysMod>().lock_mut(key).active = Some(active)
+                        }))
+                        .await
+                        .is_err()
+                    {
+                        return;
+                    }
+                }
+            })
+        }))
+    }
+}
+
+/// Whether any keyboard LED of this key is lit, or `None` if there is none
+fn read_led(key: LockKey) -> io::Result<Option<bool>> {
+    let mut found = None;
+    for entry in fs::read_dir(LEDS)? {
+        let entry = entry?;
+        if !entry
+            .file_name()
+            .to_string_lossy()
+            .ends_with(key.led_name())
+        {
+            continue;
+        }
+        let lit = fs::read_to_string(entry.path().join("brightness"))?.trim() != "0";
+        found = Some(found.unwrap_or(false) || lit);
+    }
+    Ok(found)
+}
+
+/// Reads the LED events of all keyboards in a thread each, which the compositor sends whenever a
+/// lock key changes. Returns the number of keyboards being watched.
+fn watch_keyboards(sender: UnboundedSender<(LockKey, bool)>) -> usize {
+    let Ok(devices) = fs::read_dir(INPUT) else {
+        return 0;
+    };
+    devices
+        .flatten()
+        .filter(|device| {
+            device.file_name().to_string_lossy().starts_with("event")
+                // A bitmask of the supported LEDs
+                && fs::read_to_string(device.path().join("device/capabilities/led"))
+                    .is_ok_and(|leds| leds.trim() != "0")
+        })
+        .filter_map(|device| {
+            File::open(format!(
+                "/dev/input/{}",
+                device.file_name().to_string_lossy()
+            ))
+            .ok()
+        })
+        .map(|mut file| {
+            let sender = sender.clone();
+            thread::spawn(move || {
+                let mut buf = [0; size_of::<libc::input_event>()];
+                while file.read_exact(&mut buf).is_ok() {
+                    let event = unsafe {
+                        std::ptr::read_unaligned(buf.as_ptr() as *const libc::input_event)
+                    };
+                    if event.type_ != EV_LED {
+                        continue;
+                    }
+                    let key = match event.code {
+                        LED_CAPSL => LockKey::Caps,
+                        LED_NUML => LockKey::Num,
+                        _ => continue,
+                    };
+                    if sender.send((key, event.value != 0)).is_err() {
+                        return;
+                    }
+                }
+            });
+        })
+        .count()
+}
@@ -0,0 +1,374 @@
+use std::{
+    collections::HashMap,
+    env, fs, io,
+    path::{Path, PathBuf},
+    process::Stdio,
+    time::Duration,
+};
+
+use bar_rs_derive::Builder;
+use handlebars::Handlebars;
+use iced::widget::{container, text};
+use iced::{
+    border::Radius,
+    futures::{channel::mpsc::Sender, SinkExt},
+    stream, Background, Border, Color, Element, Subscription,
+};
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use tokio::{process::Command, select, sync::mpsc, time::sleep};
+
+use crate::config::parse::StringExt;
+use crate::config::popup_config::PopupConfig;
+use crate::list::list;
+use crate::{
+    config::{
+        anchor::BarAnchor,
+        module_config::{LocalModuleConfig, ModuleConfigOverride},
+        schema::{ConfigKey, Kind},
+    },
+    fill::FillExt,
+    impl_on_click, impl_wrapper, Message,
+};
+
+use super::{CommandAction, Module, OnClickAction};
+
+/// Where the number of unread mails comes from
+#[derive(Debug, Clone, Hash, PartialEq)]
+enum MailSource {
+    /// Unread mails are those in `new/` and those in `cur/` without the seen flag
+    Maildirs(Vec<PathBuf>),
+    /// A shell command printing the number of unread mails
+    Command(String),
+}
+
+impl Default for MailSource {
+    fn default() -> Self {
+        let home = env::var("HOME").unwrap_or_default();
+        MailSource::Maildirs(vec![Path::new(&home).join("Mail/INBOX")])
+    }
+}
+
+impl MailSource {
+    async fn unread(&self) -> Result<usize, String> {
+        match self {
+            MailSource::Maildirs(maildirs) => maildirs.iter().try_fold(0, |sum, maildir| {
+                count_unread(maildir)
+                    .map(|count| sum + count)
+                    .map_err(|e| format!("Failed to read maildir {}: {e}", maildir.display()))
+            }),
+            MailSource::Command(cmd) => {
+                let output = Command::new("sh")
+                    .arg("-c")
+                    .arg(cmd)
+                    .stderr(Stdio::inherit())
+                    .output()
+                    .await
+                    .map_err(|e| format!("Failed to run `{cmd}`: {e}"))?;
+                let stdout = String::from_utf8_lossy(&output.stdout);
+                stdout
+                    .trim()
+                    .parse()
+                    .map_err(|_| format!("`{cmd}` printed `{}` instead of a number", stdout.trim()))
+            }
+        }
+    }
+}
+
+fn count_unread(maildir: &Path) -> io::Result<usize> {
+    let new = fs::read_dir(maildir.join("new"))?.count();
+    let cur = fs::read_dir(maildir.join("cur"))?
+        .flatten()
+        .filter(|mail| {
+            // Flags are appended to the file name like `<unique>:2,FS`
+            !mail
+                .file_name()
+                .to_string_lossy()
+                .rsplit_once(":2,")
+                .is_some_and(|(_, flags)| flags.contains('S'))
+        })
+        .count();
+    Ok(new + cur)
+}
+
+/// Expands a leading `~` to the home directory
+fn expand_home(path: &str) -> PathBuf {
+    match path.strip_prefix("~/") {
+        Some(path) => Path::new(&env::var("HOME").unwrap_or_default()).join(path),
+        None => PathBuf::from(path),
+    }
+}
+
+#[derive(Debug, Builder)]
+pub struct MailMod {
+    cfg_override: ModuleConfigOverride,
+    /// `None` if the count couldn't be read
+    unread: Option<usize>,
+    source: MailSource,
+    /// How often the count is refreshed
+    interval: Duration,
+    /// Whether the maildirs are watched for changes in addition to the interval
+    watch: bool,
+    icon: String,
+    icon_empty: String,
+    /// The icon color if there is no unread mail
+    empty_color: Option<Color>,
+    badge_color: Color,
+    badge_background: Color,
+    /// Opens the mail client on left click
+    open: OnClickAction,
+}
+
+impl Default for MailMod {
+    fn default() -> Self {
+        Self {
+            cfg_override: Default::default(),
+            unread: None,
+            source: MailSource::default(),
+            interval: Duration::from_secs(60),
+            watch: true,
+            icon: "󰇮".to_string(),
+            icon_empty: "󰇰".to_string(),
+            empty_color: None,
+            badge_color: Color::WHITE,
+            badge_background: Color::from_rgb(0.8, 0.2, 0.2),
+            open: OnClickAction::default(),
+        }
+    }
+}
+
+impl Module for MailMod {
+    fn name(&self) -> String {
+        "mail".to_string()
+    }
+
+    fn active(&self) -> bool {
+        self.unread.is_some()
+    }
+
+    fn view(
+        &self,
+        config: &LocalModuleConfig,
+        _popup_config: &PopupConfig,
+        anchor: &BarAnchor,
+        _handlebars: &Handlebars,
+    ) -> Element<Message> {
+        let unread = self.unread.unwrap_or_default();
+        let (icon, color) = match unread {
+            0 => (&self.icon_empty, self.empty_color),
+            _ => (&self.icon, None),
+        };
+        let font_size = self.cfg_override.font_size.unwrap_or(config.font_size);
+        let badge = (unread > 0).then(|| {
+            container(
+                container(
+                    text(unread.to_string())
+                        .size(font_size * 0.8)
+                        .font(self.cfg_override.text_font.unwrap_or(config.text_font))
+                        .color(self.badge_color),
+                )
+                .padding([0, 5])
+                .style(|_| container::Style {
+                    background: Some(Background::Color(self.badge_background)),
+                    border: Border {
+                        radius: Radius::from(font_size),
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                }),
+            )
+            .padding(self.cfg_override.text_margin.unwrap_or(config.text_margin))
+        });
+        let icon = container(
+            text(self.cfg_override.icon(config, icon))
+                .fill(anchor)
+                .size(self.cfg_override.icon_size.unwrap_or(config.icon_size))
+                .color(
+                    color
+                        .or(self.cfg_override.icon_color)
+                        .unwrap_or(config.icon_color),
+                )
+                .font(config.icon_font),
+        )
+        .padding(self.cfg_override.icon_margin.unwrap_or(config.icon_margin))
+        .into();
+        list(anchor, [icon].into_iter().chain(badge.map(Element::from)))
+            .spacing(self.cfg_override.spacing.unwrap_or(config.spacing))
+            .into()
+    }
+
+    impl_wrapper!();
+
+    fn read_config(
+        &mut self,
+        config: &HashMap<String, Option<String>>,
+        _popup_config: &HashMap<String, Option<String>>,
+        _templates: &mut Handlebars,
+        scale: f32,
+    ) {
+        let default = Self::default();
+        self.cfg_override = (config, scale).into();
+        self.source = match (
+            config.get("command").and_then(|v| v.clone()),
+            config.get("maildir").and_then(|v| v.as_ref()),
+        ) {
+            (Some(cmd), _) => MailSource::Command(cmd),
+            (None, Some(maildirs)) => MailSource::Maildirs(
+                maildirs
+                    .split(',')
+                    .map(|maildir| maildir.trim())
+                    .filter(|maildir| !maildir.is_empty())
+                    .map(expand_home)
+                    .collect(),
+            ),
+            (None, None) => default.source,
+        };
+        self.interval = config
+            .get("interval")
+            .and_then(|v| v.as_ref().and_then(|v| v.parse().ok()))
+            .map(Duration::from_secs)
+            .unwrap_or(default.interval);
+        self.watch = config
+            .get("watch")
+            .and_then(|v| v.into_bool())
+            .unwrap_or(default.watch);
+        self.icon = config
+            .get("icon")
+            .and_then(|v| v.clone())
+            .unwrap_or(default.icon);
+        self.icon_empty = config
+            .get("icon_empty")
+            .and_then(|v| v.clone())
+            .unwrap_or(default.icon_empty);
+        self.empty_color = config
+            .get("empty_color")
+            .and_then(|v| v.into_color())
+            .or(default.empty_color);
+        self.badge_color = config
+            .get("badge_color")
+            .and_then(|v| v.into_color())
+            .unwrap_or(default.badge_color);
+        self.badge_background = config
+            .get("badge_background")
+            .and_then(|v| v.into_color())
+            .unwrap_or(default.badge_background);
+        self.open = match config.get("client").and_then(|v| v.clone()) {
+            Some(cmd) => OnClickAction {
+                left: Some(Box::new(CommandAction(cmd))),
+                ..Default::default()
+            },
+            None => default.open,
+        };
+    }
+
+    fn known_keys(&self) -> Vec<ConfigKey> {
+        vec![
+            ConfigKey::new("command", Kind::Text, None),
+            // Separated by commas
+            ConfigKey::new("maildir", Kind::Text, Some("~/Mail/INBOX")),
+            // In seconds
+            ConfigKey::new("interval", Kind::Integer, Some("60")),
+            ConfigKey::new("watch", Kind::Bool, Some("true")),
+            ConfigKey::new("icon", Kind::Text, Some("󰇮")),
+            ConfigKey::new("icon_empty", Kind::Text, Some("󰇰")),
+            ConfigKey::new("empty_color", Kind::Color, None),
+            ConfigKey::new("badge_color", Kind::Color, Some("white")),
+            ConfigKey::new("badge_background", Kind::Color, Some("rgb(80%, 20%, 20%)")),
+            ConfigKey::new("client", Kind::Text, None),
+        ]
+    }
+
+    impl_on_click!();
+
+    fn default_action(&self) -> Option<&OnClickAction> {
+        Some(&self.open)
+    }
+
+    fn collect_data(&mut self) -> Result<(), String> {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .map_err(|e| e.to_string())?;
+        self.unread = Some(runtime.block_on(self.source.unread())?);
+        Ok(())
+    }
+
+    fn text_content(&self, _template: &Handlebars) -> Option<Result<String, String>> {
+        self.unread.map(|unread| Ok(unread.to_string()))
+    }
+
+    fn subscription(&self) -> Option<Subscription<Message>> {
+        let source = self.source.clone();
+        let interval = self.interval;
+        let watch = self.watch;
+        Some(Subscription::run_with_id(
+            ("mail", source.clone(), interval, watch),
+            stream::channel(1, move |mut sender| async move {
+                watch_mail(&mut sender, source, interval, watch).await;
+            }),
+        ))
+    }
+}
+
+/// Refreshes the count on the interval and whenever a maildir changes. Returns if the bar is
+/// closing.
+async fn watch_mail(
+    sender: &mut Sender<Message>,
+    source: MailSource,
+    interval: Duration,
+    watch: bool,
+) {
+    let (change_sx, mut change_rx) = mpsc::unbounded_channel();
+    // Kept until the subscription ends, the watcher stops when it is dropped
+    let _watcher = match (&source, watch) {
+        (MailSource::Maildirs(maildirs), true) => {
+            let watcher = RecommendedWatcher::new(
+                move |result: notify::Result<Event>| {
+                    if result.is_ok_and(|event| !matches!(event.kind, EventKind::Access(_))) {
+                        let _ = change_sx.send(());
+                    }
+                },
+                notify::Config::default(),
+            )
+            .map(|mut watcher| {
+                for maildir in maildirs {
+                    for dir in ["new", "cur"] {
+                        if let Err(e) =
+                            watcher.watch(&maildir.join(dir), RecursiveMode::NonRecursive)
+                        {
+                            eprintln!("Failed to watch maildir {}: {e}", maildir.display());
+                        }
+                    }
+                }
+                watcher
+            });
+            watcher
+                .inspect_err(|e| eprintln!("Failed to watch the maildirs: {e}"))
+                .ok()
+        }
+        _ => None,
+    };
+    let mut previous = None;
+    loop {
+        let unread = source.unread().await.inspect_err(|e| eprintln!("{e}")).ok();
+        if previous != Some(unread) {
+            previous = Some(unread);
+            if sender
+                .send(Message::update(move |reg| {
+                    reg.get_module_mut::<MailMod>().unread = unread
+                }))
+                .await
+                .is_err()
+            {
+                return;
+            }
+        }
+        select! {
+            _ = sleep(interval) => {}
+            Some(_) = change_rx.recv() => {
+                // Mail clients and sync tools usually change many files at once
+                sleep(Duration::from_millis(500)).await;
+                while change_rx.try_recv().is_ok() {}
+            }
+        }
+    }
+}
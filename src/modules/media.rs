@@ -1,46 +1,94 @@
 use std::collections::{BTreeMap, HashSet};
-use std::{collections::HashMap, process::Stdio};
+use std::{collections::HashMap, process::Stdio, time::Duration};
 
 use bar_rs_derive::Builder;
 use handlebars::Handlebars;
 use iced::widget::button::Style;
-use iced::widget::{column, container, image, row, scrollable, Container, Text};
+use iced::widget::{column, container, image, row, scrollable, Column, Container, Text};
 use iced::Length::Fill;
-use iced::{futures::SinkExt, stream, widget::text, Element, Subscription};
+use iced::{
+    futures::{channel::mpsc::Sender, SinkExt, StreamExt},
+    mouse, stream,
+    widget::text,
+    Alignment, Background, Color, Element, Event, Subscription,
+};
 use serde::Deserialize;
 use tokio::{
     io::{AsyncBufReadExt, BufReader},
     process::Command,
+    time::sleep,
+};
+use zbus::{
+    fdo::{DBusProxy, PropertiesProxy},
+    names::InterfaceName,
+    Connection,
 };
 
+use crate::bar_text::bar_text;
 use crate::button::button;
+use crate::config::parse::StringExt;
 use crate::config::popup_config::{PopupConfig, PopupConfigOverride};
-use crate::helpers::UnEscapeString;
+use crate::config::scale::Scale;
+use crate::helpers::{marquee::Marquee, UnEscapeString};
 use crate::{
     config::{
         anchor::BarAnchor,
         module_config::{LocalModuleConfig, ModuleConfigOverride},
+        schema::{ConfigKey, Kind},
     },
     fill::FillExt,
-    Message, NERD_FONT,
+    Message,
 };
 use crate::{impl_on_click, impl_wrapper};
 
-use super::Module;
+use super::{click_message, Module};
+
+/// The metadata format passed to `playerctl`, parsed into a [TrackInfo]
+const PLAYERCTL_FORMAT: &str = r#"{"title": "{{title}}", "artist": "{{artist}}", "album": "{{album}}", "art_url": "{{mpris:artUrl}}", "length": {{mpris:length}}, "status": "{{status}}", "player": "{{playerName}}", "instance": "{{playerInstance}}"}"#;
+
+/// The prefix of the bus names of MPRIS players, followed by the player instance
+const MPRIS_PREFIX: &str = "org.mpris.MediaPlayer2.";
+/// The object path of MPRIS players
+const MPRIS_PATH: &str = "/org/mpris/MediaPlayer2";
+/// The interface of MPRIS players which has the `Position` property
+const MPRIS_PLAYER: &str = "org.mpris.MediaPlayer2.Player";
+/// How often the position is read while the active player is playing
+const POSITION_INTERVAL: Duration = Duration::from_secs(1);
+/// The elapsed time, followed by the length unless it's unknown
+const DEFAULT_POSITION_FORMAT: &str =
+    r#"{{duration position "clock"}}{{#if length}}/{{duration length "clock"}}{{/if}}"#;
 
 #[derive(Debug, Builder)]
 pub struct MediaMod {
-    track: Option<TrackInfo>,
-    img: Option<Vec<u8>>,
+    /// The track of every player, by player instance
+    tracks: BTreeMap<String, TrackInfo>,
+    /// Downloaded covers by their url
+    covers: HashMap<String, Vec<u8>>,
+    /// The player instance which is shown and controlled
     active_player: Option<String>,
+    /// Player instances which started playing, the most recent one last
+    recently_playing: Vec<String>,
     cfg_override: ModuleConfigOverride,
     popup_cfg_override: PopupConfigOverride,
     icon: String,
+    /// Icons for players whose name contains the key
+    player_icons: BTreeMap<String, String>,
     ctrl_icons: PlayerCtrlIcons,
     max_length: usize,
     max_title_length: usize,
-    players: HashSet<String>,
+    /// Scrolls the title and artist instead of shortening them
+    marquee: Marquee,
+    /// The players which are shown, in the order they're preferred in
+    players: Vec<String>,
+    /// Whether the player which most recently started playing becomes the active one
+    follow_playing: bool,
+    /// Whether the artist is shown below the title on bars which are thick enough
+    multiline: bool,
+    /// Whether the position in the track is shown after the title and artist
+    show_position: bool,
     cover_width: f32,
+    active_color: Option<Color>,
+    active_background: Option<Background>,
 }
 
 #[derive(Debug)]
@@ -54,9 +102,10 @@ struct PlayerCtrlIcons {
 impl Default for MediaMod {
     fn default() -> Self {
         Self {
-            track: None,
-            img: None,
+            tracks: BTreeMap::new(),
+            covers: HashMap::new(),
             active_player: None,
+            recently_playing: vec![],
             cfg_override: Default::default(),
             popup_cfg_override: PopupConfigOverride {
                 width: Some(300),
@@ -72,15 +121,42 @@ impl Default for MediaMod {
             },
             max_length: 28,
             max_title_length: 16,
-            players: HashSet::from(["spotify".to_string(), "kew".to_string()]),
+            marquee: Marquee::default(),
+            player_icons: BTreeMap::new(),
+            players: vec!["spotify".to_string(), "kew".to_string()],
+            follow_playing: false,
+            multiline: false,
+            show_position: false,
             cover_width: 260.,
+            active_color: None,
+            active_background: Some(Background::Color(Color::from_rgba(1., 1., 1., 0.2))),
         }
     }
 }
 
 impl MediaMod {
+    /// The track of the active player
+    fn track(&self) -> Option<&TrackInfo> {
+        self.active_player
+            .as_ref()
+            .and_then(|player| self.tracks.get(player))
+    }
+
+    /// The icon of the player whose name contains a key of `icon.{{player}}`, otherwise `icon`
+    fn player_icon(&self, track: Option<&TrackInfo>) -> &str {
+        track
+            .and_then(|track| {
+                let player = track.instance.to_lowercase();
+                self.player_icons
+                    .iter()
+                    .find(|(pattern, _)| player.contains(pattern.as_str()))
+            })
+            .map(|(_, icon)| icon.as_str())
+            .unwrap_or(&self.icon)
+    }
+
     fn get_active_trimmed(&self) -> Option<String> {
-        self.track.as_ref().map(|track| {
+        self.track().map(|track| {
             let mut title = track.title.clone();
             let mut artist = track.artist.clone();
             if self.is_overlength() {
@@ -103,17 +179,161 @@ impl MediaMod {
         })
     }
 
+    /// The title and the artist without shortening them
+    fn get_active_full(&self) -> Option<String> {
+        self.track().map(|track| match track.artist.is_empty() {
+            true => track.title.clone(),
+            false => format!("{} - {}", track.title, track.artist),
+        })
+    }
+
+    /// The part of the title and artist the marquee shows, `None` if it's disabled
+    fn get_active_marquee(&self) -> Option<String> {
+        self.marquee
+            .enabled
+            .then(|| self.get_active_full())
+            .flatten()
+            .map(|text| self.marquee.visible(&text))
+    }
+
+    /// The title and the artist below it, each shortened to `max_length`
+    fn get_active_lines(&self) -> Option<String> {
+        let shorten = |text: &str| match text.chars().count() > self.max_length {
+            true => format!(
+                "{}...",
+                text.chars()
+                    .take(self.max_length.saturating_sub(3))
+                    .collect::<String>()
+            ),
+            false => text.to_string(),
+        };
+        self.track()
+            .filter(|track| !track.artist.is_empty())
+            .map(|track| format!("{}\n{}", shorten(&track.title), shorten(&track.artist)))
+    }
+
+    /// The position in the active track, rendered with `format_position`, `None` unless
+    /// `show_position` is set
+    fn get_position(&self, handlebars: &Handlebars) -> Option<String> {
+        let track = self.track().filter(|_| self.show_position)?;
+        let ctx = BTreeMap::from([
+            ("position", (track.position / 1000000.).floor() as u32),
+            ("length", (track.length / 1000000.).round() as u32),
+        ]);
+        handlebars
+            .render("media_position", &ctx)
+            .map_err(|e| eprintln!("Failed to render media position: {e}"))
+            .ok()
+    }
+
+    /// Appends the position to the text of the bar if it's shown
+    fn with_position(&self, text: String, handlebars: &Handlebars) -> String {
+        match self.get_position(handlebars) {
+            Some(position) if !text.is_empty() => format!("{text} {position}"),
+            Some(position) => position,
+            None => text,
+        }
+    }
+
+    fn set_position(&mut self, instance: &str, position: f32) {
+        if let Some(track) = self.tracks.get_mut(instance) {
+            track.position = position;
+        }
+    }
+
     fn is_overlength(&self) -> bool {
-        self.track
-            .as_ref()
+        self.track()
             .is_some_and(|t| t.title.len() + t.artist.len() + 3 > self.max_length)
     }
 
-    fn new_track(&mut self, track: TrackInfo) {
-        if self.players.contains(&track.player) {
-            self.active_player = Some(track.player.clone());
-            self.track = Some(track)
+    fn new_track(&mut self, mut track: TrackInfo) {
+        if !self.players.contains(&track.player) {
+            return;
+        }
+        let instance = track.instance.clone();
+        // Until it's read again, the position stays if only the status changed
+        if let Some(previous) = self
+            .tracks
+            .get(&instance)
+            .filter(|t| t.title == track.title && t.artist == track.artist)
+        {
+            track.position = previous.position;
+        }
+        let started = !track.paused && self.tracks.get(&instance).map_or(true, |t| t.paused);
+        self.tracks.insert(instance.clone(), track);
+        if started {
+            self.recently_playing.retain(|p| *p != instance);
+            self.recently_playing.push(instance.clone());
         }
+        if self.follow_playing && started {
+            self.active_player = Some(instance);
+        } else if self.track().is_none() {
+            self.active_player = self.fallback_player();
+        }
+        self.prune_covers();
+    }
+
+    fn remove_player(&mut self, instance: &str) {
+        if self.tracks.remove(instance).is_none() {
+            return;
+        }
+        self.recently_playing.retain(|p| p != instance);
+        if self.active_player.as_deref() == Some(instance) {
+            self.active_player = self.fallback_player();
+        }
+        self.prune_covers();
+    }
+
+    /// The player which becomes active if the active one is gone: the one which most recently
+    /// started playing with `follow_playing`, otherwise the first one in the order of `players`
+    fn fallback_player(&self) -> Option<String> {
+        let playing = self
+            .recently_playing
+            .iter()
+            .rev()
+            .find(|player| self.tracks.get(*player).is_some_and(|t| !t.paused));
+        match (self.follow_playing, playing) {
+            (true, Some(player)) => Some(player.clone()),
+            _ => self
+                .tracks
+                .values()
+                .min_by_key(|t| {
+                    (
+                        self.players.iter().position(|p| *p == t.player),
+                        &t.instance,
+                    )
+                })
+                .map(|t| t.instance.clone()),
+        }
+    }
+
+    /// Makes the next player active, in alphabetical order
+    fn cycle_player(&mut self) {
+        let next = match &self.active_player {
+            Some(active) => self
+                .tracks
+                .keys()
+                .find(|player| *player > active)
+                .or_else(|| self.tracks.keys().next()),
+            None => self.tracks.keys().next(),
+        };
+        self.active_player = next.cloned();
+    }
+
+    fn select_player(&mut self, instance: String) {
+        if self.tracks.contains_key(&instance) {
+            self.active_player = Some(instance);
+        }
+    }
+
+    /// Drops the covers no player shows anymore
+    fn prune_covers(&mut self) {
+        let urls = self
+            .tracks
+            .values()
+            .map(|t| t.art_url.as_str())
+            .collect::<HashSet<_>>();
+        self.covers.retain(|url, _| urls.contains(url.as_str()));
     }
 }
 
@@ -123,9 +343,16 @@ struct TrackInfo {
     artist: String,
     album: String,
     art_url: String,
+    /// The name of the player, like `firefox`
     player: String,
+    /// The player instance, like `firefox.instance_1_23`, which tells apart multiple players of
+    /// the same name
+    instance: String,
     art_is_local: bool,
+    /// In microseconds, `0` if the player doesn't know it, e.g. for streams
     length: f32,
+    /// In microseconds
+    position: f32,
     paused: bool,
 }
 
@@ -172,12 +399,19 @@ impl<'de> Deserialize<'de> for TrackInfo {
                 .and_then(|v| v.as_str())
                 .unwrap_or_default()
                 .to_string(),
+            instance: map
+                .get("instance")
+                .or_else(|| map.get("player"))
+                .and_then(|v| v.as_str())
+                .unwrap_or_default()
+                .to_string(),
             art_url,
             art_is_local,
             length: map
                 .get("length")
                 .and_then(|v| v.as_f64())
                 .unwrap_or_default() as f32,
+            position: 0.,
             paused: map
                 .get("status")
                 .map(|v| !matches!(v.as_str(), Some("Playing")))
@@ -192,7 +426,7 @@ impl Module for MediaMod {
     }
 
     fn active(&self) -> bool {
-        self.track.is_some()
+        self.track().is_some()
     }
 
     fn view(
@@ -200,42 +434,83 @@ impl Module for MediaMod {
         config: &LocalModuleConfig,
         popup_config: &PopupConfig,
         anchor: &BarAnchor,
-        _handlebars: &Handlebars,
+        handlebars: &Handlebars,
     ) -> Element<Message> {
+        let popup = self.track().map(|_| {
+            Message::popup::<Self>(
+                self.popup_cfg_override.width.unwrap_or(popup_config.width),
+                self.popup_cfg_override
+                    .height
+                    .unwrap_or(popup_config.height),
+                anchor,
+            )
+        });
+        // Middle clicks switch to the next player, unless `on_click_middle` is set
+        let middle = click_message(self, mouse::Button::Middle, config).or_else(|| {
+            (self.tracks.len() > 1)
+                .then(|| Message::update(|reg| reg.get_module_mut::<MediaMod>().cycle_player()))
+        });
+        let left = click_message(self, mouse::Button::Left, config);
+        let right = click_message(self, mouse::Button::Right, config);
+        let font_size = self.cfg_override.font_size.unwrap_or(config.font_size);
+        let two_lines =
+            self.multiline && !config.bar.anchor.vertical() && config.bar.lines(font_size) >= 2;
+        let content = two_lines
+            .then(|| self.get_active_lines())
+            .flatten()
+            .or_else(|| self.get_active_marquee())
+            .or_else(|| self.get_active_trimmed())
+            .unwrap_or_default();
+        let content = self.with_position(content, handlebars);
         button(
             list![
                 anchor,
                 container(
-                    text(&self.icon)
-                        .fill(anchor)
-                        .size(self.cfg_override.icon_size.unwrap_or(config.icon_size))
-                        .color(self.cfg_override.icon_color.unwrap_or(config.icon_color))
-                        .font(NERD_FONT)
+                    text(
+                        self.cfg_override
+                            .icon(config, self.player_icon(self.track()))
+                    )
+                    .fill(anchor)
+                    .size(self.cfg_override.icon_size.unwrap_or(config.icon_size))
+                    .color(self.cfg_override.icon_color.unwrap_or(config.icon_color))
+                    .font(config.icon_font)
                 )
                 .padding(self.cfg_override.icon_margin.unwrap_or(config.icon_margin)),
                 container(
-                    text(self.get_active_trimmed().unwrap_or_default())
-                        .fill(anchor)
-                        .size(self.cfg_override.font_size.unwrap_or(config.font_size))
-                        .color(self.cfg_override.text_color.unwrap_or(config.text_color))
+                    bar_text(
+                        content,
+                        anchor,
+                        self.cfg_override.vertical_text(config, anchor)
+                    )
+                    .size(font_size)
+                    .font(self.cfg_override.text_font.unwrap_or(config.text_font))
+                    .color(self.cfg_override.text_color.unwrap_or(config.text_color))
                 )
                 .padding(self.cfg_override.text_margin.unwrap_or(config.text_margin))
             ]
             .spacing(self.cfg_override.spacing.unwrap_or(config.spacing)),
         )
-        .on_event_maybe_with(self.track.as_ref().map(|_| {
-            Message::popup::<Self>(
-                self.popup_cfg_override.width.unwrap_or(popup_config.width),
-                self.popup_cfg_override
-                    .height
-                    .unwrap_or(popup_config.height),
-                anchor,
-            )
-        }))
+        .on_event_try(move |event, layout, cursor, clipboard, viewport| {
+            let clicked = match event {
+                Event::Mouse(mouse::Event::ButtonReleased(mouse::Button::Left)) => left.clone(),
+                Event::Mouse(mouse::Event::ButtonReleased(mouse::Button::Middle)) => middle.clone(),
+                Event::Mouse(mouse::Event::ButtonReleased(mouse::Button::Right)) => right.clone(),
+                _ => None,
+            };
+            clicked.or_else(|| {
+                popup
+                    .as_ref()
+                    .map(|popup| popup(event, layout, cursor, clipboard, viewport))
+            })
+        })
         .style(|_, _| Style::default())
         .into()
     }
 
+    fn popup_cfg_override(&self) -> Option<&PopupConfigOverride> {
+        Some(&self.popup_cfg_override)
+    }
+
     fn popup_view<'a>(
         &'a self,
         config: &'a PopupConfig,
@@ -260,13 +535,91 @@ impl Module for MediaMod {
                     .unwrap_or(config.text_margin),
             )
         };
-        container(match &self.track {
+        let text_color = self
+            .popup_cfg_override
+            .text_color
+            .unwrap_or(config.text_color);
+        let player_text = |content: String, color: Color| -> Container<'a, Message> {
+            container(
+                text(content)
+                    .size(
+                        self.popup_cfg_override
+                            .font_size
+                            .unwrap_or(config.font_size),
+                    )
+                    .color(color),
+            )
+            .padding(
+                self.popup_cfg_override
+                    .text_margin
+                    .unwrap_or(config.text_margin),
+            )
+        };
+        // Every player with its track, clicking one makes it the active player
+        let players = self.tracks.values().map(|track| -> Element<'a, Message> {
+            let is_active = self.active_player.as_ref() == Some(&track.instance);
+            let color = match is_active {
+                true => self.active_color.unwrap_or(text_color),
+                false => text_color,
+            };
+            let background = is_active.then_some(self.active_background).flatten();
+            let instance = track.instance.clone();
+            button(
+                row![
+                    container(
+                        text(config.icon(self.player_icon(Some(track))))
+                            .font(config.icon_font)
+                            .size(
+                                self.popup_cfg_override
+                                    .icon_size
+                                    .unwrap_or(config.icon_size),
+                            )
+                            .color(color),
+                    )
+                    .padding(
+                        self.popup_cfg_override
+                            .icon_margin
+                            .unwrap_or(config.icon_margin),
+                    ),
+                    player_text(
+                        match track.artist.is_empty() {
+                            true => track.title.clone(),
+                            false => format!("{} - {}", track.title, track.artist),
+                        },
+                        color
+                    )
+                    .width(Fill),
+                    player_text(
+                        match track.paused {
+                            true => "paused",
+                            false => "playing",
+                        }
+                        .to_string(),
+                        color
+                    ),
+                ]
+                .align_y(Alignment::Center),
+            )
+            .on_event(Message::update(move |reg| {
+                reg.get_module_mut::<MediaMod>()
+                    .select_player(instance.clone())
+            }))
+            .style(move |_, _| Style {
+                background,
+                ..Default::default()
+            })
+            .width(Fill)
+            .into()
+        });
+        let players = Column::with_children(players)
+            .spacing(self.popup_cfg_override.spacing.unwrap_or(config.spacing));
+        container(match self.track() {
             Some(track) => {
                 let minutes = (track.length / 60000000.).trunc();
-                let icon = |icon| {
+                let icon = |icon: &'a str| {
                     container(
-                        text(icon)
-                            .font(NERD_FONT)
+                        text(config.icon(icon))
+                            .font(config.icon_font)
                             .size(
                                 self.popup_cfg_override
                                     .icon_size
@@ -303,6 +656,7 @@ impl Module for MediaMod {
                         "seconds",
                         ((track.length / 1000000.) - minutes * 60.).round() as u32,
                     ),
+                    ("length", (track.length / 1000000.).round() as u32),
                 ]);
                 let length = template
                     .render("media_popup_length", &length_ctx)
@@ -328,7 +682,7 @@ impl Module for MediaMod {
                                 .width(self.cover_width)
                             ),
                             false =>
-                                if let Some(bytes) = self.img.clone() {
+                                if let Some(bytes) = self.covers.get(&track.art_url).cloned() {
                                     <iced::widget::Image as Into<Element<Message>>>::into(image(
                                         image::Handle::from_bytes(bytes),
                                     ))
@@ -361,26 +715,12 @@ impl Module for MediaMod {
                                 .unwrap_or_default()
                         )),
                     ]
+                    .push(players)
                     .spacing(self.popup_cfg_override.spacing.unwrap_or(config.spacing)),
                 ))
             }
             None => fmt_text(text("No media is playing right now")).into(),
         })
-        .padding(self.popup_cfg_override.padding.unwrap_or(config.padding))
-        .style(|_| container::Style {
-            background: Some(
-                self.popup_cfg_override
-                    .background
-                    .unwrap_or(config.background),
-            ),
-            border: self.popup_cfg_override.border.unwrap_or(config.border),
-            ..Default::default()
-        })
-        .fill_maybe(
-            self.popup_cfg_override
-                .fill_content_to_size
-                .unwrap_or(config.fill_content_to_size),
-        )
         .into()
     }
 
@@ -391,10 +731,11 @@ impl Module for MediaMod {
         config: &HashMap<String, Option<String>>,
         popup_config: &HashMap<String, Option<String>>,
         templates: &mut Handlebars,
+        scale: f32,
     ) {
         let default = Self::default();
-        self.cfg_override = config.into();
-        self.popup_cfg_override.update(popup_config);
+        self.cfg_override = (config, scale).into();
+        self.popup_cfg_override.update(popup_config, scale);
         self.icon = config
             .get("icon")
             .and_then(|v| v.clone())
@@ -407,17 +748,47 @@ impl Module for MediaMod {
             .get("max_title_length")
             .and_then(|v| v.as_ref().and_then(|v| v.parse().ok()))
             .unwrap_or(default.max_title_length);
-        self.players = popup_config
+        self.marquee.read_config(config);
+        self.player_icons = config
+            .iter()
+            .filter_map(|(key, icon)| {
+                let player = key.strip_prefix("icon.")?;
+                Some((player.to_lowercase(), icon.clone()?))
+            })
+            .collect();
+        self.multiline = config
+            .get("multiline")
+            .and_then(|v| v.into_bool())
+            .unwrap_or(default.multiline);
+        self.show_position = config
+            .get("show_position")
+            .and_then(|v| v.into_bool())
+            .unwrap_or(default.show_position);
+        self.follow_playing = config
+            .get("follow_playing")
+            .and_then(|v| v.into_bool())
+            .unwrap_or(default.follow_playing);
+        self.players = config
             .get("players")
+            .or_else(|| popup_config.get("players"))
             .and_then(|v| {
                 v.as_ref()
                     .map(|v| v.split(',').map(|i| i.trim().to_string()).collect())
             })
             .unwrap_or(default.players);
+        self.active_color = popup_config
+            .get("active_color")
+            .and_then(|v| v.into_color())
+            .or(default.active_color);
+        self.active_background = popup_config
+            .get("active_background")
+            .map(|v| v.into_background())
+            .unwrap_or(default.active_background);
         self.cover_width = popup_config
             .get("cover_width")
             .and_then(|v| v.as_ref().and_then(|v| v.parse().ok()))
-            .unwrap_or(default.cover_width);
+            .unwrap_or(default.cover_width)
+            .scaled(scale);
         self.ctrl_icons = {
             let default = default.ctrl_icons;
             PlayerCtrlIcons {
@@ -457,21 +828,132 @@ impl Module for MediaMod {
                 popup_config
                     .get("format_length")
                     .unescape()
-                    .unwrap_or("{{minutes}}min {{seconds}}sec".to_string()),
+                    .unwrap_or("{{duration length \"clock\"}}".to_string()),
             )
             .unwrap_or_else(|e| eprintln!("Failed to parse battery popup time format: {e}"));
+        templates
+            .register_template_string(
+                "media_position",
+                config
+                    .get("format_position")
+                    .unescape()
+                    .unwrap_or(DEFAULT_POSITION_FORMAT.to_string()),
+            )
+            .unwrap_or_else(|e| eprintln!("Failed to parse media position format: {e}"));
+    }
+
+    fn known_keys(&self) -> Vec<ConfigKey> {
+        let mut keys = vec![
+            ConfigKey::new("icon", Kind::Text, Some("")),
+            ConfigKey::new("icon.*", Kind::Text, None),
+            ConfigKey::new("max_length", Kind::Integer, Some("28")),
+            ConfigKey::new("max_title_length", Kind::Integer, Some("16")),
+            ConfigKey::new("multiline", Kind::Bool, Some("false")),
+            ConfigKey::new("show_position", Kind::Bool, Some("false")),
+            ConfigKey::new("format_position", Kind::Text, Some(DEFAULT_POSITION_FORMAT)),
+            ConfigKey::new("follow_playing", Kind::Bool, Some("false")),
+            ConfigKey::new("players", Kind::Text, Some("spotify, kew")),
+        ];
+        keys.extend_from_slice(Marquee::KEYS);
+        keys
+    }
+
+    fn known_popup_keys(&self) -> Vec<ConfigKey> {
+        vec![
+            ConfigKey::new("players", Kind::Text, None),
+            ConfigKey::new("active_color", Kind::Color, None),
+            ConfigKey::new(
+                "active_background",
+                Kind::Color,
+                Some("rgba(255, 255, 255, 0.2)"),
+            ),
+            ConfigKey::new("cover_width", Kind::Float, Some("260")),
+            ConfigKey::new("icon_previous", Kind::Text, Some("󰒮")),
+            ConfigKey::new("icon_play", Kind::Text, Some("")),
+            ConfigKey::new("icon_pause", Kind::Text, Some("")),
+            ConfigKey::new("icon_next", Kind::Text, Some("󰒭")),
+            ConfigKey::new(
+                "format",
+                Kind::Text,
+                Some(r#"{{title}}{{status}}\nin: {{album}}\nby: {{artist}}\n{{length}}"#),
+            ),
+            ConfigKey::new(
+                "format_length",
+                Kind::Text,
+                Some(r#"{{duration length "clock"}}"#),
+            ),
+        ]
     }
 
     impl_on_click!();
 
+    fn has_popup(&self) -> bool {
+        // There's only something to show while something is playing
+        self.track().is_some()
+    }
+
+    fn collect_data(&mut self) -> Result<(), String> {
+        let output = std::process::Command::new("playerctl")
+            .args(["--all-players", "metadata", "--format", PLAYERCTL_FORMAT])
+            .output()
+            .map_err(|e| format!("Failed to run playerctl: {e}"))?;
+        // playerctl fails if no player is running, which just means there's nothing to show
+        for track in output
+            .stdout
+            .split(|b| *b == b'\n')
+            .filter_map(|line| serde_json::from_slice::<TrackInfo>(line).ok())
+        {
+            self.new_track(track);
+        }
+        Ok(())
+    }
+
+    fn text_content(&self, template: &Handlebars) -> Option<Result<String, String>> {
+        let text = self.get_active_trimmed().unwrap_or_default();
+        Some(Ok(self.with_position(text, template)))
+    }
+
     fn subscription(&self) -> Option<iced::Subscription<Message>> {
-        Some(Subscription::run(|| {
+        // The timer only runs while the text is too long to fit
+        let marquee = self
+            .get_active_full()
+            .filter(|text| self.marquee.scrolls(text))
+            .map(|_| {
+                iced::time::every(self.marquee.interval()).map(|_| {
+                    Message::update(|reg| {
+                        let m = reg.get_module_mut::<MediaMod>();
+                        let text = m.get_active_full().unwrap_or_default();
+                        m.marquee.tick(text);
+                    })
+                })
+            });
+        // The position is read once the track is paused and every second while it's playing
+        let position = self.track().filter(|_| self.show_position).map(|track| {
+            let instance = track.instance.clone();
+            let playing = !track.paused;
+            Subscription::run_with_id(
+                ("media_position", instance.clone(), playing),
+                stream::channel(1, move |sender| async move {
+                    if let Err(e) = watch_position(&instance, playing, sender).await {
+                        eprintln!("Failed to read the media position of {instance}: {e}");
+                    }
+                }),
+            )
+        });
+        let media = Subscription::run(|| {
             stream::channel(1, |mut sender| async move {
+                let watcher = sender.clone();
+                tokio::task::spawn(async move {
+                    if let Err(e) = watch_players(watcher).await {
+                        eprintln!("Failed to watch for closed media players: {e}");
+                    }
+                });
+
                 let mut child = Command::new("sh")
                     .arg("-c")
-                    .arg(
-                        "playerctl --follow metadata --format '{\"title\": \"{{title}}\", \"artist\": \"{{artist}}\", \"album\": \"{{album}}\", \"art_url\": \"{{mpris:artUrl}}\", \"length\": {{mpris:length}}, \"status\": \"{{status}}\", \"player\": \"{{playerName}}\"}'",
-                    )
+                    .arg(format!(
+                        "playerctl --all-players --follow metadata --format '{PLAYERCTL_FORMAT}'"
+                    ))
                     .stdout(Stdio::piped())
                     .spawn()
                     .expect("Failed to read output from playerctl");
@@ -482,55 +964,165 @@ impl Module for MediaMod {
                     .expect("child did not have a handle to stdout");
 
                 let mut reader = BufReader::new(stdout).lines();
-                let mut last_track = String::new();
+                // The cover url of every player, to download each cover once
+                let mut covers: HashMap<String, String> = HashMap::new();
 
-                loop {
-                    let line = reader.next_line().await.ok().flatten();
-                    if let Some(track) = line
-                        .as_ref()
-                        .and_then(|line| serde_json::from_str::<TrackInfo>(line.as_str()).ok())
+                while let Ok(Some(line)) = reader.next_line().await {
+                    // Empty lines can't be told apart by player, closed players are removed by
+                    // `watch_players` instead
+                    let Ok(track) = serde_json::from_str::<TrackInfo>(line.as_str()) else {
+                        continue;
+                    };
+                    if !track.art_is_local
+                        && !track.art_url.is_empty()
+                        && covers.get(&track.instance) != Some(&track.art_url)
                     {
-                        if let Some(url) = (!track.art_is_local).then_some(track.art_url.clone()) {
-                            if url != last_track {
-                                last_track = url.clone();
-                                let mut sender = sender.clone();
-                                tokio::task::spawn(async move {
-                                    let Ok(response) = reqwest::get(&url).await else {
-                                        eprintln!("Failed to get media cover: \"{url}\"");
-                                        return;
-                                    };
-                                    let Ok(bytes) = response.bytes().await else {
-                                        eprintln!(
-                                            "Failed to get bytes from media cover: \"{url}\""
-                                        );
-                                        return;
-                                    };
-                                    sender
-                                        .send(Message::update(move |reg| {
-                                            reg.get_module_mut::<MediaMod>().img =
-                                                Some(bytes.to_vec())
-                                        }))
-                                        .await
-                                        .unwrap();
-                                });
-                            }
-                        }
-                        sender
-                            .send(Message::update(move |reg| {
-                                reg.get_module_mut::<MediaMod>().new_track(track)
-                            }))
-                            .await
-                            .unwrap();
-                    } else if matches!(line.as_ref().map(|l| l.trim()), Some("")) {
-                        sender
-                            .send(Message::update(move |reg| {
-                                reg.get_module_mut::<MediaMod>().track = None
-                            }))
-                            .await
-                            .unwrap();
+                        let url = track.art_url.clone();
+                        covers.insert(track.instance.clone(), url.clone());
+                        let mut sender = sender.clone();
+                        tokio::task::spawn(async move {
+                            let Ok(response) = reqwest::get(&url).await else {
+                                eprintln!("Failed to get media cover: \"{url}\"");
+                                return;
+                            };
+                            let Ok(bytes) = response.bytes().await else {
+                                eprintln!("Failed to get bytes from media cover: \"{url}\"");
+                                return;
+                            };
+                            sender
+                                .send(Message::update(move |reg| {
+                                    reg.get_module_mut::<MediaMod>()
+                                        .covers
+                                        .insert(url, bytes.to_vec());
+                                }))
+                                .await
+                                .unwrap();
+                        });
                     }
+                    sender
+                        .send(Message::update(move |reg| {
+                            reg.get_module_mut::<MediaMod>().new_track(track)
+                        }))
+                        .await
+                        .unwrap();
                 }
             })
-        }))
+        });
+        Some(Subscription::batch(
+            [Some(media), marquee, position].into_iter().flatten(),
+        ))
+    }
+}
+
+/// Sends the `Position` of the player, every second until the subscription is dropped if it's
+/// `playing`
+async fn watch_position(
+    instance: &str,
+    playing: bool,
+    mut sender: Sender<Message>,
+) -> Result<(), String> {
+    let connection = Connection::session().await.map_err(|e| e.to_string())?;
+    let proxy = PropertiesProxy::builder(&connection)
+        .destination(format!("{MPRIS_PREFIX}{instance}"))
+        .and_then(|builder| builder.path(MPRIS_PATH))
+        .map_err(|e| e.to_string())?
+        .build()
+        .await
+        .map_err(|e| e.to_string())?;
+    let interface = InterfaceName::from_static_str_unchecked(MPRIS_PLAYER);
+    loop {
+        let position = proxy
+            .get(interface.clone(), "Position")
+            .await
+            .map_err(|e| e.to_string())
+            .and_then(|value| i64::try_from(value).map_err(|e| e.to_string()))?;
+        let instance = instance.to_string();
+        if sender
+            .send(Message::update(move |reg| {
+                reg.get_module_mut::<MediaMod>()
+                    .set_position(&instance, position as f32)
+            }))
+            .await
+            .is_err()
+            || !playing
+        {
+            return Ok(());
+        }
+        sleep(POSITION_INTERVAL).await;
+    }
+}
+
+/// Removes the players whose bus name is released, since playerctl doesn't report which player
+/// closed
+async fn watch_players(mut sender: Sender<Message>) -> Result<(), String> {
+    let connection = Connection::session().await.map_err(|e| e.to_string())?;
+    let mut owner_changes = DBusProxy::new(&connection)
+        .await
+        .map_err(|e| e.to_string())?
+        .receive_name_owner_changed()
+        .await
+        .map_err(|e| e.to_string())?;
+    while let Some(signal) = owner_changes.next().await {
+        let Ok(args) = signal.args() else {
+            continue;
+        };
+        // New players are reported by playerctl as soon as they have metadata
+        if args.new_owner().is_some() {
+            continue;
+        }
+        let Some(instance) = args.name().as_str().strip_prefix(MPRIS_PREFIX) else {
+            continue;
+        };
+        let instance = instance.to_string();
+        if sender
+            .send(Message::update(move |reg| {
+                reg.get_module_mut::<MediaMod>().remove_player(&instance)
+            }))
+            .await
+            .is_err()
+        {
+            return Ok(());
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::modules::testing::Harness;
+
+    use super::*;
+
+    fn text(length: u64, position: f32) -> String {
+        let track = serde_json::from_str::<TrackInfo>(&format!(
+            r#"{{"title": "Song", "artist": "Band", "length": {length}, "status": "Playing", "player": "spotify", "instance": "spotify"}}"#
+        ))
+        .unwrap();
+        let mut harness = Harness::new::<MediaMod>(&[("show_position", "true")]);
+        harness.update(move |m: &mut MediaMod| {
+            m.new_track(track);
+            m.set_position("spotify", position);
+        });
+        harness.text::<MediaMod>()
+    }
+
+    #[test]
+    fn shows_the_position_and_length() {
+        assert_eq!(text(225_000_000, 83_400_000.), "Song - Band 01:23/03:45");
+    }
+
+    #[test]
+    fn shows_the_elapsed_time_of_streams() {
+        assert_eq!(text(0, 3_723_000_000.), "Song - Band 01:02:03");
+    }
+
+    #[test]
+    fn position_is_hidden_by_default() {
+        let mut harness = Harness::new::<MediaMod>(&[]);
+        harness.update(|m: &mut MediaMod| {
+            m.new_track(serde_json::from_str(r#"{"title": "Song", "player": "kew"}"#).unwrap());
+            m.set_position("kew", 1000000.);
+        });
+        assert_eq!(harness.text::<MediaMod>(), "Song");
     }
 }
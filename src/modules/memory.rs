@@ -1,18 +1,30 @@
-use std::{collections::HashMap, process::Command};
+use std::{
+    collections::{BTreeMap, HashMap},
+    time::Duration,
+};
 
 use bar_rs_derive::Builder;
 use handlebars::Handlebars;
 use iced::widget::container;
-use iced::{widget::text, Element};
+use iced::{futures::SinkExt, stream, widget::text, Element, Subscription};
+use serde_json::Value;
+use tokio::{task, time::sleep};
 
+use crate::bar_text::bar_text;
 use crate::config::popup_config::PopupConfig;
+use crate::helpers::{
+    source::{Host, Source},
+    UnEscapeString,
+};
 use crate::{
     config::{
         anchor::BarAnchor,
         module_config::{LocalModuleConfig, ModuleConfigOverride},
+        schema::{ConfigKey, Kind},
+        thresholds::Thresholds,
     },
     fill::FillExt,
-    Message, NERD_FONT,
+    Message,
 };
 use crate::{impl_on_click, impl_wrapper};
 
@@ -22,6 +34,50 @@ use super::Module;
 pub struct MemoryMod {
     cfg_override: ModuleConfigOverride,
     icon: Option<String>,
+    /// `None` until the subscription read them for the first time
+    stats: Option<MemoryStats>,
+    thresholds: Thresholds,
+}
+
+/// The memory as reported by `free`, in bytes
+#[derive(Debug, Default)]
+struct MemoryStats {
+    total: u64,
+    used: u64,
+    free: u64,
+    available: u64,
+}
+
+impl MemoryStats {
+    /// Used memory in percentage points
+    fn usage(&self) -> u64 {
+        match self.total {
+            0 => 0,
+            total => (self.used as f64 / total as f64 * 100.).round() as u64,
+        }
+    }
+
+    fn context(&self) -> BTreeMap<String, Value> {
+        BTreeMap::from([
+            ("usage".to_string(), self.usage().into()),
+            ("total".to_string(), self.total.into()),
+            ("used".to_string(), self.used.into()),
+            ("free".to_string(), self.free.into()),
+            ("available".to_string(), self.available.into()),
+        ])
+    }
+}
+
+impl MemoryMod {
+    fn text(&self, handlebars: &Handlebars) -> Result<String, String> {
+        let stats = self
+            .stats
+            .as_ref()
+            .ok_or("The memory usage wasn't read yet")?;
+        handlebars
+            .render("memory", &stats.context())
+            .map_err(|e| e.to_string())
+    }
 }
 
 impl Module for MemoryMod {
@@ -34,38 +90,43 @@ impl Module for MemoryMod {
         config: &LocalModuleConfig,
         _popup_config: &PopupConfig,
         anchor: &BarAnchor,
-        _handlebars: &Handlebars,
+        handlebars: &Handlebars,
     ) -> Element<Message> {
-        let usage = Command::new("sh")
-            .arg("-c")
-            .arg("free | grep Mem | awk '{printf \"%.0f\", $3/$2 * 100.0}'")
-            .output()
-            .map(|out| String::from_utf8_lossy(&out.stdout).to_string())
-            .unwrap_or_else(|e| {
-                eprintln!("Failed to get memory usage. err: {e}");
-                "0".to_string()
-            })
-            .parse()
-            .unwrap_or_else(|e| {
-                eprintln!("Failed to parse memory usage (output from free), e: {e}");
-                999
-            });
+        // Only the icon is shown until the memory usage was read
+        let usage = match self.stats {
+            Some(_) => self.text(handlebars).unwrap_or_else(|e| {
+                eprintln!("{e}");
+                "999%".to_string()
+            }),
+            None => String::new(),
+        };
+        let level = self
+            .stats
+            .as_ref()
+            .and_then(|stats| self.thresholds.color(stats.usage() as f32));
 
         list![
             anchor,
             container(
-                text!("{}", self.icon.as_ref().unwrap_or(&"󰍛".to_string()))
-                    .fill(anchor)
-                    .size(self.cfg_override.icon_size.unwrap_or(config.icon_size))
-                    .color(self.cfg_override.icon_color.unwrap_or(config.icon_color))
-                    .font(NERD_FONT)
+                text(
+                    self.cfg_override
+                        .icon(config, self.icon.as_deref().unwrap_or("󰍛"))
+                )
+                .fill(anchor)
+                .size(self.cfg_override.icon_size.unwrap_or(config.icon_size))
+                .color(level.unwrap_or(self.cfg_override.icon_color.unwrap_or(config.icon_color)))
+                .font(config.icon_font)
             )
             .padding(self.cfg_override.icon_margin.unwrap_or(config.icon_margin)),
             container(
-                text!["{}%", usage]
-                    .fill(anchor)
-                    .size(self.cfg_override.font_size.unwrap_or(config.font_size))
-                    .color(self.cfg_override.text_color.unwrap_or(config.text_color))
+                bar_text(
+                    usage,
+                    anchor,
+                    self.cfg_override.vertical_text(config, anchor)
+                )
+                .size(self.cfg_override.font_size.unwrap_or(config.font_size))
+                .font(self.cfg_override.text_font.unwrap_or(config.text_font))
+                .color(level.unwrap_or(self.cfg_override.text_color.unwrap_or(config.text_color)))
             )
             .padding(self.cfg_override.text_margin.unwrap_or(config.text_margin)),
         ]
@@ -79,11 +140,156 @@ impl Module for MemoryMod {
         &mut self,
         config: &HashMap<String, Option<String>>,
         _popup_config: &HashMap<String, Option<String>>,
-        _templates: &mut Handlebars,
+        templates: &mut Handlebars,
+        scale: f32,
     ) {
-        self.cfg_override = config.into();
+        self.cfg_override = (config, scale).into();
         self.icon = config.get("icon").and_then(|v| v.clone());
+        self.thresholds = Thresholds::read(config.get("thresholds").and_then(|v| v.as_deref()));
+        templates
+            .register_template_string(
+                "memory",
+                config
+                    .get("format")
+                    .unescape()
+                    .unwrap_or("{{usage}}%".to_string()),
+            )
+            .unwrap_or_else(|e| eprintln!("Failed to parse memory format: {e}"));
+    }
+
+    fn known_keys(&self) -> Vec<ConfigKey> {
+        vec![
+            ConfigKey::new("icon", Kind::Text, None),
+            ConfigKey::new("thresholds", Kind::Text, None),
+            ConfigKey::new("format", Kind::Text, Some("{{usage}}%")),
+        ]
     }
 
     impl_on_click!();
+
+    fn collect_data(&mut self) -> Result<(), String> {
+        self.stats = Some(get_stats(&Host)?);
+        Ok(())
+    }
+
+    fn collect_data_from(&mut self, source: &dyn Source) -> Result<(), String> {
+        self.stats = Some(get_stats(&source)?);
+        Ok(())
+    }
+
+    fn text_content(&self, template: &Handlebars) -> Option<Result<String, String>> {
+        Some(self.text(template))
+    }
+
+    fn variables(&self) -> BTreeMap<String, Value> {
+        self.stats
+            .as_ref()
+            .map(|stats| stats.context())
+            .unwrap_or_default()
+    }
+
+    fn subscription(&self) -> Option<Subscription<Message>> {
+        Some(Subscription::run(|| {
+            stream::channel(1, |mut sender| async move {
+                loop {
+                    let stats = task::spawn_blocking(|| get_stats(&Host))
+                        .await
+                        .unwrap_or_else(|e| Err(e.to_string()));
+                    match stats {
+                        Ok(stats) => {
+                            if sender
+                                .send(Message::update(move |reg| {
+                                    reg.get_module_mut::<MemoryMod>().stats = Some(stats)
+                                }))
+                                .await
+                                .is_err()
+                            {
+                                return;
+                            }
+                        }
+                        Err(e) => eprintln!("{e}"),
+                    }
+                    sleep(Duration::from_secs(2)).await;
+                }
+            })
+        }))
+    }
+}
+
+/// Reads the `Mem:` line of `free -b`: total, used, free, shared, buff/cache and available
+fn get_stats(source: &impl Source) -> Result<MemoryStats, String> {
+    let stdout = source
+        .output("free", &["-b"])
+        .map_err(|e| format!("Failed to get memory usage. err: {e}"))?;
+    let values = stdout
+        .lines()
+        .find_map(|line| line.strip_prefix("Mem:"))
+        .ok_or("Failed to parse memory usage (output from free): no `Mem:` line")?
+        .split_whitespace()
+        .map(|value| value.parse::<u64>())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Failed to parse memory usage (output from free), e: {e}"))?;
+    match values[..] {
+        [total, used, free, _shared, _cache, available, ..] => Ok(MemoryStats {
+            total,
+            used,
+            free,
+            available,
+        }),
+        _ => Err("Failed to parse memory usage (output from free): too few values".to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{helpers::source::Fixtures, modules::testing::Harness};
+
+    use super::*;
+
+    const FREE: &str =
+        "               total        used        free      shared  buff/cache   available
+Mem:     17179869184  4294967296  8589934592   104857600  4294967296 12884901888
+Swap:              0           0           0
+";
+
+    fn free(output: &str) -> Fixtures {
+        Fixtures::default().output("free -b", output)
+    }
+
+    #[test]
+    fn renders_the_format() {
+        let stats = get_stats(&free(FREE)).unwrap();
+        let mut harness = Harness::new::<MemoryMod>(&[("format", "{{usage}}% ({{bytes used}})")]);
+        harness.update(|m: &mut MemoryMod| m.stats = Some(stats));
+        assert_eq!(harness.text::<MemoryMod>(), "25% (4.0 GiB)");
+    }
+
+    #[test]
+    fn instances_render_their_own_format() {
+        let mut harness = Harness::new::<MemoryMod>(&[]);
+        harness.add_instance("memory.used", &[("format", "{{bytes used}} used")]);
+        harness.add_instance("memory.free", &[("format", "{{bytes free}} free")]);
+        for name in ["memory", "memory.used", "memory.free"] {
+            let stats = get_stats(&free(FREE)).unwrap();
+            harness.update_instance(name, |m: &mut MemoryMod| m.stats = Some(stats));
+        }
+        assert_eq!(harness.instance_text("memory.used"), "4.0 GiB used");
+        assert_eq!(harness.instance_text("memory.free"), "8.0 GiB free");
+        assert_eq!(harness.text::<MemoryMod>(), "25%");
+    }
+
+    #[test]
+    fn no_text_before_the_first_reading() {
+        let harness = Harness::new::<MemoryMod>(&[]);
+        let m = harness.module::<MemoryMod>();
+        assert!(m.text_content(&Handlebars::new()).unwrap().is_err());
+    }
+
+    #[test]
+    fn rejects_unexpected_output() {
+        assert!(get_stats(&free("Swap: 0 0 0\n")).is_err());
+        assert!(get_stats(&free("Mem: 1 2 3\n")).is_err());
+        assert!(get_stats(&Fixtures::default()).is_err());
+        assert_eq!(MemoryStats::default().usage(), 0);
+    }
 }
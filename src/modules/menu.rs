@@ -0,0 +1,324 @@
+use std::collections::HashMap;
+
+use bar_rs_derive::Builder;
+use handlebars::Handlebars;
+use iced::widget::{button::Style, container, row, scrollable, text, Column, Container};
+use iced::{mouse, Alignment, Element, Event, Length::Fill};
+
+use crate::bar_text::bar_text;
+use crate::button::button;
+use crate::config::parse::StringExt;
+use crate::config::popup_config::{PopupConfig, PopupConfigOverride};
+use crate::{
+    config::{
+        anchor::BarAnchor,
+        module_config::{LocalModuleConfig, ModuleConfigOverride},
+        schema::{ConfigKey, Kind},
+    },
+    fill::FillExt,
+    impl_on_click, impl_wrapper, Message,
+};
+
+use super::{click_message, Action, CommandAction, Module};
+
+#[derive(Debug)]
+struct MenuEntry {
+    label: String,
+    icon: Option<String>,
+    command: CommandAction,
+    /// Whether the entry has to be confirmed before running the command
+    confirm: bool,
+}
+
+#[derive(Debug, Builder)]
+pub struct MenuMod {
+    cfg_override: ModuleConfigOverride,
+    popup_cfg_override: PopupConfigOverride,
+    icon: String,
+    label: Option<String>,
+    entries: Vec<MenuEntry>,
+    /// The entry waiting for confirmation
+    confirming: Option<usize>,
+}
+
+impl Default for MenuMod {
+    fn default() -> Self {
+        Self {
+            cfg_override: Default::default(),
+            popup_cfg_override: PopupConfigOverride {
+                width: Some(200),
+                height: Some(200),
+                ..Default::default()
+            },
+            icon: "󰍜".to_string(),
+            label: None,
+            entries: Vec::new(),
+            confirming: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum MenuAction {
+    Confirm(usize),
+    Cancel,
+}
+
+impl Action for MenuAction {
+    fn as_message(&self) -> Message {
+        let action = *self;
+        Message::update(move |reg| reg.get_module_mut::<MenuMod>().handle_action(&action))
+    }
+}
+
+impl MenuMod {
+    /// Runs the command of the entry and closes the menu
+    fn run(&self, entry: &MenuEntry) -> Message {
+        Message::Batch(vec![
+            MenuAction::Cancel.as_message(),
+            entry.command.as_message(),
+            Message::ClosePopup,
+        ])
+    }
+}
+
+impl Module for MenuMod {
+    fn name(&self) -> String {
+        "menu".to_string()
+    }
+
+    fn view(
+        &self,
+        config: &LocalModuleConfig,
+        popup_config: &PopupConfig,
+        anchor: &BarAnchor,
+        _handlebars: &Handlebars,
+    ) -> Element<Message> {
+        let popup = Message::popup::<Self>(
+            self.popup_cfg_override.width.unwrap_or(popup_config.width),
+            self.popup_cfg_override
+                .height
+                .unwrap_or(popup_config.height),
+            anchor,
+        );
+        // Left clicks open the popup, the other buttons keep their on_click actions
+        let middle = click_message(self, mouse::Button::Middle, config);
+        let right = click_message(self, mouse::Button::Right, config);
+        button(
+            list![
+                anchor,
+                container(
+                    text(self.cfg_override.icon(config, &self.icon))
+                        .size(self.cfg_override.icon_size.unwrap_or(config.icon_size))
+                        .color(self.cfg_override.icon_color.unwrap_or(config.icon_color))
+                        .font(config.icon_font)
+                        .fill(anchor)
+                )
+                .fill(anchor)
+                .padding(self.cfg_override.icon_margin.unwrap_or(config.icon_margin)),
+            ]
+            .push_maybe(self.label.as_ref().map(|label| {
+                container(
+                    bar_text(
+                        label,
+                        anchor,
+                        self.cfg_override.vertical_text(config, anchor),
+                    )
+                    .size(self.cfg_override.font_size.unwrap_or(config.font_size))
+                    .font(self.cfg_override.text_font.unwrap_or(config.text_font))
+                    .color(self.cfg_override.text_color.unwrap_or(config.text_color)),
+                )
+                .fill(anchor)
+                .padding(self.cfg_override.text_margin.unwrap_or(config.text_margin))
+            }))
+            .spacing(self.cfg_override.spacing.unwrap_or(config.spacing)),
+        )
+        .on_event_try(
+            move |event, layout, cursor, clipboard, viewport| match event {
+                Event::Mouse(mouse::Event::ButtonReleased(mouse::Button::Middle)) => middle.clone(),
+                Event::Mouse(mouse::Event::ButtonReleased(mouse::Button::Right)) => right.clone(),
+                // A confirmation left open when the menu was closed is dropped
+                _ => Some(Message::Batch(vec![
+                    MenuAction::Cancel.as_message(),
+                    popup(event, layout, cursor, clipboard, viewport),
+                ])),
+            },
+        )
+        .style(|_, _| Style::default())
+        .into()
+    }
+
+    fn popup_cfg_override(&self) -> Option<&PopupConfigOverride> {
+        Some(&self.popup_cfg_override)
+    }
+
+    fn popup_view<'a>(
+        &'a self,
+        config: &'a PopupConfig,
+        _template: &Handlebars,
+    ) -> Element<'a, Message> {
+        let fmt_text = |content: String| -> Container<'a, Message> {
+            container(
+                text(content)
+                    .size(
+                        self.popup_cfg_override
+                            .font_size
+                            .unwrap_or(config.font_size),
+                    )
+                    .color(
+                        self.popup_cfg_override
+                            .text_color
+                            .unwrap_or(config.text_color),
+                    ),
+            )
+            .padding(
+                self.popup_cfg_override
+                    .text_margin
+                    .unwrap_or(config.text_margin),
+            )
+        };
+        let icon = |icon: &'a str| -> Container<'a, Message> {
+            container(
+                text(config.icon(icon))
+                    .font(config.icon_font)
+                    .size(
+                        self.popup_cfg_override
+                            .icon_size
+                            .unwrap_or(config.icon_size),
+                    )
+                    .color(
+                        self.popup_cfg_override
+                            .icon_color
+                            .unwrap_or(config.icon_color),
+                    ),
+            )
+            .padding(
+                self.popup_cfg_override
+                    .icon_margin
+                    .unwrap_or(config.icon_margin),
+            )
+        };
+
+        let entries =
+            self.entries
+                .iter()
+                .enumerate()
+                .map(|(index, entry)| -> Element<'a, Message> {
+                    if self.confirming == Some(index) {
+                        return row![
+                            fmt_text(format!("{}?", entry.label)).width(Fill),
+                            button(fmt_text("Yes".to_string()))
+                                .on_event(self.run(entry))
+                                .style(|_, _| Style::default()),
+                            button(fmt_text("No".to_string()))
+                                .on_event(MenuAction::Cancel.as_message())
+                                .style(|_, _| Style::default()),
+                        ]
+                        .align_y(Alignment::Center)
+                        .into();
+                    }
+                    button(
+                        row![]
+                            .push_maybe(entry.icon.as_deref().map(icon))
+                            .push(fmt_text(entry.label.clone()).width(Fill))
+                            .align_y(Alignment::Center),
+                    )
+                    .on_event(match entry.confirm {
+                        true => MenuAction::Confirm(index).as_message(),
+                        false => self.run(entry),
+                    })
+                    .style(|_, _| Style::default())
+                    .width(Fill)
+                    .into()
+                });
+
+        container(scrollable(Column::with_children(entries).spacing(
+            self.popup_cfg_override.spacing.unwrap_or(config.spacing),
+        )))
+        .into()
+    }
+
+    impl_wrapper!();
+
+    fn read_config(
+        &mut self,
+        config: &HashMap<String, Option<String>>,
+        popup_config: &HashMap<String, Option<String>>,
+        _templates: &mut Handlebars,
+        scale: f32,
+    ) {
+        let default = Self::default();
+        self.cfg_override = (config, scale).into();
+        self.popup_cfg_override.update(popup_config, scale);
+        self.icon = config
+            .get("icon")
+            .and_then(|v| v.clone())
+            .unwrap_or(default.icon);
+        self.label = config.get("label").and_then(|v| v.clone());
+        // Option names are lowercase, so `entries` defines the order and spelling of the labels
+        let order: Vec<String> = config
+            .get("entries")
+            .and_then(|v| v.as_ref())
+            .map(|v| v.split(',').map(|e| e.trim().to_string()).collect())
+            .unwrap_or_default();
+        let mut entries = config
+            .iter()
+            .filter_map(|(key, command)| {
+                let name = key.strip_prefix("entry.")?;
+                let option = |option: &str| config.get(&format!("{option}.{name}"));
+                Some(MenuEntry {
+                    label: order
+                        .iter()
+                        .find(|label| label.to_lowercase() == name)
+                        .cloned()
+                        .unwrap_or(name.to_string()),
+                    icon: option("icon").and_then(|v| v.clone()),
+                    command: CommandAction(command.clone()?),
+                    confirm: option("confirm")
+                        .and_then(|v| v.into_bool())
+                        .unwrap_or(false),
+                })
+            })
+            .collect::<Vec<_>>();
+        entries.sort_by_key(|entry| {
+            (
+                order
+                    .iter()
+                    .position(|label| *label == entry.label)
+                    .unwrap_or(order.len()),
+                entry.label.clone(),
+            )
+        });
+        self.entries = entries;
+        self.confirming = None;
+    }
+
+    fn known_keys(&self) -> Vec<ConfigKey> {
+        vec![
+            ConfigKey::new("icon", Kind::Text, Some("󰍜")),
+            ConfigKey::new("label", Kind::Text, None),
+            ConfigKey::new("entries", Kind::Text, None),
+            ConfigKey::new("entry.*", Kind::Text, None),
+            ConfigKey::new("icon.*", Kind::Text, None),
+            ConfigKey::new("confirm.*", Kind::Bool, None),
+        ]
+    }
+
+    impl_on_click!();
+
+    fn has_popup(&self) -> bool {
+        true
+    }
+
+    fn handle_action(&mut self, action: &dyn Action) {
+        match action.downcast_ref::<MenuAction>() {
+            Some(MenuAction::Confirm(index)) => self.confirming = Some(*index),
+            Some(MenuAction::Cancel) => self.confirming = None,
+            None => {}
+        }
+    }
+
+    fn text_content(&self, _template: &Handlebars) -> Option<Result<String, String>> {
+        self.label.clone().map(Ok)
+    }
+}
@@ -1,54 +1,122 @@
 use std::{
     any::{Any, TypeId},
-    collections::HashMap,
+    collections::{BTreeMap, HashMap},
     fmt::Debug,
 };
 
 use battery::BatteryMod;
 use bluetooth::BluetoothMod;
+use caffeine::CaffeineMod;
+use clipboard::ClipboardMod;
 use cpu::CpuMod;
+use cpu_freq::CpuFreqMod;
 use date::DateMod;
+use dbus::DbusMod;
 use disk_usage::DiskUsageMod;
+use dnd::DndMod;
+use docker::DockerMod;
 use downcast_rs::{impl_downcast, Downcast};
 use empty::EmptyModule;
+use focus::FocusMod;
+use gpu_memory::GpuMemoryMod;
 use handlebars::Handlebars;
-use hyprland::{window::HyprWindowMod, workspaces::HyprWorkspaceMod};
+use hyprland::{
+    scratchpad::HyprScratchpadMod, submap::HyprSubmapMod, window::HyprWindowMod,
+    workspaces::HyprWorkspaceMod,
+};
 use iced::{
+    mouse::ScrollDelta,
     theme::Palette,
-    widget::{container, Container},
-    Alignment, Color, Event, Theme,
+    widget::{container, stack, text, Container},
+    Alignment, Background, Color, Event, Theme,
 };
 use iced::{widget::container::Style, Element, Subscription};
+use ime::ImeMod;
+use lock_keys::LockKeysMod;
+use mail::MailMod;
 use media::MediaMod;
 use memory::MemoryMod;
+use menu::MenuMod;
 use niri::{NiriWindowMod, NiriWorkspaceMod};
+use notifications::NotificationsMod;
+use pipe::PipeMod;
+use power_profile::PowerProfileMod;
+use recording::RecordingMod;
+use serde_json::Value;
+use session::SessionMod;
+use spacer::{SpacerMod, StretchMod};
+use sys_tray::SysTrayMod;
+use systemd::SystemdMod;
 use time::TimeMod;
+use updates::UpdatesMod;
+use uptime::UptimeMod;
 use volume::VolumeMod;
 use wayfire::{WayfireWindowMod, WayfireWorkspaceMod};
 
 use crate::{
-    config::{anchor::BarAnchor, module_config::LocalModuleConfig, popup_config::PopupConfig},
+    border::with_sides,
+    config::{
+        anchor::BarAnchor,
+        module_config::{LocalModuleConfig, ModuleConfigOverride},
+        popup_config::{PopupConfig, PopupConfigOverride},
+        schedule::Schedule,
+        schema::ConfigKey,
+    },
     fill::FillExt,
+    helpers::{
+        health,
+        scroll::ScrollDirection,
+        source::Source,
+        status::{ModuleStatus, OnError},
+    },
+    list::min_length,
     listeners::Listener,
+    pin::pin_button,
     registry::Registry,
+    tooltip::ElementExt,
     Message,
 };
 
 pub mod battery;
 pub mod bluetooth;
+pub mod caffeine;
+pub mod clipboard;
 pub mod cpu;
+pub mod cpu_freq;
 pub mod date;
+pub mod dbus;
 pub mod disk_usage;
+pub mod dnd;
+pub mod docker;
 pub mod empty;
+pub mod focus;
+pub mod gpu_memory;
 pub mod hyprland;
+pub mod ime;
+pub mod lock_keys;
+pub mod mail;
 pub mod media;
 pub mod memory;
+pub mod menu;
 pub mod niri;
+pub mod notifications;
+pub mod pipe;
+pub mod power_profile;
+pub mod recording;
+pub mod session;
+pub mod spacer;
 pub mod sys_tray;
+pub mod systemd;
+#[cfg(test)]
+pub mod testing;
 pub mod time;
+pub mod updates;
+pub mod uptime;
 pub mod volume;
 pub mod wayfire;
 
+static STATUS_OK: ModuleStatus = ModuleStatus::Ok;
+
 pub trait Module: Any + Debug + Send + Sync + Downcast {
     /// The name used to enable the Module in the config.
     fn name(&self) -> String;
@@ -56,6 +124,33 @@ pub trait Module: Any + Debug + Send + Sync + Downcast {
     fn active(&self) -> bool {
         true
     }
+    /// Whether the backend of the module works. While it doesn't, the module is hidden or shows
+    /// the error icon, depending on `on_error`.
+    fn status(&self) -> &ModuleStatus {
+        &STATUS_OK
+    }
+    /// Called by subscriptions once the backend fails or works again, see [send_status].
+    /// Modules keeping a `status` field implement this with [impl_status], others only log the
+    /// error.
+    ///
+    /// [send_status]: crate::helpers::status::send_status
+    fn set_status(&mut self, status: ModuleStatus) {
+        if let Some(reason) = status.reason() {
+            eprintln!("[{}] {reason}", self.name());
+        }
+    }
+    /// The options of the module's config section which override `[module_style]`, provided by
+    /// [impl_wrapper].
+    fn cfg_override(&self) -> Option<&ModuleConfigOverride> {
+        None
+    }
+    /// Whether the current time is within the module's `visible_between`, outside of it the
+    /// module is hidden regardless of [Module::active] and its status.
+    fn scheduled(&self) -> bool {
+        self.cfg_override()
+            .and_then(|c| c.visible_between.as_ref())
+            .map_or(true, Schedule::now)
+    }
     /// What the module actually shows.
     /// See [widgets-and-elements](https://docs.iced.rs/iced/#widgets-and-elements).
     fn view(
@@ -73,20 +168,50 @@ pub trait Module: Any + Debug + Send + Sync + Downcast {
         content: Element<'a, Message>,
         anchor: &BarAnchor,
     ) -> Element<'a, Message> {
-        container(
-            container(content)
-                .fill(anchor)
-                .padding(config.padding)
-                .style(|_| Style {
-                    background: config.background,
-                    border: config.border,
-                    ..Default::default()
-                }),
-        )
+        container(with_sides(
+            container(min_length(
+                anchor,
+                content,
+                config.min_width,
+                config.content_align,
+            ))
+            .fill(anchor)
+            .padding(config.padding)
+            .style(|_| Style {
+                background: config.background,
+                border: config.border,
+                ..Default::default()
+            }),
+            &config.border_sides,
+            config.border.color,
+        ))
         .fill(anchor)
         .padding(config.margin)
         .into()
     }
+    /// A background temporarily replacing the configured one, e.g. to flash the module. Used by
+    /// [impl_wrapper].
+    fn background_override(&self) -> Option<Background> {
+        None
+    }
+    /// The share of the free space along the bar this module takes up, relative to the other
+    /// stretching modules of the same section. `None` for modules which are as large as their
+    /// content.
+    fn stretch(&self) -> Option<u16> {
+        None
+    }
+    /// Whether the module is only empty space, which already separates its neighbors, so the
+    /// `separator` isn't drawn next to it
+    fn spacer(&self) -> bool {
+        false
+    }
+    /// Whether the updates of this module's subscription are shown right away, instead of
+    /// together with the other updates within the next frame (see `redraw_interval`). Meant for
+    /// modules reacting to the user, like the volume changed by scrolling: the feedback to
+    /// scrolling or pressing a key shouldn't lag behind.
+    fn urgent_updates(&self) -> bool {
+        false
+    }
     /// The module may optionally have a subscription listening for external events.
     /// See [passive-subscriptions](https://docs.iced.rs/iced/#passive-subscriptions).
     fn subscription(&self) -> Option<Subscription<Message>> {
@@ -98,14 +223,26 @@ pub trait Module: Any + Debug + Send + Sync + Downcast {
         vec![]
     }
     #[allow(unused_variables)]
-    /// Read configuration options from the config section of this module
+    /// Read configuration options from the config section of this module. Sizes are multiplied
+    /// with `scale`, the resolved `scale` of `[general]`.
     fn read_config(
         &mut self,
         config: &HashMap<String, Option<String>>,
         popup_config: &HashMap<String, Option<String>>,
         templates: &mut Handlebars,
+        scale: f32,
     ) {
     }
+    /// The options [Module::read_config] reads from the `[module:{{name}}]` section, besides the
+    /// ones every module has. Used to report unknown options and invalid values.
+    fn known_keys(&self) -> Vec<ConfigKey> {
+        vec![]
+    }
+    /// The options [Module::read_config] reads from the `[module_popup:{{name}}]` section,
+    /// besides the ones of `[popup_style]`
+    fn known_popup_keys(&self) -> Vec<ConfigKey> {
+        vec![]
+    }
     #[allow(unused_variables)]
     /// The action to perform on a on_click event
     fn on_click<'a>(
@@ -115,10 +252,77 @@ pub trait Module: Any + Debug + Send + Sync + Downcast {
     ) -> Option<&'a dyn Action> {
         None
     }
+    /// Actions used for the mouse buttons which have no action configured, e.g. to open the
+    /// settings app of this module
+    fn default_action(&self) -> Option<&OnClickAction> {
+        None
+    }
+    /// Whether clicking the module opens its popup (unless `on_click` is set)
+    fn has_popup(&self) -> bool {
+        false
+    }
+    /// Whether any mouse button has an action, which makes the module show a pointer cursor and
+    /// the hover and pressed backgrounds.
+    fn has_action(&self, config: &LocalModuleConfig) -> bool {
+        self.has_popup() || has_click_action(self, config)
+    }
     #[allow(unused_variables, dead_code)]
     /// Handle an action (likely produced by a user interaction).
     fn handle_action(&mut self, action: &dyn Action) {}
     #[allow(unused_variables)]
+    /// The message produced by scrolling over the module, if any.
+    fn on_scroll(&self, delta: ScrollDelta) -> Option<Message> {
+        None
+    }
+    /// The action bound to scrolling in `direction` with `on_scroll_up` or `on_scroll_down`,
+    /// which takes precedence over [Module::on_scroll].
+    fn scroll_binding<'a>(
+        &'a self,
+        direction: ScrollDirection,
+        config: &'a LocalModuleConfig,
+    ) -> Option<&'a dyn Action> {
+        self.cfg_override()
+            .and_then(|cfg| cfg.action.as_ref())
+            .unwrap_or(&config.action)
+            .scroll(direction)
+    }
+    /// Runtime state (e.g. toggled by clicking) which should survive a restart if
+    /// `persist_state` is enabled. `Value::Null` if there is none.
+    fn save_state(&self) -> Value {
+        Value::Null
+    }
+    #[allow(unused_variables)]
+    /// Restore the state [Module::save_state] returned on the last exit.
+    fn load_state(&mut self, state: Value) {}
+    /// Collect the data this module shows once, without the iced event loop (used by
+    /// `bar-rs check`). Modules which get their data inside of `view` don't need this.
+    fn collect_data(&mut self) -> Result<(), String> {
+        Ok(())
+    }
+    #[allow(unused_variables)]
+    /// Like [Module::collect_data], but from a snapshot of a system instead of the one bar-rs
+    /// runs on (used by `bar-rs render --snapshot`). Modules which don't implement it fail, so
+    /// that they show what they would show without a working backend.
+    fn collect_data_from(&mut self, source: &dyn Source) -> Result<(), String> {
+        Err("the module can't read its data from a snapshot".to_string())
+    }
+    #[allow(unused_variables)]
+    /// The text this module currently shows, without building any widgets.
+    /// `None` if the module doesn't show any text.
+    fn text_content(&self, template: &Handlebars) -> Option<Result<String, String>> {
+        None
+    }
+    /// The variables the `format` of this module can use, with their current values. They are
+    /// exported over D-Bus for other programs.
+    fn variables(&self) -> BTreeMap<String, Value> {
+        BTreeMap::new()
+    }
+    /// The popup settings of this module, merged with `[popup_style]` by [Module::popup_wrapper]
+    /// and [Module::popup_theme]
+    fn popup_cfg_override(&self) -> Option<&PopupConfigOverride> {
+        None
+    }
+    #[allow(unused_variables)]
     /// The view of a popup
     fn popup_view<'a>(
         &'a self,
@@ -127,12 +331,13 @@ pub trait Module: Any + Debug + Send + Sync + Downcast {
     ) -> Element<'a, Message> {
         "Missing implementation".into()
     }
-    /// The wrapper around a popup
+    /// The wrapper around a popup, with a pin button in its corner unless `pinned` is `None`
     fn popup_wrapper<'a>(
         &'a self,
         config: &'a PopupConfig,
         anchor: &BarAnchor,
         template: &Handlebars,
+        pinned: Option<bool>,
     ) -> Element<'a, Message> {
         let align = |elem: Container<'a, Message>| -> Container<'a, Message> {
             match anchor {
@@ -142,18 +347,60 @@ pub trait Module: Any + Debug + Send + Sync + Downcast {
                 BarAnchor::Right => elem.align_x(Alignment::End),
             }
         };
-        align(container(self.popup_view(config, template)).fill(anchor)).into()
+        let cfg_override = self.popup_cfg_override();
+        let border = cfg_override.and_then(|c| c.border).unwrap_or(config.border);
+        let view = self.popup_view(config, template);
+        let view = match pinned {
+            Some(pinned) => stack![view, pin_button(pinned, config)].into(),
+            None => view,
+        };
+        let content = container(view)
+            .padding(
+                cfg_override
+                    .and_then(|c| c.padding)
+                    .unwrap_or(config.padding),
+            )
+            .style(move |_| container::Style {
+                background: Some(
+                    cfg_override
+                        .and_then(|c| c.background)
+                        .unwrap_or(config.background),
+                ),
+                border,
+                ..Default::default()
+            })
+            .fill_maybe(
+                cfg_override
+                    .and_then(|c| c.fill_content_to_size)
+                    .unwrap_or(config.fill_content_to_size),
+            );
+        let content = with_sides(
+            content,
+            &cfg_override
+                .and_then(|c| c.border_sides)
+                .unwrap_or(config.border_sides),
+            border.color,
+        );
+        align(container(content).fill(anchor)).into()
     }
-    /// The theme of a popup
-    fn popup_theme(&self) -> Theme {
+    /// The theme of a popup, the window itself stays transparent since [Module::popup_wrapper]
+    /// draws the background
+    fn popup_theme(&self, config: &PopupConfig) -> Theme {
+        let cfg_override = self.popup_cfg_override();
+        let text = cfg_override
+            .and_then(|c| c.text_color)
+            .unwrap_or(config.text_color);
+        let icon = cfg_override
+            .and_then(|c| c.icon_color)
+            .unwrap_or(config.icon_color);
         Theme::custom(
-            "Default popup theme".to_string(),
+            "Popup theme".to_string(),
             Palette {
                 background: Color::TRANSPARENT,
-                text: Color::WHITE,
-                primary: Color::WHITE,
-                success: Color::WHITE,
-                danger: Color::WHITE,
+                text,
+                primary: icon,
+                success: text,
+                danger: text,
             },
         )
     }
@@ -167,7 +414,13 @@ impl_downcast!(Action);
 
 impl From<&String> for Box<dyn Action> {
     fn from(value: &String) -> Box<dyn Action> {
-        Box::new(CommandAction(value.clone()))
+        if let Some(group) = value.strip_prefix("toggle_group:") {
+            return Box::new(ToggleGroupAction(group.trim().to_string()));
+        }
+        match value.strip_prefix("profile:") {
+            Some(profile) => Box::new(ProfileAction(profile.trim().to_string())),
+            None => Box::new(CommandAction(value.clone())),
+        }
     }
 }
 
@@ -180,14 +433,77 @@ impl Action for CommandAction {
     }
 }
 
+/// Hides or shows all modules of a group defined in the `[groups]` section
+#[derive(Debug, Clone)]
+pub struct ToggleGroupAction(pub String);
+
+impl Action for ToggleGroupAction {
+    fn as_message(&self) -> Message {
+        let group = self.0.clone();
+        Message::update(move |reg| {
+            let hidden = reg.toggle_group(&group);
+            reg.instances_mut::<FocusMod>()
+                .for_each(|m| m.group_toggled(&group, hidden));
+        })
+    }
+}
+
+/// Switches to a profile defined by a `[profile:{name}]` section
+#[derive(Debug, Clone)]
+pub struct ProfileAction(pub String);
+
+impl Action for ProfileAction {
+    fn as_message(&self) -> Message {
+        Message::SwitchProfile(self.0.clone())
+    }
+}
+
+/// The actions bound to the mouse buttons and the scroll wheel
 #[derive(Debug, Default)]
 pub struct OnClickAction {
     pub left: Option<Box<dyn Action>>,
     pub center: Option<Box<dyn Action>>,
     pub right: Option<Box<dyn Action>>,
+    pub scroll_up: Option<Box<dyn Action>>,
+    pub scroll_down: Option<Box<dyn Action>>,
 }
 
 impl OnClickAction {
+    /// Reads the bindings from the values `get` returns for their keys, `None` if none is set.
+    /// `on_click`, `on_middle_click` and `on_right_click` are still accepted for the mouse
+    /// buttons.
+    pub fn read(get: impl Fn(&str) -> Option<String>) -> Option<Self> {
+        let binding = |keys: &[&str]| -> Option<Box<dyn Action>> {
+            keys.iter()
+                .find_map(|key| get(key))
+                .map(|cmd| (&cmd).into())
+        };
+        let action = Self {
+            left: binding(&["on_click_left", "on_click"]),
+            center: binding(&["on_click_middle", "on_middle_click"]),
+            right: binding(&["on_click_right", "on_right_click"]),
+            scroll_up: binding(&["on_scroll_up"]),
+            scroll_down: binding(&["on_scroll_down"]),
+        };
+        [
+            &action.left,
+            &action.center,
+            &action.right,
+            &action.scroll_up,
+            &action.scroll_down,
+        ]
+        .iter()
+        .any(|binding| binding.is_some())
+        .then_some(action)
+    }
+
+    pub fn scroll(&self, direction: ScrollDirection) -> Option<&dyn Action> {
+        match direction {
+            ScrollDirection::Up => self.scroll_up.as_deref(),
+            ScrollDirection::Down => self.scroll_down.as_deref(),
+        }
+    }
+
     pub fn event(&self, event: Event) -> Option<&dyn Action> {
         match event {
             Event::Mouse(iced::mouse::Event::ButtonReleased(iced::mouse::Button::Left)) => {
@@ -204,6 +520,146 @@ impl OnClickAction {
     }
 }
 
+/// The message of the on_click action of `module` for the given mouse button. Used by modules
+/// whose content is a button capturing all clicks (e.g. to open a popup), which therefore never
+/// reach the wrapper.
+pub fn click_message<M>(
+    module: &M,
+    button: iced::mouse::Button,
+    config: &LocalModuleConfig,
+) -> Option<Message>
+where
+    M: Module,
+{
+    module
+        .on_click(
+            Event::Mouse(iced::mouse::Event::ButtonReleased(button)),
+            config,
+        )
+        .map(|action| Message::Click(Box::new(action.as_message())))
+}
+
+/// The message of scrolling over `module`: the action bound to the direction, otherwise the one
+/// of [Module::on_scroll]
+pub fn scroll_message<M>(
+    module: &M,
+    delta: ScrollDelta,
+    config: &LocalModuleConfig,
+) -> Option<Message>
+where
+    M: Module + ?Sized,
+{
+    ScrollDirection::from_delta(delta)
+        .and_then(|direction| module.scroll_binding(direction, config))
+        .map(|action| action.as_message())
+        .or_else(|| module.on_scroll(delta))
+}
+
+/// The module as shown in the bar, taking its status into account. `None` if it's hidden.
+/// A panic while building the view only breaks this module instance (named `name`), which shows
+/// the error icon instead.
+pub fn module_view<'a>(
+    name: &str,
+    module: &'a dyn Module,
+    config: &'a LocalModuleConfig,
+    popup_config: &'a PopupConfig,
+    anchor: &BarAnchor,
+    templates: &'a Handlebars,
+) -> Option<Element<'a, Message>> {
+    if health::is_disabled(name) || !module.scheduled() {
+        return None;
+    }
+    let cfg_override = module.cfg_override();
+    let error_icon = |reason: String| -> Element<'a, Message> {
+        let icon = cfg_override
+            .and_then(|c| c.error_icon.as_deref())
+            .unwrap_or(&config.error_icon);
+        let icon = match cfg_override {
+            Some(c) => c.icon(config, icon),
+            None => icon,
+        };
+        container(
+            text(icon)
+                .fill(anchor)
+                .size(
+                    cfg_override
+                        .and_then(|c| c.icon_size)
+                        .unwrap_or(config.icon_size),
+                )
+                .color(
+                    cfg_override
+                        .and_then(|c| c.error_color)
+                        .unwrap_or(config.error_color),
+                )
+                .font(config.icon_font),
+        )
+        .padding(
+            cfg_override
+                .and_then(|c| c.icon_margin)
+                .unwrap_or(config.icon_margin),
+        )
+        .tooltip(text(reason).size(12))
+        .into()
+    };
+    let view = || {
+        health::catch(name, || {
+            module.view(config, popup_config, anchor, templates)
+        })
+    };
+    // The last known data, marked by the error icon
+    let stale = |reason: String| -> Element<'a, Message> {
+        match module.active().then(view) {
+            Some(Ok(view)) => list![anchor, view, error_icon(reason)]
+                .spacing(
+                    cfg_override
+                        .and_then(|c| c.spacing)
+                        .unwrap_or(config.spacing),
+                )
+                .into(),
+            Some(Err(crash)) => error_icon(format!("{name}: {crash}")),
+            None => error_icon(reason),
+        }
+    };
+    let content = match (module.status().reason(), health::failure(name)) {
+        (None, None) => match module.active() {
+            true => view().unwrap_or_else(|crash| error_icon(format!("{name}: {crash}"))),
+            false => return None,
+        },
+        // The subscription failed and is restarted
+        (None, Some(failure)) => stale(format!("{name}: {failure}, restarting")),
+        (Some(reason), _) => match cfg_override
+            .and_then(|c| c.on_error)
+            .unwrap_or(config.on_error)
+        {
+            OnError::Hide => return None,
+            OnError::Show => error_icon(reason.to_string()),
+            OnError::Stale => stale(reason.to_string()),
+        },
+    };
+    Some(module.wrapper(config, content, anchor))
+}
+
+/// Whether `on_click` returns an action for any mouse button
+pub fn has_click_action<M>(module: &M, config: &LocalModuleConfig) -> bool
+where
+    M: Module + ?Sized,
+{
+    [
+        iced::mouse::Button::Left,
+        iced::mouse::Button::Middle,
+        iced::mouse::Button::Right,
+    ]
+    .into_iter()
+    .any(|button| {
+        module
+            .on_click(
+                Event::Mouse(iced::mouse::Event::ButtonReleased(button)),
+                config,
+            )
+            .is_some()
+    })
+}
+
 pub fn require_listener<T>() -> TypeId
 where
     T: Listener,
@@ -213,8 +669,12 @@ where
 
 pub fn register_modules(registry: &mut Registry) {
     registry.register_module::<EmptyModule>();
+    registry.register_module::<SpacerMod>();
+    registry.register_module::<StretchMod>();
     registry.register_module::<CpuMod>();
+    registry.register_module::<CpuFreqMod>();
     registry.register_module::<MemoryMod>();
+    registry.register_module::<GpuMemoryMod>();
     registry.register_module::<BatteryMod>();
     registry.register_module::<BluetoothMod>();
     registry.register_module::<VolumeMod>();
@@ -222,36 +682,104 @@ pub fn register_modules(registry: &mut Registry) {
     registry.register_module::<DateMod>();
     registry.register_module::<TimeMod>();
     registry.register_module::<DiskUsageMod>();
+    registry.register_module::<PowerProfileMod>();
+    registry.register_module::<MenuMod>();
+    registry.register_module::<SessionMod>();
+    registry.register_module::<LockKeysMod>();
+    registry.register_module::<RecordingMod>();
+    registry.register_module::<SystemdMod>();
+    registry.register_module::<DndMod>();
+    registry.register_module::<NotificationsMod>();
+    registry.register_module::<CaffeineMod>();
+    registry.register_module::<DockerMod>();
+    registry.register_module::<UptimeMod>();
+    registry.register_module::<UpdatesMod>();
+    registry.register_module::<MailMod>();
+    registry.register_module::<ImeMod>();
+    registry.register_module::<ClipboardMod>();
+    registry.register_module::<PipeMod>();
+    registry.register_module::<DbusMod>();
+    registry.register_module::<FocusMod>();
+    registry.register_module::<SysTrayMod>();
     registry.register_module::<HyprWindowMod>();
     registry.register_module::<HyprWorkspaceMod>();
+    registry.register_module::<HyprSubmapMod>();
+    registry.register_module::<HyprScratchpadMod>();
     registry.register_module::<WayfireWorkspaceMod>();
     registry.register_module::<WayfireWindowMod>();
     registry.register_module::<NiriWorkspaceMod>();
     registry.register_module::<NiriWindowMod>();
 }
 
+/// Implements `wrapper` using the module's `cfg_override`, which is also made available to
+/// the bar through `Module::cfg_override`
 #[macro_export]
 macro_rules! impl_wrapper {
     () => {
+        fn cfg_override(&self) -> Option<&$crate::config::module_config::ModuleConfigOverride> {
+            Some(&self.cfg_override)
+        }
+
         fn wrapper<'a>(
             &'a self,
             config: &'a LocalModuleConfig,
             content: Element<'a, Message>,
             anchor: &BarAnchor,
         ) -> Element<'a, Message> {
-            iced::widget::container(
-                $crate::button::button(content)
-                    .fill(anchor)
-                    .padding(self.cfg_override.padding.unwrap_or(config.padding))
-                    .on_event_try(|evt, _, _, _, _| {
-                        self.on_click(evt, config).map(|evt| evt.as_message())
-                    })
-                    .style(|_, _| iced::widget::button::Style {
-                        background: self.cfg_override.background.unwrap_or(config.background),
-                        border: self.cfg_override.border.unwrap_or(config.border),
+            let border = self.cfg_override.border.unwrap_or(config.border);
+            let content = $crate::list::min_length(
+                anchor,
+                content,
+                self.cfg_override.min_width.unwrap_or(config.min_width),
+                self.cfg_override
+                    .content_align
+                    .unwrap_or(config.content_align),
+            );
+            let button = $crate::button::button(content)
+                .fill(anchor)
+                .padding(self.cfg_override.padding.unwrap_or(config.padding))
+                .style(move |_, status| {
+                    let background = self
+                        .background_override()
+                        .or(self.cfg_override.background.unwrap_or(config.background));
+                    let hover = self
+                        .cfg_override
+                        .hover_background
+                        .unwrap_or(config.hover_background);
+                    let pressed = self
+                        .cfg_override
+                        .pressed_background
+                        .unwrap_or(config.pressed_background);
+                    iced::widget::button::Style {
+                        background: match status {
+                            iced::widget::button::Status::Hovered => hover.or(background),
+                            iced::widget::button::Status::Pressed => {
+                                pressed.or(hover).or(background)
+                            }
+                            _ => background,
+                        },
+                        border,
                         ..Default::default()
+                    }
+                });
+            // Without an on_event handler, the button keeps the default cursor and isn't styled
+            // as hovered or pressed
+            let button =
+                button.on_scroll(|delta| $crate::modules::scroll_message(self, delta, config));
+            iced::widget::container($crate::border::with_sides(
+                match self.has_action(config) {
+                    true => button.on_event_try(|evt, _, _, _, _| {
+                        self.on_click(evt, config)
+                            .map(|evt| Message::Click(Box::new(evt.as_message())))
                     }),
-            )
+                    false => button,
+                },
+                &self
+                    .cfg_override
+                    .border_sides
+                    .unwrap_or(config.border_sides),
+                border.color,
+            ))
             .fill(anchor)
             .padding(self.cfg_override.margin.unwrap_or(config.margin))
             .into()
@@ -259,6 +787,21 @@ macro_rules! impl_wrapper {
     };
 }
 
+/// Implements `status` and `set_status` using the module's `status` field, so that failures of
+/// its subscription are shown in the bar
+#[macro_export]
+macro_rules! impl_status {
+    () => {
+        fn status(&self) -> &$crate::helpers::status::ModuleStatus {
+            &self.status
+        }
+
+        fn set_status(&mut self, status: $crate::helpers::status::ModuleStatus) {
+            self.status.update(&self.name(), status);
+        }
+    };
+}
+
 #[macro_export]
 macro_rules! impl_on_click {
     () => {
@@ -271,7 +814,8 @@ macro_rules! impl_on_click {
                 .action
                 .as_ref()
                 .unwrap_or(&config.action)
-                .event(event)
+                .event(event.clone())
+                .or_else(|| self.default_action().and_then(|action| action.event(event)))
         }
     };
 }
@@ -8,16 +8,18 @@ use iced::widget::{container, scrollable, text};
 use iced::Element;
 use niri_ipc::Window;
 
+use crate::bar_text::bar_text;
 use crate::button::button;
 use crate::config::popup_config::{PopupConfig, PopupConfigOverride};
-use crate::helpers::UnEscapeString;
+use crate::helpers::{window::WindowTitle, UnEscapeString};
+use crate::list::list;
 use crate::{
     config::{
         anchor::BarAnchor,
         module_config::{LocalModuleConfig, ModuleConfigOverride},
         parse::StringExt,
+        schema::{ConfigKey, Kind},
     },
-    fill::FillExt,
     listeners::niri::NiriListener,
     modules::{require_listener, Module},
     Message,
@@ -29,7 +31,7 @@ pub struct NiriWindowMod {
     // (title, app_id)
     pub windows: HashMap<u64, Window>,
     pub focused: Option<u64>,
-    max_length: usize,
+    window_title: WindowTitle,
     show_app_id: bool,
     cfg_override: ModuleConfigOverride,
     popup_cfg_override: PopupConfigOverride,
@@ -40,7 +42,7 @@ impl Default for NiriWindowMod {
         Self {
             windows: HashMap::new(),
             focused: None,
-            max_length: 25,
+            window_title: WindowTitle::default(),
             show_app_id: false,
             cfg_override: Default::default(),
             popup_cfg_override: PopupConfigOverride {
@@ -53,24 +55,24 @@ impl Default for NiriWindowMod {
 }
 
 impl NiriWindowMod {
+    fn focused_window(&self) -> Option<&Window> {
+        self.focused.and_then(|id| self.windows.get(&id))
+    }
+
     fn get_title(&self) -> Option<&String> {
-        self.focused.and_then(|id| {
-            self.windows.get(&id).and_then(|w| match self.show_app_id {
-                true => w.app_id.as_ref(),
-                false => w.title.as_ref(),
-            })
+        self.focused_window().and_then(|w| match self.show_app_id {
+            true => w.app_id.as_ref(),
+            false => w.title.as_ref(),
         })
     }
 
+    fn get_app_id(&self) -> Option<&str> {
+        self.focused_window().and_then(|w| w.app_id.as_deref())
+    }
+
     fn trimmed_title(&self) -> String {
         self.get_title()
-            .map(|title| match title.len() > self.max_length {
-                true => format!(
-                    "{}...",
-                    &title.chars().take(self.max_length - 3).collect::<String>()
-                ),
-                false => title.to_string(),
-            })
+            .map(|title| self.window_title.trimmed(self.get_app_id(), title))
             .unwrap_or_default()
     }
 }
@@ -91,13 +93,33 @@ impl Module for NiriWindowMod {
         anchor: &BarAnchor,
         _handlebars: &Handlebars,
     ) -> Element<Message> {
-        button(
-            text(self.trimmed_title())
-                .size(self.cfg_override.font_size.unwrap_or(config.font_size))
-                .color(self.cfg_override.text_color.unwrap_or(config.text_color))
-                .fill(anchor),
+        let icon: Option<Element<Message>> = self
+            .window_title
+            .icon(
+                self.get_app_id(),
+                self.cfg_override.icon_size.unwrap_or(config.icon_size),
+            )
+            .map(|icon| {
+                container(icon)
+                    .padding(self.cfg_override.icon_margin.unwrap_or(config.icon_margin))
+                    .into()
+            });
+        let title_text: Element<Message> = container(
+            bar_text(
+                self.trimmed_title(),
+                anchor,
+                self.cfg_override.vertical_text(config, anchor),
+            )
+            .size(self.cfg_override.font_size.unwrap_or(config.font_size))
+            .font(self.cfg_override.text_font.unwrap_or(config.text_font))
+            .color(self.cfg_override.text_color.unwrap_or(config.text_color)),
         )
         .padding(self.cfg_override.text_margin.unwrap_or(config.text_margin))
+        .into();
+        button(
+            list(anchor, icon.into_iter().chain([title_text]))
+                .spacing(self.cfg_override.spacing.unwrap_or(config.spacing)),
+        )
         .on_event_with(Message::popup::<Self>(
             self.popup_cfg_override.width.unwrap_or(popup_config.width),
             self.popup_cfg_override
@@ -109,6 +131,10 @@ impl Module for NiriWindowMod {
         .into()
     }
 
+    fn popup_cfg_override(&self) -> Option<&PopupConfigOverride> {
+        Some(&self.popup_cfg_override)
+    }
+
     fn popup_view<'a>(
         &'a self,
         config: &'a PopupConfig,
@@ -116,7 +142,7 @@ impl Module for NiriWindowMod {
     ) -> Element<'a, Message> {
         container(scrollable(
             container(
-                if let Some(window) = self.focused.and_then(|id| self.windows.get(&id)) {
+                if let Some(window) = self.focused_window() {
                     let unset = String::from("Unset");
                     let window_id = window.id.to_string();
                     let workspace_id = window.workspace_id.unwrap_or_default().to_string();
@@ -147,21 +173,6 @@ impl Module for NiriWindowMod {
                     .unwrap_or(config.text_margin),
             ),
         ))
-        .padding(self.popup_cfg_override.padding.unwrap_or(config.padding))
-        .style(|_| container::Style {
-            background: Some(
-                self.popup_cfg_override
-                    .background
-                    .unwrap_or(config.background),
-            ),
-            border: self.popup_cfg_override.border.unwrap_or(config.border),
-            ..Default::default()
-        })
-        .fill_maybe(
-            self.popup_cfg_override
-                .fill_content_to_size
-                .unwrap_or(config.fill_content_to_size),
-        )
         .into()
     }
 
@@ -176,14 +187,12 @@ impl Module for NiriWindowMod {
         config: &HashMap<String, Option<String>>,
         popup_config: &HashMap<String, Option<String>>,
         templates: &mut Handlebars,
+        scale: f32,
     ) {
         let default = Self::default();
-        self.cfg_override = config.into();
-        self.popup_cfg_override.update(popup_config);
-        self.max_length = config
-            .get("max_length")
-            .and_then(|v| v.as_ref().and_then(|v| v.parse().ok()))
-            .unwrap_or(default.max_length);
+        self.cfg_override = (config, scale).into();
+        self.popup_cfg_override.update(popup_config, scale);
+        self.window_title.read_config(config);
         self.show_app_id = config
             .get("show_app_id")
             .and_then(|v| v.into_bool())
@@ -199,5 +208,25 @@ impl Module for NiriWindowMod {
             .unwrap_or_else(|e| eprintln!("Failed to parse battery popup format: {e}"));
     }
 
+    fn known_keys(&self) -> Vec<ConfigKey> {
+        let mut keys = vec![ConfigKey::new("show_app_id", Kind::Bool, Some("false"))];
+        keys.extend_from_slice(WindowTitle::KEYS);
+        keys
+    }
+
+    fn known_popup_keys(&self) -> Vec<ConfigKey> {
+        vec![ConfigKey::new(
+            "format",
+            Kind::Text,
+            Some(
+                r#"Title: {{title}}\nApplication ID: {{app_id}}\nWindow ID: {{window_id}}\nWorkspace ID: {{workspace_id}}"#,
+            ),
+        )]
+    }
+
     impl_on_click!();
+
+    fn has_popup(&self) -> bool {
+        true
+    }
 }
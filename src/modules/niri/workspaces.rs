@@ -1,16 +1,19 @@
 use std::{
     any::{Any, TypeId},
-    collections::HashMap,
+    borrow::Cow,
+    collections::{BTreeMap, HashMap},
     sync::Arc,
 };
 
 use bar_rs_derive::Builder;
 use handlebars::Handlebars;
 use iced::{
+    mouse::{self, ScrollDelta},
     widget::{button, container, text},
-    Background, Border, Color, Element, Padding,
+    Background, Border, Color, Element, Event, Padding,
 };
-use niri_ipc::Workspace;
+use niri_ipc::{Window, Workspace};
+use serde_json::{json, Value};
 use tokio::sync::broadcast;
 
 use crate::{
@@ -18,19 +21,32 @@ use crate::{
         anchor::BarAnchor,
         module_config::{LocalModuleConfig, ModuleConfigOverride},
         parse::StringExt,
-        popup_config::PopupConfig,
+        popup_config::{PopupConfig, PopupConfigOverride},
+        scale::Scale,
+        schema::{ConfigKey, Kind},
     },
     fill::FillExt,
+    helpers::{
+        scroll::{ScrollDirection, WorkspaceScroll},
+        window::WindowTitle,
+        workspaces::{
+            OverviewWindow, OverviewWorkspace, WorkspaceOverview, WorkspaceState, WorkspaceStyles,
+            ICON_KEYS,
+        },
+        UnEscapeString,
+    },
     impl_on_click, impl_wrapper, list,
     listeners::niri::NiriListener,
-    modules::{require_listener, Module},
-    Message, NERD_FONT,
+    modules::{click_message, require_listener, Action, Module},
+    Message,
 };
 
 #[derive(Debug, Builder)]
 pub struct NiriWorkspaceMod {
     pub workspaces: HashMap<String, Vec<Workspace>>,
     pub focused: u64,
+    /// All windows by id, the same as [NiriWindowMod::windows](super::NiriWindowMod)
+    pub windows: HashMap<u64, Window>,
     pub sender: broadcast::Sender<Arc<dyn Any + Send + Sync>>,
     cfg_override: ModuleConfigOverride,
     icon_padding: Padding,
@@ -43,9 +59,18 @@ pub struct NiriWorkspaceMod {
     active_icon_border: Border,
     // Output, (idx, icon)
     icons: HashMap<String, HashMap<u8, String>>,
-    fallback_icon: String,
-    active_fallback_icon: String,
+    /// The icons of named workspaces by their (lowercase) name, set by `icon.<name>`
+    named_icons: HashMap<String, String>,
+    /// Unnamed workspaces without an icon show their index if this isn't set
+    fallback_icon: Option<String>,
+    active_fallback_icon: Option<String>,
     output_order: Vec<String>,
+    /// Only the workspaces of this output are shown
+    output: Option<String>,
+    styles: WorkspaceStyles,
+    format: String,
+    scroll: WorkspaceScroll,
+    overview: WorkspaceOverview,
 }
 
 impl Default for NiriWorkspaceMod {
@@ -53,6 +78,7 @@ impl Default for NiriWorkspaceMod {
         Self {
             workspaces: HashMap::new(),
             focused: 0,
+            windows: HashMap::new(),
             sender: broadcast::channel(1).0,
             cfg_override: Default::default(),
             icon_padding: Padding::default(),
@@ -64,35 +90,145 @@ impl Default for NiriWorkspaceMod {
             active_background: None,
             active_icon_border: Border::default().rounded(8),
             icons: HashMap::new(),
-            fallback_icon: String::from(""),
-            active_fallback_icon: String::from(""),
+            named_icons: HashMap::new(),
+            fallback_icon: None,
+            active_fallback_icon: None,
             output_order: vec![],
+            output: None,
+            styles: WorkspaceStyles::default(),
+            format: "{{icon}}".to_string(),
+            scroll: WorkspaceScroll::default(),
+            overview: WorkspaceOverview::default(),
         }
     }
 }
 
 impl NiriWorkspaceMod {
-    fn sort_by_outputs<'a, F, I>(&'a self, f: F) -> Vec<Element<'a, Message>>
+    /// niri doesn't report urgent workspaces (yet). Every output shows one of its workspaces,
+    /// the ones on outputs which aren't focused are `visible`.
+    fn state(&self, ws: &Workspace) -> WorkspaceState {
+        WorkspaceState::new(ws.id == self.focused, false, ws.active_window_id.is_some())
+            .visible(ws.is_active)
+    }
+
+    /// Named workspaces show the icon set for their name or the name itself, unnamed ones the
+    /// icon set for their index, the fallback icon or their index
+    fn icon(&self, output: &str, ws: &Workspace) -> Cow<str> {
+        let named = ws
+            .name
+            .as_ref()
+            .map(|name| self.named_icons.get(&name.to_lowercase()).unwrap_or(name));
+        let fallback = match ws.id == self.focused {
+            true => self
+                .active_fallback_icon
+                .as_ref()
+                .or(self.fallback_icon.as_ref()),
+            false => self.fallback_icon.as_ref(),
+        };
+        match named
+            .or_else(|| {
+                self.icons
+                    .get(&output.to_lowercase())
+                    .and_then(|icons| icons.get(&ws.idx))
+            })
+            .or(fallback)
+        {
+            Some(icon) => Cow::Borrowed(icon),
+            None => Cow::Owned(ws.idx.to_string()),
+        }
+    }
+
+    /// The windows on the workspace, by their id
+    fn windows_on(&self, workspace: u64) -> Vec<&Window> {
+        let mut windows = self
+            .windows
+            .values()
+            .filter(|window| window.workspace_id == Some(workspace))
+            .collect::<Vec<_>>();
+        windows.sort_by_key(|window| window.id);
+        windows
+    }
+
+    fn label(&self, output: &str, ws: &Workspace, icon: &str, handlebars: &Handlebars) -> String {
+        handlebars
+            .render_template(
+                &self.format,
+                &json!({
+                    "icon": icon,
+                    "name": ws.name,
+                    "idx": ws.idx,
+                    "output": output,
+                    "state": self.state(ws).as_str(),
+                    "windows": self.windows_on(ws.id).len(),
+                }),
+            )
+            .unwrap_or_else(|e| {
+                eprintln!("Failed to render niri.workspaces: {e}");
+                icon.to_string()
+            })
+    }
+
+    /// The workspaces of the outputs in `output_order`, or of `output` if it's set
+    fn sort_by_outputs<'a, F, I, T>(&'a self, f: F) -> Vec<T>
     where
         F: Fn((&'a String, &'a Vec<Workspace>)) -> I,
-        I: Iterator<Item = Element<'a, Message>>,
+        I: Iterator<Item = T>,
     {
-        match self.output_order.is_empty() {
-            true => self
+        if let Some(output) = &self.output {
+            return self
                 .workspaces
                 .iter()
+                .filter(|(o, _)| o.eq_ignore_ascii_case(output))
                 .flat_map(f)
-                .collect::<Vec<Element<Message>>>(),
+                .collect();
+        }
+        match self.output_order.is_empty() {
+            true => self.workspaces.iter().flat_map(f).collect::<Vec<T>>(),
             false => self
                 .output_order
                 .iter()
                 .filter_map(|o| self.workspaces.get_key_value(o))
                 .flat_map(f)
-                .collect::<Vec<Element<Message>>>(),
+                .collect::<Vec<T>>(),
+        }
+    }
+
+    fn switch_by_scroll(&mut self, direction: ScrollDirection) {
+        // In the order they are shown in
+        let ids = self.sort_by_outputs(|(_, workspaces)| workspaces.iter().map(|ws| ws.id));
+        let Some(active) = ids.iter().position(|id| *id == self.focused) else {
+            return;
+        };
+        let Some(id) = self
+            .scroll
+            .target(direction, active, ids.len())
+            .map(|target| ids[target])
+        else {
+            return;
+        };
+        if self.sender.send(Arc::new(id)).is_err() {
+            eprintln!("Failed to switch to workspace {id}: the niri listener is gone");
         }
     }
 }
 
+#[derive(Debug, Clone)]
+pub enum NiriWorkspaceAction {
+    /// Switch to the workspace with this id
+    Switch(u64),
+    FocusWindow(u64),
+}
+
+impl Action for NiriWorkspaceAction {
+    fn as_message(&self) -> Message {
+        let action = self.clone();
+        Message::update(move |reg| {
+            reg.get_module_mut::<NiriWorkspaceMod>()
+                .handle_action(&action)
+        })
+    }
+}
+
 impl Module for NiriWorkspaceMod {
     fn name(&self) -> String {
         "niri.workspaces".to_string()
@@ -101,28 +237,37 @@ impl Module for NiriWorkspaceMod {
     fn view(
         &self,
         config: &LocalModuleConfig,
-        _popup_config: &PopupConfig,
+        popup_config: &PopupConfig,
         anchor: &BarAnchor,
-        _handlebars: &Handlebars,
+        handlebars: &Handlebars,
     ) -> Element<Message> {
-        list(
+        let rows = self.workspaces.values().map(|ws| ws.len()).sum::<usize>() + self.windows.len();
+        let popup = Message::popup::<Self>(
+            self.overview.width(popup_config),
+            self.overview.height(popup_config, rows),
+            anchor,
+        );
+        // Right clicks open the overview, unless `on_click_right` is set. Left clicks on a
+        // workspace switch to it.
+        let left = click_message(self, mouse::Button::Left, config);
+        let middle = click_message(self, mouse::Button::Middle, config);
+        let right = click_message(self, mouse::Button::Right, config);
+        let workspaces = list(
             anchor,
             self.sort_by_outputs(|(output, workspaces)| {
-                workspaces.iter().map(|ws| {
-                    let mut text = text(
-                        self.icons
-                            .get(&output.to_lowercase())
-                            .and_then(|icons| icons.get(&ws.idx))
-                            .unwrap_or(match ws.id == self.focused {
-                                true => &self.active_fallback_icon,
-                                false => &self.fallback_icon,
-                            }),
-                    )
-                    .size(self.cfg_override.icon_size.unwrap_or(config.icon_size))
-                    .color(self.cfg_override.icon_color.unwrap_or(config.icon_color))
-                    .font(NERD_FONT);
+                workspaces.iter().map(move |ws| {
+                    let icon = self.icon(output, ws);
+                    let icon = self.cfg_override.icon(config, &icon);
+                    let style = self.styles.get(self.state(ws));
+                    let mut text =
+                        text(self.label(output, ws, icon, handlebars))
+                            .size(self.cfg_override.icon_size.unwrap_or(config.icon_size))
+                            .color(style.color.unwrap_or(
+                                self.cfg_override.icon_color.unwrap_or(config.icon_color),
+                            ))
+                            .font(config.icon_font);
                     let mut btn_style = button::Style {
-                        background: self.icon_background,
+                        background: style.background.or(self.icon_background),
                         border: self.icon_border,
                         ..Default::default()
                     };
@@ -139,12 +284,7 @@ impl Module for NiriWorkspaceMod {
                                 false => self.icon_padding,
                             })
                             .style(move |_, _| btn_style)
-                            .on_press(Message::action(move |reg| {
-                                reg.get_module::<NiriWorkspaceMod>()
-                                    .sender
-                                    .send(Arc::new(id))
-                                    .unwrap();
-                            })),
+                            .on_press(NiriWorkspaceAction::Switch(id).as_message()),
                     )
                     .fill(anchor)
                     .padding(self.cfg_override.icon_margin.unwrap_or(config.icon_margin))
@@ -153,12 +293,77 @@ impl Module for NiriWorkspaceMod {
             }),
         )
         .padding(self.cfg_override.padding.unwrap_or(config.padding))
-        .spacing(self.cfg_override.spacing.unwrap_or(config.spacing))
-        .into()
+        .spacing(self.cfg_override.spacing.unwrap_or(config.spacing));
+        crate::button::button(workspaces)
+            .on_event_try(
+                move |event, layout, cursor, clipboard, viewport| match event {
+                    Event::Mouse(mouse::Event::ButtonReleased(mouse::Button::Right)) => right
+                        .clone()
+                        .or_else(|| Some(popup(event, layout, cursor, clipboard, viewport))),
+                    Event::Mouse(mouse::Event::ButtonReleased(mouse::Button::Middle)) => {
+                        middle.clone()
+                    }
+                    _ => left.clone(),
+                },
+            )
+            .style(|_, _| button::Style::default())
+            .into()
     }
 
     impl_wrapper!();
 
+    fn popup_cfg_override(&self) -> Option<&PopupConfigOverride> {
+        Some(&self.overview.popup_cfg_override)
+    }
+
+    fn popup_view<'a>(
+        &'a self,
+        config: &'a PopupConfig,
+        template: &Handlebars,
+    ) -> Element<'a, Message> {
+        self.overview.view(
+            config,
+            self.sort_by_outputs(|(output, workspaces)| {
+                workspaces.iter().map(move |ws| OverviewWorkspace {
+                    label: self.label(output, ws, config.icon(&self.icon(output, ws)), template),
+                    state: self.state(ws),
+                    windows: self
+                        .windows_on(ws.id)
+                        .into_iter()
+                        .map(|window| OverviewWindow {
+                            app_id: window.app_id.as_deref(),
+                            title: window.title.as_deref().unwrap_or_default(),
+                            on_press: NiriWorkspaceAction::FocusWindow(window.id).as_message(),
+                        })
+                        .collect(),
+                })
+            }),
+        )
+    }
+
+    fn handle_action(&mut self, action: &dyn Action) {
+        let Some(action) = action.downcast_ref::<NiriWorkspaceAction>() else {
+            return;
+        };
+        match *action {
+            NiriWorkspaceAction::Switch(id) => {
+                if self.sender.send(Arc::new(id)).is_err() {
+                    eprintln!("Failed to switch to workspace {id}: the niri listener is gone");
+                }
+            }
+            NiriWorkspaceAction::FocusWindow(id) => {
+                // The window might have been closed while the overview was open
+                if !self.windows.contains_key(&id) {
+                    return;
+                }
+                let action = niri_ipc::Action::FocusWindow { id };
+                if self.sender.send(Arc::new(action)).is_err() {
+                    eprintln!("Failed to focus window {id}: the niri listener is gone");
+                }
+            }
+        }
+    }
+
     fn requires(&self) -> Vec<TypeId> {
         vec![require_listener::<NiriListener>()]
     }
@@ -166,15 +371,18 @@ impl Module for NiriWorkspaceMod {
     fn read_config(
         &mut self,
         config: &HashMap<String, Option<String>>,
-        _popup_config: &HashMap<String, Option<String>>,
+        popup_config: &HashMap<String, Option<String>>,
         _templates: &mut Handlebars,
+        scale: f32,
     ) {
         let default = Self::default();
-        self.cfg_override = config.into();
+        self.cfg_override = (config, scale).into();
+        self.overview.read_config(popup_config, scale);
         self.icon_padding = config
             .get("icon_padding")
             .and_then(|v| v.into_insets().map(|i| i.into()))
-            .unwrap_or(default.icon_padding);
+            .unwrap_or(default.icon_padding)
+            .scaled(scale);
         self.icon_background = config
             .get("icon_background")
             .map(|v| v.into_background())
@@ -194,15 +402,18 @@ impl Module for NiriWorkspaceMod {
             } else {
                 default.active_icon_border
             }
-        };
+        }
+        .scaled(scale);
         self.active_padding = config
             .get("active_padding")
             .map(|v| v.into_insets().map(|i| i.into()))
-            .unwrap_or(default.active_padding);
+            .unwrap_or(default.active_padding)
+            .scaled(scale);
         self.active_size = config
             .get("active_size")
             .and_then(|v| v.into_float())
-            .unwrap_or(default.active_size);
+            .unwrap_or(default.active_size)
+            .scaled(scale);
         self.active_color = config
             .get("active_color")
             .and_then(|v| v.into_color())
@@ -230,20 +441,36 @@ impl Module for NiriWorkspaceMod {
             } else {
                 default.active_icon_border
             }
-        };
+        }
+        .scaled(scale);
         self.fallback_icon = config
             .get("fallback_icon")
             .and_then(|v| v.clone())
-            .unwrap_or(default.fallback_icon);
+            .or(default.fallback_icon);
         self.active_fallback_icon = config
             .get("active_fallback_icon")
             .and_then(|v| v.clone())
-            .unwrap_or(default.active_fallback_icon);
+            .or(default.active_fallback_icon);
+        self.output = config
+            .get("output")
+            .and_then(|v| v.clone())
+            .map(|v| v.trim().to_string())
+            .or(default.output);
+        self.named_icons = config
+            .iter()
+            .filter_map(|(key, icon)| {
+                let name = key.strip_prefix("icon.")?;
+                Some((name.to_lowercase(), icon.clone()?))
+            })
+            .collect();
         self.output_order = config
             .get("output_order")
             .and_then(|v| v.clone())
             .map(|v| v.split(',').map(|v| v.trim().to_string()).collect())
             .unwrap_or(default.output_order);
+        self.styles.read_config(config);
+        self.format = config.get("format").unescape().unwrap_or(default.format);
+        self.scroll.read_config(config);
         config.iter().for_each(|(key, val)| {
             let Some(val) = val.clone() else {
                 return;
@@ -264,5 +491,139 @@ impl Module for NiriWorkspaceMod {
         });
     }
 
+    fn known_keys(&self) -> Vec<ConfigKey> {
+        let mut keys = vec![
+            ConfigKey::new("fallback_icon", Kind::Text, None),
+            ConfigKey::new("active_fallback_icon", Kind::Text, None),
+            ConfigKey::new("output_order", Kind::Text, None),
+            ConfigKey::new("output", Kind::Text, None),
+            // The icon of a named workspace, like `icon.chat`
+            ConfigKey::new("icon.*", Kind::Text, None),
+            ConfigKey::new("format", Kind::Text, Some("{{icon}}")),
+            // The icon of a workspace by output and index, like `DP-1:1`
+            ConfigKey::new("*:*", Kind::Text, None),
+        ];
+        keys.extend_from_slice(ICON_KEYS);
+        keys.extend_from_slice(WorkspaceStyles::KEYS);
+        keys.extend_from_slice(WorkspaceStyles::VISIBLE_KEYS);
+        keys.extend_from_slice(WorkspaceScroll::KEYS);
+        keys
+    }
+
+    fn known_popup_keys(&self) -> Vec<ConfigKey> {
+        [WorkspaceOverview::KEYS, WindowTitle::KEYS].concat()
+    }
+
     impl_on_click!();
+
+    /// The focused workspace
+    fn variables(&self) -> BTreeMap<String, Value> {
+        let Some((output, ws)) = self
+            .workspaces
+            .iter()
+            .flat_map(|(output, workspaces)| workspaces.iter().map(move |ws| (output, ws)))
+            .find(|(_, ws)| ws.id == self.focused)
+        else {
+            return BTreeMap::new();
+        };
+        BTreeMap::from([
+            ("id".to_string(), ws.id.into()),
+            ("idx".to_string(), ws.idx.into()),
+            ("name".to_string(), ws.name.clone().into()),
+            ("output".to_string(), output.clone().into()),
+            ("windows".to_string(), self.windows_on(ws.id).len().into()),
+        ])
+    }
+
+    fn on_scroll(&self, delta: ScrollDelta) -> Option<Message> {
+        let direction = ScrollDirection::from_delta(delta)?;
+        Some(Message::update(move |reg| {
+            reg.get_module_mut::<NiriWorkspaceMod>()
+                .switch_by_scroll(direction)
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::modules::testing::Harness;
+
+    use super::*;
+
+    fn workspace(id: u64, idx: u8, name: Option<&str>, output: &str, active: bool) -> Workspace {
+        Workspace {
+            id,
+            idx,
+            name: name.map(|name| name.to_string()),
+            output: Some(output.to_string()),
+            is_active: active,
+            is_focused: false,
+            active_window_id: None,
+        }
+    }
+
+    fn harness(options: &[(&str, &str)]) -> Harness {
+        let mut harness = Harness::new::<NiriWorkspaceMod>(options);
+        harness.update(|m: &mut NiriWorkspaceMod| {
+            m.workspaces = HashMap::from([
+                (
+                    "eDP-1".to_string(),
+                    vec![
+                        workspace(10, 1, Some("chat"), "eDP-1", true),
+                        workspace(11, 2, Some("Web"), "eDP-1", false),
+                        workspace(12, 3, None, "eDP-1", false),
+                    ],
+                ),
+                (
+                    "HDMI-A-1".to_string(),
+                    vec![workspace(20, 1, None, "HDMI-A-1", true)],
+                ),
+            ]);
+            m.focused = 20;
+        });
+        harness
+    }
+
+    fn icons(harness: &Harness, output: &str) -> Vec<String> {
+        let m = harness.module::<NiriWorkspaceMod>();
+        m.workspaces[output]
+            .iter()
+            .map(|ws| m.icon(output, ws).into_owned())
+            .collect()
+    }
+
+    #[test]
+    fn named_workspaces_show_their_name() {
+        let harness = harness(&[("icon.chat", "C"), ("edp-1: 3", "3rd")]);
+        assert_eq!(icons(&harness, "eDP-1"), ["C", "Web", "3rd"]);
+        assert_eq!(icons(&harness, "HDMI-A-1"), ["1"]);
+        let harness = self::harness(&[("fallback_icon", "o"), ("active_fallback_icon", "*")]);
+        assert_eq!(icons(&harness, "eDP-1"), ["chat", "Web", "o"]);
+        assert_eq!(icons(&harness, "HDMI-A-1"), ["*"]);
+    }
+
+    #[test]
+    fn shows_the_workspaces_of_one_output() {
+        let harness = harness(&[("output", "edp-1")]);
+        let m = harness.module::<NiriWorkspaceMod>();
+        let shown = m.sort_by_outputs(|(_, workspaces)| workspaces.iter().map(|ws| ws.id));
+        assert_eq!(shown, [10, 11, 12]);
+        // The active workspace of an output which isn't focused
+        let states = m.workspaces["eDP-1"]
+            .iter()
+            .map(|ws| m.state(ws))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            states,
+            [
+                WorkspaceState::Visible,
+                WorkspaceState::Empty,
+                WorkspaceState::Empty
+            ]
+        );
+        assert_eq!(
+            m.state(&m.workspaces["HDMI-A-1"][0]),
+            WorkspaceState::Focused
+        );
+    }
 }
@@ -0,0 +1,383 @@
+use std::collections::{BTreeMap, HashMap};
+
+use bar_rs_derive::Builder;
+use handlebars::Handlebars;
+use iced::widget::{button::Style, column, container, row, scrollable, text, Column, Container};
+use iced::{
+    futures::{channel::mpsc::Sender, SinkExt, StreamExt},
+    mouse, stream, Alignment, Element, Event,
+    Length::Fill,
+    Subscription,
+};
+use serde_json::Value;
+use tokio::{
+    select,
+    sync::mpsc::{self, UnboundedReceiver, UnboundedSender},
+};
+
+use crate::bar_text::bar_text;
+use crate::button::button;
+use crate::config::parse::StringExt;
+use crate::config::popup_config::{PopupConfig, PopupConfigOverride};
+use crate::helpers::{
+    backoff::Backoff,
+    notify_daemon::{Notifications, NotifyDaemon},
+    status::{send_status, ModuleStatus},
+};
+use crate::{
+    config::{
+        anchor::BarAnchor,
+        module_config::{LocalModuleConfig, ModuleConfigOverride},
+        schema::{ConfigKey, Kind},
+    },
+    fill::FillExt,
+    impl_on_click, impl_status, impl_wrapper, Message,
+};
+
+use super::{click_message, Action, Module};
+
+#[derive(Debug, Builder)]
+pub struct NotificationsMod {
+    cfg_override: ModuleConfigOverride,
+    popup_cfg_override: PopupConfigOverride,
+    /// `None` until they were read from the notification daemon
+    notifications: Option<Notifications>,
+    icon: String,
+    icon_unread: String,
+    /// Hides the module while there are no notifications
+    hide_empty: bool,
+    /// Passes dismissals to the subscription
+    actions: Option<UnboundedSender<NotificationsAction>>,
+    status: ModuleStatus,
+}
+
+impl Default for NotificationsMod {
+    fn default() -> Self {
+        Self {
+            cfg_override: Default::default(),
+            popup_cfg_override: PopupConfigOverride {
+                width: Some(350),
+                height: Some(300),
+                ..Default::default()
+            },
+            notifications: None,
+            icon: "󰂜".to_string(),
+            icon_unread: "󰂞".to_string(),
+            hide_empty: false,
+            actions: None,
+            status: ModuleStatus::Ok,
+        }
+    }
+}
+
+impl NotificationsMod {
+    fn count(&self) -> usize {
+        self.notifications.as_ref().map_or(0, |n| n.count)
+    }
+
+    fn icon(&self) -> &str {
+        match self.count() {
+            0 => &self.icon,
+            _ => &self.icon_unread,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum NotificationsAction {
+    /// Dismisses the notification with this id
+    Dismiss(u32),
+    DismissAll,
+}
+
+impl Action for NotificationsAction {
+    fn as_message(&self) -> Message {
+        let action = *self;
+        Message::update(move |reg| {
+            reg.get_module_mut::<NotificationsMod>()
+                .handle_action(&action)
+        })
+    }
+}
+
+impl Module for NotificationsMod {
+    fn name(&self) -> String {
+        "notifications".to_string()
+    }
+
+    fn active(&self) -> bool {
+        self.notifications.is_some() && (!self.hide_empty || self.count() > 0)
+    }
+
+    impl_status!();
+
+    fn view(
+        &self,
+        config: &LocalModuleConfig,
+        popup_config: &PopupConfig,
+        anchor: &BarAnchor,
+        _handlebars: &Handlebars,
+    ) -> Element<Message> {
+        let popup = Message::popup::<Self>(
+            self.popup_cfg_override.width.unwrap_or(popup_config.width),
+            self.popup_cfg_override
+                .height
+                .unwrap_or(popup_config.height),
+            anchor,
+        );
+        // Left clicks open the popup, unless `on_click` is set
+        let left = click_message(self, mouse::Button::Left, config);
+        let middle = click_message(self, mouse::Button::Middle, config);
+        let right = click_message(self, mouse::Button::Right, config);
+        button(
+            list![
+                anchor,
+                container(
+                    text(self.cfg_override.icon(config, self.icon()))
+                        .fill(anchor)
+                        .size(self.cfg_override.icon_size.unwrap_or(config.icon_size))
+                        .color(self.cfg_override.icon_color.unwrap_or(config.icon_color))
+                        .font(config.icon_font)
+                )
+                .padding(self.cfg_override.icon_margin.unwrap_or(config.icon_margin)),
+                container(
+                    bar_text(
+                        self.count().to_string(),
+                        anchor,
+                        self.cfg_override.vertical_text(config, anchor)
+                    )
+                    .size(self.cfg_override.font_size.unwrap_or(config.font_size))
+                    .font(self.cfg_override.text_font.unwrap_or(config.text_font))
+                    .color(self.cfg_override.text_color.unwrap_or(config.text_color))
+                )
+                .padding(self.cfg_override.text_margin.unwrap_or(config.text_margin)),
+            ]
+            .spacing(self.cfg_override.spacing.unwrap_or(config.spacing)),
+        )
+        .on_event_try(
+            move |event, layout, cursor, clipboard, viewport| match event {
+                Event::Mouse(mouse::Event::ButtonReleased(mouse::Button::Middle)) => middle.clone(),
+                Event::Mouse(mouse::Event::ButtonReleased(mouse::Button::Right)) => right.clone(),
+                Event::Mouse(mouse::Event::ButtonReleased(mouse::Button::Left)) => left
+                    .clone()
+                    .or_else(|| Some(popup(event, layout, cursor, clipboard, viewport))),
+                _ => None,
+            },
+        )
+        .style(|_, _| Style::default())
+        .into()
+    }
+
+    fn popup_cfg_override(&self) -> Option<&PopupConfigOverride> {
+        Some(&self.popup_cfg_override)
+    }
+
+    fn popup_view<'a>(
+        &'a self,
+        config: &'a PopupConfig,
+        _template: &Handlebars,
+    ) -> Element<'a, Message> {
+        let font_size = self
+            .popup_cfg_override
+            .font_size
+            .unwrap_or(config.font_size);
+        let fmt_text = |content: String, size: f32| -> Container<'a, Message> {
+            container(text(content).size(size)).padding(
+                self.popup_cfg_override
+                    .text_margin
+                    .unwrap_or(config.text_margin),
+            )
+        };
+        let spacing = self.popup_cfg_override.spacing.unwrap_or(config.spacing);
+        let summary = match self.count() {
+            0 => "No notifications".to_string(),
+            1 => "1 notification".to_string(),
+            count => format!("{count} notifications"),
+        };
+        let notifications: Element<'a, Message> =
+            match self.notifications.as_ref().and_then(|n| n.list.as_ref()) {
+                Some(list) => scrollable(
+                    Column::with_children(list.iter().map(|n| -> Element<'a, Message> {
+                        let content = column![
+                            fmt_text(n.app_name.clone(), font_size * 0.8),
+                            fmt_text(n.summary.clone(), font_size),
+                        ]
+                        .push_maybe(
+                            (!n.body.is_empty()).then(|| fmt_text(n.body.clone(), font_size * 0.9)),
+                        );
+                        row![
+                            container(content).width(Fill),
+                            button(fmt_text("✕".to_string(), font_size))
+                                .on_event(NotificationsAction::Dismiss(n.id).as_message())
+                                .style(|_, _| Style::default()),
+                        ]
+                        .align_y(Alignment::Center)
+                        .into()
+                    }))
+                    .spacing(spacing),
+                )
+                .into(),
+                // swaync only reports how many there are
+                None => fmt_text(
+                    "The notification daemon doesn't list its notifications".to_string(),
+                    font_size * 0.8,
+                )
+                .into(),
+            };
+        column![
+            fmt_text(summary, font_size),
+            container(notifications).height(Fill),
+            button(fmt_text("Dismiss all".to_string(), font_size))
+                .on_event(NotificationsAction::DismissAll.as_message())
+                .style(|_, _| Style::default()),
+        ]
+        .spacing(spacing)
+        .into()
+    }
+
+    impl_wrapper!();
+
+    fn read_config(
+        &mut self,
+        config: &HashMap<String, Option<String>>,
+        popup_config: &HashMap<String, Option<String>>,
+        _templates: &mut Handlebars,
+        scale: f32,
+    ) {
+        let default = Self::default();
+        self.cfg_override = (config, scale).into();
+        self.popup_cfg_override.update(popup_config, scale);
+        self.icon = config
+            .get("icon")
+            .and_then(|v| v.clone())
+            .unwrap_or(default.icon);
+        self.icon_unread = config
+            .get("icon_unread")
+            .and_then(|v| v.clone())
+            .unwrap_or(default.icon_unread);
+        self.hide_empty = config
+            .get("hide_empty")
+            .and_then(|v| v.into_bool())
+            .unwrap_or(default.hide_empty);
+    }
+
+    fn known_keys(&self) -> Vec<ConfigKey> {
+        vec![
+            ConfigKey::new("icon", Kind::Text, Some("󰂜")),
+            ConfigKey::new("icon_unread", Kind::Text, Some("󰂞")),
+            ConfigKey::new("hide_empty", Kind::Bool, Some("false")),
+        ]
+    }
+
+    impl_on_click!();
+
+    fn has_popup(&self) -> bool {
+        true
+    }
+
+    fn handle_action(&mut self, action: &dyn Action) {
+        let Some(action) = action.downcast_ref::<NotificationsAction>() else {
+            return;
+        };
+        if let Some(Err(_)) = self.actions.as_ref().map(|sender| sender.send(*action)) {
+            eprintln!("Failed to dismiss notifications: the subscription is gone");
+        }
+    }
+
+    fn collect_data(&mut self) -> Result<(), String> {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .map_err(|e| e.to_string())?;
+        self.notifications =
+            Some(runtime.block_on(async { NotifyDaemon::connect().await?.notifications().await })?);
+        Ok(())
+    }
+
+    fn text_content(&self, _template: &Handlebars) -> Option<Result<String, String>> {
+        self.notifications
+            .as_ref()
+            .map(|notifications| Ok(notifications.count.to_string()))
+    }
+
+    fn variables(&self) -> BTreeMap<String, Value> {
+        BTreeMap::from([("count".to_string(), self.count().into())])
+    }
+
+    fn subscription(&self) -> Option<Subscription<Message>> {
+        Some(Subscription::run(|| {
+            stream::channel(1, |mut sender| async move {
+                let (action_sx, mut action_rx) = mpsc::unbounded_channel();
+                if sender
+                    .send(Message::update(move |reg| {
+                        reg.get_module_mut::<NotificationsMod>().actions = Some(action_sx)
+                    }))
+                    .await
+                    .is_err()
+                {
+                    return;
+                }
+                // The daemon may be started after the bar, or restarted
+                let mut backoff = Backoff::default();
+                while let Err(e) =
+                    watch_notifications(&mut sender, &mut action_rx, &mut backoff).await
+                {
+                    if !send_status::<NotificationsMod>(&mut sender, ModuleStatus::Unavailable(e))
+                        .await
+                    {
+                        return;
+                    }
+                    backoff.wait().await;
+                }
+            })
+        }))
+    }
+}
+
+/// Follows the notifications of the notification daemon and dismisses them. Returns if the bar
+/// is closing.
+async fn watch_notifications(
+    sender: &mut Sender<Message>,
+    actions: &mut UnboundedReceiver<NotificationsAction>,
+    backoff: &mut Backoff,
+) -> Result<(), String> {
+    let daemon = NotifyDaemon::connect().await?;
+    let mut changes = daemon.notification_changes().await?;
+    backoff.reset();
+    let mut notifications = daemon.notifications().await?;
+    loop {
+        let update = notifications.clone();
+        if sender
+            .send(Message::update(move |reg| {
+                let m = reg.get_module_mut::<NotificationsMod>();
+                m.notifications = Some(update);
+                m.set_status(ModuleStatus::Ok);
+            }))
+            .await
+            .is_err()
+        {
+            return Ok(());
+        }
+        // Wait for the notifications to actually change
+        loop {
+            select! {
+                Some(()) = changes.next() => {}
+                Some(action) = actions.recv() => {
+                    let dismissed = match action {
+                        NotificationsAction::Dismiss(id) => daemon.dismiss(id).await,
+                        NotificationsAction::DismissAll => daemon.dismiss_all().await,
+                    };
+                    if let Err(e) = dismissed {
+                        eprintln!("Failed to dismiss notifications of {}: {e}", daemon.name());
+                    }
+                }
+                else => return Err(format!("{} stopped", daemon.name())),
+            }
+            let new = daemon.notifications().await?;
+            if new != notifications {
+                notifications = new;
+                break;
+            }
+        }
+    }
+}
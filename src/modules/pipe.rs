@@ -0,0 +1,403 @@
+use std::{collections::HashMap, process::Stdio};
+
+use bar_rs_derive::Builder;
+use handlebars::Handlebars;
+use iced::widget::{container, text};
+use iced::{
+    futures::{channel::mpsc::Sender, SinkExt},
+    stream, Color, Element, Event, Subscription,
+};
+use serde_json::{json, Value};
+use tokio::{
+    io::{self, AsyncBufRead, AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
+    process::Command,
+    select,
+    sync::mpsc::{self, UnboundedReceiver, UnboundedSender},
+};
+
+use crate::bar_text::bar_text;
+use crate::{
+    config::{
+        anchor::BarAnchor,
+        module_config::{LocalModuleConfig, ModuleConfigOverride},
+        parse::StringExt,
+        popup_config::PopupConfig,
+        schema::{ConfigKey, Kind},
+    },
+    fill::FillExt,
+    helpers::{
+        backoff::Backoff,
+        process::ChildGroup,
+        status::{send_status, ModuleStatus},
+        UnEscapeString,
+    },
+    impl_status, impl_wrapper,
+    list::list,
+    tooltip::ElementExt,
+    Message,
+};
+
+use super::{Action, Module};
+
+/// Longer lines of output are skipped, so that a misbehaving command can't fill the memory
+const MAX_LINE: usize = 64 * 1024;
+
+/// One line of output of the command, in the format of waybar's custom modules
+#[derive(Debug, Default, Clone, PartialEq)]
+struct PipeOutput {
+    text: String,
+    tooltip: Option<String>,
+    class: Option<String>,
+    percentage: Option<f64>,
+}
+
+impl PipeOutput {
+    fn parse(line: &str) -> Result<Self, String> {
+        let value = serde_json::from_str::<Value>(line).map_err(|e| e.to_string())?;
+        if !value.is_object() {
+            return Err("expected a JSON object".to_string());
+        }
+        let string = |key: &str| value[key].as_str().map(|s| s.to_string());
+        Ok(Self {
+            text: string("text").unwrap_or_default(),
+            tooltip: string("tooltip").filter(|tooltip| !tooltip.is_empty()),
+            class: match &value["class"] {
+                // waybar allows a list of classes, the first one is used
+                Value::Array(classes) => classes
+                    .first()
+                    .and_then(|c| c.as_str())
+                    .map(|c| c.to_string()),
+                _ => string("class"),
+            },
+            percentage: value["percentage"].as_f64(),
+        })
+    }
+}
+
+/// Shows the output of a long running command, see `exec`
+#[derive(Debug, Default, Builder)]
+pub struct PipeMod {
+    cfg_override: ModuleConfigOverride,
+    icon: Option<String>,
+    /// The command printing one JSON object per line
+    exec: Option<String>,
+    format: String,
+    /// Write clicks to the stdin of the command
+    send_clicks: bool,
+    /// The text color for each `class` of the output, set by `color_{class}`
+    class_colors: HashMap<String, Color>,
+    /// The last line of output, `None` until the command printed one
+    output: Option<PipeOutput>,
+    status: ModuleStatus,
+    /// Passes clicks to the subscription
+    clicks: Option<UnboundedSender<String>>,
+}
+
+impl PipeMod {
+    fn text(&self, handlebars: &Handlebars) -> Result<String, String> {
+        let output = self.output.clone().unwrap_or_default();
+        let ctx = json!({
+            "text": output.text,
+            "tooltip": output.tooltip,
+            "class": output.class,
+            "percentage": output.percentage,
+        });
+        handlebars
+            .render_template(&self.format, &ctx)
+            .map_err(|e| e.to_string())
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum PipeAction {
+    /// Writes `{"button": "..."}` to the stdin of the command
+    Click(&'static str),
+}
+
+impl Action for PipeAction {
+    fn as_message(&self) -> Message {
+        let action = *self;
+        Message::update(move |reg| reg.get_module_mut::<PipeMod>().handle_action(&action))
+    }
+}
+
+impl Module for PipeMod {
+    fn name(&self) -> String {
+        "pipe".to_string()
+    }
+
+    fn active(&self) -> bool {
+        self.output
+            .as_ref()
+            .is_some_and(|output| !output.text.is_empty())
+    }
+
+    impl_status!();
+
+    fn view(
+        &self,
+        config: &LocalModuleConfig,
+        _popup_config: &PopupConfig,
+        anchor: &BarAnchor,
+        handlebars: &Handlebars,
+    ) -> Element<Message> {
+        let text_color = self
+            .output
+            .as_ref()
+            .and_then(|output| output.class.as_ref())
+            .and_then(|class| self.class_colors.get(class))
+            .copied()
+            .unwrap_or(self.cfg_override.text_color.unwrap_or(config.text_color));
+        let icon = self.icon.as_ref().map(|icon| -> Element<Message> {
+            container(
+                text(self.cfg_override.icon(config, icon))
+                    .fill(anchor)
+                    .size(self.cfg_override.icon_size.unwrap_or(config.icon_size))
+                    .color(self.cfg_override.icon_color.unwrap_or(config.icon_color))
+                    .font(config.icon_font),
+            )
+            .padding(self.cfg_override.icon_margin.unwrap_or(config.icon_margin))
+            .into()
+        });
+        let content: Element<Message> = container(
+            bar_text(
+                self.text(handlebars)
+                    .inspect_err(|e| eprintln!("Failed to render pipe module: {e}"))
+                    .unwrap_or_default(),
+                anchor,
+                self.cfg_override.vertical_text(config, anchor),
+            )
+            .size(self.cfg_override.font_size.unwrap_or(config.font_size))
+            .font(self.cfg_override.text_font.unwrap_or(config.text_font))
+            .color(text_color),
+        )
+        .padding(self.cfg_override.text_margin.unwrap_or(config.text_margin))
+        .into();
+        list(anchor, icon.into_iter().chain([content]))
+            .spacing(self.cfg_override.spacing.unwrap_or(config.spacing))
+            .tooltip_maybe(
+                self.output
+                    .as_ref()
+                    .and_then(|output| output.tooltip.as_ref())
+                    .map(|tooltip| text(tooltip).size(12)),
+            )
+    }
+
+    impl_wrapper!();
+
+    fn read_config(
+        &mut self,
+        config: &HashMap<String, Option<String>>,
+        _popup_config: &HashMap<String, Option<String>>,
+        _templates: &mut Handlebars,
+        scale: f32,
+    ) {
+        self.cfg_override = (config, scale).into();
+        self.icon = config.get("icon").and_then(|v| v.clone());
+        self.exec = config.get("exec").and_then(|v| v.clone());
+        if self.exec.is_none() {
+            eprintln!("The pipe module needs a command to run, set `exec` in its section");
+        }
+        self.format = config
+            .get("format")
+            .unescape()
+            .unwrap_or("{{text}}".to_string());
+        self.send_clicks = config
+            .get("send_clicks")
+            .and_then(|v| v.into_bool())
+            .unwrap_or(false);
+        self.class_colors = config
+            .iter()
+            .filter_map(|(key, value)| {
+                let class = key.strip_prefix("color_")?;
+                Some((class.to_string(), Some(value).and_then(|v| v.into_color())?))
+            })
+            .collect();
+    }
+
+    fn known_keys(&self) -> Vec<ConfigKey> {
+        vec![
+            ConfigKey::new("icon", Kind::Text, None),
+            ConfigKey::new("exec", Kind::Text, None),
+            ConfigKey::new("format", Kind::Text, Some("{{text}}")),
+            ConfigKey::new("send_clicks", Kind::Bool, Some("false")),
+            ConfigKey::new("color_*", Kind::Color, None),
+        ]
+    }
+
+    fn on_click<'a>(
+        &'a self,
+        event: Event,
+        config: &'a LocalModuleConfig,
+    ) -> Option<&'a dyn Action> {
+        self.cfg_override
+            .action
+            .as_ref()
+            .unwrap_or(&config.action)
+            .event(event.clone())
+            .or(match (self.send_clicks, event) {
+                (true, Event::Mouse(iced::mouse::Event::ButtonReleased(button))) => match button {
+                    iced::mouse::Button::Left => Some(&PipeAction::Click("left") as &dyn Action),
+                    iced::mouse::Button::Middle => {
+                        Some(&PipeAction::Click("middle") as &dyn Action)
+                    }
+                    iced::mouse::Button::Right => Some(&PipeAction::Click("right") as &dyn Action),
+                    _ => None,
+                },
+                _ => None,
+            })
+    }
+
+    fn handle_action(&mut self, action: &dyn Action) {
+        let Some(PipeAction::Click(button)) = action.downcast_ref::<PipeAction>() else {
+            return;
+        };
+        let click = json!({ "button": button }).to_string();
+        if let Some(Err(_)) = self.clicks.as_ref().map(|sender| sender.send(click)) {
+            eprintln!("Failed to pass a click to the pipe module: the subscription is gone");
+        }
+    }
+
+    fn text_content(&self, template: &Handlebars) -> Option<Result<String, String>> {
+        self.output.as_ref().map(|_| self.text(template))
+    }
+
+    fn subscription(&self) -> Option<Subscription<Message>> {
+        let exec = self.exec.clone()?;
+        let send_clicks = self.send_clicks;
+        Some(Subscription::run_with_id(
+            ("pipe", exec.clone(), send_clicks),
+            stream::channel(1, move |mut sender| async move {
+                let (click_sx, mut click_rx) = mpsc::unbounded_channel();
+                if sender
+                    .send(Message::update(move |reg| {
+                        reg.get_module_mut::<PipeMod>().clicks = Some(click_sx)
+                    }))
+                    .await
+                    .is_err()
+                {
+                    return;
+                }
+                let mut backoff = Backoff::default();
+                loop {
+                    let Some(reason) =
+                        run(&exec, send_clicks, &mut sender, &mut click_rx, &mut backoff).await
+                    else {
+                        return;
+                    };
+                    eprintln!("[pipe] {reason}, restarting it");
+                    if !send_status::<PipeMod>(&mut sender, ModuleStatus::Error(reason)).await {
+                        return;
+                    }
+                    backoff.wait().await;
+                }
+            }),
+        ))
+    }
+}
+
+/// Runs the command until it exits. Returns why it did, or `None` if the bar is closing.
+/// The command is killed once this returns or is dropped.
+async fn run(
+    exec: &str,
+    send_clicks: bool,
+    sender: &mut Sender<Message>,
+    clicks: &mut UnboundedReceiver<String>,
+    backoff: &mut Backoff,
+) -> Option<String> {
+    let mut command = Command::new("sh");
+    command
+        .arg("-c")
+        .arg(exec)
+        .stdout(Stdio::piped())
+        .stdin(match send_clicks {
+            true => Stdio::piped(),
+            false => Stdio::null(),
+        });
+    let mut group = match ChildGroup::spawn(&mut command) {
+        Ok(group) => group,
+        Err(e) => return Some(format!("Failed to run `{exec}`: {e}")),
+    };
+    let mut stdout = BufReader::new(
+        group
+            .child
+            .stdout
+            .take()
+            .expect("child did not have a handle to stdout"),
+    );
+    let mut stdin = group.child.stdin.take();
+    loop {
+        select! {
+            line = next_line(&mut stdout) => match line {
+                Ok(Some(line)) if line.trim().is_empty() => {}
+                Ok(Some(line)) => match PipeOutput::parse(&line) {
+                    Ok(output) => {
+                        backoff.reset();
+                        if sender
+                            .send(Message::update(move |reg| {
+                                let m = reg.get_module_mut::<PipeMod>();
+                                m.set_status(ModuleStatus::Ok);
+                                m.output = Some(output);
+                            }))
+                            .await
+                            .is_err()
+                        {
+                            return None;
+                        }
+                    }
+                    Err(e) => eprintln!("[pipe] Skipping invalid output of `{exec}`: {e}"),
+                },
+                Ok(None) => break,
+                Err(e) => return Some(format!("Failed to read the output of `{exec}`: {e}")),
+            },
+            Some(click) = clicks.recv() => {
+                if let Some(stdin) = stdin.as_mut() {
+                    if let Err(e) = stdin.write_all(format!("{click}\n").as_bytes()).await {
+                        eprintln!("[pipe] Failed to pass a click to `{exec}`: {e}");
+                    }
+                }
+            }
+        }
+    }
+    match group.child.wait().await {
+        Ok(status) => Some(format!("`{exec}` exited with {status}")),
+        Err(e) => Some(format!("`{exec}` failed: {e}")),
+    }
+}
+
+/// The next line of output, without the newline. Lines longer than [MAX_LINE] are skipped.
+/// `None` once the output is closed.
+async fn next_line<R>(reader: &mut R) -> io::Result<Option<String>>
+where
+    R: AsyncBufRead + Unpin,
+{
+    let mut buf = vec![];
+    loop {
+        buf.clear();
+        let read = (&mut *reader)
+            .take(MAX_LINE as u64)
+            .read_until(b'\n', &mut buf)
+            .await?;
+        if read == 0 {
+            return Ok(None);
+        }
+        if buf.ends_with(b"\n") || read < MAX_LINE {
+            return Ok(Some(String::from_utf8_lossy(&buf).trim_end().to_string()));
+        }
+        eprintln!("[pipe] Skipping a line of output longer than {MAX_LINE} bytes");
+        // Discard the rest of the line
+        loop {
+            buf.clear();
+            let read = (&mut *reader)
+                .take(MAX_LINE as u64)
+                .read_until(b'\n', &mut buf)
+                .await?;
+            if read == 0 {
+                return Ok(None);
+            }
+            if buf.ends_with(b"\n") {
+                break;
+            }
+        }
+    }
+}
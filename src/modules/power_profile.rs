@@ -0,0 +1,581 @@
+use std::{collections::HashMap, fs, io, process::Stdio, time::Duration};
+
+use bar_rs_derive::Builder;
+use handlebars::Handlebars;
+use iced::widget::{button::Style, column, container, row, text, Column, Container};
+use iced::{
+    futures::{channel::mpsc::Sender, SinkExt, StreamExt},
+    mouse, stream, Alignment, Background, Color, Element, Event,
+    Length::Fill,
+    Subscription,
+};
+use tokio::{
+    process::Command,
+    select,
+    sync::mpsc::{self, UnboundedReceiver, UnboundedSender},
+    time::sleep,
+};
+use zbus::{dbus_proxy, zvariant::OwnedValue, Connection};
+
+use crate::bar_text::bar_text;
+use crate::button::button;
+use crate::config::parse::StringExt;
+use crate::config::popup_config::{PopupConfig, PopupConfigOverride};
+use crate::{
+    config::{
+        anchor::BarAnchor,
+        module_config::{LocalModuleConfig, ModuleConfigOverride},
+        schema::{ConfigKey, Kind},
+    },
+    fill::FillExt,
+    impl_wrapper, Message,
+};
+
+use super::{click_message, Action, Module};
+
+const PLATFORM_PROFILE: &str = "/sys/firmware/acpi/platform_profile";
+const PLATFORM_PROFILE_CHOICES: &str = "/sys/firmware/acpi/platform_profile_choices";
+
+#[dbus_proxy(
+    interface = "org.freedesktop.UPower.PowerProfiles",
+    default_service = "org.freedesktop.UPower.PowerProfiles",
+    default_path = "/org/freedesktop/UPower/PowerProfiles"
+)]
+trait PowerProfiles {
+    #[dbus_proxy(property)]
+    fn active_profile(&self) -> zbus::Result<String>;
+    #[dbus_proxy(property)]
+    fn set_active_profile(&self, profile: &str) -> zbus::Result<()>;
+    #[dbus_proxy(property)]
+    fn profiles(&self) -> zbus::Result<Vec<HashMap<String, OwnedValue>>>;
+    #[dbus_proxy(property)]
+    fn performance_degraded(&self) -> zbus::Result<String>;
+}
+
+/// Where the power profile is read from and written to
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+enum Backend {
+    /// Neither power-profiles-daemon nor the platform profile of the kernel are available
+    #[default]
+    None,
+    Daemon,
+    Sysfs,
+}
+
+#[derive(Debug, Default, Clone)]
+struct ProfileState {
+    backend: Backend,
+    active: String,
+    profiles: Vec<String>,
+    /// Why the performance profile is degraded, e.g. `lap-detected`
+    degraded: Option<String>,
+}
+
+impl ProfileState {
+    async fn from_daemon(proxy: &PowerProfilesProxy<'_>) -> zbus::Result<Self> {
+        Ok(ProfileState {
+            backend: Backend::Daemon,
+            active: proxy.active_profile().await?,
+            profiles: proxy
+                .profiles()
+                .await?
+                .into_iter()
+                .filter_map(|profile| {
+                    profile
+                        .get("Profile")
+                        .and_then(|name| String::try_from(name.clone()).ok())
+                })
+                .collect(),
+            degraded: Some(proxy.performance_degraded().await?).filter(|r| !r.is_empty()),
+        })
+    }
+
+    fn from_sysfs() -> io::Result<Self> {
+        Ok(ProfileState {
+            backend: Backend::Sysfs,
+            active: fs::read_to_string(PLATFORM_PROFILE)?.trim().to_string(),
+            profiles: fs::read_to_string(PLATFORM_PROFILE_CHOICES)?
+                .split_whitespace()
+                .map(|p| p.to_string())
+                .collect(),
+            degraded: None,
+        })
+    }
+}
+
+/// A profile to switch to, passed to the subscription
+#[derive(Debug)]
+struct SetProfile {
+    profile: String,
+    /// Used to write the platform profile if bar-rs isn't allowed to
+    command: String,
+}
+
+#[derive(Debug, Builder)]
+pub struct PowerProfileMod {
+    cfg_override: ModuleConfigOverride,
+    popup_cfg_override: PopupConfigOverride,
+    state: ProfileState,
+    icon_power_saver: String,
+    icon_balanced: String,
+    icon_performance: String,
+    active_color: Option<Color>,
+    active_background: Option<Background>,
+    set_command: String,
+    /// Passes profile changes to the subscription
+    actions: Option<UnboundedSender<SetProfile>>,
+}
+
+impl Default for PowerProfileMod {
+    fn default() -> Self {
+        Self {
+            cfg_override: Default::default(),
+            popup_cfg_override: PopupConfigOverride {
+                width: Some(250),
+                height: Some(200),
+                ..Default::default()
+            },
+            state: ProfileState::default(),
+            icon_power_saver: "󰌪".to_string(),
+            icon_balanced: "󰗑".to_string(),
+            icon_performance: "󰓅".to_string(),
+            active_color: None,
+            active_background: Some(Background::Color(Color::from_rgba(1., 1., 1., 0.2))),
+            set_command: format!("echo \"$1\" | pkexec tee {PLATFORM_PROFILE}"),
+            actions: None,
+        }
+    }
+}
+
+impl PowerProfileMod {
+    fn icon(&self, profile: &str) -> &str {
+        match profile {
+            // platform profile names of the kernel
+            "power-saver" | "low-power" | "quiet" | "cool" => &self.icon_power_saver,
+            "performance" => &self.icon_performance,
+            _ => &self.icon_balanced,
+        }
+    }
+    /// The profile after the active one
+    fn next_profile(&self) -> Option<&String> {
+        let profiles = &self.state.profiles;
+        let index = profiles.iter().position(|p| *p == self.state.active)?;
+        profiles.get((index + 1) % profiles.len())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum PowerProfileAction {
+    Next,
+    Set(String),
+}
+
+impl Action for PowerProfileAction {
+    fn as_message(&self) -> Message {
+        let action = self.clone();
+        Message::update(move |reg| {
+            reg.get_module_mut::<PowerProfileMod>()
+                .handle_action(&action)
+        })
+    }
+}
+
+impl Module for PowerProfileMod {
+    fn name(&self) -> String {
+        "power_profile".to_string()
+    }
+
+    fn active(&self) -> bool {
+        self.state.backend != Backend::None
+    }
+
+    fn view(
+        &self,
+        config: &LocalModuleConfig,
+        popup_config: &PopupConfig,
+        anchor: &BarAnchor,
+        _handlebars: &Handlebars,
+    ) -> Element<Message> {
+        let popup = Message::popup::<Self>(
+            self.popup_cfg_override.width.unwrap_or(popup_config.width),
+            self.popup_cfg_override
+                .height
+                .unwrap_or(popup_config.height),
+            anchor,
+        );
+        // Right clicks open the popup, the other buttons keep their on_click actions
+        let left = click_message(self, mouse::Button::Left, config);
+        let middle = click_message(self, mouse::Button::Middle, config);
+        button(
+            list![
+                anchor,
+                container(
+                    text(
+                        self.cfg_override
+                            .icon(config, self.icon(&self.state.active))
+                    )
+                    .size(self.cfg_override.icon_size.unwrap_or(config.icon_size))
+                    .color(self.cfg_override.icon_color.unwrap_or(config.icon_color))
+                    .font(config.icon_font)
+                    .fill(anchor)
+                )
+                .fill(anchor)
+                .padding(self.cfg_override.icon_margin.unwrap_or(config.icon_margin)),
+                container(
+                    bar_text(
+                        &self.state.active,
+                        anchor,
+                        self.cfg_override.vertical_text(config, anchor)
+                    )
+                    .size(self.cfg_override.font_size.unwrap_or(config.font_size))
+                    .font(self.cfg_override.text_font.unwrap_or(config.text_font))
+                    .color(self.cfg_override.text_color.unwrap_or(config.text_color))
+                )
+                .fill(anchor)
+                .padding(self.cfg_override.text_margin.unwrap_or(config.text_margin)),
+            ]
+            .spacing(self.cfg_override.spacing.unwrap_or(config.spacing)),
+        )
+        .on_event_try(
+            move |event, layout, cursor, clipboard, viewport| match event {
+                Event::Mouse(mouse::Event::ButtonReleased(mouse::Button::Right)) => {
+                    Some(popup(event, layout, cursor, clipboard, viewport))
+                }
+                Event::Mouse(mouse::Event::ButtonReleased(mouse::Button::Middle)) => middle.clone(),
+                _ => left.clone(),
+            },
+        )
+        .style(|_, _| Style::default())
+        .into()
+    }
+
+    fn popup_cfg_override(&self) -> Option<&PopupConfigOverride> {
+        Some(&self.popup_cfg_override)
+    }
+
+    fn popup_view<'a>(
+        &'a self,
+        config: &'a PopupConfig,
+        _template: &Handlebars,
+    ) -> Element<'a, Message> {
+        let text_color = self
+            .popup_cfg_override
+            .text_color
+            .unwrap_or(config.text_color);
+        let fmt_text = |content: &'a str, color: Color| -> Container<'a, Message> {
+            container(
+                text(content)
+                    .size(
+                        self.popup_cfg_override
+                            .font_size
+                            .unwrap_or(config.font_size),
+                    )
+                    .color(color),
+            )
+            .padding(
+                self.popup_cfg_override
+                    .text_margin
+                    .unwrap_or(config.text_margin),
+            )
+        };
+        let icon = |icon: &'a str, color: Color| -> Container<'a, Message> {
+            container(
+                text(config.icon(icon))
+                    .font(config.icon_font)
+                    .color(color)
+                    .size(
+                        self.popup_cfg_override
+                            .icon_size
+                            .unwrap_or(config.icon_size),
+                    ),
+            )
+            .padding(
+                self.popup_cfg_override
+                    .icon_margin
+                    .unwrap_or(config.icon_margin),
+            )
+        };
+
+        let profiles = self
+            .state
+            .profiles
+            .iter()
+            .map(|profile| -> Element<'a, Message> {
+                let is_active = *profile == self.state.active;
+                let color = match is_active {
+                    true => self.active_color.unwrap_or(text_color),
+                    false => text_color,
+                };
+                let background = is_active.then_some(self.active_background).flatten();
+                button(
+                    row![
+                        icon(
+                            self.icon(profile),
+                            match is_active {
+                                true => color,
+                                false => self
+                                    .popup_cfg_override
+                                    .icon_color
+                                    .unwrap_or(config.icon_color),
+                            }
+                        ),
+                        fmt_text(profile, color).width(Fill),
+                    ]
+                    .align_y(Alignment::Center),
+                )
+                .on_event(PowerProfileAction::Set(profile.clone()).as_message())
+                .style(move |_, _| Style {
+                    background,
+                    ..Default::default()
+                })
+                .width(Fill)
+                .into()
+            });
+
+        container(
+            Column::with_children(profiles)
+                .push_maybe(self.state.degraded.as_ref().map(|reason| {
+                    column![
+                        fmt_text("Performance degraded:", text_color),
+                        fmt_text(reason, text_color)
+                    ]
+                }))
+                .spacing(self.popup_cfg_override.spacing.unwrap_or(config.spacing)),
+        )
+        .into()
+    }
+
+    impl_wrapper!();
+
+    fn read_config(
+        &mut self,
+        config: &HashMap<String, Option<String>>,
+        popup_config: &HashMap<String, Option<String>>,
+        _templates: &mut Handlebars,
+        scale: f32,
+    ) {
+        let default = Self::default();
+        self.cfg_override = (config, scale).into();
+        self.popup_cfg_override.update(popup_config, scale);
+        self.icon_power_saver = config
+            .get("icon_power_saver")
+            .and_then(|v| v.clone())
+            .unwrap_or(default.icon_power_saver);
+        self.icon_balanced = config
+            .get("icon_balanced")
+            .and_then(|v| v.clone())
+            .unwrap_or(default.icon_balanced);
+        self.icon_performance = config
+            .get("icon_performance")
+            .and_then(|v| v.clone())
+            .unwrap_or(default.icon_performance);
+        self.set_command = config
+            .get("set_command")
+            .and_then(|v| v.clone())
+            .unwrap_or(default.set_command);
+        self.active_color = popup_config
+            .get("active_color")
+            .and_then(|v| v.into_color())
+            .or(default.active_color);
+        self.active_background = popup_config
+            .get("active_background")
+            .map(|v| v.into_background())
+            .unwrap_or(default.active_background);
+    }
+
+    fn known_keys(&self) -> Vec<ConfigKey> {
+        vec![
+            ConfigKey::new("icon_power_saver", Kind::Text, Some("󰌪")),
+            ConfigKey::new("icon_balanced", Kind::Text, Some("󰗑")),
+            ConfigKey::new("icon_performance", Kind::Text, Some("󰓅")),
+            ConfigKey::new(
+                "set_command",
+                Kind::Text,
+                Some(r#"echo "$1" | pkexec tee /sys/firmware/acpi/platform_profile"#),
+            ),
+        ]
+    }
+
+    fn known_popup_keys(&self) -> Vec<ConfigKey> {
+        vec![
+            ConfigKey::new("active_color", Kind::Color, None),
+            ConfigKey::new(
+                "active_background",
+                Kind::Color,
+                Some("rgba(255, 255, 255, 0.2)"),
+            ),
+        ]
+    }
+
+    fn on_click<'a>(
+        &'a self,
+        event: iced::Event,
+        config: &'a LocalModuleConfig,
+    ) -> Option<&'a dyn Action> {
+        self.cfg_override
+            .action
+            .as_ref()
+            .unwrap_or(&config.action)
+            .event(event.clone())
+            .or(match event {
+                Event::Mouse(mouse::Event::ButtonReleased(mouse::Button::Left)) => {
+                    Some(&PowerProfileAction::Next as &dyn Action)
+                }
+                _ => None,
+            })
+    }
+
+    fn handle_action(&mut self, action: &dyn Action) {
+        let Some(action) = action.downcast_ref::<PowerProfileAction>() else {
+            return;
+        };
+        let profile = match action {
+            PowerProfileAction::Next => match self.next_profile() {
+                Some(profile) => profile.clone(),
+                None => return,
+            },
+            PowerProfileAction::Set(profile) => profile.clone(),
+        };
+        let set = SetProfile {
+            profile,
+            command: self.set_command.clone(),
+        };
+        if let Some(Err(e)) = self.actions.as_ref().map(|sender| sender.send(set)) {
+            eprintln!(
+                "Failed to set power profile `{}`: the subscription is gone",
+                e.0.profile
+            );
+        }
+    }
+
+    fn collect_data(&mut self) -> Result<(), String> {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .map_err(|e| e.to_string())?;
+        self.state = match runtime.block_on(async {
+            let connection = Connection::system().await?;
+            ProfileState::from_daemon(&PowerProfilesProxy::new(&connection).await?).await
+        }) {
+            Ok(state) => state,
+            Err(_) => ProfileState::from_sysfs().map_err(|e| {
+                format!(
+                    "power-profiles-daemon isn't running and {PLATFORM_PROFILE} can't be read: {e}"
+                )
+            })?,
+        };
+        Ok(())
+    }
+
+    fn text_content(&self, _template: &Handlebars) -> Option<Result<String, String>> {
+        Some(Ok(format!(
+            "{} {}",
+            self.icon(&self.state.active),
+            self.state.active
+        )))
+    }
+
+    fn subscription(&self) -> Option<Subscription<Message>> {
+        Some(Subscription::run(|| {
+            stream::channel(1, |mut sender| async move {
+                let (action_sx, mut action_rx) = mpsc::unbounded_channel();
+                if sender
+                    .send(Message::update(move |reg| {
+                        reg.get_module_mut::<PowerProfileMod>().actions = Some(action_sx)
+                    }))
+                    .await
+                    .is_err()
+                {
+                    return;
+                }
+                if let Err(e) = watch_daemon(&mut sender, &mut action_rx).await {
+                    eprintln!(
+                        "power-profiles-daemon is unavailable ({e}), falling back to {PLATFORM_PROFILE}"
+                    );
+                    watch_sysfs(&mut sender, &mut action_rx).await;
+                }
+            })
+        }))
+    }
+}
+
+async fn send_state(sender: &mut Sender<Message>, state: ProfileState) -> bool {
+    sender
+        .send(Message::update(move |reg| {
+            reg.get_module_mut::<PowerProfileMod>().state = state
+        }))
+        .await
+        .is_ok()
+}
+
+/// Follows the profile of power-profiles-daemon. Returns if the daemon isn't available or the
+/// bar is closing.
+async fn watch_daemon(
+    sender: &mut Sender<Message>,
+    actions: &mut UnboundedReceiver<SetProfile>,
+) -> zbus::Result<()> {
+    let connection = Connection::system().await?;
+    let proxy = PowerProfilesProxy::new(&connection).await?;
+    let mut active_changes = proxy.receive_active_profile_changed().await;
+    let mut degraded_changes = proxy.receive_performance_degraded_changed().await;
+    loop {
+        if !send_state(sender, ProfileState::from_daemon(&proxy).await?).await {
+            return Ok(());
+        }
+        select! {
+            Some(_) = active_changes.next() => {}
+            Some(_) = degraded_changes.next() => {}
+            Some(set) = actions.recv() => {
+                if let Err(e) = proxy.set_active_profile(&set.profile).await {
+                    eprintln!("Failed to set power profile `{}`: {e}", set.profile);
+                }
+            }
+            else => return Ok(()),
+        }
+    }
+}
+
+/// Polls the platform profile of the kernel, since sysfs doesn't support inotify
+async fn watch_sysfs(sender: &mut Sender<Message>, actions: &mut UnboundedReceiver<SetProfile>) {
+    loop {
+        let state = ProfileState::from_sysfs()
+            .inspect_err(|e| eprintln!("Failed to read {PLATFORM_PROFILE}: {e}"))
+            .unwrap_or_default();
+        let available = state.backend != Backend::None;
+        if !send_state(sender, state).await || !available {
+            return;
+        }
+        select! {
+            _ = sleep(Duration::from_secs(5)) => {}
+            Some(set) = actions.recv() => write_platform_profile(set).await,
+        }
+    }
+}
+
+/// Writes the platform profile directly if possible, and using the configured command otherwise
+async fn write_platform_profile(set: SetProfile) {
+    match fs::write(PLATFORM_PROFILE, &set.profile) {
+        Ok(()) => return,
+        Err(e) if e.kind() == io::ErrorKind::PermissionDenied => {}
+        Err(e) => {
+            eprintln!("Failed to set power profile `{}`: {e}", set.profile);
+            return;
+        }
+    }
+    match Command::new("sh")
+        .arg("-c")
+        .arg(&set.command)
+        .arg("sh")
+        .arg(&set.profile)
+        .stdout(Stdio::null())
+        .status()
+        .await
+    {
+        Ok(status) if status.success() => {}
+        Ok(status) => eprintln!(
+            "Failed to set power profile `{}`: `{}` exited with {status}",
+            set.profile, set.command
+        ),
+        Err(e) => eprintln!("Failed to set power profile `{}`: {e}", set.profile),
+    }
+}
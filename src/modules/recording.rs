@@ -0,0 +1,289 @@
+use std::{collections::HashMap, fs, time::Duration};
+
+use bar_rs_derive::Builder;
+use handlebars::Handlebars;
+use iced::widget::{container, text};
+use iced::{futures::SinkExt, stream, Color, Element, Subscription};
+use serde_json::Value;
+use tokio::{process::Command, time::sleep};
+
+use crate::config::parse::StringExt;
+use crate::config::popup_config::PopupConfig;
+use crate::{
+    config::{
+        anchor::BarAnchor,
+        module_config::{LocalModuleConfig, ModuleConfigOverride},
+        schema::{ConfigKey, Kind},
+    },
+    fill::FillExt,
+    impl_on_click, impl_wrapper, Message,
+};
+
+use super::{Action, CommandAction, Module, OnClickAction};
+
+/// The kernel truncates process names in `/proc/<pid>/comm` to 15 bytes
+const COMM_LEN: usize = 15;
+
+#[derive(Debug, Default, Clone, PartialEq)]
+struct RecordingState {
+    /// The recorder processes which are running
+    pids: Vec<libc::pid_t>,
+    /// The number of running PipeWire screencast streams
+    screencasts: usize,
+}
+
+impl RecordingState {
+    fn recording(&self) -> bool {
+        !self.pids.is_empty() || self.screencasts > 0
+    }
+}
+
+#[derive(Debug, Builder)]
+pub struct RecordingMod {
+    cfg_override: ModuleConfigOverride,
+    state: RecordingState,
+    icon: String,
+    /// Names of screen recorder processes
+    processes: Vec<String>,
+    /// Whether PipeWire is checked for screencasts (e.g. by OBS or browsers)
+    pipewire: bool,
+    /// Stops the recording on left click
+    stop: OnClickAction,
+}
+
+impl Default for RecordingMod {
+    fn default() -> Self {
+        Self {
+            cfg_override: Default::default(),
+            state: RecordingState::default(),
+            icon: "󰑊".to_string(),
+            processes: ["wf-recorder", "wl-screenrec", "gpu-screen-recorder"]
+                .map(|p| p.to_string())
+                .to_vec(),
+            pipewire: true,
+            stop: OnClickAction {
+                left: Some(Box::new(RecordingAction::Stop)),
+                ..Default::default()
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum RecordingAction {
+    /// Sends SIGINT to the recorder processes, which makes them finish the file they're writing
+    Stop,
+}
+
+impl Action for RecordingAction {
+    fn as_message(&self) -> Message {
+        let action = *self;
+        Message::update(move |reg| reg.get_module_mut::<RecordingMod>().handle_action(&action))
+    }
+}
+
+impl Module for RecordingMod {
+    fn name(&self) -> String {
+        "recording".to_string()
+    }
+
+    fn active(&self) -> bool {
+        self.state.recording()
+    }
+
+    fn view(
+        &self,
+        config: &LocalModuleConfig,
+        _popup_config: &PopupConfig,
+        anchor: &BarAnchor,
+        _handlebars: &Handlebars,
+    ) -> Element<Message> {
+        container(
+            text(self.cfg_override.icon(config, &self.icon))
+                .fill(anchor)
+                .size(self.cfg_override.icon_size.unwrap_or(config.icon_size))
+                .color(
+                    self.cfg_override
+                        .icon_color
+                        .unwrap_or(Color::from_rgb(0.9, 0.1, 0.1)),
+                )
+                .font(config.icon_font),
+        )
+        .padding(self.cfg_override.icon_margin.unwrap_or(config.icon_margin))
+        .into()
+    }
+
+    impl_wrapper!();
+
+    fn read_config(
+        &mut self,
+        config: &HashMap<String, Option<String>>,
+        _popup_config: &HashMap<String, Option<String>>,
+        _templates: &mut Handlebars,
+        scale: f32,
+    ) {
+        let default = Self::default();
+        self.cfg_override = (config, scale).into();
+        self.icon = config
+            .get("icon")
+            .and_then(|v| v.clone())
+            .unwrap_or(default.icon);
+        self.processes = config
+            .get("processes")
+            .and_then(|v| v.as_ref())
+            .map(|v| {
+                v.split(',')
+                    .map(|p| p.trim().to_string())
+                    .filter(|p| !p.is_empty())
+                    .collect()
+            })
+            .unwrap_or(default.processes);
+        self.pipewire = config
+            .get("pipewire")
+            .and_then(|v| v.into_bool())
+            .unwrap_or(default.pipewire);
+        self.stop = match config.get("stop_command").and_then(|v| v.clone()) {
+            Some(cmd) => OnClickAction {
+                left: Some(Box::new(CommandAction(cmd))),
+                ..Default::default()
+            },
+            None => default.stop,
+        };
+    }
+
+    fn known_keys(&self) -> Vec<ConfigKey> {
+        vec![
+            ConfigKey::new("icon", Kind::Text, Some("󰑊")),
+            ConfigKey::new(
+                "processes",
+                Kind::Text,
+                Some("wf-recorder, wl-screenrec, gpu-screen-recorder"),
+            ),
+            ConfigKey::new("pipewire", Kind::Bool, Some("true")),
+            ConfigKey::new("stop_command", Kind::Text, None),
+        ]
+    }
+
+    impl_on_click!();
+
+    fn default_action(&self) -> Option<&OnClickAction> {
+        Some(&self.stop)
+    }
+
+    fn handle_action(&mut self, action: &dyn Action) {
+        if let Some(RecordingAction::Stop) = action.downcast_ref::<RecordingAction>() {
+            for pid in &self.state.pids {
+                if unsafe { libc::kill(*pid, libc::SIGINT) } != 0 {
+                    eprintln!(
+                        "Failed to stop the recording process {pid}: {}",
+                        std::io::Error::last_os_error()
+                    );
+                }
+            }
+        }
+    }
+
+    fn collect_data(&mut self) -> Result<(), String> {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .map_err(|e| e.to_string())?;
+        let mut pipewire = self.pipewire;
+        self.state = runtime.block_on(check_recording(&self.processes, &mut pipewire));
+        Ok(())
+    }
+
+    fn text_content(&self, _template: &Handlebars) -> Option<Result<String, String>> {
+        self.state.recording().then(|| Ok(self.icon.clone()))
+    }
+
+    fn subscription(&self) -> Option<Subscription<Message>> {
+        let processes = self.processes.clone();
+        let pipewire = self.pipewire;
+        Some(Subscription::run_with_id(
+            ("recording", processes.clone(), pipewire),
+            stream::channel(1, move |mut sender| async move {
+                let mut pipewire = pipewire;
+                let mut previous = None;
+                loop {
+                    let state = check_recording(&processes, &mut pipewire).await;
+                    if previous.as_ref() != Some(&state) {
+                        previous = Some(state.clone());
+                        if sender
+                            .send(Message::update(move |reg| {
+                                reg.get_module_mut::<RecordingMod>().state = state
+                            }))
+                            .await
+                            .is_err()
+                        {
+                            return;
+                        }
+                    }
+                    sleep(Duration::from_secs(2)).await;
+                }
+            }),
+        ))
+    }
+}
+
+/// Disables the PipeWire check if `pw-dump` can't be used
+async fn check_recording(processes: &[String], pipewire: &mut bool) -> RecordingState {
+    let screencasts = match *pipewire {
+        true => count_screencasts().await.unwrap_or_else(|e| {
+            eprintln!("{e}, not checking PipeWire for screencasts");
+            *pipewire = false;
+            0
+        }),
+        false => 0,
+    };
+    RecordingState {
+        pids: find_processes(processes),
+        screencasts,
+    }
+}
+
+/// The pids of all processes with one of the given names, like `pidof`
+fn find_processes(names: &[String]) -> Vec<libc::pid_t> {
+    if names.is_empty() {
+        return vec![];
+    }
+    let Ok(entries) = fs::read_dir("/proc") else {
+        eprintln!("Failed to read /proc");
+        return vec![];
+    };
+    entries
+        .flatten()
+        .filter_map(|entry| {
+            let pid = entry.file_name().to_str()?.parse().ok()?;
+            let comm = fs::read_to_string(entry.path().join("comm")).ok()?;
+            let comm = comm.trim_end();
+            names
+                .iter()
+                .any(|name| name.get(..COMM_LEN).unwrap_or(name) == comm)
+                .then_some(pid)
+        })
+        .collect()
+}
+
+/// Counts the running PipeWire video streams which aren't backed by a device (like a webcam).
+/// These are created by the screencast portal of the compositor.
+async fn count_screencasts() -> Result<usize, String> {
+    let output = Command::new("pw-dump")
+        .output()
+        .await
+        .map_err(|e| format!("Failed to run pw-dump: {e}"))?;
+    let Ok(Value::Array(objects)) = serde_json::from_slice(&output.stdout) else {
+        return Err("Failed to parse the output of pw-dump".to_string());
+    };
+    Ok(objects
+        .iter()
+        .filter(|object| object["type"] == "PipeWire:Interface:Node")
+        .filter(|object| {
+            let info = &object["info"];
+            let props = &info["props"];
+            info["state"] == "running"
+                && props["media.class"] == "Video/Source"
+                && props.get("device.api").is_none()
+        })
+        .count())
+}
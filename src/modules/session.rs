@@ -0,0 +1,578 @@
+use std::{collections::HashMap, env};
+
+use bar_rs_derive::Builder;
+use handlebars::Handlebars;
+use iced::widget::{button::Style, container, row, scrollable, text, Column, Container};
+use iced::{
+    futures::SinkExt, mouse, stream, Alignment, Background, Color, Element, Event, Length::Fill,
+    Subscription,
+};
+use tokio::sync::mpsc::{self, UnboundedSender};
+use zbus::{dbus_proxy, zvariant::OwnedObjectPath, Connection};
+
+use crate::button::button;
+use crate::config::parse::StringExt;
+use crate::config::popup_config::{PopupConfig, PopupConfigOverride};
+use crate::{
+    config::{
+        anchor::BarAnchor,
+        module_config::{LocalModuleConfig, ModuleConfigOverride},
+        schema::{ConfigKey, Kind},
+    },
+    fill::FillExt,
+    impl_wrapper, Message,
+};
+
+use super::{click_message, Action, CommandAction, Module};
+
+#[dbus_proxy(
+    interface = "org.freedesktop.login1.Manager",
+    default_service = "org.freedesktop.login1",
+    default_path = "/org/freedesktop/login1"
+)]
+trait Manager {
+    /// `yes`, `challenge` (requires authentication), `no` or `na`, like the other `can_*` methods
+    fn can_suspend(&self) -> zbus::Result<String>;
+    fn can_hibernate(&self) -> zbus::Result<String>;
+    fn can_reboot(&self) -> zbus::Result<String>;
+    fn can_power_off(&self) -> zbus::Result<String>;
+    /// `interactive` lets polkit ask for authentication, like the other power methods
+    fn suspend(&self, interactive: bool) -> zbus::Result<()>;
+    fn hibernate(&self, interactive: bool) -> zbus::Result<()>;
+    fn reboot(&self, interactive: bool) -> zbus::Result<()>;
+    fn power_off(&self, interactive: bool) -> zbus::Result<()>;
+    fn get_session(&self, session_id: &str) -> zbus::Result<OwnedObjectPath>;
+}
+
+#[dbus_proxy(
+    interface = "org.freedesktop.login1.Session",
+    default_service = "org.freedesktop.login1"
+)]
+trait Session {
+    fn terminate(&self) -> zbus::Result<()>;
+}
+
+/// What an entry of the popup does
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Command {
+    Lock,
+    Logout,
+    Suspend,
+    Hibernate,
+    Reboot,
+    PowerOff,
+}
+
+impl Command {
+    const ALL: [Command; 6] = [
+        Command::Lock,
+        Command::Logout,
+        Command::Suspend,
+        Command::Hibernate,
+        Command::Reboot,
+        Command::PowerOff,
+    ];
+
+    /// The name used in the config
+    fn name(&self) -> &'static str {
+        match self {
+            Command::Lock => "lock",
+            Command::Logout => "logout",
+            Command::Suspend => "suspend",
+            Command::Hibernate => "hibernate",
+            Command::Reboot => "reboot",
+            Command::PowerOff => "poweroff",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|command| command.name() == name)
+    }
+
+    fn default_label(&self) -> &'static str {
+        match self {
+            Command::Lock => "Lock",
+            Command::Logout => "Log out",
+            Command::Suspend => "Suspend",
+            Command::Hibernate => "Hibernate",
+            Command::Reboot => "Reboot",
+            Command::PowerOff => "Power off",
+        }
+    }
+
+    fn default_icon(&self) -> &'static str {
+        match self {
+            Command::Lock => "󰌾",
+            Command::Logout => "󰍃",
+            Command::Suspend => "󰒲",
+            Command::Hibernate => "󰜗",
+            Command::Reboot => "󰜉",
+            Command::PowerOff => "󰐥",
+        }
+    }
+
+    /// Whether logind allows this, locking and logging out are always possible
+    async fn available(&self, manager: &ManagerProxy<'_>) -> zbus::Result<bool> {
+        let answer = match self {
+            Command::Suspend => manager.can_suspend().await?,
+            Command::Hibernate => manager.can_hibernate().await?,
+            Command::Reboot => manager.can_reboot().await?,
+            Command::PowerOff => manager.can_power_off().await?,
+            Command::Lock | Command::Logout => return Ok(true),
+        };
+        Ok(answer != "no" && answer != "na")
+    }
+
+    async fn run(&self, connection: &Connection) -> zbus::Result<()> {
+        let manager = ManagerProxy::new(connection).await?;
+        match self {
+            // Locking is compositor-specific, so it's done by the `lock_command`
+            Command::Lock => Ok(()),
+            Command::Logout => {
+                // `auto` is the session of bar-rs, or the graphical session of the user if bar-rs
+                // doesn't run in one (e.g. as a service)
+                let id = env::var("XDG_SESSION_ID").unwrap_or("auto".to_string());
+                SessionProxy::builder(connection)
+                    .path(manager.get_session(&id).await?)?
+                    .build()
+                    .await?
+                    .terminate()
+                    .await
+            }
+            Command::Suspend => manager.suspend(true).await,
+            Command::Hibernate => manager.hibernate(true).await,
+            Command::Reboot => manager.reboot(true).await,
+            Command::PowerOff => manager.power_off(true).await,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct SessionEntry {
+    command: Command,
+    label: String,
+    icon: String,
+    /// Whether the entry has to be clicked twice
+    confirm: bool,
+}
+
+#[derive(Debug, Builder)]
+pub struct SessionMod {
+    cfg_override: ModuleConfigOverride,
+    popup_cfg_override: PopupConfigOverride,
+    icon: String,
+    lock: CommandAction,
+    entries: Vec<SessionEntry>,
+    /// The commands logind doesn't allow on this system, their entries are hidden
+    unavailable: Vec<Command>,
+    /// The entry waiting for its second click
+    confirming: Option<usize>,
+    confirm_color: Option<Color>,
+    confirm_background: Option<Background>,
+    /// Passes the commands to run to the subscription
+    commands: Option<UnboundedSender<Command>>,
+}
+
+impl Default for SessionMod {
+    fn default() -> Self {
+        Self {
+            cfg_override: Default::default(),
+            popup_cfg_override: PopupConfigOverride {
+                width: Some(200),
+                height: Some(250),
+                ..Default::default()
+            },
+            icon: "󰐥".to_string(),
+            lock: CommandAction("loginctl lock-session".to_string()),
+            entries: Command::ALL
+                .into_iter()
+                .map(|command| SessionEntry {
+                    command,
+                    label: command.default_label().to_string(),
+                    icon: command.default_icon().to_string(),
+                    confirm: false,
+                })
+                .collect(),
+            unavailable: vec![],
+            confirming: None,
+            confirm_color: Some(Color::from_rgb8(255, 85, 85)),
+            confirm_background: Some(Background::Color(Color::from_rgba(1., 1., 1., 0.2))),
+            commands: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum SessionAction {
+    Confirm(usize),
+    Cancel,
+    Run(Command),
+}
+
+impl Action for SessionAction {
+    fn as_message(&self) -> Message {
+        let action = *self;
+        Message::update(move |reg| reg.get_module_mut::<SessionMod>().handle_action(&action))
+    }
+}
+
+impl SessionMod {
+    /// Runs the command of the entry and closes the popup
+    fn run(&self, entry: &SessionEntry) -> Message {
+        Message::Batch(vec![
+            SessionAction::Cancel.as_message(),
+            match entry.command {
+                Command::Lock => self.lock.as_message(),
+                command => SessionAction::Run(command).as_message(),
+            },
+            Message::ClosePopup,
+        ])
+    }
+}
+
+impl Module for SessionMod {
+    fn name(&self) -> String {
+        "session".to_string()
+    }
+
+    fn view(
+        &self,
+        config: &LocalModuleConfig,
+        popup_config: &PopupConfig,
+        anchor: &BarAnchor,
+        _handlebars: &Handlebars,
+    ) -> Element<Message> {
+        let popup = Message::popup::<Self>(
+            self.popup_cfg_override.width.unwrap_or(popup_config.width),
+            self.popup_cfg_override
+                .height
+                .unwrap_or(popup_config.height),
+            anchor,
+        );
+        // Right clicks open the popup, the other buttons keep their on_click actions
+        let left = click_message(self, mouse::Button::Left, config);
+        let middle = click_message(self, mouse::Button::Middle, config);
+        button(
+            container(
+                text(self.cfg_override.icon(config, &self.icon))
+                    .size(self.cfg_override.icon_size.unwrap_or(config.icon_size))
+                    .color(self.cfg_override.icon_color.unwrap_or(config.icon_color))
+                    .font(config.icon_font)
+                    .fill(anchor),
+            )
+            .fill(anchor)
+            .padding(self.cfg_override.icon_margin.unwrap_or(config.icon_margin)),
+        )
+        .on_event_try(
+            move |event, layout, cursor, clipboard, viewport| match event {
+                // A confirmation left open when the popup was closed is dropped
+                Event::Mouse(mouse::Event::ButtonReleased(mouse::Button::Right)) => {
+                    Some(Message::Batch(vec![
+                        SessionAction::Cancel.as_message(),
+                        popup(event, layout, cursor, clipboard, viewport),
+                    ]))
+                }
+                Event::Mouse(mouse::Event::ButtonReleased(mouse::Button::Middle)) => middle.clone(),
+                _ => left.clone(),
+            },
+        )
+        .style(|_, _| Style::default())
+        .into()
+    }
+
+    fn popup_cfg_override(&self) -> Option<&PopupConfigOverride> {
+        Some(&self.popup_cfg_override)
+    }
+
+    fn popup_view<'a>(
+        &'a self,
+        config: &'a PopupConfig,
+        _template: &Handlebars,
+    ) -> Element<'a, Message> {
+        let text_color = self
+            .popup_cfg_override
+            .text_color
+            .unwrap_or(config.text_color);
+        let fmt_text = |content: String, color: Color| -> Container<'a, Message> {
+            container(
+                text(content)
+                    .size(
+                        self.popup_cfg_override
+                            .font_size
+                            .unwrap_or(config.font_size),
+                    )
+                    .color(color),
+            )
+            .padding(
+                self.popup_cfg_override
+                    .text_margin
+                    .unwrap_or(config.text_margin),
+            )
+        };
+        let icon = |icon: &'a str, color: Color| -> Container<'a, Message> {
+            container(
+                text(config.icon(icon))
+                    .font(config.icon_font)
+                    .color(color)
+                    .size(
+                        self.popup_cfg_override
+                            .icon_size
+                            .unwrap_or(config.icon_size),
+                    ),
+            )
+            .padding(
+                self.popup_cfg_override
+                    .icon_margin
+                    .unwrap_or(config.icon_margin),
+            )
+        };
+
+        let entries = self
+            .entries
+            .iter()
+            .enumerate()
+            .filter(|(_, entry)| !self.unavailable.contains(&entry.command))
+            .map(|(index, entry)| -> Element<'a, Message> {
+                let confirming = self.confirming == Some(index);
+                let (label, color, icon_color) = match confirming {
+                    true => {
+                        let color = self.confirm_color.unwrap_or(text_color);
+                        (format!("{}?", entry.label), color, color)
+                    }
+                    false => (
+                        entry.label.clone(),
+                        text_color,
+                        self.popup_cfg_override
+                            .icon_color
+                            .unwrap_or(config.icon_color),
+                    ),
+                };
+                let background = confirming.then_some(self.confirm_background).flatten();
+                button(
+                    row![
+                        icon(&entry.icon, icon_color),
+                        fmt_text(label, color).width(Fill)
+                    ]
+                    .align_y(Alignment::Center),
+                )
+                .on_event(match entry.confirm && !confirming {
+                    true => SessionAction::Confirm(index).as_message(),
+                    false => self.run(entry),
+                })
+                .style(move |_, _| Style {
+                    background,
+                    ..Default::default()
+                })
+                .width(Fill)
+                .into()
+            });
+
+        container(scrollable(Column::with_children(entries).spacing(
+            self.popup_cfg_override.spacing.unwrap_or(config.spacing),
+        )))
+        .into()
+    }
+
+    impl_wrapper!();
+
+    fn read_config(
+        &mut self,
+        config: &HashMap<String, Option<String>>,
+        popup_config: &HashMap<String, Option<String>>,
+        _templates: &mut Handlebars,
+        scale: f32,
+    ) {
+        let default = Self::default();
+        self.cfg_override = (config, scale).into();
+        self.popup_cfg_override.update(popup_config, scale);
+        self.icon = config
+            .get("icon")
+            .and_then(|v| v.clone())
+            .unwrap_or(default.icon);
+        self.lock = config
+            .get("lock_command")
+            .and_then(|v| v.clone())
+            .map(CommandAction)
+            .unwrap_or(default.lock);
+        let commands = config
+            .get("entries")
+            .and_then(|v| v.as_ref())
+            .map(|v| {
+                v.split(',')
+                    .map(|name| name.trim().to_lowercase())
+                    .filter_map(|name| {
+                        Command::from_name(&name).or_else(|| {
+                            eprintln!("[session] Unknown entry `{name}`, it is ignored");
+                            None
+                        })
+                    })
+                    .collect()
+            })
+            .unwrap_or(Command::ALL.to_vec());
+        self.entries = commands
+            .into_iter()
+            .map(|command| {
+                let option = |option: &str| config.get(&format!("{option}.{}", command.name()));
+                SessionEntry {
+                    command,
+                    label: option("label")
+                        .and_then(|v| v.clone())
+                        .unwrap_or(command.default_label().to_string()),
+                    icon: option("icon")
+                        .and_then(|v| v.clone())
+                        .unwrap_or(command.default_icon().to_string()),
+                    confirm: option("confirm")
+                        .and_then(|v| v.into_bool())
+                        .unwrap_or(false),
+                }
+            })
+            .collect();
+        self.confirming = None;
+        self.confirm_color = popup_config
+            .get("confirm_color")
+            .and_then(|v| v.into_color())
+            .or(default.confirm_color);
+        self.confirm_background = popup_config
+            .get("confirm_background")
+            .map(|v| v.into_background())
+            .unwrap_or(default.confirm_background);
+    }
+
+    fn known_keys(&self) -> Vec<ConfigKey> {
+        vec![
+            ConfigKey::new("icon", Kind::Text, Some("󰐥")),
+            ConfigKey::new("lock_command", Kind::Text, Some("loginctl lock-session")),
+            ConfigKey::new("entries", Kind::Text, None),
+            ConfigKey::new("label.*", Kind::Text, None),
+            ConfigKey::new("icon.*", Kind::Text, None),
+            ConfigKey::new("confirm.*", Kind::Bool, None),
+        ]
+    }
+
+    fn known_popup_keys(&self) -> Vec<ConfigKey> {
+        vec![
+            ConfigKey::new("confirm_color", Kind::Color, Some("rgb(255, 85, 85)")),
+            ConfigKey::new(
+                "confirm_background",
+                Kind::Color,
+                Some("rgba(255, 255, 255, 0.2)"),
+            ),
+        ]
+    }
+
+    fn on_click<'a>(
+        &'a self,
+        event: iced::Event,
+        config: &'a LocalModuleConfig,
+    ) -> Option<&'a dyn Action> {
+        self.cfg_override
+            .action
+            .as_ref()
+            .unwrap_or(&config.action)
+            .event(event.clone())
+            .or(match event {
+                Event::Mouse(mouse::Event::ButtonReleased(mouse::Button::Left)) => {
+                    Some(&self.lock as &dyn Action)
+                }
+                _ => None,
+            })
+    }
+
+    fn handle_action(&mut self, action: &dyn Action) {
+        match action.downcast_ref::<SessionAction>() {
+            Some(SessionAction::Confirm(index)) => self.confirming = Some(*index),
+            Some(SessionAction::Cancel) => self.confirming = None,
+            Some(SessionAction::Run(command)) => {
+                if let Some(Err(e)) = self.commands.as_ref().map(|sender| sender.send(*command)) {
+                    eprintln!("Failed to {}: the subscription is gone", e.0.name());
+                }
+            }
+            None => {}
+        }
+    }
+
+    fn collect_data(&mut self) -> Result<(), String> {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .map_err(|e| e.to_string())?;
+        self.unavailable = runtime.block_on(async {
+            let connection = Connection::system().await.map_err(|e| e.to_string())?;
+            Ok::<_, String>(unavailable(&connection).await)
+        })?;
+        Ok(())
+    }
+
+    fn text_content(&self, _template: &Handlebars) -> Option<Result<String, String>> {
+        Some(Ok(self.icon.clone()))
+    }
+
+    fn subscription(&self) -> Option<Subscription<Message>> {
+        Some(Subscription::run(|| {
+            stream::channel(1, |mut sender| async move {
+                let (command_sx, mut command_rx) = mpsc::unbounded_channel();
+                if sender
+                    .send(Message::update(move |reg| {
+                        reg.get_module_mut::<SessionMod>().commands = Some(command_sx)
+                    }))
+                    .await
+                    .is_err()
+                {
+                    return;
+                }
+                let connection = Connection::system().await;
+                // Without logind, only locking works
+                let unavailable = match &connection {
+                    Ok(connection) => unavailable(connection).await,
+                    Err(e) => {
+                        eprintln!("[session] Failed to connect to the system bus: {e}");
+                        Command::ALL[1..].to_vec()
+                    }
+                };
+                if sender
+                    .send(Message::update(move |reg| {
+                        reg.get_module_mut::<SessionMod>().unavailable = unavailable
+                    }))
+                    .await
+                    .is_err()
+                {
+                    return;
+                }
+                let Ok(connection) = connection else {
+                    return;
+                };
+                while let Some(command) = command_rx.recv().await {
+                    if let Err(e) = command.run(&connection).await {
+                        eprintln!("Failed to {}: {e}", command.name());
+                    }
+                }
+            })
+        }))
+    }
+}
+
+/// The commands logind doesn't allow, checked once since this rarely changes while the system runs
+async fn unavailable(connection: &Connection) -> Vec<Command> {
+    let manager = match ManagerProxy::new(connection).await {
+        Ok(manager) => manager,
+        Err(e) => {
+            eprintln!("[session] Failed to connect to logind: {e}");
+            return Command::ALL[1..].to_vec();
+        }
+    };
+    let mut unavailable = vec![];
+    for command in Command::ALL {
+        match command.available(&manager).await {
+            Ok(true) => {}
+            Ok(false) => unavailable.push(command),
+            Err(e) => {
+                eprintln!(
+                    "[session] Failed to check whether the system can {}: {e}",
+                    command.name()
+                );
+                unavailable.push(command);
+            }
+        }
+    }
+    unavailable
+}
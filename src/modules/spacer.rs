@@ -0,0 +1,144 @@
+use std::collections::HashMap;
+
+use bar_rs_derive::Builder;
+use handlebars::Handlebars;
+use iced::{
+    widget::Space,
+    Element,
+    Length::{Fill, Fixed},
+};
+
+use crate::{
+    config::{
+        anchor::BarAnchor,
+        module_config::{LocalModuleConfig, ModuleConfigOverride},
+        parse::StringExt,
+        popup_config::PopupConfig,
+        scale::Scale,
+        schema::{ConfigKey, Kind},
+    },
+    impl_wrapper, FillExt, Message,
+};
+
+use super::Module;
+
+/// An empty space of a fixed `size` along the bar
+#[derive(Debug, Default, Builder)]
+pub struct SpacerMod {
+    cfg_override: ModuleConfigOverride,
+    size: f32,
+}
+
+impl Module for SpacerMod {
+    fn name(&self) -> String {
+        String::from("spacer")
+    }
+
+    fn view(
+        &self,
+        _config: &LocalModuleConfig,
+        _popup_config: &PopupConfig,
+        anchor: &BarAnchor,
+        _template: &Handlebars,
+    ) -> Element<Message> {
+        match anchor.vertical() {
+            true => Space::new(Fill, Fixed(self.size)),
+            false => Space::new(Fixed(self.size), Fill),
+        }
+        .into()
+    }
+
+    fn spacer(&self) -> bool {
+        true
+    }
+
+    impl_wrapper!();
+
+    fn read_config(
+        &mut self,
+        config: &HashMap<String, Option<String>>,
+        _popup_config: &HashMap<String, Option<String>>,
+        _templates: &mut Handlebars,
+        scale: f32,
+    ) {
+        let default = Self::default();
+        self.cfg_override = (config, scale).into();
+        self.size = config
+            .get("size")
+            .and_then(|v| v.into_float())
+            .map(|s| s.scaled(scale))
+            .unwrap_or(default.size);
+    }
+
+    fn known_keys(&self) -> Vec<ConfigKey> {
+        vec![ConfigKey::new("size", Kind::Float, Some("0"))]
+    }
+}
+
+/// An empty space taking up the free space of its section. Multiple stretches divide the space
+/// according to their `weight`.
+#[derive(Debug, Builder)]
+pub struct StretchMod {
+    cfg_override: ModuleConfigOverride,
+    weight: u16,
+}
+
+impl Default for StretchMod {
+    fn default() -> Self {
+        Self {
+            cfg_override: Default::default(),
+            weight: 1,
+        }
+    }
+}
+
+impl Module for StretchMod {
+    fn name(&self) -> String {
+        String::from("stretch")
+    }
+
+    fn view(
+        &self,
+        _config: &LocalModuleConfig,
+        _popup_config: &PopupConfig,
+        _anchor: &BarAnchor,
+        _template: &Handlebars,
+    ) -> Element<Message> {
+        Space::new(Fill, Fill).into()
+    }
+
+    fn stretch(&self) -> Option<u16> {
+        Some(self.weight)
+    }
+
+    fn spacer(&self) -> bool {
+        true
+    }
+
+    impl_wrapper!();
+
+    fn read_config(
+        &mut self,
+        config: &HashMap<String, Option<String>>,
+        _popup_config: &HashMap<String, Option<String>>,
+        _templates: &mut Handlebars,
+        scale: f32,
+    ) {
+        let default = Self::default();
+        self.cfg_override = (config, scale).into();
+        self.weight = config
+            .get("weight")
+            .and_then(|v| v.as_ref())
+            .and_then(|v| {
+                v.parse().ok().filter(|weight| *weight > 0).or_else(|| {
+                    eprintln!("Invalid stretch weight `{v}`, expected a positive integer");
+                    None
+                })
+            })
+            .unwrap_or(default.weight);
+    }
+
+    fn known_keys(&self) -> Vec<ConfigKey> {
+        vec![ConfigKey::new("weight", Kind::Integer, Some("1"))]
+    }
+}
@@ -1,22 +1,809 @@
-use iced::{futures::Stream, stream};
-//use system_tray::client::Client;
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    time::Duration,
+};
 
-use crate::Message;
+use bar_rs_derive::Builder;
+use handlebars::Handlebars;
+use iced::widget::{button::Style, container, image, svg, text};
+use iced::{
+    futures::{
+        channel::mpsc::Sender,
+        stream::{BoxStream, SelectAll},
+        SinkExt, StreamExt,
+    },
+    mouse, stream, Element, Event, Subscription,
+};
+use tokio::{
+    select,
+    sync::mpsc::{self, UnboundedReceiver, UnboundedSender},
+    task::JoinHandle,
+    time::sleep,
+};
+use zbus::{
+    dbus_interface, dbus_proxy, fdo::DBusProxy, CacheProperties, Connection, MessageHeader,
+    SignalContext,
+};
 
-pub fn _system_tray() -> impl Stream<Item = Message> {
-    stream::channel(1, |mut _sender| async move {
-        /*let client = Client::new().await.unwrap();
-        let mut tray_rx = client.subscribe();
+use crate::button::button;
+use crate::config::parse::StringExt;
+use crate::config::popup_config::PopupConfig;
+use crate::list::list;
+use crate::tooltip::ElementExt;
+use crate::{
+    config::{
+        anchor::BarAnchor,
+        module_config::{LocalModuleConfig, ModuleConfigOverride},
+        schema::{ConfigKey, Kind},
+    },
+    fill::FillExt,
+    helpers::{
+        backoff::Backoff,
+        scroll::ScrollDirection,
+        status::{send_status, ModuleStatus},
+    },
+    impl_status, impl_wrapper, Message,
+};
 
-        let initial_items = client.items();
+use super::{Action, Module};
 
-        println!("initial_items: {initial_items:#?}\n\n");
+const WATCHER_NAME: &str = "org.kde.StatusNotifierWatcher";
+const WATCHER_PATH: &str = "/StatusNotifierWatcher";
+/// The size icons of the theme are looked up in, they are scaled to the `icon_size`
+const ICON_LOOKUP_SIZE: u16 = 64;
 
-        // do something with initial items...
-        drop(initial_items);
+#[dbus_proxy(
+    interface = "org.kde.StatusNotifierWatcher",
+    default_service = "org.kde.StatusNotifierWatcher",
+    default_path = "/StatusNotifierWatcher"
+)]
+trait StatusNotifierWatcher {
+    fn register_status_notifier_host(&self, service: &str) -> zbus::Result<()>;
+    /// The items as `{service}{object path}`
+    #[dbus_proxy(property)]
+    fn registered_status_notifier_items(&self) -> zbus::Result<Vec<String>>;
+    #[dbus_proxy(signal)]
+    fn status_notifier_item_registered(&self, service: &str) -> zbus::Result<()>;
+    #[dbus_proxy(signal)]
+    fn status_notifier_item_unregistered(&self, service: &str) -> zbus::Result<()>;
+}
+
+#[dbus_proxy(interface = "org.kde.StatusNotifierItem")]
+trait StatusNotifierItem {
+    fn activate(&self, x: i32, y: i32) -> zbus::Result<()>;
+    fn secondary_activate(&self, x: i32, y: i32) -> zbus::Result<()>;
+    fn context_menu(&self, x: i32, y: i32) -> zbus::Result<()>;
+    fn scroll(&self, delta: i32, orientation: &str) -> zbus::Result<()>;
+    #[dbus_proxy(property)]
+    fn id(&self) -> zbus::Result<String>;
+    #[dbus_proxy(property)]
+    fn title(&self) -> zbus::Result<String>;
+    /// `Passive`, `Active` or `NeedsAttention`
+    #[dbus_proxy(property)]
+    fn status(&self) -> zbus::Result<String>;
+    #[dbus_proxy(property)]
+    fn icon_name(&self) -> zbus::Result<String>;
+    /// An additional directory to look up the icon names of the item in
+    #[dbus_proxy(property)]
+    fn icon_theme_path(&self) -> zbus::Result<String>;
+    /// Images as (width, height, ARGB32 in network byte order)
+    #[dbus_proxy(property)]
+    fn icon_pixmap(&self) -> zbus::Result<Vec<(i32, i32, Vec<u8>)>>;
+    #[dbus_proxy(property)]
+    fn attention_icon_name(&self) -> zbus::Result<String>;
+    #[dbus_proxy(property)]
+    fn attention_icon_pixmap(&self) -> zbus::Result<Vec<(i32, i32, Vec<u8>)>>;
+    #[dbus_proxy(property)]
+    fn item_is_menu(&self) -> zbus::Result<bool>;
+    #[dbus_proxy(signal)]
+    fn new_icon(&self) -> zbus::Result<()>;
+    #[dbus_proxy(signal)]
+    fn new_attention_icon(&self) -> zbus::Result<()>;
+    #[dbus_proxy(signal)]
+    fn new_status(&self, status: &str) -> zbus::Result<()>;
+    #[dbus_proxy(signal)]
+    fn new_title(&self) -> zbus::Result<()>;
+}
+
+/// The watcher bar-rs serves if no other program (like another bar) does, which items register
+/// themselves with
+#[derive(Debug, Default)]
+struct Watcher {
+    items: Vec<String>,
+}
+
+#[dbus_interface(name = "org.kde.StatusNotifierWatcher")]
+impl Watcher {
+    async fn register_status_notifier_item(
+        &mut self,
+        service: &str,
+        #[zbus(header)] header: MessageHeader<'_>,
+        #[zbus(signal_context)] ctxt: SignalContext<'_>,
+    ) {
+        let sender = header
+            .sender()
+            .ok()
+            .flatten()
+            .map(|sender| sender.to_string())
+            .unwrap_or_default();
+        // Some items register their object path, others their service name
+        let item = match service.starts_with('/') {
+            true => format!("{sender}{service}"),
+            false => service_and_path(service).join(""),
+        };
+        if !self.items.contains(&item) {
+            self.items.push(item.clone());
+            let _ = Self::status_notifier_item_registered(&ctxt, &item).await;
+        }
+    }
+
+    fn register_status_notifier_host(&self, _service: &str) {}
+
+    #[dbus_interface(property)]
+    fn registered_status_notifier_items(&self) -> Vec<String> {
+        self.items.clone()
+    }
+
+    #[dbus_interface(property)]
+    fn is_status_notifier_host_registered(&self) -> bool {
+        true
+    }
+
+    #[dbus_interface(property)]
+    fn protocol_version(&self) -> i32 {
+        0
+    }
+
+    #[dbus_interface(signal)]
+    async fn status_notifier_item_registered(
+        ctxt: &SignalContext<'_>,
+        service: &str,
+    ) -> zbus::Result<()>;
+
+    #[dbus_interface(signal)]
+    async fn status_notifier_item_unregistered(
+        ctxt: &SignalContext<'_>,
+        service: &str,
+    ) -> zbus::Result<()>;
+}
+
+/// Splits an item like `:1.42/org/ayatana/NotificationItem/app` into its service and object
+/// path, which is `/StatusNotifierItem` if it isn't given
+fn service_and_path(item: &str) -> [&str; 2] {
+    match item.find('/') {
+        Some(index) => [&item[..index], &item[index..]],
+        None => [item, "/StatusNotifierItem"],
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ItemStatus {
+    /// The item is hidden unless `show_passive` is set
+    Passive,
+    Active,
+    /// The attention icon is shown, blinking if `blink` is set
+    NeedsAttention,
+}
+
+#[derive(Debug, Clone)]
+enum TrayIcon {
+    /// An icon file found by the icon name of the item
+    File(PathBuf),
+    /// The images the item provides, with their width
+    Pixmaps(Vec<(u32, image::Handle)>),
+}
+
+impl TrayIcon {
+    /// Looks up the icon name in the `IconThemePath` of the item, then in the icon theme
+    fn find(name: &str, theme_path: &str) -> Option<Self> {
+        if name.is_empty() {
+            return None;
+        }
+        let path = Path::new(name);
+        if path.is_absolute() {
+            return path.exists().then(|| TrayIcon::File(path.to_path_buf()));
+        }
+        (!theme_path.is_empty())
+            .then(|| find_in_dir(Path::new(theme_path), name, 4))
+            .flatten()
+            .or_else(|| {
+                freedesktop_icons::lookup(name)
+                    .with_size(ICON_LOOKUP_SIZE)
+                    .with_cache()
+                    .find()
+            })
+            .map(TrayIcon::File)
+    }
+
+    fn from_pixmaps(pixmaps: Vec<(i32, i32, Vec<u8>)>) -> Option<Self> {
+        let handles: Vec<_> = pixmaps
+            .into_iter()
+            .filter(|(width, height, data)| {
+                *width > 0 && *height > 0 && data.len() == (width * height * 4) as usize
+            })
+            .map(|(width, height, mut data)| {
+                // ARGB in network byte order to RGBA
+                for pixel in data.chunks_exact_mut(4) {
+                    pixel.rotate_left(1);
+                }
+                (
+                    width as u32,
+                    image::Handle::from_rgba(width as u32, height as u32, data),
+                )
+            })
+            .collect();
+        (!handles.is_empty()).then_some(TrayIcon::Pixmaps(handles))
+    }
+
+    fn view<'a>(&self, size: f32) -> Element<'a, Message> {
+        match self {
+            TrayIcon::File(path) if path.extension().is_some_and(|ext| ext == "svg") => {
+                svg(svg::Handle::from_path(path))
+                    .width(size)
+                    .height(size)
+                    .into()
+            }
+            TrayIcon::File(path) => image(image::Handle::from_path(path))
+                .width(size)
+                .height(size)
+                .into(),
+            TrayIcon::Pixmaps(pixmaps) => {
+                // The one closest to the icon size, which is scaled to fit
+                let handle = pixmaps
+                    .iter()
+                    .min_by_key(|(width, _)| width.abs_diff(size as u32))
+                    .map(|(_, handle)| handle.clone())
+                    .expect("pixmaps are never empty");
+                image(handle).width(size).height(size).into()
+            }
+        }
+    }
+}
+
+/// An icon file named `name`, in `dir` or its subdirectories (like `hicolor/32x32/apps`)
+fn find_in_dir(dir: &Path, name: &str, depth: u8) -> Option<PathBuf> {
+    let entries: Vec<PathBuf> = fs::read_dir(dir)
+        .ok()?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .collect();
+    ["svg", "png"]
+        .iter()
+        .find_map(|ext| {
+            entries
+                .iter()
+                .find(|path| {
+                    path.file_stem().is_some_and(|stem| stem == name)
+                        && path.extension().is_some_and(|e| e == *ext)
+                })
+                .cloned()
+        })
+        .or_else(|| {
+            (depth > 0)
+                .then(|| {
+                    entries
+                        .iter()
+                        .filter(|path| path.is_dir())
+                        .find_map(|dir| find_in_dir(dir, name, depth - 1))
+                })
+                .flatten()
+        })
+}
+
+#[derive(Debug, Clone)]
+struct TrayItem {
+    id: String,
+    title: String,
+    status: ItemStatus,
+    icon: Option<TrayIcon>,
+    attention_icon: Option<TrayIcon>,
+    /// Whether the item only offers a menu, which left clicks open as well
+    is_menu: bool,
+}
+
+impl TrayItem {
+    /// `None` if the item doesn't exist (anymore)
+    async fn read(id: &str, proxy: &StatusNotifierItemProxy<'_>) -> Option<Self> {
+        let status = match proxy.status().await.ok()?.as_str() {
+            "Passive" => ItemStatus::Passive,
+            "NeedsAttention" => ItemStatus::NeedsAttention,
+            _ => ItemStatus::Active,
+        };
+        let theme_path = proxy.icon_theme_path().await.unwrap_or_default();
+        let icon = match TrayIcon::find(&proxy.icon_name().await.unwrap_or_default(), &theme_path) {
+            Some(icon) => Some(icon),
+            None => TrayIcon::from_pixmaps(proxy.icon_pixmap().await.unwrap_or_default()),
+        };
+        let attention_icon = match TrayIcon::find(
+            &proxy.attention_icon_name().await.unwrap_or_default(),
+            &theme_path,
+        ) {
+            Some(icon) => Some(icon),
+            None => TrayIcon::from_pixmaps(proxy.attention_icon_pixmap().await.unwrap_or_default()),
+        };
+        let title = match proxy.title().await.unwrap_or_default() {
+            title if title.is_empty() => proxy.id().await.unwrap_or_default(),
+            title => title,
+        };
+        Some(Self {
+            id: id.to_string(),
+            title,
+            status,
+            icon,
+            attention_icon,
+            is_menu: proxy.item_is_menu().await.unwrap_or_default(),
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum TrayAction {
+    Activate(String),
+    SecondaryActivate(String),
+    ContextMenu(String),
+    Scroll(String, i32),
+}
+
+impl TrayAction {
+    fn item(&self) -> &str {
+        match self {
+            TrayAction::Activate(item)
+            | TrayAction::SecondaryActivate(item)
+            | TrayAction::ContextMenu(item)
+            | TrayAction::Scroll(item, _) => item,
+        }
+    }
+
+    async fn run(&self, proxy: &StatusNotifierItemProxy<'_>) -> zbus::Result<()> {
+        // The position of the bar on the screen isn't known
+        match self {
+            TrayAction::Activate(_) => proxy.activate(0, 0).await,
+            TrayAction::SecondaryActivate(_) => proxy.secondary_activate(0, 0).await,
+            TrayAction::ContextMenu(_) => proxy.context_menu(0, 0).await,
+            TrayAction::Scroll(_, delta) => proxy.scroll(*delta, "vertical").await,
+        }
+    }
+}
+
+impl Action for TrayAction {
+    fn as_message(&self) -> Message {
+        let action = self.clone();
+        Message::update(move |reg| reg.get_module_mut::<SysTrayMod>().handle_action(&action))
+    }
+}
+
+#[derive(Debug, Builder)]
+pub struct SysTrayMod {
+    cfg_override: ModuleConfigOverride,
+    /// In the order they were added
+    items: Vec<TrayItem>,
+    /// Shown for items without a usable icon
+    placeholder: String,
+    show_passive: bool,
+    blink: bool,
+    blink_interval: Duration,
+    /// Whether blinking attention icons are currently shown
+    blink_on: bool,
+    /// Passes clicks on items to the subscription
+    actions: Option<UnboundedSender<TrayAction>>,
+    status: ModuleStatus,
+}
+
+impl Default for SysTrayMod {
+    fn default() -> Self {
+        Self {
+            cfg_override: Default::default(),
+            items: vec![],
+            placeholder: "󰣆".to_string(),
+            show_passive: false,
+            blink: true,
+            blink_interval: Duration::from_millis(500),
+            blink_on: true,
+            actions: None,
+            status: ModuleStatus::Ok,
+        }
+    }
+}
+
+impl SysTrayMod {
+    fn visible_items(&self) -> impl Iterator<Item = &TrayItem> {
+        self.items
+            .iter()
+            .filter(|item| self.show_passive || item.status != ItemStatus::Passive)
+    }
+
+    fn needs_attention(&self) -> bool {
+        self.visible_items()
+            .any(|item| item.status == ItemStatus::NeedsAttention)
+    }
+
+    fn item_view<'a>(
+        &'a self,
+        item: &'a TrayItem,
+        config: &'a LocalModuleConfig,
+        anchor: &BarAnchor,
+    ) -> Element<'a, Message> {
+        let size = self.cfg_override.icon_size.unwrap_or(config.icon_size);
+        let attention = item.status == ItemStatus::NeedsAttention && self.blink_on;
+        let icon = match attention {
+            true => item.attention_icon.as_ref().or(item.icon.as_ref()),
+            false => item.icon.as_ref(),
+        };
+        let icon = match icon {
+            Some(icon) => icon.view(size),
+            None => text(self.cfg_override.icon(config, &self.placeholder))
+                .fill(anchor)
+                .size(size)
+                .color(self.cfg_override.icon_color.unwrap_or(config.icon_color))
+                .font(config.icon_font)
+                .into(),
+        };
+        let id = item.id.clone();
+        let is_menu = item.is_menu;
+        button(
+            container(icon)
+                .center(size)
+                .padding(self.cfg_override.icon_margin.unwrap_or(config.icon_margin)),
+        )
+        .on_event_try(move |event, _, _, _, _| {
+            let id = id.clone();
+            let action = match event {
+                Event::Mouse(mouse::Event::ButtonReleased(mouse::Button::Left)) if is_menu => {
+                    TrayAction::ContextMenu(id)
+                }
+                Event::Mouse(mouse::Event::ButtonReleased(mouse::Button::Left)) => {
+                    TrayAction::Activate(id)
+                }
+                Event::Mouse(mouse::Event::ButtonReleased(mouse::Button::Middle)) => {
+                    TrayAction::SecondaryActivate(id)
+                }
+                Event::Mouse(mouse::Event::ButtonReleased(mouse::Button::Right)) => {
+                    TrayAction::ContextMenu(id)
+                }
+                _ => return None,
+            };
+            Some(action.as_message())
+        })
+        .on_scroll(|delta| {
+            let delta = match ScrollDirection::from_delta(delta)? {
+                ScrollDirection::Up => -1,
+                ScrollDirection::Down => 1,
+            };
+            Some(TrayAction::Scroll(item.id.clone(), delta).as_message())
+        })
+        .style(|_, _| Style::default())
+        .tooltip_maybe((!item.title.is_empty()).then(|| text(&item.title).size(12)))
+    }
+}
+
+impl Module for SysTrayMod {
+    fn name(&self) -> String {
+        "tray".to_string()
+    }
+
+    fn active(&self) -> bool {
+        self.visible_items().next().is_some()
+    }
+
+    impl_status!();
+
+    fn view(
+        &self,
+        config: &LocalModuleConfig,
+        _popup_config: &PopupConfig,
+        anchor: &BarAnchor,
+        _handlebars: &Handlebars,
+    ) -> Element<Message> {
+        list(
+            anchor,
+            self.visible_items()
+                .map(|item| self.item_view(item, config, anchor)),
+        )
+        .spacing(self.cfg_override.spacing.unwrap_or(config.spacing))
+        .into()
+    }
+
+    impl_wrapper!();
+
+    fn read_config(
+        &mut self,
+        config: &HashMap<String, Option<String>>,
+        _popup_config: &HashMap<String, Option<String>>,
+        _templates: &mut Handlebars,
+        scale: f32,
+    ) {
+        let default = Self::default();
+        self.cfg_override = (config, scale).into();
+        self.placeholder = config
+            .get("placeholder")
+            .and_then(|v| v.clone())
+            .unwrap_or(default.placeholder);
+        self.show_passive = config
+            .get("show_passive")
+            .and_then(|v| v.into_bool())
+            .unwrap_or(default.show_passive);
+        self.blink = config
+            .get("blink")
+            .and_then(|v| v.into_bool())
+            .unwrap_or(default.blink);
+        self.blink_interval = config
+            .get("blink_interval")
+            .and_then(|v| v.into_duration())
+            .unwrap_or(default.blink_interval);
+    }
+
+    fn known_keys(&self) -> Vec<ConfigKey> {
+        vec![
+            ConfigKey::new("placeholder", Kind::Text, Some("󰣆")),
+            ConfigKey::new("show_passive", Kind::Bool, Some("false")),
+            ConfigKey::new("blink", Kind::Bool, Some("true")),
+            ConfigKey::new("blink_interval", Kind::Duration, Some("500ms")),
+        ]
+    }
+
+    fn handle_action(&mut self, action: &dyn Action) {
+        let Some(action) = action.downcast_ref::<TrayAction>() else {
+            return;
+        };
+        if let Some(Err(e)) = self
+            .actions
+            .as_ref()
+            .map(|sender| sender.send(action.clone()))
+        {
+            eprintln!("[tray] Failed to pass a click to the tray: {e}");
+        }
+    }
+
+    fn text_content(&self, _template: &Handlebars) -> Option<Result<String, String>> {
+        Some(Ok(self
+            .visible_items()
+            .map(|item| item.title.as_str())
+            .collect::<Vec<_>>()
+            .join(", ")))
+    }
+
+    fn subscription(&self) -> Option<Subscription<Message>> {
+        // Only wakes up while an attention icon is blinking
+        let blink = (self.blink && self.needs_attention()).then(|| {
+            iced::time::every(self.blink_interval).map(|_| {
+                Message::update(|reg| {
+                    let m = reg.get_module_mut::<SysTrayMod>();
+                    m.blink_on = !m.blink_on;
+                })
+            })
+        });
+        let tray = Subscription::run(|| {
+            stream::channel(1, |mut sender| async move {
+                let (action_sx, mut action_rx) = mpsc::unbounded_channel();
+                if sender
+                    .send(Message::update(move |reg| {
+                        reg.get_module_mut::<SysTrayMod>().actions = Some(action_sx)
+                    }))
+                    .await
+                    .is_err()
+                {
+                    return;
+                }
+                let mut backoff = Backoff::default();
+                while let Err(e) = watch_tray(&mut sender, &mut action_rx, &mut backoff).await {
+                    if !send_status::<SysTrayMod>(&mut sender, ModuleStatus::Unavailable(e)).await {
+                        return;
+                    }
+                    backoff.wait().await;
+                }
+            })
+        });
+        Some(Subscription::batch(
+            [Some(tray), blink].into_iter().flatten(),
+        ))
+    }
+}
+
+/// An item and the task following its changes, which is stopped once the item is removed
+struct WatchedItem {
+    proxy: StatusNotifierItemProxy<'static>,
+    task: JoinHandle<()>,
+}
+
+impl Drop for WatchedItem {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// Reads the item whenever it reports a change, `None` once it's gone
+async fn watch_item(
+    connection: &Connection,
+    id: &str,
+    updates: UnboundedSender<(String, Option<TrayItem>)>,
+) -> zbus::Result<WatchedItem> {
+    let [service, path] = service_and_path(id);
+    let proxy = StatusNotifierItemProxy::builder(connection)
+        .destination(service.to_string())?
+        .path(path.to_string())?
+        // Items don't emit PropertiesChanged, but the signals below
+        .cache_properties(CacheProperties::No)
+        .build()
+        .await?;
+    let mut changes: SelectAll<BoxStream<'static, ()>> = SelectAll::new();
+    changes.push(proxy.receive_new_icon().await?.map(|_| ()).boxed());
+    changes.push(
+        proxy
+            .receive_new_attention_icon()
+            .await?
+            .map(|_| ())
+            .boxed(),
+    );
+    changes.push(proxy.receive_new_status().await?.map(|_| ()).boxed());
+    changes.push(proxy.receive_new_title().await?.map(|_| ()).boxed());
+    let id = id.to_string();
+    let item_proxy = proxy.clone();
+    let task = tokio::spawn(async move {
+        loop {
+            let item = TrayItem::read(&id, &item_proxy).await;
+            if updates.send((id.clone(), item)).is_err() || changes.next().await.is_none() {
+                return;
+            }
+            // Apps often change their icon and status at once
+            sleep(Duration::from_millis(50)).await;
+        }
+    });
+    Ok(WatchedItem { proxy, task })
+}
+
+/// Follows the items of the tray and runs the clicks on them. Returns if the bar is closing.
+async fn watch_tray(
+    sender: &mut Sender<Message>,
+    actions: &mut UnboundedReceiver<TrayAction>,
+    backoff: &mut Backoff,
+) -> Result<(), String> {
+    let connection = Connection::session()
+        .await
+        .map_err(|e| format!("Failed to connect to the session bus: {e}"))?;
+    // Serve the watcher if no other program does
+    let own_watcher = connection
+        .object_server()
+        .at(WATCHER_PATH, Watcher::default())
+        .await
+        .map_err(|e| e.to_string())?
+        && connection.request_name(WATCHER_NAME).await.is_ok();
+    if !own_watcher {
+        let _ = connection
+            .object_server()
+            .remove::<Watcher, _>(WATCHER_PATH)
+            .await;
+    }
+    let watcher = StatusNotifierWatcherProxy::new(&connection)
+        .await
+        .map_err(|e| e.to_string())?;
+    let mut registered = watcher
+        .receive_status_notifier_item_registered()
+        .await
+        .map_err(|e| e.to_string())?;
+    let mut unregistered = watcher
+        .receive_status_notifier_item_unregistered()
+        .await
+        .map_err(|e| e.to_string())?;
+    let mut owner_changes = DBusProxy::new(&connection)
+        .await
+        .map_err(|e| e.to_string())?
+        .receive_name_owner_changed()
+        .await
+        .map_err(|e| e.to_string())?;
+    if !own_watcher {
+        let host = format!("org.kde.StatusNotifierHost-{}", std::process::id());
+        let _ = connection.request_name(host.as_str()).await;
+        watcher
+            .register_status_notifier_host(&host)
+            .await
+            .map_err(|e| format!("Failed to register with the tray watcher: {e}"))?;
+    }
+    backoff.reset();
+
+    let (update_sx, mut updates) = mpsc::unbounded_channel();
+    let mut watched: HashMap<String, WatchedItem> = HashMap::new();
+    let mut items: Vec<TrayItem> = vec![];
+    for id in watcher
+        .registered_status_notifier_items()
+        .await
+        .unwrap_or_default()
+    {
+        match watch_item(&connection, &id, update_sx.clone()).await {
+            Ok(item) => {
+                watched.insert(id, item);
+            }
+            Err(e) => eprintln!("[tray] Failed to watch the tray item {id}: {e}"),
+        }
+    }
+    loop {
+        let items_to_send = items.clone();
+        if sender
+            .send(Message::update(move |reg| {
+                let m = reg.get_module_mut::<SysTrayMod>();
+                m.items = items_to_send;
+                m.set_status(ModuleStatus::Ok);
+            }))
+            .await
+            .is_err()
+        {
+            return Ok(());
+        }
+        select! {
+            Some(signal) = registered.next() => {
+                let Ok(args) = signal.args() else {
+                    continue;
+                };
+                let id = args.service().to_string();
+                if watched.contains_key(&id) {
+                    continue;
+                }
+                match watch_item(&connection, &id, update_sx.clone()).await {
+                    Ok(item) => {
+                        watched.insert(id, item);
+                    }
+                    Err(e) => eprintln!("[tray] Failed to watch the tray item {id}: {e}"),
+                }
+            }
+            Some(signal) = unregistered.next() => {
+                let Ok(args) = signal.args() else {
+                    continue;
+                };
+                watched.remove(*args.service());
+                items.retain(|item| item.id != *args.service());
+            }
+            Some(signal) = owner_changes.next() => {
+                let Ok(args) = signal.args() else {
+                    continue;
+                };
+                if args.new_owner().is_some() {
+                    continue;
+                }
+                // The app quit without unregistering its items
+                let gone: Vec<String> = watched
+                    .keys()
+                    .filter(|id| service_and_path(id)[0] == args.name().as_str())
+                    .cloned()
+                    .collect();
+                for id in gone {
+                    watched.remove(&id);
+                    items.retain(|item| item.id != id);
+                    if own_watcher {
+                        unregister_item(&connection, &id).await;
+                    }
+                }
+            }
+            Some((id, item)) = updates.recv() => {
+                let index = items.iter().position(|i| i.id == id);
+                match (index, item) {
+                    (Some(index), Some(item)) => items[index] = item,
+                    (None, Some(item)) => items.push(item),
+                    (Some(index), None) => {
+                        items.remove(index);
+                    }
+                    (None, None) => {}
+                }
+            }
+            Some(action) = actions.recv() => {
+                if let Some(item) = watched.get(action.item()) {
+                    if let Err(e) = action.run(&item.proxy).await {
+                        eprintln!("[tray] Failed to click the tray item {}: {e}", action.item());
+                    }
+                }
+            }
+        }
+    }
+}
 
-        while let Ok(ev) = tray_rx.recv().await {
-            println!("{ev:#?}"); // do something with event...
-        }*/
-    })
+/// Removes the item from the watcher served by bar-rs
+async fn unregister_item(connection: &Connection, id: &str) {
+    let Ok(watcher) = connection
+        .object_server()
+        .interface::<_, Watcher>(WATCHER_PATH)
+        .await
+    else {
+        return;
+    };
+    watcher.get_mut().await.items.retain(|item| item != id);
+    let _ = Watcher::status_notifier_item_unregistered(watcher.signal_context(), id).await;
 }
@@ -0,0 +1,561 @@
+use std::collections::HashMap;
+
+use bar_rs_derive::Builder;
+use handlebars::Handlebars;
+use iced::widget::{button::Style, container, row, scrollable, text, Column, Container};
+use iced::{
+    futures::{
+        channel::mpsc::Sender,
+        stream::{self, select_all, BoxStream},
+        SinkExt, StreamExt,
+    },
+    Alignment, Color, Element,
+    Length::Fill,
+    Subscription,
+};
+use tokio::{
+    select,
+    sync::mpsc::{self, UnboundedReceiver, UnboundedSender},
+};
+use zbus::{dbus_proxy, zvariant::OwnedObjectPath, Connection};
+
+use crate::button::button;
+use crate::config::parse::StringExt;
+use crate::config::popup_config::{PopupConfig, PopupConfigOverride};
+use crate::list::list;
+use crate::{
+    config::{
+        anchor::BarAnchor,
+        module_config::{LocalModuleConfig, ModuleConfigOverride},
+        schema::{ConfigKey, Kind},
+    },
+    fill::FillExt,
+    helpers::status::{send_status, ModuleStatus},
+    impl_on_click, impl_status, impl_wrapper, Message,
+};
+
+use super::{Action, Module};
+
+#[dbus_proxy(
+    interface = "org.freedesktop.systemd1.Manager",
+    default_service = "org.freedesktop.systemd1",
+    default_path = "/org/freedesktop/systemd1"
+)]
+trait Manager {
+    fn load_unit(&self, name: &str) -> zbus::Result<OwnedObjectPath>;
+    fn start_unit(&self, name: &str, mode: &str) -> zbus::Result<OwnedObjectPath>;
+    fn stop_unit(&self, name: &str, mode: &str) -> zbus::Result<OwnedObjectPath>;
+    /// Makes systemd emit signals, e.g. when the state of a unit changes
+    fn subscribe(&self) -> zbus::Result<()>;
+}
+
+#[dbus_proxy(
+    interface = "org.freedesktop.systemd1.Unit",
+    default_service = "org.freedesktop.systemd1"
+)]
+trait Unit {
+    #[dbus_proxy(property)]
+    fn load_state(&self) -> zbus::Result<String>;
+    #[dbus_proxy(property)]
+    fn active_state(&self) -> zbus::Result<String>;
+    #[dbus_proxy(property)]
+    fn sub_state(&self) -> zbus::Result<String>;
+}
+
+#[derive(Debug, Clone, Hash, PartialEq, Eq)]
+struct UnitId {
+    name: String,
+    /// Whether this is a unit of the user's service manager (`systemctl --user`)
+    user: bool,
+}
+
+#[derive(Debug, Clone)]
+struct UnitState {
+    id: UnitId,
+    /// `active`, `inactive`, `failed`, `activating`, ... or the load state if the unit couldn't
+    /// be loaded (e.g. `not-found`)
+    state: String,
+    /// A more detailed state, e.g. `running` or `exited`
+    sub_state: String,
+}
+
+impl UnitState {
+    fn unknown(id: UnitId) -> Self {
+        Self {
+            id,
+            state: "unknown".to_string(),
+            sub_state: String::new(),
+        }
+    }
+
+    async fn read(id: UnitId, proxy: &UnitProxy<'_>) -> zbus::Result<Self> {
+        let load_state = proxy.load_state().await?;
+        if load_state != "loaded" {
+            return Ok(Self {
+                id,
+                state: load_state,
+                sub_state: String::new(),
+            });
+        }
+        Ok(Self {
+            id,
+            state: proxy.active_state().await?,
+            sub_state: proxy.sub_state().await?,
+        })
+    }
+
+    fn active(&self) -> bool {
+        self.state == "active"
+    }
+}
+
+/// Starts or stops a unit, passed to the subscription
+#[derive(Debug)]
+struct SetUnit {
+    id: UnitId,
+    start: bool,
+}
+
+#[derive(Debug, Builder)]
+pub struct SystemdMod {
+    cfg_override: ModuleConfigOverride,
+    popup_cfg_override: PopupConfigOverride,
+    icon: String,
+    units: Vec<UnitState>,
+    active_color: Color,
+    inactive_color: Color,
+    /// The color of units which are starting, stopping or reloading
+    transition_color: Color,
+    /// Passes unit changes to the subscription
+    actions: Option<UnboundedSender<SetUnit>>,
+    status: ModuleStatus,
+}
+
+impl Default for SystemdMod {
+    fn default() -> Self {
+        Self {
+            cfg_override: Default::default(),
+            popup_cfg_override: PopupConfigOverride {
+                width: Some(300),
+                height: Some(200),
+                ..Default::default()
+            },
+            icon: "󰒓".to_string(),
+            units: vec![],
+            active_color: Color::from_rgb(0.3, 0.8, 0.3),
+            inactive_color: Color::from_rgb(0.9, 0.2, 0.2),
+            transition_color: Color::from_rgb(0.9, 0.8, 0.2),
+            actions: None,
+            status: ModuleStatus::Ok,
+        }
+    }
+}
+
+impl SystemdMod {
+    fn color(&self, unit: &UnitState) -> Color {
+        match unit.state.as_str() {
+            "active" => self.active_color,
+            "activating" | "deactivating" | "reloading" | "refreshing" => self.transition_color,
+            _ => self.inactive_color,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum SystemdAction {
+    /// Stops the unit at this index if it is active, and starts it otherwise
+    Toggle(usize),
+}
+
+impl Action for SystemdAction {
+    fn as_message(&self) -> Message {
+        let action = *self;
+        Message::update(move |reg| reg.get_module_mut::<SystemdMod>().handle_action(&action))
+    }
+}
+
+impl Module for SystemdMod {
+    fn name(&self) -> String {
+        "systemd".to_string()
+    }
+
+    impl_status!();
+
+    fn active(&self) -> bool {
+        !self.units.is_empty()
+    }
+
+    fn view(
+        &self,
+        config: &LocalModuleConfig,
+        popup_config: &PopupConfig,
+        anchor: &BarAnchor,
+        _handlebars: &Handlebars,
+    ) -> Element<Message> {
+        button(
+            list![
+                anchor,
+                container(
+                    text(self.cfg_override.icon(config, &self.icon))
+                        .fill(anchor)
+                        .size(self.cfg_override.icon_size.unwrap_or(config.icon_size))
+                        .color(self.cfg_override.icon_color.unwrap_or(config.icon_color))
+                        .font(config.icon_font)
+                )
+                .padding(self.cfg_override.icon_margin.unwrap_or(config.icon_margin)),
+                list(
+                    anchor,
+                    self.units.iter().map(|unit| {
+                        text("●")
+                            .fill(anchor)
+                            .size(self.cfg_override.font_size.unwrap_or(config.font_size))
+                            .color(self.color(unit))
+                            .into()
+                    })
+                )
+                .padding(self.cfg_override.text_margin.unwrap_or(config.text_margin)),
+            ]
+            .spacing(self.cfg_override.spacing.unwrap_or(config.spacing)),
+        )
+        .on_event_with(Message::popup::<Self>(
+            self.popup_cfg_override.width.unwrap_or(popup_config.width),
+            self.popup_cfg_override
+                .height
+                .unwrap_or(popup_config.height),
+            anchor,
+        ))
+        .style(|_, _| Style::default())
+        .into()
+    }
+
+    fn popup_cfg_override(&self) -> Option<&PopupConfigOverride> {
+        Some(&self.popup_cfg_override)
+    }
+
+    fn popup_view<'a>(
+        &'a self,
+        config: &'a PopupConfig,
+        _template: &Handlebars,
+    ) -> Element<'a, Message> {
+        let text_color = self
+            .popup_cfg_override
+            .text_color
+            .unwrap_or(config.text_color);
+        let fmt_text = |content: String, color: Color| -> Container<'a, Message> {
+            container(
+                text(content)
+                    .size(
+                        self.popup_cfg_override
+                            .font_size
+                            .unwrap_or(config.font_size),
+                    )
+                    .color(color),
+            )
+            .padding(
+                self.popup_cfg_override
+                    .text_margin
+                    .unwrap_or(config.text_margin),
+            )
+        };
+
+        let units = self
+            .units
+            .iter()
+            .enumerate()
+            .map(|(index, unit)| -> Element<'a, Message> {
+                let name = match unit.id.user {
+                    true => format!("{} (user)", unit.id.name),
+                    false => unit.id.name.clone(),
+                };
+                let state = match unit.sub_state.is_empty() {
+                    true => unit.state.clone(),
+                    false => format!("{} ({})", unit.state, unit.sub_state),
+                };
+                button(
+                    row![
+                        fmt_text("●".to_string(), self.color(unit)),
+                        fmt_text(name, text_color).width(Fill),
+                        fmt_text(state, text_color),
+                    ]
+                    .align_y(Alignment::Center),
+                )
+                .on_event(SystemdAction::Toggle(index).as_message())
+                .style(|_, _| Style::default())
+                .width(Fill)
+                .into()
+            });
+
+        container(scrollable(Column::with_children(units).spacing(
+            self.popup_cfg_override.spacing.unwrap_or(config.spacing),
+        )))
+        .into()
+    }
+
+    impl_wrapper!();
+
+    fn read_config(
+        &mut self,
+        config: &HashMap<String, Option<String>>,
+        popup_config: &HashMap<String, Option<String>>,
+        _templates: &mut Handlebars,
+        scale: f32,
+    ) {
+        let default = Self::default();
+        self.cfg_override = (config, scale).into();
+        self.popup_cfg_override.update(popup_config, scale);
+        self.icon = config
+            .get("icon")
+            .and_then(|v| v.clone())
+            .unwrap_or(default.icon);
+        let units = |key: &str, user: bool| -> Vec<UnitState> {
+            config
+                .get(key)
+                .and_then(|v| v.as_ref())
+                .map(|v| {
+                    v.split(',')
+                        .map(|name| name.trim())
+                        .filter(|name| !name.is_empty())
+                        .map(|name| {
+                            UnitState::unknown(UnitId {
+                                name: name.to_string(),
+                                user,
+                            })
+                        })
+                        .collect()
+                })
+                .unwrap_or_default()
+        };
+        self.units = units("units", false);
+        self.units.extend(units("user_units", true));
+        self.active_color = config
+            .get("active_color")
+            .and_then(|v| v.into_color())
+            .unwrap_or(default.active_color);
+        self.inactive_color = config
+            .get("inactive_color")
+            .and_then(|v| v.into_color())
+            .unwrap_or(default.inactive_color);
+        self.transition_color = config
+            .get("transition_color")
+            .and_then(|v| v.into_color())
+            .unwrap_or(default.transition_color);
+    }
+
+    fn known_keys(&self) -> Vec<ConfigKey> {
+        vec![
+            ConfigKey::new("icon", Kind::Text, Some("󰒓")),
+            ConfigKey::new("units", Kind::Text, None),
+            ConfigKey::new("user_units", Kind::Text, None),
+            ConfigKey::new("active_color", Kind::Color, Some("rgb(30%, 80%, 30%)")),
+            ConfigKey::new("inactive_color", Kind::Color, Some("rgb(90%, 20%, 20%)")),
+            ConfigKey::new("transition_color", Kind::Color, Some("rgb(90%, 80%, 20%)")),
+        ]
+    }
+
+    impl_on_click!();
+
+    fn has_popup(&self) -> bool {
+        true
+    }
+
+    fn handle_action(&mut self, action: &dyn Action) {
+        let Some(SystemdAction::Toggle(index)) = action.downcast_ref::<SystemdAction>() else {
+            return;
+        };
+        let Some(unit) = self.units.get(*index) else {
+            return;
+        };
+        let set = SetUnit {
+            id: unit.id.clone(),
+            start: !unit.active(),
+        };
+        if let Some(Err(e)) = self.actions.as_ref().map(|sender| sender.send(set)) {
+            eprintln!(
+                "Failed to change unit `{}`: the subscription is gone",
+                e.0.id.name
+            );
+        }
+    }
+
+    fn collect_data(&mut self) -> Result<(), String> {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .map_err(|e| e.to_string())?;
+        let ids = self.units.iter().map(|u| u.id.clone()).collect::<Vec<_>>();
+        self.units = runtime
+            .block_on(async {
+                let connections = Connections::new(&ids).await;
+                let mut units = vec![];
+                for id in ids {
+                    let proxy = connections.unit(&id).await?;
+                    units.push(UnitState::read(id, &proxy).await?);
+                }
+                zbus::Result::Ok(units)
+            })
+            .map_err(|e| format!("Failed to read the unit states from systemd: {e}"))?;
+        Ok(())
+    }
+
+    fn text_content(&self, _template: &Handlebars) -> Option<Result<String, String>> {
+        Some(Ok(self
+            .units
+            .iter()
+            .map(|unit| format!("{}: {}", unit.id.name, unit.state))
+            .collect::<Vec<_>>()
+            .join(", ")))
+    }
+
+    fn subscription(&self) -> Option<Subscription<Message>> {
+        let ids = self.units.iter().map(|u| u.id.clone()).collect::<Vec<_>>();
+        if ids.is_empty() {
+            return None;
+        }
+        Some(Subscription::run_with_id(
+            ("systemd", ids.clone()),
+            iced::stream::channel(1, move |mut sender| async move {
+                let (action_sx, mut action_rx) = mpsc::unbounded_channel();
+                if sender
+                    .send(Message::update(move |reg| {
+                        let m = reg.get_module_mut::<SystemdMod>();
+                        m.actions = Some(action_sx);
+                        m.set_status(ModuleStatus::Ok);
+                    }))
+                    .await
+                    .is_err()
+                {
+                    return;
+                }
+                if let Err(e) = watch_units(&mut sender, &mut action_rx, ids).await {
+                    let status = ModuleStatus::Error(format!("Failed to watch systemd units: {e}"));
+                    send_status::<SystemdMod>(&mut sender, status).await;
+                }
+            }),
+        ))
+    }
+}
+
+/// The connections to the system and user service manager, if any of their units are used
+struct Connections {
+    system: Option<Connection>,
+    user: Option<Connection>,
+}
+
+impl Connections {
+    async fn new(ids: &[UnitId]) -> Self {
+        let system = match ids.iter().any(|id| !id.user) {
+            true => Connection::system()
+                .await
+                .inspect_err(|e| eprintln!("Failed to connect to the system bus: {e}"))
+                .ok(),
+            false => None,
+        };
+        let user = match ids.iter().any(|id| id.user) {
+            true => Connection::session()
+                .await
+                .inspect_err(|e| eprintln!("Failed to connect to the session bus: {e}"))
+                .ok(),
+            false => None,
+        };
+        Self { system, user }
+    }
+
+    fn get(&self, user: bool) -> zbus::Result<&Connection> {
+        match user {
+            true => self.user.as_ref(),
+            false => self.system.as_ref(),
+        }
+        .ok_or_else(|| zbus::Error::Failure("Not connected to the service manager".to_string()))
+    }
+
+    async fn manager(&self, user: bool) -> zbus::Result<ManagerProxy<'static>> {
+        ManagerProxy::new(self.get(user)?).await
+    }
+
+    async fn unit(&self, id: &UnitId) -> zbus::Result<UnitProxy<'static>> {
+        let path = self.manager(id.user).await?.load_unit(&id.name).await?;
+        UnitProxy::builder(self.get(id.user)?)
+            .path(path)?
+            .build()
+            .await
+    }
+}
+
+async fn send_unit(sender: &mut Sender<Message>, index: usize, unit: UnitState) -> bool {
+    sender
+        .send(Message::update(move |reg| {
+            if let Some(u) = reg.get_module_mut::<SystemdMod>().units.get_mut(index) {
+                *u = unit
+            }
+        }))
+        .await
+        .is_ok()
+}
+
+/// Follows the state of the units and starts or stops them. Returns if the bar is closing.
+async fn watch_units(
+    sender: &mut Sender<Message>,
+    actions: &mut UnboundedReceiver<SetUnit>,
+    ids: Vec<UnitId>,
+) -> zbus::Result<()> {
+    let connections = Connections::new(&ids).await;
+    for user in [false, true] {
+        if let Ok(manager) = connections.manager(user).await {
+            manager.subscribe().await?;
+        }
+    }
+    let mut proxies = vec![];
+    let mut changes: Vec<BoxStream<'static, usize>> = vec![];
+    for (index, id) in ids.iter().enumerate() {
+        let proxy = match connections.unit(id).await {
+            Ok(proxy) => proxy,
+            Err(e) => {
+                eprintln!("Failed to load unit `{}`: {e}", id.name);
+                proxies.push(None);
+                continue;
+            }
+        };
+        changes.push(
+            stream::select(
+                proxy.receive_active_state_changed().await.map(|_| ()),
+                proxy.receive_sub_state_changed().await.map(|_| ()),
+            )
+            .map(move |_| index)
+            .boxed(),
+        );
+        let unit = UnitState::read(id.clone(), &proxy)
+            .await
+            .unwrap_or_else(|_| UnitState::unknown(id.clone()));
+        if !send_unit(sender, index, unit).await {
+            return Ok(());
+        }
+        proxies.push(Some(proxy));
+    }
+    let mut changes = select_all(changes);
+    loop {
+        select! {
+            Some(index) = changes.next() => {
+                let (Some(Some(proxy)), Some(id)) = (proxies.get(index), ids.get(index)) else {
+                    continue;
+                };
+                let unit = UnitState::read(id.clone(), proxy)
+                    .await
+                    .unwrap_or_else(|_| UnitState::unknown(id.clone()));
+                if !send_unit(sender, index, unit).await {
+                    return Ok(());
+                }
+            }
+            Some(set) = actions.recv() => {
+                let manager = connections.manager(set.id.user).await?;
+                let result = match set.start {
+                    true => manager.start_unit(&set.id.name, "replace").await,
+                    false => manager.stop_unit(&set.id.name, "replace").await,
+                };
+                if let Err(e) = result {
+                    eprintln!("Failed to change unit `{}`: {e}", set.id.name);
+                }
+            }
+            else => return Ok(()),
+        }
+    }
+}
@@ -0,0 +1,116 @@
+//! Runs a module without the bar, so that tests can feed it the messages of its subscription and
+//! check what it shows.
+use std::{collections::HashMap, sync::Arc};
+
+use crate::{
+    helpers::templates::Templates,
+    registry::{Builder, Registry},
+    Message,
+};
+
+use super::Module;
+
+/// A registry holding a single module and the instances added with [Harness::add_instance]
+pub struct Harness {
+    registry: Registry,
+    templates: Templates,
+    instances: Vec<String>,
+}
+
+impl Harness {
+    /// Registers the module and reads `options` as its `[module:<name>]` section
+    pub fn new<M>(options: &[(&str, &str)]) -> Self
+    where
+        M: Module + Builder<Output = M>,
+    {
+        let mut registry = Registry::default();
+        registry.register_module::<M>();
+        let name = registry.get_module::<M>().name();
+        let mut harness = Self {
+            registry,
+            templates: Templates::default(),
+            instances: vec![],
+        };
+        harness.read_config(&name, options);
+        harness
+    }
+
+    /// Adds the instance `name` of the module, like `memory.home`, and reads `options` as its
+    /// `[module:<name>]` section
+    pub fn add_instance(&mut self, name: &str, options: &[(&str, &str)]) {
+        self.instances.push(name.to_string());
+        self.registry.sync_instances(self.instances.iter());
+        self.read_config(name, options);
+    }
+
+    fn read_config(&mut self, instance: &str, options: &[(&str, &str)]) {
+        let config = options
+            .iter()
+            .map(|(key, value)| (key.to_string(), Some(value.to_string())))
+            .collect();
+        self.registry
+            .get_instance_mut(instance)
+            .expect("the instance doesn't exist")
+            .read_config(
+                &config,
+                &HashMap::new(),
+                self.templates.get_mut(instance),
+                1.,
+            );
+    }
+
+    /// Handles a message like the bar does, as far as it concerns the module: updates are applied
+    /// to it, everything else is dropped
+    pub fn send(&mut self, msg: Message) {
+        match msg {
+            Message::Update(f) => {
+                Arc::into_inner(f).expect("the update was cloned").0(&mut self.registry)
+            }
+            Message::Batch(msgs) | Message::Coalesced(msgs) => {
+                msgs.into_iter().for_each(|msg| self.send(msg))
+            }
+            Message::Instance { name, msg } => {
+                let previous = self.registry.set_target(Some(name));
+                self.send(*msg);
+                self.registry.set_target(previous);
+            }
+            _ => {}
+        }
+    }
+
+    /// Changes the module like its subscription would
+    pub fn update<M: Module>(&mut self, f: impl FnOnce(&mut M) + Send + Sync + 'static) {
+        self.send(Message::update(move |reg| f(reg.get_module_mut::<M>())));
+    }
+
+    /// Changes the instance named `name`, see [Harness::update]
+    pub fn update_instance<M: Module>(
+        &mut self,
+        name: &str,
+        f: impl FnOnce(&mut M) + Send + Sync + 'static,
+    ) {
+        self.send(Message::Instance {
+            name: name.to_string(),
+            msg: Box::new(Message::update(move |reg| f(reg.get_module_mut::<M>()))),
+        });
+    }
+
+    pub fn module<M: Module>(&self) -> &M {
+        self.registry.get_module::<M>()
+    }
+
+    /// The text the module shows, panics if it shows none or its format fails to render
+    pub fn text<M: Module>(&self) -> String {
+        self.instance_text(&self.module::<M>().name())
+    }
+
+    /// The text the instance named `name` shows, see [Harness::text]
+    pub fn instance_text(&self, name: &str) -> String {
+        self.registry
+            .get_instance(name)
+            .expect("the instance doesn't exist")
+            .text_content(self.templates.get(name))
+            .expect("the module doesn't show any text")
+            .unwrap()
+    }
+}
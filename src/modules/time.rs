@@ -1,39 +1,150 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::time::Duration;
 
 use bar_rs_derive::Builder;
-use chrono::Local;
-use handlebars::Handlebars;
+use chrono::{Local, Locale, Timelike};
+use chrono_tz::Tz;
+use handlebars::{Handlebars, RenderError};
 use iced::widget::{container, text};
-use iced::Element;
+use iced::{futures::SinkExt, stream, Element, Event, Subscription};
+use serde_json::{json, Value};
+use tokio::time::sleep;
 
+use crate::bar_text::bar_text;
+use crate::config::parse::StringExt;
 use crate::config::popup_config::PopupConfig;
+use crate::helpers::{clock, locale, UnEscapeString};
+use crate::impl_wrapper;
 use crate::{
     config::{
         anchor::BarAnchor,
         module_config::{LocalModuleConfig, ModuleConfigOverride},
+        schema::{ConfigKey, Kind},
     },
     fill::FillExt,
-    Message, NERD_FONT,
+    Message,
 };
-use crate::{impl_on_click, impl_wrapper};
 
-use super::Module;
+use super::{Action, Module};
 
 #[derive(Debug, Builder)]
 pub struct TimeMod {
     cfg_override: ModuleConfigOverride,
     icon: String,
     fmt: String,
+    fmt_12h: String,
+    /// Shown instead of the format while toggled, instead of switching to the 12/24-hour format
+    fmt_alt: Option<String>,
+    seconds: bool,
+    twelve_hour: bool,
+    locale: Locale,
+    /// Whether the user toggled the format at runtime, kept across config reloads
+    toggled: bool,
+    timezones: Vec<Zone>,
+    show_all_zones: bool,
+    zone_separator: String,
+    /// The zone currently shown if only one is shown at a time
+    zone: usize,
+}
+
+#[derive(Debug)]
+struct Zone {
+    /// `None` for the local timezone
+    tz: Option<Tz>,
+    label: String,
+}
+
+impl Default for Zone {
+    fn default() -> Self {
+        Self {
+            tz: None,
+            label: "local".to_string(),
+        }
+    }
 }
 
 impl Default for TimeMod {
     fn default() -> Self {
         Self {
             cfg_override: Default::default(),
-            icon: "".to_string(),
+            icon: "\u{e38a}".to_string(),
             fmt: "%H:%M".to_string(),
+            fmt_12h: "%I:%M %p".to_string(),
+            fmt_alt: None,
+            seconds: false,
+            twelve_hour: false,
+            locale: Locale::POSIX,
+            toggled: false,
+            timezones: vec![Zone::default()],
+            show_all_zones: false,
+            zone_separator: " | ".to_string(),
+            zone: 0,
+        }
+    }
+}
+
+impl TimeMod {
+    /// Toggling shows `format_alt` if it's set, otherwise the other clock
+    fn format(&self) -> &String {
+        let twelve_hour = match (&self.fmt_alt, self.toggled) {
+            (Some(fmt_alt), true) => return fmt_alt,
+            (Some(_), false) => self.twelve_hour,
+            (None, toggled) => self.twelve_hour != toggled,
+        };
+        match twelve_hour {
+            true => &self.fmt_12h,
+            false => &self.fmt,
+        }
+    }
+
+    fn zone_time(&self, zone: &Zone, handlebars: &Handlebars) -> Result<String, RenderError> {
+        let now = clock::now();
+        let time = match zone.tz {
+            Some(tz) => now.with_timezone(&tz).fixed_offset(),
+            None => now.fixed_offset(),
+        };
+        let ctx = BTreeMap::from([
+            ("time", locale::format(&time, self.format(), self.locale)),
+            ("zone_label", zone.label.clone()),
+            ("offset", time.format("%:z").to_string()),
+        ]);
+        handlebars.render("time", &ctx)
+    }
+
+    /// The time of all zones shown at once, or of the current one
+    fn time(&self, handlebars: &Handlebars) -> Result<String, RenderError> {
+        match self.show_all_zones {
+            true => Ok(self
+                .timezones
+                .iter()
+                .map(|zone| self.zone_time(zone, handlebars))
+                .collect::<Result<Vec<_>, _>>()?
+                .join(&self.zone_separator)),
+            false => self.zone_time(
+                &self.timezones[self.zone % self.timezones.len()],
+                handlebars,
+            ),
         }
     }
+
+    /// Whether left clicks cycle through the timezones, which moves the format toggle to right
+    /// clicks
+    fn cycles_zones(&self) -> bool {
+        self.timezones.len() > 1 && !self.show_all_zones
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum TimeAction {
+    ToggleFormat,
+    NextZone,
+}
+
+impl Action for TimeAction {
+    fn as_message(&self) -> Message {
+        let action = *self;
+        Message::update(move |reg| reg.get_module_mut::<TimeMod>().handle_action(&action))
+    }
 }
 
 impl Module for TimeMod {
@@ -46,25 +157,32 @@ impl Module for TimeMod {
         config: &LocalModuleConfig,
         _popup_config: &PopupConfig,
         anchor: &BarAnchor,
-        _handlebars: &Handlebars,
+        handlebars: &Handlebars,
     ) -> Element<Message> {
-        let time = Local::now();
+        let time = self
+            .time(handlebars)
+            .inspect_err(|e| eprintln!("Failed to render time: {e}"))
+            .unwrap_or_default();
         list![
             anchor,
             container(
-                text!("{}", self.icon)
+                text(self.cfg_override.icon(config, &self.icon))
                     .size(self.cfg_override.icon_size.unwrap_or(config.icon_size))
                     .color(self.cfg_override.icon_color.unwrap_or(config.icon_color))
-                    .font(NERD_FONT)
+                    .font(config.icon_font)
                     .fill(anchor)
             )
             .fill(anchor)
             .padding(self.cfg_override.icon_margin.unwrap_or(config.icon_margin)),
             container(
-                text!("{}", time.format(&self.fmt))
-                    .size(self.cfg_override.font_size.unwrap_or(config.font_size))
-                    .color(self.cfg_override.text_color.unwrap_or(config.text_color))
-                    .fill(anchor)
+                bar_text(
+                    time,
+                    anchor,
+                    self.cfg_override.vertical_text(config, anchor)
+                )
+                .size(self.cfg_override.font_size.unwrap_or(config.font_size))
+                .font(self.cfg_override.text_font.unwrap_or(config.text_font))
+                .color(self.cfg_override.text_color.unwrap_or(config.text_color))
             )
             .fill(anchor)
             .padding(self.cfg_override.text_margin.unwrap_or(config.text_margin)),
@@ -79,19 +197,193 @@ impl Module for TimeMod {
         &mut self,
         config: &HashMap<String, Option<String>>,
         _popup_config: &HashMap<String, Option<String>>,
-        _templates: &mut Handlebars,
+        templates: &mut Handlebars,
+        scale: f32,
     ) {
         let default = Self::default();
-        self.cfg_override = config.into();
+        self.cfg_override = (config, scale).into();
         self.icon = config
             .get("icon")
             .and_then(|v| v.clone())
             .unwrap_or(default.icon);
+        self.seconds = config
+            .get("seconds")
+            .and_then(|v| v.into_bool())
+            .unwrap_or(default.seconds);
+        self.locale = locale::read_locale(config.get("locale").and_then(|v| v.as_deref()));
+        // The clock of the locale, unless it's set explicitly
+        self.twelve_hour = config
+            .get("twelve_hour")
+            .and_then(|v| v.into_bool())
+            .unwrap_or_else(|| locale::twelve_hour(self.locale));
         self.fmt = config
             .get("format")
             .and_then(|v| v.clone())
-            .unwrap_or(default.fmt);
+            .unwrap_or(match self.seconds {
+                true => "%H:%M:%S".to_string(),
+                false => default.fmt,
+            });
+        self.fmt_12h =
+            config
+                .get("format_12h")
+                .and_then(|v| v.clone())
+                .unwrap_or(match self.seconds {
+                    true => "%I:%M:%S %p".to_string(),
+                    false => default.fmt_12h,
+                });
+        self.fmt_alt = config.get("format_alt").and_then(|v| v.clone());
+        let labels = config
+            .get("labels")
+            .and_then(|v| v.as_ref())
+            .map(|v| v.split(',').map(|l| l.trim()).collect::<Vec<_>>())
+            .unwrap_or_default();
+        let zones = config.get("timezones").and_then(|v| v.as_ref());
+        self.timezones = zones
+            .map(|zones| {
+                zones
+                    .split(',')
+                    .map(|zone| zone.trim())
+                    .filter(|zone| !zone.is_empty())
+                    .enumerate()
+                    .filter_map(|(i, zone)| {
+                        let tz = match zone.eq_ignore_ascii_case("local") {
+                            true => None,
+                            false => match zone.parse::<Tz>() {
+                                Ok(tz) => Some(tz),
+                                Err(e) => {
+                                    eprintln!("Invalid timezone `{zone}` in module:time: {e}");
+                                    return None;
+                                }
+                            },
+                        };
+                        Some(Zone {
+                            tz,
+                            label: labels
+                                .get(i)
+                                .filter(|l| !l.is_empty())
+                                .unwrap_or(&zone)
+                                .to_string(),
+                        })
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .filter(|zones| !zones.is_empty())
+            .unwrap_or(default.timezones);
+        self.show_all_zones = config
+            .get("show_all_zones")
+            .and_then(|v| v.into_bool())
+            .unwrap_or(default.show_all_zones);
+        self.zone_separator = config
+            .get("zone_separator")
+            .unescape()
+            .unwrap_or(default.zone_separator);
+        templates
+            .register_template_string(
+                "time",
+                config
+                    .get("zone_format")
+                    .unescape()
+                    .unwrap_or(match zones.is_some() {
+                        true => "{{zone_label}} {{time}}".to_string(),
+                        false => "{{time}}".to_string(),
+                    }),
+            )
+            .unwrap_or_else(|e| eprintln!("Failed to parse time zone format: {e}"));
     }
 
-    impl_on_click!();
+    fn known_keys(&self) -> Vec<ConfigKey> {
+        vec![
+            ConfigKey::new("icon", Kind::Text, Some("\u{e38a}")),
+            ConfigKey::new("seconds", Kind::Bool, Some("false")),
+            ConfigKey::new("locale", Kind::Text, None),
+            // Defaults to the clock of the locale
+            ConfigKey::new("twelve_hour", Kind::Bool, None),
+            ConfigKey::new("format", Kind::Text, Some("%H:%M")),
+            ConfigKey::new("format_12h", Kind::Text, Some("%I:%M %p")),
+            ConfigKey::new("format_alt", Kind::Text, None),
+            ConfigKey::new("timezones", Kind::Text, None),
+            ConfigKey::new("labels", Kind::Text, None),
+            ConfigKey::new("show_all_zones", Kind::Bool, Some("false")),
+            ConfigKey::new("zone_separator", Kind::Text, Some(" | ")),
+            ConfigKey::new("zone_format", Kind::Text, None),
+        ]
+    }
+
+    fn on_click<'a>(
+        &'a self,
+        event: iced::Event,
+        config: &'a LocalModuleConfig,
+    ) -> Option<&'a dyn Action> {
+        self.cfg_override
+            .action
+            .as_ref()
+            .unwrap_or(&config.action)
+            .event(event.clone())
+            .or(match event {
+                Event::Mouse(iced::mouse::Event::ButtonReleased(iced::mouse::Button::Left)) => {
+                    match self.cycles_zones() {
+                        true => Some(&TimeAction::NextZone as &dyn Action),
+                        false => Some(&TimeAction::ToggleFormat as &dyn Action),
+                    }
+                }
+                Event::Mouse(iced::mouse::Event::ButtonReleased(iced::mouse::Button::Right))
+                    if self.cycles_zones() =>
+                {
+                    Some(&TimeAction::ToggleFormat as &dyn Action)
+                }
+                _ => None,
+            })
+    }
+
+    fn handle_action(&mut self, action: &dyn Action) {
+        match action.downcast_ref::<TimeAction>() {
+            Some(TimeAction::ToggleFormat) => self.toggled = !self.toggled,
+            Some(TimeAction::NextZone) => {
+                self.zone = (self.zone + 1) % self.timezones.len();
+            }
+            None => {}
+        }
+    }
+
+    fn save_state(&self) -> Value {
+        json!({
+            "toggled": self.toggled,
+            "zone": self.zone,
+        })
+    }
+
+    fn load_state(&mut self, state: Value) {
+        if let Some(toggled) = state["toggled"].as_bool() {
+            self.toggled = toggled;
+        }
+        if let Some(zone) = state["zone"].as_u64() {
+            self.zone = zone as usize;
+        }
+    }
+
+    fn text_content(&self, template: &Handlebars) -> Option<Result<String, String>> {
+        Some(self.time(template).map_err(|e| e.to_string()))
+    }
+
+    fn subscription(&self) -> Option<Subscription<Message>> {
+        let seconds = self.seconds;
+        Some(Subscription::run_with_id(
+            ("time", seconds),
+            stream::channel(1, move |mut sender| async move {
+                loop {
+                    // Wake up right at the start of the next second/minute
+                    let now = Local::now();
+                    let millis = now.timestamp_subsec_millis().min(999) as u64;
+                    let wait = match seconds {
+                        true => 1000 - millis,
+                        false => (60 - now.second().min(59) as u64) * 1000 - millis,
+                    };
+                    sleep(Duration::from_millis(wait)).await;
+                    if sender.send(Message::update(|_| {})).await.is_err() {
+                        return;
+                    }
+                }
+            }),
+        ))
+    }
 }
@@ -0,0 +1,537 @@
+use std::{collections::HashMap, process::Stdio, time::Duration};
+
+use bar_rs_derive::Builder;
+use handlebars::Handlebars;
+use iced::widget::{button::Style, column, container, row, scrollable, text, Column, Container};
+use iced::{
+    futures::{channel::mpsc::Sender, SinkExt},
+    mouse, stream, Alignment, Element, Event,
+    Length::Fill,
+    Subscription,
+};
+use tokio::{
+    process::Command,
+    select,
+    sync::mpsc::{self, UnboundedReceiver, UnboundedSender},
+    time::sleep,
+};
+
+use crate::bar_text::bar_text;
+use crate::button::button;
+use crate::config::parse::StringExt;
+use crate::config::popup_config::{PopupConfig, PopupConfigOverride};
+use crate::{
+    config::{
+        anchor::BarAnchor,
+        module_config::{LocalModuleConfig, ModuleConfigOverride},
+        schema::{ConfigKey, Kind},
+    },
+    fill::FillExt,
+    impl_on_click, impl_wrapper, Message,
+};
+
+use super::{click_message, Action, Module};
+
+/// How the output of the `command` is turned into a list of updates
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+enum Parser {
+    /// Every line is an update, starting with the package, like `checkupdates` prints them
+    #[default]
+    Lines,
+    /// `apt list --upgradable`: `package/suite version arch [upgradable from: old]`, after a
+    /// `Listing...` line
+    Apt,
+    /// `dnf check-update`: `package.arch version repo`, obsoleted packages are ignored
+    Dnf,
+}
+
+impl Parser {
+    fn named(parser: &str) -> Option<Self> {
+        match parser.trim() {
+            "lines" => Some(Self::Lines),
+            "apt" => Some(Self::Apt),
+            "dnf" => Some(Self::Dnf),
+            _ => None,
+        }
+    }
+
+    fn parse(self, output: &str) -> Vec<Update> {
+        output
+            .lines()
+            .map(str::trim)
+            .take_while(|line| self != Parser::Dnf || !line.starts_with("Obsoleting"))
+            .filter(|line| !line.is_empty())
+            .filter_map(|line| match self {
+                Parser::Lines => {
+                    let (package, details) = line.split_once(char::is_whitespace).unzip();
+                    Some(Update::new(
+                        package.unwrap_or(line),
+                        details.unwrap_or_default().trim(),
+                    ))
+                }
+                Parser::Apt => {
+                    let (package, rest) = line.split_once('/')?;
+                    Some(Update::new(
+                        package,
+                        rest.split_whitespace().nth(1).unwrap_or_default(),
+                    ))
+                }
+                Parser::Dnf => match line.split_whitespace().collect::<Vec<_>>()[..] {
+                    [package, version, _repo] => Some(Update::new(
+                        package.rsplit_once('.').map_or(package, |(name, _)| name),
+                        version,
+                    )),
+                    _ => None,
+                },
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Update {
+    package: String,
+    /// Usually the new version
+    details: String,
+}
+
+impl Update {
+    fn new(package: &str, details: &str) -> Self {
+        Self {
+            package: package.to_string(),
+            details: details.to_string(),
+        }
+    }
+}
+
+/// Passed to the subscription
+#[derive(Debug)]
+enum Request {
+    Refresh,
+    Upgrade,
+}
+
+#[derive(Debug, Builder)]
+pub struct UpdatesMod {
+    cfg_override: ModuleConfigOverride,
+    popup_cfg_override: PopupConfigOverride,
+    icon: String,
+    /// Prints the available updates
+    command: String,
+    parser: Parser,
+    /// How often the updates are checked
+    interval: Duration,
+    /// Installs the updates, run in the `terminal`
+    upgrade: Option<String>,
+    /// Runs the command following it in a terminal
+    terminal: String,
+    /// `None` until the updates were checked, or if the command can't be run
+    updates: Option<Vec<Update>>,
+    /// Passes refreshes and upgrades to the subscription
+    requests: Option<UnboundedSender<Request>>,
+}
+
+impl Default for UpdatesMod {
+    fn default() -> Self {
+        Self {
+            cfg_override: Default::default(),
+            popup_cfg_override: PopupConfigOverride {
+                width: Some(350),
+                height: Some(300),
+                ..Default::default()
+            },
+            icon: "󰚰".to_string(),
+            command: "checkupdates".to_string(),
+            parser: Parser::default(),
+            interval: Duration::from_secs(3600),
+            upgrade: None,
+            terminal: "${TERMINAL:-xterm} -e".to_string(),
+            updates: None,
+            requests: None,
+        }
+    }
+}
+
+impl UpdatesMod {
+    fn count(&self) -> usize {
+        self.updates.as_ref().map_or(0, |updates| updates.len())
+    }
+
+    fn request(&self, request: Request) {
+        if let Some(Err(_)) = self.requests.as_ref().map(|sender| sender.send(request)) {
+            eprintln!("Failed to check for updates: the subscription is gone");
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum UpdatesAction {
+    /// Checks for updates right away
+    Refresh,
+    /// Runs the `upgrade` command in the `terminal`, and checks for updates once it's done
+    Upgrade,
+}
+
+impl Action for UpdatesAction {
+    fn as_message(&self) -> Message {
+        let action = *self;
+        Message::update(move |reg| reg.get_module_mut::<UpdatesMod>().handle_action(&action))
+    }
+}
+
+impl Module for UpdatesMod {
+    fn name(&self) -> String {
+        "updates".to_string()
+    }
+
+    fn active(&self) -> bool {
+        self.updates.is_some()
+    }
+
+    fn view(
+        &self,
+        config: &LocalModuleConfig,
+        popup_config: &PopupConfig,
+        anchor: &BarAnchor,
+        _handlebars: &Handlebars,
+    ) -> Element<Message> {
+        let popup = Message::popup::<Self>(
+            self.popup_cfg_override.width.unwrap_or(popup_config.width),
+            self.popup_cfg_override
+                .height
+                .unwrap_or(popup_config.height),
+            anchor,
+        );
+        // Middle clicks check for updates, unless `on_click_middle` is set
+        let middle = click_message(self, mouse::Button::Middle, config)
+            .unwrap_or(UpdatesAction::Refresh.as_message());
+        let right = click_message(self, mouse::Button::Right, config);
+        let left = click_message(self, mouse::Button::Left, config);
+        button(
+            list![
+                anchor,
+                container(
+                    text(self.cfg_override.icon(config, &self.icon))
+                        .fill(anchor)
+                        .size(self.cfg_override.icon_size.unwrap_or(config.icon_size))
+                        .color(self.cfg_override.icon_color.unwrap_or(config.icon_color))
+                        .font(config.icon_font)
+                )
+                .padding(self.cfg_override.icon_margin.unwrap_or(config.icon_margin)),
+                container(
+                    bar_text(
+                        self.count().to_string(),
+                        anchor,
+                        self.cfg_override.vertical_text(config, anchor)
+                    )
+                    .size(self.cfg_override.font_size.unwrap_or(config.font_size))
+                    .font(self.cfg_override.text_font.unwrap_or(config.text_font))
+                    .color(self.cfg_override.text_color.unwrap_or(config.text_color))
+                )
+                .padding(self.cfg_override.text_margin.unwrap_or(config.text_margin)),
+            ]
+            .spacing(self.cfg_override.spacing.unwrap_or(config.spacing)),
+        )
+        .on_event_try(
+            move |event, layout, cursor, clipboard, viewport| match event {
+                Event::Mouse(mouse::Event::ButtonReleased(mouse::Button::Middle)) => {
+                    Some(middle.clone())
+                }
+                Event::Mouse(mouse::Event::ButtonReleased(mouse::Button::Right)) => right.clone(),
+                Event::Mouse(mouse::Event::ButtonReleased(mouse::Button::Left)) => left
+                    .clone()
+                    .or_else(|| Some(popup(event, layout, cursor, clipboard, viewport))),
+                _ => None,
+            },
+        )
+        .style(|_, _| Style::default())
+        .into()
+    }
+
+    fn popup_cfg_override(&self) -> Option<&PopupConfigOverride> {
+        Some(&self.popup_cfg_override)
+    }
+
+    fn popup_view<'a>(
+        &'a self,
+        config: &'a PopupConfig,
+        _template: &Handlebars,
+    ) -> Element<'a, Message> {
+        let fmt_text = |content: String| -> Container<'a, Message> {
+            container(
+                text(content).size(
+                    self.popup_cfg_override
+                        .font_size
+                        .unwrap_or(config.font_size),
+                ),
+            )
+            .padding(
+                self.popup_cfg_override
+                    .text_margin
+                    .unwrap_or(config.text_margin),
+            )
+        };
+        let spacing = self.popup_cfg_override.spacing.unwrap_or(config.spacing);
+        let summary = match self.count() {
+            0 => "Everything is up to date".to_string(),
+            1 => "1 update available".to_string(),
+            count => format!("{count} updates available"),
+        };
+        let updates = self
+            .updates
+            .iter()
+            .flatten()
+            .map(|update| -> Element<'a, Message> {
+                row![
+                    fmt_text(update.package.clone()).width(Fill),
+                    fmt_text(update.details.clone()),
+                ]
+                .align_y(Alignment::Center)
+                .into()
+            });
+        let actions = row![button(fmt_text("Refresh".to_string()))
+            .on_event(UpdatesAction::Refresh.as_message())
+            .style(|_, _| Style::default())]
+        .push_maybe(self.upgrade.as_ref().map(|_| {
+            button(fmt_text("Upgrade".to_string()))
+                .on_event(UpdatesAction::Upgrade.as_message())
+                .style(|_, _| Style::default())
+        }))
+        .spacing(spacing);
+        column![
+            fmt_text(summary),
+            container(scrollable(Column::with_children(updates).spacing(spacing))).height(Fill),
+            actions,
+        ]
+        .spacing(spacing)
+        .into()
+    }
+
+    impl_wrapper!();
+
+    fn read_config(
+        &mut self,
+        config: &HashMap<String, Option<String>>,
+        popup_config: &HashMap<String, Option<String>>,
+        _templates: &mut Handlebars,
+        scale: f32,
+    ) {
+        let default = Self::default();
+        self.cfg_override = (config, scale).into();
+        self.popup_cfg_override.update(popup_config, scale);
+        self.icon = config
+            .get("icon")
+            .and_then(|v| v.clone())
+            .unwrap_or(default.icon);
+        self.command = config
+            .get("command")
+            .and_then(|v| v.clone())
+            .unwrap_or(default.command);
+        self.parser = config
+            .get("parser")
+            .and_then(|v| v.as_ref())
+            .and_then(|parser| {
+                Parser::named(parser).or_else(|| {
+                    eprintln!("Unknown parser `{parser}` of the updates module, using `lines`");
+                    None
+                })
+            })
+            .unwrap_or(default.parser);
+        self.interval = config
+            .get("interval")
+            .and_then(|v| v.into_duration())
+            .filter(|interval| !interval.is_zero())
+            .unwrap_or(default.interval);
+        self.upgrade = config.get("upgrade").and_then(|v| v.clone());
+        self.terminal = config
+            .get("terminal")
+            .and_then(|v| v.clone())
+            .unwrap_or(default.terminal);
+    }
+
+    fn known_keys(&self) -> Vec<ConfigKey> {
+        vec![
+            ConfigKey::new("icon", Kind::Text, Some("󰚰")),
+            ConfigKey::new("command", Kind::Text, Some("checkupdates")),
+            ConfigKey::new(
+                "parser",
+                Kind::Choice(&["lines", "apt", "dnf"]),
+                Some("lines"),
+            ),
+            ConfigKey::new("interval", Kind::Duration, Some("1h")),
+            ConfigKey::new("upgrade", Kind::Text, None),
+            ConfigKey::new("terminal", Kind::Text, Some("${TERMINAL:-xterm} -e")),
+        ]
+    }
+
+    impl_on_click!();
+
+    fn has_popup(&self) -> bool {
+        true
+    }
+
+    fn handle_action(&mut self, action: &dyn Action) {
+        match action.downcast_ref::<UpdatesAction>() {
+            Some(UpdatesAction::Refresh) => self.request(Request::Refresh),
+            Some(UpdatesAction::Upgrade) => self.request(Request::Upgrade),
+            None => {}
+        }
+    }
+
+    fn collect_data(&mut self) -> Result<(), String> {
+        let output = std::process::Command::new("sh")
+            .arg("-c")
+            .arg(&self.command)
+            .output()
+            .map_err(|e| format!("Failed to run `{}`: {e}", self.command))?;
+        self.updates = Some(self.parser.parse(&String::from_utf8_lossy(&output.stdout)));
+        Ok(())
+    }
+
+    fn text_content(&self, _template: &Handlebars) -> Option<Result<String, String>> {
+        self.updates
+            .as_ref()
+            .map(|updates| Ok(updates.len().to_string()))
+    }
+
+    fn subscription(&self) -> Option<Subscription<Message>> {
+        let command = self.command.clone();
+        let parser = self.parser;
+        let interval = self.interval;
+        let upgrade = self
+            .upgrade
+            .as_ref()
+            .map(|upgrade| format!("{} {upgrade}", self.terminal));
+        Some(Subscription::run_with_id(
+            (
+                "updates",
+                command.clone(),
+                parser,
+                interval,
+                upgrade.clone(),
+            ),
+            stream::channel(1, move |mut sender| async move {
+                let (request_sx, mut request_rx) = mpsc::unbounded_channel();
+                if sender
+                    .send(Message::update(move |reg| {
+                        reg.get_module_mut::<UpdatesMod>().requests = Some(request_sx)
+                    }))
+                    .await
+                    .is_err()
+                {
+                    return;
+                }
+                watch_updates(
+                    &mut sender,
+                    &mut request_rx,
+                    &command,
+                    parser,
+                    interval,
+                    upgrade.as_deref(),
+                )
+                .await;
+            }),
+        ))
+    }
+}
+
+/// Checks for updates on the interval and when requested, and runs the upgrade. Returns if the
+/// bar is closing.
+async fn watch_updates(
+    sender: &mut Sender<Message>,
+    requests: &mut UnboundedReceiver<Request>,
+    command: &str,
+    parser: Parser,
+    interval: Duration,
+    upgrade: Option<&str>,
+) {
+    loop {
+        // Package managers exit with all kinds of codes if there are updates, so only failing to
+        // run the command counts
+        let updates = Command::new("sh")
+            .arg("-c")
+            .arg(command)
+            .stdin(Stdio::null())
+            .output()
+            .await
+            .map(|output| parser.parse(&String::from_utf8_lossy(&output.stdout)))
+            .inspect_err(|e| eprintln!("Failed to run `{command}`: {e}"))
+            .ok();
+        if sender
+            .send(Message::update(move |reg| {
+                reg.get_module_mut::<UpdatesMod>().updates = updates
+            }))
+            .await
+            .is_err()
+        {
+            return;
+        }
+        select! {
+            _ = sleep(interval) => {}
+            Some(request) = requests.recv() => {
+                if let (Request::Upgrade, Some(upgrade)) = (request, upgrade) {
+                    // Checked again once the terminal is closed
+                    if let Err(e) = Command::new("sh").arg("-c").arg(upgrade).status().await {
+                        eprintln!("Failed to run `{upgrade}`: {e}");
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn packages(parser: Parser, output: &str) -> Vec<String> {
+        parser
+            .parse(output)
+            .into_iter()
+            .map(|update| format!("{}: {}", update.package, update.details))
+            .collect()
+    }
+
+    #[test]
+    fn parses_checkupdates() {
+        assert_eq!(
+            packages(
+                Parser::Lines,
+                "linux 6.12.8.arch1-1 -> 6.12.9.arch1-1\nmesa 1:24.3.2-1 -> 1:24.3.3-1\n\n"
+            ),
+            [
+                "linux: 6.12.8.arch1-1 -> 6.12.9.arch1-1",
+                "mesa: 1:24.3.2-1 -> 1:24.3.3-1",
+            ]
+        );
+        assert!(Parser::Lines.parse("").is_empty());
+    }
+
+    #[test]
+    fn parses_apt() {
+        assert_eq!(
+            packages(
+                Parser::Apt,
+                "Listing... Done\ncurl/stable-security 7.88.1-10+deb12u8 amd64 [upgradable from: 7.88.1-10+deb12u7]\n"
+            ),
+            ["curl: 7.88.1-10+deb12u8"]
+        );
+    }
+
+    #[test]
+    fn parses_dnf() {
+        let output =
+            "Last metadata expiration check: 0:42:01 ago on Mon 13 Jan 2025 10:00:00 AM CET.
+
+kernel.x86_64                 6.12.9-200.fc41               updates
+vim-enhanced.x86_64           2:9.1.984-1.fc41              updates
+Obsoleting Packages
+grub2-tools.x86_64            1:2.12-15.fc41                updates
+    grub2-tools.x86_64        1:2.12-10.fc41                @updates
+";
+        assert_eq!(
+            packages(Parser::Dnf, output),
+            ["kernel: 6.12.9-200.fc41", "vim-enhanced: 2:9.1.984-1.fc41"]
+        );
+    }
+}
@@ -0,0 +1,412 @@
+use std::{collections::HashMap, io, time::Duration};
+
+use bar_rs_derive::Builder;
+use chrono::{DateTime, Local};
+use handlebars::Handlebars;
+use iced::widget::{button::Style, container, scrollable, text};
+use iced::{futures::SinkExt, stream, Element, Subscription};
+use serde_json::{json, Value};
+use tokio::time::sleep;
+
+use crate::bar_text::bar_text;
+use crate::button::button;
+use crate::config::popup_config::{PopupConfig, PopupConfigOverride};
+use crate::{
+    config::{
+        anchor::BarAnchor,
+        module_config::{LocalModuleConfig, ModuleConfigOverride},
+        schema::{ConfigKey, Kind},
+    },
+    fill::FillExt,
+    helpers::{
+        source::{Host, Source},
+        UnEscapeString,
+    },
+    impl_on_click, impl_wrapper, Message,
+};
+
+use super::Module;
+
+#[derive(Debug, Default, Clone)]
+struct UptimeState {
+    /// In seconds
+    uptime: u64,
+    /// The load average over 1, 5 and 15 minutes
+    load: [f64; 3],
+    boot_time: Option<DateTime<Local>>,
+}
+
+impl UptimeState {
+    fn read(source: &impl Source) -> io::Result<Self> {
+        let invalid = |file: &str| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("unexpected format of {file}"),
+            )
+        };
+        let uptime = source
+            .read("/proc/uptime")?
+            .split_whitespace()
+            .next()
+            .and_then(|secs| secs.parse::<f64>().ok())
+            .ok_or_else(|| invalid("/proc/uptime"))?;
+        let loadavg = source.read("/proc/loadavg")?;
+        let mut load = loadavg
+            .split_whitespace()
+            .map(|load| load.parse::<f64>().map_err(|_| invalid("/proc/loadavg")));
+        let mut next_load = || load.next().unwrap_or(Err(invalid("/proc/loadavg")));
+        Ok(Self {
+            uptime: uptime as u64,
+            load: [next_load()?, next_load()?, next_load()?],
+            boot_time: boot_time(source),
+        })
+    }
+
+    fn context(&self) -> Value {
+        json!({
+            "days": self.uptime / 86400,
+            "hours": self.uptime / 3600 % 24,
+            "minutes": self.uptime / 60 % 60,
+            "total_hours": self.uptime / 3600,
+            "load1": self.load[0],
+            "load5": self.load[1],
+            "load15": self.load[2],
+        })
+    }
+}
+
+/// The boot time is read from `btime` in `/proc/stat` rather than being calculated from the
+/// uptime, which doesn't include the time the system was suspended.
+fn boot_time(source: &impl Source) -> Option<DateTime<Local>> {
+    let stat = source.read("/proc/stat").ok()?;
+    let btime = stat
+        .lines()
+        .find_map(|line| line.strip_prefix("btime "))?
+        .trim()
+        .parse()
+        .ok()?;
+    DateTime::from_timestamp(btime, 0).map(|time| time.with_timezone(&Local))
+}
+
+/// The kernel release, e.g. `6.12.8-arch1-1`
+fn kernel_version(source: &impl Source) -> Option<String> {
+    source
+        .read("/proc/version")
+        .ok()?
+        .split_whitespace()
+        .nth(2)
+        .map(|version| version.to_string())
+}
+
+#[derive(Debug, Builder)]
+pub struct UptimeMod {
+    cfg_override: ModuleConfigOverride,
+    popup_cfg_override: PopupConfigOverride,
+    state: UptimeState,
+    kernel: Option<String>,
+    icon: String,
+    /// How often the uptime is refreshed
+    interval: Duration,
+    boot_time_format: String,
+}
+
+impl Default for UptimeMod {
+    fn default() -> Self {
+        Self {
+            cfg_override: Default::default(),
+            popup_cfg_override: PopupConfigOverride {
+                width: Some(250),
+                height: Some(100),
+                ..Default::default()
+            },
+            state: UptimeState::default(),
+            kernel: None,
+            icon: "󰔟".to_string(),
+            interval: Duration::from_secs(60),
+            boot_time_format: "%Y-%m-%d %H:%M".to_string(),
+        }
+    }
+}
+
+impl Module for UptimeMod {
+    fn name(&self) -> String {
+        "uptime".to_string()
+    }
+
+    fn view(
+        &self,
+        config: &LocalModuleConfig,
+        popup_config: &PopupConfig,
+        anchor: &BarAnchor,
+        handlebars: &Handlebars,
+    ) -> Element<Message> {
+        let format = handlebars
+            .render("uptime", &self.state.context())
+            .map_err(|e| eprintln!("Failed to render uptime: {e}"))
+            .unwrap_or_default();
+        button(
+            list![
+                anchor,
+                container(
+                    text(self.cfg_override.icon(config, &self.icon))
+                        .fill(anchor)
+                        .size(self.cfg_override.icon_size.unwrap_or(config.icon_size))
+                        .color(self.cfg_override.icon_color.unwrap_or(config.icon_color))
+                        .font(config.icon_font)
+                )
+                .padding(self.cfg_override.icon_margin.unwrap_or(config.icon_margin)),
+                container(
+                    bar_text(
+                        format,
+                        anchor,
+                        self.cfg_override.vertical_text(config, anchor)
+                    )
+                    .size(self.cfg_override.font_size.unwrap_or(config.font_size))
+                    .font(self.cfg_override.text_font.unwrap_or(config.text_font))
+                    .color(self.cfg_override.text_color.unwrap_or(config.text_color))
+                )
+                .padding(self.cfg_override.text_margin.unwrap_or(config.text_margin)),
+            ]
+            .spacing(self.cfg_override.spacing.unwrap_or(config.spacing)),
+        )
+        .on_event_with(Message::popup::<Self>(
+            self.popup_cfg_override.width.unwrap_or(popup_config.width),
+            self.popup_cfg_override
+                .height
+                .unwrap_or(popup_config.height),
+            anchor,
+        ))
+        .style(|_, _| Style::default())
+        .into()
+    }
+
+    fn popup_cfg_override(&self) -> Option<&PopupConfigOverride> {
+        Some(&self.popup_cfg_override)
+    }
+
+    fn popup_view<'a>(
+        &'a self,
+        config: &'a PopupConfig,
+        template: &Handlebars,
+    ) -> Element<'a, Message> {
+        let mut ctx = self.state.context();
+        ctx["boot_time"] = json!(self
+            .state
+            .boot_time
+            .map(|time| time.format(&self.boot_time_format).to_string())
+            .unwrap_or_default());
+        ctx["kernel"] = json!(self.kernel.clone().unwrap_or_default());
+        let format = template
+            .render("uptime_popup", &ctx)
+            .map_err(|e| eprintln!("Failed to render uptime popup: {e}"))
+            .unwrap_or_default();
+        container(scrollable(
+            container(
+                text(format)
+                    .size(
+                        self.popup_cfg_override
+                            .font_size
+                            .unwrap_or(config.font_size),
+                    )
+                    .color(
+                        self.popup_cfg_override
+                            .text_color
+                            .unwrap_or(config.text_color),
+                    ),
+            )
+            .padding(
+                self.popup_cfg_override
+                    .text_margin
+                    .unwrap_or(config.text_margin),
+            ),
+        ))
+        .into()
+    }
+
+    impl_wrapper!();
+
+    fn read_config(
+        &mut self,
+        config: &HashMap<String, Option<String>>,
+        popup_config: &HashMap<String, Option<String>>,
+        templates: &mut Handlebars,
+        scale: f32,
+    ) {
+        let default = Self::default();
+        self.cfg_override = (config, scale).into();
+        self.popup_cfg_override.update(popup_config, scale);
+        self.icon = config
+            .get("icon")
+            .and_then(|v| v.clone())
+            .unwrap_or(default.icon);
+        self.interval = config
+            .get("interval")
+            .and_then(|v| v.as_ref().and_then(|v| v.parse().ok()))
+            .map(Duration::from_secs)
+            .unwrap_or(default.interval);
+        self.boot_time_format = popup_config
+            .get("boot_time_format")
+            .and_then(|v| v.clone())
+            .unwrap_or(default.boot_time_format);
+        self.kernel = kernel_version(&Host);
+        templates
+            .register_template_string(
+                "uptime",
+                config.get("format").unescape().unwrap_or(
+                    "{{#if days}}{{plural days \"day\"}} {{/if}}{{hours}}h {{minutes}}m"
+                        .to_string(),
+                ),
+            )
+            .unwrap_or_else(|e| eprintln!("Failed to parse uptime format: {e}"));
+        templates
+            .register_template_string(
+                "uptime_popup",
+                popup_config.get("format").unescape().unwrap_or(
+                    "Up since {{boot_time}}\nKernel: {{kernel}}\nLoad: {{load1}}, {{load5}}, {{load15}}"
+                        .to_string(),
+                ),
+            )
+            .unwrap_or_else(|e| eprintln!("Failed to parse uptime popup format: {e}"));
+    }
+
+    fn known_keys(&self) -> Vec<ConfigKey> {
+        vec![
+            ConfigKey::new("icon", Kind::Text, Some("󰔟")),
+            // In seconds
+            ConfigKey::new("interval", Kind::Integer, Some("60")),
+            ConfigKey::new(
+                "format",
+                Kind::Text,
+                Some(r#"{{#if days}}{{plural days "day"}} {{/if}}{{hours}}h {{minutes}}m"#),
+            ),
+        ]
+    }
+
+    fn known_popup_keys(&self) -> Vec<ConfigKey> {
+        vec![
+            ConfigKey::new("boot_time_format", Kind::Text, Some("%Y-%m-%d %H:%M")),
+            ConfigKey::new(
+                "format",
+                Kind::Text,
+                Some(
+                    r#"Up since {{boot_time}}\nKernel: {{kernel}}\nLoad: {{load1}}, {{load5}}, {{load15}}"#,
+                ),
+            ),
+        ]
+    }
+
+    impl_on_click!();
+
+    fn has_popup(&self) -> bool {
+        true
+    }
+
+    fn collect_data(&mut self) -> Result<(), String> {
+        self.state =
+            UptimeState::read(&Host).map_err(|e| format!("Failed to read the uptime: {e}"))?;
+        Ok(())
+    }
+
+    fn collect_data_from(&mut self, source: &dyn Source) -> Result<(), String> {
+        self.state =
+            UptimeState::read(&source).map_err(|e| format!("Failed to read the uptime: {e}"))?;
+        self.kernel = kernel_version(&source);
+        Ok(())
+    }
+
+    fn text_content(&self, template: &Handlebars) -> Option<Result<String, String>> {
+        Some(
+            template
+                .render("uptime", &self.state.context())
+                .map_err(|e| e.to_string()),
+        )
+    }
+
+    fn subscription(&self) -> Option<Subscription<Message>> {
+        let interval = self.interval;
+        Some(Subscription::run_with_id(
+            ("uptime", interval),
+            stream::channel(1, move |mut sender| async move {
+                loop {
+                    match UptimeState::read(&Host) {
+                        Ok(state) => {
+                            if sender
+                                .send(Message::update(move |reg| {
+                                    reg.get_module_mut::<UptimeMod>().state = state
+                                }))
+                                .await
+                                .is_err()
+                            {
+                                return;
+                            }
+                        }
+                        Err(e) => eprintln!("Failed to read the uptime: {e}"),
+                    }
+                    sleep(interval).await;
+                }
+            }),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{helpers::source::Fixtures, modules::testing::Harness};
+
+    use super::*;
+
+    fn proc(uptime: &str) -> Fixtures {
+        Fixtures::default()
+            .file("/proc/uptime", uptime)
+            .file("/proc/loadavg", "0.52 0.58 0.59 1/123 4567\n")
+            .file(
+                "/proc/stat",
+                "cpu  1 2 3 4 5 6 7 8 9 10\nbtime 1700000000\n",
+            )
+            .file(
+                "/proc/version",
+                "Linux version 6.12.8-arch1-1 (linux@archlinux) #1 SMP PREEMPT_DYNAMIC\n",
+            )
+    }
+
+    fn text(uptime: &str, options: &[(&str, &str)]) -> String {
+        let state = UptimeState::read(&proc(uptime)).unwrap();
+        let mut harness = Harness::new::<UptimeMod>(options);
+        harness.update(|m: &mut UptimeMod| m.state = state);
+        harness.text::<UptimeMod>()
+    }
+
+    #[test]
+    fn renders_the_default_format() {
+        assert_eq!(text("300.42 1000.00\n", &[]), "0h 5m");
+        assert_eq!(text("93784.52 1000.00\n", &[]), "1 day 2h 3m");
+        assert_eq!(text("172800.00 1000.00\n", &[]), "2 days 0h 0m");
+    }
+
+    #[test]
+    fn renders_the_load() {
+        let options = [(
+            "format",
+            "{{total_hours}}h, load {{load1}} {{round load15 1}}",
+        )];
+        assert_eq!(text("93784.52 1000.00\n", &options), "26h, load 0.52 0.6");
+    }
+
+    #[test]
+    fn reads_the_boot_time_and_kernel() {
+        let source = proc("1.00 1.00\n");
+        assert_eq!(
+            boot_time(&source).map(|time| time.timestamp()),
+            Some(1700000000)
+        );
+        assert_eq!(kernel_version(&source).as_deref(), Some("6.12.8-arch1-1"));
+        assert_eq!(boot_time(&Fixtures::default()), None);
+    }
+
+    #[test]
+    fn rejects_malformed_files() {
+        assert!(UptimeState::read(&proc("")).is_err());
+        let source = proc("1.00 1.00\n").file("/proc/loadavg", "0.52 0.58\n");
+        assert!(UptimeState::read(&source).is_err());
+    }
+}
@@ -1,32 +1,326 @@
-use std::{collections::HashMap, process::Stdio};
+use std::{
+    collections::{BTreeMap, HashMap},
+    process::Stdio,
+    sync::LazyLock,
+    time::{Duration, Instant},
+};
 
 use bar_rs_derive::Builder;
 use handlebars::Handlebars;
-use iced::widget::{button, container};
-use iced::{futures::SinkExt, stream, widget::text, Element, Subscription};
+use iced::widget::{button::Style, column, container, row, scrollable, text, Column, Container};
+use iced::{
+    futures::{channel::mpsc::Sender, SinkExt},
+    mouse,
+    mouse::ScrollDelta,
+    stream, Alignment, Background, Color, Element, Event,
+    Length::Fill,
+    Subscription,
+};
+use serde_json::Value;
 use tokio::{
     io::{AsyncBufReadExt, BufReader},
     process::Command,
+    select,
+    sync::mpsc::{self, UnboundedReceiver, UnboundedSender},
 };
 
-use crate::config::popup_config::PopupConfig;
+use crate::bar_text::bar_text;
+use crate::button::button;
+use crate::config::parse::StringExt;
+use crate::config::popup_config::{PopupConfig, PopupConfigOverride};
 use crate::{
     config::{
         anchor::BarAnchor,
         module_config::{LocalModuleConfig, ModuleConfigOverride},
+        schema::{ConfigKey, Kind},
     },
     fill::FillExt,
-    Message, NERD_FONT,
+    helpers::{
+        backoff::Backoff,
+        scroll::ScrollDirection,
+        status::{send_status, ModuleStatus},
+        UnEscapeString,
+    },
+    osd::{OsdContent, OsdPosition},
+    popup::slider,
+    Message,
 };
-use crate::{impl_on_click, impl_wrapper};
+use crate::{impl_on_click, impl_status, impl_wrapper};
+
+use super::{click_message, Action, CommandAction, Module, OnClickAction};
+
+const SINK: &str = "@DEFAULT_AUDIO_SINK@";
+const SOURCE: &str = "@DEFAULT_AUDIO_SOURCE@";
+/// How long volume changes made with the module don't show the OSD, `pactl` reports a change
+/// more than once
+const OWN_CHANGE_WINDOW: Duration = Duration::from_secs(1);
+
+static DEFAULT_ACTION: LazyLock<OnClickAction> = LazyLock::new(|| OnClickAction {
+    left: Some(Box::new(CommandAction(
+        "wpctl set-mute @DEFAULT_AUDIO_SINK@ toggle".to_string(),
+    ))),
+    right: Some(Box::new(CommandAction("pavucontrol".to_string()))),
+    ..Default::default()
+});
+
+/// An audio output, as reported by `pactl`
+#[derive(Debug, Clone)]
+struct Sink {
+    /// The PipeWire id used by `wpctl`, `None` without PipeWire
+    id: Option<String>,
+    name: String,
+    description: String,
+    volume: u16,
+    muted: bool,
+}
+
+impl Sink {
+    fn parse(sink: &Value) -> Option<Self> {
+        let name = sink["name"].as_str()?.to_string();
+        Some(Self {
+            id: sink["properties"]["object.id"]
+                .as_str()
+                .map(|id| id.to_string()),
+            description: sink["description"].as_str().unwrap_or(&name).to_string(),
+            name,
+            // The volume of the first channel, like pavucontrol shows with locked channels
+            volume: sink["volume"]
+                .as_object()
+                .and_then(|channels| channels.values().next())
+                .and_then(|channel| channel["value_percent"].as_str())
+                .and_then(|percent| percent.trim_end_matches('%').parse().ok())
+                .unwrap_or_default(),
+            muted: sink["mute"].as_bool().unwrap_or_default(),
+        })
+    }
+
+    /// Makes this the default sink
+    fn action(&self) -> CommandAction {
+        CommandAction(match &self.id {
+            Some(id) => format!("wpctl set-default {id}"),
+            None => format!("pactl set-default-sink '{}'", self.name),
+        })
+    }
+}
+
+/// The settings of the OSD, which the subscription shows on volume changes
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct OsdSettings {
+    timeout: Duration,
+    position: OsdPosition,
+    max_volume: u16,
+    icons: BTreeMap<&'static str, String>,
+}
+
+impl OsdSettings {
+    fn content(&self, level: u16, muted: bool) -> OsdContent {
+        OsdContent {
+            icon: self
+                .icons
+                .get(level_icon(level, muted))
+                .cloned()
+                .unwrap_or_default(),
+            level: level as f32 / self.max_volume as f32,
+            text: match muted {
+                true => "Muted".to_string(),
+                false => format!("{level}%"),
+            },
+        }
+    }
+}
+
+/// Changes the volume with `wpctl set-volume <args>` from the module itself, by scrolling or
+/// with the slider of its popup. The subscription is told first, so that the change doesn't
+/// show the OSD in addition.
+#[derive(Debug)]
+struct SetVolume(String);
+
+impl Action for SetVolume {
+    fn as_message(&self) -> Message {
+        Message::Batch(vec![
+            Message::action(|reg| {
+                if let Some(changes) = &reg.get_module::<VolumeMod>().own_changes {
+                    // The subscription is gone if the bar is closing
+                    let _ = changes.send(());
+                }
+            }),
+            Message::command_sh(format!("wpctl set-volume {}", self.0)),
+        ])
+    }
+}
 
-use super::Module;
+/// The name of the icon for the volume: `muted`, `high`, `medium` or `low`
+fn level_icon(level: u16, muted: bool) -> &'static str {
+    match (muted, level) {
+        (true, _) => "muted",
+        (false, n) if n >= 50 => "high",
+        (false, n) if n >= 25 => "medium",
+        _ => "low",
+    }
+}
 
-#[derive(Default, Debug, Builder)]
+#[derive(Debug, Builder)]
 pub struct VolumeMod {
     level: u16,
-    icon: &'static str,
+    muted: bool,
+    /// The icons by state: `muted`, `high`, `medium` and `low`, and `mic` and `mic_muted` for
+    /// the microphone
+    icons: HashMap<&'static str, String>,
+    muted_color: Color,
+    muted_strikethrough: bool,
+    /// How much scrolling changes the volume, in percent
+    scroll_step: u16,
+    /// The highest volume scrolling and the slider of the popup go to, in percent
+    max_volume: u16,
+    /// The color of the icon and the text while the volume is above 100%
+    boost_color: Color,
+    sinks: Vec<Sink>,
+    /// The name of the default sink
+    default_sink: Option<String>,
+    /// Whether the volume of the microphone is shown next to the output volume
+    show_mic: bool,
+    /// The volume of the default source and whether it's muted, `None` if there is none
+    mic: Option<(u16, bool)>,
+    active_color: Option<Color>,
+    active_background: Option<Background>,
+    /// Whether volume changes show an OSD, unless they were made with the module
+    osd: bool,
+    osd_timeout: Duration,
+    osd_position: OsdPosition,
+    /// Tells the subscription about volume changes made with the module, see [SetVolume]
+    own_changes: Option<UnboundedSender<()>>,
     cfg_override: ModuleConfigOverride,
+    popup_cfg_override: PopupConfigOverride,
+    status: ModuleStatus,
+}
+
+impl Default for VolumeMod {
+    fn default() -> Self {
+        Self {
+            level: 0,
+            muted: false,
+            icons: HashMap::from([
+                ("muted", "󰖁".to_string()),
+                ("high", "󰕾".to_string()),
+                ("medium", "󰖀".to_string()),
+                ("low", "󰕿".to_string()),
+                ("mic", "󰍬".to_string()),
+                ("mic_muted", "󰍭".to_string()),
+            ]),
+            muted_color: Color::from_rgb8(243, 139, 168),
+            muted_strikethrough: false,
+            scroll_step: 5,
+            max_volume: 100,
+            boost_color: Color::from_rgb8(250, 179, 135),
+            sinks: vec![],
+            default_sink: None,
+            show_mic: false,
+            mic: None,
+            active_color: None,
+            active_background: Some(Background::Color(Color::from_rgba(1., 1., 1., 0.2))),
+            osd: false,
+            osd_timeout: Duration::from_millis(1500),
+            osd_position: OsdPosition::Bottom,
+            own_changes: None,
+            cfg_override: Default::default(),
+            popup_cfg_override: PopupConfigOverride {
+                width: Some(350),
+                height: Some(200),
+                ..Default::default()
+            },
+            status: ModuleStatus::Ok,
+        }
+    }
+}
+
+impl VolumeMod {
+    fn icon(&self) -> &str {
+        self.icons
+            .get(level_icon(self.level, self.muted))
+            .map(|icon| icon.as_str())
+            .unwrap_or_default()
+    }
+
+    fn mic_icon(&self, muted: bool) -> &str {
+        self.icons
+            .get(match muted {
+                true => "mic_muted",
+                false => "mic",
+            })
+            .map(|icon| icon.as_str())
+            .unwrap_or_default()
+    }
+
+    fn render(&self, template: &str, handlebars: &Handlebars) -> String {
+        handlebars
+            .render(template, &self.variables())
+            .map_err(|e| eprintln!("Failed to render the volume: {e}"))
+            .unwrap_or_default()
+    }
+
+    /// The microphone segment, with its own actions: clicking toggles mute and scrolling changes
+    /// the volume of the default source
+    fn mic_view<'a>(
+        &'a self,
+        config: &LocalModuleConfig,
+        anchor: &BarAnchor,
+        handlebars: &Handlebars,
+    ) -> Option<Element<'a, Message>> {
+        let (_, muted) = self.mic.filter(|_| self.show_mic)?;
+        let (icon_color, text_color) = match muted {
+            true => (self.muted_color, self.muted_color),
+            false => (
+                self.cfg_override.icon_color.unwrap_or(config.icon_color),
+                self.cfg_override.text_color.unwrap_or(config.text_color),
+            ),
+        };
+        Some(
+            button(
+                list![
+                    anchor,
+                    container(
+                        text(self.cfg_override.icon(config, self.mic_icon(muted)))
+                            .fill(anchor)
+                            .size(self.cfg_override.icon_size.unwrap_or(config.icon_size))
+                            .color(icon_color)
+                            .font(config.icon_font)
+                    )
+                    .padding(self.cfg_override.icon_margin.unwrap_or(config.icon_margin)),
+                    container(
+                        bar_text(
+                            self.render("volume_mic", handlebars),
+                            anchor,
+                            self.cfg_override.vertical_text(config, anchor)
+                        )
+                        .size(self.cfg_override.font_size.unwrap_or(config.font_size))
+                        .font(self.cfg_override.text_font.unwrap_or(config.text_font))
+                        .color(text_color)
+                        .strikethrough(muted && self.muted_strikethrough)
+                    )
+                    .padding(self.cfg_override.text_margin.unwrap_or(config.text_margin)),
+                ]
+                .spacing(self.cfg_override.spacing.unwrap_or(config.spacing)),
+            )
+            .on_event_try(|event, _, _, _, _| match event {
+                Event::Mouse(mouse::Event::ButtonReleased(mouse::Button::Left)) => Some(
+                    Message::command_sh(format!("wpctl set-mute {SOURCE} toggle")),
+                ),
+                _ => None,
+            })
+            .on_scroll(|delta| {
+                let change = match ScrollDirection::from_delta(delta)? {
+                    ScrollDirection::Up => "+",
+                    ScrollDirection::Down => "-",
+                };
+                Some(Message::command_sh(format!(
+                    "wpctl set-volume --limit 1 {SOURCE} {}%{change}",
+                    self.scroll_step
+                )))
+            })
+            .style(|_, _| Style::default())
+            .into(),
+        )
+    }
 }
 
 impl Module for VolumeMod {
@@ -34,36 +328,169 @@ impl Module for VolumeMod {
         "volume".to_string()
     }
 
+    impl_status!();
+
     fn view(
         &self,
         config: &LocalModuleConfig,
-        _popup_config: &PopupConfig,
+        popup_config: &PopupConfig,
         anchor: &BarAnchor,
-        _handlebars: &Handlebars,
+        handlebars: &Handlebars,
     ) -> Element<Message> {
-        list![
+        let popup = Message::popup::<Self>(
+            self.popup_cfg_override.width.unwrap_or(popup_config.width),
+            self.popup_cfg_override
+                .height
+                .unwrap_or(popup_config.height),
             anchor,
+        );
+        // Left clicks toggle mute and middle clicks open the sink switcher, unless
+        // `on_middle_click` is set
+        let left = click_message(self, mouse::Button::Left, config);
+        let middle = click_message(self, mouse::Button::Middle, config);
+        let right = click_message(self, mouse::Button::Right, config);
+        let (icon_color, text_color) = match (self.muted, self.level > 100) {
+            (true, _) => (self.muted_color, self.muted_color),
+            // Boosted audio may clip
+            (false, true) => (self.boost_color, self.boost_color),
+            (false, false) => (
+                self.cfg_override.icon_color.unwrap_or(config.icon_color),
+                self.cfg_override.text_color.unwrap_or(config.text_color),
+            ),
+        };
+        let output: Element<Message> = button(
+            list![
+                anchor,
+                container(
+                    text(self.cfg_override.icon(config, self.icon()))
+                        .fill(anchor)
+                        .size(self.cfg_override.icon_size.unwrap_or(config.icon_size))
+                        .color(icon_color)
+                        .font(config.icon_font)
+                )
+                .padding(self.cfg_override.icon_margin.unwrap_or(config.icon_margin)),
+                container(
+                    bar_text(
+                        self.render("volume", handlebars),
+                        anchor,
+                        self.cfg_override.vertical_text(config, anchor)
+                    )
+                    .size(self.cfg_override.font_size.unwrap_or(config.font_size))
+                    .font(self.cfg_override.text_font.unwrap_or(config.text_font))
+                    .color(text_color)
+                    .strikethrough(self.muted && self.muted_strikethrough)
+                )
+                .padding(self.cfg_override.text_margin.unwrap_or(config.text_margin)),
+            ]
+            .spacing(self.cfg_override.spacing.unwrap_or(config.spacing)),
+        )
+        .on_event_try(
+            move |event, layout, cursor, clipboard, viewport| match event {
+                Event::Mouse(mouse::Event::ButtonReleased(mouse::Button::Left)) => left.clone(),
+                Event::Mouse(mouse::Event::ButtonReleased(mouse::Button::Right)) => right.clone(),
+                _ => Some(
+                    middle
+                        .clone()
+                        .unwrap_or_else(|| popup(event, layout, cursor, clipboard, viewport)),
+                ),
+            },
+        )
+        .style(|_, _| Style::default())
+        .into();
+        match self.mic_view(config, anchor, handlebars) {
+            Some(mic) => list![anchor, output, mic]
+                .spacing(self.cfg_override.spacing.unwrap_or(config.spacing))
+                .into(),
+            None => output,
+        }
+    }
+
+    fn popup_cfg_override(&self) -> Option<&PopupConfigOverride> {
+        Some(&self.popup_cfg_override)
+    }
+
+    fn popup_view<'a>(
+        &'a self,
+        config: &'a PopupConfig,
+        _template: &Handlebars,
+    ) -> Element<'a, Message> {
+        let text_color = self
+            .popup_cfg_override
+            .text_color
+            .unwrap_or(config.text_color);
+        let fmt_text = |content: String, color: Color| -> Container<'a, Message> {
+            container(
+                text(content)
+                    .size(
+                        self.popup_cfg_override
+                            .font_size
+                            .unwrap_or(config.font_size),
+                    )
+                    .color(color),
+            )
+            .padding(
+                self.popup_cfg_override
+                    .text_margin
+                    .unwrap_or(config.text_margin),
+            )
+        };
+
+        let sinks = self.sinks.iter().map(|sink| -> Element<'a, Message> {
+            let is_default = self.default_sink.as_ref() == Some(&sink.name);
+            let color = match is_default {
+                true => self.active_color.unwrap_or(text_color),
+                false => text_color,
+            };
+            let background = is_default.then_some(self.active_background).flatten();
             button(
-                text!("{}", self.icon)
-                    .fill(anchor)
-                    .size(self.cfg_override.icon_size.unwrap_or(config.icon_size))
-                    .color(self.cfg_override.icon_color.unwrap_or(config.icon_color))
-                    .font(NERD_FONT)
+                row![
+                    fmt_text(sink.description.clone(), color).width(Fill),
+                    fmt_text(
+                        match sink.muted {
+                            true => "muted".to_string(),
+                            false => format!("{}%", sink.volume),
+                        },
+                        color
+                    ),
+                ]
+                .align_y(Alignment::Center),
             )
-            .style(|_, _| button::Style::default())
-            .on_press(Message::command_sh(
-                "wpctl set-mute @DEFAULT_AUDIO_SINK@ toggle"
-            ))
-            .padding(self.cfg_override.icon_margin.unwrap_or(config.icon_margin)),
-            container(
-                text!["{}%", self.level,]
-                    .fill(anchor)
-                    .size(self.cfg_override.font_size.unwrap_or(config.font_size))
-                    .color(self.cfg_override.text_color.unwrap_or(config.text_color))
+            .on_event(sink.action().as_message())
+            .style(move |_, _| Style {
+                background,
+                ..Default::default()
+            })
+            .width(Fill)
+            .into()
+        });
+
+        let volume = container(
+            slider(
+                "volume",
+                0. ..=self.max_volume as f32,
+                self.level as f32,
+                |level| SetVolume(format!("{SINK} {level:.0}%")),
+            )
+            .width(Fill),
+        )
+        .padding(
+            self.popup_cfg_override
+                .text_margin
+                .unwrap_or(config.text_margin),
+        );
+
+        column![
+            volume,
+            scrollable(
+                Column::with_children(sinks)
+                    .push_maybe(
+                        self.sinks
+                            .is_empty()
+                            .then(|| fmt_text("No audio outputs found".to_string(), text_color)),
+                    )
+                    .spacing(self.popup_cfg_override.spacing.unwrap_or(config.spacing)),
             )
-            .padding(self.cfg_override.text_margin.unwrap_or(config.text_margin)),
         ]
-        .spacing(self.cfg_override.spacing.unwrap_or(config.spacing))
         .into()
     }
 
@@ -72,92 +499,360 @@ impl Module for VolumeMod {
     fn read_config(
         &mut self,
         config: &HashMap<String, Option<String>>,
-        _popup_config: &HashMap<String, Option<String>>,
-        _templates: &mut Handlebars,
+        popup_config: &HashMap<String, Option<String>>,
+        templates: &mut Handlebars,
+        scale: f32,
     ) {
-        self.cfg_override = config.into();
+        let default = Self::default();
+        self.cfg_override = (config, scale).into();
+        self.popup_cfg_override.update(popup_config, scale);
+        self.active_color = popup_config
+            .get("active_color")
+            .and_then(|v| v.into_color())
+            .or(default.active_color);
+        self.active_background = popup_config
+            .get("active_background")
+            .map(|v| v.into_background())
+            .unwrap_or(default.active_background);
+        self.icons = default.icons;
+        for (state, icon) in self.icons.iter_mut() {
+            if let Some(custom) = config.get(&format!("icon.{state}")).unescape() {
+                *icon = custom;
+            }
+        }
+        self.muted_color = config
+            .get("muted_color")
+            .and_then(|v| v.into_color())
+            .unwrap_or(default.muted_color);
+        self.muted_strikethrough = config
+            .get("muted_strikethrough")
+            .and_then(|v| v.into_bool())
+            .unwrap_or(default.muted_strikethrough);
+        self.scroll_step = config
+            .get("scroll_step")
+            .and_then(|v| v.as_ref().and_then(|v| v.parse().ok()))
+            .unwrap_or(default.scroll_step);
+        self.max_volume = config
+            .get("max_volume")
+            .and_then(|v| v.as_ref().and_then(|v| v.parse().ok()))
+            .filter(|max| *max > 0)
+            .unwrap_or(default.max_volume);
+        self.boost_color = config
+            .get("boost_color")
+            .and_then(|v| v.into_color())
+            .unwrap_or(default.boost_color);
+        self.show_mic = config
+            .get("show_mic")
+            .and_then(|v| v.into_bool())
+            .unwrap_or(default.show_mic);
+        self.osd = config
+            .get("osd")
+            .and_then(|v| v.into_bool())
+            .unwrap_or(default.osd);
+        self.osd_timeout = config
+            .get("osd_timeout")
+            .and_then(|v| v.into_duration())
+            .unwrap_or(default.osd_timeout);
+        self.osd_position = config
+            .get("osd_position")
+            .and_then(|v| v.as_deref())
+            .and_then(OsdPosition::named)
+            .unwrap_or(default.osd_position);
+        templates
+            .register_template_string(
+                "volume",
+                config
+                    .get("format")
+                    .unescape()
+                    .unwrap_or("{{level}}%".to_string()),
+            )
+            .unwrap_or_else(|e| eprintln!("Failed to parse volume format: {e}"));
+        templates
+            .register_template_string(
+                "volume_mic",
+                config
+                    .get("format_mic")
+                    .unescape()
+                    .unwrap_or("{{mic_volume}}%".to_string()),
+            )
+            .unwrap_or_else(|e| eprintln!("Failed to parse volume mic format: {e}"));
+    }
+
+    fn known_keys(&self) -> Vec<ConfigKey> {
+        vec![
+            ConfigKey::new("icon.*", Kind::Text, None),
+            ConfigKey::new("muted_color", Kind::Color, Some("rgb(243, 139, 168)")),
+            ConfigKey::new("muted_strikethrough", Kind::Bool, Some("false")),
+            ConfigKey::new("scroll_step", Kind::Integer, Some("5")),
+            ConfigKey::new("max_volume", Kind::Integer, Some("100")),
+            ConfigKey::new("boost_color", Kind::Color, Some("rgb(250, 179, 135)")),
+            ConfigKey::new("show_mic", Kind::Bool, Some("false")),
+            ConfigKey::new("osd", Kind::Bool, Some("false")),
+            ConfigKey::new("osd_timeout", Kind::Duration, Some("1.5s")),
+            ConfigKey::new(
+                "osd_position",
+                Kind::Choice(&["top", "center", "bottom"]),
+                Some("bottom"),
+            ),
+            ConfigKey::new("format", Kind::Text, Some("{{level}}%")),
+            ConfigKey::new("format_mic", Kind::Text, Some("{{mic_volume}}%")),
+        ]
+    }
+
+    fn known_popup_keys(&self) -> Vec<ConfigKey> {
+        vec![
+            ConfigKey::new("active_color", Kind::Color, None),
+            ConfigKey::new(
+                "active_background",
+                Kind::Color,
+                Some("rgba(255, 255, 255, 0.2)"),
+            ),
+        ]
     }
 
     impl_on_click!();
 
-    fn subscription(&self) -> Option<iced::Subscription<Message>> {
-        Some(Subscription::run(|| {
-            stream::channel(1, |mut sender| async move {
-                let volume = || {
-                    Message::update(move |reg| {
-                        let vmod = reg.get_module_mut::<VolumeMod>();
-                        let volume = get_volume();
-                        vmod.level = volume.0;
-                        vmod.icon = volume.1;
-                    })
-                };
+    fn default_action(&self) -> Option<&OnClickAction> {
+        Some(&DEFAULT_ACTION)
+    }
 
-                sender.send(volume()).await.unwrap_or_else(|err| {
-                    eprintln!("Trying to send volume failed with err: {err}");
-                });
-
-                let mut child = Command::new("sh")
-                    .arg("-c")
-                    .arg("pactl subscribe")
-                    .stdout(Stdio::piped())
-                    .spawn()
-                    .expect("Failed to spawn pactl to monitor volume changes");
-
-                let stdout = child
-                    .stdout
-                    .take()
-                    .expect("child did not have a handle to stdout");
-
-                let mut reader = BufReader::new(stdout).lines();
-
-                while let Some(line) = reader.next_line().await.unwrap() {
-                    if line.contains("'change' on sink") {
-                        sender.send(volume()).await.unwrap_or_else(|err| {
-                            eprintln!("Trying to send volume failed with err: {err}");
-                        });
+    fn on_scroll(&self, delta: ScrollDelta) -> Option<Message> {
+        let change = match ScrollDirection::from_delta(delta)? {
+            ScrollDirection::Up => "+",
+            ScrollDirection::Down => "-",
+        };
+        // `--limit` keeps scrolling up from going past `max_volume`
+        Some(
+            SetVolume(format!(
+                "--limit {} {SINK} {}%{change}",
+                self.max_volume as f32 / 100.,
+                self.scroll_step
+            ))
+            .as_message(),
+        )
+    }
+
+    fn collect_data(&mut self) -> Result<(), String> {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .map_err(|e| e.to_string())?;
+        (self.level, self.muted) = runtime.block_on(get_volume(SINK))?;
+        (self.sinks, self.default_sink) = runtime.block_on(get_sinks())?;
+        self.mic = runtime.block_on(get_volume(SOURCE)).ok();
+        Ok(())
+    }
+
+    fn text_content(&self, template: &Handlebars) -> Option<Result<String, String>> {
+        Some(
+            template
+                .render("volume", &self.variables())
+                .map_err(|e| e.to_string()),
+        )
+    }
+
+    fn variables(&self) -> BTreeMap<String, Value> {
+        // Without a microphone, `mic_volume` and `mic_muted` are missing, so `{{#if}}` can hide
+        // them
+        BTreeMap::from_iter(
+            [
+                Some(("level".to_string(), self.level.into())),
+                Some(("muted".to_string(), self.muted.into())),
+                self.mic
+                    .map(|(volume, _)| ("mic_volume".to_string(), volume.into())),
+                self.mic
+                    .map(|(_, muted)| ("mic_muted".to_string(), muted.into())),
+            ]
+            .into_iter()
+            .flatten(),
+        )
+    }
+
+    fn urgent_updates(&self) -> bool {
+        true
+    }
+
+    fn subscription(&self) -> Option<iced::Subscription<Message>> {
+        let osd = self.osd.then(|| OsdSettings {
+            timeout: self.osd_timeout,
+            position: self.osd_position,
+            max_volume: self.max_volume,
+            icons: self
+                .icons
+                .iter()
+                .map(|(state, icon)| (*state, icon.clone()))
+                .collect(),
+        });
+        Some(Subscription::run_with_id(
+            ("volume", osd.clone()),
+            stream::channel(1, move |mut sender| async move {
+                let (changes_sx, mut changes_rx) = mpsc::unbounded_channel();
+                if sender
+                    .send(Message::update(move |reg| {
+                        reg.get_module_mut::<VolumeMod>().own_changes = Some(changes_sx)
+                    }))
+                    .await
+                    .is_err()
+                {
+                    return;
+                }
+                // PipeWire may be started after the bar, or restarted
+                let mut backoff = Backoff::default();
+                while let Err(e) =
+                    watch_volume(&mut sender, &mut changes_rx, osd.as_ref(), &mut backoff).await
+                {
+                    if !send_status::<VolumeMod>(&mut sender, ModuleStatus::Unavailable(e)).await {
+                        return;
                     }
+                    backoff.wait().await;
                 }
-            })
-        }))
+            }),
+        ))
     }
 }
 
-fn get_volume() -> (u16, &'static str) {
+/// Reads the volume whenever `pactl` reports a change, and shows the OSD if the volume changed
+/// without the module. Returns if the bar is closing.
+async fn watch_volume(
+    sender: &mut Sender<Message>,
+    own_changes: &mut UnboundedReceiver<()>,
+    osd: Option<&OsdSettings>,
+    backoff: &mut Backoff,
+) -> Result<(), String> {
+    let mut child = Command::new("pactl")
+        .arg("subscribe")
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|e| format!("Failed to spawn pactl to monitor volume changes: {e}"))?;
+    let stdout = child
+        .stdout
+        .take()
+        .ok_or("pactl did not have a handle to stdout")?;
+    let mut reader = BufReader::new(stdout).lines();
+
+    // Read here instead of in the update, which would block the bar while `wpctl` and `pactl` run
+    let volume = || async {
+        let data = match get_volume(SINK).await {
+            Ok(volume) => get_sinks().await.map(|sinks| (volume, sinks)),
+            Err(e) => Err(e),
+        };
+        // Fails if there is no microphone
+        let mic = get_volume(SOURCE).await.ok();
+        let volume = data.as_ref().ok().map(|(volume, _)| *volume);
+        let update = Message::update(move |reg| {
+            let vmod = reg.get_module_mut::<VolumeMod>();
+            vmod.mic = mic;
+            let status = data.map(|((level, muted), (sinks, default_sink))| {
+                vmod.level = level;
+                vmod.muted = muted;
+                vmod.sinks = sinks;
+                vmod.default_sink = default_sink;
+            });
+            vmod.set_status(match status {
+                Ok(()) => ModuleStatus::Ok,
+                Err(e) => ModuleStatus::Error(e),
+            });
+        });
+        (volume, update)
+    };
+
+    let (mut last, update) = volume().await;
+    if sender.send(update).await.is_err() {
+        return Ok(());
+    }
+    let mut own_change = None;
+    loop {
+        let line = select! {
+            line = reader.next_line() => line,
+            Some(()) = own_changes.recv() => {
+                own_change = Some(Instant::now());
+                continue;
+            }
+        };
+        let Some(line) = line.map_err(|e| format!("Failed to read from pactl: {e}"))? else {
+            break;
+        };
+        // Sinks or sources being added, removed or changed, or a new default sink or source
+        if line.contains("on sink #") || line.contains("on source #") || line.contains("on server")
+        {
+            backoff.reset();
+            let (current, update) = volume().await;
+            let own = own_change.is_some_and(|at| at.elapsed() < OWN_CHANGE_WINDOW);
+            let osd = match (osd, last, current) {
+                (Some(osd), Some(last), Some((level, muted))) if last != (level, muted) && !own => {
+                    Some(Message::osd(
+                        osd.content(level, muted),
+                        osd.timeout,
+                        osd.position,
+                    ))
+                }
+                _ => None,
+            };
+            last = current;
+            let msg = match osd {
+                Some(osd) => Message::Batch(vec![update, osd]),
+                None => update,
+            };
+            if sender.send(msg).await.is_err() {
+                return Ok(());
+            }
+        }
+    }
+    Err("pactl stopped, is PipeWire or PulseAudio running?".to_string())
+}
+
+/// The volume of the `target` ([SINK] or [SOURCE]) in percent and whether it's muted
+async fn get_volume(target: &str) -> Result<(u16, bool), String> {
     let volume = String::from_utf8(
-        std::process::Command::new("sh")
-            .arg("-c")
-            .arg("wpctl get-volume @DEFAULT_AUDIO_SINK@")
+        Command::new("wpctl")
+            .args(["get-volume", target])
             .output()
-            .expect("Couldn't get volume from wpctl")
+            .await
+            .map_err(|e| format!("Couldn't get volume from wpctl: {e}"))?
             .stdout,
     )
-    .expect("Couldn't convert output from wpctl to String");
+    .map_err(|e| format!("Couldn't convert output from wpctl to String: {e}"))?;
     let mut volume = volume
         .as_str()
         .strip_prefix("Volume: ")
-        .unwrap_or_else(|| {
-            eprintln!(
-                "Failed to get volume from wpctl, tried: `wpctl get-volume @DEFAULT_AUDIO_SINK@`"
-            );
-            "0"
-        })
+        .ok_or(format!(
+            "Failed to get volume from wpctl, tried: `wpctl get-volume {target}`"
+        ))?
         .trim();
     let mut muted = false;
     if let Some(x) = volume.strip_suffix(" [MUTED]") {
         volume = x;
         muted = true;
     }
-    let volume = volume.parse::<f32>().unwrap();
-    let volume = (volume * 100.) as u16;
-    (
-        volume,
-        match muted {
-            true => "󰖁",
-            false => match volume {
-                n if n >= 50 => "󰕾",
-                n if n >= 25 => "󰖀",
-                _ => "󰕿",
-            },
-        },
-    )
+    let volume = volume
+        .parse::<f32>()
+        .map_err(|e| format!("Couldn't parse volume `{volume}` from wpctl: {e}"))?;
+    Ok(((volume * 100.) as u16, muted))
+}
+
+/// The sinks and the name of the default sink
+async fn get_sinks() -> Result<(Vec<Sink>, Option<String>), String> {
+    let output = Command::new("pactl")
+        .args(["--format=json", "list", "sinks"])
+        .output()
+        .await
+        .map_err(|e| format!("Couldn't get sinks from pactl: {e}"))?;
+    let sinks = serde_json::from_slice::<Value>(&output.stdout)
+        .map_err(|e| format!("Couldn't parse sinks from pactl: {e}"))?;
+    let default_sink = Command::new("pactl")
+        .arg("get-default-sink")
+        .output()
+        .await
+        .ok()
+        .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string())
+        .filter(|name| !name.is_empty());
+    Ok((
+        sinks
+            .as_array()
+            .map(|sinks| sinks.iter().filter_map(Sink::parse).collect())
+            .unwrap_or_default(),
+        default_sink,
+    ))
 }
@@ -6,11 +6,14 @@ use iced::widget::{container, rich_text, span, text};
 use iced::Element;
 
 use crate::config::popup_config::PopupConfig;
+use crate::helpers::window::WindowTitle;
+use crate::list::list;
 use crate::tooltip::ElementExt;
 use crate::{
     config::{
         anchor::BarAnchor,
         module_config::{LocalModuleConfig, ModuleConfigOverride},
+        schema::ConfigKey,
     },
     fill::FillExt,
     listeners::wayfire::WayfireListener,
@@ -19,37 +22,14 @@ use crate::{
 };
 use crate::{impl_on_click, impl_wrapper};
 
-#[derive(Debug, Builder)]
+#[derive(Debug, Default, Builder)]
 pub struct WayfireWindowMod {
     pub title: Option<String>,
-    max_length: usize,
+    pub app_id: Option<String>,
+    window_title: WindowTitle,
     cfg_override: ModuleConfigOverride,
 }
 
-impl Default for WayfireWindowMod {
-    fn default() -> Self {
-        Self {
-            title: None,
-            max_length: 25,
-            cfg_override: Default::default(),
-        }
-    }
-}
-
-impl WayfireWindowMod {
-    pub fn get_title(&self) -> Option<String> {
-        self.title
-            .as_ref()
-            .map(|title| match title.len() > self.max_length {
-                true => format!(
-                    "{}...",
-                    title.chars().take(self.max_length - 3).collect::<String>()
-                ),
-                false => title.to_string(),
-            })
-    }
-}
-
 impl Module for WayfireWindowMod {
     fn name(&self) -> String {
         "wayfire.window".to_string()
@@ -66,17 +46,35 @@ impl Module for WayfireWindowMod {
         anchor: &BarAnchor,
         _handlebars: &Handlebars,
     ) -> Element<Message> {
-        container(
-            rich_text([span(self.get_title().unwrap_or_default())
+        let title = self.title.as_deref().unwrap_or_default();
+        let app_id = self.app_id.as_deref();
+        let icon: Option<Element<Message>> = self
+            .window_title
+            .icon(
+                app_id,
+                self.cfg_override.icon_size.unwrap_or(config.icon_size),
+            )
+            .map(|icon| {
+                container(icon)
+                    .padding(self.cfg_override.icon_margin.unwrap_or(config.icon_margin))
+                    .into()
+            });
+        let title_text: Element<Message> = container(
+            rich_text([span(self.window_title.trimmed(app_id, title))
                 .size(self.cfg_override.font_size.unwrap_or(config.font_size))
+                .font(self.cfg_override.text_font.unwrap_or(config.text_font))
                 .color(self.cfg_override.text_color.unwrap_or(config.text_color))])
             .fill(anchor),
         )
         .padding(self.cfg_override.text_margin.unwrap_or(config.text_margin))
-        .tooltip_maybe(
-            self.get_title()
-                .and_then(|t| (t.len() > self.max_length).then_some(text(t).size(12))),
-        )
+        .into();
+        list(anchor, icon.into_iter().chain([title_text]))
+            .spacing(self.cfg_override.spacing.unwrap_or(config.spacing))
+            .tooltip_maybe(
+                self.window_title
+                    .is_truncated(app_id, title)
+                    .then(|| text(self.window_title.full(app_id, title)).size(12)),
+            )
     }
 
     impl_wrapper!();
@@ -90,12 +88,14 @@ impl Module for WayfireWindowMod {
         config: &HashMap<String, Option<String>>,
         _popup_config: &HashMap<String, Option<String>>,
         _templates: &mut Handlebars,
+        scale: f32,
     ) {
-        self.cfg_override = config.into();
-        self.max_length = config
-            .get("max_length")
-            .and_then(|v| v.as_ref().and_then(|v| v.parse().ok()))
-            .unwrap_or(Self::default().max_length);
+        self.cfg_override = (config, scale).into();
+        self.window_title.read_config(config);
+    }
+
+    fn known_keys(&self) -> Vec<ConfigKey> {
+        WindowTitle::KEYS.to_vec()
     }
 
     impl_on_click!();
@@ -2,21 +2,27 @@ use std::{any::TypeId, collections::HashMap};
 
 use bar_rs_derive::Builder;
 use handlebars::Handlebars;
-use iced::widget::{container, rich_text, span};
-use iced::Element;
+use iced::mouse::ScrollDelta;
+use iced::widget::{button, container, rich_text, span};
 use iced::Padding;
+use iced::{Background, Color, Element};
+use tokio::sync::mpsc::UnboundedSender;
 
 use crate::config::parse::StringExt;
 use crate::config::popup_config::PopupConfig;
+use crate::config::scale::Scale;
+use crate::helpers::scroll::{ScrollDirection, WorkspaceScroll};
 use crate::{
     config::{
         anchor::BarAnchor,
         module_config::{LocalModuleConfig, ModuleConfigOverride},
+        schema::{ConfigKey, Kind},
     },
     fill::FillExt,
+    list::list,
     listeners::wayfire::WayfireListener,
-    modules::Module,
-    Message, NERD_FONT,
+    modules::{Action, Module},
+    Message,
 };
 use crate::{impl_on_click, impl_wrapper};
 
@@ -28,10 +34,69 @@ use crate::{impl_on_click, impl_wrapper};
 #[derive(Debug, Default, Builder)]
 pub struct WayfireWorkspaceMod {
     pub active: (i64, i64),
+    /// The number of columns and rows of the workspace grid
+    pub grid: (i64, i64),
+    /// Passes the workspace to switch to to the listener
+    pub switch: Option<UnboundedSender<(i64, i64)>>,
     icons: HashMap<(i64, i64), String>,
     cfg_override: ModuleConfigOverride,
     icon_padding: Padding,
     fallback_icon: Option<String>,
+    /// Show every workspace of the grid instead of only the focused one
+    show_all: bool,
+    active_color: Option<Color>,
+    active_background: Option<Background>,
+    scroll: WorkspaceScroll,
+}
+
+impl WayfireWorkspaceMod {
+    fn icon(&self, workspace: (i64, i64)) -> String {
+        self.icons
+            .get(&workspace)
+            .or(self.fallback_icon.as_ref())
+            .cloned()
+            .unwrap_or(format!("{}/{}", workspace.0, workspace.1))
+    }
+
+    fn switch(&self, workspace: (i64, i64)) {
+        if let Some(Err(_)) = self.switch.as_ref().map(|switch| switch.send(workspace)) {
+            eprintln!("Failed to switch workspaces: the wayfire listener is gone");
+        }
+    }
+
+    fn switch_by_scroll(&mut self, direction: ScrollDirection) {
+        // Go through the grid row by row
+        let (columns, rows) = self.grid;
+        let (x, y) = self.active;
+        let Some(target) = self
+            .scroll
+            .target(
+                direction,
+                (y * columns + x) as usize,
+                (columns * rows).max(0) as usize,
+            )
+            .map(|target| target as i64)
+        else {
+            return;
+        };
+        self.switch((target % columns, target / columns));
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum WayfireWorkspaceAction {
+    /// Switch to the workspace at this position of the grid
+    Switch((i64, i64)),
+}
+
+impl Action for WayfireWorkspaceAction {
+    fn as_message(&self) -> Message {
+        let action = self.clone();
+        Message::update(move |reg| {
+            reg.get_module_mut::<WayfireWorkspaceMod>()
+                .handle_action(&action)
+        })
+    }
 }
 
 impl Module for WayfireWorkspaceMod {
@@ -46,21 +111,39 @@ impl Module for WayfireWorkspaceMod {
         anchor: &BarAnchor,
         _handlebars: &Handlebars,
     ) -> Element<Message> {
-        container(
-            rich_text([span(
-                self.icons
-                    .get(&self.active)
-                    .or(self.fallback_icon.as_ref())
-                    .cloned()
-                    .unwrap_or(format!("{}/{}", self.active.0, self.active.1)),
+        let workspace = |workspace: (i64, i64)| -> Element<Message> {
+            let active = workspace == self.active;
+            let mut span = span(self.icon(workspace))
+                .padding(self.icon_padding)
+                .size(self.cfg_override.icon_size.unwrap_or(config.icon_size))
+                .color(self.cfg_override.icon_color.unwrap_or(config.icon_color))
+                .font(config.icon_font);
+            if active && self.show_all {
+                span = span
+                    .color_maybe(self.active_color)
+                    .background_maybe(self.active_background);
+            }
+            container(
+                button(rich_text([span]).fill(anchor))
+                    .padding(0)
+                    .style(|_, _| button::Style::default())
+                    .on_press(WayfireWorkspaceAction::Switch(workspace).as_message()),
             )
-            .padding(self.icon_padding)
-            .size(self.cfg_override.icon_size.unwrap_or(config.icon_size))
-            .color(self.cfg_override.icon_color.unwrap_or(config.icon_color))
-            .font(NERD_FONT)])
-            .fill(anchor),
+            .padding(self.cfg_override.icon_margin.unwrap_or(config.icon_margin))
+            .into()
+        };
+        if !self.show_all {
+            return workspace(self.active);
+        }
+        // Row by row, like scrolling goes through them
+        let (columns, rows) = self.grid;
+        list(
+            anchor,
+            (0..rows)
+                .flat_map(|y| (0..columns).map(move |x| (x, y)))
+                .map(workspace),
         )
-        .padding(self.cfg_override.icon_margin.unwrap_or(config.icon_margin))
+        .spacing(self.cfg_override.spacing.unwrap_or(config.spacing))
         .into()
     }
 
@@ -75,13 +158,24 @@ impl Module for WayfireWorkspaceMod {
         config: &HashMap<String, Option<String>>,
         _popup_config: &HashMap<String, Option<String>>,
         _templates: &mut Handlebars,
+        scale: f32,
     ) {
-        self.cfg_override = config.into();
+        self.cfg_override = (config, scale).into();
         self.icon_padding = config
             .get("icon_padding")
             .and_then(|v| v.into_insets().map(|i| i.into()))
-            .unwrap_or(Self::default().icon_padding);
+            .unwrap_or(Self::default().icon_padding)
+            .scaled(scale);
         self.fallback_icon = config.get("fallback_icon").and_then(|v| v.clone());
+        self.show_all = config
+            .get("show_all")
+            .and_then(|v| v.into_bool())
+            .unwrap_or_default();
+        self.active_color = config.get("active_color").and_then(|v| v.into_color());
+        self.active_background = config
+            .get("active_background")
+            .and_then(|v| v.into_background());
+        self.scroll.read_config(config);
         config.iter().for_each(|(key, val)| {
             if let Some(key) = key
                 .strip_prefix('(')
@@ -99,5 +193,33 @@ impl Module for WayfireWorkspaceMod {
         });
     }
 
+    fn known_keys(&self) -> Vec<ConfigKey> {
+        let mut keys = vec![
+            ConfigKey::new("icon_padding", Kind::Insets, Some("0")),
+            ConfigKey::new("fallback_icon", Kind::Text, None),
+            ConfigKey::new("show_all", Kind::Bool, Some("false")),
+            ConfigKey::new("active_color", Kind::Color, None),
+            ConfigKey::new("active_background", Kind::Color, None),
+            // The icon of a workspace by its position, like `(0, 1)`
+            ConfigKey::new("(*)", Kind::Text, None),
+        ];
+        keys.extend_from_slice(WorkspaceScroll::KEYS);
+        keys
+    }
+
     impl_on_click!();
+
+    fn handle_action(&mut self, action: &dyn Action) {
+        if let Some(WayfireWorkspaceAction::Switch(workspace)) = action.downcast_ref() {
+            self.switch(*workspace);
+        }
+    }
+
+    fn on_scroll(&self, delta: ScrollDelta) -> Option<Message> {
+        let direction = ScrollDirection::from_delta(delta)?;
+        Some(Message::update(move |reg| {
+            reg.get_module_mut::<WayfireWorkspaceMod>()
+                .switch_by_scroll(direction)
+        }))
+    }
 }
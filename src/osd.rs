@@ -0,0 +1,208 @@
+use std::time::Duration;
+
+use iced::{
+    platform_specific::shell::commands::layer_surface::{
+        destroy_layer_surface, get_layer_surface, Anchor, Layer,
+    },
+    runtime::platform_specific::wayland::layer_surface::{
+        IcedMargin, IcedOutput, SctkLayerSurfaceSettings,
+    },
+    widget::{column, container, progress_bar, row, text},
+    window::Id,
+    Alignment, Border, Element, Length, Task, Theme,
+};
+use tokio::time::sleep;
+
+use crate::{
+    config::{popup_config::PopupConfig, scale::Scale, Config},
+    toast::Toast,
+    Message,
+};
+
+/// The space between the edge of the screen and an OSD at the top or bottom
+const MARGIN: i32 = 80;
+/// How long each step of fading out takes
+const FADE_INTERVAL: Duration = Duration::from_millis(30);
+/// How much the opacity drops with every step of fading out
+const FADE_STEP: f32 = 0.1;
+
+/// Where the OSD is shown on the screen, independent of the bar
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum OsdPosition {
+    Top,
+    Center,
+    #[default]
+    Bottom,
+}
+
+impl OsdPosition {
+    pub fn named(position: &str) -> Option<Self> {
+        match position.trim() {
+            "top" => Some(Self::Top),
+            "center" => Some(Self::Center),
+            "bottom" => Some(Self::Bottom),
+            _ => None,
+        }
+    }
+
+    fn anchor(self) -> Anchor {
+        match self {
+            OsdPosition::Top => Anchor::TOP,
+            // Layer surfaces without an anchor are centered
+            OsdPosition::Center => Anchor::empty(),
+            OsdPosition::Bottom => Anchor::BOTTOM,
+        }
+    }
+}
+
+/// What an OSD shows: an icon, a level as a progress bar and a short text
+#[derive(Debug, Clone, PartialEq)]
+pub struct OsdContent {
+    pub icon: String,
+    /// From `0` to `1`
+    pub level: f32,
+    pub text: String,
+}
+
+/// A transient overlay showing a level after it changed, like the volume when it's changed with
+/// media keys. Raised by modules with [Message::osd], it fades out after its timeout. A new OSD
+/// replaces the one which is shown.
+#[derive(Debug, Default)]
+pub struct Osd {
+    /// The layer surface and content of the OSD which is shown
+    shown: Option<(Id, OsdContent)>,
+    /// Counts the raised OSDs, so that only the timer of the latest one fades it out
+    serial: u64,
+    /// Drops from `1` to `0` while the OSD fades out
+    opacity: f32,
+}
+
+impl Osd {
+    /// The id of the layer surface of the OSD, if one is shown
+    pub fn id(&self) -> Option<Id> {
+        self.shown.as_ref().map(|(id, _)| *id)
+    }
+
+    pub fn show(
+        &mut self,
+        content: OsdContent,
+        timeout: Duration,
+        position: OsdPosition,
+        output: IcedOutput,
+        config: &Config,
+    ) -> Task<Message> {
+        self.serial += 1;
+        self.opacity = 1.;
+        let serial = self.serial;
+        let open = match &mut self.shown {
+            Some((_, shown)) => {
+                *shown = content;
+                Task::none()
+            }
+            None => {
+                let id = Id::unique();
+                self.shown = Some((id, content));
+                let popup = &config.popup_config;
+                // The icon and the progress bar, and one line of text
+                let height = popup.icon_size.max(popup.font_size * 1.5)
+                    + popup.font_size * 1.5
+                    + popup.spacing
+                    + popup.padding.top
+                    + popup.padding.bottom;
+                let margin = MARGIN.scaled(config.scale_factor);
+                get_layer_surface(SctkLayerSurfaceSettings {
+                    layer: Layer::Overlay,
+                    anchor: position.anchor(),
+                    exclusive_zone: -1,
+                    size: Some((Some(popup.width as u32), Some(height.ceil() as u32))),
+                    namespace: format!("{}-osd", config.namespace),
+                    output,
+                    margin: IcedMargin {
+                        top: margin,
+                        right: 0,
+                        bottom: margin,
+                        left: 0,
+                    },
+                    id,
+                    ..Default::default()
+                })
+            }
+        };
+        let timer = Task::future(async move {
+            sleep(timeout).await;
+            Message::FadeOsd(serial)
+        });
+        Task::batch([open, timer])
+    }
+
+    /// Fades the OSD out step by step and closes it at the end, unless a newer one was raised
+    /// after the one with this serial
+    pub fn fade(&mut self, serial: u64) -> Task<Message> {
+        if serial != self.serial {
+            return Task::none();
+        }
+        self.opacity -= FADE_STEP;
+        if self.opacity > 0. {
+            return Task::future(async move {
+                sleep(FADE_INTERVAL).await;
+                Message::FadeOsd(serial)
+            });
+        }
+        match self.shown.take() {
+            Some((id, _)) => destroy_layer_surface(id),
+            None => Task::none(),
+        }
+    }
+
+    /// Drawn like a popup, see `[popup_style]`
+    pub fn view<'a>(&'a self, config: &'a PopupConfig) -> Element<'a, Message> {
+        let Some((_, content)) = &self.shown else {
+            return "".into();
+        };
+        let opacity = self.opacity.clamp(0., 1.);
+        let icon_color = config.icon_color.scale_alpha(opacity);
+        let text_color = config.text_color.scale_alpha(opacity);
+        let level = progress_bar(0. ..=1., content.level.clamp(0., 1.))
+            .height(config.font_size / 2.)
+            .style(move |_| progress_bar::Style {
+                background: icon_color.scale_alpha(0.3).into(),
+                bar: icon_color.into(),
+                border: Border::default().rounded(config.font_size / 4.),
+            });
+        container(
+            column![
+                row![
+                    text(&content.icon)
+                        .size(config.icon_size)
+                        .color(icon_color)
+                        .font(config.icon_font),
+                    level,
+                ]
+                .spacing(config.spacing)
+                .align_y(Alignment::Center),
+                text(&content.text)
+                    .size(config.font_size)
+                    .color(text_color)
+                    .width(Length::Fill)
+                    .center(),
+            ]
+            .spacing(config.spacing),
+        )
+        .padding(config.padding)
+        .center(Length::Fill)
+        .style(move |_| container::Style {
+            background: Some(config.background.scale_alpha(opacity)),
+            border: Border {
+                color: config.border.color.scale_alpha(opacity),
+                ..config.border
+            },
+            ..Default::default()
+        })
+        .into()
+    }
+
+    /// Like a toast, the window stays transparent since [Osd::view] draws the background
+    pub fn theme(config: &PopupConfig) -> Theme {
+        Toast::theme(config)
+    }
+}
@@ -0,0 +1,173 @@
+use iced::{
+    platform_specific::shell::commands::layer_surface::{
+        destroy_layer_surface, get_layer_surface, Anchor, KeyboardInteractivity, Layer,
+    },
+    runtime::platform_specific::wayland::layer_surface::{
+        IcedMargin, IcedOutput, SctkLayerSurfaceSettings,
+    },
+    widget::{button, container, text},
+    window::Id,
+    Alignment, Element, Length, Rectangle, Task,
+};
+
+use crate::{
+    config::{anchor::BarAnchor, popup_config::PopupConfig, Config},
+    Message,
+};
+
+/// Popups which stay open until they're unpinned, instead of closing as soon as something else
+/// is clicked. They are layer surfaces of their own rather than popups of the bar, so that they
+/// stay where they are while the bar is hidden.
+#[derive(Debug, Default)]
+pub struct Pinned {
+    /// The module instance and layer surface of each pinned popup, the oldest first
+    popups: Vec<(String, Id)>,
+}
+
+impl Pinned {
+    pub fn is_empty(&self) -> bool {
+        self.popups.is_empty()
+    }
+
+    /// The module instance of the pinned popup with this layer surface
+    pub fn instance(&self, id: Id) -> Option<&String> {
+        self.popups
+            .iter()
+            .find(|(_, p_id)| *p_id == id)
+            .map(|(name, _)| name)
+    }
+
+    pub fn contains(&self, instance: &str) -> bool {
+        self.popups.iter().any(|(name, _)| name == instance)
+    }
+
+    /// Opens the popup of `instance` at `bounds`, which are relative to the bar of the given
+    /// size. The oldest pinned popups are closed if there are more than `max_pinned`.
+    pub fn pin(
+        &mut self,
+        instance: String,
+        bounds: Rectangle<i32>,
+        bar_size: (u32, u32),
+        output_size: (u32, u32),
+        output: IcedOutput,
+        config: &Config,
+    ) -> Task<Message> {
+        let evicted = self.sync(|_| true, config.popup_config.max_pinned.saturating_sub(1));
+        let id = Id::unique();
+        self.popups.push((instance, id));
+        let (anchor, margin) = placement(bounds, bar_size, output_size, config);
+        evicted.chain(get_layer_surface(SctkLayerSurfaceSettings {
+            layer: Layer::Top,
+            // Focused once it's clicked, so that Escape closes it
+            keyboard_interactivity: KeyboardInteractivity::OnDemand,
+            anchor,
+            // Placed relative to the edges of the output instead of next to the exclusive zone
+            // of the bar, which changes while the bar auto-hides
+            exclusive_zone: -1,
+            size: Some((Some(bounds.width as u32), Some(bounds.height as u32))),
+            namespace: format!("{}-popup", config.namespace),
+            output,
+            margin,
+            id,
+            ..Default::default()
+        }))
+    }
+
+    pub fn unpin(&mut self, instance: &str) -> Task<Message> {
+        self.close(|name, _| name == instance)
+    }
+
+    /// Closes the pinned popups of module instances which don't exist anymore, and the oldest
+    /// ones if there are more than `max_pinned`
+    pub fn sync(&mut self, exists: impl Fn(&str) -> bool, max_pinned: usize) -> Task<Message> {
+        let excess = self.popups.len().saturating_sub(max_pinned);
+        let mut index = 0;
+        self.close(|name, _| {
+            index += 1;
+            index <= excess || !exists(name)
+        })
+    }
+
+    fn close(&mut self, mut f: impl FnMut(&str, Id) -> bool) -> Task<Message> {
+        let (closed, kept): (Vec<_>, Vec<_>) = std::mem::take(&mut self.popups)
+            .into_iter()
+            .partition(|(name, id)| f(name, *id));
+        self.popups = kept;
+        Task::batch(
+            closed
+                .into_iter()
+                .map(|(_, id)| destroy_layer_surface(id))
+                .collect::<Vec<_>>(),
+        )
+    }
+}
+
+/// How a layer surface has to be anchored to cover `bounds`, which are relative to the bar
+fn placement(
+    bounds: Rectangle<i32>,
+    (bar_width, bar_height): (u32, u32),
+    (output_width, output_height): (u32, u32),
+    config: &Config,
+) -> (Anchor, IcedMargin) {
+    let bar_margin = config.module_config.global.margin;
+    let (bar_width, bar_height) = (bar_width as i32, bar_height as i32);
+    // The bar is only anchored to one edge, so it's centered along that edge
+    let left = (output_width as i32 - bar_width) / 2 + bounds.x;
+    let top = (output_height as i32 - bar_height) / 2 + bounds.y;
+    let margin = |top, right, bottom, left| IcedMargin {
+        top,
+        right,
+        bottom,
+        left,
+    };
+    match config.anchor {
+        BarAnchor::Top => (
+            Anchor::TOP | Anchor::LEFT,
+            margin(bar_margin.top + bounds.y, 0, 0, left),
+        ),
+        BarAnchor::Bottom => (
+            Anchor::BOTTOM | Anchor::LEFT,
+            margin(
+                0,
+                0,
+                bar_margin.bottom + bar_height - bounds.y - bounds.height,
+                left,
+            ),
+        ),
+        BarAnchor::Left => (
+            Anchor::LEFT | Anchor::TOP,
+            margin(top, 0, 0, bar_margin.left + bounds.x),
+        ),
+        BarAnchor::Right => (
+            Anchor::RIGHT | Anchor::TOP,
+            margin(
+                top,
+                bar_margin.right + bar_width - bounds.x - bounds.width,
+                0,
+                0,
+            ),
+        ),
+    }
+}
+
+/// The button in the corner of a popup which pins or unpins it, see [Message::TogglePin]
+pub fn pin_button<'a>(pinned: bool, config: &PopupConfig) -> Element<'a, Message> {
+    let icon = match pinned {
+        true => "󰐃",
+        false => "󰤱",
+    };
+    container(
+        button(
+            text(config.icon(icon))
+                .font(config.icon_font)
+                .size(config.font_size)
+                .color(config.icon_color),
+        )
+        .padding(0)
+        .style(|_, _| button::Style::default())
+        .on_press(Message::TogglePin),
+    )
+    .width(Length::Fill)
+    .align_x(Alignment::End)
+    .into()
+}
@@ -0,0 +1,82 @@
+use std::{
+    collections::HashMap,
+    ops::RangeInclusive,
+    sync::{LazyLock, Mutex, MutexGuard},
+};
+
+use iced::{
+    event::{
+        self,
+        wayland::{self, PopupEvent},
+        PlatformSpecific,
+    },
+    keyboard::{self, key::Named, Key},
+    mouse,
+    widget::{slider as iced_slider, Slider},
+    Event, Subscription,
+};
+
+use crate::{modules::Action, Message};
+
+/// The values of the sliders which are being dragged, by id
+static DRAGGED: LazyLock<Mutex<HashMap<String, f32>>> = LazyLock::new(Default::default);
+
+fn dragged() -> MutexGuard<'static, HashMap<String, f32>> {
+    DRAGGED.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// A slider for the popup of a module (like the volume or the brightness).
+///
+/// While it's dragged, the slider shows the new value without touching the module, the message of
+/// the action returned by `action` is only sent once it's released, so that dragging doesn't run a
+/// command for every step. `id` has to be unique among the sliders shown at the same time.
+pub fn slider<'a, A: Action>(
+    id: impl Into<String>,
+    range: RangeInclusive<f32>,
+    value: f32,
+    action: impl FnOnce(f32) -> A,
+) -> Slider<'a, f32, Message> {
+    let id = id.into();
+    let dragged_value = dragged().get(&id).copied();
+    let released = dragged_value.map(|value| {
+        let id = id.clone();
+        Message::Batch(vec![
+            Message::update(move |_| {
+                dragged().remove(&id);
+            }),
+            action(value).as_message(),
+        ])
+    });
+    let slider = iced_slider(range, dragged_value.unwrap_or(value), move |value| {
+        let id = id.clone();
+        Message::update(move |_| {
+            dragged().insert(id, value);
+        })
+    })
+    .step(1.);
+    match released {
+        Some(msg) => slider.on_release(msg),
+        None => slider,
+    }
+}
+
+/// Closes popups: Escape closes the focused popup, pinned or not. A click on the bar which isn't
+/// handled by a module closes the open popup, just like a click anywhere else, after which the
+/// compositor reports the popup as done.
+pub fn dismiss_subscription() -> Subscription<Message> {
+    event::listen_with(|event, status, id| match event {
+        Event::Keyboard(keyboard::Event::KeyPressed {
+            key: Key::Named(Named::Escape),
+            ..
+        }) => Some(Message::EscapePressed(id)),
+        Event::Mouse(mouse::Event::ButtonPressed(_)) if status == event::Status::Ignored => {
+            Some(Message::ClickedOutside(id))
+        }
+        Event::PlatformSpecific(PlatformSpecific::Wayland(wayland::Event::Popup(
+            PopupEvent::Done,
+            _,
+            id,
+        ))) => Some(Message::PopupDone(id)),
+        _ => None,
+    })
+}
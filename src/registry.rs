@@ -6,9 +6,9 @@ use std::{
 
 use crate::{
     config::{Config, EnabledModules},
+    helpers::state::ModuleState,
     listeners::Listener,
     modules::Module,
-    OptionExt,
 };
 
 pub trait Builder: Any {
@@ -19,10 +19,16 @@ pub trait Builder: Any {
 #[allow(clippy::type_complexity)]
 #[derive(Default, Debug)]
 pub struct Registry {
-    modules: HashMap<TypeId, Box<dyn Module>>,
+    /// Module instances by name, e.g. `disk_usage` or `disk_usage.home`
+    modules: HashMap<String, Box<dyn Module>>,
     listeners: HashMap<TypeId, Box<dyn Listener>>,
     module_names: HashMap<String, TypeId>,
+    /// The name and a constructor of every registered module type
+    module_types: HashMap<TypeId, (String, fn() -> Box<dyn Module>)>,
     resolvers: HashMap<String, fn(Option<&Config>) -> Option<TypeId>>,
+    hidden_groups: HashSet<String>,
+    /// The instance a `Message::Instance` is currently handled for
+    target: Option<String>,
 }
 
 #[allow(dead_code)]
@@ -33,8 +39,13 @@ impl Registry {
     {
         let output = T::build();
         let type_id = TypeId::of::<T>();
-        self.module_names.insert(output.name(), type_id);
-        self.modules.insert(type_id, Box::new(output));
+        let name = output.name();
+        self.module_names.insert(name.clone(), type_id);
+        self.module_types.insert(
+            type_id,
+            (name.clone(), || -> Box<dyn Module> { Box::new(T::build()) }),
+        );
+        self.modules.insert(name, Box::new(output));
     }
 
     pub fn register_listener<T: Builder>(&mut self)
@@ -45,9 +56,28 @@ impl Registry {
         self.listeners.insert(TypeId::of::<T>(), Box::new(output));
     }
 
+    /// The instance of the given module type messages currently refer to: the targeted one if
+    /// it's of this type, otherwise the default instance.
+    pub fn current_instance(&self, id: TypeId) -> Option<&String> {
+        self.target
+            .as_ref()
+            .filter(|target| self.type_of(target) == Some(id))
+            .or_else(|| self.module_types.get(&id).map(|(name, _)| name))
+    }
+
+    /// The instance the message which is handled right now refers to, if any
+    pub fn target(&self) -> Option<&String> {
+        self.target.as_ref()
+    }
+
+    /// Set the instance `get_module` and `get_module_mut` refer to, returns the previous one
+    pub fn set_target(&mut self, target: Option<String>) -> Option<String> {
+        std::mem::replace(&mut self.target, target)
+    }
+
     pub fn try_get_module<T: Module>(&self) -> Option<&T> {
-        let id = &TypeId::of::<T>();
-        self.modules.get(id).and_then(|t| t.downcast_ref::<T>())
+        let name = self.current_instance(TypeId::of::<T>())?;
+        self.modules.get(name).and_then(|t| t.downcast_ref::<T>())
     }
 
     pub fn try_get_listener<T: Listener>(&self) -> Option<&T> {
@@ -56,8 +86,10 @@ impl Registry {
     }
 
     pub fn try_get_module_mut<T: Module>(&mut self) -> Option<&mut T> {
-        let id = &TypeId::of::<T>();
-        self.modules.get_mut(id).and_then(|t| t.downcast_mut::<T>())
+        let name = self.current_instance(TypeId::of::<T>())?.clone();
+        self.modules
+            .get_mut(&name)
+            .and_then(|t| t.downcast_mut::<T>())
     }
 
     pub fn try_get_listener_mut<T: Listener>(&mut self) -> Option<&mut T> {
@@ -67,8 +99,19 @@ impl Registry {
             .and_then(|t| t.downcast_mut::<T>())
     }
 
-    pub fn get_module_by_id(&self, id: TypeId) -> &dyn Module {
-        self.modules.get(&id).unwrap().as_ref()
+    /// All instances of the given module type
+    pub fn instances_mut<T: Module>(&mut self) -> impl Iterator<Item = &mut T> {
+        self.modules
+            .values_mut()
+            .filter_map(|m| m.downcast_mut::<T>())
+    }
+
+    pub fn get_instance(&self, name: &str) -> Option<&dyn Module> {
+        self.modules.get(name).map(|m| m.as_ref())
+    }
+
+    pub fn get_instance_mut(&mut self, name: &str) -> Option<&mut dyn Module> {
+        self.modules.get_mut(name).map(|m| m.as_mut())
     }
 
     pub fn get_module<T: Module>(&self) -> &T {
@@ -87,20 +130,99 @@ impl Registry {
         self.try_get_listener_mut().unwrap()
     }
 
+    /// The name of the default instance of the given module type
+    pub fn module_name(&self, id: TypeId) -> Option<&String> {
+        self.module_types.get(&id).map(|(name, _)| name)
+    }
+
+    /// Split an instance name like `disk_usage.home` into the module and the instance name.
+    /// Module names may contain dots themselves (e.g. `hyprland.window`), so the longest
+    /// matching module name wins.
+    fn split_instance<'a>(&self, name: &'a str) -> Option<(&'a str, &'a str)> {
+        name.char_indices()
+            .rev()
+            .filter(|(_, c)| *c == '.')
+            .map(|(i, _)| (&name[..i], &name[i + 1..]))
+            .find(|(module, instance)| {
+                !instance.is_empty() && self.module_names.contains_key(*module)
+            })
+    }
+
+    /// The type of the module or module instance with this name, not taking resolvers into account
+    pub fn type_of(&self, name: &str) -> Option<TypeId> {
+        self.module_names.get(name).copied().or_else(|| {
+            self.split_instance(name)
+                .and_then(|(module, _)| self.module_names.get(module).copied())
+        })
+    }
+
+    /// The name of the instance enabled as `name` in the `[modules]` section, resolvers included
+    pub fn resolve_instance(&self, name: &str, config: &Config) -> Option<String> {
+        match self.type_of(name) {
+            Some(_) => Some(name.to_string()),
+            None => self
+                .resolvers
+                .get(name)
+                .and_then(|f| f(Some(config)))
+                .and_then(|id| self.module_name(id).cloned()),
+        }
+    }
+
+    /// Create the enabled module instances which don't exist yet and drop the ones which aren't
+    /// enabled anymore. Default instances always exist.
+    pub fn sync_instances<'a, I>(&mut self, enabled: I)
+    where
+        I: Iterator<Item = &'a String>,
+    {
+        let enabled: HashSet<&String> = enabled.collect();
+        self.modules
+            .retain(|name, _| self.module_names.contains_key(name) || enabled.contains(name));
+        for name in enabled {
+            if self.modules.contains_key(name) {
+                continue;
+            }
+            let Some(build) = self
+                .type_of(name)
+                .and_then(|id| self.module_types.get(&id))
+                .map(|(_, build)| *build)
+            else {
+                continue;
+            };
+            self.modules.insert(name.clone(), build());
+        }
+    }
+
+    /// The runtime state of every module instance which has some
+    pub fn save_state(&self) -> ModuleState {
+        self.modules
+            .iter()
+            .map(|(name, m)| (name.clone(), m.save_state()))
+            .filter(|(_, state)| !state.is_null())
+            .collect()
+    }
+
+    /// Pass the saved state to the module instances which (still) exist
+    pub fn load_state(&mut self, state: &ModuleState) {
+        for (name, state) in state {
+            if let Some(m) = self.modules.get_mut(name) {
+                m.load_state(state.clone());
+            }
+        }
+    }
+
+    /// The enabled module instances and their names
     pub fn get_modules<'a, I>(
         &'a self,
         enabled: I,
         config: &'a Config,
-    ) -> impl Iterator<Item = &'a Box<dyn Module>>
+    ) -> impl Iterator<Item = (String, &'a dyn Module)>
     where
         I: Iterator<Item = &'a String>,
     {
-        enabled.filter_map(|id| {
-            self.module_names
-                .get(id)
-                .copied()
-                .or_else(|| self.resolvers.get(id).and_then(|f| f(Some(config))))
-                .and_then(|id| self.modules.get(&id))
+        enabled.filter_map(|name| {
+            let name = self.resolve_instance(name, config)?;
+            let module = self.modules.get(&name)?.as_ref();
+            Some((name, module))
         })
     }
 
@@ -108,64 +230,111 @@ impl Registry {
         &'a mut self,
         enabled: I,
         config: &Config,
-    ) -> impl Iterator<Item = &'a mut Box<dyn Module>>
+    ) -> impl Iterator<Item = (&'a String, &'a mut Box<dyn Module>)>
     where
         I: Iterator<Item = &'a String>,
     {
-        let resolver_types = self
+        let resolved = self
             .resolvers
             .values()
             .filter_map(|r| r(Some(config)))
-            .collect::<Vec<TypeId>>();
-        let type_ids = self
-            .module_names
-            .iter()
-            .collect::<HashMap<&String, &TypeId>>();
+            .filter_map(|id| self.module_name(id).cloned())
+            .collect::<HashSet<String>>();
         let enabled: HashSet<&String> = enabled.collect();
-        self.modules.values_mut().filter(move |m| {
-            let name = m.name();
-            enabled.contains(&name)
-                || type_ids
-                    .get(&name)
-                    .is_some_and(|ty| resolver_types.contains(*ty))
-        })
+        self.modules
+            .iter_mut()
+            .filter(move |(name, _)| enabled.contains(name) || resolved.contains(*name))
     }
 
+    /// The listeners which should run: the ones enabled by their own config option (see
+    /// `Config::enabled_listeners`) and the ones required by at least one module instance
     pub fn get_listeners<'a>(
         &'a self,
-        enabled: &'a HashSet<TypeId>,
+        config: &'a Config,
     ) -> impl Iterator<Item = &'a Box<dyn Listener>> {
-        enabled
+        let refs = self.listener_refs(config);
+        self.listeners
             .iter()
-            .map(|id| self.listeners.get(id).expect("Listener was not registered"))
+            .filter(move |(id, _)| {
+                config.enabled_listeners.contains(id) || refs.get(id).is_some_and(|n| *n > 0)
+            })
+            .map(|(_, l)| l)
     }
 
-    pub fn enabled_listeners<'a>(
-        &'a self,
-        modules: &'a EnabledModules,
-        config: &'a Option<&Config>,
-    ) -> impl Iterator<Item = TypeId> + 'a {
+    /// How many enabled module instances, which aren't part of a hidden group, require each
+    /// listener
+    pub fn listener_refs(&self, config: &Config) -> HashMap<TypeId, usize> {
+        let mut refs = HashMap::new();
+        let enabled = config
+            .enabled_modules
+            .get_all()
+            .filter(|m| !self.is_hidden(m, config));
+        for (_, module) in self.get_modules(enabled, config) {
+            for id in module.requires() {
+                *refs.entry(id).or_default() += 1;
+            }
+        }
+        refs
+    }
+
+    /// Warn about enabled modules which aren't registered
+    pub fn check_modules(&self, modules: &EnabledModules) {
         modules
             .get_all()
-            .filter_map(|m| {
-                self.module_names
-                    .get(m)
-                    .copied()
-                    .or_else(|| self.resolvers.get(m).and_then(|f| f(*config)))
-                    .map_none(|| {
-                        if !m.is_empty() {
-                            eprintln!("No Module named {m} is registered")
-                        }
-                    })
-                    .and_then(|m_id| self.modules.get(&m_id).map(|m| m.requires()))
-            })
-            .flat_map(|required| required.into_iter())
+            .filter(|m| !m.is_empty())
+            .filter(|m| !self.is_known(m))
+            .for_each(|m| eprintln!("No Module named {m} is registered"));
+    }
+
+    /// Whether a module can be enabled with this name: a registered module, an instance of one
+    /// (like `disk_usage.home`) or a name which is resolved to a module (like `workspaces`)
+    pub fn is_known(&self, name: &str) -> bool {
+        self.type_of(name).is_some() || self.resolvers.contains_key(name)
+    }
+
+    /// The names of the registered modules and resolvers, sorted
+    pub fn known_names(&self) -> Vec<&String> {
+        let mut names = self
+            .module_names
+            .keys()
+            .chain(self.resolvers.keys())
+            .collect::<Vec<_>>();
+        names.sort();
+        names
+    }
+
+    /// The default instance of every registered module type, by module name
+    pub fn registered_modules(&self) -> impl Iterator<Item = (&String, &dyn Module)> {
+        self.module_types
+            .values()
+            .filter_map(|(name, _)| Some((name, self.modules.get(name)?.as_ref())))
     }
 
     pub fn all_listeners(&self) -> impl Iterator<Item = (&TypeId, &Box<dyn Listener>)> {
         self.listeners.iter()
     }
 
+    /// Hide or show a group of modules, returns whether the group is hidden now
+    pub fn toggle_group(&mut self, group: &str) -> bool {
+        if !self.hidden_groups.remove(group) {
+            self.hidden_groups.insert(group.to_string());
+            return true;
+        }
+        false
+    }
+
+    /// Whether the module (as named in the `[modules]` section) is part of a hidden group.
+    /// Every module also forms an implicit group of its own name.
+    pub fn is_hidden(&self, module: &str, config: &Config) -> bool {
+        self.hidden_groups.contains(module)
+            || self.hidden_groups.iter().any(|group| {
+                config
+                    .groups
+                    .get(group)
+                    .is_some_and(|modules| modules.contains(module))
+            })
+    }
+
     pub fn add_resolver<S: ToString>(&mut self, name: S, f: fn(Option<&Config>) -> Option<TypeId>) {
         self.resolvers.insert(name.to_string(), f);
     }
@@ -0,0 +1,200 @@
+use std::{borrow::Cow, fs::File, io::BufWriter, path::PathBuf};
+
+use chrono::{DateTime, Local};
+use iced::{
+    advanced::{
+        graphics::{text::font_system, Viewport},
+        renderer,
+    },
+    mouse::Cursor,
+    runtime::user_interface::{Cache, UserInterface},
+    Color, Element, Font, Pixels, Size,
+};
+
+use crate::{
+    compose,
+    config::{get_config_dir, load_ini, read_config},
+    helpers::{
+        clock,
+        source::{Snapshot, Source},
+        status::ModuleStatus,
+        templates::Templates,
+    },
+    listeners::register_listeners,
+    modules::register_modules,
+    registry::Registry,
+    resolvers::register_resolvers,
+    Message,
+};
+
+/// The options of `bar-rs render`
+#[derive(Debug)]
+struct Args {
+    config: PathBuf,
+    output: PathBuf,
+    width: Option<u32>,
+    height: Option<u32>,
+    snapshot: Option<Snapshot>,
+    time: Option<DateTime<Local>>,
+}
+
+impl Args {
+    fn parse(mut args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut parsed = Args {
+            config: get_config_dir(),
+            output: PathBuf::from("bar.png"),
+            width: None,
+            height: None,
+            snapshot: None,
+            time: None,
+        };
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().ok_or(format!("{arg} needs a value"));
+            let size = |value: String| {
+                value
+                    .parse::<u32>()
+                    .map_err(|e| format!("{arg}: invalid size `{value}`: {e}"))
+            };
+            match arg.as_str() {
+                "--config" => parsed.config = value()?.into(),
+                "--output" => parsed.output = value()?.into(),
+                "--width" => parsed.width = Some(size(value()?)?),
+                "--height" => parsed.height = Some(size(value()?)?),
+                "--snapshot" => {
+                    parsed.snapshot = Some(Snapshot {
+                        root: value()?.into(),
+                    })
+                }
+                "--time" => {
+                    let time = value()?;
+                    parsed.time = Some(
+                        DateTime::parse_from_rfc3339(&time)
+                            .map_err(|e| format!("--time: invalid time `{time}`: {e}"))?
+                            .with_timezone(&Local),
+                    )
+                }
+                // Handled in `main`
+                "--verbose" | "--timings" => {}
+                _ => return Err(format!("unknown option `{arg}`")),
+            }
+        }
+        Ok(parsed)
+    }
+}
+
+/// `bar-rs render`: Draws the bar like it would be shown with the given config into a PNG,
+/// without a compositor. With `--snapshot <dir>`, modules read their data from a saved system
+/// instead of the one bar-rs runs on, modules which can't do that show what they show without
+/// a working backend. Together with `--time`, which stops the clock, the image only depends on
+/// the config and the snapshot. Returns the exit code.
+pub fn run() -> i32 {
+    let args = match Args::parse(std::env::args().skip(2)) {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("{e}");
+            eprintln!(
+                "Usage: bar-rs render [--config <path>] [--output <png>] [--width <px>] \
+                [--height <px>] [--snapshot <dir>] [--time <rfc3339>]"
+            );
+            return 2;
+        }
+    };
+    if let Some(time) = args.time {
+        clock::freeze(time);
+    }
+
+    let mut registry = Registry::default();
+    register_modules(&mut registry);
+    register_listeners(&mut registry);
+    register_resolvers(&mut registry);
+
+    let mut templates = Templates::default();
+    if let Err(e) = load_ini(&args.config) {
+        eprintln!("Failed to read config: {e}");
+        return 1;
+    }
+    let config = read_config(&args.config, &mut registry, &mut templates, 1.);
+
+    for name in config.enabled_modules.get_all() {
+        let Some(module) = registry
+            .resolve_instance(name, &config)
+            .and_then(|instance| registry.get_instance_mut(&instance))
+        else {
+            continue;
+        };
+        let collected = match &args.snapshot {
+            Some(snapshot) => module.collect_data_from(snapshot as &dyn Source),
+            None => module.collect_data(),
+        };
+        // The module is drawn anyway, like the bar shows it while its backend doesn't work
+        if let Err(e) = collected {
+            module.set_status(ModuleStatus::Unavailable(e));
+        }
+    }
+
+    let (width, height) = compose::size(
+        &config,
+        (args.width.unwrap_or(1920), args.height.unwrap_or(1080)),
+    );
+    let pixels = draw(
+        compose::bar(&registry, &config, &templates, false),
+        width,
+        height,
+    );
+    match write_png(&args.output, width, height, &pixels) {
+        Ok(()) => {
+            println!(
+                "Rendered {width}x{height} to {}",
+                args.output.to_string_lossy()
+            );
+            0
+        }
+        Err(e) => {
+            eprintln!("Failed to write {}: {e}", args.output.to_string_lossy());
+            1
+        }
+    }
+}
+
+/// Lays out and draws the bar with the software renderer, returns the RGBA pixels
+fn draw(bar: Element<'_, Message>, width: u32, height: u32) -> Vec<u8> {
+    // The font of the bar is bundled, so its text looks the same on every machine
+    font_system()
+        .write()
+        .expect("the font system is poisoned")
+        .load_font(Cow::Borrowed(include_bytes!(
+            "../assets/3270/3270NerdFont-Regular.ttf"
+        )));
+    let mut renderer =
+        iced::Renderer::Secondary(iced_tiny_skia::Renderer::new(Font::DEFAULT, Pixels(16.)));
+    let size = Size::new(width as f32, height as f32);
+    let mut ui = UserInterface::build(bar, size, Cache::default(), &mut renderer);
+    ui.draw(
+        &mut renderer,
+        &compose::theme(),
+        &renderer::Style {
+            text_color: Color::WHITE,
+        },
+        Cursor::Unavailable,
+    );
+    let iced::Renderer::Secondary(renderer) = &mut renderer else {
+        unreachable!("the renderer was created as the software renderer");
+    };
+    iced_tiny_skia::window::compositor::screenshot(
+        renderer,
+        &Viewport::with_physical_size(Size::new(width, height), 1.),
+        Color::TRANSPARENT,
+        &[] as &[&str],
+    )
+}
+
+fn write_png(path: &PathBuf, width: u32, height: u32, pixels: &[u8]) -> Result<(), String> {
+    let file = File::create(path).map_err(|e| e.to_string())?;
+    let mut encoder = png::Encoder::new(BufWriter::new(file), width, height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder
+        .write_header()
+        .and_then(|mut writer| writer.write_image_data(pixels))
+        .map_err(|e| e.to_string())
+}
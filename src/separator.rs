@@ -0,0 +1,46 @@
+use iced::{
+    widget::{container, text},
+    Element,
+};
+
+use crate::{
+    config::{
+        anchor::BarAnchor,
+        module_config::{GlobalModuleConfig, LocalModuleConfig},
+    },
+    fill::FillExt,
+    Message,
+};
+
+/// Puts the `separator` of `[style]` between adjacent modules of a section, each given with
+/// whether it's a spacer. Spacers already separate their neighbors, so no separator is drawn
+/// next to them.
+pub fn with_separators<'a>(
+    modules: Vec<(bool, Element<'a, Message>)>,
+    global: &'a GlobalModuleConfig,
+    local: &LocalModuleConfig,
+    anchor: &BarAnchor,
+) -> Vec<Element<'a, Message>> {
+    let Some(separator) = global.separator.as_ref() else {
+        return modules.into_iter().map(|(_, module)| module).collect();
+    };
+    let mut elements = Vec::with_capacity(modules.len() * 2);
+    let mut previous_spacer = true;
+    for (spacer, module) in modules {
+        if !previous_spacer && !spacer {
+            elements.push(
+                container(
+                    text(separator.as_str())
+                        .fill(anchor)
+                        .size(global.separator_size.unwrap_or(local.font_size))
+                        .color(global.separator_color)
+                        .font(local.icon_font),
+                )
+                .into(),
+            );
+        }
+        previous_spacer = spacer;
+        elements.push(module);
+    }
+    elements
+}
@@ -0,0 +1,127 @@
+use std::time::Duration;
+
+use iced::{
+    platform_specific::shell::commands::layer_surface::{
+        destroy_layer_surface, get_layer_surface, Layer,
+    },
+    runtime::platform_specific::wayland::layer_surface::{
+        IcedMargin, IcedOutput, SctkLayerSurfaceSettings,
+    },
+    theme::Palette,
+    widget::{container, mouse_area, text},
+    window::Id,
+    Color, Element, Length, Task, Theme,
+};
+use tokio::time::sleep;
+
+use crate::{
+    config::{popup_config::PopupConfig, scale::Scale, Config},
+    Message,
+};
+
+/// The space between the bar and a toast
+const GAP: i32 = 8;
+
+/// A short text shown next to the bar for a while, raised by modules with [Message::toast].
+/// A new toast replaces the one which is shown, clicking a toast dismisses it.
+#[derive(Debug, Default)]
+pub struct Toast {
+    /// The layer surface and text of the toast which is shown
+    shown: Option<(Id, String)>,
+    /// Counts the raised toasts, so that only the timer of the latest one dismisses it
+    serial: u64,
+}
+
+impl Toast {
+    /// The id of the layer surface of the toast, if one is shown
+    pub fn id(&self) -> Option<Id> {
+        self.shown.as_ref().map(|(id, _)| *id)
+    }
+
+    pub fn show(
+        &mut self,
+        text: String,
+        duration: Duration,
+        output: IcedOutput,
+        config: &Config,
+    ) -> Task<Message> {
+        self.serial += 1;
+        let serial = self.serial;
+        let open = match &mut self.shown {
+            Some((_, shown)) => {
+                *shown = text;
+                Task::none()
+            }
+            None => {
+                let id = Id::unique();
+                self.shown = Some((id, text));
+                let popup = &config.popup_config;
+                // One line of text
+                let height = popup.font_size * 1.5 + popup.padding.top + popup.padding.bottom;
+                let gap = GAP.scaled(config.scale_factor);
+                get_layer_surface(SctkLayerSurfaceSettings {
+                    layer: Layer::Overlay,
+                    anchor: (&config.anchor).into(),
+                    exclusive_zone: 0,
+                    size: Some((Some(popup.width as u32), Some(height.ceil() as u32))),
+                    namespace: format!("{}-toast", config.namespace),
+                    output,
+                    // Layer surfaces without an exclusive zone are placed next to the bar
+                    margin: IcedMargin {
+                        top: gap,
+                        right: gap,
+                        bottom: gap,
+                        left: gap,
+                    },
+                    id,
+                    ..Default::default()
+                })
+            }
+        };
+        let timer = Task::future(async move {
+            sleep(duration).await;
+            Message::DismissToast(serial)
+        });
+        Task::batch([open, timer])
+    }
+
+    /// Closes the toast, unless a newer one was raised after the one with this serial
+    pub fn dismiss(&mut self, serial: u64) -> Task<Message> {
+        match self.shown.take_if(|_| serial == self.serial) {
+            Some((id, _)) => destroy_layer_surface(id),
+            None => Task::none(),
+        }
+    }
+
+    /// Drawn like a popup, see `[popup_style]`
+    pub fn view<'a>(&'a self, config: &'a PopupConfig) -> Element<'a, Message> {
+        let Some((_, content)) = &self.shown else {
+            return "".into();
+        };
+        let toast = container(text(content).size(config.font_size))
+            .padding(config.padding)
+            .center(Length::Fill)
+            .style(move |_| container::Style {
+                background: Some(config.background),
+                border: config.border,
+                ..Default::default()
+            });
+        mouse_area(toast)
+            .on_press(Message::DismissToast(self.serial))
+            .into()
+    }
+
+    /// The window stays transparent since [Toast::view] draws the background
+    pub fn theme(config: &PopupConfig) -> Theme {
+        Theme::custom(
+            "Toast theme".to_string(),
+            Palette {
+                background: Color::TRANSPARENT,
+                text: config.text_color,
+                primary: config.icon_color,
+                success: config.text_color,
+                danger: config.text_color,
+            },
+        )
+    }
+}